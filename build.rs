@@ -0,0 +1,15 @@
+/*!
+ * build.rs
+ *
+ * Compiles the gRPC service of proto/certified_dp.proto when the `grpc` feature is on, with a vendored protoc so the
+ * build does not need one installed.
+ */
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform"));
+        tonic_build::compile_protos("proto/certified_dp.proto").expect("could not compile proto/certified_dp.proto");
+    }
+}
@@ -0,0 +1,38 @@
+/*!
+ * bit_sigma.rs
+ *
+ * Final check of the bit sigma protocol, on the values the verifier holds once the prover has responded.
+ */
+
+use crate::group::Group;
+use crate::pedersen;
+
+/// Check of the bit sigma protocol that a response fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The challenge is not split as e = e_0 + e_1
+    Challenge,
+    /// The response does not open c_0 + e_0 * b_comm to 0
+    Zero,
+    /// The response does not open c_1 + e_1 * b_comm to 1 + e_1
+    One,
+}
+
+/// Check the response (z_0, z_1, e_0, e_1) to challenge `e`, for the commitment `b_comm` to a bit and the prover's
+/// first message (c_0, c_1).
+pub fn verify<G: Group>(pp: &pedersen::PublicParams<G>, b_comm: G::Point, e: G::Scalar, [c_0, c_1]: [G::Point; 2],
+                        [z_0, z_1]: [G::Scalar; 2], [e_0, e_1]: [G::Scalar; 2]) -> Result<(), Failure> {
+    if e != e_0 + e_1 {
+        return Err(Failure::Challenge);
+    }
+
+    if pedersen::commit_with_r(&G::Scalar::from(0), &z_0, pp) != c_0 + (e_0 * b_comm) {
+        return Err(Failure::Zero);
+    }
+
+    if pedersen::commit_with_r(&(G::Scalar::from(1) + e_1), &z_1, pp) != c_1 + (e_1 * b_comm) {
+        return Err(Failure::One);
+    }
+
+    Ok(())
+}
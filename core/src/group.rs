@@ -0,0 +1,103 @@
+/*!
+ * group.rs
+ *
+ * Prime-order groups the commitment scheme and sigma protocols run over. `Ristretto` is the default everywhere and is
+ * what the protocol messages use; the main crate adds other backends behind cargo features.
+ */
+
+use core::fmt::Debug;
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub};
+use curve25519_dalek::{constants, ristretto::RistrettoPoint, scalar::Scalar};
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
+
+/// A prime-order group written additively, with its scalar field
+pub trait Group: Clone + Copy + Debug + Send + Sync + 'static {
+    type Scalar: Copy + Debug + Default + PartialEq + Send + Sync + From<u64>
+        + Add<Output = Self::Scalar> + Sub<Output = Self::Scalar> + Mul<Output = Self::Scalar> + Neg<Output = Self::Scalar>
+        + MulAssign + Mul<Self::Point, Output = Self::Point> + Zeroize;
+
+    type Point: Copy + Debug + Default + PartialEq + Send + Sync
+        + Add<Output = Self::Point> + Sub<Output = Self::Point> + AddAssign + Mul<Self::Scalar, Output = Self::Point>;
+
+    /// Multiples of a fixed point, precomputed once to multiply it by many scalars
+    #[cfg(feature = "alloc")]
+    type Table: Send + Sync;
+
+    /// Uniformly random scalar
+    fn random_scalar<T: RngCore + CryptoRng>(rng: &mut T) -> Self::Scalar;
+
+    /// Fixed generator of the group
+    fn generator() -> Self::Point;
+
+    /// Point hashed from `message` under the domain-separation string `domain`, whose discrete log to any other point
+    /// nobody knows
+    fn hash_to_point(domain: &[u8], message: &[u8]) -> Self::Point;
+
+    /// a * p + b * q, which backends may compute faster than two separate multiplications
+    fn multiscalar_mul([a, b]: [&Self::Scalar; 2], [p, q]: [Self::Point; 2]) -> Self::Point {
+        (*a * p) + (*b * q)
+    }
+
+    /// Sum of `scalars[i] * points[i]`, in time that may depend on the inputs, so only for public values
+    fn vartime_multiscalar_mul(scalars: &[Self::Scalar], points: &[Self::Point]) -> Self::Point {
+        scalars.iter().zip(points).fold(Self::Point::default(), |sum, (s, p)| sum + (*s * *p))
+    }
+
+    /// Table of the multiples of `p`
+    #[cfg(feature = "alloc")]
+    fn table(p: &Self::Point) -> Self::Table;
+
+    /// s * p, for the `p` that `table` was built from, in constant time
+    #[cfg(feature = "alloc")]
+    fn mul_table(table: &Self::Table, s: &Self::Scalar) -> Self::Point;
+}
+
+/// The Ristretto group over Curve25519
+#[derive(Clone, Copy, Debug)]
+pub struct Ristretto;
+
+impl Group for Ristretto {
+    type Scalar = Scalar;
+    type Point = RistrettoPoint;
+    #[cfg(feature = "alloc")]
+    type Table = curve25519_dalek::ristretto::RistrettoBasepointTable;
+
+    fn random_scalar<T: RngCore + CryptoRng>(rng: &mut T) -> Scalar {
+        Scalar::random(rng)
+    }
+
+    fn generator() -> RistrettoPoint {
+        constants::RISTRETTO_BASEPOINT_POINT
+    }
+
+    /// Elligator map of a SHA-512 hash, as `RistrettoPoint::hash_from_bytes`
+    fn hash_to_point(domain: &[u8], message: &[u8]) -> RistrettoPoint {
+        RistrettoPoint::from_hash(Sha512::new().chain_update(domain).chain_update(message))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn multiscalar_mul(scalars: [&Scalar; 2], points: [RistrettoPoint; 2]) -> RistrettoPoint {
+        use curve25519_dalek::traits::MultiscalarMul;
+        RistrettoPoint::multiscalar_mul(scalars, points)
+    }
+
+    /// Pippenger's algorithm for large inputs
+    #[cfg(feature = "alloc")]
+    fn vartime_multiscalar_mul(scalars: &[Scalar], points: &[RistrettoPoint]) -> RistrettoPoint {
+        use curve25519_dalek::traits::VartimeMultiscalarMul;
+        RistrettoPoint::vartime_multiscalar_mul(scalars, points)
+    }
+
+    /// Constant-time lookup tables of the multiples of `p`, as for the basepoint
+    #[cfg(feature = "alloc")]
+    fn table(p: &RistrettoPoint) -> Self::Table {
+        Self::Table::create(p)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn mul_table(table: &Self::Table, s: &Scalar) -> RistrettoPoint {
+        table * s
+    }
+}
@@ -0,0 +1,18 @@
+/*!
+ * lib.rs
+ *
+ * Verification core for Certified DP: the commitment scheme, the checks of the bit and product sigma protocols, and
+ * the query answer check. It has no dependency on std, so a verifier running on an embedded device or inside an
+ * enclave can validate certificates without the networking and serialization of the main crate, which builds on it.
+ */
+
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod group;
+pub mod pedersen;
+pub mod bit_sigma;
+pub mod product_sigma;
+pub mod query;
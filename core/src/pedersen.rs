@@ -0,0 +1,168 @@
+/*!
+ * pedersen.rs
+ *
+ * Base Pedersen commitment implementation, over any `Group` (Ristretto by default).
+ * Heavily based on the tiny_ped_com library by Alex Ledger (https://github.com/aled1027/tiny_ped_com).
+ *
+ * With the `alloc` feature, the parameters from `setup` carry tables of the multiples of `g` and `h`, built once per
+ * session, so the millions of commitments the prover makes cost two fixed-base multiplications each instead of a
+ * variable-base one. Parameters built with `PublicParams::new` have none until `precompute` builds them.
+ */
+
+#[cfg(feature = "alloc")]
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::fmt;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::group::{Group, Ristretto};
+
+/// Domain-separation string `g` is hashed to the group under
+const GENERATOR_DOMAIN: &[u8] = b"certified-dp pedersen g";
+
+/// Fewest terms worth handing to a thread of their own in `vartime_multiscalar_mul`
+#[cfg(feature = "parallel")]
+const MIN_TERMS_PER_THREAD: usize = 1 << 10;
+
+/// Fewest commitments worth building tables for in `commit_batch_with_r`, when the parameters have none, as the tables
+/// cost about as much as two multiplications by an arbitrary point
+#[cfg(feature = "alloc")]
+const MIN_BATCH_FOR_TABLES: usize = 8;
+
+/// Public parameters, shared from the prover to the verifier
+#[derive(Clone, Debug)]
+pub struct PublicParams<G: Group = Ristretto> {
+    pub g: G::Point,
+    pub h: G::Point,
+    /// Tables of `g` and `h`, if built, shared between clones
+    #[cfg(feature = "alloc")]
+    precomp: Option<Arc<PedersenPrecomp<G>>>,
+}
+
+/// Fixed-base tables of the multiples of `g` and `h`
+#[cfg(feature = "alloc")]
+pub struct PedersenPrecomp<G: Group = Ristretto> {
+    g: G::Table,
+    h: G::Table,
+}
+
+#[cfg(feature = "alloc")]
+impl<G: Group> fmt::Debug for PedersenPrecomp<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PedersenPrecomp").finish_non_exhaustive()
+    }
+}
+
+impl<G: Group> PublicParams<G> {
+    /// Parameters with generators `g` and `h`, without tables, e.g. for a one-off commitment to another generator
+    pub fn new(g: G::Point, h: G::Point) -> Self {
+        PublicParams {
+            g, h,
+            #[cfg(feature = "alloc")]
+            precomp: None,
+        }
+    }
+
+    /// These parameters with the tables of `g` and `h` built, for the many commitments of a session
+    #[cfg(feature = "alloc")]
+    pub fn precompute(mut self) -> Self {
+        self.precomp = Some(Arc::new(PedersenPrecomp { g: G::table(&self.g), h: G::table(&self.h) }));
+        self
+    }
+
+    /// Whether commitments under these parameters use tables of `g` and `h`
+    pub fn is_precomputed(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        return self.precomp.is_some();
+        #[cfg(not(feature = "alloc"))]
+        false
+    }
+
+    /// a * g + b * h, from the tables if built
+    #[inline]
+    fn combine(&self, a: &G::Scalar, b: &G::Scalar) -> G::Point {
+        #[cfg(feature = "alloc")]
+        if let Some(precomp) = &self.precomp {
+            return G::mul_table(&precomp.g, a) + G::mul_table(&precomp.h, b);
+        }
+        G::multiscalar_mul([a, b], [self.g, self.h])
+    }
+}
+
+/// Generate `g` and `h` for use in the commitment scheme, hashing `label` to the group for `g`, so that nobody knows the
+/// discrete log of `g` to `h` whoever picked the label, and build their tables if there is an allocator
+pub fn setup<G: Group>(label: &[u8]) -> PublicParams<G> {
+    let h = G::generator();
+    let g = G::hash_to_point(GENERATOR_DOMAIN, label);
+
+    let params = PublicParams::new(g, h);
+    #[cfg(feature = "alloc")]
+    let params = params.precompute();
+    params
+}
+
+/// Generate a commitment to a value `val` with randomness `r`
+#[inline]
+pub fn commit<G: Group, T: RngCore + CryptoRng>(rng: &mut T, val: &G::Scalar, params: &PublicParams<G>) -> (G::Point, G::Scalar) {
+    let r = G::random_scalar(rng);
+
+    let commitment = params.combine(val, &r);
+    (commitment, r)
+}
+
+/// Generate a commitment to a value `val` with given randomness `r`
+#[inline]
+pub fn commit_with_r<G: Group>(val: &G::Scalar, r: &G::Scalar, params: &PublicParams<G>) -> G::Point {
+    params.combine(val, r)
+}
+
+/// Generate a commitment to each of `values` with fresh randomness, drawing all of the randomness first and then
+/// computing the commitments in one pass over the tables of `g` and `h`. Returns each commitment with its randomness, in
+/// order.
+#[cfg(feature = "alloc")]
+pub fn commit_batch<G: Group, T: RngCore + CryptoRng>(rng: &mut T, values: &[G::Scalar], params: &PublicParams<G>) -> Vec<(G::Point, G::Scalar)> {
+    let openings: Vec<(G::Scalar, G::Scalar)> = values.iter().map(|val| (*val, G::random_scalar(rng))).collect();
+    let commitments = commit_batch_with_r(&openings, params);
+    commitments.into_iter().zip(openings).map(|(commitment, (_, r))| (commitment, r)).collect()
+}
+
+/// Generate a commitment to each (value, randomness) pair of `openings`, in order, over the tables of `g` and `h`, which
+/// are built for the batch if the parameters have none and the batch is large enough
+#[cfg(feature = "alloc")]
+pub fn commit_batch_with_r<G: Group>(openings: &[(G::Scalar, G::Scalar)], params: &PublicParams<G>) -> Vec<G::Point> {
+    let tabled;
+    let params = if !params.is_precomputed() && openings.len() >= MIN_BATCH_FOR_TABLES {
+        tabled = params.clone().precompute();
+        &tabled
+    } else {
+        params
+    };
+    openings.iter().map(|(val, r)| params.combine(val, r)).collect()
+}
+
+/// Verify that `commitment` is a correct commitment to a value `val` using the given `proof`
+#[inline]
+pub fn verify<G: Group>(commitment: &G::Point, val: &G::Scalar, proof: &G::Scalar, params: &PublicParams<G>) -> bool {
+    let lhs = params.combine(val, proof);
+    lhs == *commitment
+}
+
+/// Linear combination `sum(scalars[i] * points[i])` of public commitments, e.g. a query commitment. Uses the backend's
+/// variable-time multiscalar multiplication (Pippenger's algorithm for Ristretto), split across threads with the
+/// `parallel` feature.
+pub fn vartime_multiscalar_mul<G: Group>(scalars: &[G::Scalar], points: &[G::Point]) -> G::Point {
+    debug_assert_eq!(scalars.len(), points.len());
+
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        let chunk = scalars.len().div_ceil(rayon::current_num_threads()).max(MIN_TERMS_PER_THREAD);
+        scalars.par_chunks(chunk).zip(points.par_chunks(chunk))
+            .map(|(s, p)| G::vartime_multiscalar_mul(s, p))
+            .reduce(G::Point::default, |a, b| a + b)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    G::vartime_multiscalar_mul(scalars, points)
+}
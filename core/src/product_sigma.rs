@@ -0,0 +1,20 @@
+/*!
+ * product_sigma.rs
+ *
+ * Final check of the product sigma protocol, on the values the verifier holds once the prover has responded.
+ */
+
+use crate::group::Group;
+use crate::pedersen;
+
+/// Check the response (z_1, ..., z_5) against the commitments c'_i = (alpha, beta, gamma) + e * c_i, where c_1 is the
+/// commitment to the first factor, which serves as the generator for gamma.
+pub fn verify<G: Group>(pp: &pedersen::PublicParams<G>, c_1: G::Point,
+                        [c1_prime, c2_prime, c3_prime]: [G::Point; 3], [z_1, z_2, z_3, z_4, z_5]: [G::Scalar; 5]) -> bool {
+
+    let special_pp = pedersen::PublicParams::<G>::new(c_1, pp.h);
+
+    pedersen::verify(&c1_prime, &z_1, &z_2, pp)
+        && pedersen::verify(&c2_prime, &z_3, &z_4, pp)
+        && pedersen::verify(&c3_prime, &z_3, &z_5, &special_pp)
+}
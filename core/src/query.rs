@@ -0,0 +1,20 @@
+/*!
+ * query.rs
+ *
+ * Query answer check. The verifier computes the commitment to the noisy answer homomorphically from the commitment to
+ * the noise and the aggregated monomial commitments, then checks the prover's opening against it.
+ */
+
+use crate::group::Group;
+use crate::pedersen;
+
+/// Commitment to the answer of a query with the given coefficients of the monomial commitments, plus the noise in
+/// `noise_comm`. The query terms are public, so this is one large variable-time multiscalar multiplication.
+pub fn commitment<G: Group>(noise_comm: G::Point, coefficients: &[G::Scalar], monomial_comms: &[G::Point]) -> G::Point {
+    noise_comm + pedersen::vartime_multiscalar_mul::<G>(coefficients, monomial_comms)
+}
+
+/// Check that `answer` and `proof` open the query commitment
+pub fn verify_answer<G: Group>(pp: &pedersen::PublicParams<G>, query_comm: &G::Point, answer: &G::Scalar, proof: &G::Scalar) -> bool {
+    pedersen::verify(query_comm, answer, proof, pp)
+}
@@ -8,7 +8,7 @@ to stdout.
 
 ----
 
-usage: experiment.py [-h] --db-size DB_SIZE --max-degree MAX_DEGREE [--dimension DIMENSION] --epsilon EPSILON [--delta DELTA] --sparsity SPARSITY [--debug] [--no-logs] [--skip-dishonest] [--num-queries NUM_QUERIES] [--sparsity-experiment]
+usage: experiment.py [-h] --db-size DB_SIZE --max-degree MAX_DEGREE [--dimension DIMENSION] --epsilon EPSILON [--delta DELTA] --sparsity SPARSITY [--debug] [--no-logs] [--skip-dishonest] [--num-queries NUM_QUERIES] [--spot-check SPOT_CHECK] [--audit-rate AUDIT_RATE] [--cut-and-choose CUT_AND_CHOOSE] [--sparsity-experiment]
 
 options:
   -h, --help            show this help message and exit
@@ -25,6 +25,12 @@ options:
   --skip-dishonest      Skip dishonest commitment phase
   --num-queries NUM_QUERIES
                         Number of queries to execute; timing averaged over queries
+  --spot-check SPOT_CHECK
+                        Spot-check the honest commitment phase, auditing this fraction of entries
+  --audit-rate AUDIT_RATE
+                        Verify only this fraction of the dishonest commitment phase proofs
+  --cut-and-choose CUT_AND_CHOOSE
+                        Certify the dishonest commitment phase by cut-and-choose with this many encodings per entry
   --sparsity-experiment
                         Run sparsity evaluation experiment
 '''
@@ -57,6 +63,9 @@ if __name__ == "__main__":
     parser.add_argument('--no-logs', action='store_true', help='Do not print logs', default=False)
     parser.add_argument('--skip-dishonest', action='store_true', help='Skip dishonest commitment phase', default=False)
     parser.add_argument('--num-queries', type=int, help='Number of queries to execute; timing averaged over queries', default=100)
+    parser.add_argument('--spot-check', type=float, help='Spot-check the honest commitment phase, auditing this fraction of entries')
+    parser.add_argument('--audit-rate', type=float, help='Verify only this fraction of the dishonest commitment phase proofs')
+    parser.add_argument('--cut-and-choose', type=int, help='Certify the dishonest commitment phase by cut-and-choose with this many encodings per entry')
     parser.add_argument('--sparsity-experiment', action='store_true', help='Run sparsity evaluation experiment', default=False)
 
     args = parser.parse_args()
@@ -64,6 +73,8 @@ if __name__ == "__main__":
     cargo_command = ["cargo", "run"]
     if not args.debug:
         cargo_command.append("--release")
+    if args.sparsity_experiment:
+        cargo_command.extend(["--features", "experiments"])
 
     flamegraph_command = ["cargo", "flamegraph", "-o=prover.svg"]
     if not args.debug:
@@ -88,6 +99,12 @@ if __name__ == "__main__":
     if args.num_queries:
         prover_command.append("--num-queries")
         prover_command.append(str(args.num_queries))
+    if args.spot_check:
+        prover_command.append("--spot-check")
+        prover_command.append(str(args.spot_check))
+    if args.cut_and_choose:
+        prover_command.append("--cut-and-choose")
+        prover_command.append(str(args.cut_and_choose))
     if args.sparsity_experiment:
         prover_command.append("--sparsity-experiment")
 
@@ -103,6 +120,7 @@ if __name__ == "__main__":
         *cargo_command,
         "--bin", "verifier", "--",
         "--db-size", str(args.db_size),
+        "--max-degree", str(args.max_degree),
         "--epsilon", str(args.epsilon),
         "--sparsity", str(args.sparsity),
         "--prover-address", "127.0.0.1:10020"
@@ -118,6 +136,15 @@ if __name__ == "__main__":
     if args.num_queries:
         verifier_command.append("--num-queries")
         verifier_command.append(str(args.num_queries))
+    if args.spot_check:
+        verifier_command.append("--spot-check")
+        verifier_command.append(str(args.spot_check))
+    if args.audit_rate:
+        verifier_command.append("--audit-rate")
+        verifier_command.append(str(args.audit_rate))
+    if args.cut_and_choose:
+        verifier_command.append("--cut-and-choose")
+        verifier_command.append(str(args.cut_and_choose))
     if args.sparsity_experiment:
         verifier_command.append("--sparsity-experiment")
         
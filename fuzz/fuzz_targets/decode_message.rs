@@ -0,0 +1,69 @@
+//! Decode arbitrary bytes as each protocol message type; malformed input must be rejected without panicking.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use certified_dp::config::DataT;
+use certified_dp::messages::*;
+
+fuzz_target!(|input: (u8, bool, &[u8])| {
+    let (selector, binary, bytes) = input;
+    let format = if binary { WireFormat::Bincode } else { WireFormat::Json };
+    match selector % 54 {
+        0 => { let _ = format.decode::<ReadyMessage>(bytes); },
+        1 => { let _ = format.decode::<SetupMessage>(bytes); },
+        2 => { let _ = format.decode::<BitSigmaCommitmentMessage>(bytes); },
+        3 => { let _ = format.decode::<MonomialCommitmentTreeNode>(bytes); },
+        4 => { let _ = format.decode::<BitSigmaChallengeMessage>(bytes); },
+        5 => { let _ = format.decode::<MonomialChallengeTreeNode>(bytes); },
+        6 => { let _ = format.decode::<BitSigmaResponseMessage>(bytes); },
+        7 => { let _ = format.decode::<MonomialResponseTreeNode>(bytes); },
+        8 => { let _ = format.decode::<CommitmentMapMessage<DataT>>(bytes); },
+        9 => { let _ = format.decode::<ProverRandomnessComm>(bytes); },
+        10 => { let _ = format.decode::<VerifierRandomnessChallenge>(bytes); },
+        11 => { let _ = format.decode::<ProverRandomnessResponse>(bytes); },
+        12 => { let _ = format.decode::<VerifierCheckMessage>(bytes); },
+        13 => { let _ = format.decode::<QueryMessage<DataT>>(bytes); },
+        14 => { let _ = format.decode::<QueryAnswerMessage>(bytes); },
+        15 => { let _ = format.decode::<SpotCheckMessage>(bytes); },
+        16 => { let _ = format.decode::<BitSigmaCombinedChallengeMessage>(bytes); },
+        17 => { let _ = format.decode::<CutAndChooseCommitmentMessage>(bytes); },
+        18 => { let _ = format.decode::<CutAndChooseChallengeMessage>(bytes); },
+        19 => { let _ = format.decode::<CutAndChooseResponseMessage>(bytes); },
+        20 => { let _ = format.decode::<MonomialCommitmentTree>(bytes); },
+        21 => { let _ = format.decode::<MonomialChallengeTree>(bytes); },
+        22 => { let _ = format.decode::<MonomialResponseTree>(bytes); },
+        23 => { let _ = format.decode::<EncryptedQueryAnswerMessage>(bytes); },
+        24 => { let _ = format.decode::<ElGamalQueryAnswerMessage>(bytes); },
+        25 => { let _ = format.decode::<UpdateMessage>(bytes); },
+        26 => { let _ = format.decode::<DatasetMessage>(bytes); },
+        27 => { let _ = format.decode::<ContributionCommitmentMessage>(bytes); },
+        28 => { let _ = format.decode::<ContributionDigestsMessage>(bytes); },
+        29 => { let _ = format.decode::<ContributionMessage>(bytes); },
+        30 => { let _ = format.decode::<ContributionsMessage>(bytes); },
+        31 => { let _ = format.decode::<BeaconRoundMessage>(bytes); },
+        32 => { let _ = format.decode::<CertificateSignatureMessage>(bytes); },
+        33 => { let _ = format.decode::<SketchCommitmentMessage>(bytes); },
+        34 => { let _ = format.decode::<SketchChallengeMessage>(bytes); },
+        35 => { let _ = format.decode::<SketchResponseMessage>(bytes); },
+        36 => { let _ = format.decode::<WireFormatMessage>(bytes); },
+        37 => { let _ = format.decode::<SeedContributionMessage>(bytes); },
+        38 => { let _ = format.decode::<SeedRevealMessage>(bytes); },
+        39 => { let _ = format.decode::<ProverRandomnessComms>(bytes); },
+        40 => { let _ = format.decode::<VerifierRandomnessChallenges>(bytes); },
+        41 => { let _ = format.decode::<ProverRandomnessResponses>(bytes); },
+        42 => { let _ = format.decode::<NoiseProductCommitments>(bytes); },
+        43 => { let _ = format.decode::<NoiseProductChallenges>(bytes); },
+        44 => { let _ = format.decode::<NoiseProductResponses>(bytes); },
+        45 => { let _ = format.decode::<BatchQueryMessage<DataT>>(bytes); },
+        46 => { let _ = format.decode::<BatchQueryAnswerMessage>(bytes); },
+        47 => { let _ = format.decode::<AnalystMessage>(bytes); },
+        48 => { let _ = format.decode::<AuthChallengeMessage>(bytes); },
+        49 => { let _ = format.decode::<AuthResponseMessage>(bytes); },
+        50 => { let _ = format.decode::<KeyShareMessage>(bytes); },
+        51 => { let _ = format.decode::<ResumeRandomnessMessage>(bytes); },
+        52 => { let _ = format.decode::<CheckpointMessage>(bytes); },
+        _ => { let _ = format.decode::<AbortMessage>(bytes); },
+    }
+});
@@ -0,0 +1,14 @@
+//! Read frames from an arbitrary byte stream; forged size headers must not panic or allocate past the input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use certified_dp::transport::{read_frame, DEFAULT_MAX_FRAME_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    let mut stream = data;
+    while let Ok(frame) = read_frame(&mut stream, DEFAULT_MAX_FRAME_SIZE) {
+        assert!(frame.len() <= data.len());
+    }
+});
@@ -0,0 +1,141 @@
+//! Every well-formed message must survive framing, encoding, and decoding unchanged.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use certified_dp::config::DataT;
+use certified_dp::messages::*;
+use certified_dp::transport::{read_frame, write_frame, DEFAULT_MAX_FRAME_SIZE};
+
+#[derive(Arbitrary, Debug)]
+enum AnyMessage {
+    Ready(ReadyMessage),
+    Setup(SetupMessage),
+    BitSigmaCommitment(BitSigmaCommitmentMessage),
+    MonomialCommitmentTree(MonomialCommitmentTreeNode),
+    BitSigmaChallenge(BitSigmaChallengeMessage),
+    MonomialChallengeTree(MonomialChallengeTreeNode),
+    BitSigmaResponse(BitSigmaResponseMessage),
+    MonomialResponseTree(MonomialResponseTreeNode),
+    CommitmentMap(CommitmentMapMessage<DataT>),
+    RandomnessComm(ProverRandomnessComm),
+    RandomnessChallenge(VerifierRandomnessChallenge),
+    RandomnessResponse(ProverRandomnessResponse),
+    Check(VerifierCheckMessage),
+    Query(QueryMessage<DataT>),
+    QueryAnswer(QueryAnswerMessage),
+    SpotCheck(SpotCheckMessage),
+    BitSigmaCombinedChallenge(BitSigmaCombinedChallengeMessage),
+    CutAndChooseCommitment(CutAndChooseCommitmentMessage),
+    CutAndChooseChallenge(CutAndChooseChallengeMessage),
+    CutAndChooseResponse(CutAndChooseResponseMessage),
+    CommitmentTree(MonomialCommitmentTree),
+    ChallengeTree(MonomialChallengeTree),
+    ResponseTree(MonomialResponseTree),
+    EncryptedQueryAnswer(EncryptedQueryAnswerMessage),
+    ElGamalQueryAnswer(ElGamalQueryAnswerMessage),
+    Update(UpdateMessage),
+    Dataset(DatasetMessage),
+    ContributionCommitment(ContributionCommitmentMessage),
+    ContributionDigests(ContributionDigestsMessage),
+    Contribution(ContributionMessage),
+    Contributions(ContributionsMessage),
+    BeaconRound(BeaconRoundMessage),
+    CertificateSignature(CertificateSignatureMessage),
+    Abort(AbortMessage),
+    SketchCommitment(SketchCommitmentMessage),
+    SketchChallenge(SketchChallengeMessage),
+    SketchResponse(SketchResponseMessage),
+    WireFormat(WireFormatMessage),
+    SeedContribution(SeedContributionMessage),
+    SeedReveal(SeedRevealMessage),
+    RandomnessComms(ProverRandomnessComms),
+    RandomnessChallenges(VerifierRandomnessChallenges),
+    RandomnessResponses(ProverRandomnessResponses),
+    NoiseProductCommitments(NoiseProductCommitments),
+    NoiseProductChallenges(NoiseProductChallenges),
+    NoiseProductResponses(NoiseProductResponses),
+    BatchQuery(BatchQueryMessage<DataT>),
+    BatchQueryAnswer(BatchQueryAnswerMessage),
+    Analyst(AnalystMessage),
+    AuthChallenge(AuthChallengeMessage),
+    AuthResponse(AuthResponseMessage),
+    KeyShare(KeyShareMessage),
+    ResumeRandomness(ResumeRandomnessMessage),
+    Checkpoint(CheckpointMessage),
+}
+
+/// Frame and encode `m` in each wire format, read it back, and compare the re-encoded value
+fn roundtrip<M: Serialize + DeserializeOwned>(m: &M) {
+    for format in [WireFormat::Json, WireFormat::Bincode] {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, &format.encode(m)).unwrap();
+
+        let frame = read_frame(&mut wire.as_slice(), DEFAULT_MAX_FRAME_SIZE).expect("well-formed frame failed to read");
+        let decoded: M = format.decode(&frame).expect("well-formed message failed to decode");
+        assert_eq!(serde_json::to_value(m).unwrap(), serde_json::to_value(&decoded).unwrap());
+    }
+}
+
+fuzz_target!(|m: AnyMessage| {
+    match &m {
+        AnyMessage::Ready(m) => roundtrip(m),
+        AnyMessage::Setup(m) => roundtrip(m),
+        AnyMessage::BitSigmaCommitment(m) => roundtrip(m),
+        AnyMessage::MonomialCommitmentTree(m) => roundtrip(m),
+        AnyMessage::BitSigmaChallenge(m) => roundtrip(m),
+        AnyMessage::MonomialChallengeTree(m) => roundtrip(m),
+        AnyMessage::BitSigmaResponse(m) => roundtrip(m),
+        AnyMessage::MonomialResponseTree(m) => roundtrip(m),
+        AnyMessage::CommitmentMap(m) => roundtrip(m),
+        AnyMessage::RandomnessComm(m) => roundtrip(m),
+        AnyMessage::RandomnessChallenge(m) => roundtrip(m),
+        AnyMessage::RandomnessResponse(m) => roundtrip(m),
+        AnyMessage::Check(m) => roundtrip(m),
+        AnyMessage::Query(m) => roundtrip(m),
+        AnyMessage::QueryAnswer(m) => roundtrip(m),
+        AnyMessage::SpotCheck(m) => roundtrip(m),
+        AnyMessage::BitSigmaCombinedChallenge(m) => roundtrip(m),
+        AnyMessage::CutAndChooseCommitment(m) => roundtrip(m),
+        AnyMessage::CutAndChooseChallenge(m) => roundtrip(m),
+        AnyMessage::CutAndChooseResponse(m) => roundtrip(m),
+        AnyMessage::CommitmentTree(m) => roundtrip(m),
+        AnyMessage::ChallengeTree(m) => roundtrip(m),
+        AnyMessage::ResponseTree(m) => roundtrip(m),
+        AnyMessage::EncryptedQueryAnswer(m) => roundtrip(m),
+        AnyMessage::ElGamalQueryAnswer(m) => roundtrip(m),
+        AnyMessage::Update(m) => roundtrip(m),
+        AnyMessage::Dataset(m) => roundtrip(m),
+        AnyMessage::ContributionCommitment(m) => roundtrip(m),
+        AnyMessage::ContributionDigests(m) => roundtrip(m),
+        AnyMessage::Contribution(m) => roundtrip(m),
+        AnyMessage::Contributions(m) => roundtrip(m),
+        AnyMessage::BeaconRound(m) => roundtrip(m),
+        AnyMessage::CertificateSignature(m) => roundtrip(m),
+        AnyMessage::Abort(m) => roundtrip(m),
+        AnyMessage::SketchCommitment(m) => roundtrip(m),
+        AnyMessage::SketchChallenge(m) => roundtrip(m),
+        AnyMessage::SketchResponse(m) => roundtrip(m),
+        AnyMessage::WireFormat(m) => roundtrip(m),
+        AnyMessage::SeedContribution(m) => roundtrip(m),
+        AnyMessage::SeedReveal(m) => roundtrip(m),
+        AnyMessage::RandomnessComms(m) => roundtrip(m),
+        AnyMessage::RandomnessChallenges(m) => roundtrip(m),
+        AnyMessage::RandomnessResponses(m) => roundtrip(m),
+        AnyMessage::NoiseProductCommitments(m) => roundtrip(m),
+        AnyMessage::NoiseProductChallenges(m) => roundtrip(m),
+        AnyMessage::NoiseProductResponses(m) => roundtrip(m),
+        AnyMessage::BatchQuery(m) => roundtrip(m),
+        AnyMessage::BatchQueryAnswer(m) => roundtrip(m),
+        AnyMessage::Analyst(m) => roundtrip(m),
+        AnyMessage::AuthChallenge(m) => roundtrip(m),
+        AnyMessage::AuthResponse(m) => roundtrip(m),
+        AnyMessage::KeyShare(m) => roundtrip(m),
+        AnyMessage::ResumeRandomness(m) => roundtrip(m),
+        AnyMessage::Checkpoint(m) => roundtrip(m),
+    }
+});
@@ -0,0 +1,247 @@
+/*!
+ * analyst.rs
+ *
+ * Encryption of query answers to an analyst's key, for deployments where the verifier is an intermediary (e.g. a
+ * dealer or gateway) that should check the answers without learning them. With the analyst's key X = x * h, the prover
+ * encrypts the answer y as (A, B) = (k * h, y * g + k * X), and proves non-interactively that B hides the same y as the
+ * query commitment C = y * g + r * h. The verifier checks the proof against C, which it computes homomorphically as
+ * before. Since B only determines y * g, the prover also sends y masked with a hash of the shared secret k * X; the
+ * analyst unmasks it with x * A and checks it against B.
+ */
+
+use curve25519_dalek::{constants, ristretto::CompressedRistretto, RistrettoPoint, Scalar};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+
+use crate::elgamal;
+use crate::pedersen;
+use crate::snapshot::SnapshotId;
+#[cfg(feature = "arbitrary")]
+use crate::fuzzing;
+
+/// Domain separators for the proof challenge and the answer mask
+const CHALLENGE_DOMAIN: &[u8] = b"certified-dp analyst answer proof";
+const MASK_DOMAIN: &[u8] = b"certified-dp analyst answer mask";
+
+/// Analyst's decryption key
+#[derive(Clone)]
+pub struct SecretKey {
+    x: Scalar,
+}
+
+/// Analyst's public key, given to the prover and the verifier
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PublicKey {
+    point: RistrettoPoint,
+}
+
+/// Proof that a ciphertext and a Pedersen commitment hide the same value
+#[derive(Serialize, Deserialize, Debug)]
+struct EqualityProof {
+    t_c: RistrettoPoint,
+    t_a: RistrettoPoint,
+    t_b: RistrettoPoint,
+    z_y: Scalar,
+    z_r: Scalar,
+    z_k: Scalar,
+}
+
+/// Query answer encrypted to the analyst, with the proof that it matches the query commitment
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncryptedAnswer {
+    a: RistrettoPoint,
+    b: RistrettoPoint,
+    masked: Scalar,
+    proof: EqualityProof,
+}
+
+/// How query answers are returned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnswerMode {
+    /// In the clear, along with the opening of the query commitment
+    Plain,
+    /// Encrypted to the analyst's key by the prover, see `encrypt`
+    Encrypted(PublicKey),
+    /// Only the handle of the query commitment, with commitments made under `elgamal::params` for the analyst's key
+    ElGamal(PublicKey),
+}
+
+impl AnswerMode {
+    /// Mode selected by the `--analyst-key` and `--elgamal-key` options, which are mutually exclusive
+    pub fn from_keys(analyst_key: Option<PublicKey>, elgamal_key: Option<PublicKey>) -> AnswerMode {
+        match (analyst_key, elgamal_key) {
+            (Some(pk), _) => AnswerMode::Encrypted(pk),
+            (None, Some(pk)) => AnswerMode::ElGamal(pk),
+            (None, None) => AnswerMode::Plain,
+        }
+    }
+}
+
+/// Verified answer as saved for the analyst, with the commitment generator `g` it was proven against and the snapshot
+/// and budget epoch that answered it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnswerRecord {
+    pub g: RistrettoPoint,
+    #[serde(default)]
+    pub snapshot: SnapshotId,
+    #[serde(default)]
+    pub epoch: u64,
+    pub answer: SavedAnswer,
+}
+
+/// Either form of answer the analyst can decrypt; there is one per query, so the size difference does not matter
+#[derive(Serialize, Deserialize, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum SavedAnswer {
+    /// Answer encrypted by the prover, see `encrypt`
+    Encrypted(EncryptedAnswer),
+    /// Query commitment and its handle, under `elgamal` commitments
+    ElGamal(elgamal::Ciphertext),
+}
+
+/// Generate an analyst key pair; the key is on the Ristretto basepoint, which is `h` for every set of public parameters
+pub fn keygen<T: Rng + CryptoRng>(rng: &mut T) -> (SecretKey, PublicKey) {
+    let x = Scalar::random(rng);
+    (SecretKey { x }, PublicKey { point: x * constants::RISTRETTO_BASEPOINT_POINT })
+}
+
+/// Fiat-Shamir challenge over the statement and the proof commitments
+fn challenge(pp: &pedersen::PublicParams, pk: &PublicKey, c: &RistrettoPoint, a: &RistrettoPoint, b: &RistrettoPoint,
+             (t_c, t_a, t_b): (&RistrettoPoint, &RistrettoPoint, &RistrettoPoint)) -> Scalar {
+    let mut input = CHALLENGE_DOMAIN.to_vec();
+    for p in [&pp.g, &pp.h, &pk.point, c, a, b, t_c, t_a, t_b] {
+        input.extend_from_slice(p.compress().as_bytes());
+    }
+    Scalar::hash_from_bytes::<Sha512>(&input)
+}
+
+/// Mask for the answer, derived from the shared secret k * X = x * A
+fn mask(shared: &RistrettoPoint) -> Scalar {
+    let mut input = MASK_DOMAIN.to_vec();
+    input.extend_from_slice(shared.compress().as_bytes());
+    Scalar::hash_from_bytes::<Sha512>(&input)
+}
+
+/// (1) The prover encrypts `answer` to the analyst and proves it is the value committed in `commitment` with randomness `proof`.
+pub fn encrypt<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams, pk: &PublicKey,
+                                   commitment: &RistrettoPoint, answer: &Scalar, proof: &Scalar) -> EncryptedAnswer {
+
+    let k = Scalar::random(rng);
+    let a = k * pp.h;
+    let b = (answer * pp.g) + (k * pk.point);
+
+    let (a_y, a_r, a_k) = (Scalar::random(rng), Scalar::random(rng), Scalar::random(rng));
+    let t_c = pedersen::commit_with_r(&a_y, &a_r, pp);
+    let t_a = a_k * pp.h;
+    let t_b = (a_y * pp.g) + (a_k * pk.point);
+
+    let e = challenge(pp, pk, commitment, &a, &b, (&t_c, &t_a, &t_b));
+
+    EncryptedAnswer {
+        a, b,
+        masked: answer + mask(&(k * pk.point)),
+        proof: EqualityProof {
+            t_c, t_a, t_b,
+            z_y: a_y + (e * answer),
+            z_r: a_r + (e * proof),
+            z_k: a_k + (e * k),
+        },
+    }
+}
+
+/// (2) The verifier checks that the ciphertext hides the value committed in `commitment`, without learning it.
+pub fn verify(pp: &pedersen::PublicParams, pk: &PublicKey, commitment: &RistrettoPoint, answer: &EncryptedAnswer) -> bool {
+    // the analyst's key must be on h, or the ciphertext would not be decryptable
+    if pp.h != constants::RISTRETTO_BASEPOINT_POINT {
+        return false;
+    }
+
+    let p = &answer.proof;
+    let e = challenge(pp, pk, commitment, &answer.a, &answer.b, (&p.t_c, &p.t_a, &p.t_b));
+
+    pedersen::verify(&(p.t_c + (e * commitment)), &p.z_y, &p.z_r, pp)
+        && p.z_k * pp.h == p.t_a + (e * answer.a)
+        && (p.z_y * pp.g) + (p.z_k * pk.point) == p.t_b + (e * answer.b)
+}
+
+/// (3) The analyst decrypts the answer; returns None if the masked answer does not match the ciphertext.
+pub fn decrypt(pp: &pedersen::PublicParams, sk: &SecretKey, answer: &EncryptedAnswer) -> Option<Scalar> {
+    let shared = sk.shared_secret(&answer.a);
+    let y = answer.masked - mask(&shared);
+
+    if y * pp.g == answer.b - shared {
+        Some(y)
+    } else {
+        None
+    }
+}
+
+impl SecretKey {
+    pub fn to_hex(&self) -> String {
+        to_hex(self.x.as_bytes())
+    }
+
+    /// Shared secret x * A for a ciphertext whose randomness is committed in `handle` = k * h
+    pub fn shared_secret(&self, handle: &RistrettoPoint) -> RistrettoPoint {
+        self.x * handle
+    }
+}
+
+impl PublicKey {
+    pub fn to_hex(&self) -> String {
+        to_hex(self.point.compress().as_bytes())
+    }
+
+    pub fn point(&self) -> RistrettoPoint {
+        self.point
+    }
+}
+
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<[u8; 32], String> {
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(format!("expected 64 hex digits, got {:?}", s));
+    }
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|e| format!("{}", e))?;
+    }
+    Ok(bytes)
+}
+
+/// Parse an analyst public key, a compressed Ristretto point in hex, from the command line
+pub fn parse_public_key(s: &str) -> Result<PublicKey, String> {
+    CompressedRistretto(from_hex(s)?).decompress()
+        .map(|point| PublicKey { point })
+        .ok_or_else(|| "analyst key is not a valid group element".to_string())
+}
+
+/// Parse an analyst secret key, a canonical scalar in hex, from the command line
+pub fn parse_secret_key(s: &str) -> Result<SecretKey, String> {
+    Option::from(Scalar::from_canonical_bytes(from_hex(s)?))
+        .map(|x| SecretKey { x })
+        .ok_or_else(|| "analyst secret key is not a canonical scalar".to_string())
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for EncryptedAnswer {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(EncryptedAnswer {
+            a: fuzzing::point(u)?,
+            b: fuzzing::point(u)?,
+            masked: fuzzing::scalar(u)?,
+            proof: EqualityProof {
+                t_c: fuzzing::point(u)?,
+                t_a: fuzzing::point(u)?,
+                t_b: fuzzing::point(u)?,
+                z_y: fuzzing::scalar(u)?,
+                z_r: fuzzing::scalar(u)?,
+                z_k: fuzzing::scalar(u)?,
+            },
+        })
+    }
+}
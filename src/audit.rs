@@ -0,0 +1,71 @@
+/*!
+ * audit.rs
+ *
+ * Sampling of database entries for partial verification of the commitment phase, and the resulting soundness error.
+ * Instead of checking every entry's bit and product proofs, the verifier can audit a random fraction of the entries;
+ * a prover that corrupted some entries is caught unless none of them land in the sample.
+ */
+
+use rand::Rng;
+use rand::seq::index;
+
+/// How much of a commitment phase the verifier checks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditMode {
+    /// Every entry is challenged and verified
+    Full,
+    /// Once commitments are fixed, the verifier reveals a sample of entries; only those are challenged and proven
+    SpotCheck(f64),
+    /// Every entry is challenged and proven, but the verifier checks the responses of a secret sample only
+    Probabilistic(f64),
+}
+
+/// Parse an audit rate from the command line; must be in (0, 1]
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if rate > 0.0 && rate <= 1.0 {
+        Ok(rate)
+    } else {
+        Err(format!("audit rate must be in (0, 1], got {}", rate))
+    }
+}
+
+/// Number of entries audited at the given rate, rounded up so at least one entry is always checked
+pub fn sample_size(db_size: u32, rate: f64) -> u32 {
+    ((db_size as f64 * rate).ceil() as u32).clamp(db_size.min(1), db_size)
+}
+
+/// Pick entries to audit uniformly at random without replacement, returned in increasing order
+pub fn sample_entries<R: Rng>(rng: &mut R, db_size: u32, rate: f64) -> Vec<u32> {
+    let mut entries: Vec<u32> = index::sample(rng, db_size as usize, sample_size(db_size, rate) as usize)
+        .into_iter()
+        .map(|i| i as u32)
+        .collect();
+    entries.sort_unstable();
+    entries
+}
+
+/// Check that a received sample is strictly increasing and in range, so each audited entry is answered exactly once
+pub fn is_valid_sample(entries: &[u32], db_size: u32) -> bool {
+    entries.windows(2).all(|w| w[0] < w[1]) && entries.last().is_none_or(|&i| i < db_size)
+}
+
+/// Probability that a prover who corrupted `corrupted` of `db_size` entries goes undetected when `audited` entries
+/// are checked, i.e. C(n - k, m) / C(n, m)
+pub fn escape_probability(db_size: u32, audited: u32, corrupted: u32) -> f64 {
+    if corrupted + audited > db_size {
+        return 0.0;
+    }
+
+    let mut p = 1.0;
+    for i in 0..audited {
+        p *= (db_size - corrupted - i) as f64 / (db_size - i) as f64;
+    }
+    p
+}
+
+/// One-line summary of an audit's sampling rate and soundness error, for the result reports
+pub fn describe(db_size: u32, audited: u32) -> String {
+    format!("audited {}/{} entries ({:.1}%), soundness error {:.4} against a single corrupted entry",
+        audited, db_size, 100.0 * audited as f64 / db_size.max(1) as f64, escape_probability(db_size, audited, 1))
+}
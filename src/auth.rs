@@ -0,0 +1,148 @@
+/*!
+ * auth.rs
+ *
+ * Authentication of the verifiers a prover serves, so that it only runs the expensive commitment and randomness phases
+ * for the ones it trusts. Once the two have agreed keys for the session's frames (see `mac`), the prover sends a fresh
+ * random challenge, and the verifier answers it with either a tag keyed with a pre-shared key, or an Ed25519 signature
+ * (see `signature`) under a key the prover authorizes. Either answer covers the challenge and both key shares, so it
+ * cannot be replayed into another session, nor relayed by an attacker that agreed keys of its own with each side. A
+ * verifier that fails to authenticate is sent an abort and disconnected before the prover has committed to anything.
+ * A prover can bind each key it authorizes to an analyst, whose sessions only a verifier holding that key may open.
+ */
+
+use hmac::{Hmac, Mac};
+use rand::{CryptoRng, Rng};
+use sha2::Sha256;
+use std::fmt;
+
+use crate::epoch;
+use crate::error::{Error, Result};
+use crate::mac::FrameKeys;
+use crate::messages::{read_message, write_abort, write_message, AbortReason, AuthChallengeMessage, AuthProof, AuthResponseMessage, Framed};
+use crate::signature::{self, SigningKey, VerifyingKey};
+use crate::transport::Transport;
+
+/// Domain separator for the tags and signatures answering a challenge
+const AUTH_DOMAIN: &[u8] = b"certified-dp authentication";
+
+/// Secret shared by the prover and its verifiers
+#[derive(Clone)]
+pub struct PresharedKey([u8; 32]);
+
+/// What a verifier authenticates with
+#[derive(Clone, Debug)]
+pub enum Credential {
+    Preshared(PresharedKey),
+    Key(SigningKey),
+}
+
+/// Key a prover authorizes, bound to the one analyst whose sessions a verifier holding it may open, if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthorizedKey {
+    pub analyst: Option<String>,
+    pub key: VerifyingKey,
+}
+
+/// Verifiers a prover serves: those holding its pre-shared key, if it has one, and those holding any of its keys
+#[derive(Clone, Debug, Default)]
+pub struct Authorized {
+    pub preshared: Option<PresharedKey>,
+    pub keys: Vec<VerifyingKey>,
+}
+
+impl PresharedKey {
+    /// Key itself, which the session's frame keys are mixed with (see `mac`)
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// HMAC-SHA256 of `answered`, to finalize into the tag answering it or check it against one
+    fn hmac(&self, answered: &[u8]) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.0).unwrap().chain_update(answered)
+    }
+}
+
+/// Keeps the secret out of logs, e.g. of parsed command line arguments
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PresharedKey(..)")
+    }
+}
+
+impl Credential {
+    /// Answer to `challenge` on a connection whose frames are authenticated with `keys`
+    pub fn prove(&self, challenge: &[u8; 32], keys: &FrameKeys) -> AuthProof {
+        let answered = answered(challenge, keys);
+        match self {
+            Credential::Preshared(key) => AuthProof::Tag { tag: key.hmac(&answered).finalize().into_bytes().into() },
+            Credential::Key(key) => AuthProof::Signature { key: key.verifying_key(), signature: key.sign(&answered) },
+        }
+    }
+}
+
+impl Authorized {
+    /// Whether any verifier has to authenticate
+    pub fn required(&self) -> bool {
+        self.preshared.is_some() || !self.keys.is_empty()
+    }
+
+    /// Whether `proof` answers `challenge`, on a connection whose frames are authenticated with `keys`, with a
+    /// credential authorized here; a tag is compared in constant time
+    pub fn check(&self, challenge: &[u8; 32], keys: &FrameKeys, proof: &AuthProof) -> bool {
+        let answered = answered(challenge, keys);
+        match proof {
+            AuthProof::Tag { tag } => self.preshared.as_ref().is_some_and(|key| key.hmac(&answered).verify_slice(tag).is_ok()),
+            AuthProof::Signature { key, signature } => self.keys.contains(key) && key.verify(&answered, signature),
+        }
+    }
+}
+
+/// What a verifier's answer to `challenge` covers: the challenge, and the prover's and verifier's shares of `keys`
+fn answered(challenge: &[u8; 32], keys: &FrameKeys) -> Vec<u8> {
+    let (prover, verifier) = keys.shares();
+    [AUTH_DOMAIN, challenge, prover.as_bytes(), verifier.as_bytes()].concat()
+}
+
+/// Keys authenticating the frames on `stream`, which an answer to a challenge covers
+fn frame_keys<S: Transport + ?Sized>(stream: &Framed<S>) -> Result<FrameKeys> {
+    stream.frame_keys().cloned().ok_or_else(|| Error::Protocol("Authentication needs keys agreed with the peer first".to_string()))
+}
+
+/// Prover side: challenge the verifier on `stream`, once keys for its frames are agreed, and abort unless it answers
+/// with a credential in `authorized`, returning the key it signed its answer with, or None for the pre-shared key
+pub fn prover_authenticate<T: Rng + CryptoRng, S: Transport + ?Sized>(rng: &mut T, stream: &mut Framed<S>, authorized: &Authorized) -> Result<Option<VerifyingKey>> {
+    let keys = frame_keys(stream)?;
+    let challenge: [u8; 32] = rng.gen();
+    write_message(stream, &AuthChallengeMessage { challenge })?;
+    let response: AuthResponseMessage = read_message(stream)?;
+    if !authorized.check(&challenge, &keys, &response.proof) {
+        let reason = AbortReason::Unauthorized;
+        // the verifier may already be gone, which makes no difference to the refusal
+        let _ = write_abort(stream, &reason);
+        return Err(Error::Aborted(reason));
+    }
+    match response.proof {
+        AuthProof::Tag { .. } => Ok(None),
+        AuthProof::Signature { key, .. } => Ok(Some(key)),
+    }
+}
+
+/// Verifier side: answer the prover's challenge on `stream` with `credential`, once keys for its frames are agreed
+pub fn verifier_authenticate<S: Transport + ?Sized>(stream: &mut Framed<S>, credential: &Credential) -> Result<()> {
+    let keys = frame_keys(stream)?;
+    let m: AuthChallengeMessage = read_message(stream)?;
+    write_message(stream, &AuthResponseMessage { proof: credential.prove(&m.challenge, &keys) })
+}
+
+/// Parse a pre-shared key, 32 bytes in hex, from the command line
+pub fn parse_preshared_key(s: &str) -> std::result::Result<PresharedKey, String> {
+    signature::from_hex(s).map(PresharedKey)
+}
+
+/// Parse a key to authorize from the command line: a verifying key in hex, bound to an analyst with `ANALYST=KEY`
+pub fn parse_authorized_key(s: &str) -> std::result::Result<AuthorizedKey, String> {
+    match s.split_once('=') {
+        Some((analyst, key)) => Ok(AuthorizedKey { analyst: Some(epoch::parse_analyst(analyst)?), key: signature::parse_verifying_key(key)? }),
+        None => Ok(AuthorizedKey { analyst: None, key: signature::parse_verifying_key(s)? }),
+    }
+}
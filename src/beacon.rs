@@ -0,0 +1,230 @@
+/*!
+ * beacon.rs
+ *
+ * Public randomness beacon as the source of the verifier's coin flips and sigma challenges in the randomness phase.
+ * The prover commits to every noise coin up front, the verifier names the first beacon round published after it has
+ * all of the commitments, and both parties derive the coins from that round's randomness, each reading the round
+ * from its own copy of the beacon. The phase takes a single round trip, and anyone holding the transcript and the
+ * beacon can recompute the coins.
+ *
+ * Beacons are read from a file of JSON lines in the format of the drand HTTP API: the chain's `/info` (which gives its
+ * `genesis_time`, `period`, `public_key` and `schemeID`) and any number of `/public/<round>` entries, appended as they
+ * are published. An entry is only accepted if its BLS signature checks against the chain's public key under the
+ * chain's scheme, and its randomness is the SHA-256 hash of the signature, as drand defines them. Whoever relays the
+ * file can withhold rounds but not forge one, so the parties only have to agree on the chain, by its public key.
+ */
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{pairing, G1Affine, G1Projective, G2Affine, G2Projective};
+use curve25519_dalek::scalar::Scalar;
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+use std::fs;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::bit_sigma;
+
+/// Domain separators for the coins and challenges derived from a beacon round
+const COIN_DOMAIN: &[u8] = b"certified-dp beacon coin";
+const CHALLENGE_DOMAIN: &[u8] = b"certified-dp beacon challenge";
+
+/// Domain separators drand hashes a round's message to a point with, for signatures on G2 and on G1
+const SIGNATURE_DST_G2: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+const SIGNATURE_DST_G1: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// How often to re-read the beacon file while waiting for a round to be published
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How a drand chain signs its rounds, from the `schemeID` of its `/info`
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Scheme {
+    /// Signatures on G2 over the previous round's signature and the round number, as the default mainnet chain signs;
+    /// chains from before schemes were named sign this way
+    #[default]
+    #[serde(rename = "pedersen-bls-chained")]
+    Chained,
+    /// Signatures on G2 over the round number alone
+    #[serde(rename = "pedersen-bls-unchained")]
+    Unchained,
+    /// Signatures on G1 over the round number alone, as quicknet signs
+    #[serde(rename = "bls-unchained-g1-rfc9380")]
+    UnchainedG1,
+}
+
+/// Beacon chain parameters, from the drand `/info` endpoint
+#[derive(Deserialize, Clone, Debug)]
+struct ChainInfo {
+    period: u64,
+    genesis_time: u64,
+    public_key: String,
+    #[serde(rename = "schemeID", default)]
+    scheme: Scheme,
+}
+
+/// Public key of a chain, on G1 for signatures on G2 and the other way around
+#[derive(Clone, Copy, Debug)]
+enum ChainKey {
+    G1(G1Affine),
+    G2(G2Affine),
+}
+
+/// Beacon entry, from the drand `/public/<round>` endpoint
+#[derive(Deserialize, Debug)]
+struct Entry {
+    round: u64,
+    randomness: String,
+    signature: String,
+    /// Signature of the round before, which a chained scheme signs along with the round
+    #[serde(default)]
+    previous_signature: Option<String>,
+}
+
+/// Randomness beacon read from a file of drand JSON lines
+#[derive(Clone, Debug)]
+pub struct Beacon {
+    path: PathBuf,
+    info: ChainInfo,
+    key: ChainKey,
+}
+
+impl Beacon {
+    /// Beacon at `path`, which must already hold the chain info
+    pub fn open(path: PathBuf) -> Result<Beacon, String> {
+        let contents = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let info = contents.lines()
+            .find_map(|line| serde_json::from_str::<ChainInfo>(line).ok())
+            .ok_or_else(|| format!("{} has no chain info with a genesis_time, period and public_key", path.display()))?;
+        if info.period == 0 {
+            return Err("beacon period must be positive".to_string());
+        }
+        let key = from_hex(&info.public_key)?;
+        let key = match info.scheme {
+            Scheme::Chained | Scheme::Unchained => key.try_into().ok()
+                .and_then(|key| Option::from(G1Affine::from_compressed(&key)))
+                .filter(|key: &G1Affine| !bool::from(key.is_identity()))
+                .map(ChainKey::G1),
+            Scheme::UnchainedG1 => key.try_into().ok()
+                .and_then(|key| Option::from(G2Affine::from_compressed(&key)))
+                .filter(|key: &G2Affine| !bool::from(key.is_identity()))
+                .map(ChainKey::G2),
+        };
+        let key = key.ok_or_else(|| format!("beacon public key is not a point of the group its scheme {:?} needs", info.scheme))?;
+        Ok(Beacon { path, info, key })
+    }
+
+    /// Latest round published at `time`; rounds start at 1 at the genesis time
+    pub fn round_at(&self, time: SystemTime) -> u64 {
+        let since = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        since.saturating_sub(self.info.genesis_time) / self.info.period + 1
+    }
+
+    /// First round published strictly after `time`, whose randomness nobody can know at `time`
+    pub fn next_round(&self, time: SystemTime) -> u64 {
+        self.round_at(time) + 1
+    }
+
+    /// Check a round named by the verifier is published after `committed` (when the prover committed to the coins), so
+    /// nobody could know its randomness beforehand, and no later than the round after next, so the prover is not kept
+    /// waiting on it; the one round of slack allows for the parties' clocks to differ
+    pub fn check_round(&self, round: u64, committed: SystemTime) -> Result<(), String> {
+        if round <= self.round_at(committed) {
+            return Err(format!("round {} was published before the coins were committed", round));
+        }
+        if round > self.next_round(SystemTime::now()) + 1 {
+            return Err(format!("round {} is too far in the future", round));
+        }
+        Ok(())
+    }
+
+    /// Randomness of `round`, waiting up to two beacon periods past its publication for it to appear in the file
+    pub fn randomness(&self, round: u64) -> Result<[u8; 32], String> {
+        let published = UNIX_EPOCH + Duration::from_secs(self.info.genesis_time + (round.saturating_sub(1)) * self.info.period);
+        let wait = published.duration_since(SystemTime::now()).unwrap_or_default() + Duration::from_secs(2 * self.info.period);
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(randomness) = self.find(round)? {
+                return Ok(randomness);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("round {} was not published to {} in time", round, self.path.display()));
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Randomness of `round` if the file has it, checked against the entry's signature, and the signature against the
+    /// chain's public key
+    fn find(&self, round: u64) -> Result<Option<[u8; 32]>, String> {
+        let contents = fs::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let entry = match contents.lines().filter_map(|line| serde_json::from_str::<Entry>(line).ok()).find(|e| e.round == round) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let randomness: [u8; 32] = from_hex(&entry.randomness)?.try_into()
+            .map_err(|_| format!("round {} randomness is not 32 bytes", round))?;
+        let signature = from_hex(&entry.signature)?;
+        if Sha256::digest(&signature).as_slice() != randomness {
+            return Err(format!("round {} randomness is not the hash of its signature", round));
+        }
+        let previous = entry.previous_signature.as_deref().map(from_hex).transpose()?;
+        if !self.signed(round, &signature, previous.as_deref())? {
+            return Err(format!("round {} signature does not check against the chain's public key", round));
+        }
+        Ok(Some(randomness))
+    }
+
+    /// Whether `signature` is the chain's BLS signature on `round`, following the signature `previous` of the round
+    /// before on a chained scheme
+    fn signed(&self, round: u64, signature: &[u8], previous: Option<&[u8]>) -> Result<bool, String> {
+        let mut message = Sha256::new();
+        if self.info.scheme == Scheme::Chained {
+            message.update(previous.ok_or_else(|| format!("round {} has no previous_signature, which its chained scheme signs", round))?);
+        }
+        message.update(round.to_be_bytes());
+        let message = message.finalize();
+        let malformed = || format!("round {} signature is not a point of the group its scheme {:?} signs on", round, self.info.scheme);
+        Ok(match self.key {
+            ChainKey::G1(key) => {
+                let signature: G2Affine = signature.try_into().ok().and_then(|s| Option::from(G2Affine::from_compressed(&s))).ok_or_else(malformed)?;
+                let hashed = <G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, SIGNATURE_DST_G2);
+                pairing(&key, &G2Affine::from(hashed)) == pairing(&G1Affine::generator(), &signature)
+            },
+            ChainKey::G2(key) => {
+                let signature: G1Affine = signature.try_into().ok().and_then(|s| Option::from(G1Affine::from_compressed(&s))).ok_or_else(malformed)?;
+                let hashed = <G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, SIGNATURE_DST_G1);
+                pairing(&signature, &G2Affine::generator()) == pairing(&G1Affine::from(hashed), &key)
+            },
+        })
+    }
+}
+
+/// Coin flip and bit sigma challenge for each of `n` coins, derived from a beacon round's randomness
+pub fn coins(randomness: &[u8; 32], n: u32) -> Vec<(u32, bit_sigma::Challenge)> {
+    (0..n).map(|i| {
+        let coin = Sha256::new()
+            .chain_update(COIN_DOMAIN)
+            .chain_update(randomness)
+            .chain_update(i.to_le_bytes())
+            .finalize();
+        let input = [CHALLENGE_DOMAIN, randomness, &i.to_le_bytes()].concat();
+        let challenge = bit_sigma::Challenge::from_scalar(Scalar::hash_from_bytes::<Sha512>(&input));
+        ((coin[0] & 1) as u32, challenge)
+    }).collect()
+}
+
+/// Decode a hex string
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(format!("invalid hex {:?}", s));
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("invalid hex {:?}: {}", s, e)))
+        .collect()
+}
+
+/// Parse a beacon file from the command line
+pub fn parse_beacon(s: &str) -> Result<Beacon, String> {
+    Beacon::open(PathBuf::from(s))
+}
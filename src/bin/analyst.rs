@@ -0,0 +1,283 @@
+/*!
+ * analyst.rs
+ *
+ * Analyst executable for query answers that only the analyst can read:
+ *
+ *   keygen: generate a key pair, printing the secret key and the public key to pass to the prover and verifier, or
+ *           with `--signing` a key pair for signing certificates
+ *   decrypt: decrypt the answers a verifier saved with `--encrypted-answers`, each printed with the snapshot and
+ *            budget epoch that answered it, or with `--sum` the sum of the answers saved under `--elgamal-key`, which
+ *            add up homomorphically as long as they come from the same snapshot and epoch
+ *   replay: verify a session recorded with `--record` by either party offline, re-running the verifier's checks on the
+ *           recorded frames and printing the result of each query
+ *   verify-certificate: re-verify the certificate of an answer written by a verifier with `--query-certificates`,
+ *                       printing the query, the answer, the Pedersen generators it holds under, and the privacy
+ *                       guarantee it was released under with the keys that signed it
+ *   sign-certificate: as an auditor, re-verify the certificate of an answer and add a signature on its guarantee
+ */
+
+use clap::Parser;
+use curve25519_dalek::{constants, ristretto::RistrettoPoint, scalar::Scalar};
+use rand::rngs::OsRng;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use certified_dp::analyst::{self, AnswerRecord, SavedAnswer};
+use certified_dp::elgamal;
+use certified_dp::pedersen;
+use certified_dp::query_certificate::QueryCertificate;
+use certified_dp::recording;
+use certified_dp::replay;
+use certified_dp::signature::{self, SigningKey, VerifyingKey};
+use certified_dp::snapshot::{self, SnapshotId};
+
+/// Main function for the analyst executable.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+enum Command {
+    /// Generate an analyst key pair
+    Keygen {
+        // (optional) generate a key pair for signing certificates instead, e.g. for an auditor
+        #[arg(long, default_value_t = false)]
+        signing: bool,
+    },
+
+    /// Decrypt the answers saved by a verifier, one per line
+    Decrypt {
+        // analyst secret key, in hex
+        #[arg(long, value_parser = analyst::parse_secret_key)]
+        secret_key: analyst::SecretKey,
+
+        // file of encrypted answers written by the verifier
+        #[arg(long)]
+        answers: PathBuf,
+
+        // (optional) largest absolute value searched for when decrypting ElGamal answers
+        #[arg(long, default_value_t = 1 << 32)]
+        max_answer: u64,
+
+        // (optional) decrypt only the sum of the answers, which must all be ElGamal answers
+        #[arg(long, default_value_t = false)]
+        sum: bool,
+    },
+
+    /// Verify a recorded session offline
+    Replay {
+        // file recorded by the prover or verifier with --record
+        #[arg(long)]
+        recording: PathBuf,
+
+        // (optional) session of the recording to verify, counting from 1
+        #[arg(long, default_value_t = 1)]
+        session: u32,
+    },
+
+    /// Re-verify the certificate of a query answer
+    VerifyCertificate {
+        // certificate file written by the verifier with --query-certificates
+        #[arg(long)]
+        certificate: PathBuf,
+
+        // (optional) snapshot ID, in hex, that the certificate must name
+        #[arg(long, value_parser = snapshot::parse_snapshot)]
+        snapshot: Option<SnapshotId>,
+
+        // (optional) verifying key, in hex, whose signature the certificate's guarantee must carry
+        #[arg(long, value_parser = signature::parse_verifying_key)]
+        signer: Option<VerifyingKey>,
+    },
+
+    /// Re-verify the certificate of a query answer and sign its guarantee
+    SignCertificate {
+        // certificate file written by the verifier with --query-certificates, to which the signature is added
+        #[arg(long)]
+        certificate: PathBuf,
+
+        // signing key, in hex, e.g. from keygen --signing
+        #[arg(long, value_parser = signature::parse_signing_key)]
+        signing_key: SigningKey,
+    },
+}
+
+/// Compressed point in hex
+fn point_hex(p: &RistrettoPoint) -> String {
+    p.compress().as_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Answers are small integers in practice (possibly negative after noise); anything else is printed as a scalar
+fn format_answer(y: &Scalar) -> String {
+    let small = |s: &Scalar| s.as_bytes()[8..].iter().all(|&b| b == 0)
+        .then(|| u64::from_le_bytes(s.as_bytes()[..8].try_into().unwrap()));
+
+    match (small(y), small(&-y)) {
+        (Some(v), _) => v.to_string(),
+        (None, Some(v)) => format!("-{}", v),
+        (None, None) => format!("0x{}", y.as_bytes().iter().rev().map(|b| format!("{:02x}", b)).collect::<String>()),
+    }
+}
+
+/// Decrypt a single saved answer
+fn decrypt(sk: &analyst::SecretKey, record: &AnswerRecord, max_answer: u64) -> Result<String, String> {
+    match &record.answer {
+        SavedAnswer::Encrypted(answer) => {
+            let pp = pedersen::PublicParams::new(record.g, constants::RISTRETTO_BASEPOINT_POINT);
+            analyst::decrypt(&pp, sk, answer)
+                .map(|y| format_answer(&y))
+                .ok_or_else(|| "answer was not encrypted to this key".to_string())
+        },
+        SavedAnswer::ElGamal(ct) => {
+            elgamal::decrypt(sk, &record.g, ct, max_answer)
+                .map(|y| y.to_string())
+                .ok_or_else(|| format!("answer is not within ±{} under this key", max_answer))
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    match Command::parse() {
+        Command::Keygen { signing: false } => {
+            let (sk, pk) = analyst::keygen(&mut OsRng);
+            println!("secret key: {}", sk.to_hex());
+            println!("public key: {}", pk.to_hex());
+        },
+        Command::Keygen { signing: true } => {
+            let (sk, pk) = signature::keygen(&mut OsRng);
+            println!("signing key: {}", sk.to_hex());
+            println!("verifying key: {}", pk.to_hex());
+        },
+        Command::Decrypt { secret_key, answers, max_answer, sum } => {
+            let contents = match fs::read_to_string(&answers) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("ERROR: Could not read {}: {}", answers.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let mut records = Vec::new();
+            for (i, line) in contents.lines().enumerate() {
+                match serde_json::from_str::<AnswerRecord>(line) {
+                    Ok(r) => records.push(r),
+                    Err(e) => {
+                        eprintln!("ERROR: Malformed answer on line {}: {}", i + 1, e);
+                        return ExitCode::FAILURE;
+                    }
+                };
+            }
+
+            if sum {
+                // ciphertexts from the same parameters add up to a ciphertext of the sum
+                let mut total: Option<AnswerRecord> = None;
+                for (i, r) in records.into_iter().enumerate() {
+                    total = match (total, r.answer) {
+                        (None, SavedAnswer::ElGamal(ct)) => Some(AnswerRecord { answer: SavedAnswer::ElGamal(ct), ..r }),
+                        (Some(AnswerRecord { g, snapshot, epoch, answer: SavedAnswer::ElGamal(sum) }), SavedAnswer::ElGamal(ct))
+                            if g == r.g && snapshot == r.snapshot && epoch == r.epoch => {
+                            Some(AnswerRecord { g, snapshot, epoch, answer: SavedAnswer::ElGamal(sum + ct) })
+                        },
+                        _ => {
+                            eprintln!("ERROR: Answer on line {} cannot be added to the previous ones", i + 1);
+                            return ExitCode::FAILURE;
+                        }
+                    };
+                }
+                records = total.into_iter().collect();
+            }
+
+            for (i, record) in records.iter().enumerate() {
+                match decrypt(&secret_key, record, max_answer) {
+                    Ok(y) => println!("{} {} {}", y, record.snapshot, record.epoch),
+                    Err(e) => {
+                        eprintln!("ERROR: Could not decrypt answer {}: {}", i + 1, e);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+        },
+        Command::Replay { recording, session } => {
+            let frames = match recording::load(&recording) {
+                Ok(frames) => frames,
+                Err(e) => {
+                    eprintln!("ERROR: Could not read recording {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let replayed = match replay::verify_session(&frames, session) {
+                Ok(replayed) => replayed,
+                Err(e) => {
+                    eprintln!("ERROR: Session {} failed to verify: {}", session, e);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let phases: Vec<&str> = replayed.phases.iter().map(|phase| phase.name()).collect();
+            println!("session {}, recorded by the {}: {}", session, format!("{:?}", replayed.side).to_lowercase(), phases.join(", "));
+            println!("epoch {}, snapshot {}", replayed.epoch, replayed.snapshot);
+            for (i, result) in replayed.queries.iter().enumerate() {
+                println!("query {}: {}", i + 1, result);
+            }
+            if !replayed.verified() {
+                eprintln!("ERROR: Session {} has answers that did not verify", session);
+                return ExitCode::FAILURE;
+            }
+        },
+        Command::VerifyCertificate { certificate, snapshot, signer } => {
+            let loaded = match QueryCertificate::load(&certificate) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    eprintln!("ERROR: Could not read certificate {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            if snapshot.is_some_and(|pinned| pinned != loaded.snapshot) {
+                eprintln!("ERROR: Certificate is of snapshot {}, not the pinned snapshot", loaded.snapshot);
+                return ExitCode::FAILURE;
+            }
+
+            println!("query {}", loaded);
+            println!("answer {}", format_answer(&loaded.answer));
+            println!("dataset {:?}, epoch {}, snapshot {}", loaded.dataset, loaded.epoch, loaded.snapshot);
+            println!("generators g {}, h {}", point_hex(&loaded.g), point_hex(&loaded.h));
+            println!("released under ε = {}, δ = {:e}", loaded.epsilon, loaded.delta);
+            if !loaded.verify() {
+                eprintln!("ERROR: Answer in {} does not open its query commitment", certificate.display());
+                return ExitCode::FAILURE;
+            }
+            // a signature on anything else means the certificate changed since it was signed
+            if let Some(key) = loaded.forged().first() {
+                eprintln!("ERROR: Signature of {} is not on the certificate's guarantee", key.to_hex());
+                return ExitCode::FAILURE;
+            }
+            for endorsement in &loaded.endorsements {
+                println!("signed by {}", endorsement.key.to_hex());
+            }
+            if let Some(key) = signer.filter(|key| !loaded.signed_by(key)) {
+                eprintln!("ERROR: Certificate is not signed by {}", key.to_hex());
+                return ExitCode::FAILURE;
+            }
+            println!("verified");
+        },
+        Command::SignCertificate { certificate, signing_key } => {
+            let mut loaded = match QueryCertificate::load(&certificate) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    eprintln!("ERROR: Could not read certificate {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            // only vouch for an answer checked here
+            if !loaded.verify() {
+                eprintln!("ERROR: Answer in {} does not open its query commitment", certificate.display());
+                return ExitCode::FAILURE;
+            }
+            loaded.sign(&signing_key);
+            if let Err(e) = loaded.save(&certificate) {
+                eprintln!("ERROR: Could not save certificate {}", e);
+                return ExitCode::FAILURE;
+            }
+            println!("guarantee {}", loaded.guarantee().fingerprint());
+        },
+    }
+    ExitCode::SUCCESS
+}
@@ -1,725 +1,1203 @@
-/**
+/*!
  * prover.rs
  * 
  * Primary Prover executable with the following arguments:
  * 
- *   db_size: number of elements in the database
+ *   db_size: number of elements in the database, unless loaded from a file
  *   dimension: (optional) override the dimension of the database entries
+ *   entry_width: (optional) bits in each database entry, otherwise the narrowest width holding every dataset's dimension
  *   max_degree: maximum monomial degree
  *   epsilon: differential privacy epsilon value
  *   delta: (optional) differential privacy delta value, otherwise set based on DB size
+ *   calibration: (optional) how epsilon and delta set the number of noise coins, approximate (the default), zcdp, or renyi,
+ *     the last two with progressive noise
+ *   noise: (optional) mechanism combining the noise coins into noise, binomial (the default) or discrete-laplace
+ *   sensitivity: (optional) largest L1 sensitivity of the session's queries, which the noise is scaled to, one by default
  *   sparsity: maximum sparsity of the supported query monomials
  *   skip_dishonest: (optional) skip dishonest commitment phase
  *   num_queries: (optional) number of queries to execute and average runtime over 
- *   sparsity_experiment: (optional) special flag to evaluate sparsity experiment from paper
+ *   batch: (optional) answer the queries in batches of this many, each in one message
+ *   sparsity_experiment: (optional) special flag to evaluate sparsity experiment from paper (requires the `experiments` feature)
+ *   spot_check: (optional) run the honest commitment phase in spot-check mode, auditing this fraction of entries
+ *   cut_and_choose: (optional) certify the dishonest commitment phase by cut-and-choose with this many encodings per entry
+ *   analyst_key: (optional) encrypt query answers to this analyst public key
+ *   elgamal_key: (optional) commit under exponential ElGamal for this analyst public key, revealing only query handles
+ *   auditors: (optional) number of verifier sessions to serve against the same committed database
+ *   daemon: (optional) serve verifier sessions until stopped, going on past any that fail
+ *   concurrent: (optional) most sessions to serve at once after the first, which commits the database they share
+ *   add_rows: (optional) add this many random rows to the database in an update phase after the commitment phases
+ *   remove_rows: (optional) remove this many random rows from the database in the same update phase
+ *   epoch_length: (optional) length of the budget epochs (e.g. 30d), after which commitments expire and the budget resets
+ *   epoch_budget: (optional) total epsilon each epoch can spend on queries, otherwise unlimited
+ *   epoch_delta: (optional) total delta each epoch can spend on queries, otherwise unlimited
+ *   composition: (optional) how the queries' privacy losses add up against the epoch budget, basic (the default) or advanced
+ *   analyst_budget: (optional) epsilon each analyst can spend of its own in each epoch, on top of the shared epoch budget
+ *   budget_state: (optional) file keeping what the current epoch has spent, so a restarted prover picks up its budgets
+ *   csv: (optional) load the database from this CSV file instead of generating random entries
+ *   jsonl: (optional) load the database from this JSON-lines file instead, one record per line
+ *   parquet: (optional) load the database from this Parquet file instead (requires the `parquet` feature)
+ *   max_rows: (optional) load only this many rows from the start of the Parquet file
+ *   column: (optional, repeatable) column of the file to encode as the next attribute of the schema, in order from bit 0
+ *   schema: (optional) JSON schema file of the attributes to encode, in place of --column
+ *   policy: (optional) JSON file of the query policy, limiting the queries the prover answers
+ *   dataset: (optional, repeatable) host a named dataset with its own commitments, budget, and parameter overrides
+ *   profile: (optional) persona to run as, data-owner (the default) or dealer, which neither updates nor answers queries
+ *   verifiers: (optional) number of verifiers jointly flipping the noise coins; all but the first join as co-verifiers
+ *   beacon: (optional) file of randomness beacon rounds to flip the noise coins with, in place of the verifier
+ *   resumable: (optional) keep the first session's commitments and noise for the later sessions, which resume it
+ *   deadline: (optional, repeatable) wall-clock limit on a phase, past which the protocol aborts
+ *   distinct: (optional) certify a sketch of this attribute of the entries, for distinct-count queries
+ *   progressive: (optional) flip only the first query's noise in the randomness phase, and each later query's before it
+ *   max_message_size: (optional) largest message, in MiB, accepted from a verifier
+ *   record: (optional) append every frame sent and received, with its session, phase, and time, to this file
+ *   tls_cert: (optional) serve the verifiers over TLS with this certificate chain
+ *   tls_key: (optional) private key of the TLS certificate
+ *   auth_psk: (optional) only serve verifiers answering a challenge with this pre-shared key, mixed into their frame keys
+ *   authorized_key: (optional, repeatable) only serve verifiers answering a challenge with a signature under this key,
+ *     bound to an analyst as ANALYST=KEY, which --analyst-budget requires
+ *   tls_ca: (optional) require each verifier to present a TLS certificate signed by this CA
+ *   http: (optional) serve the verifiers over HTTP, as JSON messages posted to each session (requires the `http` feature)
+ *   grpc: (optional) serve the verifiers over gRPC, each session a stream of frames (requires the `grpc` feature)
+ *   quic: (optional) serve the verifiers over QUIC, each frame on a stream of its own (requires the `quic` feature)
+ *   port: (optional) port to listen on, otherwise the configured default
+ *
+ * and the following subcommand:
+ *
+ *   certify: certify the commitments to an auditor in the first session and write the certificate, from which the
+ *            later sessions' analysts start without recommitting
  */
 
 #[macro_use] extern crate prettytable;
 
-use clap::Parser;
-use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use num_traits::{pow, PrimInt};
-use rand::{Rng, SeedableRng};
+use clap::{Parser, Subcommand};
+#[cfg(feature = "experiments")]
+use num_traits::pow;
+use rand::seq::index;
 use rand::rngs::OsRng;
-use rand_chacha::ChaCha20Rng;
-use serde::Serialize;
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::mem::size_of;
-use std::net::{TcpStream, TcpListener};
-use std::ops::Neg;
-use std::time::{Duration, Instant};
-
-use certified_dp::config::{get_n, get_delta, PROVER_ADDRESS, PROVER_PORT, DataT};
-use certified_dp::data::Data;
-use certified_dp::messages::{read_from_stream, write_to_stream, BitSigmaChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, CommitmentMapMessage, MonomialChallengeTreeNode, MonomialCommitmentTreeNode, MonomialResponseTreeNode, ProverRandomnessComm, ProverRandomnessResponse, QueryAnswerMessage, QueryMessage, ReadyMessage, SetupMessage, VerifierCheckMessage, VerifierRandomnessChallenge};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+use certified_dp::analyst::{self, AnswerMode};
+use certified_dp::audit;
+use certified_dp::auth::{self, Authorized, AuthorizedKey, PresharedKey};
+use certified_dp::cut_and_choose;
+use certified_dp::calibration::{self, Calibration};
+use certified_dp::config::{self, max_dimension, PROVER_ADDRESS, PROVER_PORT, DataT};
+use certified_dp::data::{Data, Entry};
+use certified_dp::dataset::{self, DatasetSpec, DEFAULT_DATASET};
+use certified_dp::deadline::{self, Deadlines};
+use certified_dp::driver::{self, DriverOptions, Phase, ProtocolDriver, ProtocolStats, Role, Side};
+#[cfg(feature = "experiments")]
+use certified_dp::driver::synchronize;
+use certified_dp::elgamal;
+use certified_dp::error::{Error, Result};
+use certified_dp::budget::{self, Accountant, Composition};
+use certified_dp::epoch::{self, Epochs, Ledger};
+use certified_dp::messages::{read_message, write_abort, write_message, AbortReason, Framed, WireFormat, AnalystMessage, CertificateSignatureMessage, DatasetMessage, SessionParams, SetupMessage};
+use certified_dp::joint;
+use certified_dp::mac;
+use certified_dp::memory::TrackingAllocator;
+use certified_dp::noise::{self, Mechanism};
 use certified_dp::pedersen;
-use certified_dp::bit_sigma;
-use certified_dp::product_sigma;
-
-/// Primary prover state for the protocol execution
-#[allow(non_snake_case)]
-struct ProverState {
-    rng: OsRng,
-    pedersen_pp: pedersen::PublicParams,
-    dealer_b: u32,
-    dealer_b_comm: RistrettoPoint,
-    dealer_b_proof: Scalar,
-    final_b: u32,
-    final_proof: Scalar,
-    randomness_bit_sum: Scalar,
-    randomness_bit_proof: Scalar,
-    sigma_prover: bit_sigma::Prover,
-    C1: RistrettoPoint,
-    CPROOF: Scalar,
-
-    randomness_sigma_duration: Duration,
-    coin_flipping_and_agg_duration: Duration,
-}
-
-///
-/// -- SETUP PHASE --
-///
-
-/// Prover setup: generate a seed for shared randomness, setup Pedersen commitment scheme, and initialize state.
-fn prover_setup(stream: &mut TcpStream) -> ProverState {
+use certified_dp::policy::Policy;
+use certified_dp::profile::{self, Profile};
+use certified_dp::recording;
+use certified_dp::beacon::{self, Beacon};
+use certified_dp::certificate::{Certificate, Endorsement, Statement};
+use certified_dp::protocol::prover::{ProverState, prover_setup, prover_fork, prover_setup_message, prover_send_setup, prover_honest_commitment_phase, prover_dishonest_commitment_phase, prover_sketch_phase, prover_cut_and_choose_phase, prover_update_phase, prover_interactive_randomness_phase, prover_batched_randomness_phase, prover_resume_randomness_phase, prover_beacon_randomness_phase, prover_randomness_phase_combine, prover_answer_query, prover_answer_batch};
+use certified_dp::schema::{self, Schema};
+use certified_dp::sensitivity;
+use certified_dp::signature::{self, SigningKey};
+use certified_dp::sketch::{self, Attribute};
+use certified_dp::snapshot::SnapshotId;
+#[cfg(feature = "grpc")]
+use certified_dp::grpc::GrpcListener;
+#[cfg(feature = "http")]
+use certified_dp::http::HttpListener;
+#[cfg(feature = "quic")]
+use certified_dp::quic::QuicListener;
+use certified_dp::net::NetListener;
+use certified_dp::tls::{self, ServerConfig};
+use certified_dp::transport::{self, Transport};
+
+/// Track heap usage so the peak memory of each phase can be reported
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
 
-    let mut rng = OsRng::default();
-    let prover_seed = rng.gen::<[u8; 32]>();
+/// Main function for the prover executable, parsing arguments and executing the protocol phases.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    // number of elements in the database, unless loaded from a file, which has one per row
+    #[arg(long, required_unless_present = "source", conflicts_with = "source")]
+    db_size: Option<u32>,
 
-    let mut shared_rng = ChaCha20Rng::from_seed(prover_seed);
-    let pp = pedersen::setup(&mut shared_rng);
+    // (optional) dimension override
+    #[arg(long, default_value_t = max_dimension::<DataT>())]
+    dimension: u32,
 
-    let proof_val = Scalar::from(0 as u32);
+    // (optional) bits in each database entry: 8, 16, 32, 64, or 128
+    #[arg(long, value_parser = config::parse_entry_width)]
+    entry_width: Option<u32>,
 
-    write_to_stream(
-        stream, &serde_json::to_vec(&SetupMessage {
-            seed: prover_seed
-        }).unwrap()
-    );
+    // max monomial degree
+    #[arg(long)]
+    max_degree: u32,
 
-    ProverState {
-        rng,
-        pedersen_pp: pp.clone(),
-        C1: pedersen::commit_with_r(&Scalar::from(1 as u32), &proof_val, &pp),
-        CPROOF: proof_val,
-        dealer_b: 0,
-        dealer_b_comm: RistrettoPoint::default(),
-        dealer_b_proof: Scalar::default(),
-        final_b: 0,
-        final_proof: Scalar::default(),
-        randomness_bit_sum: Scalar::default(),
-        randomness_bit_proof: Scalar::default(),
-        sigma_prover: bit_sigma::Prover::default(),
+    // differential privacy epsilon
+    #[arg(long)]
+    epsilon: f32,
 
-        randomness_sigma_duration: Duration::from_secs(0),
-        coin_flipping_and_agg_duration: Duration::from_secs(0),
-    }
-}
+    // (optional) delta parameter
+    #[arg(long, default_value = None)]
+    delta: Option<f32>,
 
-///
-/// -- COMMITMENT PHASE --
-///
+    // how epsilon and delta set the number of noise coins: approximate, each query (epsilon, delta)-DP on its own, or zcdp or renyi, sizing the noise so the session's --num-queries queries together are (num-queries * epsilon, delta)-DP with fewer coins, which needs --progressive (must match the verifier)
+    #[arg(long, value_parser = calibration::parse_calibration, default_value = "approximate")]
+    calibration: Calibration,
 
-/// For a given monomial, calculate count of entries that satisfy the monomial condition
-fn calculate_monomial_sum<T: PrimInt>(indices: T, data: &[T]) -> Scalar {
-    let inv_indices = !indices;
-    let mut cnt: u32 = 0;
-    for i in 0..data.len() {
-        if (data[i] | inv_indices).count_zeros() == 0 { 
-            cnt += 1;
-        }
-    }
-    Scalar::from(cnt)
-}
+    // (optional) mechanism combining the verified noise coins into noise: binomial, their sum, or discrete-laplace, the difference of two geometric variables built from products of them (must match the verifier)
+    #[arg(long, value_parser = noise::parse_mechanism, default_value = "binomial")]
+    noise: Mechanism,
 
-/// Recursive helper function to generate monomial sums for all possible monomials, limited by max monomial degree and entry dimension
-fn generate_monomial_sums_helper<T: PrimInt + Hash>(indices: T, current_idx: T, data: &[T], monomial_map: &mut HashMap<T, Scalar>,
-                                                    dimension: u32, max_degree: u32) {
+    // (optional) largest L1 sensitivity of the session's queries, the sum of the absolute values of their coefficients: the binomial noise flips its square times the coins, and the discrete Laplace noise spends epsilon over it on each unit; queries beyond it, or without integer coefficients, are refused (must match the verifier)
+    #[arg(long, value_parser = sensitivity::parse_sensitivity, default_value_t = sensitivity::DEFAULT_SENSITIVITY)]
+    sensitivity: u32,
 
-    // we've recursively set bits for the bitwidth of the database entry type OR the max configured degree (number of set bits)
-    if current_idx.to_u32().unwrap() == dimension || indices.count_ones() == max_degree {
-        // skip the empty monomial
-        if indices.count_ones() > 0 {
-            let sum = calculate_monomial_sum(indices, data);
-            monomial_map.insert(indices, sum);
-        }
-        return;
-    }
+    // sparsity
+    #[arg(long)]
+    sparsity: u32,
 
-    // set bit at current index to a 0 or 1 and recurse
-    generate_monomial_sums_helper(
-        indices, current_idx + T::one(), data, monomial_map, dimension, max_degree);
-    generate_monomial_sums_helper(
-        indices | (T::one() << current_idx.to_usize().unwrap()), current_idx + T::one(), data, monomial_map, dimension, max_degree);
-}
+    // (optional) skip dishonest commitment phase if we're measuring something else
+    #[arg(long, default_value_t = false)]
+    skip_dishonest: bool,
 
-/// Generate monomial sums for all possible monomials, limited by max monomial degree and entry dimension
-fn generate_monomial_sums<T: PrimInt + Hash>(data: &[T], dimension: u32, max_degree: u32) -> HashMap<T, Scalar> {
-    let mut map = HashMap::new();
-    generate_monomial_sums_helper(T::zero(), T::zero(), data, &mut map, dimension, max_degree);
-    map
-}
+    // (optional) number of queries to execute and average runtime over
+    #[arg(long, default_value_t = 100)]
+    num_queries: u32,
 
-/// Honest commitment phase: generate monomial sums for all possible monomials and commit to each. Send the commitments to the verifier.
-fn prover_honest_commitment_phase<T: PrimInt + Hash + Serialize>(state: &mut ProverState, stream: &mut TcpStream, database: &mut Data<T>, dimension: u32, max_degree: u32) {
+    // (optional) answer the queries in batches of this many, each batch in one message, which the verifier checks at once (must match the verifier)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["analyst_key", "elgamal_key", "distinct", "progressive"])]
+    batch: u32,
 
-    let mut m = CommitmentMapMessage::<T> {
-        commitment_map: HashMap::new()
-    };
+    // (optional) evaluate sparsity experiment
+    #[cfg(feature = "experiments")]
+    #[arg(long, default_value_t = false)]
+    sparsity_experiment: bool,
 
-    for (monomial_id, monomial_sum) in generate_monomial_sums(&database.entries, dimension, max_degree) {
-        let (comm, proof) = pedersen::commit(&mut state.rng, &monomial_sum, &state.pedersen_pp);
-        database.commitments.insert(monomial_id, (monomial_sum, comm, proof));
-        m.commitment_map.insert(monomial_id, comm);
-    }
+    // (optional) spot-check the honest commitment phase: prove only the fraction of entries the verifier audits (must match the verifier)
+    #[arg(long, value_parser = audit::parse_rate)]
+    spot_check: Option<f64>,
 
-    write_to_stream(
-        stream, &serde_json::to_vec(&m).unwrap()
-    );
-}
+    // (optional) certify the dishonest commitment phase by cut-and-choose with this many encodings per entry (must match the verifier)
+    #[arg(long, value_parser = cut_and_choose::parse_encodings)]
+    cut_and_choose: Option<u32>,
 
-/// Tree of product sigma proofs for each monomial, to avoid recomputing partial product proofs for each different monomial
-pub struct MonomialProverTreeNode {
-    pub commitment: Option<(Scalar, RistrettoPoint, Scalar)>,
-    pub product_sigma_prover: Option<product_sigma::Prover>,
-    pub children: Vec<Box<MonomialProverTreeNode>>,
-}
+    // (optional) encrypt query answers to this analyst public key, in hex (must match the verifier)
+    #[arg(long, value_parser = analyst::parse_public_key)]
+    analyst_key: Option<analyst::PublicKey>,
 
-/// Generate a tree of partial monomial sigma proofs for the dishonest commitment phase
-fn gen_monomial_tree(state: &mut ProverState, entry_bit_commitments: &Vec<(Scalar, RistrettoPoint, Scalar)>,
-                     curr_nodes: (&mut MonomialProverTreeNode, &mut MonomialCommitmentTreeNode),
-                     curr_idx: isize, curr_degree: usize, dimension: usize, max_degree: usize) {
-
-    // base case: if we get to the data dimension size or generate a monomial of max degree, we're done 
-    if curr_idx == dimension as isize || curr_degree == max_degree {
-        return;
-    } 
-
-    let (curr_prover_node, curr_comm_node) = curr_nodes;
-    
-    // recursive cases: add indices greater than curr_idx to the current monomial and recurse
-    for i in (curr_idx + 1) as usize..dimension {
-        let mut prover_child = MonomialProverTreeNode {
-            commitment: None,
-            product_sigma_prover: None,
-            children: Vec::new(),
-        };
+    // (optional) commit under exponential ElGamal for this analyst public key, in hex, revealing only the query handles (must match the verifier)
+    #[arg(long, value_parser = analyst::parse_public_key, conflicts_with = "analyst_key")]
+    elgamal_key: Option<analyst::PublicKey>,
 
-        let mut comm_child = MonomialCommitmentTreeNode {
-            commitment: None,
-            product_sigma_commitment: None,
-            children: Vec::new(),
-        };
+    // (optional) number of verifiers to serve one after another, all auditing the same committed database
+    #[arg(long, default_value_t = 1)]
+    auditors: u32,
 
-        match curr_prover_node.commitment {
-            None => {
-                prover_child.commitment = Some(entry_bit_commitments[i]);
-                comm_child.commitment = Some(entry_bit_commitments[i].1);
-            },
-            Some((m_1, c_1, r_1)) => {
-                let (m_2, c_2, r_2) = entry_bit_commitments[i];
-                let m_3 = m_1 * m_2;
-                let (c_3, r_3) = pedersen::commit(&mut state.rng, &m_3, &state.pedersen_pp);
+    // (optional) keep listening once a session ends, serving verifiers against the same committed database, as --auditors does, until the process is stopped, and going on past a session that fails
+    #[arg(long, default_value_t = false, conflicts_with = "auditors")]
+    daemon: bool,
 
-                let (prover, commitment) = product_sigma::commit(&mut state.rng, &state.pedersen_pp, (m_1, c_1, r_1), (m_2, c_2, r_2), (m_3, c_3, r_3));
+    // (optional) serve up to this many sessions at once after the first, each on a thread of its own and with its own noise, sharing the database the first one committed (requires the certify command or --resumable)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 1)]
+    concurrent: u32,
 
-                prover_child.commitment = Some((m_3, c_3, r_3));
-                prover_child.product_sigma_prover = Some(prover);
+    // (optional) add this many random rows in an update phase after the commitment phases, proving only the new rows (requires --update on the verifier)
+    #[arg(long)]
+    add_rows: Option<u32>,
 
-                comm_child.commitment = Some(c_3);
-                comm_child.product_sigma_commitment = Some(commitment);
-            }
-        };
+    // (optional) remove this many random rows in the update phase, subtracting their committed contributions (requires --update on the verifier)
+    #[arg(long)]
+    remove_rows: Option<u32>,
 
-        gen_monomial_tree(state, entry_bit_commitments, (&mut prover_child, &mut comm_child), i as isize, curr_degree + 1, dimension, max_degree);
+    // (optional) length of the budget epochs, in seconds or with an s, m, h, or d suffix; commitments expire and the budget resets at each epoch boundary
+    #[arg(long, value_parser = epoch::parse_length)]
+    epoch_length: Option<Duration>,
 
-        curr_prover_node.children.push(Box::new(prover_child));
-        curr_comm_node.children.push(Box::new(comm_child));
-    }
-}
+    // (optional) total epsilon each epoch can spend, at epsilon per query; sessions that would exceed it are refused
+    #[arg(long, value_parser = epoch::parse_budget)]
+    epoch_budget: Option<f64>,
 
-/// Given a matching prover sigma protocol state and challenge tree, generate the response tree recursively by advancing the sigma protocol at each node
-fn gen_response_tree(state: &mut ProverState, prover_node: &mut MonomialProverTreeNode, challenge_node: &MonomialChallengeTreeNode, response_node: &mut MonomialResponseTreeNode) {
-    match &challenge_node.product_sigma_challenge {
-        None => {
-            response_node.product_sigma_response = None;
-        },
-        Some(c) => {
-            response_node.product_sigma_response = Some(product_sigma::response(&mut prover_node.product_sigma_prover.as_mut().unwrap(), &c));
-        }
-    };
+    // (optional) total delta each epoch can spend, at the session's delta per query; sessions that would exceed it are refused
+    #[arg(long, value_parser = budget::parse_delta)]
+    epoch_delta: Option<f64>,
 
-    for (i, prover_child ) in prover_node.children.iter_mut().enumerate() {
-        let mut response_child = MonomialResponseTreeNode {
-            product_sigma_response: None,
-            children: Vec::new(),
-        };
+    // composition of the queries' privacy losses against the epoch budget: basic, summing them, or advanced:SLACK, trading the slack delta for an epsilon growing with the square root of the queries
+    #[arg(long, value_parser = budget::parse_composition, default_value = "basic")]
+    composition: Composition,
 
-        gen_response_tree(state, prover_child, &challenge_node.children[i], &mut response_child);
-        response_node.children.push(Box::new(response_child));
-    }
-}
+    // (optional) epsilon each analyst can spend of its own in each epoch, on top of the epoch budget they all share; verifiers must then authenticate with a key bound to their analyst by --authorized-key ANALYST=KEY and name that analyst, and an analyst's sessions past it are refused
+    #[arg(long, value_parser = epoch::parse_budget, requires = "authorized_key")]
+    analyst_budget: Option<f64>,
 
-/// Calculates the number of nodes in a monomial commitment tree
-fn _count_tree(node: &MonomialProverTreeNode) -> usize {
-    let mut count = 1;
-    for child in &node.children {
-        count += _count_tree(child);
-    }
-    count
-}
+    // (optional) keep what each dataset's current epoch has spent, shared and by analyst, in this JSON file, loaded at startup and saved as each session is charged, so a restart does not reset the budgets
+    #[arg(long)]
+    budget_state: Option<PathBuf>,
 
-/// Based on the monomial commitment tree, extract the final commitment for each monomial.
-fn extract_monomials<T: PrimInt + Hash>(prover_node: &MonomialProverTreeNode, curr_tag: T, dimension: u32, element_commitment_map: &mut HashMap<T, (Scalar, RistrettoPoint, Scalar)>) {
-    match prover_node.commitment {
-        None => {},
-        Some((m, c, r)) => {
-            element_commitment_map.insert(curr_tag, (m, c, r));
-        }
-    }
+    // budgets spent before the prover last stopped, from --budget-state, which main loads
+    #[arg(skip)]
+    ledgers: BTreeMap<String, Ledger>,
 
-    let offset = T::zero().count_zeros() - curr_tag.leading_zeros();
-    for (i, prover_child) in prover_node.children.iter().enumerate() {
-        let new_tag = curr_tag | (T::one() << (offset as usize + i));
-        extract_monomials(prover_child, new_tag, dimension, element_commitment_map);
-    }
-}
+    // (optional) load the database from this CSV file, whose header names its columns, instead of generating random entries (requires --column or --schema)
+    #[arg(long, group = "source", requires = "encoding", conflicts_with = "dataset")]
+    csv: Option<PathBuf>,
 
-/// Based on the forest of monomial trees, generate the final commitment for each monomial and aggregate them into a map by monomial.
-fn gen_monomial_map<T: PrimInt + Hash>(prover_trees: &Vec<MonomialProverTreeNode>, dimension: u32, commitment_map: &mut HashMap<T, (Scalar, RistrettoPoint, Scalar)>) {
+    // (optional) load the database from this JSON-lines file, each line a record of named fields, instead of generating random entries (requires --column or --schema)
+    #[arg(long, group = "source", requires = "encoding", conflicts_with = "dataset")]
+    jsonl: Option<PathBuf>,
 
-    for prover_root in prover_trees {
-        let mut element_commitment_map: HashMap<T, (Scalar, RistrettoPoint, Scalar)> = HashMap::new();
-        extract_monomials(prover_root, T::zero(), dimension, &mut element_commitment_map);
-        //eprintln!("  element commitment map: {:?}", element_commitment_map.len());
+    // (optional) load the database from this Parquet file, e.g. as a Spark or pandas pipeline writes it, instead of generating random entries (requires --column or --schema)
+    #[cfg(feature = "parquet")]
+    #[arg(long, group = "source", requires = "encoding", conflicts_with = "dataset")]
+    parquet: Option<PathBuf>,
 
-        for (k, v) in element_commitment_map {
-            if commitment_map.contains_key(&k) {
-                let (m, c, r) = commitment_map.get(&k).unwrap();
-                commitment_map.insert(k, (m + v.0, c + v.1, r + v.2));
-            } else {
-                commitment_map.insert(k, v);
-            }
-        }
-    }
-}
+    // (optional) load only this many rows from the start of the Parquet file
+    #[cfg(feature = "parquet")]
+    #[arg(long, requires = "parquet")]
+    max_rows: Option<usize>,
 
-/// Dishonest commitment phase: compute the result of a set of all bit and product sigma protocols for database entries between us and the verifier, then aggregate into a <monomial -> commitment> map.
-fn prover_dishonest_commitment_phase<T: PrimInt + Hash + Serialize>(state: &mut ProverState, stream: &mut TcpStream, database: &mut Data<T>, dimension: u32, max_degree: u32) -> bool {
+    // (optional, repeatable) encode this column of the file as the next attribute of the schema, in order from bit 0, as its name then =bool, =one-hot:CATEGORY,..., =buckets:BOUNDARY,..., or =binary:BITS, e.g. region=one-hot:north,south
+    #[arg(long, value_parser = schema::parse_attribute, group = "encoding", requires = "source")]
+    column: Vec<schema::Attribute>,
 
-    // Per-database entry bit sigma protocols
-    let mut db_bit_sigma_provers: Vec<Vec<bit_sigma::Prover>> = Vec::new();
-    // Forest of monomial trees per-database element
-    let mut monomial_prover_trees: Vec<MonomialProverTreeNode> = Vec::new();
-    // If d=1, we skip product proofs and use the commitments directly
-    let mut db_entry_commitments: Vec<(Scalar, RistrettoPoint, Scalar)> = Vec::new();
+    // (optional) encode the columns of the file as the attributes of this JSON schema file, in place of --column (should match the verifier's)
+    #[arg(long, group = "encoding", requires = "source")]
+    schema: Option<PathBuf>,
 
-    for (_i, entry) in database.entries.iter().enumerate() {
-        //eprintln!("  committing to entry   {}/{}", _i+1, database.entries.len());
+    // schema of the database file, from --schema or --column, which main loads
+    #[arg(skip)]
+    database_schema: Schema,
 
-        let mut entry_commitments: Vec<(Scalar, RistrettoPoint, Scalar)> = Vec::new();
-        let mut entry_sigma_provers: Vec<bit_sigma::Prover> = Vec::new();
-        let mut entry_sigma_commitments : Vec<bit_sigma::Commitment> = Vec::new();
+    // (optional) refuse the queries breaking the policy in this JSON file, of a max_degree, max_sparsity, max_coefficient, and attributes of the schema queries may read, each optional, e.g. {"max_degree": 2, "attributes": ["smoker"]}
+    #[arg(long)]
+    policy: Option<PathBuf>,
 
-        let bit_sigma_start = Instant::now();
-        for i in 0..dimension {
-            let mask = T::one() << (i as usize);
+    // query policy from --policy, which main loads against the schema
+    #[arg(skip)]
+    query_policy: Policy,
 
-            let bit: u32 = if entry.bitand(mask) == mask { 1 } else { 0 };
-            let (comm, proof) = pedersen::commit(&mut state.rng, &Scalar::from(bit), &state.pedersen_pp);
-            let (prover, commitment) = bit_sigma::commit(&mut state.rng, &state.pedersen_pp, bit, comm, proof);
+    // (optional) host this named dataset, as the name then comma separated overrides among db-size, dimension, max-degree, epsilon, and epoch-budget, e.g. census,db-size=1000; repeat for more datasets (verifiers must then name theirs)
+    #[arg(long, value_parser = dataset::parse_spec)]
+    dataset: Vec<DatasetSpec>,
 
-            entry_commitments.push((Scalar::from(bit), comm, proof));
-            entry_sigma_provers.push(prover);
-            entry_sigma_commitments.push(commitment);
-        }    
-        let _bit_sigma_duration = bit_sigma_start.elapsed();
+    // (optional) persona to run as: data-owner, or dealer to deal the parameters and noise without updating or answering queries
+    #[arg(long, value_parser = profile::parse_profile, default_value = "data-owner")]
+    profile: Profile,
 
-        if dimension == 1 {
-            db_entry_commitments.push(entry_commitments[0]);
-        }
-        db_bit_sigma_provers.push(entry_sigma_provers);
+    // (optional) number of verifiers jointly flipping the noise coins, which are unbiased if any one of them is honest; the session's verifier connects first, and the rest join as co-verifiers for the randomness phase (must match the verifiers)
+    #[arg(long, value_parser = joint::parse_verifiers, default_value_t = 1)]
+    verifiers: u32,
 
-        // send the entry bit sigma commitments to the verifier
-        write_to_stream(
-            stream, &serde_json::to_vec(&BitSigmaCommitmentMessage {
-                commitments: entry_sigma_commitments
-            }).unwrap()
-        );
+    // (optional) flip and challenge the noise coins with this file of drand beacon rounds instead of the verifier's own coins, in a single round trip (must match the verifier)
+    #[arg(long, value_parser = beacon::parse_beacon, conflicts_with = "verifiers")]
+    beacon: Option<Beacon>,
 
-        if dimension == 1 {
-            continue;
-        }
+    // (optional) keep the first session's commitments and noise, and answer the later sessions' queries on them without rerunning the commitment and randomness phases (their verifiers must pass --resume)
+    #[arg(long, default_value_t = false)]
+    resumable: bool,
 
-        let mut entry_prover_root = MonomialProverTreeNode {
-            commitment: None,
-            product_sigma_prover: None,
-            children: Vec::new(),
-        };
+    // (optional) abort the protocol, telling the verifier why, if a phase runs past this limit, as the phase and a duration, e.g. dishonest-commitment=2h; repeat for more phases
+    #[arg(long, value_parser = deadline::parse_deadline)]
+    deadline: Vec<(Phase, Duration)>,
 
-        let mut entry_commitment_root = MonomialCommitmentTreeNode {
-            commitment: None,
-            product_sigma_commitment: None,
-            children: Vec::new(),
-        };
+    // (optional) when the connection drops during the randomness phase, wait for the verifier to reconnect and resume from the last checkpoint of the coins flipped, up to this many times (must match the verifier)
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["beacon", "verifiers"])]
+    reconnect: u32,
 
-        let monomial_tree_start = Instant::now();
-        gen_monomial_tree(state, &entry_commitments, (&mut entry_prover_root, &mut entry_commitment_root), -1, 0, dimension as usize, max_degree as usize);
-        let _monomial_tree_duration = monomial_tree_start.elapsed();
+    // (optional) commit to a sketch of this attribute, as the comma separated positions of its bits in an entry, e.g. 0,1,2, and certify it after the dishonest commitment phase for distinct-count queries (must match the verifier)
+    #[arg(long, value_parser = sketch::parse_attribute, conflicts_with_all = ["skip_dishonest", "cut_and_choose"])]
+    distinct: Option<Attribute>,
 
-        monomial_prover_trees.push(entry_prover_root);
+    // (optional) give each query its own noise, flipping only the first query's coins in the randomness phase and each later query's in the query phase just before it is answered, so queries start without waiting for every query's coins (must match the verifier)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["beacon", "resumable"])]
+    progressive: bool,
 
-        // send entry monomial tree to the prover
-        write_to_stream(
-            stream, &serde_json::to_vec(&entry_commitment_root).unwrap()
-        )
-    }
+    // (optional) largest message, in MiB, accepted from a verifier, past which the session fails
+    #[arg(long, default_value_t = transport::DEFAULT_MAX_FRAME_SIZE >> 20)]
+    max_message_size: usize,
 
-    let mut response_messages = Vec::new();
+    // (optional) append every frame sent and received to this file, one JSON line each with its direction, session, phase, and time, for debugging, reproducing, or auditing the sessions
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    // (optional) serve the verifiers over TLS with this PEM certificate chain, whose first certificate must name the address they dial (requires --tls-key)
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    // (optional) PEM private key of the TLS certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    // (optional) require each verifier to present a TLS certificate signed by the CA in this PEM file (requires --tls-cert)
+    #[arg(long, requires = "tls_cert")]
+    tls_ca: Option<PathBuf>,
+
+    // (optional) only serve verifiers that answer a challenge at the start of setup with this pre-shared key, 32 bytes in hex, before running any expensive phase for them
+    #[arg(long, value_parser = auth::parse_preshared_key)]
+    auth_psk: Option<PresharedKey>,
+
+    // (optional) only serve verifiers that answer a challenge at the start of setup with a signature under this verifying key, in hex, e.g. from `analyst keygen --signing`; as ANALYST=KEY, the key only opens sessions charged to that analyst; repeat to authorize more verifiers
+    #[arg(long, value_parser = auth::parse_authorized_key)]
+    authorized_key: Vec<AuthorizedKey>,
+
+    // (optional) serve the verifiers over HTTP rather than framed TCP, each opening a session and posting its messages to it as JSON
+    #[cfg(feature = "http")]
+    #[arg(long, default_value_t = false, conflicts_with = "tls_cert")]
+    http: bool,
+
+    // (optional) serve the verifiers over gRPC rather than framed TCP, each session one bidirectional stream of frames (the verifiers must pass --grpc too)
+    #[cfg(feature = "grpc")]
+    #[arg(long, default_value_t = false, conflicts_with = "tls_cert")]
+    #[cfg_attr(feature = "http", arg(conflicts_with = "http"))]
+    grpc: bool,
+
+    // (optional) serve the verifiers over QUIC rather than TCP, sending each frame on a stream of its own so back-to-back frames travel in parallel (requires --tls-cert; the verifiers must pass --quic too)
+    #[cfg(feature = "quic")]
+    #[arg(long, default_value_t = false, requires = "tls_cert")]
+    #[cfg_attr(feature = "grpc", arg(conflicts_with = "grpc"))]
+    quic: bool,
+
+    // (optional) port to listen on for the verifier connection
+    #[arg(long, default_value = PROVER_PORT)]
+    port: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    for i in 0..database.entries.len() {
-        //eprintln!("  responding to entry {}/{}", i+1, database.entries.len());
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Certify the commitments to an auditor in the first session, which ends after the commitment phases, and write
+    /// the certificate; the remaining sessions' analysts start from it instead of the commitment phases
+    Certify {
+        // file to write the certificate to
+        #[arg(long)]
+        out: PathBuf,
+
+        // (optional) also sign the certificate with this prover signing key, in hex
+        #[arg(long, value_parser = signature::parse_signing_key)]
+        signing_key: Option<SigningKey>,
+    },
+}
 
-        let challenge_m: BitSigmaChallengeMessage = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
+/// One of the databases the prover hosts, with its own parameters, commitments, and privacy budget
+struct Dataset<T> {
+    name: String,
+    dimension: u32,
+    max_degree: u32,
+    epsilon: f32,
+    state: ProverState,
+    /// The database, shared with the concurrent sessions once the first session has committed it
+    database: Arc<Data<T>>,
+    /// Budget epochs, whose budget concurrent sessions draw on together
+    epochs: Arc<Mutex<Epochs>>,
+    /// Rows to add and entries to remove in the next update phase; only the first session's update changes anything
+    pending_update: (Vec<T>, Vec<u32>),
+}
 
-        let mut entry_responses: Vec<bit_sigma::Response> = Vec::new();
-        for (bit_idx, m) in challenge_m.challenges.iter().enumerate() {
-            let response = bit_sigma::response(&mut db_bit_sigma_provers[i][bit_idx], m);
-            entry_responses.push(response);
+impl<T: Entry> Dataset<T> {
+    /// Generate the dataset `spec` describes, taking any parameter it leaves unset from the prover's options
+    fn new(spec: &DatasetSpec, args: &Args) -> Dataset<T> {
+        let mut state = prover_setup();
+        let db_size = args.db_size(spec);
+        let database = match args.load_database() {
+            // already checked by main
+            Some(database) => database.expect("database file checked by main"),
+            None => Data::new(&mut state.rng, db_size),
+        };
+        let mut epochs = Epochs::new(args.epoch_length, Accountant::new(args.composition, spec.epoch_budget.or(args.epoch_budget), args.epoch_delta));
+        if let Some(budget) = args.analyst_budget {
+            epochs = epochs.with_analyst_budget(Accountant::new(args.composition, Some(budget), None));
         }
-
-        let resp_mesg = serde_json::to_vec(&BitSigmaResponseMessage {
-            responses: entry_responses
-        }).unwrap();
-        response_messages.push(resp_mesg);
-
-        if dimension == 1 {
-            continue;
+        if let Some(ledger) = args.ledgers.get(&spec.name) {
+            epochs.restore(ledger);
         }
 
-        let monomial_challenge_root: MonomialChallengeTreeNode = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
-
-        let mut response_root = MonomialResponseTreeNode {
-            product_sigma_response: None,
-            children: Vec::new(),
-        };
-        gen_response_tree(state, &mut monomial_prover_trees[i], &monomial_challenge_root, &mut response_root);
-
-        let resp_root = serde_json::to_vec(&response_root).unwrap();
-        response_messages.push(resp_root);
+        let added = std::mem::take(&mut Data::<T>::new(&mut state.rng, args.add_rows.unwrap_or(0)).entries);
+        let mut removed: Vec<u32> = index::sample(&mut state.rng, db_size as usize, args.remove_rows.unwrap_or(0) as usize)
+            .into_iter().map(|i| i as u32).collect();
+        removed.sort();
+
+        Dataset {
+            name: spec.name.clone(),
+            dimension: spec.dimension.unwrap_or(args.dimension),
+            max_degree: spec.max_degree.unwrap_or(args.max_degree),
+            epsilon: spec.epsilon.unwrap_or(args.epsilon),
+            state,
+            database: Arc::new(database),
+            epochs: Arc::new(Mutex::new(epochs)),
+            pending_update: (added, removed),
+        }
     }
 
-    for m in response_messages {
-        write_to_stream(stream, &m);
+    /// The dataset as a concurrent session sees it, sharing the committed database and the budget, with a fork of the
+    /// state of its own
+    fn fork(&self) -> Dataset<T> {
+        Dataset {
+            name: self.name.clone(),
+            dimension: self.dimension,
+            max_degree: self.max_degree,
+            epsilon: self.epsilon,
+            state: prover_fork(&self.state),
+            database: self.database.clone(),
+            epochs: self.epochs.clone(),
+            pending_update: (Vec::new(), Vec::new()),
+        }
     }
 
-    let check_m: VerifierCheckMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
+    /// Current budget epoch
+    fn epoch(&self) -> u64 {
+        self.epochs.lock().unwrap().current()
+    }
 
-    if !check_m.success {
-        eprintln!("ERROR: Commitment phase failed");
-        return false;
+    /// Record the snapshot the last commitment phase certified, which queries must then be for
+    fn take_snapshot(&mut self) {
+        self.state.snapshot = self.database.snapshot();
+        eprintln!("  snapshot {}", self.state.snapshot);
     }
-    eprintln!("  check successful!");
 
-    if dimension == 1 {
-        let mut total = (Scalar::from(0 as u32), RistrettoPoint::default(), Scalar::from(0 as u32));
+    /// Current number of database entries, which the update phase can change from the configured size
+    fn db_size(&self) -> u32 {
+        self.database.entries.len() as u32
+    }
 
-        for c in db_entry_commitments {
-            total.0 += c.0;
-            total.1 += c.1;
-            total.2 += c.2;
+    /// Parameters the verifiers of the next session must have been given
+    fn params(&self, args: &Args) -> SessionParams {
+        SessionParams {
+            db_size: self.db_size(), dimension: self.dimension, max_degree: self.max_degree, epsilon: self.epsilon, delta: args.delta,
+            entry_width: max_dimension::<T>(), calibration: args.calibration, mechanism: args.noise, sensitivity: args.sensitivity,
         }
-        database.commitments.insert(T::one(), total);
-    } else {
-        gen_monomial_map(&monomial_prover_trees, dimension, &mut database.commitments);
     }
 
-    true
+    /// Expire the previous epoch's commitments as `epoch` starts, so the next commitment phase commits afresh
+    fn start_epoch(&mut self, epoch: u64) {
+        self.state.entry_cache.clear();
+        self.state.sketch.clear();
+        committing(&mut self.database).commitments.clear();
+        self.state.snapshot = SnapshotId::default();
+        eprintln!("  epoch {}", epoch);
+    }
 }
 
-//
-// -- RANDOMNESS PHASE --
-//
-
-/// Prover randomness phase: generate a random bit and commit to it. Send the commitment to the verifier.
-fn prover_randomness_phase_comm(state: &mut ProverState, stream: &mut TcpStream) {
+/// The database for a session to commit to or update; concurrent sessions only share it once it is committed, and
+/// never commit themselves
+fn committing<T>(database: &mut Arc<Data<T>>) -> &mut Data<T> {
+    Arc::get_mut(database).expect("concurrent sessions do not commit")
+}
 
-    let dealer_b: u32 = state.rng.gen_range(0..2);
-    let (dealer_b_comm, dealer_b_proof) =
-        pedersen::commit(&mut state.rng, &Scalar::from(dealer_b), &state.pedersen_pp);
+/// Datasets the prover is configured to host, or the single default dataset described by its own options
+impl Args {
+    /// Number of elements in the dataset `spec`, unless it sets its own the prover's, which main sets to the rows of a
+    /// database file
+    fn db_size(&self, spec: &DatasetSpec) -> u32 {
+        spec.db_size.or(self.db_size).expect("database size required without a database file")
+    }
 
-    let _start = Instant::now();
-    let (sigma_prover, sigma_commitment) = 
-        bit_sigma::commit(&mut state.rng, &state.pedersen_pp, dealer_b, dealer_b_comm, dealer_b_proof);
-    state.randomness_sigma_duration += _start.elapsed();
+    /// Verifiers the prover serves, every one unless it has a pre-shared key or authorized keys
+    fn authorized(&self) -> Authorized {
+        Authorized { preshared: self.auth_psk.clone(), keys: self.authorized_key.iter().map(|k| k.key).collect() }
+    }
 
-    state.sigma_prover = sigma_prover;
-    state.dealer_b = dealer_b;
-    state.dealer_b_comm = dealer_b_comm;
-    state.dealer_b_proof = dealer_b_proof;
+    /// Largest frame accepted from a verifier, in bytes
+    fn max_frame_size(&self) -> usize {
+        self.max_message_size.saturating_mul(1 << 20)
+    }
 
-    write_to_stream(
-        stream, &serde_json::to_vec(&ProverRandomnessComm {
-            commitment: sigma_commitment
-        }).unwrap()
-    );
+    /// Database loaded from the CSV, JSON-lines, or Parquet file given, if any, with entries of type `T`
+    fn load_database<T: Entry>(&self) -> Option<std::result::Result<Data<T>, String>> {
+        if let Some(path) = &self.csv {
+            return Some(Data::new_from_csv(path, &self.database_schema));
+        }
+        if let Some(path) = &self.jsonl {
+            return Some(Data::new_from_jsonl(path, &self.database_schema));
+        }
+        #[cfg(feature = "parquet")]
+        if let Some(path) = &self.parquet {
+            return Some(Data::new_from_parquet(path, &self.database_schema, self.max_rows));
+        }
+        None
+    }
 }
 
-/// Prover randomness phase: generate a response to the verifier's challenge. Send the response to the verifier.
-fn prover_randomness_phase_response(state: &mut ProverState, stream: &mut TcpStream) -> bool {
-
-    let final_commitment: RistrettoPoint;
-    let final_proof: Scalar;
-    let final_b: u32;
-
-    let m: VerifierRandomnessChallenge = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-
-    if m.player_b == 0 {
-        final_commitment = state.dealer_b_comm;
-        final_proof = state.dealer_b_proof;
-        final_b = state.dealer_b;
+fn dataset_specs(args: &Args) -> Vec<DatasetSpec> {
+    if args.dataset.is_empty() {
+        vec![DatasetSpec { name: DEFAULT_DATASET.to_string(), ..Default::default() }]
     } else {
-        final_commitment = state.C1 + state.dealer_b_comm.neg();
-        final_proof = state.CPROOF + state.dealer_b_proof.neg();
-
-        final_b = 1 - state.dealer_b;
+        args.dataset.clone()
     }
+}
 
-    state.final_b = final_b;
-    state.final_proof = final_proof;
-
-    let _start = Instant::now();
-    let sigma_response = bit_sigma::response(&mut state.sigma_prover, &m.sigma_challenge);
-    state.randomness_sigma_duration += _start.elapsed();
+/// Connection to a verifier, over TLS if the prover serves a certificate
+type Connection = Box<dyn Transport + Send>;
 
-    write_to_stream(
-        stream, &serde_json::to_vec(&ProverRandomnessResponse {
-            final_commitment,
-            sigma_response
-        }).unwrap()
-    );
+/// TLS configuration from the prover's options, if it serves a certificate
+fn tls_config(args: &Args) -> certified_dp::Result<Option<Arc<ServerConfig>>> {
+    match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => tls::server_config(cert, key, args.tls_ca.as_deref()).map(Some),
+        _ => Ok(None),
+    }
+}
 
-    let result: VerifierCheckMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-    result.success
+/// Where the prover accepts its verifiers' connections
+enum Listener {
+    Tcp(NetListener),
+    #[cfg(feature = "http")]
+    Http(HttpListener),
+    #[cfg(feature = "grpc")]
+    Grpc(GrpcListener),
+    #[cfg(feature = "quic")]
+    Quic(QuicListener),
 }
 
-/// Prover randomness phase: adjust the randomness bit sum and proof based on the verifier's challenge.
-fn prover_randomness_phase_adjust(state: &mut ProverState, db_size: u32, epsilon: f32, delta: Option<f32>) {
-    let adjustment_factor = Scalar::from((get_n(db_size, epsilon, delta)/2) as u32);
-    state.randomness_bit_sum -= adjustment_factor;
-    state.randomness_bit_proof -= state.CPROOF;
+/// Prover side of the protocol, driven phase by phase by `ProtocolDriver`, over entries of type `T`
+struct Prover<T> {
+    args: Arc<Args>,
+    tls: Option<Arc<ServerConfig>>,
+    datasets: Vec<Dataset<T>>,
+    /// Index of the dataset the current session audits
+    current: usize,
+    listener: Option<Listener>,
+    /// Connections of the current session's co-verifiers, which only take part in the randomness phase
+    co_verifiers: Vec<Framed<Connection>>,
+    /// Whether the certificate has been written, so sessions start from the certified commitments
+    certified: bool,
+    /// Whether a session has completed that the later ones resume, with `--resumable`
+    resumed: bool,
+    /// Whether a query has been answered on the current noise, so that with `--progressive` the next one flips its own
+    noise_spent: bool,
+    /// Analyst the current session's queries are charged to, with `--analyst-budget`
+    analyst: String,
 }
 
-//
-// -- QUERYING PHASE --
-//
+impl<T: Entry> Prover<T> {
+    /// Dataset the current session audits
+    fn dataset(&self) -> &Dataset<T> {
+        &self.datasets[self.current]
+    }
 
-/// Prover answers a query from the verifier, based on the coefficients of the monomials in the query. Send the answer to the verifier.
-fn prover_answer_query<T>(state: &mut ProverState, database: &mut Data<T>, stream: &mut TcpStream)
-where T: Eq + Hash + Display + DeserializeOwned
-{
-    let query_m: QueryMessage<T> = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
+    /// Save what every dataset's current epoch has spent to `path`; concurrent sessions save one at a time, so the
+    /// ledgers saved last are never older than those they replace
+    fn save_budgets(&self, path: &Path) -> Result<()> {
+        static SAVING: Mutex<()> = Mutex::new(());
+        let _saving = SAVING.lock().unwrap();
+        let ledgers = self.datasets.iter().map(|d| (d.name.clone(), d.epochs.lock().unwrap().ledger())).collect();
+        epoch::save_ledgers(path, &ledgers).map_err(|e| Error::Config(format!("Budget state {}", e)))
+    }
 
-    let mut query_answer = state.randomness_bit_sum;
-    let mut query_proof = state.randomness_bit_proof;
+    /// Accept the current session's co-verifiers, framing their messages as on the verifier's connection `verifier`, and
+    /// agree keys with each of its own, which authenticate its frames apart from the other verifiers', before sending it
+    /// the session's setup with the ID of the session it joins
+    fn accept_co_verifiers(&mut self, verifier: &Framed<Connection>) -> Result<()> {
+        let d = &self.datasets[self.current];
+        let session = verifier.session_id();
+        let setup_m = SetupMessage { session, ..prover_setup_message(&d.state, &d.params(&self.args), d.epoch()) };
+        let authorized = self.args.authorized();
+        while self.co_verifiers.len() + 1 < self.args.verifiers as usize {
+            // a co-verifier learns the session's ID from its setup, which is sent without it
+            let mut stream = Framed::new(self.accept()?, self.args.max_frame_size());
+            stream.set_wire_format(verifier.wire_format(), verifier.compression());
+            mac::prover_agree(&mut OsRng, &mut stream, authorized.preshared.as_ref())?;
+            if authorized.required() {
+                auth::prover_authenticate(&mut OsRng, &mut stream, &authorized)?;
+            }
+            write_message(&mut stream, &setup_m)?;
+            stream.set_session_id(session);
+            self.co_verifiers.push(stream);
+        }
+        Ok(())
+    }
 
-    for monomial_id in query_m.coefficients.keys() {
-        if !database.commitments.contains_key(monomial_id) {
-            eprintln!("ERROR: Monomial ID {} not found in monomial map", monomial_id);
-            return;
+    /// Accept the next connection, over TLS if the prover serves a certificate, or the next session opened over HTTP,
+    /// gRPC, or QUIC
+    fn accept(&self) -> io::Result<Connection> {
+        match self.listener.as_ref().unwrap() {
+            Listener::Tcp(listener) => Ok(Box::new(listener.accept()?)),
+            #[cfg(feature = "http")]
+            Listener::Http(listener) => Ok(Box::new(listener.accept()?)),
+            #[cfg(feature = "grpc")]
+            Listener::Grpc(listener) => Ok(Box::new(listener.accept()?)),
+            #[cfg(feature = "quic")]
+            Listener::Quic(listener) => Ok(Box::new(listener.accept()?)),
         }
+    }
 
-        let (monomial_sum, _monomial_comm, monomial_proof) = database.commitments.get(monomial_id).unwrap();
-        let monomial_coefficient = query_m.coefficients.get(monomial_id).unwrap();
+    /// Flip the noise of the current dataset with the verifier (and co-verifiers, or the beacon), centered on zero
+    fn flip_noise(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let d = &mut self.datasets[self.current];
+        d.state.noise_flips.clear();
 
-        let _start = Instant::now();
-        query_answer += monomial_coefficient * monomial_sum;
-        query_proof += monomial_coefficient * monomial_proof;
-        state.coin_flipping_and_agg_duration += _start.elapsed();
+        let params = d.params(&self.args);
+        let n = params.coins();
+        match &self.args.beacon {
+            Some(beacon) => prover_beacon_randomness_phase(&mut d.state, stream, beacon, n)?,
+            None if self.args.verifiers == 1 => self.flip_resumable(stream, n)?,
+            None => {
+                // the co-verifiers only stay connected for this phase
+                let mut co_verifiers = std::mem::take(&mut self.co_verifiers);
+                let mut streams: Vec<&mut Framed<Connection>> = std::iter::once(&mut *stream).chain(co_verifiers.iter_mut()).collect();
+                prover_interactive_randomness_phase(&mut d.state, &mut streams, n)?
+            },
+        }
+        let d = &mut self.datasets[self.current];
+        prover_randomness_phase_combine(&mut d.state, stream, &params)?;
+        if self.args.verifiers > 1 || self.args.beacon.is_some() {
+            let noise = pedersen::commit_with_r(&d.state.randomness_bit_sum, &d.state.randomness_bit_proof, &d.state.pedersen_pp);
+            eprintln!("  noise commitment {}", joint::fingerprint(&noise));
+        }
+        self.noise_spent = false;
+        Ok(())
     }
 
-    write_to_stream(
-        stream,
-        &serde_json::to_vec(&QueryAnswerMessage {
-            answer: query_answer,
-            proof: query_proof
-        }).unwrap()
-    );
+    /// Flip the `n` noise coins of the current dataset with the verifier alone, resuming from the last checkpoint over
+    /// the verifier's next connection each time the connection drops, up to `--reconnect` times
+    fn flip_resumable(&mut self, stream: &mut Framed<Connection>, n: u32) -> Result<()> {
+        let mut outcome = prover_batched_randomness_phase(&mut self.datasets[self.current].state, stream, n);
+        for attempt in 1..=self.args.reconnect {
+            let Err(Error::Io(e)) = &outcome else {
+                break;
+            };
+            eprintln!("  connection lost ({}), waiting for the verifier to reconnect ({}/{})", e, attempt, self.args.reconnect);
+            stream.reconnect(self.accept()?);
+            outcome = prover_resume_randomness_phase(&mut self.datasets[self.current].state, stream, n);
+        }
+        outcome
+    }
 }
 
-/// Prover synchronizes with the verifier to ensure both parties are ready to star the protocol.
-fn synchronize_verifier(stream: &mut TcpStream) {
-    let _verifier_ready: ReadyMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-    write_to_stream(
-        stream, &serde_json::to_vec(&ReadyMessage { ready: true }).unwrap()
-    );
-}
+impl<T: Entry> Role for Prover<T> {
+    type Config = Args;
+    type Stream = Connection;
 
-/// Main function for the prover executable, parsing arguments and executing the protocol phases.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    // number of elements in the database
-    #[arg(long)]
-    db_size: u32,
+    const NAME: &'static str = "Prover";
+    const SIDE: Side = Side::Prover;
 
-    // (optional) dimension override
-    #[arg(long, default_value_t = size_of::<DataT>() as u32 * 8)]
-    dimension: u32,
+    fn new(args: Args) -> Self {
+        let datasets = dataset_specs(&args).iter().map(|spec| Dataset::new(spec, &args)).collect();
+        // already checked by main
+        let tls = tls_config(&args).ok().flatten();
+        Prover {
+            args: Arc::new(args), tls, datasets, current: 0, listener: None, co_verifiers: Vec::new(), certified: false, resumed: false, noise_spent: false,
+            analyst: String::new(),
+        }
+    }
 
-    // max monomial degree
-    #[arg(long)]
-    max_degree: u32,
+    fn listen(&mut self) -> io::Result<()> {
+        if self.listener.is_some() {
+            return Ok(());
+        }
+        let address = format!("{}:{}", PROVER_ADDRESS, self.args.port);
+        #[cfg(feature = "http")]
+        if self.args.http {
+            self.listener = Some(Listener::Http(HttpListener::bind(&address, self.args.max_frame_size())?));
+            return Ok(());
+        }
+        #[cfg(feature = "grpc")]
+        if self.args.grpc {
+            self.listener = Some(Listener::Grpc(GrpcListener::bind(&address, self.args.max_frame_size())?));
+            return Ok(());
+        }
+        #[cfg(feature = "quic")]
+        if self.args.quic {
+            let config = self.tls.clone().expect("--quic requires --tls-cert");
+            self.listener = Some(Listener::Quic(QuicListener::bind(&address, config, self.args.max_frame_size())?));
+            return Ok(());
+        }
+        self.listener = Some(Listener::Tcp(NetListener::bind(&address, self.tls.clone(), self.args.max_frame_size())?));
+        Ok(())
+    }
 
-    // differential privacy epsilon
-    #[arg(long)]
-    epsilon: f32,
+    fn connect(&mut self) -> io::Result<Connection> {
+        self.listen()?;
+        self.accept()
+    }
 
-    // (optional) delta parameter
-    #[arg(long, default_value = None)]
-    delta: Option<f32>,
+    fn setup(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        // an HTTP verifier posts its messages as JSON, and reads the prover's the same way
+        #[cfg(feature = "http")]
+        if self.args.http && (stream.wire_format() != WireFormat::Json || stream.compression()) {
+            return Err(Error::Protocol("HTTP sessions need JSON messages, uncompressed".to_string()));
+        }
+        // the session's frames are authenticated from here on, and only verifiers the prover authorizes get as far as
+        // the commitment phases
+        let authorized = self.args.authorized();
+        mac::prover_agree(&mut OsRng, stream, authorized.preshared.as_ref())?;
+        let mut signer = None;
+        if authorized.required() {
+            signer = auth::prover_authenticate(&mut OsRng, stream, &authorized)?;
+            eprintln!("  verifier authenticated");
+        }
+        // with analyst budgets, the verifier then names the analyst the session is charged to, which must be the one
+        // its key is bound to, or any verifier could spend another analyst's budget, or start afresh under a new name
+        if self.args.analyst_budget.is_some() {
+            let analyst_m: AnalystMessage = read_message(stream)?;
+            let analyst = epoch::parse_analyst(&analyst_m.name).map_err(Error::Protocol)?;
+            let bound = self.args.authorized_key.iter().find(|k| Some(k.key) == signer).and_then(|k| k.analyst.as_ref());
+            if bound != Some(&analyst) {
+                eprintln!("  analyst {:?} is not bound to the verifier's key", analyst);
+                let reason = AbortReason::Unauthorized;
+                // the verifier may already be gone, which makes no difference to the refusal
+                let _ = write_abort(stream, &reason);
+                return Err(Error::Aborted(reason));
+            }
+            self.analyst = analyst;
+            eprintln!("  analyst {}", self.analyst);
+        }
+        // with named datasets, the verifier then names the one this session audits
+        if !self.args.dataset.is_empty() {
+            let dataset_m: DatasetMessage = read_message(stream)?;
+            match self.datasets.iter().position(|d| d.name == dataset_m.name) {
+                Some(i) => self.current = i,
+                None => return Err(Error::Protocol(format!("Unknown dataset {:?}", dataset_m.name))),
+            }
+            eprintln!("  dataset {}", dataset_m.name);
+        }
 
-    // sparsity
-    #[arg(long)]
-    sparsity: u32,
+        let d = &mut self.datasets[self.current];
+        // concurrent sessions charge the same budget, so each checks and charges it under the lock
+        let budget = d.epochs.clone();
+        let mut epochs = budget.lock().unwrap();
+        if epochs.advance(SystemTime::now()) {
+            d.start_epoch(epochs.current());
+        }
 
-    // (optional) skip dishonest commitment phase if we're measuring something else
-    #[arg(long, default_value_t = false)]
-    skip_dishonest: bool,
+        // the whole session is charged up front, since its queries all run on the same noise (or, with --progressive,
+        // on noise flipped as the session goes); a certifying session makes no queries
+        let certifying = self.args.command.is_some() && !self.certified;
+        let queries = if certifying { 0 } else { self.args.num_queries };
+        if let Err(e) = epochs.spend(&self.analyst, queries, d.epsilon as f64, calibration::delta(d.db_size(), self.args.delta) as f64) {
+            return Err(Error::Config(format!("Session refused in epoch {}: {}", epochs.current(), e)));
+        }
+        let (epsilon, delta) = epochs.accountant().spent();
+        eprintln!("  epoch spent ε = {:.4}, δ = {:.3e}", epsilon, delta);
+        if let Some(accountant) = epochs.analyst(&self.analyst) {
+            eprintln!("  analyst spent ε = {:.4}", accountant.spent().0);
+        }
+        let epoch = epochs.current();
+        drop(epochs);
+        if let Some(path) = &self.args.budget_state {
+            self.save_budgets(path)?;
+        }
+        let d = &mut self.datasets[self.current];
 
-    // (optional) number of queries to execute and average runtime over
-    #[arg(long, default_value_t = 100)]
-    num_queries: u32,
+        let params = d.params(&self.args);
+        prover_send_setup(&mut d.state, stream, &params, epoch)?;
+        // the first session only now flipped the generators
+        if let Some(pk) = &self.args.elgamal_key {
+            d.state.pedersen_pp = elgamal::params(&d.state.pedersen_pp, pk);
+        }
+        if self.resumed {
+            eprintln!("  resuming snapshot {}", d.state.snapshot);
+        }
+        Ok(())
+    }
 
-    // (optional) evaluate sparsity experiment
-    #[arg(long, default_value_t = false)]
-    sparsity_experiment: bool,
-}
+    fn honest_commitment(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let d = &mut self.datasets[self.current];
+        // clear out database commitments from an earlier session, which spot-check mode would otherwise add to
+        let database = committing(&mut d.database);
+        database.commitments.clear();
+        if self.args.spot_check.is_some() {
+            prover_dishonest_commitment_phase(&mut d.state, stream, database, d.dimension, d.max_degree, self.args.spot_check)?;
+        } else {
+            prover_honest_commitment_phase(&mut d.state, stream, database, d.dimension, d.max_degree)?;
+        }
+        d.take_snapshot();
+        Ok(())
+    }
 
-fn main() {
-    // Check = verifier checks the query
-    eprintln!("Running");
+    fn dishonest_commitment(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let d = &mut self.datasets[self.current];
+        // clear out database commitments from the honest phase
+        let database = committing(&mut d.database);
+        database.commitments.clear();
+        match self.args.cut_and_choose {
+            Some(encodings) => prover_cut_and_choose_phase(&mut d.state, stream, database, d.dimension, d.max_degree, encodings)?,
+            None => prover_dishonest_commitment_phase(&mut d.state, stream, database, d.dimension, d.max_degree, None)?,
+        }
+        if let Some(attribute) = &self.args.distinct {
+            prover_sketch_phase(&mut d.state, stream, attribute, d.dimension)?;
+        }
+        d.take_snapshot();
+        Ok(())
+    }
 
-    let args = Args::parse();
-    println!("\n-- Prover --\n");
-    println!("Configuration:");
-    println!("\tDatabase size: {}", args.db_size);
-    println!("\tDimension: {}", args.dimension);
-    println!("\tMax degree: {}", args.max_degree);
-    println!("\tSparsity: {}", args.sparsity);
-    println!("\tEpsilon: {}", args.epsilon);
-    println!("\tDelta: {:?}", args.delta);
-    println!("\tProver address: {}:{}\n", PROVER_ADDRESS, PROVER_PORT);
+    fn update(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let d = &mut self.datasets[self.current];
+        let (added, removed) = std::mem::take(&mut d.pending_update);
+        eprintln!("  {} rows added, {} removed", added.len(), removed.len());
+        prover_update_phase(&mut d.state, stream, committing(&mut d.database), added, removed, d.dimension, d.max_degree)?;
+        d.take_snapshot();
+        Ok(())
+    }
 
-    // Setup
-    eprintln!("Setup phase start");
+    fn randomness(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        self.accept_co_verifiers(stream)?;
+        self.flip_noise(stream)
+    }
 
-    let listener = TcpListener::bind(format!("{}:{}", PROVER_ADDRESS, PROVER_PORT)).unwrap();
-    let (mut stream, _) = listener.accept().unwrap();
+    fn query(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        if self.args.progressive && self.noise_spent {
+            self.flip_noise(stream)?;
+        }
+        let d = &mut self.datasets[self.current];
+        if self.args.batch > 1 {
+            let repeats = prover_answer_batch(&mut d.state, &d.database, &d.name, stream, self.args.sensitivity, &self.args.query_policy)?;
+            if repeats > 0 {
+                eprintln!("  {} repeated queries in the batch, answered as before", repeats);
+            }
+            return Ok(());
+        }
+        let repeated = prover_answer_query(&mut d.state, &d.database, &d.name, stream, AnswerMode::from_keys(self.args.analyst_key, self.args.elgamal_key), self.args.sensitivity, &self.args.query_policy)?;
+        // a repeated query is answered on the noise it was first answered on, leaving the current noise for the next
+        self.noise_spent = !repeated;
+        if repeated {
+            eprintln!("  repeated query, answered as before");
+        }
+        Ok(())
+    }
 
-    let mut prover_state = prover_setup(&mut stream);
-    let mut database: Data<DataT> = Data::new(&mut prover_state.rng, args.db_size);
+    fn certify(&mut self, stream: &mut Framed<Connection>, transcript: &[u8; 32]) -> Result<()> {
+        let Some(Command::Certify { out, signing_key }) = &self.args.command else {
+            return Err(Error::Config("Certifying needs the certify command".to_string()));
+        };
+        let d = &self.datasets[self.current];
+        let statement = Statement {
+            dataset: d.name.clone(),
+            db_size: d.db_size(),
+            dimension: d.dimension,
+            epsilon: d.epsilon,
+            delta: self.args.delta,
+            g: d.state.pedersen_pp.g,
+            h: d.state.pedersen_pp.h,
+            epoch: d.epoch(),
+            snapshot: d.state.snapshot,
+            transcript: *transcript,
+        };
+        eprintln!("  certificate {}", statement.fingerprint());
 
-    eprintln!("Setup phase complete");
+        let m: CertificateSignatureMessage = read_message(stream)?;
+        let digest = statement.digest();
+        if !m.key.verify(&digest, &m.signature) {
+            return Err(Error::Verification("Auditor's signature is not on this certificate".to_string()));
+        }
+        let mut endorsements = vec![Endorsement { key: m.key, signature: m.signature }];
+        if let Some(sk) = signing_key {
+            endorsements.push(Endorsement { key: sk.verifying_key(), signature: sk.sign(&digest) });
+        }
 
-    // Honest Commitment Phase
-    eprintln!("Honest commitment phase start");
-   
-    synchronize_verifier(&mut stream);
-    let start_honest_comm = Instant::now();
-    prover_honest_commitment_phase(&mut prover_state, &mut stream, &mut database, args.dimension, args.max_degree);
-    synchronize_verifier(&mut stream);
-    let duration_honest_comm = start_honest_comm.elapsed();
+        let certificate = Certificate::<T> {
+            statement,
+            commitment_map: d.database.commitments.iter().map(|(&monomial, &(_, comm, _))| (monomial, comm)).collect(),
+            endorsements,
+        };
+        certificate.save(out).map_err(|e| Error::Config(format!("Could not write certificate: {}", e)))?;
+        eprintln!("  auditor {} certified, written to {}", m.key.to_hex(), out.display());
+        self.certified = true;
+        Ok(())
+    }
 
-    eprintln!("Honest commitment phase complete ({:?}, {:?} monomials, {:?}/monomial)",
-        duration_honest_comm, database.commitments.len(), duration_honest_comm / database.commitments.len() as u32);
+    #[cfg(feature = "experiments")]
+    fn experiments(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        if !self.args.sparsity_experiment {
+            return Ok(());
+        }
 
-    let mut duration_dishonest_comm = Duration::from_secs(0);
-    if !args.skip_dishonest {
-        // clear out database commitments for next phase
-        database.commitments.clear();
+        let d = &mut self.datasets[self.current];
+        eprintln!("Sparsity experiment begin");
+        for _s in 1..pow(2, d.dimension as usize) {
+            for _ in 0..self.args.num_queries {
+                synchronize(Side::Prover, stream)?;
+                prover_answer_query(&mut d.state, &d.database, &d.name, stream, AnswerMode::from_keys(self.args.analyst_key, self.args.elgamal_key), self.args.sensitivity, &self.args.query_policy)?;
+                synchronize(Side::Prover, stream)?;
+            }
+        }
+        eprintln!("Sparsity experiment complete");
+        Ok(())
+    }
 
-        // Dishonest Commitment Phase
-        eprintln!("Dishonest commitment phase start");
-    
-        synchronize_verifier(&mut stream);
-        let start_dishonest_comm = Instant::now();
-        let comm_success = prover_dishonest_commitment_phase(&mut prover_state, &mut stream, &mut database, args.dimension, args.max_degree);
-        synchronize_verifier(&mut stream);
-        duration_dishonest_comm = start_dishonest_comm.elapsed();
-
-        if !comm_success {
-            return;
-        }
-
-        eprintln!("Dishonest commitment phase complete ({:?}, {:?} monomials, {:?}/monomial)",
-            duration_dishonest_comm, database.commitments.len(), duration_dishonest_comm / database.commitments.len() as u32);
-    }
-   
-    // Randomness Phase
-    eprintln!("Randomness phase start (N: {:?})", get_n(args.db_size, args.epsilon, args.delta));
-
-    synchronize_verifier(&mut stream);
-    let start_rnd = Instant::now();
-    prover_state.randomness_bit_sum = Scalar::from(0 as u32);
-    prover_state.randomness_bit_proof = prover_state.CPROOF;
-
-    for _ in 0..get_n(args.db_size, args.epsilon, args.delta) {
-        prover_randomness_phase_comm(&mut prover_state, &mut stream);
-        if prover_randomness_phase_response(&mut prover_state, &mut stream) {
-            let _start = Instant::now();
-            prover_state.randomness_bit_sum += Scalar::from(prover_state.final_b);
-            prover_state.randomness_bit_proof += prover_state.final_proof;
-            prover_state.coin_flipping_and_agg_duration += _start.elapsed();
-        } else {
-            println!("ERROR: Randomness phase failed");
-            return;
+    fn phase_detail(&self, phase: Phase, duration: Duration) -> Option<String> {
+        let d = self.dataset();
+        match phase {
+            Phase::HonestCommitment | Phase::DishonestCommitment => {
+                let monomials = d.database.commitments.len();
+                Some(format!("{:?} monomials, {:?}/monomial", monomials, duration / monomials.max(1) as u32))
+            },
+            Phase::Randomness => {
+                let n = d.params(&self.args).coins();
+                Some(format!("N = {} iterations, {:?}/iteration", n, duration / n.max(1)))
+            },
+            _ => None,
         }
     }
-    prover_randomness_phase_adjust(&mut prover_state, args.db_size, args.epsilon, args.delta);
-    synchronize_verifier(&mut stream);
-    let duration_rnd = start_rnd.elapsed();
 
-    eprintln!("Randomness phase complete ({:?}, N = {} iterations, {:?}/iteration)",
-        duration_rnd, get_n(args.db_size, args.epsilon, args.delta), duration_rnd / get_n(args.db_size, args.epsilon, args.delta));
+    fn report(&self, stats: &ProtocolStats) {
+        let d = self.dataset();
+        ptable!(
+            ["Comparison", "P-Rand. Gen. (s)", "Rand. N + & Query N + (µs)"],
+            ["", format!("{:?} s", d.state.randomness_sigma_duration.as_secs_f32()), format!("{:?} µs", d.state.coin_flipping_and_agg_duration.as_micros())]
+        );
+
+        let name = if d.name.is_empty() { String::new() } else { format!("{}: ", d.name) };
+        stats.table(Self::NAME, format!("({}n={}, d={}, ε={}, δ={:?} s={})", name, d.db_size(), d.dimension, d.epsilon, calibration::delta(d.db_size(), self.args.delta), self.args.sparsity))
+            .printstd();
+    }
 
-    // Query phase
-    eprintln!("Query phase start");
+    fn finish(&mut self) -> Result<()> {
+        self.resumed = self.args.resumable;
+        Ok(())
+    }
 
-    let mut duration_query = Duration::from_secs(0);
-    for _ in 0..args.num_queries {
-        synchronize_verifier(&mut stream);
-        let iter_start_query = Instant::now();
-        prover_answer_query(&mut prover_state, &mut database, &mut stream);
-        synchronize_verifier(&mut stream);
-        let iter_duration_query = iter_start_query.elapsed();
+    fn abandon(&mut self) {
+        // co-verifiers that joined the failed session; noise it answered on stays spent
+        self.co_verifiers.clear();
+    }
 
-        duration_query += iter_duration_query;
+    fn fork(&self) -> Option<Self> {
+        let datasets = self.datasets.iter().map(Dataset::fork).collect();
+        Some(Prover {
+            args: self.args.clone(), tls: self.tls.clone(), datasets, current: 0, listener: None, co_verifiers: Vec::new(),
+            certified: self.certified, resumed: self.resumed, noise_spent: false, analyst: String::new(),
+        })
     }
-    duration_query /= args.num_queries;
+}
 
-    eprintln!("Query phase complete ({:?})", duration_query);
+fn main() -> ExitCode {
+    eprintln!("Running");
+    driver::log_progress();
 
-    if args.sparsity_experiment {
-        eprintln!("Sparsity experiment begin");
-        for _s in 1..pow(2, args.dimension as usize) {
-            for _ in 0..args.num_queries {
-                synchronize_verifier(&mut stream);
-                prover_answer_query(&mut prover_state, &mut database, &mut stream);
-                synchronize_verifier(&mut stream);
-            }
+    let mut args = Args::parse();
+    args.calibration = args.calibration.with_queries(args.num_queries);
+    let database_schema = match &args.schema {
+        Some(path) => Schema::load(path),
+        None => Schema::new(args.column.clone()),
+    };
+    match database_schema {
+        Ok(database_schema) => args.database_schema = database_schema,
+        Err(e) => {
+            eprintln!("ERROR: Schema {}", e);
+            return ExitCode::FAILURE;
+        },
+    }
+    if let Some(path) = &args.policy {
+        match Policy::load(path, &args.database_schema) {
+            Ok(policy) => args.query_policy = policy,
+            Err(e) => {
+                eprintln!("ERROR: Policy {}", e);
+                return ExitCode::FAILURE;
+            },
         }
-        eprintln!("Sparsity experiment complete");
     }
+    // each dataset loads a database file again at its entry width, so this only checks it and counts its rows
+    let bits = args.database_schema.bits();
+    if bits > args.dimension {
+        eprintln!("ERROR: Schema takes {} bits, beyond the dimension {}", bits, args.dimension);
+        return ExitCode::FAILURE;
+    }
+    match args.load_database::<u128>() {
+        Some(Ok(database)) => args.db_size = Some(database.entries.len() as u32),
+        Some(Err(e)) => {
+            eprintln!("ERROR: Database {}", e);
+            return ExitCode::FAILURE;
+        },
+        None => {},
+    }
+    if let Some(path) = &args.budget_state {
+        match epoch::load_ledgers(path) {
+            Ok(ledgers) => args.ledgers = ledgers,
+            Err(e) => {
+                eprintln!("ERROR: Budget state {}", e);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    let specs = dataset_specs(&args);
+    for (i, spec) in specs.iter().enumerate() {
+        if specs[..i].iter().any(|other| other.name == spec.name) {
+            eprintln!("ERROR: Dataset {} is configured more than once", spec.name);
+            return ExitCode::FAILURE;
+        }
 
-    ptable!(
-        ["Comparison", "P-Rand. Gen. (s)", "Rand. N + & Query N + (µs)"],
-        ["", format!("{:?} s", prover_state.randomness_sigma_duration.as_secs_f32()), format!("{:?} µs", prover_state.coin_flipping_and_agg_duration.as_micros())]
-    );
-
-    ptable!(
-        ["Prover", format!("(n={}, d={}, ε={}, δ={:?} s={})", args.db_size, args.dimension, args.epsilon, get_delta(args.db_size, args.delta), args.sparsity)],
-        ["Commit", ""],
-        ["  -> Honest", format!("{:?}", duration_honest_comm)],
-        ["  -> Dishonest", format!("{:?}", duration_dishonest_comm)],
-        ["Randomness", format!("{:?}", duration_rnd)],
-        ["Query", format!("{:?}", duration_query)]
-    );
+        let db_size = args.db_size(spec);
+        let (add_rows, remove_rows) = (args.add_rows.unwrap_or(0), args.remove_rows.unwrap_or(0));
+        if remove_rows > db_size {
+            eprintln!("ERROR: Cannot remove {} rows from a database of {}", remove_rows, db_size);
+            return ExitCode::FAILURE;
+        }
+        // the DP parameters are undefined for an empty database
+        if remove_rows == db_size && add_rows == 0 {
+            eprintln!("ERROR: Cannot remove every row without adding any");
+            return ExitCode::FAILURE;
+        }
+    }
+    // later sessions answer from the certified commitments, so they must stay the same
+    if args.command.is_some() && (specs.len() > 1 || args.add_rows.is_some() || args.remove_rows.is_some() || args.epoch_length.is_some()) {
+        eprintln!("ERROR: Certifying needs a single dataset, with no update and no epochs");
+        return ExitCode::FAILURE;
+    }
+    // the sketch is certified over the entries of each session's dishonest commitment phase
+    if args.distinct.is_some() && (args.command.is_some() || args.resumable || args.add_rows.is_some() || args.remove_rows.is_some()) {
+        eprintln!("ERROR: Distinct sketches need the dishonest commitment phase in every session, with no certificate, no resuming, and no update");
+        return ExitCode::FAILURE;
+    }
+    // an analyst's budget is theirs alone to spend, so each session charged to one must come from the key bound to them
+    if args.analyst_budget.is_some() && args.authorized_key.iter().any(|k| k.analyst.is_none()) {
+        eprintln!("ERROR: Analyst budgets need every authorized key bound to an analyst, as --authorized-key ANALYST=KEY");
+        return ExitCode::FAILURE;
+    }
+    // co-verifiers only stay for the randomness phase, so they cannot flip the later queries' noise
+    if args.progressive && args.verifiers > 1 {
+        eprintln!("ERROR: Progressive noise needs a single verifier");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = noise::check(args.noise, args.calibration, args.verifiers, args.progressive) {
+        eprintln!("ERROR: {}", e);
+        return ExitCode::FAILURE;
+    }
+    // later sessions answer on the first session's commitments and noise, so those must stay the same
+    if args.resumable && (args.command.is_some() || specs.len() > 1 || args.add_rows.is_some() || args.remove_rows.is_some() || args.epoch_length.is_some()) {
+        eprintln!("ERROR: Resumable sessions need a single dataset, with no certificate, no update, and no epochs");
+        return ExitCode::FAILURE;
+    }
+    // concurrent sessions answer from the database the first session committed, and accept no co-verifiers of their own
+    if args.concurrent > 1 && (!(args.command.is_some() || args.resumable) || args.verifiers > 1) {
+        eprintln!("ERROR: Concurrent sessions need the certify command or --resumable, and a single verifier");
+        return ExitCode::FAILURE;
+    }
+    // each attribute is a bit of the entry, and each monomial a mask of them, so the entries must hold every dataset's
+    let dimension = specs.iter().map(|spec| spec.dimension.unwrap_or(args.dimension)).max().unwrap_or(args.dimension);
+    let Some(entry_width) = args.entry_width.or(config::entry_width(dimension)).filter(|&width| width >= dimension) else {
+        let width = args.entry_width.unwrap_or(*config::ENTRY_WIDTHS.last().unwrap());
+        eprintln!("ERROR: Dimension {} exceeds the {} attributes an entry holds", dimension, width);
+        return ExitCode::FAILURE;
+    };
+    if let Err(e) = tls_config(&args) {
+        eprintln!("ERROR: {}", e);
+        return ExitCode::from(e.exit_code());
+    }
+    println!("\n-- Prover --\n");
+    println!("Configuration:");
+    println!("\tDatabase size: {}", args.db_size(&specs[0]));
+    if !args.database_schema.attributes().is_empty() {
+        println!("\tSchema: {}", args.database_schema);
+    }
+    println!("\tDimension: {}", args.dimension);
+    println!("\tEntry width: {} bits", entry_width);
+    println!("\tMax degree: {}", args.max_degree);
+    println!("\tSparsity: {}", args.sparsity);
+    println!("\tEpsilon: {}", args.epsilon);
+    println!("\tDelta: {:?}", args.delta);
+    println!("\tCalibration: {}", args.calibration);
+    println!("\tSensitivity: {}", args.sensitivity);
+    if args.epoch_budget.is_some() || args.epoch_delta.is_some() || args.composition != Composition::Basic {
+        println!("\tEpoch budget: {}, {} composition", Accountant::new(args.composition, args.epoch_budget, args.epoch_delta).budget(), args.composition);
+    }
+    if let Some(budget) = args.analyst_budget {
+        println!("\tAnalyst budget: ε = {} each", budget);
+    }
+    if let Some(path) = &args.budget_state {
+        println!("\tBudget state: {}", path.display());
+    }
+    if !args.dataset.is_empty() {
+        println!("\tDatasets: {}", specs.iter().map(|spec| spec.name.as_str()).collect::<Vec<_>>().join(", "));
+    }
+    println!("\tProfile: {}", args.profile);
+    if let Some(Command::Certify { out, .. }) = &args.command {
+        println!("\tCertificate: {}", out.display());
+    }
+    if let Some(attribute) = &args.distinct {
+        println!("\tDistinct: {}", attribute);
+    }
+    if let Some(path) = &args.policy {
+        println!("\tQuery policy: {}", path.display());
+    }
+    if args.batch > 1 {
+        println!("\tBatch: {} queries", args.batch);
+    }
+    if args.noise != Mechanism::Binomial || args.progressive {
+        println!("\tNoise: {}{}", args.noise, if args.progressive { ", progressive, per query" } else { "" });
+    }
+    if let Some(cert) = &args.tls_cert {
+        println!("\tTLS: {}{}", cert.display(), if args.tls_ca.is_some() { ", verifier certificates required" } else { "" });
+    }
+    if args.auth_psk.is_some() || !args.authorized_key.is_empty() {
+        let psk = args.auth_psk.iter().map(|_| "pre-shared key".to_string());
+        let keys = (!args.authorized_key.is_empty()).then(|| format!("authorized keys ({})", args.authorized_key.len()));
+        println!("\tAuthentication: {}", psk.chain(keys).collect::<Vec<_>>().join(" or "));
+    }
+    // the key exchange alone lets an attacker relaying the connection agree keys of its own with each side
+    if args.tls_cert.is_none() && args.auth_psk.is_none() && args.authorized_key.is_empty() {
+        eprintln!("WARNING: Without --tls-cert, --auth-psk or --authorized-key, frames are not protected against an active attacker on the network, who can agree keys of its own with each side");
+    }
+    #[cfg(feature = "http")]
+    if args.http {
+        println!("\tHTTP: verifiers post JSON messages to their sessions at /sessions");
+    }
+    #[cfg(feature = "grpc")]
+    if args.grpc {
+        println!("\tgRPC: verifiers stream their sessions' frames to the Prover service");
+    }
+    #[cfg(feature = "quic")]
+    if args.quic {
+        println!("\tQUIC: each frame on a stream of its own");
+    }
+    if args.daemon {
+        println!("\tDaemon: serving sessions until stopped");
+    }
+    if args.concurrent > 1 {
+        println!("\tConcurrent: up to {} sessions at once after the first", args.concurrent);
+    }
+    if let Some(path) = &args.record {
+        if let Err(e) = recording::open(path) {
+            eprintln!("ERROR: Recording {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+        println!("\tRecording: {}", path.display());
+    }
+    println!("\tProver address: {}:{}\n", PROVER_ADDRESS, args.port);
+
+    let options = DriverOptions {
+        skip_dishonest: args.skip_dishonest,
+        update: args.add_rows.is_some() || args.remove_rows.is_some(),
+        num_queries: args.num_queries,
+        sessions: args.auditors,
+        daemon: args.daemon,
+        concurrent: args.concurrent,
+        profile: args.profile,
+        certify: args.command.is_some(),
+        certified: false,
+        resume: args.resumable,
+        resumed: false,
+        deadlines: Deadlines::new(&args.deadline),
+        // each verifier picks the wire format and compression of its session
+        wire_format: WireFormat::default(),
+        compress: false,
+        batch: args.batch,
+        max_frame_size: args.max_frame_size(),
+    };
+    let outcome = match entry_width {
+        8 => ProtocolDriver::<Prover<u8>>::execute(args, options),
+        16 => ProtocolDriver::<Prover<u16>>::execute(args, options),
+        32 => ProtocolDriver::<Prover<u32>>::execute(args, options),
+        64 => ProtocolDriver::<Prover<u64>>::execute(args, options),
+        _ => ProtocolDriver::<Prover<u128>>::execute(args, options),
+    };
+    match outcome {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(e.exit_code()),
+    }
 }
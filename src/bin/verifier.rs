@@ -1,688 +1,1247 @@
-/**
+/*!
  * verifier.rs
  * 
  * Primary Verifier executable with the following arguments:
  *   
  *   db_size: number of elements in the database
+ *   max_degree: maximum monomial degree
  *   epsilon: differential privacy epsilon
  *   delta: (optional) differential privacy delta, otherwise calculated based on the database size
+ *   calibration: (optional) how epsilon and delta set the number of noise coins, approximate (the default), zcdp, or renyi,
+ *     the last two with progressive noise
+ *   noise: (optional) mechanism combining the noise coins into noise, binomial (the default) or discrete-laplace
+ *   sensitivity: (optional) largest L1 sensitivity of the session's queries, which the noise is scaled to, one by default
+ *   confidence: (optional) confidence level of the interval reported with each answer, 0.95 by default
+ *   budget: (optional) total epsilon the session's queries may spend, otherwise unlimited
+ *   budget_delta: (optional) total delta the session's queries may spend, otherwise unlimited
+ *   composition: (optional) how the queries' privacy losses add up against the budget, basic (the default) or advanced
  *   sparsity: max coefficients in query polynomial
  *   prover_address: prover url and port for communication
  *   dimension: (optional) dimension (bitsize) of the database entries
+ *   entry_width: (optional) bits in each database entry, which must be the prover's, otherwise the narrowest holding the dimension
  *   skip_dishonest: (optional) skip dishonest commitment phase if desired
  *   num_queries: (optional) number of queries to execute and average runtime over
- *   sparsity_experiment: (optional) sspecial flag to evaluate sparsity experiment
+ *   batch: (optional) send the queries in batches of this many, checking each batch's answers at once
+ *   spot_check: (optional) run the honest commitment phase in spot-check mode, auditing this fraction of entries
+ *   audit_rate: (optional) verify only this fraction of the entries' proofs in the dishonest commitment phase
+ *   cut_and_choose: (optional) certify the dishonest commitment phase by cut-and-choose with this many encodings per entry
+ *   update: (optional) expect an update phase after the commitment phases, verifying only the rows the prover changes
+ *   analyst_key: (optional) expect query answers encrypted to this analyst public key; they are verified but not learned
+ *   elgamal_key: (optional) expect commitments under exponential ElGamal for this analyst public key; only query handles are revealed
+ *   encrypted_answers: (optional) file to append the verified encrypted answers to, for the analyst
+ *   query_certificates: (optional) directory to write a certificate of each verified answer to, for anyone to re-verify
+ *   release_key: (optional) sign each query certificate's guarantee of the epsilon and delta its answer was released under
+ *   snapshot: (optional) only query the database if the last commitment phase certifies this snapshot
+ *   dataset: (optional) name of the prover's dataset to audit, when the prover hosts named datasets
+ *   analyst: (optional) name the session's queries are charged to, when the prover gives analysts budgets of their own
+ *   profile: (optional) persona to run as, analyst (the default) or auditor, which may only verify encrypted answers
+ *   verifiers: (optional) number of verifiers jointly flipping the noise coins, including any co-verifiers
+ *   co_verifier: (optional) only join another verifier's session to flip the noise coins with it
+ *   beacon: (optional) file of randomness beacon rounds to flip the noise coins with, in place of the verifier's own coins
+ *   certify: (optional) as the auditor of a certifying prover, sign the certificate of the commitment phases with this key
+ *   certificate: (optional) start from this certificate of the commitment phases instead of running them
+ *   auditor_key: (optional) key of the auditor whose signature the certificate must carry
+ *   save_session: (optional) file to save the verified commitments, noise commitment, and parameters to after the session
+ *   resume: (optional) resume the session saved to this file, skipping the commitment and randomness phases
+ *   deadline: (optional, repeatable) wall-clock limit on a phase, past which the protocol aborts
+ *   distinct: (optional) check a sketch of this attribute of the entries, and make the queries distinct counts of it
+ *   schema: (optional) JSON schema file of the database's attributes, naming the bits of each query
+ *   predicate: (optional) count the entries matching this predicate over the schema's attributes or entry bits in every query
+ *   queries: (optional) JSON file of named queries to ask in order, each a predicate or weighted predicates, in place of random ones
+ *   interactive: (optional) read each query from the analyst at a prompt, ending the session when they quit
+ *   answers: (optional) file to write the decoded answers to the queries of the query file or prompt to, as JSON lines
+ *   progressive: (optional) flip only the first query's noise in the randomness phase, and each later query's before it
+ *   wire_format: (optional) encoding of the session's messages, json (the default) or bincode
+ *   compress: (optional) gzip large frames of the session in both directions
+ *   max_message_size: (optional) largest message, in MiB, accepted from the prover
+ *   record: (optional) append every frame sent and received, with its session, phase, and time, to this file
+ *   tls_ca: (optional) connect to the prover over TLS, accepting only a certificate signed by this CA
+ *   tls_cert: (optional) TLS certificate chain to present to a prover that requires one
+ *   tls_key: (optional) private key of the TLS certificate
+ *   auth_psk: (optional) answer the prover's challenge with this pre-shared key, and mix it into the session's frame keys
+ *   auth_key: (optional) answer the prover's challenge with a signature under this signing key
+ *   grpc: (optional) connect to a prover serving gRPC, as one stream of frames (requires the `grpc` feature)
+ *   quic: (optional) connect to a prover serving QUIC, each frame on a stream of its own (requires the `quic` feature)
+ *   sparsity_experiment: (optional) special flag to evaluate sparsity experiment (requires the `experiments` feature)
  */
 
 #[macro_use] extern crate prettytable;
 
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use num_traits::{pow, PrimInt};
-use rand::{Rng, SeedableRng};
-use rand::prelude::IteratorRandom;
-use rand::rngs::OsRng;
-use rand_chacha::ChaCha20Rng;
-use serde::Serialize;
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::mem::size_of;
-use std::net::{SocketAddr, TcpStream};
-use std::ops::Neg;
+#[cfg(feature = "experiments")]
+use num_traits::pow;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
-use std::time::Instant;
-
-use certified_dp::bit_sigma;
-use certified_dp::product_sigma;
-use certified_dp::config::{get_n, get_delta, DataT};
-use certified_dp::messages::{read_from_stream, write_to_stream, BitSigmaChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, CommitmentMapMessage, MonomialChallengeTreeNode, MonomialCommitmentTreeNode, MonomialResponseTreeNode, ProverRandomnessComm, ProverRandomnessResponse, QueryAnswerMessage, QueryMessage, ReadyMessage, SetupMessage, VerifierCheckMessage, VerifierRandomnessChallenge};
-use certified_dp::pedersen;
-
-/// Verifier state for the main protocol.
-#[allow(non_snake_case)]
-struct VerifierState<T>
-where T: PrimInt + Hash
-{
-    rng: OsRng,
-    pedersen_pp: pedersen::PublicParams,
-    monomial_commitments: HashMap<T, RistrettoPoint>,
-    player_b: u32,
-    randomness_bit_comm: RistrettoPoint,
-    sigma_verifier: bit_sigma::Verifier,
-
-    C0: RistrettoPoint,
-    C1: RistrettoPoint,
-    CPROOF: Scalar,
-
-    comm_verify_duration: Duration,
-    randomness_bit_sigma_verify_duration: Duration,
-    randomness_coin_flip_agg_duration: Duration,
-}
 
-///
-/// -- SETUP PHASE --
-///
-
-/// Setup the verifier with the shared randomness seed from the prover and initialize state.
-fn verifier_setup<T: PrimInt + Hash>(stream: &mut TcpStream) -> VerifierState<T> {
-
-    let rng = OsRng::default();
-   
-    let setup_message: SetupMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-
-    let mut shared_rng = ChaCha20Rng::from_seed(setup_message.seed);
-    let pp= pedersen::setup(&mut shared_rng);
-
-    let proof_val = Scalar::from(0 as u32);
-    VerifierState {
-        rng,
-        pedersen_pp: pp.clone(),
-        monomial_commitments: HashMap::new(),
-        C0: pedersen::commit_with_r(&Scalar::from(0 as u32), &proof_val, &pp),
-        C1: pedersen::commit_with_r(&Scalar::from(1 as u32), &proof_val, &pp),
-        CPROOF: proof_val,
-        player_b: 0,
-        randomness_bit_comm: RistrettoPoint::default(),
-        sigma_verifier: bit_sigma::Verifier::default(),
-
-        randomness_bit_sigma_verify_duration: Duration::from_secs(0),
-        randomness_coin_flip_agg_duration: Duration::from_secs(0),
-        comm_verify_duration: Duration::from_secs(0),
+use certified_dp::analyst::{self, AnswerMode, SavedAnswer};
+use certified_dp::auth::{self, Credential, PresharedKey};
+use certified_dp::audit::{self, AuditMode};
+use certified_dp::beacon::{self, Beacon};
+use certified_dp::budget::{self, Accountant, Composition};
+use certified_dp::certificate::{Certificate, Statement};
+use certified_dp::cut_and_choose;
+use certified_dp::cache;
+use certified_dp::calibration::{self, Calibration};
+use certified_dp::config::{self, max_dimension, DataT};
+use certified_dp::data::Entry;
+use certified_dp::driver::{self, DriverOptions, Phase, ProtocolDriver, ProtocolStats, Role, Side};
+#[cfg(feature = "experiments")]
+use certified_dp::driver::synchronize;
+use certified_dp::elgamal;
+use certified_dp::epoch;
+use certified_dp::error::{Error, Result};
+use certified_dp::dataset;
+use certified_dp::deadline::{self, Deadlines};
+use certified_dp::mac;
+use certified_dp::messages::{self, write_message, Framed, WireFormat, AnalystMessage, CertificateSignatureMessage, DatasetMessage, SessionParams};
+use certified_dp::joint;
+use certified_dp::memory::TrackingAllocator;
+use certified_dp::monomial::Monomial;
+use certified_dp::noise::{self, Mechanism};
+use certified_dp::predicate::{self, Predicate, Query};
+use certified_dp::schema::Schema;
+use certified_dp::sensitivity;
+use certified_dp::session::{Parameters, SavedSession};
+use certified_dp::profile::{self, Profile};
+use certified_dp::queries::{self, NamedAnswer, NamedQuery};
+use certified_dp::recording;
+use certified_dp::result::{self, QueryResult};
+use certified_dp::protocol::verifier::{VerifierState, verifier_init, verifier_setup, verifier_honest_commitment_phase, verifier_dishonest_commitment_phase, verifier_sketch_phase, verifier_cut_and_choose_phase, verifier_update_phase, verifier_randomness_phase, verifier_resume_randomness_phase, verifier_beacon_randomness_phase, verifier_randomness_phase_combine, verifier_generate_query, verifier_send_query, verifier_check_query, verifier_send_batch, verifier_check_batch};
+use certified_dp::signature::{self, SigningKey, VerifyingKey};
+use certified_dp::sketch::{self, Attribute};
+use certified_dp::snapshot::{self, SnapshotId};
+#[cfg(feature = "grpc")]
+use certified_dp::grpc::GrpcStream;
+#[cfg(feature = "quic")]
+use certified_dp::quic::QuicStream;
+use certified_dp::net::NetStream;
+use certified_dp::tls::{self, ClientConfig};
+use certified_dp::transport::{self, Transport};
+
+/// Track heap usage so the peak memory of each phase can be reported
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
+
+/// Append verified encrypted answers to the file passed on to the analyst, one JSON record per line
+fn save_encrypted_answers(path: &Path, g: RistrettoPoint, snapshot: SnapshotId, epoch: u64, answers: Vec<SavedAnswer>) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for answer in answers {
+        serde_json::to_writer(&mut file, &analyst::AnswerRecord { g, snapshot, epoch, answer })?;
+        writeln!(file)?;
     }
+    Ok(())
 }
 
-///
-/// -- COMMITMENT PHASE --
-///
+/// Prompt the analyst on stderr and read a line from stdin, None once it ends
+fn prompt(text: &str) -> Result<Option<String>> {
+    eprint!("{}", text);
+    let mut line = String::new();
+    let read = io::stderr().flush().and_then(|()| io::stdin().lock().read_line(&mut line))
+        .map_err(|e| Error::Config(format!("Could not read the prompt: {}", e)))?;
+    Ok((read > 0).then_some(line))
+}
 
-/// Honest commitment phase: read commitment map from prover.
-fn verifier_honest_commitment_phase<T: PrimInt + Hash>(state: &mut VerifierState<T>, stream: &mut TcpStream)
-where T: Eq + Hash + DeserializeOwned
-{
-    let m: CommitmentMapMessage<T> = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
+/// Main function to run the verifier protocol.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("answer_key").args(["analyst_key", "elgamal_key"])))]
+#[command(group(ArgGroup::new("named").args(["queries", "interactive"])))]
+struct Args {
+    // number of elements in the database
+    #[arg(long)]
+    db_size: u32,
 
-    state.monomial_commitments = m.commitment_map;
-}
+    // max monomial degree
+    #[arg(long)]
+    max_degree: u32,
 
-/// Tree-based structure of product sigma protocols to verify commitments to database entries.
-pub struct MonomialVerifierTreeNode {
-    pub commitment: Option<RistrettoPoint>,
-    pub product_sigma_verifier: Option<product_sigma::Verifier>,
-    pub children: Vec<Box<MonomialVerifierTreeNode>>,
-}
+    // differential privacy epsilon
+    #[arg(long)]
+    epsilon: f32,
 
-/// Based on a tree of product sigma commitment nodes, generate a matching tree of challenges to send to the prover.
-fn gen_challenge_tree<T: PrimInt + Hash>(state: &mut VerifierState<T>, curr_comm_node: &MonomialCommitmentTreeNode, curr_verifier_node: &mut MonomialVerifierTreeNode, curr_challenge_node: &mut MonomialChallengeTreeNode) {
-    if let Some(comm) = &curr_comm_node.commitment {
-        curr_verifier_node.commitment = Some(comm.clone());
-    }
+    // (optional) differential privacy delta
+    #[arg(long, default_value = None)]
+    delta: Option<f32>,
 
-    if let Some(sigma_comm) = &curr_comm_node.product_sigma_commitment {
-        let (sigma_verifier, sigma_challenge) = product_sigma::challenge(&mut state.rng, &sigma_comm);
-        curr_verifier_node.product_sigma_verifier = Some(sigma_verifier);
-        curr_challenge_node.product_sigma_challenge = Some(sigma_challenge);
-    }
+    // how epsilon and delta set the number of noise coins: approximate, each query (epsilon, delta)-DP on its own, or zcdp or renyi, sizing the noise so the session's --num-queries queries together are (num-queries * epsilon, delta)-DP with fewer coins, which needs --progressive (must match the prover)
+    #[arg(long, value_parser = calibration::parse_calibration, default_value = "approximate")]
+    calibration: Calibration,
 
-    for child in &curr_comm_node.children {
-        let mut child_verifier = MonomialVerifierTreeNode {
-            commitment: None,
-            product_sigma_verifier: None,
-            children: Vec::new(),
-        };
-        let mut child_challenge = MonomialChallengeTreeNode {
-            product_sigma_challenge: None,
-            children: Vec::new(),
-        };
-        gen_challenge_tree(state, child, &mut child_verifier, &mut child_challenge);
-        curr_verifier_node.children.push(Box::new(child_verifier));
-        curr_challenge_node.children.push(Box::new(child_challenge));
-    }
-}
+    // (optional) mechanism combining the verified noise coins into noise: binomial, their sum, or discrete-laplace, the difference of two geometric variables built from products of them (must match the prover)
+    #[arg(long, value_parser = noise::parse_mechanism, default_value = "binomial")]
+    noise: Mechanism,
 
-/// Recursively verify the response tree of sigma protocol nodes generated by the prover.
-fn verify_response_tree(state: &pedersen::PublicParams, curr_verifier_node: &mut MonomialVerifierTreeNode, curr_response_node: &MonomialResponseTreeNode) -> bool {
+    // (optional) largest L1 sensitivity of the session's queries, the sum of the absolute values of their coefficients: the binomial noise flips its square times the coins, and the discrete Laplace noise spends epsilon over it on each unit (must match the prover)
+    #[arg(long, value_parser = sensitivity::parse_sensitivity, default_value_t = sensitivity::DEFAULT_SENSITIVITY)]
+    sensitivity: u32,
 
-    let mut sigma_verified = true;
+    // (optional) confidence level of the interval reported with each answer, which holds the true answer with this probability
+    #[arg(long, value_parser = result::parse_confidence, default_value_t = result::DEFAULT_CONFIDENCE)]
+    confidence: f64,
 
-    if let Some(sigma_verifier) = &mut curr_verifier_node.product_sigma_verifier {
-        sigma_verified = product_sigma::verify(state, sigma_verifier, curr_response_node.product_sigma_response.as_ref().unwrap());
-        if !sigma_verified {
-            eprintln!("ERROR: Product sigma verification failed");
-        }
-    }
+    // (optional) total epsilon the session's queries may spend; a session whose --num-queries would exceed it is not started
+    #[arg(long, value_parser = epoch::parse_budget)]
+    budget: Option<f64>,
 
-    for (i, child) in curr_verifier_node.children.iter_mut().enumerate() {
-        sigma_verified &= verify_response_tree(state, child, &curr_response_node.children[i]);
-    }
+    // (optional) total delta the session's queries may spend, at the session's delta per query
+    #[arg(long, value_parser = budget::parse_delta)]
+    budget_delta: Option<f64>,
 
-    sigma_verified
-}
+    // composition of the queries' privacy losses against the budget: basic, summing them, or advanced:SLACK, trading the slack delta for an epsilon growing with the square root of the queries
+    #[arg(long, value_parser = budget::parse_composition, default_value = "basic")]
+    composition: Composition,
 
-/// Helper to recursively extract monomials from the verifier tree and insert into a hashmap.
-fn extract_monomials<T: PrimInt + Hash>(verifier_node: &MonomialVerifierTreeNode, curr_tag: T, dimension: u32, element_commitment_map: &mut HashMap<T, RistrettoPoint>) {
-    match verifier_node.commitment {
-        None => {},
-        Some(c) => {
-            element_commitment_map.insert(curr_tag, c);
-        }
-    }
+    // sparsity -- aka max coefficients in query polynomial
+    #[arg(long)]
+    sparsity: u32,
 
-    let offset = T::zero().count_zeros() - curr_tag.leading_zeros();
-    for (i, verifier_child) in verifier_node.children.iter().enumerate() {
-        let new_tag = curr_tag | (T::one() << (offset as usize + i));
-        extract_monomials(verifier_child, new_tag, dimension, element_commitment_map);
-    }
-}
+    // prover address
+    #[arg(long)]
+    prover_address: String,
 
-/// Generate a map of monomials from the verifier tree.
-fn gen_monomial_map<T: PrimInt + Hash>(verifier_trees: &Vec<MonomialVerifierTreeNode>, dimension: u32, commitment_map: &mut HashMap<T, RistrettoPoint>) {
+    // dimension
+    #[arg(long, default_value_t = max_dimension::<DataT>())]
+    dimension: u32,
 
-    for verifier_root in verifier_trees {
-        let mut element_commitment_map: HashMap<T, RistrettoPoint> = HashMap::new();
-        extract_monomials(verifier_root, T::zero(), dimension, &mut element_commitment_map);
+    // (optional) bits in each database entry, 8, 16, 32, 64, or 128, which must be the prover's
+    #[arg(long, value_parser = config::parse_entry_width)]
+    entry_width: Option<u32>,
 
-        for (k, v) in element_commitment_map {
-            if commitment_map.contains_key(&k) {
-                let c = commitment_map.get(&k).unwrap();
-                commitment_map.insert(k, c + v);
-            } else {
-                commitment_map.insert(k, v);
-            }
-        }
-    }
-}
+    // (optional) skip dishonest commitment phase if we're measuring something else
+    #[arg(long, default_value_t = false)]
+    skip_dishonest: bool,
 
-/// Dishonest commitment phase: read bit sigma and product sigma commitment messages from prover and generate matching challenges.
-fn verifier_dishonest_commitment_phase<T>(state: &mut VerifierState<T>, stream: &mut TcpStream, db_size: u32, dimension: u32) -> bool
-where T: PrimInt + Eq + Hash + DeserializeOwned
-{
-    // run challenge phase for each incoming commitment
+    // (optional) number of queries to execute and average runtime over
+    #[arg(long, default_value_t = 100)]
+    num_queries: u32,
 
-    let mut db_bit_sigma_verifiers: Vec<Vec<bit_sigma::Verifier>> = Vec::new();
-    let mut monomial_product_sigma_verifiers: Vec<MonomialVerifierTreeNode> = Vec::new();
+    // (optional) send the queries in batches of this many, each batch in one message, and check all of a batch's answers with a single random combination of them (must match the prover)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["analyst_key", "elgamal_key", "distinct", "progressive"])]
+    batch: u32,
 
-    let mut challenge_messages = Vec::new();
+    // (optional) spot-check the honest commitment phase, auditing this fraction of the entries
+    #[arg(long, value_parser = audit::parse_rate)]
+    spot_check: Option<f64>,
 
-    for _i in 0..db_size {
-        //eprintln!("  challenging entry     {}/{}", _i+1, db_size);
+    // (optional) verify only this fraction of the dishonest commitment phase proofs, sampled in secret
+    #[arg(long, value_parser = audit::parse_rate)]
+    audit_rate: Option<f64>,
 
-        let mut element_bit_sigma_verifiers: Vec<bit_sigma::Verifier> = Vec::new();
-        let mut element_bit_sigma_challenges: Vec<bit_sigma::Challenge> = Vec::new();
+    // (optional) certify the dishonest commitment phase by cut-and-choose with this many encodings per entry
+    #[arg(long, value_parser = cut_and_choose::parse_encodings, conflicts_with = "audit_rate")]
+    cut_and_choose: Option<u32>,
 
-        let bit_sigma_comm_m: BitSigmaCommitmentMessage = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
+    // (optional) expect the prover to update the database after the commitment phases, verifying only the changed rows (must match the prover)
+    #[arg(long, default_value_t = false)]
+    update: bool,
 
-        for j in 0..dimension {
-            let (sigma_verifier, sigma_challenge) = bit_sigma::challenge(&mut state.rng, &bit_sigma_comm_m.commitments[j as usize]);
-            element_bit_sigma_verifiers.push(sigma_verifier);
-            element_bit_sigma_challenges.push(sigma_challenge);
-        }
-        db_bit_sigma_verifiers.push(element_bit_sigma_verifiers);
+    // (optional) expect query answers encrypted to this analyst public key, in hex; they are verified but not learned
+    #[arg(long, value_parser = analyst::parse_public_key)]
+    analyst_key: Option<analyst::PublicKey>,
 
-        challenge_messages.push(serde_json::to_vec(&BitSigmaChallengeMessage {
-            challenges: element_bit_sigma_challenges
-        }).unwrap());
+    // (optional) expect commitments under exponential ElGamal for this analyst public key, in hex; only the query handles are revealed
+    #[arg(long, value_parser = analyst::parse_public_key)]
+    elgamal_key: Option<analyst::PublicKey>,
 
-        if dimension == 1 {
-            continue;
-        }
+    // (optional) append the verified encrypted answers to this file, for the analyst to decrypt
+    #[arg(long, requires = "answer_key")]
+    encrypted_answers: Option<PathBuf>,
 
-        let mut verifier_root = MonomialVerifierTreeNode {
-            commitment: None,
-            product_sigma_verifier: None,
-            children: Vec::new(),
-        };
+    // (optional) write a certificate of each verified answer to this directory, as query-N.json for the Nth query, which anyone holding the session's Pedersen parameters can re-verify with `analyst verify-certificate`
+    #[arg(long, conflicts_with_all = ["answer_key", "distinct"])]
+    query_certificates: Option<PathBuf>,
 
-        let mut challenge_root = MonomialChallengeTreeNode {
-            product_sigma_challenge: None,
-            children: Vec::new(),
-        };
+    // (optional) sign the guarantee of each query certificate with this signing key, in hex, vouching that its answer was released under the session's epsilon and delta
+    #[arg(long, value_parser = signature::parse_signing_key, requires = "query_certificates")]
+    release_key: Option<SigningKey>,
 
-        let comm_node: MonomialCommitmentTreeNode = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
+    // (optional) snapshot ID, in hex, that the last commitment phase must certify before any query is sent
+    #[arg(long, value_parser = snapshot::parse_snapshot)]
+    snapshot: Option<SnapshotId>,
 
-        gen_challenge_tree(state, &comm_node, &mut verifier_root, &mut challenge_root);
-        monomial_product_sigma_verifiers.push(verifier_root);
+    // (optional) audit this one of the prover's named datasets (required if the prover hosts named datasets)
+    #[arg(long, value_parser = dataset::parse_name)]
+    dataset: Option<String>,
 
-        challenge_messages.push(serde_json::to_vec(&challenge_root).unwrap());
-    }
+    // (optional) charge the session's queries to this analyst's own budget (required if the prover sets --analyst-budget, along with an --auth-key the prover binds to the analyst)
+    #[arg(long, value_parser = epoch::parse_analyst)]
+    analyst: Option<String>,
 
-    for msg in challenge_messages {
-        write_to_stream(
-            stream, &msg
-        );
-    }
+    // (optional) persona to run as: analyst, or auditor to verify without learning answers (requires an analyst or ElGamal key to send queries)
+    #[arg(long, value_parser = profile::parse_profile, default_value = "analyst")]
+    profile: Profile,
 
-    let mut success = true;
+    // (optional) number of verifiers jointly flipping the noise coins, which are unbiased if any one of them is honest (must match the prover and the other verifiers)
+    #[arg(long, value_parser = joint::parse_verifiers, default_value_t = 1)]
+    verifiers: u32,
 
-    for i in 0..db_size as usize {
-        //eprintln!("  verifying entry     {}/{}", i+1, db_size);
+    // (optional) join another verifier's session as a co-verifier, only to flip the noise coins with it; start after that verifier
+    #[arg(long, default_value_t = false, conflicts_with_all = ["dataset", "analyst"])]
+    co_verifier: bool,
 
-        let resp_m: BitSigmaResponseMessage = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
+    // (optional) flip and challenge the noise coins with the first round of this file of drand beacon rounds published after the prover commits to them, instead of this verifier's own coins (must match the prover)
+    #[arg(long, value_parser = beacon::parse_beacon, conflicts_with_all = ["verifiers", "co_verifier"])]
+    beacon: Option<Beacon>,
 
-        let _start = Instant::now();
-        for (j, resp) in resp_m.responses.iter().enumerate() {
-            let sigma_verified = bit_sigma::verify(&state.pedersen_pp, &mut db_bit_sigma_verifiers[i][j], &resp);
-            if !sigma_verified {
-                eprintln!("ERROR: Bit sigma verification failed");
-                success = false;
-                break;
-            }
-        }
-        if !success {
-            break;
-        }
-        state.comm_verify_duration += _start.elapsed();
+    // (optional) as the auditor of a prover running `certify`, sign the certificate of the commitment phases with this signing key, in hex, and end the session there (requires --profile auditor)
+    #[arg(long, value_parser = signature::parse_signing_key, conflicts_with_all = ["update", "co_verifier"])]
+    certify: Option<SigningKey>,
 
-        if dimension == 1 {
-            continue;
-        }
+    // (optional) start from this certificate, written by a prover running `certify`, instead of running the commitment phases
+    #[arg(long, requires = "auditor_key", conflicts_with_all = ["certify", "update", "co_verifier"])]
+    certificate: Option<PathBuf>,
 
-        let resp_node: MonomialResponseTreeNode = serde_json::from_slice(
-            &read_from_stream(stream)
-        ).unwrap();
+    // (optional) verifying key, in hex, of the auditor whose signature the certificate must carry
+    #[arg(long, value_parser = signature::parse_verifying_key)]
+    auditor_key: Option<VerifyingKey>,
 
-        if !verify_response_tree(&state.pedersen_pp, &mut monomial_product_sigma_verifiers[i], &resp_node) {
-            eprintln!("ERROR: Monomial product sigma verification failed");
-            success = false;
-            break;
-        }
-        if !success {
-            break;
-        }
-    }
-        
-    write_to_stream(
-        stream, &serde_json::to_vec(&VerifierCheckMessage {success}).unwrap()
-    );
-    
-    if !success {
-        return false;
-    }
+    // (optional) once the session succeeds, save its verified commitments, noise commitment, and parameters to this file, from which a later session can --resume
+    #[arg(long, conflicts_with_all = ["certify", "co_verifier"])]
+    save_session: Option<PathBuf>,
 
-    if dimension == 1 {
-        let mut sum = RistrettoPoint::default();
-        for i in 0..db_size {
-            sum += db_bit_sigma_verifiers[i as usize][0].b_comm;
-        }
-        state.monomial_commitments.insert(T::one(), sum);
-    } else {
-        gen_monomial_map(&monomial_product_sigma_verifiers, dimension, &mut state.monomial_commitments);
-    }
-    
-    true
-}
+    // (optional) resume the session saved to this file, sending further queries on its commitments and noise without rerunning the commitment and randomness phases (the prover must still be serving it with --resumable)
+    #[arg(long, conflicts_with_all = ["certify", "certificate", "update", "co_verifier"])]
+    resume: Option<PathBuf>,
+
+    // (optional) abort the protocol, telling the prover why, if a phase runs past this limit, as the phase and a duration, e.g. dishonest-commitment=2h; repeat for more phases
+    #[arg(long, value_parser = deadline::parse_deadline)]
+    deadline: Vec<(Phase, Duration)>,
+
+    // (optional) when the connection drops during the randomness phase, reconnect to the prover and resume from the last checkpoint of the coins flipped, up to this many times (must match the prover)
+    #[arg(long, default_value_t = 0, conflicts_with_all = ["beacon", "verifiers", "co_verifier"])]
+    reconnect: u32,
+
+    // (optional) check a sketch of this attribute, as the comma separated positions of its bits in an entry, e.g. 0,1,2, after the dishonest commitment phase, and count its distinct values in every query instead of a random polynomial (must match the prover)
+    #[arg(long, value_parser = sketch::parse_attribute, conflicts_with_all = ["skip_dishonest", "cut_and_choose", "audit_rate", "update", "certify", "certificate", "save_session", "resume", "co_verifier"])]
+    distinct: Option<Attribute>,
+
+    // (optional) JSON schema file of the database's attributes, as the prover's, naming the bits of each query's monomials
+    #[arg(long)]
+    schema: Option<PathBuf>,
 
-///
-/// -- RANDOMNESS PHASE --
-///
+    // (optional) make every query count the entries matching this predicate over the schema's attributes, or the bare entry bits xN, in place of a random polynomial of --sparsity monomials, e.g. 'smoker & (region=north | !age=70)' or 'x3 & !x5'
+    #[arg(long, value_parser = predicate::parse_predicate, conflicts_with = "distinct")]
+    predicate: Option<Predicate>,
 
-/// Randomness phase: coin flip and bit sigma challenge generation; send results back to prover.
-fn verifer_randomness_phase_challenge<T: PrimInt + Hash>(state: &mut VerifierState<T>, stream: &mut TcpStream) {
+    // (optional) ask the named queries in this JSON file in order, each a "predicate" as --predicate takes it or "coefficients" weighing several, e.g. [{"name": "smokers", "predicate": "smoker"}, {"name": "north less south", "coefficients": {"region=north": 1, "region=south": -1}}], one per query of the session, whose --num-queries it sets (the prover's must match)
+    #[arg(long, requires = "answers", conflicts_with_all = ["predicate", "distinct", "answer_key"])]
+    queries: Option<PathBuf>,
 
-    let _cf_start = Instant::now();
-    state.player_b = state.rng.gen_range(0..2);
-    state.randomness_coin_flip_agg_duration += _cf_start.elapsed();
+    // (optional) read each query from the analyst at a prompt, as a predicate or a list of weighted monomials, show what it compiles to, and ask it once submitted, keeping the session open until the analyst quits or --num-queries are asked
+    #[arg(long, default_value_t = false, conflicts_with_all = ["predicate", "queries", "distinct", "batch", "answer_key"])]
+    interactive: bool,
 
-    let m: ProverRandomnessComm = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
+    // (optional) write the verified answers to the queries of --queries or the prompt to this file, one JSON line each with its name and the count or sum it stands for
+    #[arg(long, requires = "named")]
+    answers: Option<PathBuf>,
 
-    let _start = Instant::now();
-    let (sigma_verifier, sigma_challenge) = bit_sigma::challenge(&mut state.rng, &m.commitment);
+    // schema of the database, from --schema, which main loads
+    #[arg(skip)]
+    database_schema: Schema,
 
-    state.sigma_verifier = sigma_verifier;
-    state.randomness_bit_sigma_verify_duration += _start.elapsed();
+    // (optional) give each query its own noise, flipping only the first query's coins in the randomness phase and each later query's in the query phase just before it is sent (must match the prover)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["beacon", "co_verifier", "save_session", "resume"])]
+    progressive: bool,
 
-    write_to_stream(
-        stream, &serde_json::to_vec(&VerifierRandomnessChallenge {
-            player_b: state.player_b,
-            sigma_challenge
-        }).unwrap()
-    );
+    // (optional) encoding of the session's messages, json or the more compact bincode, which the prover agrees to at setup (a co-verifier must match its verifier)
+    #[arg(long, value_parser = messages::parse_wire_format, default_value = "json")]
+    wire_format: WireFormat,
+
+    // (optional) gzip frames over a few kilobytes in both directions, e.g. the commitment trees, which the prover agrees to at setup (a co-verifier must match its verifier)
+    #[arg(long, default_value_t = false)]
+    compress: bool,
+
+    // (optional) largest message, in MiB, accepted from the prover, past which the session fails; the commitment trees grow with the dimension and maximum degree
+    #[arg(long, default_value_t = transport::DEFAULT_MAX_FRAME_SIZE >> 20)]
+    max_message_size: usize,
+
+    // (optional) append every frame sent and received to this file, one JSON line each with its direction, session, phase, and time, for debugging, reproducing, or auditing the sessions
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    // (optional) connect to the prover over TLS, accepting only a certificate for the prover address signed by the CA in this PEM file
+    #[arg(long)]
+    tls_ca: Option<PathBuf>,
+
+    // (optional) PEM certificate chain to present to a prover that requires verifiers to have one (requires --tls-key and --tls-ca)
+    #[arg(long, requires_all = ["tls_key", "tls_ca"])]
+    tls_cert: Option<PathBuf>,
+
+    // (optional) PEM private key of the TLS certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    // (optional) answer the challenge of a prover that authenticates its verifiers with this pre-shared key, 32 bytes in hex, which both also mix into the keys the session's frames are authenticated with, so an attacker without it cannot pose as either
+    #[arg(long, value_parser = auth::parse_preshared_key)]
+    auth_psk: Option<PresharedKey>,
+
+    // (optional) answer the challenge of a prover that authenticates its verifiers with a signature under this signing key, in hex, whose verifying key the prover authorizes
+    #[arg(long, value_parser = signature::parse_signing_key, conflicts_with = "auth_psk")]
+    auth_key: Option<SigningKey>,
+
+    // (optional) connect to a prover serving gRPC with --grpc, running the session as one bidirectional stream of frames
+    #[cfg(feature = "grpc")]
+    #[arg(long, default_value_t = false, conflicts_with = "tls_ca")]
+    grpc: bool,
+
+    // (optional) connect to a prover serving QUIC with --quic, sending each frame on a stream of its own (requires --tls-ca)
+    #[cfg(feature = "quic")]
+    #[arg(long, default_value_t = false, requires = "tls_ca")]
+    quic: bool,
+
+    // (optional) evaluate sparsity experiment
+    #[cfg(feature = "experiments")]
+    #[arg(long, default_value_t = false)]
+    sparsity_experiment: bool,
 }
 
-/// Randomness phase: check prover responses
-fn verifier_randomness_phase_check<T: PrimInt + Hash>(state: &mut VerifierState<T>, stream: &mut TcpStream) -> Option<RistrettoPoint> {
-
-    let resp_msg: ProverRandomnessResponse = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-
-    let _cf_start = Instant::now();
-    if state.player_b == 0 {
-        if resp_msg.final_commitment != state.sigma_verifier.b_comm{
-            eprintln!("ERROR: player_b = 0, final_commitment != b_comm");
-            write_to_stream(
-                stream, &serde_json::to_vec(&VerifierCheckMessage {success: false}).unwrap()
-            );
-            return None;
-        }
-    } else {
-        if resp_msg.final_commitment != state.C1 + state.sigma_verifier.b_comm.neg() {
-            eprintln!("ERROR: player_b = 1, final_commitment != C1 + dealer_b_comm.neg()");
-            write_to_stream(
-                stream, &serde_json::to_vec(&VerifierCheckMessage {success: false}).unwrap()
-            );
-            return None;
-        }
-    }
-    state.randomness_coin_flip_agg_duration += _cf_start.elapsed();
-
-    let _start = Instant::now();
-    let sigma_verified = bit_sigma::verify(&state.pedersen_pp, &mut state.sigma_verifier, &resp_msg.sigma_response);
-    state.randomness_bit_sigma_verify_duration += _start.elapsed();
-
-    write_to_stream(
-        stream, &serde_json::to_vec(&VerifierCheckMessage {success: sigma_verified}).unwrap()
-    );
-
-    if sigma_verified {
-        Some(resp_msg.final_commitment)
-    } else {
-        None
-    }    
+/// Connection to the prover, over TLS if the verifier trusts a CA for it
+type Connection = Box<dyn Transport + Send>;
+
+impl Args {
+    /// Largest frame accepted from the prover, in bytes
+    fn max_frame_size(&self) -> usize {
+        self.max_message_size.saturating_mul(1 << 20)
+    }
 }
 
-/// Randomness phase: adjust commitment based on the adjustment factor
-fn verifier_randomness_phase_adjust<T: PrimInt + Hash>(state: &mut VerifierState<T>, db_size: u32, epsilon: f32, delta: Option<f32>) {
-    let adjustment_factor = Scalar::from((get_n(db_size, epsilon, delta)/2) as u32);
-    state.randomness_bit_comm -= pedersen::commit_with_r(&adjustment_factor, &state.CPROOF, &state.pedersen_pp);
+/// TLS configuration from the verifier's options, if it connects over TLS
+fn tls_config(args: &Args) -> Result<Option<Arc<ClientConfig>>> {
+    let identity = args.tls_cert.as_deref().zip(args.tls_key.as_deref());
+    args.tls_ca.as_ref().map(|ca| tls::client_config(ca, identity)).transpose()
 }
 
-///
-/// -- QUERYING PHASE --
-///
+/// Verifier side of the protocol, driven phase by phase by `ProtocolDriver`, over entries of type `T`
+struct Verifier<T: Entry> {
+    args: Args,
+    tls: Option<Arc<ClientConfig>>,
+    state: VerifierState<T>,
+    /// Current number of database entries, which the update phase can change from `--db-size`
+    db_size: u32,
+    query_coefficients: HashMap<Monomial<T>, Scalar>,
+    /// Queries prepared for the next batch, with `--batch`
+    batch_queries: Vec<HashMap<Monomial<T>, Scalar>>,
+    /// Query compiled from `--predicate`, if any, which every query then asks
+    predicate_query: Option<Query<T>>,
+    /// Queries of `--queries`, asked in order
+    named_queries: Vec<NamedQuery<T>>,
+    /// Number of queries prepared so far
+    asked: usize,
+    homomorphic_duration: Duration,
+    check_duration: Duration,
+    /// Certificate the sessions start from, if any
+    certificate: Option<Certificate<T>>,
+    /// Saved session this session resumes, if any
+    saved: Option<SavedSession<T>>,
+    /// Whether a query has been checked on the current noise, so that with `--progressive` the next one flips its own
+    noise_spent: bool,
+    /// Privacy the session's queries have spent
+    accountant: Accountant,
+}
 
-/// Generate a random query polynomial with a given sparsity, choosing random monomials and coefficients
-fn verifier_generate_query<T: PrimInt + Eq + Hash + Copy>(state: &mut VerifierState<T>, sparsity: u32) -> HashMap<T, Scalar> {
+impl<T: Entry> Verifier<T> {
+    fn answer_mode(&self) -> AnswerMode {
+        AnswerMode::from_keys(self.args.analyst_key, self.args.elgamal_key)
+    }
 
-    let mut coefficients: HashMap<T, Scalar> = HashMap::new();
-    for _ in 0..sparsity {
-        if sparsity > state.monomial_commitments.len() as u32 {
-            eprintln!("ERROR: Query sparsity ({}) to large for monomial commitments size ({})", sparsity, state.monomial_commitments.len());
+    /// Join another verifier's session as a co-verifier: take part in its setup and randomness phases only, checking
+    /// every noise coin, and print the noise commitment so it can be compared with the one the session's verifier prints
+    fn co_verify(&mut self) -> Result<()> {
+        let stream = self.connect().inspect_err(|e| eprintln!("ERROR: Failed to connect: {}", e))?;
+        let mut stream = Framed::new(stream, self.args.max_frame_size());
+        // the session's verifier already agreed the wire format and compression with the prover
+        stream.set_wire_format(self.args.wire_format, self.args.compress);
+        recording::start(Side::Verifier, 1);
+        for phase in [Phase::Setup, Phase::Randomness] {
+            eprintln!("{} phase start", phase.name());
+            recording::set_phase(phase);
+            let outcome = match phase {
+                Phase::Setup => self.setup(&mut stream),
+                _ => self.randomness(&mut stream),
+            };
+            outcome.inspect_err(|e| eprintln!("ERROR: {} phase failed: {}", phase.name(), e))?;
+            eprintln!("{} phase complete", phase.name());
         }
+        Ok(())
+    }
 
-        let mut random_id = state.monomial_commitments.keys().choose(&mut state.rng).unwrap();
-        while coefficients.contains_key(random_id) {
-            random_id = state.monomial_commitments.keys().choose(&mut state.rng).unwrap();
-        }
-        let coeff = Scalar::random(&mut state.rng);
-        coefficients.insert(*random_id, coeff);
+    /// Record the snapshot the last commitment phase certified, which queries are then sent against
+    fn take_snapshot(&mut self) {
+        self.state.snapshot = SnapshotId::of(self.state.monomial_commitments.iter().map(|(&k, &v)| (k, v)));
+        eprintln!("  snapshot {}", self.state.snapshot);
     }
 
-    coefficients
-}
+    /// Send the queries prepared since the last round as one batch, and check the prover's answers to them at once
+    fn query_batch(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let queries = std::mem::take(&mut self.batch_queries);
+        // a query repeated within the batch is answered as its first occurrence, and charged once
+        let mut digests = HashSet::new();
+        for query_coefficients in &queries {
+            let digest = cache::query_digest(query_coefficients, false);
+            if !self.state.answered.contains_key(&digest) && digests.insert(digest) {
+                let delta = calibration::delta(self.args.db_size, self.args.delta) as f64;
+                self.accountant.charge(1, self.args.epsilon as f64, delta).map_err(Error::Config)?;
+            }
+        }
 
-fn verifier_send_query<T: PrimInt + Hash>(_state: &mut VerifierState<T>, stream: &mut TcpStream, query_coefficients: &HashMap<T, Scalar>)
-where T: Eq + Hash + Clone + Serialize
-{
-    let m = QueryMessage::<T> {
-        coefficients: query_coefficients.clone()
-    };
-    write_to_stream(
-        stream, &serde_json::to_vec(&m).unwrap()
-    );
-}
+        verifier_send_batch(&mut self.state, stream, &queries)?;
+        let (query_results, homomorphic_duration, check_duration) = verifier_check_batch(&mut self.state, stream, &queries)?;
+        let first = self.asked - query_results.len();
+        for (i, query_result) in query_results.iter().enumerate() {
+            self.log_result(first + i, query_result)?;
+        }
+        self.save_certificates(first, &query_results)?;
+        let (epsilon, delta) = self.accountant.spent();
+        eprintln!("  spent ε = {:.4}, δ = {:.3e} over {} queries", epsilon, delta, self.accountant.queries());
 
-/// Having received a response from the prover, verify the query commitments
-fn verifier_check_query<T: PrimInt + Hash>(state: &mut VerifierState<T>, stream: &mut TcpStream, query_coefficients: &HashMap<T, Scalar>) -> (bool, Duration, Duration)
-where T: Eq + Hash + Display
-{
-    let mut query_comm = state.randomness_bit_comm;
+        self.homomorphic_duration += homomorphic_duration;
+        self.check_duration += check_duration;
+        Ok(())
+    }
+
+    /// Monomials of a query by what their bits say, each with its coefficient if `signed` and it is a small integer
+    /// other than 1, as a predicate's are, e.g. -1 for the product with a negated literal
+    fn describe_query(&self, coefficients: &HashMap<Monomial<T>, Scalar>, signed: bool) -> String {
+        let mut monomials: Vec<_> = coefficients.iter().collect();
+        monomials.sort_by_key(|(m, _)| **m);
+        let described: Vec<_> = monomials.into_iter().map(|(m, c)| match (signed, result::to_i64(c)) {
+            (true, Some(c)) if c != 1 => format!("{}*{}", c, self.args.database_schema.describe_monomial(*m)),
+            _ => self.args.database_schema.describe_monomial(*m),
+        }).collect();
+        described.join(", ")
+    }
+
+    /// Read the analyst's next query at the prompt, until one compiles within the session's dimension and sensitivity
+    /// and the analyst submits it; None once the analyst quits or stdin ends
+    fn prompt_query(&self) -> Result<Option<NamedQuery<T>>> {
+        let header = format!("query {}/{}> ", self.asked + 1, self.args.num_queries);
+        loop {
+            let Some(line) = prompt(&header)? else {
+                return Ok(None);
+            };
+            match line.trim() {
+                "" => continue,
+                "quit" | "exit" => return Ok(None),
+                "help" => {
+                    eprintln!("  type a predicate, e.g. smoker & !region=north, or weighted monomials, e.g. 2*smoker*region=north, -1*x3; quit to end the session");
+                    continue;
+                },
+                _ => {},
+            }
+            let named = match queries::parse_query::<T>(&line, &self.args.database_schema, self.args.max_degree) {
+                Ok(named) => named,
+                Err(e) => {
+                    eprintln!("  {}", e);
+                    continue;
+                },
+            };
+            // bare entry bits may lie past the committed ones
+            if let Some(bit) = named.query.coefficients.keys().flat_map(|m| m.variables()).find(|&bit| bit >= self.args.dimension as usize) {
+                eprintln!("  bit {} is beyond the dimension {}", bit, self.args.dimension);
+                continue;
+            }
+            if named.sensitivity > self.args.sensitivity as u64 {
+                eprintln!("  sensitivity {} is beyond the session's {}", named.sensitivity, self.args.sensitivity);
+                continue;
+            }
 
-    let start_homomorphic = Instant::now();
-    for monomial_id in query_coefficients.keys() {
-        if !state.monomial_commitments.contains_key(monomial_id) {
-            eprintln!("ERROR: Monomial ID {} not found in monomial commitment map", monomial_id);
-            return (false, Duration::from_secs(0), Duration::from_secs(0));
+            eprintln!("  query over {}", self.describe_query(&named.query.coefficients, true));
+            if let Some(constant) = result::to_i64(&named.query.constant).filter(|&c| c != 0) {
+                eprintln!("  plus {} per entry", constant);
+            }
+            let repeated = self.state.answered.contains_key(&cache::query_digest(&named.query.coefficients, false));
+            match repeated {
+                true => eprintln!("  sensitivity {}, a repeat answered as before, spending nothing", named.sensitivity),
+                false => eprintln!("  sensitivity {}, spending ε = {}", named.sensitivity, self.args.epsilon),
+            }
+            match prompt("submit? [y/N] ")? {
+                Some(answer) if matches!(answer.trim(), "y" | "yes") => return Ok(Some(named)),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
         }
+    }
 
-        let monomial_comm = state.monomial_commitments.get(monomial_id).unwrap();
-        let monomial_coefficient = query_coefficients.get(monomial_id).unwrap();
+    /// Query the `i`th query asks if it is not a random one, from `--queries` or `--predicate`, with its sensitivity
+    fn chosen_query(&self, i: usize) -> Option<(&Query<T>, u64)> {
+        match self.named_queries.get(i) {
+            Some(named) => Some((&named.query, named.sensitivity)),
+            None => self.predicate_query.as_ref().map(|query| (query, query.sensitivity())),
+        }
+    }
 
-        query_comm += monomial_coefficient * monomial_comm;
+    /// Log the result of the `i`th query, with the count or sum it stands for if it is a predicate's or from
+    /// `--queries`, and write the answer to a query from `--queries` to `--answers`
+    fn log_result(&self, i: usize, query_result: &QueryResult) -> Result<()> {
+        eprintln!("  {}", query_result);
+        if let Some(count) = self.chosen_query(i).zip(query_result.value).and_then(|((query, _), value)| query.count(value, self.db_size)) {
+            eprintln!("  count {}", count);
+        }
+        if let (Some(named), Some(path)) = (self.named_queries.get(i), &self.args.answers) {
+            queries::append_answer(path, &NamedAnswer::new(named, *query_result, self.db_size))
+                .map_err(|e| Error::Config(format!("Could not save the answer to {}: {}", path.display(), e)))?;
+        }
+        Ok(())
     }
-    let duration_homomorphic = start_homomorphic.elapsed();
 
-    let query_answer_m: QueryAnswerMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
+    /// Write the certificates of the verified answers among the results of the queries from the `first`th on to
+    /// `--query-certificates`
+    fn save_certificates(&mut self, first: usize, query_results: &[QueryResult]) -> Result<()> {
+        let Some(dir) = &self.args.query_certificates else {
+            return Ok(());
+        };
+        let mut certificates = self.state.query_certificates.as_mut().map(std::mem::take).unwrap_or_default().into_iter();
+        fs::create_dir_all(dir).map_err(|e| Error::Config(format!("Could not create {}: {}", dir.display(), e)))?;
+        for (i, _) in query_results.iter().enumerate().filter(|(_, query_result)| query_result.verified) {
+            let Some(mut certificate) = certificates.next() else {
+                break;
+            };
+            if let Some(key) = &self.args.release_key {
+                certificate.sign(key);
+            }
+            let path = dir.join(format!("query-{}.json", first + i + 1));
+            certificate.save(&path).map_err(|e| Error::Config(format!("Could not save query certificate: {}", e)))?;
+            eprintln!("  certificate saved to {}", path.display());
+        }
+        Ok(())
+    }
 
-    let query_answer = query_answer_m.answer;
-    let query_proof = query_answer_m.proof;
+    /// Flip the noise with the prover (and co-verifiers, or the beacon), centered on zero
+    fn flip_noise(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        let params = self.params();
+        let n = params.coins();
+        match &self.args.beacon {
+            Some(beacon) => verifier_beacon_randomness_phase(&mut self.state, stream, beacon, n)?,
+            None if self.args.verifiers == 1 => self.flip_resumable(stream, n)?,
+            None => verifier_randomness_phase(&mut self.state, stream, n, self.args.verifiers)?,
+        }
+        verifier_randomness_phase_combine(&mut self.state, stream, &params)?;
+        if self.args.verifiers > 1 || self.args.beacon.is_some() {
+            eprintln!("  noise commitment {}", joint::fingerprint(&self.state.randomness_bit_comm));
+        }
+        self.noise_spent = false;
+        Ok(())
+    }
 
-    // measure verification check
-    let start_verify = Instant::now();
-    let result = pedersen::verify(&query_comm, &query_answer, &query_proof, &state.pedersen_pp);
-    let duration_verify = start_verify.elapsed();
-    if result {
-        //println!("Query verified!");
-    } else {
-        println!("Query INVALID :(");
+    /// Flip the `n` noise coins with the prover alone, reconnecting and resuming from the last checkpoint each time the
+    /// connection drops, up to `--reconnect` times
+    fn flip_resumable(&mut self, stream: &mut Framed<Connection>, n: u32) -> Result<()> {
+        let mut outcome = verifier_randomness_phase(&mut self.state, stream, n, 1);
+        for attempt in 1..=self.args.reconnect {
+            let Err(Error::Io(e)) = &outcome else {
+                break;
+            };
+            eprintln!("  connection lost ({}), reconnecting to the prover ({}/{})", e, attempt, self.args.reconnect);
+            stream.reconnect(self.connect()?);
+            outcome = verifier_resume_randomness_phase(&mut self.state, stream, n);
+        }
+        outcome
     }
 
-    (result, duration_homomorphic, duration_verify)
-}
+    /// Parameters the prover must serve the session with
+    fn params(&self) -> SessionParams {
+        SessionParams {
+            db_size: self.db_size, dimension: self.args.dimension, max_degree: self.args.max_degree, epsilon: self.args.epsilon,
+            delta: self.args.delta, entry_width: max_dimension::<T>(), calibration: self.args.calibration, mechanism: self.args.noise, sensitivity: self.args.sensitivity,
+        }
+    }
 
-/// Synchronize with the prover to ensure both parties are ready to proceed.
-fn synchronize_prover(stream: &mut TcpStream) {
-    write_to_stream(
-        stream, &serde_json::to_vec(&ReadyMessage { ready: true }).unwrap()
-    );
-    let _prover_ready: ReadyMessage = serde_json::from_slice(
-        &read_from_stream(stream)
-    ).unwrap();
-}
+    /// Parameters of the session, as this verifier saw it
+    fn parameters(&self) -> Parameters {
+        Parameters {
+            dataset: self.state.dataset.clone(),
+            db_size: self.db_size,
+            dimension: self.args.dimension,
+            epsilon: self.args.epsilon,
+            delta: self.args.delta,
+            g: self.state.pedersen_pp.g,
+            h: self.state.pedersen_pp.h,
+            epoch: self.state.epoch,
+        }
+    }
 
-/// Main function to run the verifier protocol.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    // number of elements in the database
-    #[arg(long)]
-    db_size: u32,
+    /// Statement of what the session certified, as this verifier saw it
+    fn statement(&self, transcript: [u8; 32]) -> Statement {
+        Statement {
+            dataset: self.state.dataset.clone(),
+            db_size: self.db_size,
+            dimension: self.args.dimension,
+            epsilon: self.args.epsilon,
+            delta: self.args.delta,
+            g: self.state.pedersen_pp.g,
+            h: self.state.pedersen_pp.h,
+            epoch: self.state.epoch,
+            snapshot: self.state.snapshot,
+            transcript,
+        }
+    }
 
-    // differential privacy epsilon
-    #[arg(long)]
-    epsilon: f32,
+    /// Take the certificate's commitments in place of the commitment phases, once setup confirms the prover is
+    /// serving the session the certificate is for
+    fn start_from_certificate(&mut self) -> Result<()> {
+        let Some(certificate) = &self.certificate else {
+            return Ok(());
+        };
+        let expected = Statement { snapshot: certificate.statement.snapshot, ..self.statement(certificate.statement.transcript) };
+        if certificate.statement != expected {
+            return Err(Error::Config("Certificate is not for this session's parameters, generators, and epoch".to_string()));
+        }
+        self.state.monomial_commitments = certificate.commitment_map.clone();
+        self.state.snapshot = certificate.statement.snapshot;
+        eprintln!("  certified snapshot {}", self.state.snapshot);
+        Ok(())
+    }
 
-    // (optional) differential privacy delta
-    #[arg(long, default_value = None)]
-    delta: Option<f32>,
+    /// Take the saved session's commitments and noise in place of the commitment and randomness phases, once setup
+    /// confirms the prover is serving the session that was saved
+    fn resume_saved(&mut self) -> Result<()> {
+        let Some(saved) = &self.saved else {
+            return Ok(());
+        };
+        if saved.parameters != self.parameters() {
+            return Err(Error::Config("Saved session is not for this session's parameters, generators, and epoch".to_string()));
+        }
+        self.state.monomial_commitments = saved.commitment_map.clone();
+        self.state.snapshot = saved.snapshot;
+        self.state.randomness_bit_comm = saved.noise_commitment;
+        let noise = self.params().noise();
+        if saved.mechanism != self.args.noise || saved.noise_coins != noise.coins() {
+            return Err(Error::Config("Saved session is not for this session's noise".to_string()));
+        }
+        self.state.noise = noise;
+        self.state.epsilon = self.args.epsilon;
+        self.state.delta = calibration::delta(self.args.db_size, self.args.delta);
+        eprintln!("  resumed snapshot {}", self.state.snapshot);
+        Ok(())
+    }
+}
 
-    // sparsity -- aka max coefficients in query polynomial
-    #[arg(long)]
-    sparsity: u32,
+impl<T: Entry> Role for Verifier<T> {
+    type Config = Args;
+    type Stream = Connection;
 
-    // prover address
-    #[arg(long)]
-    prover_address: String,
+    const NAME: &'static str = "Verifier";
+    const SIDE: Side = Side::Verifier;
 
-    // dimension
-    #[arg(long, default_value_t = size_of::<DataT>() as u32 * 8)]
-    dimension: u32,
+    fn new(args: Args) -> Self {
+        let mut state = verifier_init();
+        state.confidence = args.confidence;
+        if args.update {
+            state.entry_commitments = Some(Vec::new());
+        }
+        if args.query_certificates.is_some() {
+            state.query_certificates = Some(Vec::new());
+        }
+        // already checked by main
+        let certificate = args.certificate.as_ref().and_then(|path| Certificate::load(path, args.auditor_key.as_ref()?).ok());
+        let saved = args.resume.as_ref().and_then(|path| SavedSession::load(path).ok());
+        let tls = tls_config(&args).ok().flatten();
+        let predicate_query = args.predicate.as_ref().and_then(|p| p.compile(&args.database_schema, args.max_degree).ok());
+        let named_queries = args.queries.as_ref().and_then(|path| queries::load(path, &args.database_schema, args.max_degree).ok()).unwrap_or_default();
+        let accountant = Accountant::new(args.composition, args.budget, args.budget_delta);
+        Verifier {
+            db_size: args.db_size,
+            args,
+            tls,
+            state,
+            query_coefficients: HashMap::new(),
+            batch_queries: Vec::new(),
+            predicate_query,
+            named_queries,
+            asked: 0,
+            homomorphic_duration: Duration::from_secs(0),
+            check_duration: Duration::from_secs(0),
+            certificate,
+            saved,
+            noise_spent: false,
+            accountant,
+        }
+    }
 
-    // (optional) skip dishonest commitment phase if we're measuring something else
-    #[arg(long, default_value_t = false)]
-    skip_dishonest: bool,
+    fn connect(&mut self) -> io::Result<Connection> {
+        let addr = self.args.prover_address.parse::<SocketAddr>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        #[cfg(feature = "grpc")]
+        if self.args.grpc {
+            return Ok(Box::new(GrpcStream::connect(addr, self.args.max_frame_size())?));
+        }
+        #[cfg(feature = "quic")]
+        if self.args.quic {
+            let config = self.tls.clone().expect("--quic requires --tls-ca");
+            return Ok(Box::new(QuicStream::connect(addr, config, self.args.max_frame_size())?));
+        }
+        Ok(Box::new(NetStream::connect(addr, self.tls.clone(), self.args.max_frame_size())?))
+    }
 
-    // (optional) number of queries to execute and average runtime over
-    #[arg(long, default_value_t = 100)]
-    num_queries: u32,
+    fn setup(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        // agree keys for the session's frames and authenticate, then name the analyst the prover charges and the dataset
+        // to audit, before the prover commits to the session
+        mac::verifier_agree(&mut self.state.rng, stream, self.args.auth_psk.as_ref())?;
+        let credential = match (&self.args.auth_psk, &self.args.auth_key) {
+            (Some(key), _) => Some(Credential::Preshared(key.clone())),
+            (None, key) => key.clone().map(Credential::Key),
+        };
+        if let Some(credential) = &credential {
+            auth::verifier_authenticate(stream, credential)?;
+        }
+        if let Some(name) = &self.args.analyst {
+            write_message(stream, &AnalystMessage { name: name.clone() })?;
+        }
+        if let Some(name) = &self.args.dataset {
+            write_message(stream, &DatasetMessage { name: name.clone() })?;
+            self.state.dataset = name.clone();
+        }
+        let params = self.params();
+        verifier_setup(&mut self.state, stream, &params)?;
+        if let Some(pk) = &self.args.elgamal_key {
+            self.state.pedersen_pp = elgamal::params(&self.state.pedersen_pp, pk);
+        }
+        self.start_from_certificate()?;
+        self.resume_saved()
+    }
 
-    // (optional) evaluate sparsity experiment
-    #[arg(long, default_value_t = false)]
-    sparsity_experiment: bool,
-}
+    fn honest_commitment(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        match self.args.spot_check {
+            Some(rate) => verifier_dishonest_commitment_phase(&mut self.state, stream, self.db_size, self.args.dimension, AuditMode::SpotCheck(rate))?,
+            None => verifier_honest_commitment_phase(&mut self.state, stream, self.db_size, self.args.dimension, self.args.max_degree)?,
+        }
+        self.take_snapshot();
+        Ok(())
+    }
 
-fn main() {
+    fn dishonest_commitment(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        // clear out the monomial commitments from the honest phase
+        self.state.monomial_commitments.clear();
+        match self.args.cut_and_choose {
+            Some(encodings) => verifier_cut_and_choose_phase(&mut self.state, stream, self.db_size, self.args.dimension, encodings)?,
+            None => {
+                let mode = match self.args.audit_rate {
+                    Some(rate) => AuditMode::Probabilistic(rate),
+                    None => AuditMode::Full,
+                };
+                verifier_dishonest_commitment_phase(&mut self.state, stream, self.db_size, self.args.dimension, mode)?
+            },
+        }
+        if let Some(attribute) = &self.args.distinct {
+            verifier_sketch_phase(&mut self.state, stream, attribute)?;
+        }
+        self.take_snapshot();
+        Ok(())
+    }
 
-    eprintln!("Running");
+    fn update(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        self.db_size = verifier_update_phase(&mut self.state, stream, self.db_size, self.args.dimension)?;
+        self.take_snapshot();
+        Ok(())
+    }
 
-    let args = Args::parse();
-    println!("\n-- Verifier --\n");
-    println!("Configuration:");
-    println!("\tDatabase size: {}", args.db_size);
-    println!("\tDimension: {}", args.dimension);
-    println!("\tEpsilon: {}", args.epsilon);
-    println!("\tDelta: {:?}", args.delta);
-    println!("\tSparsity: {}", args.sparsity);
-    println!("\tProver address: {}\n", args.prover_address);
+    fn randomness(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        self.flip_noise(stream)
+    }
 
-    // Setup
-    eprintln!("Setup phase start");
-
-    let addr = args.prover_address.parse::<SocketAddr>().unwrap();
-    let mut stream = TcpStream::connect_timeout(
-        &addr,
-        Duration::from_secs(10)
-    ).unwrap();
-
-    let mut verifier_state = verifier_setup::<DataT>(&mut stream);
-    
-    eprintln!("Setup phase complete");
-
-    // Honest Commitment Phase
-    eprintln!("Honest commitment phase start");
-   
-    synchronize_prover(&mut stream);
-    let start_honest_comm = Instant::now();
-    verifier_honest_commitment_phase(&mut verifier_state, &mut stream);
-    synchronize_prover(&mut stream);
-    let duration_honest_comm = start_honest_comm.elapsed();
-   
-    eprintln!("Honest commitment phase complete ({:?})", duration_honest_comm);
-
-    let mut duration_dishonest_comm = Duration::from_secs(0);
-    if !args.skip_dishonest {
-        // clear out the monomial commitments for the dishonest phase
-        verifier_state.monomial_commitments.clear();
-
-        // Dishonest Commitment Phase
-        eprintln!("Dishonest commitment phase start");
-    
-        synchronize_prover(&mut stream);
-        let start_dishonest_comm = Instant::now();
-        let comm_success = verifier_dishonest_commitment_phase(&mut verifier_state, &mut stream, args.db_size, args.dimension);
-        synchronize_prover(&mut stream);
-        duration_dishonest_comm = start_dishonest_comm.elapsed();
-
-        if !comm_success {
-            return;
-        }
-    
-        eprintln!("Dishonest commitment phase complete ({:?})", duration_dishonest_comm);
-    }
-    
-    // Randomness Phase
-    eprintln!("Randomness phase start");
-
-    synchronize_prover(&mut stream);
-    let start_rnd = Instant::now();
-    verifier_state.randomness_bit_comm = verifier_state.C0;
-
-    for _ in 0..get_n(args.db_size, args.epsilon, args.delta) {
-        verifer_randomness_phase_challenge(&mut verifier_state, &mut stream);
-        match verifier_randomness_phase_check(&mut verifier_state, &mut stream) {
-            Some(c) => {
-                let _agg_start = Instant::now();
-                verifier_state.randomness_bit_comm += c;
-                verifier_state.randomness_coin_flip_agg_duration += _agg_start.elapsed();
-            },
-            None => {
-                println!("ERROR: Randomness phase failed");
-                return;
+    fn prepare_query(&mut self) -> Result<bool> {
+        if self.args.interactive {
+            match self.prompt_query()? {
+                Some(named) => self.named_queries.push(named),
+                None => return Ok(false),
+            }
+        }
+        // distinct-count queries have no monomial coefficients
+        if self.args.distinct.is_none() {
+            let chosen = self.chosen_query(self.asked).map(|(query, sensitivity)| (query.coefficients.clone(), sensitivity));
+            let fixed = chosen.is_some();
+            // the noise is only scaled to the session's sensitivity, so a query moving further on one entry would leak
+            let sensitivity = match chosen {
+                Some((coefficients, sensitivity)) => {
+                    self.query_coefficients = coefficients;
+                    Some(sensitivity)
+                },
+                None => {
+                    self.query_coefficients = verifier_generate_query(&mut self.state, self.args.sparsity, self.args.sensitivity)?;
+                    sensitivity::l1(&self.query_coefficients)
+                },
+            };
+            if let Some(s) = sensitivity.filter(|&s| s > self.args.sensitivity as u64) {
+                return Err(Error::Config(format!("Query has sensitivity {} beyond the session's {}", s, self.args.sensitivity)));
+            }
+            // the prompt already showed the analyst the query
+            if let Some(named) = self.named_queries.get(self.asked).filter(|_| !self.args.interactive) {
+                eprintln!("  query {:?}", named.name);
+            }
+            if (!self.args.database_schema.attributes().is_empty() || fixed) && !self.args.interactive {
+                eprintln!("  query over {}", self.describe_query(&self.query_coefficients, fixed));
+            }
+            self.asked += 1;
+            if self.args.batch > 1 {
+                self.batch_queries.push(std::mem::take(&mut self.query_coefficients));
             }
         }
+        Ok(true)
     }
-    verifier_randomness_phase_adjust(&mut verifier_state, args.db_size, args.epsilon, args.delta);
-    synchronize_prover(&mut stream);
-    let duration_rnd = start_rnd.elapsed();
 
-    eprintln!("Randomness phase complete ({:?})", duration_rnd);
+    fn query(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        if let Some(pinned) = self.args.snapshot {
+            if pinned != self.state.snapshot {
+                return Err(Error::Verification(format!("Certified snapshot {} is not the pinned snapshot {}", self.state.snapshot, pinned)));
+            }
+        }
 
-    // Query phase
-    eprintln!("Query phase start");
+        if self.args.batch > 1 {
+            return self.query_batch(stream);
+        }
+        if self.args.progressive && self.noise_spent {
+            self.flip_noise(stream)?;
+        }
+        // a repeated query is answered on the noise it was first answered on, leaving the current noise for the next
+        let distinct = self.args.distinct.is_some();
+        let repeated = self.state.answered.contains_key(&cache::query_digest(&self.query_coefficients, distinct));
+        self.noise_spent = !repeated;
+
+        // main checked that the budget covers every query, at the delta of the session's starting size as the prover charges it
+        if !repeated {
+            let delta = calibration::delta(self.args.db_size, self.args.delta) as f64;
+            self.accountant.charge(1, self.args.epsilon as f64, delta).map_err(Error::Config)?;
+        }
 
-    let mut duration_query = Duration::from_secs(0);
-    let mut homomorphic_duration = Duration::from_secs(0);
-    let mut check_duration = Duration::from_secs(0);
+        let mode = self.answer_mode();
+        verifier_send_query(&mut self.state, stream, &self.query_coefficients, distinct)?;
+        let (query_result, homomorphic_duration, check_duration) =
+            verifier_check_query(&mut self.state, stream, &self.query_coefficients, distinct, mode)?;
+        self.log_result(self.asked - 1, &query_result)?;
+        self.save_certificates(self.asked - 1, &[query_result])?;
+        let (epsilon, delta) = self.accountant.spent();
+        eprintln!("  spent ε = {:.4}, δ = {:.3e} over {} queries", epsilon, delta, self.accountant.queries());
+
+        self.homomorphic_duration += homomorphic_duration;
+        self.check_duration += check_duration;
+
+        if let Some(path) = &self.args.encrypted_answers {
+            let answers = std::mem::take(&mut self.state.encrypted_answers);
+            save_encrypted_answers(path, self.state.pedersen_pp.g, self.state.snapshot, self.state.epoch, answers)
+                .map_err(|e| Error::Config(format!("Could not save encrypted answers to {}: {}", path.display(), e)))?;
+        }
+        Ok(())
+    }
 
-    for _ in 0..args.num_queries {
-        let query_coefficients = verifier_generate_query(&mut verifier_state, args.sparsity);
-        synchronize_prover(&mut stream);
-        let iter_start_query = Instant::now();
-        verifier_send_query(&mut verifier_state, &mut stream, &query_coefficients);
-        let (_success, iter_homomorphic_duration, iter_check_duration) = 
-            verifier_check_query(&mut verifier_state, &mut stream, &query_coefficients);
-        synchronize_prover(&mut stream);
-        let iter_duration_query = iter_start_query.elapsed();
+    fn certify(&mut self, stream: &mut Framed<Connection>, transcript: &[u8; 32]) -> Result<()> {
+        let Some(sk) = &self.args.certify else {
+            return Err(Error::Config("Certifying needs an auditor signing key".to_string()));
+        };
+        let statement = self.statement(*transcript);
+        eprintln!("  certificate {}", statement.fingerprint());
+        write_message(stream, &CertificateSignatureMessage {
+            key: sk.verifying_key(),
+            signature: sk.sign(&statement.digest()),
+        })
+    }
 
-        duration_query += iter_duration_query;
-        homomorphic_duration += iter_homomorphic_duration;
-        check_duration += iter_check_duration;
+    fn learns_answers(&self) -> bool {
+        matches!(self.answer_mode(), AnswerMode::Plain)
     }
-    duration_query /= args.num_queries;
-    homomorphic_duration /= args.num_queries;
-    check_duration /= args.num_queries;
 
-    eprintln!("Query phase complete ({:?})", duration_query);
+    #[cfg(feature = "experiments")]
+    fn experiments(&mut self, stream: &mut Framed<Connection>) -> Result<()> {
+        if !self.args.sparsity_experiment {
+            return Ok(());
+        }
 
-    if args.sparsity_experiment {
         eprintln!("Sparsity experiment start");
         println!("=== Begin Sparsity Experiment ===\n");
 
-        for s in 1..pow(2, args.dimension as usize) {
+        let mode = self.answer_mode();
+        for s in 1..pow(2, self.args.dimension as usize) {
             let mut sparsity_homomorphic_duration = Duration::from_secs(0);
             let mut sparsity_check_duration = Duration::from_secs(0);
 
-            for _ in 0..args.num_queries {
-                let query_coefficients = verifier_generate_query(&mut verifier_state, s);
-                synchronize_prover(&mut stream);
-                verifier_send_query(&mut verifier_state, &mut stream, &query_coefficients);
-                let (_success, s_homomorphic_duration, s_check_duration) = 
-                    verifier_check_query(&mut verifier_state, &mut stream, &query_coefficients);
-                synchronize_prover(&mut stream);
+            for _ in 0..self.args.num_queries {
+                let query_coefficients = verifier_generate_query(&mut self.state, s, self.args.sensitivity)?;
+                synchronize(Side::Verifier, stream)?;
+                verifier_send_query(&mut self.state, stream, &query_coefficients, false)?;
+                let (_result, s_homomorphic_duration, s_check_duration) =
+                    verifier_check_query(&mut self.state, stream, &query_coefficients, false, mode)?;
+                synchronize(Side::Verifier, stream)?;
 
                 sparsity_homomorphic_duration += s_homomorphic_duration;
                 sparsity_check_duration += s_check_duration;
             }
-            sparsity_homomorphic_duration /= args.num_queries;
-            sparsity_check_duration /= args.num_queries;
+            sparsity_homomorphic_duration /= self.args.num_queries;
+            sparsity_check_duration /= self.args.num_queries;
             println!("{:?},{:?},{:?}", s, sparsity_homomorphic_duration.as_secs_f32(), sparsity_check_duration.as_secs_f32());
         }
 
         println!("\n=== End Sparsity Experiment ===\n");
         eprintln!("Sparsity experiment complete");
+        Ok(())
+    }
+
+    fn report(&self, stats: &ProtocolStats) {
+        let homomorphic_duration = self.homomorphic_duration / self.args.num_queries.max(1);
+        let check_duration = self.check_duration / self.args.num_queries.max(1);
+
+        ptable!(
+            ["Comparison", "V-Dishonest Comm.", "V-Rand. Gen.", "Rand N +", "Query Verify"],
+            ["", format!("{:?} s", self.state.comm_verify_duration.as_secs_f32()), format!("{:?} s", self.state.randomness_bit_sigma_verify_duration.as_secs_f32()), format!("{:?} s", self.state.randomness_coin_flip_agg_duration.as_secs_f32()), format!("{:?} µs", check_duration.as_micros())]
+        );
+
+        let mut table = stats.table(Self::NAME, format!("(n={}, d={}, ε={}, δ={:?} s={})", self.db_size, self.args.dimension, self.args.epsilon, calibration::delta(self.db_size, self.args.delta), self.args.sparsity));
+        table.add_row(row!["  -> Homomorphic", format!("{:?}", homomorphic_duration), ""]);
+        table.add_row(row!["  -> Check", format!("{:?}", check_duration), ""]);
+        table.printstd();
+
+        if let Some(summary) = &self.state.audit_summary {
+            println!("\n{}", summary);
+        }
+
+        println!("\n\nCSV (s):");
+        println!("{},{},{},{},{},{}", stats.honest_commitment.duration.as_secs_f32(), stats.dishonest_commitment.duration.as_secs_f32(), stats.randomness.duration.as_secs_f32(), stats.query.duration.as_secs_f32(), homomorphic_duration.as_secs_f32(), check_duration.as_secs_f32());
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some(path) = &self.args.save_session else {
+            return Ok(());
+        };
+        let saved = SavedSession {
+            parameters: self.parameters(),
+            snapshot: self.state.snapshot,
+            commitment_map: self.state.monomial_commitments.clone(),
+            noise_commitment: self.state.randomness_bit_comm,
+            noise_coins: self.state.noise.coins(),
+            mechanism: self.args.noise,
+        };
+        saved.save(path).map_err(|e| Error::Config(format!("Could not save session: {}", e)))?;
+        eprintln!("  session saved to {}", path.display());
+        Ok(())
+    }
+}
+
+fn main() -> ExitCode {
+    eprintln!("Running");
+    driver::log_progress();
+
+    let mut args = Args::parse();
+    args.calibration = args.calibration.with_queries(args.num_queries);
+    if args.co_verifier && args.verifiers < 2 {
+        eprintln!("ERROR: A co-verifier needs --verifiers of at least 2");
+        return ExitCode::FAILURE;
+    }
+    if args.progressive && args.verifiers > 1 {
+        eprintln!("ERROR: Progressive noise needs a single verifier");
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = noise::check(args.noise, args.calibration, args.verifiers, args.progressive) {
+        eprintln!("ERROR: {}", e);
+        return ExitCode::FAILURE;
+    }
+    // each attribute is a bit of the entry, and each monomial a mask of them
+    let Some(entry_width) = args.entry_width.or(config::entry_width(args.dimension)).filter(|&width| width >= args.dimension) else {
+        let width = args.entry_width.unwrap_or(*config::ENTRY_WIDTHS.last().unwrap());
+        eprintln!("ERROR: Dimension {} exceeds the {} attributes an entry holds", args.dimension, width);
+        return ExitCode::FAILURE;
+    };
+    if let Some(j) = args.distinct.as_ref().and_then(|attribute| attribute.bits.iter().find(|&&j| j >= args.dimension as usize)) {
+        eprintln!("ERROR: Distinct attribute bit {} is beyond the dimension {}", j, args.dimension);
+        return ExitCode::FAILURE;
+    }
+    if let Some(path) = &args.schema {
+        match Schema::load(path) {
+            Ok(database_schema) if database_schema.bits() > args.dimension => {
+                eprintln!("ERROR: Schema takes {} bits, beyond the dimension {}", database_schema.bits(), args.dimension);
+                return ExitCode::FAILURE;
+            },
+            Ok(database_schema) => args.database_schema = database_schema,
+            Err(e) => {
+                eprintln!("ERROR: Schema {}", e);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    if let Some(path) = &args.queries {
+        // masks of any width fit in the widest entries
+        let named = match queries::load::<u128>(path, &args.database_schema, args.max_degree) {
+            Ok(named) => named,
+            Err(e) => {
+                eprintln!("ERROR: Query file {}", e);
+                return ExitCode::FAILURE;
+            },
+        };
+        // bare entry bits may lie past the committed ones
+        if let Some(q) = named.iter().find(|q| q.query.coefficients.keys().any(|m| m.variables().any(|bit| bit >= args.dimension as usize))) {
+            eprintln!("ERROR: Query {:?} names a bit beyond the dimension {}", q.name, args.dimension);
+            return ExitCode::FAILURE;
+        }
+        if let Some(q) = named.iter().find(|q| q.sensitivity > args.sensitivity as u64) {
+            eprintln!("ERROR: Query {:?} has sensitivity {} beyond the session's {}", q.name, q.sensitivity, args.sensitivity);
+            return ExitCode::FAILURE;
+        }
+        args.num_queries = named.len() as u32;
+        args.calibration = args.calibration.with_queries(args.num_queries);
+    }
+    // each session writes its own answers
+    if let Some(path) = &args.answers {
+        if let Err(e) = File::create(path) {
+            eprintln!("ERROR: Answers file {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    // masks of any width fit in the widest entries
+    // the prover charges its epoch for the whole session at setup, and the verifier its own budget before connecting
+    let mut accountant = Accountant::new(args.composition, args.budget, args.budget_delta);
+    if let Err(e) = accountant.charge(args.num_queries, args.epsilon as f64, calibration::delta(args.db_size, args.delta) as f64) {
+        eprintln!("ERROR: Session refused: {}", e);
+        return ExitCode::FAILURE;
+    }
+    match args.predicate.as_ref().map(|p| p.compile::<u128>(&args.database_schema, args.max_degree)) {
+        Some(Err(e)) => {
+            eprintln!("ERROR: Cannot compile the predicate: {}", e);
+            return ExitCode::FAILURE;
+        },
+        // bare entry bits may lie past the committed ones
+        Some(Ok(query)) if query.coefficients.keys().any(|m| m.variables().any(|bit| bit >= args.dimension as usize)) => {
+            eprintln!("ERROR: Predicate names a bit beyond the dimension {}", args.dimension);
+            return ExitCode::FAILURE;
+        },
+        _ => {},
+    }
+    println!("\n-- Verifier --\n");
+    println!("Configuration:");
+    println!("\tDatabase size: {}", args.db_size);
+    println!("\tDimension: {}", args.dimension);
+    println!("\tEntry width: {} bits", entry_width);
+    println!("\tMax degree: {}", args.max_degree);
+    println!("\tEpsilon: {}", args.epsilon);
+    println!("\tDelta: {:?}", args.delta);
+    println!("\tCalibration: {}", args.calibration);
+    println!("\tSensitivity: {}", args.sensitivity);
+    println!("\tConfidence: {}", args.confidence);
+    if args.budget.is_some() || args.budget_delta.is_some() || args.composition != Composition::Basic {
+        println!("\tBudget: {}, {} composition", accountant.budget(), args.composition);
+    }
+    println!("\tSparsity: {}", args.sparsity);
+    if args.batch > 1 {
+        println!("\tBatch: {} queries", args.batch);
+    }
+    if let Some(name) = &args.dataset {
+        println!("\tDataset: {}", name);
+    }
+    if let Some(name) = &args.analyst {
+        println!("\tAnalyst: {}", name);
     }
+    if let Some(attribute) = &args.distinct {
+        println!("\tDistinct: {}", attribute);
+    }
+    if !args.database_schema.attributes().is_empty() {
+        println!("\tSchema: {}", args.database_schema);
+    }
+    if let Some(predicate) = &args.predicate {
+        println!("\tPredicate: {}", predicate);
+    }
+    if let Some(path) = &args.queries {
+        println!("\tQueries: {} ({} queries), answers to {}", path.display(), args.num_queries, args.answers.as_ref().unwrap().display());
+    }
+    if args.interactive {
+        println!("\tInteractive: up to {} queries{}", args.num_queries, args.answers.as_ref().map(|path| format!(", answers to {}", path.display())).unwrap_or_default());
+    }
+    if args.noise != Mechanism::Binomial || args.progressive {
+        println!("\tNoise: {}{}", args.noise, if args.progressive { ", progressive, per query" } else { "" });
+    }
+    println!("\tProfile: {}", args.profile);
+    if args.wire_format != WireFormat::Json || args.compress {
+        println!("\tWire format: {}{}", args.wire_format, if args.compress { ", compressed" } else { "" });
+    }
+    if args.verifiers > 1 {
+        println!("\tVerifiers: {}{}", args.verifiers, if args.co_verifier { " (co-verifier)" } else { "" });
+    }
+    if let (Some(path), Some(auditor_key)) = (&args.certificate, &args.auditor_key) {
+        // masks of any width fit in the widest entries
+        match Certificate::<u128>::load(path, auditor_key) {
+            Ok(certificate) => println!("\tCertificate: {}", certificate.statement.fingerprint()),
+            Err(e) => {
+                eprintln!("ERROR: Certificate {}", e);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    if let Some(path) = &args.resume {
+        match SavedSession::<u128>::load(path) {
+            Ok(saved) => println!("\tResuming: snapshot {}", saved.snapshot),
+            Err(e) => {
+                eprintln!("ERROR: Saved session {}", e);
+                return ExitCode::FAILURE;
+            },
+        }
+    }
+    match tls_config(&args) {
+        Ok(Some(_)) => println!("\tTLS: {}{}", args.tls_ca.as_ref().unwrap().display(), if args.tls_cert.is_some() { ", with a verifier certificate" } else { "" }),
+        // the key exchange alone lets an attacker relaying the connection agree keys of its own with each side, and a
+        // signature only keeps it from the prover, not from posing as one
+        Ok(None) if args.auth_psk.is_none() => {
+            eprintln!("WARNING: Without --tls-ca or --auth-psk, frames are not protected against an active attacker on the network, who can agree keys of its own with each side");
+        },
+        Ok(None) => (),
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            return ExitCode::from(e.exit_code());
+        },
+    }
+    #[cfg(feature = "grpc")]
+    if args.grpc {
+        println!("\tgRPC: one stream of frames per session");
+    }
+    #[cfg(feature = "quic")]
+    if args.quic {
+        println!("\tQUIC: each frame on a stream of its own");
+    }
+    if let Some(path) = &args.record {
+        if let Err(e) = recording::open(path) {
+            eprintln!("ERROR: Recording {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+        println!("\tRecording: {}", path.display());
+    }
+    println!("\tProver address: {}\n", args.prover_address);
 
-    ptable!(
-        ["Comparison", "V-Dishonest Comm.", "V-Rand. Gen.", "Rand N +", "Query Verify"],
-        ["", format!("{:?} s", verifier_state.comm_verify_duration.as_secs_f32()), format!("{:?} s", verifier_state.randomness_bit_sigma_verify_duration.as_secs_f32()), format!("{:?} s", verifier_state.randomness_coin_flip_agg_duration.as_secs_f32()), format!("{:?} µs", check_duration.as_micros())]
-    );
-
-    ptable!(
-        ["Verifier", format!("(n={}, d={}, ε={}, δ={:?} s={})", args.db_size, args.dimension, args.epsilon, get_delta(args.db_size, args.delta), args.sparsity)],
-        ["Commit", ""],
-        ["  -> Honest", format!("{:?}", duration_honest_comm)],
-        ["  -> Dishonest", format!("{:?}", duration_dishonest_comm)],
-        ["Randomness", format!("{:?}", duration_rnd)],
-        ["Query", format!("{:?}", duration_query)],
-        ["  -> Homomorphic", format!("{:?}", homomorphic_duration)],
-        ["  -> Check", format!("{:?}", check_duration)]
-    );
-
-    println!("\n\nCSV (s):");
-    println!("{},{},{},{},{},{}", duration_honest_comm.as_secs_f32(), duration_dishonest_comm.as_secs_f32(), duration_rnd.as_secs_f32(), duration_query.as_secs_f32(), homomorphic_duration.as_secs_f32(), check_duration.as_secs_f32());
-}
\ No newline at end of file
+    let options = DriverOptions {
+        skip_dishonest: args.skip_dishonest,
+        update: args.update,
+        num_queries: args.num_queries,
+        sessions: 1,
+        daemon: false,
+        concurrent: 1,
+        profile: args.profile,
+        certify: args.certify.is_some(),
+        certified: args.certificate.is_some(),
+        resume: false,
+        resumed: args.resume.is_some(),
+        deadlines: Deadlines::new(&args.deadline),
+        wire_format: args.wire_format,
+        compress: args.compress,
+        batch: args.batch,
+        max_frame_size: args.max_frame_size(),
+    };
+    let outcome = match entry_width {
+        8 => execute::<u8>(args, options),
+        16 => execute::<u16>(args, options),
+        32 => execute::<u32>(args, options),
+        64 => execute::<u64>(args, options),
+        _ => execute::<u128>(args, options),
+    };
+    match outcome {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => ExitCode::from(e.exit_code()),
+    }
+}
+
+/// Run the session over entries of type `T`, or only its noise flip as a co-verifier
+fn execute<T: Entry>(args: Args, options: DriverOptions) -> Result<()> {
+    match args.co_verifier {
+        true => Verifier::<T>::new(args).co_verify(),
+        false => ProtocolDriver::<Verifier<T>>::execute(args, options),
+    }
+}
@@ -1,4 +1,4 @@
-/**
+/*!
  * bit_sigma.rs
  * 
  * Bit Sigma Protocol implementation, used as a building block for the main protocol.
@@ -6,88 +6,127 @@
 
 use std::ops::Neg;
 
-use curve25519_dalek::{RistrettoPoint, Scalar};
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use certified_dp_core::bit_sigma::Failure;
+
+use crate::error::{Error, Result};
+use crate::group::{Group, Ristretto};
 use crate::pedersen;
+#[cfg(feature = "arbitrary")]
+use crate::fuzzing;
 
 /// Prover state for the bit sigma protocol.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Prover {
+pub struct Prover<G: Group = Ristretto> {
     b: u32,
-    b_proof: Scalar,
-    r_b: Scalar,
-    z_not_b: Scalar,
-    e_not_b: Scalar,
+    b_proof: G::Scalar,
+    r_b: G::Scalar,
+    z_not_b: G::Scalar,
+    e_not_b: G::Scalar,
 }
 
 /// Zero-out values by default
-impl Default for Prover {
+impl<G: Group> Default for Prover<G> {
     fn default() -> Self {
         Prover {
             b: 0,
-            b_proof: Scalar::default(),
-            r_b: Scalar::default(),
-            z_not_b: Scalar::default(),
-            e_not_b: Scalar::default(),
+            b_proof: G::Scalar::default(),
+            r_b: G::Scalar::default(),
+            z_not_b: G::Scalar::default(),
+            e_not_b: G::Scalar::default(),
         }
     }
 }
 
+/// The bit, its opening, and the simulated half of the proof would let anyone reading the prover's memory open `b_comm`
+impl<G: Group> Zeroize for Prover<G> {
+    fn zeroize(&mut self) {
+        self.b.zeroize();
+        self.b_proof.zeroize();
+        self.r_b.zeroize();
+        self.z_not_b.zeroize();
+        self.e_not_b.zeroize();
+    }
+}
+
+impl<G: Group> Drop for Prover<G> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<G: Group> ZeroizeOnDrop for Prover<G> {}
+
 /// Verifier state for the bit sigma protocol.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Verifier {
-    pub b_comm: RistrettoPoint,
-    e: Scalar,
-    c_0: RistrettoPoint,
-    c_1: RistrettoPoint,
+pub struct Verifier<G: Group = Ristretto> {
+    pub b_comm: G::Point,
+    e: G::Scalar,
+    c_0: G::Point,
+    c_1: G::Point,
 }
 
 /// Zero-out values by default
-impl Default for Verifier {
+impl<G: Group> Default for Verifier<G> {
     fn default() -> Self {
         Verifier {
-            b_comm: RistrettoPoint::default(),
-            e: Scalar::default(),
-            c_0: RistrettoPoint::default(),
-            c_1: RistrettoPoint::default(),
+            b_comm: G::Point::default(),
+            e: G::Scalar::default(),
+            c_0: G::Point::default(),
+            c_1: G::Point::default(),
         }
     }
 }
 
 /// Commitment message for the bit sigma protocol from prover.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Commitment {
-    b_comm: RistrettoPoint,
-    c_0: RistrettoPoint,
-    c_1: RistrettoPoint,
+pub struct Commitment<G: Group = Ristretto> {
+    b_comm: G::Point,
+    c_0: G::Point,
+    c_1: G::Point,
 }
 
 /// Challenge message for the bit sigma protocol from verifier.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Challenge {
-    e: Scalar,
+pub struct Challenge<G: Group = Ristretto> {
+    e: G::Scalar,
+}
+
+impl<G: Group> Challenge<G> {
+    /// Uniformly random challenge
+    pub fn random<T: Rng + CryptoRng>(rng: &mut T) -> Self {
+        Challenge {
+            e: G::random_scalar(rng),
+        }
+    }
+
+    /// Challenge derived from public randomness, e.g. a randomness beacon, rather than picked by the verifier
+    pub fn from_scalar(e: G::Scalar) -> Self {
+        Challenge { e }
+    }
 }
 
 /// Response message for the bit sigma protocol from prover.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Response {
-    z_0: Scalar,
-    z_1: Scalar,
-    e_0: Scalar,
-    e_1: Scalar,
+pub struct Response<G: Group = Ristretto> {
+    z_0: G::Scalar,
+    z_1: G::Scalar,
+    e_0: G::Scalar,
+    e_1: G::Scalar,
 }
 
 /// (1) Prover commits to a bit `b` that it is either 0 or 1.
-pub fn commit<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams,
-                                  b: u32, b_comm: RistrettoPoint, b_proof: Scalar) -> (Prover, Commitment) {
+pub fn commit<G: Group, T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams<G>,
+                                            b: u32, b_comm: G::Point, b_proof: G::Scalar) -> (Prover<G>, Commitment<G>) {
 
-    let (c_b, r_b) = pedersen::commit(rng, &Scalar::from(b), pp);
+    let (c_b, r_b) = pedersen::commit(rng, &G::Scalar::from(b as u64), pp);
 
-    let e_not_b = Scalar::random(rng);
+    let e_not_b = G::random_scalar(rng);
     let (mut c_not_b, z_not_b) = 
-        pedersen::commit(rng, &(Scalar::from(1 - b) * (e_not_b + Scalar::from(1 as u32))), pp);
+        pedersen::commit(rng, &(G::Scalar::from(1 - b as u64) * (e_not_b + G::Scalar::from(1))), pp);
     c_not_b += e_not_b.neg() * b_comm;
 
     (
@@ -107,25 +146,49 @@ pub fn commit<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams,
 }
 
 // (2) Verifier picks a random challenge `e`.
-pub fn challenge<T: Rng + CryptoRng>(rng: &mut T, comm_msg: &Commitment) -> (Verifier, Challenge) {
+pub fn challenge<G: Group, T: Rng + CryptoRng>(rng: &mut T, comm_msg: &Commitment<G>) -> (Verifier<G>, Challenge<G>) {
 
-    let e = Scalar::random(rng);
+    let challenge = Challenge::random(rng);
 
     (
-        Verifier {
-            e,
-            b_comm: comm_msg.b_comm,
-            c_0: comm_msg.c_0,
-            c_1: comm_msg.c_1,
-        },
-        Challenge {
-            e,
-        }
+        verifier_for(comm_msg, &challenge),
+        challenge
     )
 }
 
+/// (2) Verifier state for a challenge chosen elsewhere, e.g. one derived with `expand_challenge`.
+pub fn verifier_for<G: Group>(comm_msg: &Commitment<G>, challenge: &Challenge<G>) -> Verifier<G> {
+    Verifier {
+        e: challenge.e,
+        b_comm: comm_msg.b_comm,
+        c_0: comm_msg.c_0,
+        c_1: comm_msg.c_1,
+    }
+}
+
+/// Derive `count` challenges `e, e^2, ..., e^count` from a single random challenge, so every bit of an entry can be
+/// challenged with one scalar. Each power of a uniform `e` is still unpredictable to the prover, and two different `e`
+/// give different powers except with negligible probability, so each instance keeps its special soundness.
+pub fn expand_challenge<G: Group>(challenge: &Challenge<G>, count: usize) -> Vec<Challenge<G>> {
+    let mut challenges = Vec::with_capacity(count);
+    let mut e = challenge.e;
+    for _ in 0..count {
+        challenges.push(Challenge { e });
+        e *= challenge.e;
+    }
+    challenges
+}
+
+/// Sum of several verifiers' challenges, which is uniformly random as long as any one of them is and was chosen
+/// independently of the others.
+pub fn combine_challenges<'a, G: Group + 'a>(challenges: impl IntoIterator<Item = &'a Challenge<G>>) -> Challenge<G> {
+    Challenge {
+        e: challenges.into_iter().fold(G::Scalar::from(0), |e, challenge| e + challenge.e),
+    }
+}
+
 /// (3) Prover responds based on the challenge.
-pub fn response(sigma_p: &mut Prover, challenge: &Challenge) -> Response {
+pub fn response<G: Group>(sigma_p: &mut Prover<G>, challenge: &Challenge<G>) -> Response<G> {
 
     let e_b = challenge.e - sigma_p.e_not_b;
     let z_b = sigma_p.r_b + (e_b * sigma_p.b_proof);
@@ -138,22 +201,87 @@ pub fn response(sigma_p: &mut Prover, challenge: &Challenge) -> Response {
     }
 }
 
-/// (4) Verifier verifies the response from the prover.
-pub fn verify(pp: &pedersen::PublicParams, sigma_v: &mut Verifier, response: &Response) -> bool {
-    if sigma_v.e != response.e_0 + response.e_1 {
-        println!("ERROR: e != e0 + e1");
-        return false;
+/// (4) Verifier verifies the response from the prover, naming the check that failed if it does not hold.
+pub fn verify<G: Group>(pp: &pedersen::PublicParams<G>, sigma_v: &mut Verifier<G>, response: &Response<G>) -> Result<()> {
+    let result = certified_dp_core::bit_sigma::verify(pp, sigma_v.b_comm, sigma_v.e, [sigma_v.c_0, sigma_v.c_1],
+                                                      [response.z_0, response.z_1], [response.e_0, response.e_1]);
+    result.map_err(failure_error)
+}
+
+fn failure_error(failure: Failure) -> Error {
+    Error::Verification(match failure {
+        Failure::Challenge => "e != e0 + e1",
+        Failure::Zero => "comm_0 != c0 + (e0 * b_comm)",
+        Failure::One => "comm_1 != c1 + (e1 * b_comm)",
+    }.to_string())
+}
+
+/// (4) Verifier verifies many responses at once, as `verify` would each of them, with one random linear combination of
+/// all their checks and a single multiscalar multiplication. A response that fails its own check fails the combination
+/// except with negligible probability, as the weights are unknown to the prover. Fails with the index of the first
+/// failing pair and its error, which are only worked out, one pair at a time, once the combination has failed.
+pub fn verify_batch<G: Group, T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams<G>,
+                                                  pairs: &[(&Verifier<G>, &Response<G>)]) -> std::result::Result<(), (usize, Error)> {
+    let mut scalars = Vec::with_capacity(3 * pairs.len() + 2);
+    let mut points = Vec::with_capacity(3 * pairs.len() + 2);
+    let mut g_scalar = G::Scalar::from(0);
+    let mut h_scalar = G::Scalar::from(0);
+    let mut challenges_split = true;
+
+    // each pair checks z_0 * h = c_0 + e_0 * b_comm and (1 + e_1) * g + z_1 * h = c_1 + e_1 * b_comm; the first is
+    // weighted by w_0 and the second by w_1, and the weighted differences must sum to the identity
+    for (sigma_v, response) in pairs {
+        challenges_split &= sigma_v.e == response.e_0 + response.e_1;
+        let w_0 = G::random_scalar(rng);
+        let w_1 = G::random_scalar(rng);
+        g_scalar = g_scalar + w_1 * (G::Scalar::from(1) + response.e_1);
+        h_scalar = h_scalar + w_0 * response.z_0 + w_1 * response.z_1;
+        scalars.extend([w_0.neg(), w_1.neg(), (w_0 * response.e_0 + w_1 * response.e_1).neg()]);
+        points.extend([sigma_v.c_0, sigma_v.c_1, sigma_v.b_comm]);
+    }
+    scalars.extend([g_scalar, h_scalar]);
+    points.extend([pp.g, pp.h]);
+
+    if challenges_split && pedersen::vartime_multiscalar_mul::<G>(&scalars, &points) == G::Point::default() {
+        return Ok(());
     }
+    for (i, (sigma_v, response)) in pairs.iter().enumerate() {
+        let result = certified_dp_core::bit_sigma::verify(pp, sigma_v.b_comm, sigma_v.e, [sigma_v.c_0, sigma_v.c_1],
+                                                          [response.z_0, response.z_1], [response.e_0, response.e_1]);
+        result.map_err(|failure| (i, failure_error(failure)))?;
+    }
+    // the combination only fails for pairs that each verify with negligible probability
+    Err((0, Error::Verification("Batched bit sigma check failed".to_string())))
+}
 
-    if pedersen::commit_with_r(&Scalar::from(0 as u32), &response.z_0, pp) != sigma_v.c_0 + (response.e_0 * sigma_v.b_comm) {
-        println!("ERROR: comm_0 != c0 + (e0 * b_comm)");
-        return false;
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Commitment {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Commitment {
+            b_comm: fuzzing::point(u)?,
+            c_0: fuzzing::point(u)?,
+            c_1: fuzzing::point(u)?,
+        })
     }
+}
 
-    if pedersen::commit_with_r(&(Scalar::from(1 as u32) + response.e_1), &response.z_1, pp) != sigma_v.c_1 + (response.e_1 * sigma_v.b_comm) {
-        println!("ERROR: comm_1 != c1 + (e1 * b_comm)");
-        return false;
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Challenge {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Challenge {
+            e: fuzzing::scalar(u)?,
+        })
     }
+}
 
-    true
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Response {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Response {
+            z_0: fuzzing::scalar(u)?,
+            z_1: fuzzing::scalar(u)?,
+            e_0: fuzzing::scalar(u)?,
+            e_1: fuzzing::scalar(u)?,
+        })
+    }
 }
@@ -0,0 +1,146 @@
+/*!
+ * budget.rs
+ *
+ * Privacy budget accounting. Each query answered on the noise is an (ε, δ)-differentially private release, and an
+ * accountant adds up what a run of them spends. Basic composition sums their ε's and δ's. Advanced composition (Dwork,
+ * Rothblum, and Vadhan) pays a slack δ' on top of the summed δ's for an ε that grows with the square root of the
+ * number of queries, sqrt(2 ln(1/δ') Σ ε²) + Σ ε (e^ε - 1); it only pays off over many queries, so the accountant
+ * takes whichever of the two spends less ε within the budget. What an accountant has charged can be saved and restored,
+ * so a prover picks up its budget where it left off after a restart.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How the queries' privacy losses add up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Composition {
+    Basic,
+    /// Advanced composition with this slack δ'
+    Advanced(f64),
+}
+
+impl fmt::Display for Composition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Composition::Basic => f.write_str("basic"),
+            Composition::Advanced(slack) => write!(f, "advanced:{}", slack),
+        }
+    }
+}
+
+/// Parse a composition from the command line: `basic`, or `advanced:SLACK` with the slack δ' between 0 and 1, e.g.
+/// `advanced:1e-6`
+pub fn parse_composition(s: &str) -> Result<Composition, String> {
+    match s.split_once(':') {
+        None if s == "basic" => Ok(Composition::Basic),
+        Some(("advanced", slack)) => match slack.parse::<f64>() {
+            Ok(slack) if slack > 0.0 && slack < 1.0 => Ok(Composition::Advanced(slack)),
+            _ => Err(format!("slack must be between 0 and 1, got {:?}", slack)),
+        },
+        _ => Err(format!("expected basic or advanced:SLACK, got {:?}", s)),
+    }
+}
+
+/// Parse a total delta from the command line, between 0 and 1
+pub fn parse_delta(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(delta) if delta > 0.0 && delta < 1.0 => Ok(delta),
+        _ => Err(format!("delta budget must be between 0 and 1, got {:?}", s)),
+    }
+}
+
+/// Queries charged to an accountant, from which it computes what they spent under any composition
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Charges {
+    pub queries: u64,
+    /// Sums over the queries of ε, ε², ε (e^ε - 1), and δ
+    pub epsilon: f64,
+    pub epsilon_squared: f64,
+    pub epsilon_excess: f64,
+    pub delta: f64,
+}
+
+/// Privacy spent by the queries charged so far, and the budget it must stay within
+#[derive(Clone, Debug)]
+pub struct Accountant {
+    composition: Composition,
+    epsilon_budget: Option<f64>,
+    delta_budget: Option<f64>,
+    charges: Charges,
+}
+
+impl Accountant {
+    /// Accountant composing with `composition` within the budgets, each unlimited if None
+    pub fn new(composition: Composition, epsilon_budget: Option<f64>, delta_budget: Option<f64>) -> Accountant {
+        Accountant { composition, epsilon_budget, delta_budget, charges: Charges::default() }
+    }
+
+    /// Number of queries charged
+    pub fn queries(&self) -> u64 {
+        self.charges.queries
+    }
+
+    /// Queries charged so far, e.g. to save them
+    pub fn charges(&self) -> Charges {
+        self.charges
+    }
+
+    /// Take up the queries charged earlier, e.g. by a prover before it restarted, in place of those charged so far
+    pub fn restore(&mut self, charges: Charges) {
+        self.charges = charges;
+    }
+
+    /// Whether `(epsilon, delta)` is within the budget; a relative tolerance keeps a budget of e.g. 1.0 from being
+    /// exhausted early by rounding over ten costs of 0.1
+    fn fits(&self, (epsilon, delta): (f64, f64)) -> bool {
+        self.epsilon_budget.is_none_or(|b| epsilon <= b * (1.0 + 1e-6)) && self.delta_budget.is_none_or(|b| delta <= b * (1.0 + 1e-6))
+    }
+
+    /// Total (ε, δ) spent by the queries charged so far
+    pub fn spent(&self) -> (f64, f64) {
+        let c = &self.charges;
+        let basic = (c.epsilon, c.delta);
+        let advanced = match self.composition {
+            Composition::Advanced(slack) if c.queries > 0 => {
+                Some(((2.0 * (1.0 / slack).ln() * c.epsilon_squared).sqrt() + c.epsilon_excess, c.delta + slack))
+            },
+            _ => None,
+        };
+        match advanced {
+            Some(advanced) if self.fits(advanced) != self.fits(basic) => if self.fits(advanced) { advanced } else { basic },
+            Some(advanced) if advanced.0 < basic.0 => advanced,
+            _ => basic,
+        }
+    }
+
+    /// Charge `queries` queries of `(epsilon, delta)` each. Fails, charging nothing, if the budget does not cover them.
+    pub fn charge(&mut self, queries: u32, epsilon: f64, delta: f64) -> Result<(), String> {
+        let mut charged = self.clone();
+        let n = queries as f64;
+        charged.charges.queries += queries as u64;
+        charged.charges.epsilon += n * epsilon;
+        charged.charges.epsilon_squared += n * epsilon * epsilon;
+        charged.charges.epsilon_excess += n * epsilon * epsilon.exp_m1();
+        charged.charges.delta += n * delta;
+        let spent = charged.spent();
+        if !self.fits(spent) {
+            return Err(format!("{} queries at ε = {}, δ = {:.3e} would spend ε = {:.4}, δ = {:.3e} with {} composition, beyond the budget of {}",
+                queries, epsilon, delta, spent.0, spent.1, self.composition, self.budget()));
+        }
+        *self = charged;
+        Ok(())
+    }
+
+    /// Forget the queries charged, as at the start of a budget epoch
+    pub fn reset(&mut self) {
+        *self = Accountant::new(self.composition, self.epsilon_budget, self.delta_budget);
+    }
+
+    /// The budget, e.g. `ε = 10, δ = 1e-5`
+    pub fn budget(&self) -> String {
+        let epsilon = self.epsilon_budget.map_or("unlimited".to_string(), |b| b.to_string());
+        let delta = self.delta_budget.map_or("unlimited".to_string(), |b| format!("{:e}", b));
+        format!("ε = {}, δ = {}", epsilon, delta)
+    }
+}
@@ -0,0 +1,39 @@
+/*!
+ * bulk.rs
+ *
+ * Hook for generating Pedersen commitments in bulk. The commitment phases hand a `BulkCommitter` whole batches of
+ * (value, randomness) pairs, e.g. every bit of every entry, so deployments committing to large databases can plug in
+ * an accelerator, such as a GPU kernel or a pool of machines, in place of the default CPU committer. The caller always
+ * draws the randomness, so a committer never needs a source of randomness of its own and only ever sees openings.
+ */
+
+use rand::{CryptoRng, RngCore};
+
+use crate::group::{Group, Ristretto};
+use crate::pedersen;
+
+/// Computes batches of Pedersen commitments
+pub trait BulkCommitter<G: Group = Ristretto> {
+    /// Commitments `value * g + randomness * h` to each pair, in order
+    fn commit_batch(&self, pp: &pedersen::PublicParams<G>, openings: &[(G::Scalar, G::Scalar)]) -> Vec<G::Point>;
+}
+
+/// Default committer, which commits to the whole batch on the calling thread with `pedersen::commit_batch_with_r`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuCommitter;
+
+impl<G: Group> BulkCommitter<G> for CpuCommitter {
+    fn commit_batch(&self, pp: &pedersen::PublicParams<G>, openings: &[(G::Scalar, G::Scalar)]) -> Vec<G::Point> {
+        pedersen::commit_batch_with_r(openings, pp)
+    }
+}
+
+/// Commit to each value with fresh randomness through `committer`, returning the openings (value, commitment,
+/// randomness) in order.
+pub fn commit_all<G: Group, T: RngCore + CryptoRng>(committer: &dyn BulkCommitter<G>, rng: &mut T, pp: &pedersen::PublicParams<G>,
+                                                    values: Vec<G::Scalar>) -> Vec<(G::Scalar, G::Point, G::Scalar)> {
+    let openings: Vec<(G::Scalar, G::Scalar)> = values.into_iter().map(|value| (value, G::random_scalar(rng))).collect();
+    let points = committer.commit_batch(pp, &openings);
+    assert_eq!(points.len(), openings.len(), "bulk committer returned {} commitments for {} openings", points.len(), openings.len());
+    openings.into_iter().zip(points).map(|((value, r), comm)| (value, comm, r)).collect()
+}
@@ -0,0 +1,39 @@
+/*!
+ * cache.rs
+ *
+ * Answers to repeated queries. Releasing a fresh noisy answer to a query already answered would spend budget for
+ * nothing but a second sample of its noise, so within a session the prover answers a repeated query with the answer
+ * and proof it gave the first time, and the verifier checks them against the noise they were given on, charging the
+ * budget nothing. Queries are keyed by a hash of their coefficients in order of monomial, so both parties tell a repeat
+ * the same way whatever the order of their maps; with progressive noise, the noise flipped for a repeat is then kept
+ * for the next new query.
+ */
+
+use curve25519_dalek::Scalar;
+use num_traits::PrimInt;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::monomial::Monomial;
+
+/// Domain separator for query digests
+const QUERY_DOMAIN: &[u8] = b"certified-dp query";
+
+/// Hash identifying a query by its coefficients
+pub type QueryDigest = [u8; 32];
+
+/// Digest of the query of `coefficients`, a distinct count if `distinct`, hashed in order of monomial so it does not
+/// depend on map order
+pub fn query_digest<T: PrimInt>(coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool) -> QueryDigest {
+    let mut monomials: Vec<(u128, &Scalar)> = coefficients.iter().map(|(m, c)| (m.mask().to_u128().unwrap(), c)).collect();
+    monomials.sort_by_key(|&(monomial_id, _)| monomial_id);
+
+    let mut hasher = Sha256::new();
+    hasher.update(QUERY_DOMAIN);
+    hasher.update([distinct as u8]);
+    for (monomial_id, coefficient) in monomials {
+        hasher.update(monomial_id.to_le_bytes());
+        hasher.update(coefficient.as_bytes());
+    }
+    hasher.finalize().into()
+}
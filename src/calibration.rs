@@ -0,0 +1,101 @@
+/*!
+ * calibration.rs
+ *
+ * Number of noise coins N a session flips, from its epsilon and delta. The approximate calibration makes each query
+ * (ε, δ)-differentially private on its own, with N = 8 log(2/δ) / ε², so a session of k queries spends kε by basic
+ * composition. The zCDP and Rényi calibrations instead size the noise for the session as a whole: they find the least
+ * N for which its k queries together are (kε, δ)-differentially private under zero-concentrated or Rényi
+ * composition, which grows with the square root of k rather than k, so multi-query sessions need far fewer coins for
+ * the same total. Both treat the binomial noise, of variance N/4, as the Gaussian of that variance, which it approaches
+ * as N grows: each query of sensitivity one is then 2/N-zCDP, and (α, 2α/N)-RDP. Composition only holds when each
+ * query is answered on noise of its own, so these calibrations need progressive noise; queries answered on the same
+ * noise give away the difference of their answers exactly, however many coins it takes.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How the session's epsilon and delta set the number of noise coins
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Calibration {
+    /// Each query (ε, δ)-DP on its own
+    #[default]
+    Approximate,
+    /// The session's queries together (kε, δ)-DP by zero-concentrated composition
+    Zcdp { queries: u32 },
+    /// The session's queries together (kε, δ)-DP by Rényi composition
+    Renyi { queries: u32 },
+}
+
+impl Calibration {
+    /// The calibration for a session of `queries` queries
+    pub fn with_queries(self, queries: u32) -> Calibration {
+        match self {
+            Calibration::Approximate => Calibration::Approximate,
+            Calibration::Zcdp { .. } => Calibration::Zcdp { queries },
+            Calibration::Renyi { .. } => Calibration::Renyi { queries },
+        }
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Calibration::Approximate => f.write_str("approximate"),
+            Calibration::Zcdp { queries } => write!(f, "zcdp over {} queries", queries),
+            Calibration::Renyi { queries } => write!(f, "renyi over {} queries", queries),
+        }
+    }
+}
+
+/// Parse a calibration from the command line, one of `approximate`, `zcdp`, or `renyi`, for a single query until
+/// `Calibration::with_queries` gives the session's
+pub fn parse_calibration(s: &str) -> Result<Calibration, String> {
+    match s {
+        "approximate" => Ok(Calibration::Approximate),
+        "zcdp" => Ok(Calibration::Zcdp { queries: 1 }),
+        "renyi" => Ok(Calibration::Renyi { queries: 1 }),
+        _ => Err(format!("expected approximate, zcdp, or renyi, got {:?}", s)),
+    }
+}
+
+/// DP parameter: get `delta` based on configuration provided or calculate it based on the database size
+#[inline]
+pub fn delta(db_size: u32, delta: Option<f32>) -> f32 {
+    match delta {
+        Some(d) => d,
+        None => 1.0 / (db_size as f32).powf(db_size.ilog2() as f32)
+    }
+}
+
+/// DP parameter: get `n` based on epsilon and delta under `calibration`; if delta is not provided, assume the default
+/// and calculate it based on the database size
+pub fn coins(db_size: u32, epsilon: f32, delta: Option<f32>, calibration: Calibration) -> u32 {
+    // ln(1/δ), and the session's total epsilon
+    let log = |k: u32| (-(self::delta(db_size, delta) as f64).ln(), k.max(1) as f64 * epsilon as f64);
+    match calibration {
+        Calibration::Approximate => match delta {
+            Some(d) => ((8.0 * (2.0 / d).log2()) / epsilon.powi(2)).ceil() as u32,
+            // Delta set to 1/size^(log(size)), thus N = 8 * log(2/delta) / epsilon^(2)
+            None => ((8 * (db_size.ilog2().pow(2) + 1)) as f32 / epsilon.powi(2)).ceil() as u32
+        },
+        // ρ-zCDP is (ρ + 2 sqrt(ρ ln(1/δ)), δ)-DP, so the session's k queries may spend ρ = (sqrt(ln(1/δ) + kε) -
+        // sqrt(ln(1/δ)))², each 2/N of it
+        Calibration::Zcdp { queries } => {
+            let (l, total) = log(queries);
+            let rho = ((l + total).sqrt() - l.sqrt()).powi(2);
+            (2.0 * queries.max(1) as f64 / rho).ceil() as u32
+        },
+        // (α, ε')-RDP is (ε' + ln(1 - 1/α) - (ln δ + ln α)/(α - 1), δ)-DP, so for each order α the session's k queries
+        // may spend 2αk/N up to what that leaves of kε; the best order, over a geometric grid, takes the fewest coins
+        Calibration::Renyi { queries } => {
+            let (l, total) = log(queries);
+            (-300..=300)
+                .map(|i| 1.0 + 10f64.powf(i as f64 / 100.0))
+                .map(|alpha| (alpha, total - (1.0 - 1.0 / alpha).ln() + (alpha.ln() - l) / (alpha - 1.0)))
+                .filter(|&(_, room)| room > 0.0)
+                .map(|(alpha, room)| (2.0 * alpha * queries.max(1) as f64 / room).ceil())
+                .fold(f64::INFINITY, f64::min) as u32
+        },
+    }
+}
@@ -0,0 +1,98 @@
+/*!
+ * certificate.rs
+ *
+ * Certificates of audited commitments. Once an auditor has verified the commitment phases, it signs a statement of
+ * what it certified: the session's parameters, the Pedersen generators, the snapshot that roots the commitment map,
+ * and a hash of the session's transcript. The prover writes the statement, its signatures, and the commitment map to
+ * a certificate file, from which analysts who trust the auditor start later sessions without recommitting.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use num_traits::PrimInt;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+
+use crate::monomial::Monomial;
+use crate::signature::{Signature, VerifyingKey};
+use crate::snapshot::SnapshotId;
+
+/// Domain separator for statement digests
+const STATEMENT_DOMAIN: &[u8] = b"certified-dp certificate";
+
+/// What the auditor certified, which both parties derive independently from the session
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Statement {
+    pub dataset: String,
+    pub db_size: u32,
+    pub dimension: u32,
+    pub epsilon: f32,
+    pub delta: Option<f32>,
+    pub g: RistrettoPoint,
+    pub h: RistrettoPoint,
+    pub epoch: u64,
+    pub snapshot: SnapshotId,
+    pub transcript: [u8; 32],
+}
+
+impl Statement {
+    /// Digest the signatures are on
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(STATEMENT_DOMAIN)
+            .chain_update(serde_json::to_vec(self).unwrap())
+            .finalize()
+            .into()
+    }
+
+    /// Hex digest, which both parties print so they can confirm they certified the same statement
+    pub fn fingerprint(&self) -> String {
+        self.digest().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Signature on a statement, with the key that made it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Endorsement {
+    pub key: VerifyingKey,
+    pub signature: Signature,
+}
+
+/// Certificate file: the signed statement and the commitment map its snapshot roots
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Certificate<T: Eq + Hash> {
+    pub statement: Statement,
+    pub commitment_map: HashMap<Monomial<T>, RistrettoPoint>,
+    pub endorsements: Vec<Endorsement>,
+}
+
+impl<T: PrimInt + Hash + Serialize + DeserializeOwned> Certificate<T> {
+    /// Whether `key` signed the statement
+    pub fn signed_by(&self, key: &VerifyingKey) -> bool {
+        let digest = self.statement.digest();
+        self.endorsements.iter().any(|e| e.key == *key && e.key.verify(&digest, &e.signature))
+    }
+
+    /// Load the certificate at `path`, checking that `auditor` signed it and that its commitment map is the one the
+    /// snapshot roots
+    pub fn load(path: &Path, auditor: &VerifyingKey) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let certificate: Self = serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !certificate.signed_by(auditor) {
+            return Err(format!("{} is not signed by auditor key {}", path.display(), auditor.to_hex()));
+        }
+        if SnapshotId::of(certificate.commitment_map.iter().map(|(&k, &v)| (k, v))) != certificate.statement.snapshot {
+            return Err(format!("{} commitment map does not match its snapshot {}", path.display(), certificate.statement.snapshot));
+        }
+        Ok(certificate)
+    }
+
+    /// Write the certificate to `path`
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, serde_json::to_vec_pretty(self).unwrap()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
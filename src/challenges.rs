@@ -0,0 +1,64 @@
+/*!
+ * challenges.rs
+ *
+ * Merlin transcript the verifier draws every challenge of a session from. The transcript absorbs the session's setup
+ * and each prover message before the verifier challenges it, as well as the challenges sent back, and each challenge is
+ * labelled by its phase and the index of the entry, node, or coin it is for. A challenge is then bound to everything
+ * the prover said before it, and to its place in the session, so a prover cannot mix messages from one place into
+ * another. The challenges stay random, as the verifier's own randomness keys the transcript's generator each time;
+ * keyed with fixed randomness instead, they are derived from the transcript alone, as Fiat–Shamir would need.
+ */
+
+use merlin::{Transcript, TranscriptRng};
+use rand::{CryptoRng, RngCore};
+use serde::Serialize;
+
+/// Domain separator of the transcript
+const DOMAIN: &[u8] = b"certified-dp challenges";
+
+/// Label of a challenge: the phase, and the sub-protocol within it, that it is for
+pub type Label = &'static [u8];
+
+/// Challenge to an entry's bits in the dishonest commitment phase, by entry
+pub const BIT_SIGMA: Label = b"dishonest-commitment/bit-sigma";
+/// Challenge to a node of an entry's monomial tree in the dishonest commitment phase, by entry and node
+pub const PRODUCT_SIGMA: Label = b"dishonest-commitment/product-sigma";
+/// Entries an audit checks, by the window they are drawn for
+pub const AUDIT: Label = b"dishonest-commitment/audit";
+/// Encodings kept by the cut-and-choose commitment phase
+pub const CUT_AND_CHOOSE: Label = b"cut-and-choose/kept";
+/// Challenges to the attribute sketch
+pub const SKETCH: Label = b"sketch";
+/// Flip of and challenge to a noise coin, or this verifier's contribution to both, by coin
+pub const COIN: Label = b"randomness/coin";
+/// Challenge to a product of noise coins, by product
+pub const NOISE_PRODUCT: Label = b"randomness/noise-product";
+
+/// Running transcript of a session, on the verifier's side
+pub struct ChallengeTranscript {
+    transcript: Transcript,
+}
+
+impl Default for ChallengeTranscript {
+    fn default() -> Self {
+        ChallengeTranscript { transcript: Transcript::new(DOMAIN) }
+    }
+}
+
+impl ChallengeTranscript {
+    /// Absorb `message`, in its bincode encoding, which the message maps keep canonical
+    pub fn absorb<M: Serialize + ?Sized>(&mut self, label: Label, message: &M) {
+        let encoded = bincode::serialize(message).expect("protocol messages always encode");
+        self.transcript.append_message(label, &encoded);
+    }
+
+    /// Generator of the challenge labelled `label` at `indices`, e.g. an entry and a node of its tree, from everything
+    /// absorbed so far, keyed with the verifier's own randomness from `rng`
+    pub fn rng<R: RngCore + CryptoRng>(&mut self, label: Label, indices: &[u64], rng: &mut R) -> TranscriptRng {
+        self.transcript.append_message(b"challenge", label);
+        for &index in indices {
+            self.transcript.append_u64(b"index", index);
+        }
+        self.transcript.build_rng().finalize(rng)
+    }
+}
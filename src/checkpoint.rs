@@ -0,0 +1,49 @@
+/*!
+ * checkpoint.rs
+ *
+ * Checkpoints of the randomness phase with a single verifier, so a connection that drops partway through does not lose
+ * the coins flipped before it. The phase runs in windows of coins: the prover commits to a window, the verifier flips
+ * and challenges it, and the prover answers. Once the verifier accepts a window, both sides fold the commitments to its
+ * flipped coins into a running digest, so a checkpoint is the number of coins agreed and the digest of their
+ * commitments. On a new connection, the verifier sends its checkpoint along with its challenges to the window it has
+ * not accepted yet, if any, and the prover answers with its own. The prover only catches up with a checkpoint its
+ * last answered window leads to, and only answers a window with the challenges it answered before, so resuming neither
+ * re-rolls a coin the verifier flipped nor lets the verifier challenge the same coin twice.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Domain separator for checkpoint digests
+const CHECKPOINT_DOMAIN: &[u8] = b"certified-dp randomness checkpoint";
+
+/// Coins of the randomness phase both sides agree on so far, and the digest of the commitments to them in coin order;
+/// the default is the start of the phase
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub coins: u32,
+    pub digest: [u8; 32],
+}
+
+impl Checkpoint {
+    /// Checkpoint after the coins of `commitments` are flipped on top of this one's
+    pub fn advance<'a>(&self, commitments: impl IntoIterator<Item = &'a RistrettoPoint>) -> Checkpoint {
+        let mut hasher = Sha256::new();
+        hasher.update(CHECKPOINT_DOMAIN);
+        hasher.update(self.digest);
+        let mut coins = self.coins;
+        for commitment in commitments {
+            hasher.update(commitment.compress().as_bytes());
+            coins += 1;
+        }
+        Checkpoint { coins, digest: hasher.finalize().into() }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Checkpoint {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Checkpoint { coins: u.arbitrary()?, digest: u.arbitrary()? })
+    }
+}
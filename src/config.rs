@@ -1,31 +1,39 @@
-/**
+/*!
  * config.rs
  * 
- * Calculates DP parameters and contains other global constants such as the prover address and port.
+ * Global constants such as the prover address and port, and entry widths; the DP parameters are in `calibration`.
  */
 
-/// DP parameter: get `n` based on epsilon and delta; if delta is not provided, assume the default and calculate it based on the database size
+use num_traits::PrimInt;
+
+/// Prover network address configuration
+pub const PROVER_ADDRESS: &str = "127.0.0.1";
+pub const PROVER_PORT: &str = "10020";
+
+/// Default database entry type, for code that does not pick a width at runtime
+pub type DataT = u16;
+
+/// Widths in bits of the database entries the executables can run with
+pub const ENTRY_WIDTHS: [u32; 5] = [8, 16, 32, 64, 128];
+
+/// Most attributes an entry of type `T` holds, and so the largest dimension of a database of them
 #[inline]
-pub fn get_n(db_size: u32, epsilon: f32, delta: Option<f32>) -> u32 {
-    match delta {
-        Some(d) => ((8.0 * (2.0 / d).log2()) / epsilon.powi(2)).ceil() as u32,
-        // Delta set to 1/size^(log(size)), thus N = 8 * log(2/delta) / epsilon^(2)
-        None => ((8 * (db_size.ilog2().pow(2) + 1)) as f32 / (epsilon.powi(2) as f32)).ceil() as u32
-    }
+pub fn max_dimension<T: PrimInt>() -> u32 {
+    T::zero().count_zeros()
 }
 
-/// DP parameter: get `delta` based on configuration provided or calculate it based on the database size
+/// Narrowest entry width that holds `dimension` attributes, if any does
 #[inline]
-pub fn get_delta(db_size: u32, delta: Option<f32>) -> f32 {
-    match delta {
-        Some(d) => d,
-        None => 1.0 / (db_size as f32).powf(db_size.ilog2() as f32) as f32
-    }
+pub fn entry_width(dimension: u32) -> Option<u32> {
+    ENTRY_WIDTHS.into_iter().find(|&width| width >= dimension)
 }
 
-/// Prover network address configuration
-pub const PROVER_ADDRESS: &str = "127.0.0.1";
-pub const PROVER_PORT: &str = "10020";
-
-/// Database entry type configuration
-pub type DataT = u16;
\ No newline at end of file
+/// Parse an entry width from the command line; must be one of `ENTRY_WIDTHS`
+pub fn parse_entry_width(s: &str) -> Result<u32, String> {
+    let width: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if ENTRY_WIDTHS.contains(&width) {
+        Ok(width)
+    } else {
+        Err(format!("entry width must be one of {:?} bits, got {}", ENTRY_WIDTHS, width))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,193 @@
+/*!
+ * cut_and_choose.rs
+ *
+ * Cut-and-choose certification of committed database entries, an alternative to the bit-Σ and product-Σ proofs.
+ * Every multiplication an entry needs (each bit times itself, and each partial product of its monomial tree) is checked
+ * with a committed multiplication triple c = a * b. The prover commits to several encodings of each entry, i.e. one
+ * fresh triple per multiplication; the verifier opens all but one encoding completely and the remaining one is used to
+ * prove the entry's products. Since a, b are uniform, the masked openings x - a, y - b reveal nothing about the entry.
+ */
+
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::monomial::{Committed, MonomialTree};
+use crate::pedersen;
+#[cfg(feature = "arbitrary")]
+use crate::fuzzing;
+
+/// Multiplication triple c = a * b with its commitment randomness; sent in full when its encoding is opened.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Triple {
+    a: Scalar,
+    r_a: Scalar,
+    b: Scalar,
+    r_b: Scalar,
+    c: Scalar,
+    r_c: Scalar,
+}
+
+/// Commitments to the three values of a multiplication triple.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TripleCommitment {
+    c_a: RistrettoPoint,
+    c_b: RistrettoPoint,
+    c_c: RistrettoPoint,
+}
+
+/// Proof that m_1 * m_2 = m_3 for committed values, using a triple that was not opened.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProductResponse {
+    d: Scalar,
+    r_d: Scalar,
+    e: Scalar,
+    r_e: Scalar,
+    r_z: Scalar,
+}
+
+/// (1) The prover generates a random multiplication triple and commits to it.
+pub fn triple<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams) -> (Triple, TripleCommitment) {
+
+    let (a, b) = (Scalar::random(rng), Scalar::random(rng));
+    let c = a * b;
+
+    let (c_a, r_a) = pedersen::commit(rng, &a, pp);
+    let (c_b, r_b) = pedersen::commit(rng, &b, pp);
+    let (c_c, r_c) = pedersen::commit(rng, &c, pp);
+
+    (
+        Triple {
+            a, r_a,
+            b, r_b,
+            c, r_c,
+        },
+        TripleCommitment {
+            c_a, c_b, c_c,
+        }
+    )
+}
+
+/// (2a) The verifier checks an opened triple against its commitment.
+pub fn verify_opening(pp: &pedersen::PublicParams, comm: &TripleCommitment, opening: &Triple) -> bool {
+    opening.c == opening.a * opening.b
+        && pedersen::verify(&comm.c_a, &opening.a, &opening.r_a, pp)
+        && pedersen::verify(&comm.c_b, &opening.b, &opening.r_b, pp)
+        && pedersen::verify(&comm.c_c, &opening.c, &opening.r_c, pp)
+}
+
+/// (2b) The prover uses an unopened triple to show that m_1 * m_2 = m_3, by revealing d = m_1 - a and e = m_2 - b.
+pub fn respond(triple: &Triple,
+               (m_1, _, r_1): (Scalar, RistrettoPoint, Scalar),
+               (m_2, _, r_2): (Scalar, RistrettoPoint, Scalar),
+               (_, _, r_3): (Scalar, RistrettoPoint, Scalar)) -> ProductResponse {
+
+    let d = m_1 - triple.a;
+    let e = m_2 - triple.b;
+
+    ProductResponse {
+        d,
+        r_d: r_1 - triple.r_a,
+        e,
+        r_e: r_2 - triple.r_b,
+        r_z: r_3 - triple.r_c - (d * triple.r_b) - (e * triple.r_a),
+    }
+}
+
+/// (3) The verifier checks the masked openings, and that c_3 commits to c + d * b + e * a + d * e = m_1 * m_2.
+pub fn verify(pp: &pedersen::PublicParams, comm: &TripleCommitment,
+              (c_1, c_2, c_3): (RistrettoPoint, RistrettoPoint, RistrettoPoint), response: &ProductResponse) -> bool {
+
+    if !pedersen::verify(&(c_1 - comm.c_a), &response.d, &response.r_d, pp) {
+        return false;
+    }
+
+    if !pedersen::verify(&(c_2 - comm.c_b), &response.e, &response.r_e, pp) {
+        return false;
+    }
+
+    let product = comm.c_c + (response.d * comm.c_b) + (response.e * comm.c_a) + (response.d * response.e * pp.g);
+    pedersen::verify(&(c_3 - product), &Scalar::ZERO, &response.r_z, pp)
+}
+
+/// Committed values (factor, factor, product) of a single multiplication
+pub type Multiplication<V> = (V, V, V);
+
+/// Every multiplication that certifies an entry, as (factor, factor, product): first each bit times itself, then each
+/// partial product of the monomial tree in preorder. Returns None if the tree does not match the entry's bits.
+pub fn multiplications<P: Committed>(bits: &[P::Value], tree: &MonomialTree<P>) -> Option<Vec<Multiplication<P::Value>>> {
+    if tree[tree.root()].commitment().is_some() {
+        return None;
+    }
+
+    let mut mults: Vec<_> = bits.iter().map(|&b| (b, b, b)).collect();
+    for visit in tree.preorder() {
+        let (Some(parent), Some(i)) = (visit.parent, visit.variable) else {
+            continue;
+        };
+        let bit = *bits.get(i)?;
+        let value = tree[visit.id].commitment()?;
+
+        match tree[parent].commitment() {
+            // degree one monomials are the bits themselves
+            None => if value != bit {
+                return None;
+            },
+            Some(p) => mults.push((p, bit, value)),
+        }
+    }
+    Some(mults)
+}
+
+/// Parse the number of encodings per entry from the command line; at least two, so one can be kept
+pub fn parse_encodings(s: &str) -> Result<u32, String> {
+    let encodings: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if encodings >= 2 {
+        Ok(encodings)
+    } else {
+        Err(format!("cut-and-choose needs at least 2 encodings per entry, got {}", encodings))
+    }
+}
+
+/// One-line summary of the soundness error: a single bad triple escapes only if its encoding is the one kept
+pub fn describe(encodings: u32) -> String {
+    format!("{} encodings per entry, soundness error {:.4} against a single corrupted entry", encodings, 1.0 / encodings as f64)
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Triple {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Triple {
+            a: fuzzing::scalar(u)?,
+            r_a: fuzzing::scalar(u)?,
+            b: fuzzing::scalar(u)?,
+            r_b: fuzzing::scalar(u)?,
+            c: fuzzing::scalar(u)?,
+            r_c: fuzzing::scalar(u)?,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for TripleCommitment {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(TripleCommitment {
+            c_a: fuzzing::point(u)?,
+            c_b: fuzzing::point(u)?,
+            c_c: fuzzing::point(u)?,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ProductResponse {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProductResponse {
+            d: fuzzing::scalar(u)?,
+            r_d: fuzzing::scalar(u)?,
+            e: fuzzing::scalar(u)?,
+            r_e: fuzzing::scalar(u)?,
+            r_z: fuzzing::scalar(u)?,
+        })
+    }
+}
@@ -1,44 +1,206 @@
-/**
+/*!
  * data.rs
  * 
- * Structures for the individual database entries and their commitments, based on configured type (e.g., u16)
+ * Structures for the individual database entries and their commitments, based on configured type (e.g., u16), and the
+ * entry types a database can have. A database is either random, for testing, or loaded from a CSV, JSON-lines, or
+ * Parquet file whose columns are each encoded into the bits the schema gives their attribute (see `schema.rs`).
  */
 
 use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use num_traits::{PrimInt, FromBytes, ToBytes};
-use rand::{CryptoRng, Fill, Rng};
+use num_traits::PrimInt;
+use rand::{CryptoRng, Rng};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+#[cfg(feature = "parquet")]
+use parquet::{errors::ParquetError, file::reader::{FileReader, SerializedFileReader}, record::Field, schema::types::Type};
+
+use crate::config::max_dimension;
+use crate::monomial::Monomial;
+use crate::schema::Schema;
+use crate::snapshot::SnapshotId;
 
 /// Database entries and commitments are just a vector of values and hashmap of commitments, respectively
 pub struct Data<T> {
     pub entries: Vec<T>,
-    pub commitments: HashMap<T, (Scalar, RistrettoPoint, Scalar)>,
+    pub commitments: HashMap<Monomial<T>, (Scalar, RistrettoPoint, Scalar)>,
+}
+
+/// Unsigned integer type of database entries, one bit per attribute, among those of `config::ENTRY_WIDTHS` that the
+/// executables pick from at runtime
+pub trait Entry: PrimInt + Hash + Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Entry with uniformly random bits
+    fn random<R: Rng + CryptoRng>(rng: &mut R) -> Self;
 }
 
+macro_rules! impl_entry {
+    ($($t:ty),*) => {
+        $(impl Entry for $t {
+            fn random<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+                rng.gen()
+            }
+        })*
+    };
+}
+
+impl_entry!(u8, u16, u32, u64, u128);
+
 /// Helper to generate random data and an empty hashmap of commitments for testing
-impl<T> Data<T>
-where
-    T: PrimInt + ToBytes + FromBytes<Bytes = <T as ToBytes>::Bytes>,
-    <T as ToBytes>::Bytes: Sized + Fill,
-    <T as FromBytes>::Bytes: Sized + Fill
-{
+impl<T: Entry> Data<T> {
     pub fn new<R: Rng + CryptoRng>(rng: &mut R, db_size: u32) -> Self {
 
-        let mut entries = Vec::new();
+        let entries = (0..db_size).map(|_| T::random(rng)).collect();
         let commitments = HashMap::new();
 
-        for _ in 0..db_size {
-
-            let mut val: T = T::zero();
-            let mut bytes = val.to_le_bytes();
-            rng.fill(&mut bytes);
-            val = T::from_le_bytes(&bytes);
-            entries.push(val);
-        }
-
         Data {
             entries,
             commitments,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Check that the attributes of `schema` fit in an entry of type `T`
+fn check_width<T: Entry>(schema: &Schema) -> Result<(), String> {
+    if schema.bits() > max_dimension::<T>() {
+        return Err(format!("columns take {} bits, but an entry holds {}", schema.bits(), max_dimension::<T>()));
+    }
+    Ok(())
+}
+
+/// Value of a JSON field as the text a column encoding reads, for booleans, unsigned integers, and strings
+fn json_value(value: &serde_json::Value) -> Result<String, String> {
+    match value {
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        serde_json::Value::Number(n) if n.is_u64() => Ok(n.to_string()),
+        serde_json::Value::String(s) => Ok(s.clone()),
+        _ => Err(format!("{} is not a boolean, an unsigned integer, or a string", value)),
+    }
+}
+
+/// Value of a Parquet field as the text a column encoding reads, for the boolean, integer, and string types
+#[cfg(feature = "parquet")]
+fn field_value(field: &Field) -> Result<String, String> {
+    Ok(match field {
+        Field::Bool(b) => b.to_string(),
+        Field::Byte(n) => n.to_string(),
+        Field::Short(n) => n.to_string(),
+        Field::Int(n) => n.to_string(),
+        Field::Long(n) => n.to_string(),
+        Field::UByte(n) => n.to_string(),
+        Field::UShort(n) => n.to_string(),
+        Field::UInt(n) => n.to_string(),
+        Field::ULong(n) => n.to_string(),
+        Field::Str(s) => s.clone(),
+        _ => return Err(format!("{} is not a boolean, an integer, or a string", field)),
+    })
+}
+
+impl<T: Entry> Data<T> {
+    /// Load a database from the CSV file at `path`, whose header names its columns. Each row becomes an entry, with
+    /// the columns of the attributes of `schema` encoded into its bits, so they must fit in an entry of type `T`; the
+    /// file's other columns are ignored.
+    pub fn new_from_csv(path: &Path, schema: &Schema) -> Result<Self, String> {
+        check_width::<T>(schema)?;
+
+        let unreadable = |e: csv::Error| format!("{}: {}", path.display(), e);
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(path).map_err(unreadable)?;
+        let header = reader.headers().map_err(unreadable)?.clone();
+        let indices = schema.attributes().iter()
+            .map(|c| header.iter().position(|h| h == c.name).ok_or_else(|| format!("{}: no column {:?}", path.display(), c.name)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut entries = Vec::new();
+        for (row, record) in reader.records().enumerate() {
+            let record = record.map_err(unreadable)?;
+            let values: Vec<&str> = indices.iter().map(|&i| &record[i]).collect();
+            entries.push(schema.encode(&values).map_err(|e| format!("{}: row {}, {}", path.display(), row + 1, e))?);
+        }
+        Ok(Data { entries, commitments: HashMap::new() })
+    }
+
+    /// Load a database from the JSON-lines file at `path`, each of whose non-blank lines is a record with a field per
+    /// attribute of `schema`, encoded into that record's entry as `new_from_csv` does; other fields are ignored.
+    pub fn new_from_jsonl(path: &Path, schema: &Schema) -> Result<Self, String> {
+        check_width::<T>(schema)?;
+
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut entries = Vec::new();
+        for (line, text) in BufReader::new(file).lines().enumerate() {
+            let text = text.map_err(|e| format!("{}: {}", path.display(), e))?;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let at = |e: String| format!("{}: line {}, {}", path.display(), line + 1, e);
+            let record: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&text).map_err(|e| at(e.to_string()))?;
+            let values = schema.attributes().iter()
+                .map(|c| record.get(&c.name).ok_or_else(|| "no field".to_string()).and_then(json_value).map_err(|e| at(format!("column {}: {}", c.name, e))))
+                .collect::<Result<Vec<_>, _>>()?;
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            entries.push(schema.encode(&values).map_err(at)?);
+        }
+        Ok(Data { entries, commitments: HashMap::new() })
+    }
+
+    /// Load a database from the Parquet file at `path`, encoding the columns of the attributes of `schema` into each
+    /// row's entry as `new_from_csv` does. Only those columns are read from the file, which must be top-level
+    /// booleans, integers, or strings, and with `max_rows` only that many rows from its start, so a large file can be
+    /// loaded in part.
+    #[cfg(feature = "parquet")]
+    pub fn new_from_parquet(path: &Path, schema: &Schema, max_rows: Option<usize>) -> Result<Self, String> {
+        check_width::<T>(schema)?;
+        let columns = schema.attributes();
+
+        let unreadable = |e: ParquetError| format!("{}: {}", path.display(), e);
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let reader = SerializedFileReader::new(file).map_err(unreadable)?;
+        let file_schema = reader.metadata().file_metadata().schema();
+        if let Some(c) = columns.iter().find(|c| !file_schema.get_fields().iter().any(|f| f.name() == c.name)) {
+            return Err(format!("{}: no column {:?}", path.display(), c.name));
+        }
+        // project onto the named columns, in the file's order, so the others are never decoded
+        let fields = file_schema.get_fields().iter().filter(|f| columns.iter().any(|c| c.name == f.name())).cloned().collect();
+        let projection = Type::group_type_builder(file_schema.name()).with_fields(fields).build().map_err(unreadable)?;
+
+        let rows = reader.get_row_iter(Some(projection)).map_err(unreadable)?.take(max_rows.unwrap_or(usize::MAX));
+        let mut entries = Vec::new();
+        for (row, record) in rows.enumerate() {
+            let record = record.map_err(unreadable)?;
+            let values = columns.iter()
+                .map(|c| {
+                    let field = record.get_column_iter().find(|(name, _)| **name == c.name).map_or(&Field::Null, |(_, field)| field);
+                    field_value(field).map_err(|e| format!("{}: row {}, column {}: {}", path.display(), row + 1, c.name, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            entries.push(schema.encode(&values).map_err(|e| format!("{}: row {}, {}", path.display(), row + 1, e))?);
+        }
+        Ok(Data { entries, commitments: HashMap::new() })
+    }
+}
+
+/// The values and openings of the monomial commitments would let anyone reading the prover's memory open them
+impl<T> Zeroize for Data<T> {
+    fn zeroize(&mut self) {
+        self.commitments.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl<T> Drop for Data<T> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<T> ZeroizeOnDrop for Data<T> {}
+
+impl<T: PrimInt> Data<T> {
+    /// Snapshot of the database as committed by the last commitment phase
+    pub fn snapshot(&self) -> SnapshotId {
+        SnapshotId::of(self.commitments.iter().map(|(&monomial, &(_, comm, _))| (monomial, comm)))
+    }
+}
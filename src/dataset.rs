@@ -0,0 +1,51 @@
+/*!
+ * dataset.rs
+ *
+ * Named datasets, so that one prover process can host several independent databases, each with its own commitments,
+ * privacy budget, and parameters. A verifier names the dataset it audits at the start of a session, and every query
+ * carries that name so the prover only answers it from the dataset the session committed to.
+ */
+
+/// Name of the single dataset of a prover without named datasets, which verifiers query unless told otherwise
+pub const DEFAULT_DATASET: &str = "";
+
+/// Dataset configured on the prover's command line; parameters left unset fall back to the prover's own options
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DatasetSpec {
+    pub name: String,
+    pub db_size: Option<u32>,
+    pub dimension: Option<u32>,
+    pub max_degree: Option<u32>,
+    pub epsilon: Option<f32>,
+    pub epoch_budget: Option<f64>,
+}
+
+/// Parse a dataset name: letters, digits, `-`, `_`, and `.`
+pub fn parse_name(s: &str) -> Result<String, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+        return Err(format!("dataset name must be letters, digits, '-', '_', or '.', got {:?}", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a dataset from the command line: its name, then comma separated `key=value` parameters among db-size,
+/// dimension, max-degree, epsilon, and epoch-budget, e.g. `census,db-size=1000,epsilon=0.5`
+pub fn parse_spec(s: &str) -> Result<DatasetSpec, String> {
+    let mut parts = s.split(',');
+    let mut spec = DatasetSpec { name: parse_name(parts.next().unwrap_or_default())?, ..Default::default() };
+
+    for part in parts {
+        let (key, value) = part.split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {:?}", part))?;
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid {}: {}", key, e);
+        match key {
+            "db-size" => spec.db_size = Some(value.parse().map_err(|e| invalid(&e))?),
+            "dimension" => spec.dimension = Some(value.parse().map_err(|e| invalid(&e))?),
+            "max-degree" => spec.max_degree = Some(value.parse().map_err(|e| invalid(&e))?),
+            "epsilon" => spec.epsilon = Some(value.parse().map_err(|e| invalid(&e))?),
+            "epoch-budget" => spec.epoch_budget = Some(crate::epoch::parse_budget(value)?),
+            _ => return Err(format!("unknown dataset parameter {:?}", key)),
+        }
+    }
+    Ok(spec)
+}
@@ -0,0 +1,52 @@
+/*!
+ * deadline.rs
+ *
+ * Wall-clock deadlines on protocol phases, e.g. that the dishonest commitment phase finishes within two hours. While
+ * a phase with a deadline runs, each read from the peer waits no longer than the time the phase has left, and reads,
+ * writes, and the prover's windows of work fail once none is left, however the phase's time was spent, so a peer
+ * trickling messages in gets no longer than one sending nothing. The driver then aborts the protocol, sending the peer
+ * an `AbortMessage` with the reason so both sides report it.
+ */
+
+use std::time::Duration;
+
+use crate::driver::Phase;
+use crate::epoch;
+
+/// Deadline of each phase, if it has one
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Deadlines {
+    limits: [Option<Duration>; Phase::ALL.len()],
+}
+
+impl Deadlines {
+    /// Deadlines for the given phases, the last one winning if a phase is given twice
+    pub fn new(deadlines: &[(Phase, Duration)]) -> Deadlines {
+        let mut limits = [None; Phase::ALL.len()];
+        for &(phase, limit) in deadlines {
+            limits[phase as usize] = Some(limit);
+        }
+        Deadlines { limits }
+    }
+
+    /// Deadline of `phase`, if it has one
+    pub fn get(&self, phase: Phase) -> Option<Duration> {
+        self.limits[phase as usize]
+    }
+}
+
+/// Command line name of a phase, e.g. "dishonest-commitment"
+pub fn phase_name(phase: Phase) -> String {
+    phase.name().to_lowercase().replace(' ', "-")
+}
+
+/// Parse a phase deadline from the command line, as the phase name and a duration in the form of an epoch length,
+/// e.g. dishonest-commitment=2h
+pub fn parse_deadline(s: &str) -> Result<(Phase, Duration), String> {
+    let (name, limit) = s.split_once('=').ok_or_else(|| format!("expected PHASE=DURATION, got {:?}", s))?;
+    let phase = Phase::ALL.into_iter().find(|&phase| phase_name(phase) == name).ok_or_else(|| {
+        let names: Vec<String> = Phase::ALL.into_iter().map(phase_name).collect();
+        format!("unknown phase {:?}, expected one of {}", name, names.join(", "))
+    })?;
+    Ok((phase, epoch::parse_length(limit)?))
+}
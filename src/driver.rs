@@ -0,0 +1,637 @@
+/*!
+ * driver.rs
+ *
+ * Phase sequencing shared by the prover and verifier executables. For each session the driver opens a connection, runs
+ * the phases in protocol order with a synchronization barrier around each one, and measures their runtime and peak heap
+ * usage. Each executable only supplies the work done inside a phase by implementing `Role`. Before connecting, the
+ * driver checks that the party's profile permits every phase and operation the session calls for. A certifying session
+ * ends after the commitment phases with a certify phase over the transcript of everything exchanged until then, and a
+ * resumed session goes straight from setup to the queries, on the commitments and noise of an earlier session. A phase
+ * that runs past its deadline aborts the protocol on both sides, as soon as its next read, write, or round finds no
+ * time left. Each session's setup starts by agreeing on the wire
+ * format of its messages. The verifier may end the query phase before the session's last query, at the barrier before
+ * the next one. A daemon serves sessions until it is stopped, and a session that fails only ends itself: the next
+ * verifier starts a session planned as if the failed one had never run, e.g. certifying again if the certifying session
+ * failed. Once the first session has completed, the sessions after it can run concurrently, each on a thread of its own
+ * with a fork of the role's state; their phases then measure the peak heap usage of the whole process.
+ */
+
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::deadline::Deadlines;
+use crate::error::{Error, Result};
+use crate::memory;
+use crate::messages::{self, read_message, write_abort, write_message, AbortReason, Framed, ReadyMessage, WireFormat, WireFormatMessage};
+use crate::profile::Profile;
+use crate::recording;
+use crate::transport::Transport;
+
+/// Protocol phases, in execution order
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Setup,
+    HonestCommitment,
+    DishonestCommitment,
+    Update,
+    Randomness,
+    Query,
+    Certify,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 7] = [
+        Phase::Setup,
+        Phase::HonestCommitment,
+        Phase::DishonestCommitment,
+        Phase::Update,
+        Phase::Randomness,
+        Phase::Query,
+        Phase::Certify,
+    ];
+
+    /// Name used in log lines, e.g. "Honest commitment phase start"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Setup => "Setup",
+            Phase::HonestCommitment => "Honest commitment",
+            Phase::DishonestCommitment => "Dishonest commitment",
+            Phase::Update => "Update",
+            Phase::Randomness => "Randomness",
+            Phase::Query => "Query",
+            Phase::Certify => "Certify",
+        }
+    }
+}
+
+/// Side of the protocol; decides who speaks first at a synchronization barrier
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Prover,
+    Verifier,
+}
+
+/// Logger printing what the protocol's phases report of their progress, indented under the driver's phase lines
+struct PhaseLog;
+
+impl log::Log for PhaseLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info && metadata.target().starts_with("certified_dp")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("  {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Print the progress the protocol's phases log to stderr, as the executables do; an embedding program installs a
+/// logger of its own instead. Does nothing if a logger is already installed.
+pub fn log_progress() {
+    static LOGGER: PhaseLog = PhaseLog;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+}
+
+/// Log the start of `phase`, whose frames a recording then records as the phase's
+fn start_phase(phase: Phase) {
+    eprintln!("{} phase start", phase.name());
+    recording::set_phase(phase);
+}
+
+/// Synchronize with the peer so both parties start (or finish) a phase together. The verifier sends its ready
+/// message first and the prover answers, so neither side can run ahead of the other.
+pub fn synchronize<S: Transport + ?Sized>(side: Side, stream: &mut Framed<S>) -> Result<()> {
+    synchronize_query(side, stream, true).map(|_| ())
+}
+
+/// Synchronize with the peer before a round of queries, the verifier saying whether it has one to ask; a verifier
+/// not ready ends the query phase early, which the prover learns from the ready message it echoes. Returns whether
+/// the round goes ahead.
+pub fn synchronize_query<S: Transport + ?Sized>(side: Side, stream: &mut Framed<S>, more: bool) -> Result<bool> {
+    match side {
+        Side::Prover => {
+            let m: ReadyMessage = read_message(stream)?;
+            write_message(stream, &ReadyMessage { ready: m.ready })?;
+            Ok(m.ready)
+        },
+        Side::Verifier => {
+            write_message(stream, &ReadyMessage { ready: more })?;
+            read_message::<ReadyMessage, _>(stream)?;
+            Ok(more)
+        },
+    }
+}
+
+/// Work done by one party inside each protocol phase. Phase methods fail if the peer misbehaved or a proof failed to
+/// verify, which aborts the protocol.
+pub trait Role: Sized + Send + 'static {
+    /// Role specific configuration, usually the parsed command line arguments
+    type Config;
+
+    /// Connection to the peer, e.g. a `TcpStream`
+    type Stream: Transport + Send;
+
+    /// Title of the summary table, e.g. "Prover"
+    const NAME: &'static str;
+    const SIDE: Side;
+
+    /// Initialize the role's local state; it lives across every session the driver runs
+    fn new(config: Self::Config) -> Self;
+
+    /// Start accepting connections before the first session, for a side that serves them
+    fn listen(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Open the connection to the peer for the next session
+    fn connect(&mut self) -> io::Result<Self::Stream>;
+
+    /// Agree on the shared parameters with the peer at the start of a session
+    fn setup(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    fn honest_commitment(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    fn dishonest_commitment(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    /// Move the committed database to a new version, proving only the rows that changed
+    fn update(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    fn randomness(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    /// Untimed work before each query's synchronization barrier, e.g. picking the query to send; returns whether
+    /// there is one, the verifier ending the query phase before its last query if not
+    fn prepare_query(&mut self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn query(&mut self, stream: &mut Framed<Self::Stream>) -> Result<()>;
+
+    /// Sign (or collect the signatures on) the certificate of the commitment phases, given the digest of the
+    /// session's transcript up to the certify phase
+    fn certify(&mut self, stream: &mut Framed<Self::Stream>, transcript: &[u8; 32]) -> Result<()>;
+
+    /// Whether the query phase reveals the answers to this party in the clear
+    fn learns_answers(&self) -> bool {
+        false
+    }
+
+    /// Evaluation-only runs after the query phase, e.g. the sparsity experiment
+    #[cfg(feature = "experiments")]
+    fn experiments(&mut self, _stream: &mut Framed<Self::Stream>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Extra detail for a phase's completion log line, e.g. the number of monomials committed
+    fn phase_detail(&self, _phase: Phase, _duration: Duration) -> Option<String> {
+        None
+    }
+
+    /// Print the role's result tables once all phases are complete
+    fn report(&self, stats: &ProtocolStats);
+
+    /// Work after a session completes successfully, e.g. saving its verified state
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Clean up after a session that failed, before a daemon serves the next one, e.g. closing the session's other
+    /// connections
+    fn abandon(&mut self) {}
+
+    /// State for a session run concurrently with others, sharing what the sessions before it committed, or `None` if
+    /// the role only runs its sessions one after another
+    fn fork(&self) -> Option<Self> {
+        None
+    }
+}
+
+/// Runtime and peak heap usage of a single phase
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseStats {
+    pub duration: Duration,
+    pub peak_memory: usize,
+}
+
+/// Measurements for every timed phase; the query phase is averaged over the number of queries
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProtocolStats {
+    pub honest_commitment: PhaseStats,
+    pub dishonest_commitment: PhaseStats,
+    pub update: PhaseStats,
+    pub randomness: PhaseStats,
+    pub query: PhaseStats,
+}
+
+impl ProtocolStats {
+    /// Summary table of phase runtimes and peak heap usage. Roles can append their own rows before printing.
+    pub fn table(&self, title: &str, description: String) -> Table {
+        let mut table = Table::new();
+        table.add_row(row![title, description, "Peak heap"]);
+        table.add_row(row!["Commit", "", ""]);
+        table.add_row(row!["  -> Honest", format!("{:?}", self.honest_commitment.duration), memory::format_bytes(self.honest_commitment.peak_memory)]);
+        table.add_row(row!["  -> Dishonest", format!("{:?}", self.dishonest_commitment.duration), memory::format_bytes(self.dishonest_commitment.peak_memory)]);
+        table.add_row(row!["  -> Update", format!("{:?}", self.update.duration), memory::format_bytes(self.update.peak_memory)]);
+        table.add_row(row!["Randomness", format!("{:?}", self.randomness.duration), memory::format_bytes(self.randomness.peak_memory)]);
+        table.add_row(row!["Query", format!("{:?}", self.query.duration), memory::format_bytes(self.query.peak_memory)]);
+        table
+    }
+}
+
+/// Options controlling which phases run, shared by both executables
+#[derive(Clone, Copy, Debug)]
+pub struct DriverOptions {
+    pub skip_dishonest: bool,
+    /// Run the update phase after the commitment phases
+    pub update: bool,
+    pub num_queries: u32,
+    /// Number of sessions run one after another with the same role state, e.g. several auditors of one database
+    pub sessions: u32,
+    /// Serve sessions one after another until the process is stopped, going on past any that fail, in place of
+    /// `sessions`
+    pub daemon: bool,
+    /// Most sessions run at once after the first, each on a thread of its own; 1 runs every session after the last
+    pub concurrent: u32,
+    /// Persona of the party, which limits the phases and operations it may run
+    pub profile: Profile,
+    /// Certify the commitments in the first session, which ends after the commitment phases; later sessions start
+    /// from them
+    pub certify: bool,
+    /// Start every session from commitments certified earlier, skipping the commitment phases
+    pub certified: bool,
+    /// Resume the first session in every later one, skipping the commitment and randomness phases
+    pub resume: bool,
+    /// Resume a session saved earlier in every session, skipping the commitment and randomness phases
+    pub resumed: bool,
+    /// Wall-clock limit on each phase, past which the protocol aborts
+    pub deadlines: Deadlines,
+    /// Wire format the verifier asks for as each session starts; the prover serves whichever one it is asked for
+    pub wire_format: WireFormat,
+    /// Whether the verifier asks for large frames to be gzipped; the prover does whatever it is asked
+    pub compress: bool,
+    /// Queries asked and answered together in each round of the query phase, one at a time if 1
+    pub batch: u32,
+    /// Largest frame accepted from the peer on each session's connection
+    pub max_frame_size: usize,
+}
+
+impl DriverOptions {
+    /// Phases the given session (counting from 1, over the sessions that completed before it) runs, in order
+    pub fn phases(&self, session: u32) -> Vec<Phase> {
+        let mut phases = vec![Phase::Setup];
+        if self.resumed || (self.resume && session > 1) {
+            if self.num_queries > 0 {
+                phases.push(Phase::Query);
+            }
+            return phases;
+        }
+        let from_certificate = self.certified || (self.certify && session > 1);
+        if !from_certificate {
+            phases.push(Phase::HonestCommitment);
+            if !self.skip_dishonest {
+                phases.push(Phase::DishonestCommitment);
+            }
+        }
+        if self.certify && session == 1 {
+            phases.push(Phase::Certify);
+            return phases;
+        }
+        if self.update {
+            phases.push(Phase::Update);
+        }
+        phases.push(Phase::Randomness);
+        if self.num_queries > 0 {
+            phases.push(Phase::Query);
+        }
+        phases
+    }
+}
+
+/// Runs the protocol phases for one party
+pub struct ProtocolDriver<R: Role> {
+    role: R,
+    options: DriverOptions,
+}
+
+impl<R: Role> ProtocolDriver<R> {
+
+    pub fn new(config: R::Config, options: DriverOptions) -> Self {
+        ProtocolDriver { role: R::new(config), options }
+    }
+
+    /// Connect to the peer and run the setup phase, returning the session's connection
+    pub fn setup(&mut self) -> Result<Framed<R::Stream>> {
+        start_phase(Phase::Setup);
+        let stream = self.connect()?;
+        self.setup_phase(stream)
+    }
+
+    /// Open the next session's connection, logging a failure
+    fn connect(&mut self) -> Result<Framed<R::Stream>> {
+        let stream = self.role.connect().map_err(|e| {
+            eprintln!("ERROR: Failed to connect: {}", e);
+            e
+        })?;
+        Ok(Framed::new(stream, self.options.max_frame_size))
+    }
+
+    /// Run the setup phase on a session's new connection, returning it
+    fn setup_phase(&mut self, mut stream: Framed<R::Stream>) -> Result<Framed<R::Stream>> {
+        // the session's transcript starts with its setup, and makes its ID once its commitment phases end
+        stream.start_transcript();
+        let start = self.start_deadline(&mut stream, Phase::Setup);
+        let outcome = self.negotiate_wire_format(&mut stream).and_then(|()| self.role.setup(&mut stream));
+        self.end_phase(&mut stream, Phase::Setup, start, outcome)?;
+
+        eprintln!("{} phase complete", Phase::Setup.name());
+        Ok(stream)
+    }
+
+    /// Run the phases of a session after setup in order, returning their measurements
+    pub fn run(&mut self, stream: &mut Framed<R::Stream>, session: u32) -> Result<ProtocolStats> {
+        let mut stats = ProtocolStats::default();
+        let mut id = None;
+        for phase in self.options.phases(session) {
+            if id.is_none() && !matches!(phase, Phase::Setup | Phase::HonestCommitment | Phase::DishonestCommitment | Phase::Update) {
+                let entered = stream.enter_session(R::SIDE);
+                eprintln!("  session {}", entered);
+                id = Some(entered);
+            }
+            match phase {
+                Phase::Setup => {},
+                Phase::HonestCommitment => stats.honest_commitment = self.timed(stream, phase, R::honest_commitment)?,
+                Phase::DishonestCommitment => stats.dishonest_commitment = self.timed(stream, phase, R::dishonest_commitment)?,
+                Phase::Update => stats.update = self.timed(stream, phase, R::update)?,
+                Phase::Randomness => stats.randomness = self.timed(stream, phase, R::randomness)?,
+                Phase::Query => stats.query = self.queries(stream)?,
+                Phase::Certify => {
+                    let transcript = *id.unwrap_or_default().as_bytes();
+                    self.timed(stream, phase, |role, stream| role.certify(stream, &transcript))?;
+                    return Ok(stats);
+                },
+            }
+        }
+
+        #[cfg(feature = "experiments")]
+        self.role.experiments(stream).inspect_err(|e| eprintln!("ERROR: Experiments failed: {}", e))?;
+
+        Ok(stats)
+    }
+
+    /// Run every session, printing the role's report after each. Fails as soon as any phase fails, once the failure
+    /// is logged, except as a daemon, which only stops if it cannot listen for connections. Concurrent sessions that
+    /// are still running when one fails are waited for, but no more are started.
+    pub fn execute(config: R::Config, options: DriverOptions) -> Result<()> {
+        let mut driver = Self::new(config, options);
+        driver.check_profile().inspect_err(|e| eprintln!("ERROR: {}", e))?;
+        driver.role.listen().map_err(|e| {
+            eprintln!("ERROR: Failed to listen: {}", e);
+            e
+        })?;
+
+        // concurrent sessions send their outcome once they end
+        let (done, ended) = mpsc::channel();
+        let mut running = 0;
+        let mut failure = None;
+        let mut completed = 0;
+        for session in 1.. {
+            if options.daemon {
+                eprintln!("Session {}", session);
+            } else if session > options.sessions || failure.is_some() {
+                break;
+            } else if options.sessions > 1 {
+                eprintln!("Session {}/{}", session, options.sessions);
+            }
+
+            if options.concurrent > 1 && completed > 0 {
+                while running >= options.concurrent {
+                    let (session, outcome) = ended.recv().expect("the driver keeps a sender");
+                    running -= 1;
+                    driver.ended(session, outcome, &mut completed, &mut failure);
+                }
+                driver.spawn(session, completed + 1, &done).inspect_err(|e| eprintln!("ERROR: {}", e))?;
+                running += 1;
+                continue;
+            }
+            recording::start(R::SIDE, session);
+            match driver.session(completed + 1) {
+                Ok(()) => completed += 1,
+                Err(e) if !options.daemon => return Err(e),
+                Err(_) => {
+                    driver.role.abandon();
+                    eprintln!("Session {} failed, waiting for the next verifier", session);
+                },
+            }
+        }
+        for (session, outcome) in ended.iter().take(running as usize) {
+            driver.ended(session, outcome, &mut completed, &mut failure);
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    /// Run one session, planned as the given one (counting from 1) after those that completed, through to the role's
+    /// report and its work after the session
+    fn session(&mut self, session: u32) -> Result<()> {
+        let stream = self.setup()?;
+        self.after_setup(stream, session)
+    }
+
+    /// Run the phases of a session after its setup, then the role's report and its work after the session
+    fn after_setup(&mut self, mut stream: Framed<R::Stream>, session: u32) -> Result<()> {
+        let stats = self.run(&mut stream, session)?;
+        self.role.report(&stats);
+        self.role.finish().inspect_err(|e| eprintln!("ERROR: {}", e))
+    }
+
+    /// Accept the given session's connection and run the session, planned as `planned`, on a thread of its own with a
+    /// fork of the role, which sends its outcome to `done`. Fails only if the role cannot be forked.
+    fn spawn(&mut self, session: u32, planned: u32, done: &Sender<(u32, Result<()>)>) -> Result<()> {
+        let role = self.role.fork()
+            .ok_or_else(|| Error::Config(format!("The {} side cannot run sessions concurrently", R::NAME.to_lowercase())))?;
+        let stream = match self.connect() {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = done.send((session, Err(e)));
+                return Ok(());
+            },
+        };
+        let mut driver = ProtocolDriver { role, options: self.options };
+        let done = done.clone();
+        thread::spawn(move || {
+            recording::start(R::SIDE, session);
+            start_phase(Phase::Setup);
+            let outcome = driver.setup_phase(stream).and_then(|stream| driver.after_setup(stream, planned));
+            let _ = done.send((session, outcome));
+        });
+        Ok(())
+    }
+
+    /// Count a concurrent session that ended, keeping the first failure to fail with unless serving as a daemon
+    fn ended(&self, session: u32, outcome: Result<()>, completed: &mut u32, failure: &mut Option<Error>) {
+        match outcome {
+            Ok(()) => *completed += 1,
+            Err(e) if !self.options.daemon => {
+                eprintln!("Session {} failed", session);
+                failure.get_or_insert(e);
+            },
+            Err(_) => eprintln!("Session {} failed, waiting for the next verifier", session),
+        }
+    }
+
+    pub fn role(&self) -> &R {
+        &self.role
+    }
+
+    /// Check that the profile runs this side and permits every phase and operation of a session
+    fn check_profile(&self) -> Result<()> {
+        let profile = self.options.profile;
+        if profile.side() != R::SIDE {
+            return Err(Error::Config(format!("The {} profile cannot run the {} side", profile, R::NAME.to_lowercase())));
+        }
+        // a daemon's later sessions all run the phases of its second
+        let sessions = if self.options.daemon { 2 } else { self.options.sessions };
+        let phases: Vec<Phase> = (1..=sessions).flat_map(|session| self.options.phases(session)).collect();
+        if let Some(phase) = phases.iter().find(|&&phase| !profile.permits(phase)) {
+            return Err(Error::Config(format!("The {} profile is not permitted the {} phase", profile, phase.name())));
+        }
+        if phases.contains(&Phase::Query) && self.role.learns_answers() && !profile.learns_answers() {
+            return Err(Error::Config(format!("The {} profile may not learn query answers in the clear", profile)));
+        }
+        Ok(())
+    }
+
+    /// Agree with the peer on the session's wire format and compression, before any other message of the session
+    fn negotiate_wire_format(&self, stream: &mut Framed<R::Stream>) -> Result<()> {
+        let agreed = match R::SIDE {
+            Side::Prover => messages::accept_wire_format(stream)?,
+            Side::Verifier => {
+                let request = WireFormatMessage { format: self.options.wire_format, compress: self.options.compress };
+                messages::request_wire_format(stream, request)?;
+                request
+            },
+        };
+        eprintln!("  wire format {}{}", agreed.format, if agreed.compress { ", compressed" } else { "" });
+        Ok(())
+    }
+
+    /// Run a single phase between two synchronization barriers, measuring it
+    fn timed(&mut self, stream: &mut Framed<R::Stream>, phase: Phase, work: impl FnOnce(&mut R, &mut Framed<R::Stream>) -> Result<()>) -> Result<PhaseStats> {
+        start_phase(phase);
+
+        if let Err(e) = synchronize(R::SIDE, stream) {
+            return self.end_phase(stream, phase, Instant::now(), Err(e)).map(|()| PhaseStats::default());
+        }
+        memory::reset_peak();
+        let start = self.start_deadline(stream, phase);
+        let outcome = work(&mut self.role, stream).and_then(|()| synchronize(R::SIDE, stream));
+        self.end_phase(stream, phase, start, outcome)?;
+        let stats = PhaseStats {
+            duration: start.elapsed(),
+            peak_memory: memory::peak(),
+        };
+
+        self.log_complete(phase, stats.duration);
+        Ok(stats)
+    }
+
+    /// Query phase: each round of `batch` queries runs between its own pair of barriers, every query of the round
+    /// prepared before the first, and the reported runtime is the average per query. The verifier may end the phase
+    /// at a round's first barrier, once it has no more queries to ask.
+    fn queries(&mut self, stream: &mut Framed<R::Stream>) -> Result<PhaseStats> {
+        start_phase(Phase::Query);
+
+        memory::reset_peak();
+        let phase_start = self.start_deadline(stream, Phase::Query);
+        let mut duration = Duration::from_secs(0);
+        let mut asked = 0;
+        let batch = self.options.batch.max(1);
+        for round in (0..self.options.num_queries).step_by(batch as usize) {
+            let end = self.options.num_queries.min(round + batch);
+            let mut start = Instant::now();
+            // preparing a round may take a while of its own, which counts against the phase's deadline
+            let outcome = stream.check_deadline()
+                .and_then(|_| (round..end).try_fold(true, |more, _| Ok(more && self.role.prepare_query()?)))
+                .and_then(|more| synchronize_query(R::SIDE, stream, more))
+                .and_then(|more| {
+                    if !more {
+                        return Ok(false);
+                    }
+                    start = Instant::now();
+                    self.role.query(stream).and_then(|()| synchronize(R::SIDE, stream)).map(|()| true)
+                });
+            match outcome {
+                Ok(true) => {},
+                Ok(false) => {
+                    eprintln!("  query phase ended after {} of {} queries", asked, self.options.num_queries);
+                    break;
+                },
+                Err(e) => return self.end_phase(stream, Phase::Query, phase_start, Err(e)).map(|()| PhaseStats::default()),
+            }
+            duration += start.elapsed();
+            asked += end - round;
+        }
+        self.end_phase(stream, Phase::Query, phase_start, Ok(()))?;
+        if asked > 0 {
+            duration /= asked;
+        }
+        let stats = PhaseStats {
+            duration,
+            peak_memory: memory::peak(),
+        };
+
+        self.log_complete(Phase::Query, stats.duration);
+        Ok(stats)
+    }
+
+    /// Start the clock on a phase, failing its reads and writes once it runs past the phase's deadline, if it has one,
+    /// each read waiting for the peer no longer than the time left
+    fn start_deadline(&self, stream: &mut Framed<R::Stream>, phase: Phase) -> Instant {
+        let start = Instant::now();
+        stream.set_deadline(self.options.deadlines.get(phase).map(|deadline| start + deadline));
+        start
+    }
+
+    /// End a phase started at `start` with the outcome of its work, logging it if it failed. A frame from the peer
+    /// that did not decode ends the session with an abort naming it. Past the phase's deadline, abort the protocol and
+    /// tell the peer why, whatever the outcome.
+    fn end_phase(&self, stream: &mut Framed<R::Stream>, phase: Phase, start: Instant, outcome: Result<()>) -> Result<()> {
+        if let Err(e) = &outcome {
+            eprintln!("ERROR: {} phase failed: {}", phase.name(), e);
+        }
+        stream.set_deadline(None);
+        if let Err(Error::Malformed { message, source }) = &outcome {
+            let reason = AbortReason::Malformed { message: message.to_string(), error: source.to_string() };
+            let _ = write_abort(stream, &reason);
+            return outcome;
+        }
+        let Some(deadline) = self.options.deadlines.get(phase) else {
+            return outcome;
+        };
+        let elapsed = start.elapsed();
+        if elapsed < deadline {
+            return outcome;
+        }
+        let reason = AbortReason::DeadlineExceeded { phase: phase.name().to_string(), deadline, elapsed };
+        eprintln!("ERROR: {}", reason);
+        // the peer may already be gone, which makes no difference to the abort
+        let _ = write_abort(stream, &reason);
+        Err(Error::Aborted(reason))
+    }
+
+    fn log_complete(&self, phase: Phase, duration: Duration) {
+        match self.role.phase_detail(phase, duration) {
+            Some(detail) => eprintln!("{} phase complete ({:?}, {})", phase.name(), duration, detail),
+            None => eprintln!("{} phase complete ({:?})", phase.name(), duration),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+/*!
+ * elgamal.rs
+ *
+ * Exponential ElGamal commitments, so that an analyst holding the key X = x * H (H the Ristretto basepoint) can decrypt
+ * committed aggregates directly. A commitment m * g + r * X is a Pedersen commitment with X in place of h, so the bit
+ * and product sigma protocols run over it unchanged; together with the handle r * H it is an ElGamal ciphertext of
+ * m * g. Since ciphertexts add up, the analyst decrypts sums and linear combinations of committed values, as long as
+ * the result is small enough to recover from m * g.
+ */
+
+use curve25519_dalek::{constants, ristretto::CompressedRistretto, RistrettoPoint, Scalar};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use std::collections::HashMap;
+use std::ops::{Add, Mul};
+
+use crate::analyst;
+use crate::pedersen;
+#[cfg(feature = "arbitrary")]
+use crate::fuzzing;
+
+/// Domain separator for the handle proof challenge
+const HANDLE_DOMAIN: &[u8] = b"certified-dp elgamal handle proof";
+
+/// Commitment parameters whose commitments are the second half of ElGamal ciphertexts under the analyst's key, with
+/// their tables built, as they replace the session's parameters for every commitment after setup
+pub fn params(pp: &pedersen::PublicParams, pk: &analyst::PublicKey) -> pedersen::PublicParams {
+    pedersen::PublicParams::new(pp.g, pk.point()).precompute()
+}
+
+/// ElGamal ciphertext (r * H, m * g + r * X) of a committed value m
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Ciphertext {
+    pub handle: RistrettoPoint,
+    pub commitment: RistrettoPoint,
+}
+
+impl Add for Ciphertext {
+    type Output = Ciphertext;
+
+    fn add(self, other: Ciphertext) -> Ciphertext {
+        Ciphertext { handle: self.handle + other.handle, commitment: self.commitment + other.commitment }
+    }
+}
+
+impl Mul<Scalar> for Ciphertext {
+    type Output = Ciphertext;
+
+    fn mul(self, s: Scalar) -> Ciphertext {
+        Ciphertext { handle: s * self.handle, commitment: s * self.commitment }
+    }
+}
+
+/// Proof that a handle r * H uses the randomness of a commitment m * g + r * X, i.e. that the two form a ciphertext
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HandleProof {
+    t_h: RistrettoPoint,
+    t_c: RistrettoPoint,
+    z_m: Scalar,
+    z_r: Scalar,
+}
+
+/// Fiat-Shamir challenge over the statement and the proof commitments
+fn challenge(pp: &pedersen::PublicParams, commitment: &RistrettoPoint, handle: &RistrettoPoint, t_h: &RistrettoPoint, t_c: &RistrettoPoint) -> Scalar {
+    let mut input = HANDLE_DOMAIN.to_vec();
+    for p in [&pp.g, &pp.h, commitment, handle, t_h, t_c] {
+        input.extend_from_slice(p.compress().as_bytes());
+    }
+    Scalar::hash_from_bytes::<Sha512>(&input)
+}
+
+/// (1) The prover reveals the handle of a commitment to `val` with randomness `r` under the ElGamal parameters `pp`,
+/// proving it matches without revealing `val`.
+pub fn reveal_handle<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams, commitment: &RistrettoPoint,
+                                         val: &Scalar, r: &Scalar) -> (RistrettoPoint, HandleProof) {
+
+    let handle = r * constants::RISTRETTO_BASEPOINT_POINT;
+
+    let (a_m, a_r) = (Scalar::random(rng), Scalar::random(rng));
+    let t_h = a_r * constants::RISTRETTO_BASEPOINT_POINT;
+    let t_c = pedersen::commit_with_r(&a_m, &a_r, pp);
+
+    let e = challenge(pp, commitment, &handle, &t_h, &t_c);
+
+    (
+        handle,
+        HandleProof {
+            t_h, t_c,
+            z_m: a_m + (e * val),
+            z_r: a_r + (e * r),
+        }
+    )
+}
+
+/// (2) The verifier checks the handle against the commitment, which then form a ciphertext the analyst can decrypt.
+pub fn verify_handle(pp: &pedersen::PublicParams, commitment: &RistrettoPoint, handle: &RistrettoPoint, proof: &HandleProof) -> bool {
+    let e = challenge(pp, commitment, handle, &proof.t_h, &proof.t_c);
+
+    proof.z_r * constants::RISTRETTO_BASEPOINT_POINT == proof.t_h + (e * handle)
+        && pedersen::verify(&(proof.t_c + (e * commitment)), &proof.z_m, &proof.z_r, pp)
+}
+
+/// (3) The analyst decrypts a ciphertext to m * g and recovers m by baby-step giant-step, searching |m| <= bound.
+/// Returns None if m is outside that range.
+pub fn decrypt(sk: &analyst::SecretKey, g: &RistrettoPoint, ct: &Ciphertext, bound: u64) -> Option<i64> {
+    let target = ct.commitment - sk.shared_secret(&ct.handle);
+    let bound = bound.min(i64::MAX as u64);
+    let step = (bound as f64).sqrt() as u64 + 1;
+
+    // baby steps: j * g for 0 <= j < step
+    let mut baby: HashMap<CompressedRistretto, u64> = HashMap::with_capacity(step as usize);
+    let mut p = RistrettoPoint::default();
+    for j in 0..step {
+        baby.insert(p.compress(), j);
+        p += g;
+    }
+
+    // giant steps: target - i * step * g, for both signs of m
+    let giant = Scalar::from(step) * g;
+    for (sign, start) in [(1, target), (-1, -target)] {
+        let mut p = start;
+        for i in 0..=step {
+            if let Some(j) = baby.get(&p.compress()) {
+                let m = i * step + j;
+                if m <= bound {
+                    return Some(sign * m as i64);
+                }
+            }
+            p -= giant;
+        }
+    }
+    None
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for HandleProof {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(HandleProof {
+            t_h: fuzzing::point(u)?,
+            t_c: fuzzing::point(u)?,
+            z_m: fuzzing::scalar(u)?,
+            z_r: fuzzing::scalar(u)?,
+        })
+    }
+}
@@ -0,0 +1,190 @@
+/*!
+ * epoch.rs
+ *
+ * Privacy budget epochs. With a schedule, the prover's lifetime is divided into fixed-length epochs aligned to the Unix
+ * epoch (e.g. 30 days for monthly resets), each with its own privacy budget. Commitments expire with their epoch, so
+ * the first session of a new epoch starts a fresh commitment and randomness cycle. A session belongs to the epoch it
+ * starts in, which the prover announces at setup and the verifier records with every saved answer. Besides the budget
+ * every analyst shares, each analyst can have a budget of its own in every epoch. What the current epoch has spent is
+ * kept in a ledger, which the prover saves so a restart does not reset its budgets.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::budget::{Accountant, Charges};
+
+/// Epochs of the prover and the privacy budget spent in the current one
+#[derive(Clone, Debug)]
+pub struct Epochs {
+    length: Option<Duration>,
+    current: Option<u64>,
+    accountant: Accountant,
+    /// Budget each analyst has of its own, if any, and what each has spent of it in the current epoch
+    analyst_budget: Option<Accountant>,
+    analysts: BTreeMap<String, Accountant>,
+}
+
+/// What the current epoch has spent, from the shared budget and from each analyst's own
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Ledger {
+    pub epoch: u64,
+    pub shared: Charges,
+    #[serde(default)]
+    pub analysts: BTreeMap<String, Charges>,
+}
+
+impl Epochs {
+    /// Epochs of `length` (a single epoch if None), each starting with `accountant`'s budget unspent
+    pub fn new(length: Option<Duration>, mut accountant: Accountant) -> Epochs {
+        accountant.reset();
+        Epochs { length, current: None, accountant, analyst_budget: None, analysts: BTreeMap::new() }
+    }
+
+    /// Also give each analyst `accountant`'s budget of its own in every epoch, which its sessions spend on top of the
+    /// shared one
+    pub fn with_analyst_budget(mut self, mut accountant: Accountant) -> Epochs {
+        accountant.reset();
+        self.analyst_budget = Some(accountant);
+        self
+    }
+
+    /// Epoch containing `time`; always epoch 0 without a schedule
+    pub fn epoch_at(&self, time: SystemTime) -> u64 {
+        match self.length {
+            Some(length) => {
+                let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                since.as_secs() / length.as_secs().max(1)
+            },
+            None => 0,
+        }
+    }
+
+    /// Move to the epoch containing `now`, resetting the budget. Returns true if this starts a new epoch.
+    pub fn advance(&mut self, now: SystemTime) -> bool {
+        let epoch = self.epoch_at(now);
+        if self.current == Some(epoch) {
+            return false;
+        }
+        self.current = Some(epoch);
+        self.accountant.reset();
+        self.analysts.clear();
+        true
+    }
+
+    /// Current epoch ID
+    pub fn current(&self) -> u64 {
+        self.current.unwrap_or_default()
+    }
+
+    /// Privacy spent in the current epoch
+    pub fn accountant(&self) -> &Accountant {
+        &self.accountant
+    }
+
+    /// Privacy `analyst` has spent of its own budget in the current epoch, if analysts have budgets of their own
+    pub fn analyst(&self, analyst: &str) -> Option<&Accountant> {
+        let budget = self.analyst_budget.as_ref()?;
+        Some(self.analysts.get(analyst).unwrap_or(budget))
+    }
+
+    /// Spend `queries` queries of `(epsilon, delta)` each for `analyst` from the current epoch's budget, and from the
+    /// analyst's own if analysts have one. Fails, spending nothing, if either budget does not cover them.
+    pub fn spend(&mut self, analyst: &str, queries: u32, epsilon: f64, delta: f64) -> Result<(), String> {
+        let mut shared = self.accountant.clone();
+        shared.charge(queries, epsilon, delta)?;
+        if let Some(own) = self.analyst(analyst) {
+            let mut own = own.clone();
+            own.charge(queries, epsilon, delta).map_err(|e| format!("analyst {:?}: {}", analyst, e))?;
+            self.analysts.insert(analyst.to_string(), own);
+        }
+        self.accountant = shared;
+        Ok(())
+    }
+
+    /// What the current epoch has spent
+    pub fn ledger(&self) -> Ledger {
+        Ledger {
+            epoch: self.current(),
+            shared: self.accountant.charges(),
+            analysts: self.analysts.iter().map(|(analyst, accountant)| (analyst.clone(), accountant.charges())).collect(),
+        }
+    }
+
+    /// Take up what `ledger` spent in its epoch, e.g. as saved before the prover restarted; if that epoch has since
+    /// ended, the next `advance` starts the current one unspent
+    pub fn restore(&mut self, ledger: &Ledger) {
+        self.current = Some(ledger.epoch);
+        self.accountant.restore(ledger.shared);
+        self.analysts.clear();
+        if let Some(budget) = &self.analyst_budget {
+            for (analyst, &charges) in &ledger.analysts {
+                let mut accountant = budget.clone();
+                accountant.restore(charges);
+                self.analysts.insert(analyst.clone(), accountant);
+            }
+        }
+    }
+}
+
+/// Ledgers saved to `path` by name, e.g. of each dataset, or none if nothing has been saved there yet
+pub fn load_ledgers(path: &Path) -> Result<BTreeMap<String, Ledger>, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Save `ledgers` to `path`, replacing what was saved there at once, so a prover stopped while saving keeps the last
+/// ledgers it saved
+pub fn save_ledgers(path: &Path, ledgers: &BTreeMap<String, Ledger>) -> Result<(), String> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, serde_json::to_vec_pretty(ledgers).unwrap())
+        .and_then(|()| fs::rename(&partial, path))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Parse an epoch length (or another duration) from the command line: a number of seconds, optionally suffixed with s,
+/// m, h, or d
+pub fn parse_length(s: &str) -> Result<Duration, String> {
+    let (digits, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c),
+        _ => (s, 's'),
+    };
+    let scale = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {:?}, expected s, m, h, or d", unit)),
+    };
+    let n: u64 = digits.parse().map_err(|e| format!("{}", e))?;
+    if n == 0 {
+        return Err("duration must be positive".to_string());
+    }
+    n.checked_mul(scale)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {} is too long", s))
+}
+
+/// Parse an analyst's name, as the prover charges its own budget to: letters, digits, `-`, `_`, `.`, and `@`
+pub fn parse_analyst(s: &str) -> Result<String, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c)) {
+        return Err(format!("analyst name must be letters, digits, '-', '_', '.', or '@', got {:?}", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a per-epoch privacy budget (total epsilon) from the command line
+pub fn parse_budget(s: &str) -> Result<f64, String> {
+    let budget: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if budget > 0.0 && budget.is_finite() {
+        Ok(budget)
+    } else {
+        Err(format!("epoch budget must be positive, got {}", budget))
+    }
+}
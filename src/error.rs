@@ -0,0 +1,53 @@
+/*!
+ * error.rs
+ *
+ * Errors that end a protocol session. Each phase returns one in place of logging and carrying on, and the executables
+ * print it and exit with the code its kind maps to, so a script driving them can tell a dropped connection from a
+ * proof that failed to verify.
+ */
+
+use std::io;
+
+use crate::messages::{AbortReason, DecodeError};
+
+/// Why a protocol session failed
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The party's own configuration or files rule the session out, e.g. a spent budget or an unreadable certificate
+    #[error("{0}")]
+    Config(String),
+    /// The connection to the peer failed, e.g. it disconnected mid-frame or a read timed out
+    #[error("connection failed: {0}")]
+    Io(#[from] io::Error),
+    /// The peer's frame did not decode as the message the protocol expects next; the party tells the peer so before it
+    /// hangs up
+    #[error("malformed {message}: {source}")]
+    Malformed { message: &'static str, source: DecodeError },
+    /// The peer's message is well-formed but breaks the protocol, e.g. a query for a monomial that was never committed
+    #[error("{0}")]
+    Protocol(String),
+    /// A proof or commitment from the peer failed to verify
+    #[error("{0}")]
+    Verification(String),
+    /// The peer aborted the protocol, sending the reason
+    #[error("peer aborted: {0}")]
+    PeerAborted(AbortReason),
+    /// This party aborted the protocol and sent the peer the reason
+    #[error("{0}")]
+    Aborted(AbortReason),
+}
+
+impl Error {
+    /// Exit code of the executables when a session fails with this error; 2 is left to argument parsing errors
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) => 1,
+            Error::Io(_) => 3,
+            Error::Malformed { .. } | Error::Protocol(_) => 4,
+            Error::Verification(_) => 5,
+            Error::PeerAborted(_) | Error::Aborted(_) => 6,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
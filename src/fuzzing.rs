@@ -0,0 +1,473 @@
+/*!
+ * fuzzing.rs
+ *
+ * Arbitrary impls for the protocol messages, used by the cargo-fuzz targets in `fuzz/`. Only built with the `arbitrary` feature.
+ */
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use curve25519_dalek::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
+use num_traits::PrimInt;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::calibration::Calibration;
+use crate::messages::*;
+use crate::monomial::{Monomial, MonomialTree};
+use crate::noise::Mechanism;
+
+/// Deepest monomial tree generated; keeps round-tripped trees under serde_json's recursion limit
+const MAX_TREE_DEPTH: usize = 16;
+
+/// Arbitrary canonical scalar
+pub fn scalar(u: &mut Unstructured) -> Result<Scalar> {
+    Ok(Scalar::from_bytes_mod_order(u.arbitrary()?))
+}
+
+/// Arbitrary valid group element
+pub fn point(u: &mut Unstructured) -> Result<RistrettoPoint> {
+    Ok(RistrettoPoint::from_uniform_bytes(&u.arbitrary()?))
+}
+
+/// Arbitrary list of children for a tree node, stopping once the tree is `MAX_TREE_DEPTH` deep
+fn children<'a, N>(u: &mut Unstructured<'a>, depth: usize, node: fn(&mut Unstructured<'a>, usize) -> Result<N>) -> Result<Vec<Box<N>>> {
+    if depth >= MAX_TREE_DEPTH {
+        return Ok(Vec::new());
+    }
+
+    let mut children = Vec::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+        children.push(Box::new(node(u, depth + 1)?));
+    }
+    Ok(children)
+}
+
+fn commitment_node(u: &mut Unstructured, depth: usize) -> Result<MonomialCommitmentTreeNode> {
+    Ok(MonomialCommitmentTreeNode {
+        commitment: if u.arbitrary()? { Some(point(u)?) } else { None },
+        product_sigma_commitment: u.arbitrary()?,
+        children: children(u, depth, commitment_node)?,
+    })
+}
+
+fn challenge_node(u: &mut Unstructured, depth: usize) -> Result<MonomialChallengeTreeNode> {
+    Ok(MonomialChallengeTreeNode {
+        product_sigma_challenge: u.arbitrary()?,
+        children: children(u, depth, challenge_node)?,
+    })
+}
+
+fn response_node(u: &mut Unstructured, depth: usize) -> Result<MonomialResponseTreeNode> {
+    Ok(MonomialResponseTreeNode {
+        product_sigma_response: u.arbitrary()?,
+        children: children(u, depth, response_node)?,
+    })
+}
+
+impl<'a> Arbitrary<'a> for ReadyMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ReadyMessage { ready: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for WireFormatMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let format = if u.arbitrary()? { WireFormat::Bincode } else { WireFormat::Json };
+        Ok(WireFormatMessage { format, compress: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DatasetMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(DatasetMessage { name: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AuthChallengeMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(AuthChallengeMessage { challenge: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AuthResponseMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let proof = match u.arbitrary()? {
+            true => AuthProof::Tag { tag: u.arbitrary()? },
+            false => AuthProof::Signature { key: u.arbitrary()?, signature: u.arbitrary()? },
+        };
+        Ok(AuthResponseMessage { proof })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AnalystMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(AnalystMessage { name: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SessionParams {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        // finite, so they survive JSON
+        let epsilon = f32::from(u.arbitrary::<u16>()?) / 256.0;
+        let delta = u.arbitrary::<Option<u16>>()?.map(|d| f32::from(d) / 65536.0);
+        let mechanism = if u.arbitrary()? { Mechanism::DiscreteLaplace } else { Mechanism::Binomial };
+        let calibration = match u.int_in_range(0..=2)? {
+            0 => Calibration::Approximate,
+            1 => Calibration::Zcdp { queries: u.arbitrary()? },
+            _ => Calibration::Renyi { queries: u.arbitrary()? },
+        };
+        Ok(SessionParams { db_size: u.arbitrary()?, dimension: u.arbitrary()?, max_degree: u.arbitrary()?, epsilon, delta, entry_width: u.arbitrary()?, calibration, mechanism, sensitivity: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SetupMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SetupMessage { seed: u.arbitrary()?, epoch: u.arbitrary()?, version: u.arbitrary()?, params: u.arbitrary()?, seed_commitment: u.arbitrary()?, nonce: u.arbitrary()?, session: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for KeyShareMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(KeyShareMessage { share: CompressedRistretto(u.arbitrary()?), preshared: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SeedContributionMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SeedContributionMessage { seed: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SeedRevealMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SeedRevealMessage { seed: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BitSigmaCommitmentMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BitSigmaCommitmentMessage { commitments: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MonomialCommitmentTreeNode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        commitment_node(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CommitmentPayload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CommitmentPayload {
+            commitment: if u.arbitrary()? { Some(point(u)?) } else { None },
+            product_sigma_commitment: u.arbitrary()?,
+        })
+    }
+}
+
+/// Arbitrary well-formed tree, built by adding each node under a random earlier one
+impl<'a, P: Arbitrary<'a>> Arbitrary<'a> for MonomialTree<P> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut tree = MonomialTree::new(u.arbitrary()?);
+        for _ in 0..u.arbitrary_len::<(P, u32)>()? {
+            let parent = u.int_in_range(0..=tree.len() - 1)?;
+            tree.add_child(parent, u.arbitrary()?);
+        }
+        Ok(tree)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SpotCheckMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SpotCheckMessage { entries: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for UpdateMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(UpdateMessage { removed: u.arbitrary()?, added: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CutAndChooseCommitmentMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CutAndChooseCommitmentMessage {
+            bit_commitments: (0..u.arbitrary_len::<[u8; 64]>()?).map(|_| point(u)).collect::<Result<_>>()?,
+            tree: u.arbitrary()?,
+            encodings: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CutAndChooseChallengeMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CutAndChooseChallengeMessage { kept: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CutAndChooseResponseMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CutAndChooseResponseMessage {
+            openings: u.arbitrary()?,
+            responses: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BitSigmaChallengeMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BitSigmaChallengeMessage { challenges: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BitSigmaCombinedChallengeMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BitSigmaCombinedChallengeMessage { challenge: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MonomialChallengeTreeNode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        challenge_node(u, 0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for BitSigmaResponseMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BitSigmaResponseMessage { responses: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for MonomialResponseTreeNode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        response_node(u, 0)
+    }
+}
+
+impl<'a, T: Arbitrary<'a> + PrimInt> Arbitrary<'a> for Monomial<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Monomial::new(T::arbitrary(u)?))
+    }
+}
+
+impl<'a, T: Arbitrary<'a> + PrimInt + Hash> Arbitrary<'a> for CommitmentMapMessage<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut commitment_map = HashMap::new();
+        for _ in 0..u.arbitrary_len::<(T, [u8; 64])>()? {
+            commitment_map.insert(Monomial::arbitrary(u)?, point(u)?);
+        }
+        Ok(CommitmentMapMessage { commitment_map, snapshot: u.arbitrary()?, entries: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProverRandomnessComm {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ProverRandomnessComm { commitment: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ContributionCommitmentMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ContributionCommitmentMessage { digest: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ContributionDigestsMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ContributionDigestsMessage { digests: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ContributionMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ContributionMessage {
+            player_b: u.arbitrary()?,
+            sigma_challenge: u.arbitrary()?,
+            nonce: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ContributionsMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ContributionsMessage { contributions: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BeaconRoundMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BeaconRoundMessage { round: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for AbortMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let reason = match u.int_in_range(0..=7)? {
+            0 => AbortReason::DeadlineExceeded { phase: u.arbitrary()?, deadline: u.arbitrary()?, elapsed: u.arbitrary()? },
+            1 => AbortReason::Malformed { message: u.arbitrary()?, error: u.arbitrary()? },
+            2 => AbortReason::ParameterMismatch { prover: u.arbitrary()?, verifier: u.arbitrary()? },
+            3 => AbortReason::QueryRefused { violations: u.arbitrary()? },
+            4 => AbortReason::CommitmentMapMismatch {
+                missing: u.arbitrary()?, unexpected: u.arbitrary()?, first_missing: u.arbitrary()?, first_unexpected: u.arbitrary()?,
+            },
+            5 => AbortReason::CountMismatch { what: u.arbitrary()?, expected: u.arbitrary()?, found: u.arbitrary()? },
+            6 => AbortReason::CheckpointMismatch { prover: u.arbitrary()?, verifier: u.arbitrary()? },
+            _ => AbortReason::Unauthorized,
+        };
+        Ok(AbortMessage { reason })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CertificateSignatureMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CertificateSignatureMessage { key: u.arbitrary()?, signature: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SketchRowCommitment {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SketchRowCommitment {
+            indicators: u.arbitrary()?,
+            sum_proof: scalar(u)?,
+            bit_proofs: (0..u.arbitrary_len::<[u8; 32]>()?).map(|_| scalar(u)).collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SketchCommitmentMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SketchCommitmentMessage { rows: u.arbitrary()?, chains: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SketchChallengeMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SketchChallengeMessage { indicators: u.arbitrary()?, chains: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SketchResponseMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SketchResponseMessage { indicators: u.arbitrary()?, chains: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for VerifierRandomnessChallenge {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(VerifierRandomnessChallenge {
+            player_b: u.arbitrary()?,
+            sigma_challenge: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProverRandomnessResponse {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ProverRandomnessResponse {
+            final_commitment: point(u)?,
+            sigma_response: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProverRandomnessComms {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ProverRandomnessComms { commitments: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for VerifierRandomnessChallenges {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(VerifierRandomnessChallenges { challenges: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ResumeRandomnessMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ResumeRandomnessMessage { checkpoint: u.arbitrary()?, challenges: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for CheckpointMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CheckpointMessage { checkpoint: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProverRandomnessResponses {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ProverRandomnessResponses { responses: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for NoiseProductCommitments {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(NoiseProductCommitments { commitments: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for NoiseProductChallenges {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(NoiseProductChallenges { challenges: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for NoiseProductResponses {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(NoiseProductResponses { responses: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for VerifierCheckMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(VerifierCheckMessage { success: u.arbitrary()? })
+    }
+}
+
+impl<'a, T: Arbitrary<'a> + PrimInt + Hash> Arbitrary<'a> for QueryMessage<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut coefficients = HashMap::new();
+        for _ in 0..u.arbitrary_len::<(T, [u8; 32])>()? {
+            coefficients.insert(Monomial::arbitrary(u)?, scalar(u)?);
+        }
+        Ok(QueryMessage { coefficients, snapshot: u.arbitrary()?, dataset: u.arbitrary()?, distinct: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for QueryAnswerMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(QueryAnswerMessage {
+            answer: scalar(u)?,
+            proof: scalar(u)?,
+        })
+    }
+}
+
+impl<'a, T: Arbitrary<'a> + PrimInt + Hash> Arbitrary<'a> for BatchQueryMessage<T> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let queries = (0..u.arbitrary_len::<(T, [u8; 32])>()?)
+            .map(|_| QueryMessage::<T>::arbitrary(u).map(|query| query.coefficients))
+            .collect::<Result<_>>()?;
+        Ok(BatchQueryMessage { queries, snapshot: u.arbitrary()?, dataset: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for BatchQueryAnswerMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(BatchQueryAnswerMessage { answers: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for EncryptedQueryAnswerMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(EncryptedQueryAnswerMessage { answer: u.arbitrary()? })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ElGamalQueryAnswerMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(ElGamalQueryAnswerMessage {
+            handle: point(u)?,
+            proof: u.arbitrary()?,
+        })
+    }
+}
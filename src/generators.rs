@@ -0,0 +1,42 @@
+/*!
+ * generators.rs
+ *
+ * Pedersen generators flipped jointly by the prover and the verifier of a dataset's first session, so that neither can
+ * pick them. `pedersen::setup` hashes the seed to the group, so nobody knows the discrete log between the generators
+ * whatever the seed; the flip only keeps either party from choosing which generators a dataset is committed under. The
+ * prover commits to its half of the seed with a hash, the verifier answers with its own half, and only then does the
+ * prover reveal its half; the generators are derived from the hash of both halves. The prover's half is fixed before it
+ * sees the verifier's, and the verifier's is sent before it sees the prover's, so the seed is uniform as long as either
+ * party is honest. Later sessions of the dataset reuse the generators, as their commitments are cached under them.
+ */
+
+use rand::{CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::pedersen;
+
+/// Domain separator for the commitment to the prover's half of the seed
+const COMMITMENT_DOMAIN: &[u8] = b"certified-dp generator seed commitment";
+
+/// Domain separator for the seed of the generators
+const SEED_DOMAIN: &[u8] = b"certified-dp generator seed";
+
+/// Fresh half of a seed, from either party
+pub fn half<T: Rng + CryptoRng>(rng: &mut T) -> [u8; 32] {
+    rng.gen()
+}
+
+/// Digest committing the prover to its half of the seed, which is uniform so needs no further salt
+pub fn commit(half: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update(COMMITMENT_DOMAIN).chain_update(half).finalize().into()
+}
+
+/// Seed of the generators flipped from the prover's and the verifier's halves
+pub fn combine(prover: &[u8; 32], verifier: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update(SEED_DOMAIN).chain_update(prover).chain_update(verifier).finalize().into()
+}
+
+/// Pedersen generators hashed from a flipped seed
+pub fn params(seed: &[u8; 32]) -> pedersen::PublicParams {
+    pedersen::setup(seed)
+}
@@ -0,0 +1,65 @@
+/*!
+ * group.rs
+ *
+ * Prime-order groups the commitment scheme and sigma protocols run over. The `Group` trait and the default `Ristretto`
+ * backend, which the protocol messages use, live in the no_std core; other backends are compiled in with their cargo
+ * feature:
+ *
+ *   bls12-381: the G1 group of the pairing-friendly BLS12-381 curve
+ */
+
+#[cfg(feature = "bls12-381")]
+use rand::{CryptoRng, RngCore};
+#[cfg(feature = "bls12-381")]
+use sha2::{Digest, Sha512};
+
+pub use certified_dp_core::group::{Group, Ristretto};
+
+/// The G1 group of BLS12-381
+#[cfg(feature = "bls12-381")]
+#[derive(Clone, Copy, Debug)]
+pub struct Bls12_381;
+
+#[cfg(feature = "bls12-381")]
+impl Group for Bls12_381 {
+    type Scalar = bls12_381::Scalar;
+    type Point = bls12_381::G1Projective;
+    /// The crate has no fixed-base tables, so just the affine point, whose mixed additions are a little cheaper
+    type Table = bls12_381::G1Affine;
+
+    fn random_scalar<T: RngCore + CryptoRng>(rng: &mut T) -> bls12_381::Scalar {
+        <bls12_381::Scalar as ff::Field>::random(rng)
+    }
+
+    fn generator() -> bls12_381::G1Projective {
+        bls12_381::G1Projective::generator()
+    }
+
+    /// Try-and-increment: the first counter whose hash is the x-coordinate of a curve point, with the cofactor cleared.
+    /// Runs in variable time, so only for public inputs such as the generators.
+    fn hash_to_point(domain: &[u8], message: &[u8]) -> bls12_381::G1Projective {
+        for counter in 0u32.. {
+            let hash = Sha512::new().chain_update(domain).chain_update(message).chain_update(counter.to_le_bytes()).finalize();
+            let mut x = [0; 48];
+            x.copy_from_slice(&hash[..48]);
+            // compressed encoding, not the point at infinity, with the sign bit left from the hash
+            x[0] = (x[0] & 0x3f) | (hash[48] & 0x20) | 0x80;
+            let point = bls12_381::G1Affine::from_compressed_unchecked(&x);
+            if bool::from(point.is_some()) {
+                let point = bls12_381::G1Projective::from(point.unwrap()).clear_cofactor();
+                if !bool::from(point.is_identity()) {
+                    return point;
+                }
+            }
+        }
+        unreachable!("no counter hashed to the curve")
+    }
+
+    fn table(p: &bls12_381::G1Projective) -> bls12_381::G1Affine {
+        bls12_381::G1Affine::from(p)
+    }
+
+    fn mul_table(table: &bls12_381::G1Affine, s: &bls12_381::Scalar) -> bls12_381::G1Projective {
+        table * s
+    }
+}
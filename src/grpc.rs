@@ -0,0 +1,209 @@
+/*!
+ * grpc.rs
+ *
+ * gRPC transport between the parties, with tonic, so verifiers in other languages can run against the prover with
+ * stubs generated from proto/certified_dp.proto. Each session is one bidirectional stream of `Frame`s, each holding a
+ * message in the session's wire format as it would cross a TCP connection, so the phases, their transcripts, and the
+ * certificates signed over them are the same on either transport. The prover's server and the verifier's client run on
+ * tokio runtimes of their own, and hand each session to the protocol as a blocking `Transport`, whose phase deadlines
+ * bound each read like any other's. Only built with the `grpc` feature.
+ */
+
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::{self, Runtime};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::transport::{FrameTooLarge, Transport};
+
+mod proto {
+    tonic::include_proto!("certified_dp");
+}
+
+pub use proto::Frame;
+use proto::prover_client::ProverClient;
+use proto::prover_server::{Prover, ProverServer};
+
+/// Frames sent ahead of the peer reading them before `send` waits for it
+const PENDING_FRAMES: usize = 16;
+
+/// Bytes a `Frame` adds to the message it holds: its field's tag and length
+const FRAME_OVERHEAD: usize = 16;
+
+/// Longest a party waits as it closes a session, or the prover stops serving, for its last frames to reach the peer
+const LINGER: Duration = Duration::from_secs(10);
+
+/// One end of a session over gRPC, either the prover's end of a call or the verifier's
+pub struct GrpcStream {
+    /// Sender of the frames to the peer, dropped to end the session's stream
+    outgoing: Option<tokio::sync::mpsc::Sender<Frame>>,
+    incoming: Receiver<io::Result<Vec<u8>>>,
+    timeout: Option<Duration>,
+    /// The verifier's client and the runtime driving its call, which end the session when dropped
+    client: Option<(ProverClient<Channel>, Runtime)>,
+}
+
+impl GrpcStream {
+    /// Verifier's end of a new session with the prover at `address`, refusing frames from it over `limit` bytes
+    pub fn connect(address: SocketAddr, limit: usize) -> io::Result<GrpcStream> {
+        let runtime = runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()?;
+        let (outgoing, requests) = tokio::sync::mpsc::channel(PENDING_FRAMES);
+        let (client, incoming) = runtime.block_on(async {
+            let channel = Endpoint::from_shared(format!("http://{}", address))
+                .map_err(io::Error::other)?
+                .connect_timeout(Duration::from_secs(10))
+                .connect()
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::ConnectionRefused, e))?;
+            let mut client = ProverClient::new(channel).max_decoding_message_size(limit.saturating_add(FRAME_OVERHEAD));
+            let response = client.session(ReceiverStream::new(requests)).await.map_err(status_error)?;
+            Ok::<_, io::Error>((client, forward(response.into_inner())))
+        })?;
+        Ok(GrpcStream { outgoing: Some(outgoing), incoming, timeout: None, client: Some((client, runtime)) })
+    }
+}
+
+impl Transport for GrpcStream {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        let outgoing = self.outgoing.as_ref().expect("the stream is only closed as it drops");
+        outgoing.blocking_send(Frame { message: frame.to_vec() }).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "peer disconnected"))
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let disconnected = || io::Error::new(io::ErrorKind::UnexpectedEof, "peer disconnected");
+        let frame = match self.timeout {
+            Some(timeout) => self.incoming.recv_timeout(timeout).unwrap_or_else(|e| match e {
+                RecvTimeoutError::Timeout => Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out")),
+                RecvTimeoutError::Disconnected => Err(disconnected()),
+            })?,
+            None => self.incoming.recv().unwrap_or_else(|_| Err(disconnected()))?,
+        };
+
+        if frame.len() > limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size: frame.len(), limit }));
+        }
+        Ok(frame)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for GrpcStream {
+    /// End the session's stream; the verifier then waits for the prover to end its own, so the runtime driving the
+    /// call is not shut down before the frames sent last reach the prover
+    fn drop(&mut self) {
+        self.outgoing.take();
+        if self.client.is_some() {
+            let deadline = Instant::now() + LINGER;
+            while self.incoming.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_ok() {}
+        }
+    }
+}
+
+/// Prover's gRPC server, accepting the sessions verifiers open in order
+pub struct GrpcListener {
+    opened: Receiver<GrpcStream>,
+    address: SocketAddr,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    /// Disconnected once the server has stopped
+    stopped: Receiver<()>,
+}
+
+impl GrpcListener {
+    /// Serve the `Prover` service on `address` from a thread of its own, refusing frames over `limit` bytes
+    pub fn bind(address: &str, limit: usize) -> io::Result<GrpcListener> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let runtime = runtime::Builder::new_current_thread().enable_all().build()?;
+
+        let (opened, accepted) = mpsc::channel();
+        let service = ProverServer::new(Service { opened }).max_decoding_message_size(limit.saturating_add(FRAME_OVERHEAD));
+        let (shutdown, signal) = tokio::sync::oneshot::channel();
+        let (running, stopped) = mpsc::channel();
+        // once the server stops, the prover's next accept fails
+        thread::spawn(move || {
+            let _running: Sender<()> = running;
+            runtime.block_on(async move {
+                let incoming = TcpListenerStream::new(tokio::net::TcpListener::from_std(listener)?);
+                Server::builder().add_service(service)
+                    .serve_with_incoming_shutdown(incoming, async { let _ = signal.await; })
+                    .await
+                    .map_err(io::Error::other)
+            })
+        });
+        Ok(GrpcListener { opened: accepted, address, shutdown: Some(shutdown), stopped })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Wait for a verifier to open its next session
+    pub fn accept(&self) -> io::Result<GrpcStream> {
+        self.opened.recv().map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "gRPC server stopped"))
+    }
+}
+
+impl Drop for GrpcListener {
+    /// Stop serving once the calls in flight have ended, since the prover exits right after the last session, whose
+    /// last frames may not have reached its verifier yet
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = self.stopped.recv_timeout(LINGER);
+    }
+}
+
+/// `Prover` service, handing each call to the prover as a session
+struct Service {
+    opened: Sender<GrpcStream>,
+}
+
+#[tonic::async_trait]
+impl Prover for Service {
+    type SessionStream = Pin<Box<dyn Stream<Item = Result<Frame, Status>> + Send>>;
+
+    async fn session(&self, request: Request<Streaming<Frame>>) -> Result<Response<Self::SessionStream>, Status> {
+        let (outgoing, replies) = tokio::sync::mpsc::channel(PENDING_FRAMES);
+        let session = GrpcStream { outgoing: Some(outgoing), incoming: forward(request.into_inner()), timeout: None, client: None };
+        self.opened.send(session).map_err(|_| Status::unavailable("prover stopped"))?;
+        Ok(Response::new(Box::pin(ReceiverStream::new(replies).map(Ok))))
+    }
+}
+
+/// Forward the peer's frames to a channel the protocol reads without a runtime, which ends with the peer's stream
+fn forward(mut frames: Streaming<Frame>) -> Receiver<io::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel();
+    tokio::spawn(async move {
+        loop {
+            let frame = match frames.message().await {
+                Ok(Some(frame)) => Ok(frame.message),
+                Ok(None) => break,
+                Err(status) => Err(status_error(status)),
+            };
+            let failed = frame.is_err();
+            if tx.send(frame).is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Failed call or stream, as the error of the read or connection it fails
+fn status_error(status: Status) -> io::Error {
+    io::Error::other(format!("gRPC {}: {}", status.code(), status.message()))
+}
@@ -0,0 +1,218 @@
+/*!
+ * http.rs
+ *
+ * HTTP front for the prover, so a verifier written in any language can run the protocol with plain JSON requests rather
+ * than the framed TCP protocol. A verifier opens a session with `POST /sessions`, which answers with the session's ID,
+ * then posts its messages to `POST /sessions/{id}/messages` as a JSON array, in the order it would send them over TCP.
+ * The reply is the JSON array of the prover's messages, from the first sent after the request arrived to the last before
+ * the prover waits on the verifier again, or ends the session. `DELETE /sessions/{id}` hangs up. The setup, commitment,
+ * and query phases are then exchanges of the same messages as over TCP, e.g. a query is posted as its ready message,
+ * query, and closing ready message, and answered with the prover's. The server runs on a thread of its own, and hands
+ * each session to the prover as a `Transport`, so the phases run on it unchanged. Only built with the `http` feature.
+ */
+
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, post};
+use axum::{Json, Router};
+use serde_json::json;
+use serde_json::value::RawValue;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use crate::transport::{FrameTooLarge, Transport};
+
+/// Longest the prover waits as it stops serving for the replies in flight to reach their verifiers
+const LINGER: Duration = Duration::from_secs(10);
+
+/// Messages a verifier posts in one request, and where the prover's replies to them go
+struct Exchange {
+    frames: Vec<Vec<u8>>,
+    reply: oneshot::Sender<Vec<Vec<u8>>>,
+}
+
+/// State shared by the request handlers
+struct Server {
+    /// Sessions opened and not yet accepted by the prover
+    opened: Sender<HttpConnection>,
+    /// Sessions by ID, until the verifier hangs up or posts to one the prover has ended
+    sessions: Mutex<HashMap<u64, Sender<Exchange>>>,
+    next_id: AtomicU64,
+}
+
+/// Prover's end of the HTTP server, accepting the sessions verifiers open in order
+pub struct HttpListener {
+    opened: Receiver<HttpConnection>,
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    /// Disconnected once the server has stopped
+    stopped: Receiver<()>,
+}
+
+impl HttpListener {
+    /// Serve the API on `address` from a thread of its own, refusing request bodies over `limit` bytes
+    pub fn bind(address: &str, limit: usize) -> io::Result<HttpListener> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build()?;
+
+        let (opened, accepted) = mpsc::channel();
+        let server = Server { opened, sessions: Mutex::new(HashMap::new()), next_id: AtomicU64::new(1) };
+        let app = Router::new()
+            .route("/sessions", post(open_session))
+            .route("/sessions/:id", delete(close_session))
+            .route("/sessions/:id/messages", post(exchange))
+            .layer(DefaultBodyLimit::max(limit))
+            .with_state(Arc::new(server));
+        let (shutdown, signal) = oneshot::channel();
+        let (running, stopped) = mpsc::channel();
+        // once the server stops, the prover's next accept fails
+        thread::spawn(move || {
+            let _running: Sender<()> = running;
+            runtime.block_on(async move {
+                axum::serve(tokio::net::TcpListener::from_std(listener)?, app)
+                    .with_graceful_shutdown(async { let _ = signal.await; })
+                    .await
+            })
+        });
+        Ok(HttpListener { opened: accepted, address, shutdown: Some(shutdown), stopped })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Wait for a verifier to open its next session
+    pub fn accept(&self) -> io::Result<HttpConnection> {
+        self.opened.recv().map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "HTTP server stopped"))
+    }
+}
+
+impl Drop for HttpListener {
+    /// Stop serving once the requests in flight have their replies, since the prover exits right after the last
+    /// session, whose last messages they carry
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = self.stopped.recv_timeout(LINGER);
+    }
+}
+
+/// Prover's end of a session opened over HTTP. Frames the verifier posts are received in order, and the frames the
+/// prover sends are held until it next waits on the verifier, then returned as the reply to the verifier's request.
+pub struct HttpConnection {
+    exchanges: Receiver<Exchange>,
+    /// Frames of the current request not yet received
+    frames: VecDeque<Vec<u8>>,
+    /// Frames sent since the current request arrived
+    replies: Vec<Vec<u8>>,
+    reply: Option<oneshot::Sender<Vec<Vec<u8>>>>,
+    timeout: Option<Duration>,
+}
+
+impl HttpConnection {
+    /// Answer the current request with the frames sent since it arrived
+    fn flush(&mut self) {
+        if let Some(reply) = self.reply.take() {
+            // a verifier that gave up on the request ends the session at the next read
+            let _ = reply.send(std::mem::take(&mut self.replies));
+        }
+    }
+}
+
+impl Transport for HttpConnection {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.replies.push(frame.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let disconnected = || io::Error::new(io::ErrorKind::UnexpectedEof, "verifier hung up");
+        let frame = loop {
+            if let Some(frame) = self.frames.pop_front() {
+                break frame;
+            }
+            self.flush();
+            let exchange = match self.timeout {
+                Some(timeout) => self.exchanges.recv_timeout(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, "read timed out"),
+                    RecvTimeoutError::Disconnected => disconnected(),
+                })?,
+                None => self.exchanges.recv().map_err(|_| disconnected())?,
+            };
+            self.frames = exchange.frames.into();
+            self.reply = Some(exchange.reply);
+        };
+
+        if frame.len() > limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size: frame.len(), limit }));
+        }
+        Ok(frame)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for HttpConnection {
+    /// The last messages of a session answer the request pending as it ends
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// `POST /sessions`: open a session, which the prover serves once it has served the ones opened before
+async fn open_session(State(server): State<Arc<Server>>) -> Response {
+    let id = server.next_id.fetch_add(1, Ordering::Relaxed);
+    let (exchanges, received) = mpsc::channel();
+    let connection = HttpConnection { exchanges: received, frames: VecDeque::new(), replies: Vec::new(), reply: None, timeout: None };
+    server.sessions.lock().unwrap().insert(id, exchanges);
+    if server.opened.send(connection).is_err() {
+        server.sessions.lock().unwrap().remove(&id);
+        return (StatusCode::SERVICE_UNAVAILABLE, "prover stopped").into_response();
+    }
+    (StatusCode::CREATED, Json(json!({ "session": id }))).into_response()
+}
+
+/// `POST /sessions/{id}/messages`: send the verifier's messages, answering with the prover's replies. Both pass through
+/// byte for byte, as the session's ID hashes the setup messages as they were sent.
+async fn exchange(State(server): State<Arc<Server>>, Path(id): Path<u64>, Json(messages): Json<Vec<Box<RawValue>>>) -> Response {
+    let Some(session) = server.sessions.lock().unwrap().get(&id).cloned() else {
+        return (StatusCode::NOT_FOUND, "no such session").into_response();
+    };
+    let (reply, replied) = oneshot::channel();
+    let frames = messages.iter().map(|m| m.get().as_bytes().to_vec()).collect();
+    let replies = match session.send(Exchange { frames, reply }) {
+        Ok(()) => replied.await.ok(),
+        Err(_) => None,
+    };
+    let Some(replies) = replies else {
+        server.sessions.lock().unwrap().remove(&id);
+        return (StatusCode::GONE, "session ended").into_response();
+    };
+    match replies.iter().map(|frame| serde_json::from_slice(frame)).collect::<Result<Vec<Box<RawValue>>, _>>() {
+        Ok(replies) => Json(replies).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "prover replied in a binary wire format").into_response(),
+    }
+}
+
+/// `DELETE /sessions/{id}`: hang up, failing the prover's next read of the session
+async fn close_session(State(server): State<Arc<Server>>, Path(id): Path<u64>) -> StatusCode {
+    match server.sessions.lock().unwrap().remove(&id) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
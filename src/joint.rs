@@ -0,0 +1,86 @@
+/*!
+ * joint.rs
+ *
+ * Noise coins flipped jointly by several verifiers, so the noise is unbiased as long as any one of them is honest.
+ * For each coin, every verifier commits to its contribution (a coin and a share of the bit sigma challenge) with a
+ * salted hash, and only reveals it once the prover has relayed every verifier's digest. The prover's coin is then
+ * XORed with every verifier's coin and challenged with the sum of their challenges. An honest verifier's contribution
+ * is fixed before it sees anyone else's, and theirs are fixed before it reveals, so neither the prover nor the other
+ * verifiers can cancel or bias it.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use rand::{CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::bit_sigma;
+use crate::messages::ContributionMessage;
+
+/// Domain separator for contribution digests
+const CONTRIBUTION_DOMAIN: &[u8] = b"certified-dp joint coin contribution";
+
+/// Fresh contribution to a joint coin flip: a random coin and challenge share, hidden by a random nonce
+pub fn contribute<T: Rng + CryptoRng>(rng: &mut T) -> ContributionMessage {
+    ContributionMessage {
+        player_b: rng.gen_range(0..2),
+        sigma_challenge: bit_sigma::Challenge::random(rng),
+        nonce: rng.gen(),
+    }
+}
+
+/// Digest committing a verifier to its contribution
+pub fn digest(contribution: &ContributionMessage) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(CONTRIBUTION_DOMAIN);
+    hasher.update(serde_json::to_vec(contribution).unwrap());
+    hasher.finalize().into()
+}
+
+/// Check there is one digest per verifier, and that they are distinct, so a contribution cannot be counted twice to
+/// cancel out its own coin
+pub fn check_digests(verifiers: usize, digests: &[[u8; 32]]) -> Result<(), String> {
+    if digests.len() != verifiers {
+        return Err(format!("expected {} contribution digests, got {}", verifiers, digests.len()));
+    }
+    if digests.iter().enumerate().any(|(i, d)| digests[..i].contains(d)) {
+        return Err("contribution digests are not distinct".to_string());
+    }
+    Ok(())
+}
+
+/// Joint coin and challenge of `verifiers` contributions, checking the digests and that each contribution opens the
+/// digest in the same position
+pub fn combine(verifiers: usize, digests: &[[u8; 32]], contributions: &[ContributionMessage]) -> Result<(u32, bit_sigma::Challenge), String> {
+    check_digests(verifiers, digests)?;
+    if contributions.len() != verifiers {
+        return Err(format!("expected {} contributions, got {}", verifiers, contributions.len()));
+    }
+
+    let mut player_b = 0;
+    for (i, (d, contribution)) in digests.iter().zip(contributions).enumerate() {
+        if digest(contribution) != *d {
+            return Err(format!("contribution {} does not match its digest", i));
+        }
+        if contribution.player_b > 1 {
+            return Err(format!("contribution {} coin flip {} is not a bit", i, contribution.player_b));
+        }
+        player_b ^= contribution.player_b;
+    }
+    let challenge = bit_sigma::combine_challenges(contributions.iter().map(|c| &c.sigma_challenge));
+    Ok((player_b, challenge))
+}
+
+/// Hex fingerprint of a session's noise commitment, which every verifier prints so they can confirm they share it
+pub fn fingerprint(noise_commitment: &RistrettoPoint) -> String {
+    noise_commitment.compress().as_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse the number of verifiers jointly flipping the noise coins from the command line
+pub fn parse_verifiers(s: &str) -> Result<u32, String> {
+    let verifiers: u32 = s.parse().map_err(|e| format!("{}", e))?;
+    if verifiers >= 1 {
+        Ok(verifiers)
+    } else {
+        Err("need at least one verifier".to_string())
+    }
+}
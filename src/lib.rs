@@ -1,12 +1,65 @@
-/**
+/*!
  * lib.rs
  * 
  * Main library file for Certified DP containing all the individual modules
  */
 
-pub mod pedersen;
+pub mod error;
+pub use error::{Error, Result};
+pub mod group;
+pub use certified_dp_core::{pedersen, query};
 pub mod config;
+pub mod cache;
+pub mod calibration;
+pub mod noise;
+pub mod transport;
+pub mod net;
+pub mod tls;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "quic")]
+pub mod quic;
 pub mod messages;
 pub mod data;
+pub mod schema;
+pub mod policy;
+pub mod predicate;
+pub mod queries;
+pub mod sensitivity;
 pub mod bit_sigma;
-pub mod product_sigma;
\ No newline at end of file
+pub mod product_sigma;
+pub mod challenges;
+pub mod monomial;
+pub mod memory;
+pub mod driver;
+pub mod recording;
+pub mod replay;
+pub mod audit;
+pub mod cut_and_choose;
+pub mod analyst;
+pub mod elgamal;
+pub mod snapshot;
+pub mod epoch;
+pub mod budget;
+pub mod dataset;
+pub mod profile;
+pub mod joint;
+pub mod checkpoint;
+pub mod generators;
+pub mod beacon;
+pub mod result;
+pub mod signature;
+pub mod auth;
+pub mod mac;
+pub mod certificate;
+pub mod query_certificate;
+pub mod session;
+pub mod deadline;
+pub mod sketch;
+pub mod bulk;
+pub mod protocol;
+
+#[cfg(feature = "arbitrary")]
+pub mod fuzzing;
@@ -0,0 +1,172 @@
+/*!
+ * mac.rs
+ *
+ * Authentication of the frames of a session, so that bytes a network attacker injects into the connection, or alters
+ * on it, are refused even without TLS. Setup opens, once the wire format is agreed, with the prover sending a fresh
+ * key share and the verifier answering with one of its own. From the Diffie-Hellman secret of the two shares, each
+ * side derives a key for the frames the prover sends and one for the frames the verifier sends. Every frame after the
+ * exchange carries a tag, an HMAC-SHA256 of the frame and of its number among the frames authenticated in its direction,
+ * so a frame that is altered, dropped, replayed, or reflected back at its sender fails to check. A phase resumed over a
+ * new connection numbers its frames from zero again, under keys derived from the old ones, so none of the old
+ * connection's frames open on the new one. The exchange itself is not authenticated: an attacker relaying the
+ * connection from the start of setup can run it with each side. A verifier holding the prover's pre-shared key (see
+ * `auth`) rules this out by mixing the key into the secret, which its answer tells the prover to do too, so an attacker
+ * without it agrees keys with neither side. A verifier authenticating with a signature only keeps the attacker from
+ * the prover, since its answer covers both shares, but not from posing as the prover; nor does anything keep an
+ * attacker out of a session without a credential, short of TLS.
+ */
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use rand::{CryptoRng, RngCore};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+
+use crate::auth::PresharedKey;
+use crate::driver::Side;
+use crate::error::{Error, Result};
+use crate::messages::{read_message, write_message, Framed, KeyShareMessage};
+use crate::transport::Transport;
+
+/// Domain separator for the keys and the tags they make
+const MAC_DOMAIN: &[u8] = b"certified-dp frame authentication";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// One side's half of the key exchange: a fresh secret, and the share sent to the peer
+pub struct KeyExchange {
+    secret: Scalar,
+    share: CompressedRistretto,
+}
+
+/// Keys a session's frames are authenticated with, on one side, the prover's and the verifier's shares they were
+/// agreed from, and the number of frames authenticated each way
+#[derive(Clone)]
+pub struct FrameKeys {
+    shares: (CompressedRistretto, CompressedRistretto),
+    sending: [u8; 32],
+    receiving: [u8; 32],
+    sent: u64,
+    received: u64,
+}
+
+impl KeyExchange {
+    /// Fresh secret, and its share
+    pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let secret = Scalar::random(rng);
+        KeyExchange { secret, share: RistrettoPoint::mul_base(&secret).compress() }
+    }
+
+    /// Share to send to the peer
+    pub fn share(&self) -> CompressedRistretto {
+        self.share
+    }
+
+    /// Keys of `side` from the secret this exchange makes with the peer's share `peer`, which must be a point other
+    /// than the identity, or the secret would be known to anyone, mixed with `psk` if both sides hold one
+    pub fn finish(self, side: Side, peer: &CompressedRistretto, psk: Option<&PresharedKey>) -> Result<FrameKeys> {
+        let point = peer.decompress().filter(|point| !point.is_identity())
+            .ok_or_else(|| Error::Protocol("Peer's key share is not a valid point".to_string()))?;
+        let secret = (self.secret * point).compress();
+        let (prover, verifier) = match side {
+            Side::Prover => (self.share, *peer),
+            Side::Verifier => (*peer, self.share),
+        };
+        let mut hash = Sha512::new()
+            .chain_update(MAC_DOMAIN)
+            .chain_update(prover.as_bytes())
+            .chain_update(verifier.as_bytes())
+            .chain_update(secret.as_bytes());
+        if let Some(psk) = psk {
+            hash.update(b"preshared");
+            hash.update(psk.as_bytes());
+        }
+        let digest = hash.finalize();
+        let (from_prover, from_verifier) = (digest[..32].try_into().unwrap(), digest[32..].try_into().unwrap());
+        let (sending, receiving) = match side {
+            Side::Prover => (from_prover, from_verifier),
+            Side::Verifier => (from_verifier, from_prover),
+        };
+        Ok(FrameKeys { shares: (prover, verifier), sending, receiving, sent: 0, received: 0 })
+    }
+}
+
+impl FrameKeys {
+    /// Prover's and verifier's shares the keys were agreed from
+    pub fn shares(&self) -> (CompressedRistretto, CompressedRistretto) {
+        self.shares
+    }
+
+    /// Tag of `frame` as the next frame sent
+    pub fn seal(&mut self, frame: &[u8]) -> [u8; 32] {
+        self.sent += 1;
+        hmac(&self.sending, self.sent - 1, frame).finalize().into_bytes().into()
+    }
+
+    /// Keys for a new connection the session resumes over, each derived from the key of its direction, numbering
+    /// frames from zero again
+    pub fn resume(&mut self) {
+        self.sending = rekey(&self.sending);
+        self.receiving = rekey(&self.receiving);
+        (self.sent, self.received) = (0, 0);
+    }
+
+    /// Whether `tag` authenticates `frame` as the next frame received, comparing in constant time. Only a frame that
+    /// checks is counted, so a forged one cannot shift the frames after it.
+    pub fn open(&mut self, frame: &[u8], tag: &[u8; 32]) -> bool {
+        let checks = hmac(&self.receiving, self.received, frame).verify_slice(tag).is_ok();
+        if checks {
+            self.received += 1;
+        }
+        checks
+    }
+}
+
+/// Keeps the keys out of logs
+impl fmt::Debug for FrameKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameKeys").field("sent", &self.sent).field("received", &self.received).finish_non_exhaustive()
+    }
+}
+
+/// Key following `key` on a resumed connection
+fn rekey(key: &[u8; 32]) -> [u8; 32] {
+    let mac = HmacSha256::new_from_slice(key).unwrap().chain_update(MAC_DOMAIN).chain_update(b"resume");
+    mac.finalize().into_bytes().into()
+}
+
+/// HMAC-SHA256 under `key` of `frame` as frame number `counter` of its direction, to finalize into the frame's tag or
+/// check it against one
+fn hmac(key: &[u8; 32], counter: u64, frame: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).unwrap().chain_update(counter.to_le_bytes()).chain_update(frame)
+}
+
+/// Prover side: send a fresh key share to the verifier on `stream`, read the verifier's answer, and authenticate every
+/// frame sent to and received from this verifier from then on, with keys mixing in the pre-shared key `psk` if the
+/// verifier answers that it holds one
+pub fn prover_agree<R: RngCore + CryptoRng, S: Transport + ?Sized>(rng: &mut R, stream: &mut Framed<S>, psk: Option<&PresharedKey>) -> Result<()> {
+    let exchange = KeyExchange::new(rng);
+    write_message(stream, &KeyShareMessage { share: exchange.share(), preshared: false })?;
+    let answer: KeyShareMessage = read_message(stream)?;
+    let psk = match (answer.preshared, psk) {
+        (true, None) => return Err(Error::Protocol("Verifier mixes a pre-shared key into the session's keys, but this prover has none".to_string())),
+        (preshared, psk) => psk.filter(|_| preshared),
+    };
+    let keys = exchange.finish(Side::Prover, &answer.share, psk)?;
+    stream.set_frame_keys(Some(keys));
+    Ok(())
+}
+
+/// Verifier side: answer the prover's key share on `stream` with one of its own, and authenticate every frame sent to
+/// and received from the prover from then on, with keys mixing in the pre-shared key `psk` if it holds one
+pub fn verifier_agree<R: RngCore + CryptoRng, S: Transport + ?Sized>(rng: &mut R, stream: &mut Framed<S>, psk: Option<&PresharedKey>) -> Result<()> {
+    let m: KeyShareMessage = read_message(stream)?;
+    let exchange = KeyExchange::new(rng);
+    let answer = KeyShareMessage { share: exchange.share(), preshared: psk.is_some() };
+    let keys = exchange.finish(Side::Verifier, &m.share, psk)?;
+    write_message(stream, &answer)?;
+    stream.set_frame_keys(Some(keys));
+    Ok(())
+}
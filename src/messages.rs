@@ -1,32 +1,235 @@
-/**
+/*!
  * messages.rs
  * 
  * Structures for all messages sent over the network during the protocol execution between the prover and verifier.
+ * Messages are encoded as JSON, or as bincode if the verifier asks for it when the session starts; the same structures
+ * are sent either way. The verifier may also ask for large frames to be gzipped, which the stream layer does on its
+ * own, so the phases never see a compressed frame. From the key exchange at setup on, every frame is sent in an
+ * envelope, in the session's wire format, that authenticates it (see `mac`), and once the commitment phases end also
+ * carries the session's ID, the digest of its transcript until then, and the frame's number among those sent to the
+ * peer since. A frame from another session, or one replayed or out of order, is refused as soon as it arrives.
  */
 
-use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use curve25519_dalek::{ristretto::{CompressedRistretto, RistrettoPoint}, scalar::Scalar};
 use flate2::write::GzEncoder;
 use flate2::read::GzDecoder;
 use flate2::Compression;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use bincode::Options;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::Hash;
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
 
+use crate::analyst;
 use crate::bit_sigma;
+use crate::calibration::Calibration;
+use crate::checkpoint::Checkpoint;
+use crate::cut_and_choose;
+use crate::driver::Side;
+use crate::elgamal;
+use crate::mac::FrameKeys;
+use crate::error::{Error, Result};
+use crate::monomial::{Monomial, MonomialTree};
+use crate::noise::{Mechanism, NoiseMechanism};
 use crate::product_sigma;
+use crate::recording::{self, Direction};
+use crate::signature;
+use crate::snapshot::SnapshotId;
+use crate::transport::{FrameTooLarge, Transport};
 
-/// Message to synchronize the start of the protocol
+/// Version of the message encoding below. Bump whenever a message changes shape on the wire, and check in a new set of
+/// fixtures under `tests/fixtures/wire/` so older encodings stay covered by the decoding tests.
+pub const WIRE_VERSION: u32 = 38;
+
+/// Frames longer than this are gzipped when a session compresses its frames; shorter ones would barely shrink
+pub const COMPRESSION_THRESHOLD: usize = 4096;
+
+/// Encoding of the messages of a session
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    /// Self-describing and readable in packet captures
+    #[default]
+    Json,
+    /// Compact binary, with points and scalars as raw bytes rather than arrays of numbers
+    Bincode,
+}
+
+impl fmt::Display for WireFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WireFormat::Json => "json",
+            WireFormat::Bincode => "bincode",
+        })
+    }
+}
+
+/// Parse a wire format from the command line: json or bincode
+pub fn parse_wire_format(s: &str) -> std::result::Result<WireFormat, String> {
+    match s {
+        "json" => Ok(WireFormat::Json),
+        "bincode" => Ok(WireFormat::Bincode),
+        _ => Err(format!("unknown wire format {:?}, expected json or bincode", s)),
+    }
+}
+
+/// Message to synchronize the start of the protocol, or of a round of queries, which a verifier not ready ends the
+/// query phase before
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReadyMessage {
     pub ready: bool
 }
 
-/// Seed for shared randomness generation
+/// Wire format and compression the verifier asks for as a session starts, and the prover's answer with the ones both
+/// then use. Always sent as JSON, uncompressed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WireFormatMessage {
+    pub format: WireFormat,
+    #[serde(default)]
+    pub compress: bool,
+}
+
+/// Dataset a session audits, sent by the verifier before setup when the prover hosts named datasets
 #[derive(Serialize, Deserialize, Debug)]
+pub struct DatasetMessage {
+    pub name: String,
+}
+
+/// Fresh challenge the prover sends at the start of setup when it only serves verifiers it authorizes
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuthChallengeMessage {
+    pub challenge: [u8; 32],
+}
+
+/// How a verifier answers the prover's challenge; there is one per session, so the size difference does not matter
+#[derive(Serialize, Deserialize, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum AuthProof {
+    /// HMAC-SHA256 under the pre-shared key of the challenge and the session's key shares
+    Tag { tag: [u8; 32] },
+    /// Signature on the challenge and the session's key shares, under the verifier's key
+    Signature { key: signature::VerifyingKey, signature: signature::Signature },
+}
+
+/// Verifier's answer to the prover's challenge
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuthResponseMessage {
+    pub proof: AuthProof,
+}
+
+/// Analyst a session's queries are charged to, sent by the verifier before setup when the prover gives analysts budgets
+/// of their own
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnalystMessage {
+    pub name: String,
+}
+
+/// Parameters of the session the prover serves, which the verifier must have been given as well
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SessionParams {
+    pub db_size: u32,
+    pub dimension: u32,
+    pub max_degree: u32,
+    pub epsilon: f32,
+    /// None when both derive delta from the database size
+    pub delta: Option<f32>,
+    /// Bits in each entry, and so in each monomial's mask as bincode encodes it; zero from peers that predate it
+    #[serde(default)]
+    pub entry_width: u32,
+    /// How epsilon and delta set the number of noise coins; approximate from peers that predate it
+    #[serde(default)]
+    pub calibration: Calibration,
+    /// How the noise coins are combined into noise; binomial from peers that predate it
+    #[serde(default)]
+    pub mechanism: Mechanism,
+    /// Largest L1 sensitivity of the session's queries, which the noise is scaled to; zero from peers that predate it,
+    /// which scale it to a sensitivity of one
+    #[serde(default)]
+    pub sensitivity: u32,
+}
+
+impl SessionParams {
+    /// Each parameter that differs between the prover's `self` and the verifier's `other`, with both values
+    pub fn differences(&self, other: &SessionParams) -> Vec<String> {
+        let delta = |d: Option<f32>| d.map_or("derived".to_string(), |d| d.to_string());
+        [
+            ("db size", self.db_size.to_string(), other.db_size.to_string()),
+            ("dimension", self.dimension.to_string(), other.dimension.to_string()),
+            ("max degree", self.max_degree.to_string(), other.max_degree.to_string()),
+            ("epsilon", self.epsilon.to_string(), other.epsilon.to_string()),
+            ("delta", delta(self.delta), delta(other.delta)),
+            ("entry width", self.entry_width.to_string(), other.entry_width.to_string()),
+            ("calibration", self.calibration.to_string(), other.calibration.to_string()),
+            ("noise mechanism", self.mechanism.to_string(), other.mechanism.to_string()),
+            ("sensitivity", self.sensitivity.to_string(), other.sensitivity.to_string()),
+        ].into_iter()
+            .filter(|(_, prover, verifier)| prover != verifier)
+            .map(|(name, prover, verifier)| format!("{} {} at the prover, {} at the verifier", name, prover, verifier))
+            .collect()
+    }
+
+    /// Noise the session's queries are answered on
+    pub fn noise(&self) -> Box<dyn NoiseMechanism> {
+        self.mechanism.noise(self.db_size, self.epsilon, self.delta, self.calibration, self.sensitivity.max(1))
+    }
+
+    /// Number of noise coins the session flips
+    pub fn coins(&self) -> u32 {
+        self.noise().coins()
+    }
+}
+
+/// Seed of the Pedersen generators, or the prover's commitment to its half of a seed to flip them from, the budget epoch
+/// the session belongs to, and the protocol version and parameters the prover serves it with
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SetupMessage {
-    pub seed: [u8; 32]
+    /// Seed an earlier session flipped the generators from, or zero while `seed_commitment` is set
+    pub seed: [u8; 32],
+    #[serde(default)]
+    pub epoch: u64,
+    /// Prover's `WIRE_VERSION`, sent since version 15
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub params: Option<SessionParams>,
+    /// Digest of the prover's half of the seed to flip the generators from with this verifier, since version 16
+    #[serde(default)]
+    pub seed_commitment: Option<[u8; 32]>,
+    /// Fresh bytes making the session's transcript, and so its ID, unlike any other session's, e.g. one resumed on the
+    /// same commitments, since version 29
+    #[serde(default)]
+    pub nonce: [u8; 32],
+    /// ID of the session a co-verifier joins, which it did not see commit, since version 29
+    #[serde(default)]
+    pub session: Option<SessionId>,
+}
+
+/// Share of the key the session's frames are authenticated with (see `mac`), which the prover sends as setup opens and
+/// the verifier answers with its own. From version 31 to 35, the prover's share came in its setup instead.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyShareMessage {
+    pub share: CompressedRistretto,
+    /// Whether the verifier mixes its pre-shared key into the keys, as the prover must then too; always false from the
+    /// prover, and sent since version 38
+    #[serde(default)]
+    pub preshared: bool,
+}
+
+/// Verifier's half of the seed of the generators, sent once the prover has committed to its own
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeedContributionMessage {
+    pub seed: [u8; 32],
+}
+
+/// Prover's half of the seed of the generators, opening its commitment
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeedRevealMessage {
+    pub seed: [u8; 32],
 }
 
 /// Set of commitments for bits in the database entries
@@ -35,7 +238,8 @@ pub struct BitSigmaCommitmentMessage {
     pub commitments: Vec<bit_sigma::Commitment>,
 }
 
-/// Tree of product sigma commitments for the database entries
+/// Nested tree of product sigma commitments, as sent up to wire version 2. Only kept so older encodings still decode;
+/// since version 3 the tree is sent as a `MonomialCommitmentTree`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MonomialCommitmentTreeNode {
     pub commitment: Option<RistrettoPoint>,
@@ -43,13 +247,57 @@ pub struct MonomialCommitmentTreeNode {
     pub children: Vec<Box<MonomialCommitmentTreeNode>>,
 }
 
-/// Set of challenges for bits in the database entries
+/// Entries the verifier audits in spot-check mode, in increasing order; only these receive challenges
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpotCheckMessage {
+    pub entries: Vec<u32>,
+}
+
+/// Cut-and-choose commitments for one database entry: its bit commitments, the commitments of its monomial tree (without
+/// product sigma commitments), and one multiplication triple per multiplication for each encoding
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CutAndChooseCommitmentMessage {
+    pub bit_commitments: Vec<RistrettoPoint>,
+    pub tree: MonomialCommitmentTree,
+    pub encodings: Vec<Vec<cut_and_choose::TripleCommitment>>,
+}
+
+/// Encoding each entry keeps for proving its products, one index per entry; every other encoding is opened
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CutAndChooseChallengeMessage {
+    pub kept: Vec<u32>,
+}
+
+/// Cut-and-choose response for one database entry: the triples of every opened encoding in order, and one product
+/// response per multiplication using the kept encoding
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CutAndChooseResponseMessage {
+    pub openings: Vec<Vec<cut_and_choose::Triple>>,
+    pub responses: Vec<cut_and_choose::ProductResponse>,
+}
+
+/// Change to the committed database certified by the update phase: the indices of the removed entries, in increasing
+/// order, and the number of rows added, whose commitments are then proven as in the dishonest commitment phase
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateMessage {
+    pub removed: Vec<u32>,
+    pub added: u32,
+}
+
+/// Set of challenges for bits in the database entries, one per bit. Sent in wire version 1; since version 2 the verifier
+/// sends a `BitSigmaCombinedChallengeMessage` instead.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BitSigmaChallengeMessage {
     pub challenges: Vec<bit_sigma::Challenge>,
 }
 
-/// Tree of product sigma challenges for the database entries, mirror to the commitment tree
+/// Single challenge for all bits of a database entry; the per-bit challenges are derived with `bit_sigma::expand_challenge`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BitSigmaCombinedChallengeMessage {
+    pub challenge: bit_sigma::Challenge,
+}
+
+/// Nested tree of product sigma challenges, as sent up to wire version 2; replaced by `MonomialChallengeTree`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MonomialChallengeTreeNode {
     pub product_sigma_challenge: Option<product_sigma::Challenge>,
@@ -62,17 +310,60 @@ pub struct BitSigmaResponseMessage {
     pub responses: Vec<bit_sigma::Response>,
 }
 
-/// Tree of product sigma responses for the database entries, mirror to the commitment and challenge trees
+/// Nested tree of product sigma responses, as sent up to wire version 2; replaced by `MonomialResponseTree`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MonomialResponseTreeNode {
     pub product_sigma_response: Option<product_sigma::Response>,
     pub children: Vec<Box<MonomialResponseTreeNode>>,
 }
 
-/// Contains the final monomial commitments for the database entries
+/// Contents of a commitment tree node
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CommitmentPayload {
+    pub commitment: Option<RistrettoPoint>,
+    pub product_sigma_commitment: Option<product_sigma::Commitment>,
+}
+
+/// Tree of product sigma commitments for a database entry
+pub type MonomialCommitmentTree = MonomialTree<CommitmentPayload>;
+/// Tree of product sigma challenges, mirror to the commitment tree
+pub type MonomialChallengeTree = MonomialTree<Option<product_sigma::Challenge>>;
+/// Tree of product sigma responses, mirror to the commitment and challenge trees
+pub type MonomialResponseTree = MonomialTree<Option<product_sigma::Response>>;
+
+/// Map encoded in order of its keys, whatever order it iterates in, so the same message always encodes to the same bytes
+/// and the transcripts of identical sessions match
+struct Sorted<'a, K, V>(&'a HashMap<K, V>);
+
+impl<K: Ord + Serialize, V: Serialize> Serialize for Sorted<'_, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut entries: Vec<(&K, &V)> = self.0.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        serializer.collect_map(entries)
+    }
+}
+
+/// Encode `map` in order of its keys
+fn sorted<K: Ord + Serialize, V: Serialize, S: Serializer>(map: &HashMap<K, V>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    Sorted(map).serialize(serializer)
+}
+
+/// Encode each of `maps` in order of its keys
+fn sorted_each<K: Ord + Serialize, V: Serialize, S: Serializer>(maps: &[HashMap<K, V>], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(maps.iter().map(Sorted))
+}
+
+/// Contains the final monomial commitments for the database entries, and the snapshot they make up
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(serialize = "T: Ord + Serialize"))]
 pub struct CommitmentMapMessage<T: Eq + Hash> {
-    pub commitment_map: HashMap<T, RistrettoPoint>
+    #[serde(serialize_with = "sorted")]
+    pub commitment_map: HashMap<Monomial<T>, RistrettoPoint>,
+    #[serde(default)]
+    pub snapshot: SnapshotId,
+    /// Number of entries the map sums over, since version 33
+    #[serde(default)]
+    pub entries: u64,
 }
 
 /// Prover randomness phase commitment
@@ -88,6 +379,66 @@ pub struct VerifierRandomnessChallenge {
     pub sigma_challenge: bit_sigma::Challenge
 }
 
+/// Prover's commitments to every noise coin of a batched randomness phase, in coin order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProverRandomnessComms {
+    pub commitments: Vec<bit_sigma::Commitment>,
+}
+
+/// Verifier's coin flip and challenge for every noise coin of a batched randomness phase, in coin order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VerifierRandomnessChallenges {
+    pub challenges: Vec<VerifierRandomnessChallenge>,
+}
+
+/// Verifier's first message on a new connection resuming a randomness phase: its checkpoint, and its flip and challenge
+/// of every coin of the window it has not accepted yet, unless it never challenged one
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResumeRandomnessMessage {
+    pub checkpoint: Checkpoint,
+    pub challenges: Option<VerifierRandomnessChallenges>,
+}
+
+/// Prover's checkpoint answering a `ResumeRandomnessMessage`, once it caught up with the verifier's
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckpointMessage {
+    pub checkpoint: Checkpoint,
+}
+
+/// Digest committing a verifier to its contribution to a joint coin flip, before it sees any other verifier's
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContributionCommitmentMessage {
+    pub digest: [u8; 32],
+}
+
+/// Digests of every verifier's contribution to a joint coin flip, relayed by the prover in verifier order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContributionDigestsMessage {
+    pub digests: Vec<[u8; 32]>,
+}
+
+/// One verifier's contribution to a joint coin flip: its coin, its share of the bit sigma challenge, and the nonce
+/// hiding both in its digest
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContributionMessage {
+    pub player_b: u32,
+    pub sigma_challenge: bit_sigma::Challenge,
+    pub nonce: [u8; 32],
+}
+
+/// Every verifier's revealed contribution to a joint coin flip, relayed by the prover in verifier order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContributionsMessage {
+    pub contributions: Vec<ContributionMessage>,
+}
+
+/// Beacon round whose randomness flips every noise coin, named by the verifier once it has all of the prover's coin
+/// commitments
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BeaconRoundMessage {
+    pub round: u64,
+}
+
 /// Prover randomness phase response
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProverRandomnessResponse {
@@ -95,16 +446,83 @@ pub struct ProverRandomnessResponse {
     pub sigma_response: bit_sigma::Response
 }
 
+/// Prover's responses for every noise coin of a batched randomness phase, in coin order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProverRandomnessResponses {
+    pub responses: Vec<ProverRandomnessResponse>,
+}
+
+/// Prover's commitments to the products of noise coins its noise mechanism combines them with, each with the product
+/// sigma commitment proving it, in gate order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NoiseProductCommitments {
+    pub commitments: Vec<product_sigma::Commitment>,
+}
+
+/// Verifier's challenge for every product of noise coins, in gate order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NoiseProductChallenges {
+    pub challenges: Vec<product_sigma::Challenge>,
+}
+
+/// Prover's response for every product of noise coins, in gate order
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NoiseProductResponses {
+    pub responses: Vec<product_sigma::Response>,
+}
+
 /// Verifier randomness phase check; indicator of success
 #[derive(Serialize, Deserialize, Debug)]
 pub struct VerifierCheckMessage {
     pub success: bool
 }
 
-/// Verifier specific query
+/// One row's part of an attribute sketch: its indicator bit commitments, one per bucket, the randomness opening their
+/// sum to one, and per attribute bit, the randomness opening the indicators of the buckets with that bit set, less the
+/// row's commitment to the bit, to zero
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SketchRowCommitment {
+    pub indicators: Vec<bit_sigma::Commitment>,
+    pub sum_proof: Scalar,
+    pub bit_proofs: Vec<Scalar>,
+}
+
+/// Sketch of an attribute for distinct-count queries, sent after the dishonest commitment phase: every row's
+/// indicators, and per bucket the chain of product sigma commitments over the rows
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SketchCommitmentMessage {
+    pub rows: Vec<SketchRowCommitment>,
+    pub chains: Vec<Vec<product_sigma::Commitment>>,
+}
+
+/// Verifier challenges for the sketch: one for every indicator bit sigma protocol, from which each is derived, and one
+/// per product sigma protocol in each bucket's chain
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SketchChallengeMessage {
+    pub indicators: bit_sigma::Challenge,
+    pub chains: Vec<Vec<product_sigma::Challenge>>,
+}
+
+/// Prover responses for the sketch, row by row for the indicators and bucket by bucket for the chains
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SketchResponseMessage {
+    pub indicators: Vec<bit_sigma::Response>,
+    pub chains: Vec<Vec<product_sigma::Response>>,
+}
+
+/// Verifier specific query, against the snapshot the verifier certified of the dataset the session audits. A distinct
+/// query counts the values in the attribute sketch instead, and has no coefficients.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(serialize = "T: Ord + Serialize"))]
 pub struct QueryMessage<T: Eq + Hash> {
-    pub coefficients: HashMap<T, Scalar>
+    #[serde(serialize_with = "sorted")]
+    pub coefficients: HashMap<Monomial<T>, Scalar>,
+    #[serde(default)]
+    pub snapshot: SnapshotId,
+    #[serde(default)]
+    pub dataset: String,
+    #[serde(default)]
+    pub distinct: bool,
 }
 
 /// Prover answer to a verifier query
@@ -114,33 +532,539 @@ pub struct QueryAnswerMessage {
     pub proof: Scalar
 }
 
-/// Reads a buffer of bytes from a stream, determined by a 4-byte size header
-pub fn read_from_stream(stream: &mut TcpStream) -> Vec<u8> {
+/// Several verifier queries at once, each the coefficients of a `QueryMessage`, answered on the same noise and checked
+/// together; distinct queries are not batched
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(serialize = "T: Ord + Serialize"))]
+pub struct BatchQueryMessage<T: Eq + Hash> {
+    #[serde(serialize_with = "sorted_each")]
+    pub queries: Vec<HashMap<Monomial<T>, Scalar>>,
+    pub snapshot: SnapshotId,
+    pub dataset: String,
+}
 
-    let mut size_buf = [0; 4];
-    stream.read_exact(&mut size_buf).unwrap();
+/// Prover answers to a batch of queries, in the clear and in the order of the queries
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchQueryAnswerMessage {
+    pub answers: Vec<QueryAnswerMessage>,
+}
 
-    let size = u32::from_le_bytes(size_buf) as usize;
-    let mut buffer = vec![0; size];
-    stream.read_exact(&mut buffer).unwrap();
+/// Prover answer to a verifier query, encrypted to the analyst's key in place of a `QueryAnswerMessage`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EncryptedQueryAnswerMessage {
+    pub answer: analyst::EncryptedAnswer,
+}
 
-    buffer
+/// Prover answer to a verifier query under ElGamal commitments: only the handle that makes the query commitment a
+/// ciphertext for the analyst, in place of a `QueryAnswerMessage`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ElGamalQueryAnswerMessage {
+    pub handle: RistrettoPoint,
+    pub proof: elgamal::HandleProof,
 }
 
-/// Writes a buffer of bytes to a stream, with a 4-byte size header
-pub fn write_to_stream(stream: &mut TcpStream, a: &[u8]) {
-    let size_buf = (a.len() as u32).to_le_bytes();
-    match stream.write_all(&size_buf) {
-        Ok(_) => (),
-        Err(e) => println!("Error: {}", e)
+/// Auditor's signature on the certificate of the commitment phases it audited
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CertificateSignatureMessage {
+    pub key: signature::VerifyingKey,
+    pub signature: signature::Signature,
+}
+
+/// Why a party aborted the protocol
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AbortReason {
+    /// The phase ran past the deadline the party set for it
+    DeadlineExceeded { phase: String, deadline: Duration, elapsed: Duration },
+    /// A frame from the peer did not decode as the message the party expected next
+    Malformed { message: String, error: String },
+    /// The verifier was given other session parameters than the prover serves
+    ParameterMismatch { prover: SessionParams, verifier: SessionParams },
+    /// The verifier's query breaks the prover's query policy, in each of these ways
+    QueryRefused { violations: Vec<String> },
+    /// The verifier did not answer the prover's challenge with a credential the prover authorizes
+    Unauthorized,
+    /// The prover's commitment map lacks some of the monomials of the session's dimension and maximum degree, or holds
+    /// others: how many of each, and the first few of each in order of mask
+    CommitmentMapMismatch { missing: u64, unexpected: u64, first_missing: Vec<String>, first_unexpected: Vec<String> },
+    /// The prover committed to another number of `what`, e.g. entries or an entry's bits, than the session's parameters
+    /// call for
+    CountMismatch { what: String, expected: u64, found: u64 },
+    /// The verifier resumed the randomness phase from another checkpoint than the prover can reach: the number of
+    /// coins each side agreed on
+    CheckpointMismatch { prover: u32, verifier: u32 },
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortReason::DeadlineExceeded { phase, deadline, elapsed } =>
+                write!(f, "{} phase exceeded its deadline of {:?} after {:?}", phase, deadline, elapsed),
+            AbortReason::Malformed { message, error } => write!(f, "could not decode {}: {}", message, error),
+            AbortReason::ParameterMismatch { prover, verifier } =>
+                write!(f, "session parameters differ: {}", prover.differences(verifier).join(", ")),
+            AbortReason::QueryRefused { violations } => write!(f, "query refused by the prover's policy: {}", violations.join("; ")),
+            AbortReason::Unauthorized => write!(f, "verifier not authorized by the prover"),
+            AbortReason::CommitmentMapMismatch { missing, unexpected, first_missing, first_unexpected } =>
+                write!(f, "commitment map lacks {} of the session's monomials ({}) and holds {} others ({})",
+                       missing, first_missing.join(", "), unexpected, first_unexpected.join(", ")),
+            AbortReason::CountMismatch { what, expected, found } =>
+                write!(f, "prover committed to {} {}, where the session's parameters call for {}", found, what, expected),
+            AbortReason::CheckpointMismatch { prover, verifier } =>
+                write!(f, "randomness phase resumed after {} coins, but the prover's checkpoint is after {}", verifier, prover),
+        }
+    }
+}
+
+/// Sent in place of whatever the peer expects next when a party aborts, so both sides report the same reason
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AbortMessage {
+    pub reason: AbortReason,
+}
+
+/// Why a frame from the peer did not decode as the message expected
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+    /// A bincode frame that is empty, or whose first byte marks neither a message nor an abort
+    #[error("frame is neither a message nor an abort")]
+    FrameKind,
+}
+
+/// Domain separator for session transcripts
+const TRANSCRIPT_DOMAIN: &[u8] = b"certified-dp transcript";
+
+
+/// Identifier of a session: the digest of its transcript through the commitment phases, which both parties derive
+/// independently, and which every frame after them carries
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SessionId([u8; 32]);
+
+impl SessionId {
+    /// Session of the transcript of digest `transcript`
+    pub fn new(transcript: [u8; 32]) -> Self {
+        SessionId(transcript)
     }
 
-    match stream.write(&a) {
-        Ok(_) => (),
-        Err(e) => println!("Error: {}", e)
+    /// Digest of the session's transcript
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
     }
 }
 
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for SessionId {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(SessionId(u.arbitrary()?))
+    }
+}
+
+
+/// First byte of a frame when a session compresses its frames, saying whether the rest is gzipped
+const FRAME_PLAIN: u8 = 0;
+const FRAME_GZIP: u8 = 1;
+
+/// First byte of a bincode frame, which tells a message from an abort sent in its place
+const BINCODE_MESSAGE: u8 = 0;
+const BINCODE_ABORT: u8 = 1;
+
+/// Frame of a session from the key exchange at setup on: the frame as encoded in the session's wire format, with the
+/// session's ID and the frame's number among those sent since, once the session has an ID, and a tag authenticating
+/// all three, once keys with the peer are agreed. A JSON envelope carries a JSON message as its body as is, and a
+/// bincode one a bincode frame as its bytes.
+#[derive(Serialize, Deserialize)]
+struct Envelope<B> {
+    session: Option<SessionId>,
+    seq: Option<u64>,
+    mac: Option<[u8; 32]>,
+    body: B,
+}
+
+/// What an envelope's tag authenticates: its session ID, number and body, encoded the same way whatever the wire format
+fn authenticated(session: Option<SessionId>, seq: Option<u64>, body: &[u8]) -> Vec<u8> {
+    bincode_options().serialize(&(session, seq, body)).unwrap()
+}
+
+/// Connection to the peer, with the framing of the messages of the session on it: the wire format and compression the
+/// session agreed, the largest frame accepted from the peer, the transcript the session's ID is made from, and the ID,
+/// numbering and keys of its frames. Sessions running at once on one thread, or a prover's connections to its verifier
+/// and co-verifiers, each frame their messages on their own.
+pub struct Framed<S: ?Sized> {
+    format: WireFormat,
+    compress: bool,
+    max_frame_size: usize,
+    /// Hashes of the frames sent and received since `start_transcript`, while a transcript is taken
+    transcript: Option<(Sha256, Sha256)>,
+    /// Session every frame sent and received carries, once its commitment phases end
+    session: Option<SessionId>,
+    /// Number of the next frame to send, and of the next frame to receive, since frames carry the session's ID
+    sent: u64,
+    received: u64,
+    /// Keys authenticating the frames exchanged with the peer, once agreed
+    keys: Option<FrameKeys>,
+    /// When the current phase must be done by, if it has a deadline
+    deadline: Option<Instant>,
+    stream: S,
+}
+
+impl<S> Framed<S> {
+    /// Frame JSON messages, uncompressed, on `stream`, as every session starts before agreeing on its wire format,
+    /// refusing any frame from the peer over `max_frame_size` bytes
+    pub fn new(stream: S, max_frame_size: usize) -> Self {
+        Framed { format: WireFormat::Json, compress: false, max_frame_size, transcript: None, session: None, sent: 0, received: 0, keys: None, deadline: None, stream }
+    }
+
+    /// Carry on the session over `stream`, a new connection to the same peer, e.g. as a phase resumes after the last
+    /// one dropped: frames are numbered from zero again, and authenticated with keys derived afresh from the current
+    /// ones, so no frame of the old connection opens on the new one
+    pub fn reconnect(&mut self, stream: S) {
+        self.stream = stream;
+        self.sent = 0;
+        self.received = 0;
+        if let Some(keys) = self.keys.as_mut() {
+            keys.resume();
+        }
+    }
+
+    /// Connection underneath the framing
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: ?Sized> Framed<S> {
+    /// Format of the messages encoded and decoded on this connection
+    pub fn wire_format(&self) -> WireFormat {
+        self.format
+    }
+
+    /// Whether frames on this connection may be gzipped
+    pub fn compression(&self) -> bool {
+        self.compress
+    }
+
+    /// Encode and decode messages in `format`, and gzip frames over `COMPRESSION_THRESHOLD` if `compress`, expecting
+    /// the peer to do the same, e.g. for a co-verifier, which joins a session whose framing its verifier already agreed
+    pub fn set_wire_format(&mut self, format: WireFormat, compress: bool) {
+        self.format = format;
+        self.compress = compress;
+    }
+
+    /// Largest frame accepted from the peer on this connection
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// Fail every read and write once `deadline` passes, each read waiting for the peer no longer than the time left
+    /// until then, as a phase with a deadline needs; None lifts it, and reads wait as long as the peer takes again
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) where S: Transport {
+        self.deadline = deadline;
+        if deadline.is_none() {
+            let _ = self.stream.set_read_timeout(None);
+        }
+    }
+
+    /// Time left until the deadline, if there is one, failing as a read timing out would once none is left, so that
+    /// work between reads, e.g. the prover's windows of entries, stops there
+    pub fn check_deadline(&self) -> Result<Option<Duration>> {
+        let Some(deadline) = self.deadline else {
+            return Ok(None);
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::TimedOut, "phase deadline passed")));
+        }
+        Ok(Some(left))
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Start hashing every frame sent or received into a transcript of a new session, whose frames carry no session
+    /// ID until `enter_session`, are numbered from zero from then on, and go unauthenticated until keys are agreed
+    pub fn start_transcript(&mut self) {
+        self.transcript = Some(Default::default());
+        self.session = None;
+        self.sent = 0;
+        self.received = 0;
+        self.keys = None;
+    }
+
+    /// Finish the transcript of the session's setup and commitment phases into its ID, which every frame sent and
+    /// received carries from now on
+    pub fn enter_session(&mut self, side: Side) -> SessionId {
+        let id = SessionId(self.finish_transcript(side));
+        self.session = Some(id);
+        id
+    }
+
+    /// Stop taking the transcript, returning its digest. The prover's frames are hashed apart from the verifier's, so
+    /// both parties get the same digest even where one of them sends before it has received the other's frame.
+    pub fn finish_transcript(&mut self, side: Side) -> [u8; 32] {
+        let (sent, received) = self.transcript.take().unwrap_or_default();
+        let (prover, verifier) = match side {
+            Side::Prover => (sent, received),
+            Side::Verifier => (received, sent),
+        };
+        Sha256::new()
+            .chain_update(TRANSCRIPT_DOMAIN)
+            .chain_update(prover.finalize())
+            .chain_update(verifier.finalize())
+            .finalize()
+            .into()
+    }
+
+    /// Tag every frame sent with the session ID `id`, and refuse any frame received without it, or stop with None,
+    /// e.g. as a prover sends a co-verifier the setup naming the session it joins
+    pub fn set_session_id(&mut self, id: Option<SessionId>) {
+        self.session = id;
+    }
+
+    /// ID of the session of the frames sent and received, once its commitment phases end
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.session
+    }
+
+    /// Keys the frames exchanged with the peer are authenticated with, once agreed
+    pub fn frame_keys(&self) -> Option<&FrameKeys> {
+        self.keys.as_ref()
+    }
+
+    /// Authenticate the frames sent to and received from the peer with `keys` from now on, or stop with None
+    pub fn set_frame_keys(&mut self, keys: Option<FrameKeys>) {
+        self.keys = keys;
+    }
+
+    /// Add a frame to the transcript, if one is being taken
+    fn record(&mut self, frame: &[u8], sent: bool) {
+        if let Some((sent_hash, received_hash)) = self.transcript.as_mut() {
+            let hash = if sent { sent_hash } else { received_hash };
+            hash.update((frame.len() as u32).to_le_bytes());
+            hash.update(frame);
+        }
+    }
+
+    /// `frame` in its envelope once the session has an ID or keys with the peer are agreed, or as is before either
+    fn seal(&mut self, frame: &[u8]) -> Result<Vec<u8>> {
+        if self.session.is_none() && self.keys.is_none() {
+            return Ok(frame.to_vec());
+        }
+        let session = self.session;
+        let seq = session.map(|_| {
+            self.sent += 1;
+            self.sent - 1
+        });
+        let mac = self.keys.as_mut().map(|keys| keys.seal(&authenticated(session, seq, frame)));
+        Ok(match self.format {
+            WireFormat::Json => {
+                let body = String::from_utf8(frame.to_vec()).ok().and_then(|body| RawValue::from_string(body).ok())
+                    .ok_or_else(|| Error::Protocol("Frame to send is not a JSON message".to_string()))?;
+                serde_json::to_vec(&Envelope { session, seq, mac, body }).unwrap()
+            },
+            WireFormat::Bincode => bincode_options().serialize(&Envelope { session, seq, mac, body: frame }).unwrap(),
+        })
+    }
+
+    /// Frame in the envelope `frame` once the session has an ID or keys with the peer are agreed, or `frame` as is before
+    /// either. The envelope's tag must check as the next frame from the peer's, if keys were agreed, and it must carry
+    /// the session's ID and the number of the next frame from the peer, if the session has an ID.
+    fn open(&mut self, frame: Vec<u8>) -> Result<Vec<u8>> {
+        if self.session.is_none() && self.keys.is_none() {
+            return Ok(frame);
+        }
+        let malformed = |source: DecodeError| Error::Malformed { message: "frame envelope", source };
+        let Envelope { session, seq, mac, body } = match self.format {
+            WireFormat::Json => serde_json::from_slice::<Envelope<Box<RawValue>>>(&frame)
+                .map(|e| Envelope { session: e.session, seq: e.seq, mac: e.mac, body: e.body.get().as_bytes().to_vec() })
+                .map_err(|source| malformed(source.into()))?,
+            WireFormat::Bincode => bincode_options().deserialize::<Envelope<Vec<u8>>>(&frame).map_err(|source| malformed(source.into()))?,
+        };
+        match (&mut self.keys, mac) {
+            (Some(keys), Some(mac)) => if !keys.open(&authenticated(session, seq, &body), &mac) {
+                return Err(Error::Protocol("Frame fails authentication".to_string()));
+            },
+            (Some(_), None) => return Err(Error::Protocol("Frame carries no authentication tag".to_string())),
+            (None, Some(_)) => return Err(Error::Protocol("Frame carries an authentication tag, but no key was agreed to check it".to_string())),
+            (None, None) => {},
+        }
+        let Some(id) = self.session else {
+            return Ok(body);
+        };
+        let seq = match (session, seq) {
+            (Some(carried), _) if carried != id => return Err(Error::Protocol(format!("Frame is from session {}, not this session {}", carried, id))),
+            (Some(_), Some(seq)) => seq,
+            (Some(_), None) => return Err(Error::Protocol("Frame carries no sequence number".to_string())),
+            (None, _) => return Err(Error::Protocol(format!("Frame carries no session ID, where this session is {}", id))),
+        };
+        if seq < self.received {
+            return Err(Error::Protocol(format!("Frame {} of the session is replayed, where frame {} is next", seq, self.received)));
+        }
+        if seq > self.received {
+            return Err(Error::Protocol(format!("Frame {} of the session is out of order, where frame {} is next", seq, self.received)));
+        }
+        self.received += 1;
+        Ok(body)
+    }
+}
+
+/// Variable-length integers, and no bytes left over after a message
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// Verifier's side of agreeing on the wire format as a session starts: ask for the format and compression in `request`,
+/// which every later frame on the connection uses once the prover agrees to them
+pub fn request_wire_format<S: Transport + ?Sized>(stream: &mut Framed<S>, request: WireFormatMessage) -> Result<()> {
+    stream.set_wire_format(WireFormat::Json, false);
+    write_message(stream, &request)?;
+    let answer: WireFormatMessage = read_message(stream)?;
+    if answer != request {
+        return Err(Error::Protocol(format!("Prover answered wire format {:?} to a request for {:?}", answer, request)));
+    }
+    stream.set_wire_format(request.format, request.compress);
+    Ok(())
+}
+
+/// Prover's side of agreeing on the wire format as a session starts: serve whichever format and compression the
+/// verifier asks for, returning them
+pub fn accept_wire_format<S: Transport + ?Sized>(stream: &mut Framed<S>) -> Result<WireFormatMessage> {
+    stream.set_wire_format(WireFormat::Json, false);
+    let request: WireFormatMessage = read_message(stream)?;
+    write_message(stream, &request)?;
+    stream.set_wire_format(request.format, request.compress);
+    Ok(request)
+}
+
+impl WireFormat {
+    /// Serializes a message in this format
+    pub fn encode<M: Serialize>(self, m: &M) -> Vec<u8> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(m).unwrap(),
+            WireFormat::Bincode => {
+                let mut frame = vec![BINCODE_MESSAGE];
+                bincode_options().serialize_into(&mut frame, m).unwrap();
+                frame
+            },
+        }
+    }
+
+    /// Serializes an abort in this format, to send in place of whatever message the peer expects next
+    pub fn encode_abort(self, reason: &AbortReason) -> Vec<u8> {
+        let m = AbortMessage { reason: reason.clone() };
+        match self {
+            WireFormat::Json => serde_json::to_vec(&m).unwrap(),
+            WireFormat::Bincode => {
+                let mut frame = vec![BINCODE_ABORT];
+                bincode_options().serialize_into(&mut frame, &m).unwrap();
+                frame
+            },
+        }
+    }
+
+    /// Deserializes a message of type `M` from a frame in this format, failing if the bytes are malformed or the peer
+    /// aborted in its place
+    pub fn decode<M: DeserializeOwned>(self, buffer: &[u8]) -> Result<M> {
+        let malformed = |source: DecodeError| Error::Malformed { message: std::any::type_name::<M>(), source };
+        match self {
+            WireFormat::Json => serde_json::from_slice(buffer).map_err(|source| match serde_json::from_slice::<AbortMessage>(buffer) {
+                Ok(abort) => Error::PeerAborted(abort.reason),
+                Err(_) => malformed(source.into()),
+            }),
+            WireFormat::Bincode => match buffer.split_first() {
+                Some((&BINCODE_MESSAGE, body)) => bincode_options().deserialize(body).map_err(|source| malformed(source.into())),
+                Some((&BINCODE_ABORT, body)) => match bincode_options().deserialize::<AbortMessage>(body) {
+                    Ok(abort) => Err(Error::PeerAborted(abort.reason)),
+                    Err(source) => Err(malformed(source.into())),
+                },
+                _ => Err(malformed(DecodeError::FrameKind)),
+            },
+        }
+    }
+}
+
+/// Receives the next frame from the peer, decompressing it if need be, returning an error if the peer disconnects or
+/// truncates it, or does not send it before the connection's deadline. A frame over the connection's size limit, before
+/// or after decompression, breaks the protocol.
+pub fn read_from_stream<S: Transport + ?Sized>(stream: &mut Framed<S>) -> Result<Vec<u8>> {
+    if let Some(left) = stream.check_deadline()? {
+        stream.stream.set_read_timeout(Some(left))?;
+    }
+    let limit = stream.max_frame_size;
+    let mut buffer = stream.stream.recv(limit).map_err(|e| match e.get_ref().and_then(|inner| inner.downcast_ref::<FrameTooLarge>()) {
+        Some(too_large) => Error::Protocol(too_large.to_string()),
+        None => Error::Io(e),
+    })?;
+    // transports other than byte streams may hand over whole frames without checking them
+    if buffer.len() > limit {
+        return Err(Error::Protocol(FrameTooLarge { size: buffer.len(), limit }.to_string()));
+    }
+    if stream.compress {
+        buffer = match buffer.split_first() {
+            Some((&FRAME_PLAIN, rest)) => rest.to_vec(),
+            Some((&FRAME_GZIP, rest)) => decompress(rest, limit).map_err(|e| Error::Protocol(format!("Could not decompress frame: {}", e)))?,
+            _ => return Err(Error::Protocol("Frame has no compression marker".to_string())),
+        };
+    }
+    let buffer = stream.open(buffer)?;
+    stream.record(&buffer, false);
+    recording::record(&buffer, Direction::Received).map_err(recording_failed)?;
+    Ok(buffer)
+}
+
+/// Reads and deserializes a message of type `M`, failing if the frame is missing or malformed
+pub fn read_message<M: DeserializeOwned, S: Transport + ?Sized>(stream: &mut Framed<S>) -> Result<M> {
+    stream.format.decode(&read_from_stream(stream)?)
+}
+
+/// Sends a buffer of bytes to the peer as one frame, tagged with the session's ID and the frame's number once it has
+/// one, authenticated once keys with the peer are agreed, and gzipped if it is large and the session compresses its
+/// frames. The transcript records the bytes before any of these. Fails without sending once the connection's deadline
+/// has passed.
+pub fn write_to_stream<S: Transport + ?Sized>(stream: &mut Framed<S>, a: &[u8]) -> Result<()> {
+    stream.check_deadline()?;
+    stream.record(a, true);
+    recording::record(a, Direction::Sent).map_err(recording_failed)?;
+    let a = &stream.seal(a)?;
+    if !stream.compress {
+        stream.stream.send(a)?;
+    } else if a.len() > COMPRESSION_THRESHOLD {
+        stream.stream.send(&[&[FRAME_GZIP][..], &compress(a)].concat())?;
+    } else {
+        stream.stream.send(&[&[FRAME_PLAIN][..], a].concat())?;
+    }
+    Ok(())
+}
+
+/// Serializes a message in the connection's wire format and sends it to the peer
+pub fn write_message<M: Serialize, S: Transport + ?Sized>(stream: &mut Framed<S>, m: &M) -> Result<()> {
+    let frame = stream.format.encode(m);
+    write_to_stream(stream, &frame)
+}
+
+/// Sends the peer an abort in place of whatever message it expects next
+pub fn write_abort<S: Transport + ?Sized>(stream: &mut Framed<S>, reason: &AbortReason) -> Result<()> {
+    let frame = stream.format.encode_abort(reason);
+    write_to_stream(stream, &frame)
+}
+
+/// Failure to append a frame to the recording, which fails the session rather than leave a gap in the recording
+fn recording_failed(e: io::Error) -> Error {
+    Error::Config(format!("Could not record frame: {}", e))
+}
+
 /// Compresses a buffer of bytes using gzip, trades off speed for compression ratio
 pub fn compress(buf: &[u8]) -> Vec<u8> {
     let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
@@ -148,10 +1072,14 @@ pub fn compress(buf: &[u8]) -> Vec<u8> {
     encoder.finish().unwrap().to_vec()
 }
 
-/// Decompresses a buffer of bytes using gzip
-pub fn decompress(buf: &[u8]) -> Vec<u8> {
-    let mut decoder = GzDecoder::new(buf);
+/// Decompresses a buffer of bytes using gzip, failing if they are not a well-formed gzip stream or expand past `limit`
+/// bytes, so a small frame cannot unpack into gigabytes
+pub fn decompress(buf: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(buf).take(limit as u64 + 1);
     let mut decompressed = Vec::new();
-    decoder.read_to_end(&mut decompressed).unwrap();
-    decompressed
+    decoder.read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("expands past the limit of {} bytes", limit)));
+    }
+    Ok(decompressed)
 }
\ No newline at end of file
@@ -0,0 +1,395 @@
+/*!
+ * monomial.rs
+ *
+ * Monomial trees shared by the prover and verifier. Each database entry's partial products are kept in a tree where
+ * the path from the root to a node spells out the monomial it commits to: the k-th child of a node whose variable is
+ * `j` (or of the root, with `j = -1`) multiplies in variable `j + 1 + k`. `MonomialTree` stores such a tree as an arena
+ * of nodes with child indices, which is also its wire encoding, and is generic over what each node carries.
+ *
+ * A `Monomial` names the product of some of an entry's bits by the mask of those bits, and keys the maps of monomial
+ * commitments and query coefficients; on the wire it is just the mask.
+ */
+
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use num_traits::PrimInt;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+
+use crate::messages::CommitmentPayload;
+
+/// Index of a node in its `MonomialTree`; the root is always 0
+pub type NodeId = usize;
+
+/// Monomial over an entry's bits, as the mask of the variables it multiplies together, e.g. `x0*x3` as `0b1001`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Monomial<T>(T);
+
+impl<T: PrimInt> Monomial<T> {
+    /// Monomial multiplying together the variables set in `mask`
+    pub fn new(mask: T) -> Self {
+        Monomial(mask)
+    }
+
+    /// Monomial of the single variable `bit`
+    pub fn variable(bit: usize) -> Self {
+        Monomial(T::one() << bit)
+    }
+
+    /// Mask of the variables multiplied together
+    pub fn mask(self) -> T {
+        self.0
+    }
+
+    /// Number of variables multiplied together
+    pub fn degree(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Whether the variable `bit` is multiplied in
+    pub fn contains(self, bit: usize) -> bool {
+        bit < Self::width() && self.0 & (T::one() << bit) != T::zero()
+    }
+
+    /// Variables multiplied together, in increasing order
+    pub fn variables(self) -> impl Iterator<Item = usize> {
+        (0..Self::width()).filter(move |&bit| self.contains(bit))
+    }
+
+    /// This monomial with the variable `bit` multiplied in
+    pub fn times(self, bit: usize) -> Self {
+        Monomial(self.0 | (T::one() << bit))
+    }
+
+    /// Variables the mask has room for
+    fn width() -> usize {
+        T::zero().count_zeros() as usize
+    }
+}
+
+/// As the product of its variables, e.g. `x3*x7*x12`, or `1` for the empty monomial
+impl<T: PrimInt> fmt::Display for Monomial<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut variables = self.variables();
+        match variables.next() {
+            Some(first) => {
+                write!(f, "x{}", first)?;
+                variables.try_for_each(|bit| write!(f, "*x{}", bit))
+            },
+            None => write!(f, "1"),
+        }
+    }
+}
+
+/// Monomial tree stored as an arena: `nodes[0]` is the root, and every other node appears after its parent, which
+/// refers to it by index. Decoding checks this structure, so a decoded tree is always well formed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "RawTree<P>")]
+pub struct MonomialTree<P> {
+    nodes: Vec<Node<P>>,
+}
+
+/// Node of a `MonomialTree`: its payload and the indices of its children, in order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Node<P> {
+    pub payload: P,
+    pub children: Vec<u32>,
+}
+
+/// Tree as received, before its structure is checked
+#[derive(Deserialize)]
+struct RawTree<P> {
+    nodes: Vec<Node<P>>,
+}
+
+impl<P> TryFrom<RawTree<P>> for MonomialTree<P> {
+    type Error = String;
+
+    fn try_from(raw: RawTree<P>) -> Result<Self, String> {
+        if raw.nodes.is_empty() {
+            return Err("tree has no root".to_string());
+        }
+
+        let mut has_parent = vec![false; raw.nodes.len()];
+        for (i, node) in raw.nodes.iter().enumerate() {
+            for &c in &node.children {
+                let c = c as usize;
+                if c <= i || c >= raw.nodes.len() {
+                    return Err(format!("node {} has child index {} out of order", i, c));
+                }
+                if has_parent[c] {
+                    return Err(format!("node {} has more than one parent", c));
+                }
+                has_parent[c] = true;
+            }
+        }
+        if let Some(orphan) = has_parent.iter().skip(1).position(|&p| !p) {
+            return Err(format!("node {} has no parent", orphan + 1));
+        }
+
+        Ok(MonomialTree { nodes: raw.nodes })
+    }
+}
+
+/// A node reached by a preorder traversal, with its parent and the variable it multiplies in (None for the root)
+#[derive(Clone, Copy, Debug)]
+pub struct Visit {
+    pub id: NodeId,
+    pub parent: Option<NodeId>,
+    pub variable: Option<usize>,
+}
+
+impl<P> MonomialTree<P> {
+
+    /// Tree holding only a root
+    pub fn new(root: P) -> Self {
+        MonomialTree { nodes: vec![Node { payload: root, children: Vec::new() }] }
+    }
+
+    /// Tree of every monomial over `dimension` variables up to `max_degree`, built depth first. `child` computes each
+    /// node's payload from its parent's payload and the variable it multiplies in, and is called in preorder, so the
+    /// n-th call creates node n + 1.
+    pub fn build(dimension: usize, max_degree: usize, root: P, mut child: impl FnMut(&P, usize) -> P) -> Self {
+        let mut tree = MonomialTree::new(root);
+        tree.build_below(0, -1, 0, dimension, max_degree, &mut child);
+        tree
+    }
+
+    fn build_below(&mut self, id: NodeId, variable: isize, degree: usize, dimension: usize, max_degree: usize, child: &mut impl FnMut(&P, usize) -> P) {
+        if degree == max_degree {
+            return;
+        }
+        for i in (variable + 1) as usize..dimension {
+            let payload = child(&self.nodes[id].payload, i);
+            let c = self.add_child(id, payload);
+            self.build_below(c, i as isize, degree + 1, dimension, max_degree, child);
+        }
+    }
+
+    /// Append a child to `parent`, returning its id
+    pub fn add_child(&mut self, parent: NodeId, payload: P) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node { payload, children: Vec::new() });
+        self.nodes[parent].children.push(id as u32);
+        id
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Trees always hold a root, so are never empty
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes[id].children.iter().map(|&c| c as usize)
+    }
+
+    /// Every node in preorder
+    pub fn preorder(&self) -> Preorder<'_, P> {
+        Preorder { tree: self, stack: vec![Visit { id: 0, parent: None, variable: None }] }
+    }
+
+    /// Tree of the same shape with each payload transformed
+    pub fn map<Q>(&self, mut f: impl FnMut(&P) -> Q) -> MonomialTree<Q> {
+        MonomialTree {
+            nodes: self.nodes.iter().map(|n| Node { payload: f(&n.payload), children: n.children.clone() }).collect(),
+        }
+    }
+
+    /// Corresponding nodes of two trees of the same shape, in preorder; None if the shapes differ
+    pub fn zip<Q>(&self, other: &MonomialTree<Q>) -> Option<Vec<(NodeId, NodeId)>> {
+        let mut pairs = Vec::with_capacity(self.len());
+        let mut stack = vec![(0, 0)];
+
+        while let Some((a, b)) = stack.pop() {
+            let (a_children, b_children) = (&self.nodes[a].children, &other.nodes[b].children);
+            if a_children.len() != b_children.len() {
+                return None;
+            }
+            pairs.push((a, b));
+            stack.extend(a_children.iter().zip(b_children).rev().map(|(&x, &y)| (x as usize, y as usize)));
+        }
+
+        Some(pairs)
+    }
+
+    /// Tree of this shape whose payloads are computed from corresponding nodes of both trees. Returns None if the
+    /// shapes differ or `f` rejects a pair of nodes.
+    pub fn zip_map<Q, R>(&mut self, other: &MonomialTree<Q>, mut f: impl FnMut(&mut P, &Q) -> Option<R>) -> Option<MonomialTree<R>> {
+        let mut payloads: Vec<Option<R>> = (0..self.len()).map(|_| None).collect();
+        for (a, b) in self.zip(other)? {
+            payloads[a] = Some(f(&mut self.nodes[a].payload, &other.nodes[b].payload)?);
+        }
+
+        Some(MonomialTree {
+            nodes: payloads.into_iter().zip(&self.nodes)
+                .map(|(payload, n)| Some(Node { payload: payload?, children: n.children.clone() }))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Check every pair of corresponding nodes in parallel; no further pairs are started once one fails. Returns false
+    /// if the shapes differ.
+    pub fn par_zip_all<Q: Sync>(&mut self, other: &MonomialTree<Q>, f: impl Fn(&mut P, &Q) -> bool + Sync) -> bool
+    where P: Send
+    {
+        let mut partner = vec![0; self.len()];
+        match self.zip(other) {
+            Some(pairs) => pairs.into_iter().for_each(|(a, b)| partner[a] = b),
+            None => return false,
+        }
+
+        self.nodes.par_iter_mut().zip(partner.par_iter())
+            .all(|(n, &b)| f(&mut n.payload, &other.nodes[b].payload))
+    }
+
+    /// Split a tree of pairs into two trees of the same shape
+    pub fn unzip<A, B>(self) -> (MonomialTree<A>, MonomialTree<B>)
+    where P: Into<(A, B)>
+    {
+        let (a, b) = self.nodes.into_iter()
+            .map(|n| {
+                let (x, y) = n.payload.into();
+                (Node { payload: x, children: n.children.clone() }, Node { payload: y, children: n.children })
+            })
+            .unzip();
+        (MonomialTree { nodes: a }, MonomialTree { nodes: b })
+    }
+
+    /// Every node with its monomial, in preorder
+    pub fn monomials<T: PrimInt>(&self) -> Vec<(Monomial<T>, NodeId)> {
+        let mut tags = vec![Monomial::new(T::zero()); self.len()];
+        self.preorder()
+            .map(|v| {
+                if let (Some(p), Some(i)) = (v.parent, v.variable) {
+                    tags[v.id] = tags[p].times(i);
+                }
+                (tags[v.id], v.id)
+            })
+            .collect()
+    }
+}
+
+impl<P> Index<NodeId> for MonomialTree<P> {
+    type Output = P;
+
+    fn index(&self, id: NodeId) -> &P {
+        &self.nodes[id].payload
+    }
+}
+
+impl<P> IndexMut<NodeId> for MonomialTree<P> {
+    fn index_mut(&mut self, id: NodeId) -> &mut P {
+        &mut self.nodes[id].payload
+    }
+}
+
+/// Preorder traversal of a `MonomialTree`
+pub struct Preorder<'a, P> {
+    tree: &'a MonomialTree<P>,
+    stack: Vec<Visit>,
+}
+
+impl<P> Iterator for Preorder<'_, P> {
+    type Item = Visit;
+
+    fn next(&mut self) -> Option<Visit> {
+        let visit = self.stack.pop()?;
+        let first = visit.variable.map_or(0, |j| j + 1);
+        self.stack.extend(self.tree.nodes[visit.id].children.iter().enumerate().rev().map(|(k, &c)| Visit {
+            id: c as usize,
+            parent: Some(visit.id),
+            variable: Some(first + k),
+        }));
+        Some(visit)
+    }
+}
+
+/// Node payload that may carry the commitment to the node's partial product
+pub trait Committed {
+    type Value: Copy + PartialEq;
+
+    fn commitment(&self) -> Option<Self::Value>;
+}
+
+impl Committed for CommitmentPayload {
+    type Value = RistrettoPoint;
+
+    fn commitment(&self) -> Option<RistrettoPoint> {
+        self.commitment
+    }
+}
+
+/// Commitment that can be summed homomorphically across database entries
+pub trait Homomorphic: Copy {
+    fn combine(self, other: Self) -> Self;
+
+    /// Commitment that cancels this one out when combined with it
+    fn negate(self) -> Self;
+}
+
+impl Homomorphic for RistrettoPoint {
+    fn combine(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn negate(self) -> Self {
+        -self
+    }
+}
+
+/// An opened commitment: value, commitment, and randomness
+impl Homomorphic for (Scalar, RistrettoPoint, Scalar) {
+    fn combine(self, other: Self) -> Self {
+        (self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+
+    fn negate(self) -> Self {
+        (-self.0, -self.1, -self.2)
+    }
+}
+
+/// Based on a monomial tree, extract the commitment of each monomial, tagged by the bitmask of its variables.
+pub fn extract_monomials<T: PrimInt + Hash, P: Committed>(tree: &MonomialTree<P>, element_commitment_map: &mut HashMap<Monomial<T>, P::Value>) {
+    for (tag, id) in tree.monomials::<T>() {
+        if let Some(v) = tree[id].commitment() {
+            element_commitment_map.insert(tag, v);
+        }
+    }
+}
+
+/// Based on the forest of monomial trees, generate the final commitment for each monomial and aggregate them into a map by monomial.
+pub fn gen_monomial_map<T: PrimInt + Hash, P: Committed>(trees: &[MonomialTree<P>], commitment_map: &mut HashMap<Monomial<T>, P::Value>)
+where P::Value: Homomorphic
+{
+    for tree in trees {
+        let mut element_commitment_map: HashMap<Monomial<T>, P::Value> = HashMap::new();
+        extract_monomials(tree, &mut element_commitment_map);
+
+        for (k, v) in element_commitment_map {
+            commitment_map.entry(k)
+                .and_modify(|c| *c = c.combine(v))
+                .or_insert(v);
+        }
+    }
+}
+
+/// Remove one entry's monomial commitments from an aggregated map, e.g. for an entry deleted from the database.
+pub fn subtract_monomial_map<T: PrimInt + Hash, V: Homomorphic>(commitment_map: &mut HashMap<Monomial<T>, V>, element_commitment_map: &HashMap<Monomial<T>, V>) {
+    for (&k, &v) in element_commitment_map {
+        commitment_map.entry(k)
+            .and_modify(|c| *c = c.combine(v.negate()))
+            .or_insert(v.negate());
+    }
+}
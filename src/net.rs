@@ -0,0 +1,404 @@
+/*!
+ * net.rs
+ *
+ * Async networking between the parties, on tokio. Every TCP connection a party opens or accepts, with or without TLS, is
+ * driven by two tasks on a runtime the whole process shares: a reader taking the peer's frames off the socket as they
+ * arrive, and a writer draining the party's own frames onto it. The phases stay blocking code, as the commitment and
+ * query computations are, and reach the tasks through a `NetStream`, the `Transport` they run on: a send queues its
+ * frame and returns, so the party keeps computing while its frames drain to the peer, and a read waits on tokio's timers
+ * for as long as the phase's deadline allows. A `NetListener` accepts connections, and runs their TLS handshakes, on
+ * the runtime as they arrive, so a verifier connecting while the prover computes is taken up at once rather than left
+ * in the backlog, and however many sessions a process holds open, their sockets wait on the same two threads. Frames
+ * cross the socket behind a 4-byte size header, as over a blocking `TcpStream` or `certified_dp::tls` stream, so a
+ * party on either can talk to a party on the other.
+ */
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, Connection, ServerConnection};
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{self, Handle, Runtime};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+
+use crate::tls::{ClientConfig, ServerConfig};
+use crate::transport::{FrameTooLarge, Transport};
+
+/// Frames queued in either direction before a send, or the reader, waits for the other end to catch up
+const PENDING_FRAMES: usize = 16;
+
+/// Longest a party waits as it closes a connection for its last frames to reach the peer
+const LINGER: Duration = Duration::from_secs(10);
+
+/// Longest a verifier waits for the prover to accept its connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bytes read off a socket at once
+const READ_CHUNK: usize = 16 << 10;
+
+/// Runtime driving every connection of the process, started with the first one
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        runtime::Builder::new_multi_thread().worker_threads(2).thread_name("certified-dp-net").enable_all().build()
+            .expect("failed to start the network runtime")
+    })
+}
+
+/// TLS session of a connection, shared by its reader and writer tasks
+#[derive(Clone)]
+struct Tls {
+    session: Arc<Mutex<Connection>>,
+    /// Wakes the writer when the reader leaves records to send, e.g. an alert or a key update
+    pending: Arc<Notify>,
+}
+
+/// One end of a TCP connection, driven by its tasks on the network runtime
+pub struct NetStream {
+    /// Queue of the frames to the peer, dropped to close the connection
+    outgoing: Option<mpsc::Sender<Vec<u8>>>,
+    incoming: mpsc::Receiver<io::Result<Vec<u8>>>,
+    timeout: Option<Duration>,
+    reader: JoinHandle<()>,
+    /// Disconnected once the writer has sent every frame and closed its half of the connection
+    written: std_mpsc::Receiver<()>,
+}
+
+impl NetStream {
+    /// Verifier's connection to the prover at `address`, over TLS with `tls` if given, accepting only a certificate for
+    /// the address's IP, and refusing frames from it over `limit` bytes
+    pub fn connect(address: SocketAddr, tls: Option<Arc<ClientConfig>>, limit: usize) -> io::Result<NetStream> {
+        runtime().block_on(async {
+            let socket = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))??;
+            let session = match tls {
+                Some(config) => {
+                    let name = ServerName::try_from(address.ip().to_string()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                    Some(ClientConnection::new(config, name).map_err(io::Error::other)?.into())
+                },
+                None => None,
+            };
+            NetStream::start(socket, session, limit).await
+        })
+    }
+
+    /// Run the TLS handshake, if there is a session, then hand the socket to its reader and writer tasks
+    async fn start(mut socket: TcpStream, session: Option<Connection>, limit: usize) -> io::Result<NetStream> {
+        let mut plaintext = Vec::new();
+        let tls = match session {
+            Some(mut session) => {
+                handshake(&mut socket, &mut session).await?;
+                // the peer's first frame may have come in with the end of the handshake
+                drain(&mut session, &mut plaintext)?;
+                Some(Tls { session: Arc::new(Mutex::new(session)), pending: Arc::new(Notify::new()) })
+            },
+            None => None,
+        };
+
+        let (read, write) = socket.into_split();
+        let (frames, incoming) = mpsc::channel(PENDING_FRAMES);
+        let (outgoing, queued) = mpsc::channel(PENDING_FRAMES);
+        let (writing, written) = std_mpsc::channel();
+        let source = Source { socket: read, tls: tls.clone(), buffer: plaintext, chunk: vec![0; READ_CHUNK], open: true };
+        let reader = tokio::spawn(read_frames(source, limit, frames));
+        tokio::spawn(async move {
+            let _writing: std_mpsc::Sender<()> = writing;
+            // a failed write loses the connection, which fails the reads after it
+            let _ = write_frames(write, tls, queued).await;
+        });
+        Ok(NetStream { outgoing: Some(outgoing), incoming, timeout: None, reader, written })
+    }
+}
+
+impl Transport for NetStream {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        let outgoing = self.outgoing.as_ref().expect("the stream is only closed as it drops");
+        outgoing.blocking_send(frame.to_vec()).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "peer disconnected"))
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let (timeout, incoming) = (self.timeout, &mut self.incoming);
+        let frame = runtime().block_on(async move {
+            let next = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, incoming.recv()).await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "read timed out"))?,
+                None => incoming.recv().await,
+            };
+            next.unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer disconnected")))
+        })?;
+
+        if frame.len() > limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size: frame.len(), limit }));
+        }
+        Ok(frame)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for NetStream {
+    /// Close the connection once the frames queued have been written, since a party exits right after its last
+    /// session; a stream dropped on the runtime itself, e.g. one accepted after its listener was dropped, does not wait
+    fn drop(&mut self) {
+        self.outgoing.take();
+        if Handle::try_current().is_err() {
+            let _ = self.written.recv_timeout(LINGER);
+        }
+        self.reader.abort();
+    }
+}
+
+/// Prover's TCP listener, accepting the connections verifiers open in order
+pub struct NetListener {
+    accepted: std_mpsc::Receiver<io::Result<NetStream>>,
+    address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl NetListener {
+    /// Accept connections on `address` as they arrive, running the TLS handshake with `tls` on each if given, and
+    /// refusing frames over `limit` bytes
+    pub fn bind(address: &str, tls: Option<Arc<ServerConfig>>, limit: usize) -> io::Result<NetListener> {
+        let listener = std::net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let listener = {
+            let _runtime = runtime().enter();
+            TcpListener::from_std(listener)?
+        };
+
+        let (opened, accepted) = std_mpsc::channel();
+        let (handshakes, mut ordered) = mpsc::unbounded_channel::<JoinHandle<io::Result<NetStream>>>();
+        let task = runtime().spawn(async move {
+            // handshakes run side by side, and their connections are accepted in the order they arrived
+            tokio::spawn(async move {
+                while let Some(handshake) = ordered.recv().await {
+                    if opened.send(handshake.await.unwrap_or_else(|e| Err(io::Error::other(e)))).is_err() {
+                        break;
+                    }
+                }
+            });
+            loop {
+                let handshake = match listener.accept().await {
+                    Ok((socket, _)) => {
+                        let tls = tls.clone();
+                        tokio::spawn(async move {
+                            let session = tls.map(|config| ServerConnection::new(config).map(Connection::from)).transpose()
+                                .map_err(io::Error::other)?;
+                            NetStream::start(socket, session, limit).await
+                        })
+                    },
+                    Err(e) => tokio::spawn(async move { Err(e) }),
+                };
+                if handshakes.send(handshake).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(NetListener { accepted, address, task })
+    }
+
+    /// Address the listener is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Wait for the next verifier's connection, failing if its TLS handshake failed
+    pub fn accept(&self) -> io::Result<NetStream> {
+        self.accepted.recv().map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "listener stopped"))?
+    }
+}
+
+impl Drop for NetListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Plaintext of the peer's side of a connection, read off the socket and decrypted if it runs over TLS
+struct Source {
+    socket: OwnedReadHalf,
+    tls: Option<Tls>,
+    /// Plaintext received and not yet framed
+    buffer: Vec<u8>,
+    chunk: Vec<u8>,
+    /// Whether the peer may still send, i.e. it has neither closed the socket nor sent a TLS close_notify
+    open: bool,
+}
+
+impl Source {
+    /// Next frame from the peer, refusing one announced over `limit` bytes before reading it, or None once the peer
+    /// has closed the connection between frames. The buffer only grows with the bytes actually received.
+    async fn frame(&mut self, limit: usize) -> io::Result<Option<Vec<u8>>> {
+        while self.buffer.len() < 4 {
+            if !self.fill().await? {
+                return match self.buffer.is_empty() {
+                    true => Ok(None),
+                    false => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "frame header truncated")),
+                };
+            }
+        }
+        let size = u32::from_le_bytes(self.buffer[..4].try_into().unwrap()) as usize;
+        if size > limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size, limit }));
+        }
+        while self.buffer.len() < 4 + size {
+            if !self.fill().await? {
+                let received = self.buffer.len() - 4;
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("frame truncated at {} of {} bytes", received, size)));
+            }
+        }
+        let rest = self.buffer.split_off(4 + size);
+        let mut frame = std::mem::replace(&mut self.buffer, rest);
+        frame.drain(..4);
+        Ok(Some(frame))
+    }
+
+    /// Receive more plaintext, returning false once the peer has closed the connection
+    async fn fill(&mut self) -> io::Result<bool> {
+        let start = self.buffer.len();
+        while self.open && self.buffer.len() == start {
+            let n = self.socket.read(&mut self.chunk).await?;
+            if n == 0 {
+                self.open = false;
+                break;
+            }
+            match &self.tls {
+                Some(tls) => self.open = decrypt(tls, &self.chunk[..n], &mut self.buffer)?,
+                None => self.buffer.extend_from_slice(&self.chunk[..n]),
+            }
+        }
+        Ok(self.buffer.len() > start)
+    }
+}
+
+/// Forward the peer's frames to the stream until the peer closes the connection, or a frame fails
+async fn read_frames(mut source: Source, limit: usize, frames: mpsc::Sender<io::Result<Vec<u8>>>) {
+    loop {
+        let frame = match source.frame(limit).await {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => break,
+            Err(e) => Err(e),
+        };
+        let failed = frame.is_err();
+        if frames.send(frame).await.is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// Write the party's frames to the socket, each behind its size header, then close the connection once the stream
+/// drops its queue
+async fn write_frames(mut socket: OwnedWriteHalf, tls: Option<Tls>, mut queued: mpsc::Receiver<Vec<u8>>) -> io::Result<()> {
+    loop {
+        let frame = match &tls {
+            Some(tls) => tokio::select! {
+                frame = queued.recv() => frame,
+                _ = tls.pending.notified() => {
+                    flush(&mut socket, tls).await?;
+                    continue;
+                },
+            },
+            None => queued.recv().await,
+        };
+        let Some(frame) = frame else {
+            break;
+        };
+        let mut data = Vec::with_capacity(4 + frame.len());
+        data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        data.extend_from_slice(&frame);
+        match &tls {
+            Some(tls) => encrypt(&mut socket, tls, &data).await?,
+            None => socket.write_all(&data).await?,
+        }
+    }
+    if let Some(tls) = &tls {
+        tls.session.lock().unwrap().send_close_notify();
+        flush(&mut socket, tls).await?;
+    }
+    socket.shutdown().await
+}
+
+/// Run a TLS handshake on a new connection, sending the peer an alert if it fails, as the blocking handshake does
+async fn handshake(socket: &mut TcpStream, session: &mut Connection) -> io::Result<()> {
+    let mut chunk = vec![0; READ_CHUNK];
+    loop {
+        let mut records = Vec::new();
+        while session.wants_write() {
+            session.write_tls(&mut records)?;
+        }
+        socket.write_all(&records).await?;
+        if !session.is_handshaking() {
+            return Ok(());
+        }
+
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer closed the connection during the TLS handshake"));
+        }
+        let mut received = &chunk[..n];
+        while !received.is_empty() {
+            session.read_tls(&mut received)?;
+            if let Err(e) = session.process_new_packets() {
+                let mut alert = Vec::new();
+                while session.wants_write() && session.write_tls(&mut alert).is_ok() {}
+                let _ = socket.write_all(&alert).await;
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
+    }
+}
+
+/// Decrypt records received from the peer into `plaintext`, returning whether the peer may still send
+fn decrypt(tls: &Tls, mut received: &[u8], plaintext: &mut Vec<u8>) -> io::Result<bool> {
+    let mut session = tls.session.lock().unwrap();
+    let mut open = true;
+    while !received.is_empty() {
+        session.read_tls(&mut received)?;
+        let state = session.process_new_packets();
+        if session.wants_write() {
+            tls.pending.notify_one();
+        }
+        open &= !state.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?.peer_has_closed();
+        drain(&mut session, plaintext)?;
+    }
+    Ok(open)
+}
+
+/// Move the plaintext a TLS session has decrypted into `plaintext`
+fn drain(session: &mut Connection, plaintext: &mut Vec<u8>) -> io::Result<()> {
+    match session.reader().read_to_end(plaintext) {
+        Err(e) if e.kind() != io::ErrorKind::WouldBlock => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Encrypt `data` to the peer, as much at a time as the session buffers
+async fn encrypt(socket: &mut OwnedWriteHalf, tls: &Tls, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        let n = tls.session.lock().unwrap().writer().write(data)?;
+        data = &data[n..];
+        flush(socket, tls).await?;
+    }
+    Ok(())
+}
+
+/// Send the records a TLS session has queued
+async fn flush(socket: &mut OwnedWriteHalf, tls: &Tls) -> io::Result<()> {
+    let mut records = Vec::new();
+    {
+        let mut session = tls.session.lock().unwrap();
+        while session.wants_write() {
+            session.write_tls(&mut records)?;
+        }
+    }
+    socket.write_all(&records).await
+}
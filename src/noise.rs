@@ -0,0 +1,260 @@
+/*!
+ * noise.rs
+ *
+ * Noise mechanisms built from the session's verified coin flips. A mechanism says how many fair coins the randomness
+ * phase flips, which products of them the prover must commit to and prove with the product sigma protocol, and how the
+ * coins and products add up into the noise, less an offset that centers it on zero. Both parties combine the same
+ * commitments the same way, so the noise commitment stays homomorphic in the coins whatever the mechanism.
+ *
+ * The binomial mechanism sums N coins less N/2 and needs no products. The discrete Laplace mechanism is the difference
+ * of two geometric variables, each counting the trials it continues through before its first stop, where a trial
+ * stops if all of its m coins come up one: the prover commits to the AND of each trial's coins and to every prefix of
+ * continued trials, so each geometric is the sum of its prefixes. Continuing with probability r = 1 - 2^-m, the noise
+ * is (-ln r)-differentially private for queries of sensitivity one; m is the fewest coins that keep that within
+ * epsilon, and the trials are cut off once the chance of running past them is within delta.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::calibration::{self, Calibration};
+use crate::result;
+
+/// Value the randomness phase produces, which factors of the noise are taken from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wire {
+    /// The `i`th flipped coin
+    Coin(u32),
+    /// The output of the `i`th product gate
+    Product(u32),
+}
+
+/// A wire, or one minus it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Factor {
+    pub wire: Wire,
+    pub negated: bool,
+}
+
+impl Factor {
+    pub fn of(wire: Wire) -> Factor {
+        Factor { wire, negated: false }
+    }
+
+    /// One minus this factor
+    pub fn negate(self) -> Factor {
+        Factor { negated: !self.negated, ..self }
+    }
+
+    /// This factor's value (or commitment, or opening) from those of the coins and the products so far, where `not`
+    /// takes a wire's to its negation's
+    pub fn resolve<V: Copy>(&self, coins: &[V], products: &[V], not: impl Fn(V) -> V) -> V {
+        let v = match self.wire {
+            Wire::Coin(i) => coins[i as usize],
+            Wire::Product(i) => products[i as usize],
+        };
+        if self.negated { not(v) } else { v }
+    }
+}
+
+/// How the session's flipped coins are combined into noise
+pub trait NoiseMechanism: Send + Sync {
+    /// Number of fair coins the randomness phase flips
+    fn coins(&self) -> u32;
+    /// Products the prover commits to and proves, in order; the `i`th gate's output is `Wire::Product(i)`, and a gate
+    /// may only take the outputs of earlier gates
+    fn gates(&self) -> Vec<(Factor, Factor)>;
+    /// Weighted factors whose sum, less `offset`, is the noise
+    fn terms(&self) -> Vec<(Factor, i64)>;
+    fn offset(&self) -> u32;
+    /// Standard deviation of the noise
+    fn std(&self) -> f64;
+    /// Smallest k such that the noise is within [-k, k] with probability at least `confidence`
+    fn bound(&self, confidence: f64) -> u32;
+}
+
+/// Sum of `coins` fair coins less half of them, rounded down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binomial {
+    pub coins: u32,
+}
+
+impl NoiseMechanism for Binomial {
+    fn coins(&self) -> u32 {
+        self.coins
+    }
+
+    fn gates(&self) -> Vec<(Factor, Factor)> {
+        Vec::new()
+    }
+
+    fn terms(&self) -> Vec<(Factor, i64)> {
+        (0..self.coins).map(|i| (Factor::of(Wire::Coin(i)), 1)).collect()
+    }
+
+    fn offset(&self) -> u32 {
+        self.coins / 2
+    }
+
+    fn std(&self) -> f64 {
+        result::noise_std(self.coins)
+    }
+
+    fn bound(&self, confidence: f64) -> u32 {
+        result::noise_bound(self.coins, confidence)
+    }
+}
+
+/// Difference of two geometric variables of `trials` trials each, every trial stopping when all of its `stop` coins
+/// come up one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiscreteLaplace {
+    pub stop: u32,
+    pub trials: u32,
+}
+
+impl DiscreteLaplace {
+    /// Least coins per trial and trials per geometric for noise that is (ε, δ)-differentially private, with δ bounding
+    /// the chance of either geometric running past its trials
+    pub fn new(epsilon: f32, delta: f32) -> DiscreteLaplace {
+        let epsilon = epsilon as f64;
+        let stop = (1..64).find(|&m| -(1.0 - 0.5f64.powi(m)).ln() <= epsilon).unwrap_or(64) as u32;
+        let r = DiscreteLaplace { stop, trials: 0 }.ratio();
+        let trials = ((delta as f64 / 2.0).ln() / r.ln()).ceil().max(1.0) as u32;
+        DiscreteLaplace { stop, trials }
+    }
+
+    /// Chance r of a trial continuing
+    pub fn ratio(&self) -> f64 {
+        1.0 - 0.5f64.powi(self.stop as i32)
+    }
+
+    /// Epsilon the noise spends on a query of sensitivity one, at most the session's
+    pub fn epsilon(&self) -> f64 {
+        -self.ratio().ln()
+    }
+
+    /// Number of gates before those of trial `t`: each trial has a gate for every coin after its first, ANDing it in,
+    /// and one more extending the prefix, unless it starts its geometric
+    fn gates_before(&self, t: u32) -> u32 {
+        t * (self.stop - 1) + t - t.div_ceil(self.trials)
+    }
+
+    /// Whether trial `t` stops: the AND of its coins
+    fn stopped(&self, t: u32) -> Factor {
+        match self.stop {
+            1 => Factor::of(Wire::Coin(t)),
+            m => Factor::of(Wire::Product(self.gates_before(t) + m - 2)),
+        }
+    }
+
+    /// Whether the geometric continues through trial `t` and every one of its trials before
+    fn prefix(&self, t: u32) -> Factor {
+        match t % self.trials {
+            0 => self.stopped(t).negate(),
+            _ => Factor::of(Wire::Product(self.gates_before(t) + self.stop - 1)),
+        }
+    }
+}
+
+impl NoiseMechanism for DiscreteLaplace {
+    fn coins(&self) -> u32 {
+        2 * self.trials * self.stop
+    }
+
+    fn gates(&self) -> Vec<(Factor, Factor)> {
+        let mut gates = Vec::with_capacity(self.gates_before(2 * self.trials) as usize);
+        for t in 0..2 * self.trials {
+            let first = t * self.stop;
+            for coin in first + 1..first + self.stop {
+                let and = match coin - first {
+                    1 => Factor::of(Wire::Coin(first)),
+                    _ => Factor::of(Wire::Product(gates.len() as u32 - 1)),
+                };
+                gates.push((and, Factor::of(Wire::Coin(coin))));
+            }
+            if t % self.trials != 0 {
+                gates.push((self.prefix(t - 1), self.stopped(t).negate()));
+            }
+        }
+        gates
+    }
+
+    /// The first geometric's prefixes less the second's
+    fn terms(&self) -> Vec<(Factor, i64)> {
+        (0..2 * self.trials).map(|t| (self.prefix(t), if t < self.trials { 1 } else { -1 })).collect()
+    }
+
+    fn offset(&self) -> u32 {
+        0
+    }
+
+    fn std(&self) -> f64 {
+        let r = self.ratio();
+        (2.0 * r).sqrt() / (1.0 - r)
+    }
+
+    fn bound(&self, confidence: f64) -> u32 {
+        // the noise is beyond k either way with probability 2 r^(k+1) / (1 + r)
+        let r = self.ratio();
+        (((1.0 - confidence) * (1.0 + r) / 2.0).ln() / r.ln() - 1.0).ceil().max(0.0) as u32
+    }
+}
+
+/// Noise mechanism a session is run with
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mechanism {
+    #[default]
+    Binomial,
+    DiscreteLaplace,
+}
+
+impl Mechanism {
+    /// The mechanism's noise for a session over `db_size` entries with `epsilon` and `delta`, for queries of up to
+    /// `sensitivity`; only the binomial noise is calibrated by `calibration`, the discrete Laplace noise being sized for
+    /// each query on its own. The binomial noise takes the square of the sensitivity times the coins to scale its
+    /// deviation, and the discrete Laplace noise spends epsilon over the sensitivity on each unit.
+    pub fn noise(self, db_size: u32, epsilon: f32, delta: Option<f32>, calibration: Calibration, sensitivity: u32) -> Box<dyn NoiseMechanism> {
+        match self {
+            Mechanism::Binomial => {
+                let coins = calibration::coins(db_size, epsilon, delta, calibration).saturating_mul(sensitivity.saturating_mul(sensitivity));
+                Box::new(Binomial { coins })
+            },
+            Mechanism::DiscreteLaplace => Box::new(DiscreteLaplace::new(epsilon / sensitivity as f32, calibration::delta(db_size, delta))),
+        }
+    }
+}
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mechanism::Binomial => f.write_str("binomial"),
+            Mechanism::DiscreteLaplace => f.write_str("discrete-laplace"),
+        }
+    }
+}
+
+/// Check that `mechanism` can run with `calibration` and this many `verifiers`, with or without `progressive` noise: the
+/// session calibrations compose the queries' noise, so each query needs its own, the discrete Laplace noise is sized
+/// for each query on its own, and its products are proven to a single verifier
+pub fn check(mechanism: Mechanism, calibration: Calibration, verifiers: u32, progressive: bool) -> Result<(), String> {
+    if calibration != Calibration::Approximate && !progressive {
+        return Err(format!("Calibration {} needs progressive noise, fresh for each query", calibration));
+    }
+    match mechanism {
+        Mechanism::DiscreteLaplace if calibration != Calibration::Approximate => {
+            Err(format!("Discrete Laplace noise takes the approximate calibration, not {}", calibration))
+        },
+        Mechanism::DiscreteLaplace if verifiers > 1 => Err("Discrete Laplace noise needs a single verifier".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Parse a noise mechanism from the command line, `binomial` or `discrete-laplace`
+pub fn parse_mechanism(s: &str) -> Result<Mechanism, String> {
+    match s {
+        "binomial" => Ok(Mechanism::Binomial),
+        "discrete-laplace" => Ok(Mechanism::DiscreteLaplace),
+        _ => Err(format!("expected binomial or discrete-laplace, got {:?}", s)),
+    }
+}
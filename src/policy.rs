@@ -0,0 +1,91 @@
+/*!
+ * policy.rs
+ *
+ * Query policy of the prover: limits on the queries it answers, so that it need not answer whatever the verifier
+ * sends. A policy bounds the degree of each monomial a query weighs, the number of monomials, and the magnitude of each
+ * coefficient read as a signed integer, and may allow only some attributes of the database's schema, whose bits are
+ * then the only ones a monomial may read. Every limit is optional, and the default policy allows every query. The
+ * prover refuses a query breaking the policy with an abort listing each violation, before touching its commitments.
+ */
+
+use curve25519_dalek::Scalar;
+use num_traits::PrimInt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::monomial::Monomial;
+use crate::result;
+use crate::schema::Schema;
+
+/// Limits on the queries the prover answers, each unlimited if None
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Highest degree of a monomial of the query
+    #[serde(default)]
+    pub max_degree: Option<u32>,
+    /// Most monomials in the query
+    #[serde(default)]
+    pub max_sparsity: Option<u32>,
+    /// Largest absolute value of a coefficient, which must be a signed 64-bit integer
+    #[serde(default)]
+    pub max_coefficient: Option<u64>,
+    /// Attributes of the schema a monomial may read, checked once `resolve` has found their bits
+    #[serde(default)]
+    pub attributes: Option<Vec<String>>,
+    /// Bits the allowed attributes take, once resolved against the schema
+    #[serde(skip)]
+    bits: Option<Vec<Range<u32>>>,
+}
+
+impl Policy {
+    /// Load a policy from the JSON file at `path`, e.g. `{"max_degree": 2, "max_sparsity": 8, "max_coefficient": 1,
+    /// "attributes": ["smoker", "region"]}`, resolving its attributes against `schema`
+    pub fn load(path: &Path, schema: &Schema) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let policy: Policy = serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))?;
+        policy.resolve(schema)
+    }
+
+    /// This policy with its allowed attributes resolved to the bits they take in `schema`; fails if one is not in it
+    pub fn resolve(mut self, schema: &Schema) -> Result<Self, String> {
+        self.bits = match &self.attributes {
+            Some(names) => Some(names.iter().map(|name| schema.attribute(name).map(|(_, bits)| bits)).collect::<Result<_, _>>()?),
+            None => None,
+        };
+        Ok(self)
+    }
+
+    /// Every way the query of `coefficients` breaks the policy, in the order of its monomials; empty if it conforms
+    pub fn violations<T: PrimInt + Hash>(&self, coefficients: &HashMap<Monomial<T>, Scalar>) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Some(max) = self.max_sparsity.filter(|&max| coefficients.len() > max as usize) {
+            violations.push(format!("query has {} monomials, beyond {}", coefficients.len(), max));
+        }
+        let mut monomials: Vec<_> = coefficients.iter().collect();
+        monomials.sort_by_key(|(m, _)| **m);
+        for (m, c) in monomials {
+            if let Some(max) = self.max_degree.filter(|&max| m.degree() > max) {
+                violations.push(format!("monomial {} has degree {}, beyond {}", m, m.degree(), max));
+            }
+            if let Some(max) = self.max_coefficient {
+                match result::to_i64(c) {
+                    Some(c) if c.unsigned_abs() <= max => {},
+                    Some(c) => violations.push(format!("coefficient {} of monomial {} is beyond {}", c, m, max)),
+                    None => violations.push(format!("coefficient of monomial {} is not an integer", m)),
+                }
+            }
+            if let Some(bits) = &self.bits {
+                if let Some(bit) = m.variables().find(|&bit| !bits.iter().any(|b| b.contains(&(bit as u32)))) {
+                    violations.push(format!("monomial {} reads bit {}, outside the allowed attributes", m, bit));
+                }
+            }
+        }
+        violations
+    }
+}
@@ -0,0 +1,487 @@
+/*!
+ * predicate.rs
+ *
+ * Counting queries from boolean predicates over the attributes of a schema, e.g. `smoker & (region=north | !age=70)`.
+ * Over entries of bits, a literal is its bit, a negation one minus its predicate, a conjunction the product of its
+ * predicates, a disjunction their inclusion–exclusion sum, p + q - pq, and an exclusive or p + q - 2pq, whose
+ * expansion over literals x_1 ... x_k is the sum over their nonempty subsets S of (-2)^(|S|-1) times the product of
+ * S, i.e. (1 - (1 - 2x_1) ... (1 - 2x_k)) / 2 with each 1 - 2x the ±1 sign of a literal. Since each bit is 0 or 1,
+ * a product of monomials is the monomial of their variables together, or 0 for two categories or buckets of one
+ * attribute, which no entry sets together. A negated literal is one minus its bit, so with a schema or over the bare
+ * entry bits `xN`, e.g. `x3 & !x5` or `x3 & x5=0`, complements expand into the committed monomials with signed
+ * coefficients and a constant. A predicate thus compiles to a polynomial that is 1 on the entries matching it and 0 elsewhere,
+ * whose sum over the database counts them, with a sensitivity of one whatever its coefficients. The prover commits to no constant monomial, so a compiled query keeps its constant term apart, to be
+ * added once per entry to the answer over the other monomials. The k-way marginals over a set of attributes, the usual
+ * workload for evaluating a private release, come as one such query per cell.
+ */
+
+use curve25519_dalek::Scalar;
+use num_traits::PrimInt;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::monomial::Monomial;
+use crate::result;
+use crate::schema::{Attribute, Encoding, Schema};
+
+/// Boolean predicate over the attributes of a schema
+#[derive(Clone, Debug, PartialEq)]
+pub enum Predicate {
+    /// Entries whose attribute is true, is the category, or is in the bucket of the value, as `Schema::bit` takes it,
+    /// or for a binary attribute, is the value; an attribute `xN` the schema does not name is the entry bit N, and
+    /// the value 1 or 0
+    Literal { attribute: String, value: String },
+    /// Entries whose binary attribute is between `low` and `high`, inclusive
+    Range { attribute: String, low: u64, high: u64 },
+    /// Entries not matching the predicate
+    Not(Box<Predicate>),
+    /// Entries matching every predicate
+    And(Vec<Predicate>),
+    /// Entries matching any predicate
+    Or(Vec<Predicate>),
+    /// Entries matching an odd number of the predicates
+    Xor(Vec<Predicate>),
+}
+
+/// Query counting the entries matching a predicate: its coefficients over the committed monomials, and the constant
+/// every entry adds to the count
+#[derive(Clone, Debug)]
+pub struct Query<T> {
+    pub coefficients: HashMap<Monomial<T>, Scalar>,
+    pub constant: Scalar,
+}
+
+impl<T> Query<T> {
+    /// Count of the entries matching the predicate, from the answer to the query's coefficients over `db_size`
+    /// entries, if the constant's share fits in 64 bits
+    pub fn count(&self, answer: i64, db_size: u32) -> Option<i64> {
+        result::to_i64(&self.constant)?.checked_mul(db_size as i64)?.checked_add(answer)
+    }
+
+    /// L1 sensitivity of the count: one, since the query's polynomial is 0 or 1 on every entry, however large the sum
+    /// of its coefficients
+    pub fn sensitivity(&self) -> u64 {
+        1
+    }
+}
+
+/// Polynomial over the bits of an entry, by the coefficient of each monomial, the constant included
+type Polynomial<T> = HashMap<Monomial<T>, Scalar>;
+
+fn add<T: PrimInt + Hash>(mut p: Polynomial<T>, q: &Polynomial<T>, scale: Scalar) -> Polynomial<T> {
+    for (&m, &c) in q {
+        *p.entry(m).or_insert(Scalar::ZERO) += scale * c;
+    }
+    p.retain(|_, c| *c != Scalar::ZERO);
+    p
+}
+
+/// Product of `p` and `q` on the entries encoded with `schema`
+fn multiply<T: PrimInt + Hash>(p: &Polynomial<T>, q: &Polynomial<T>, schema: &Schema) -> Polynomial<T> {
+    let mut product = HashMap::new();
+    for (&m, &c) in p {
+        for (&n, &d) in q {
+            // each bit is 0 or 1, so it squares to itself, and bits no entry sets together multiply to 0
+            let mn = Monomial::new(m.mask() | n.mask());
+            if !schema.excludes(mn) {
+                *product.entry(mn).or_insert(Scalar::ZERO) += c * d;
+            }
+        }
+    }
+    product.retain(|_, c| *c != Scalar::ZERO);
+    product
+}
+
+/// Polynomial that is 1 on the entries whose unsigned integer in `bits`, least significant first, is at least `t`.
+/// Past the bits where the integer and `t` agree, the first where they differ decides, so this sums, over each bit
+/// clear in `t`, the entries agreeing with `t` above it that set it, plus the entries equal to `t`; the terms are
+/// disjoint, and each is a product of literals.
+fn at_least<T: PrimInt + Hash>(bits: Range<u32>, t: u128, schema: &Schema) -> Polynomial<T> {
+    let one = || HashMap::from([(Monomial::new(T::zero()), Scalar::ONE)]);
+    if t >> bits.len() != 0 {
+        return HashMap::new();
+    }
+    let mut sum = HashMap::new();
+    let mut prefix = one();
+    for (i, bit) in bits.enumerate().rev() {
+        let set = HashMap::from([(Monomial::variable(bit as usize), Scalar::ONE)]);
+        if t >> i & 1 == 1 {
+            prefix = multiply(&prefix, &set, schema);
+        } else {
+            sum = add(sum, &multiply(&prefix, &set, schema), Scalar::ONE);
+            prefix = multiply(&prefix, &add(one(), &set, -Scalar::ONE), schema);
+        }
+    }
+    add(sum, &prefix, Scalar::ONE)
+}
+
+/// Polynomial that is 1 on the entries whose unsigned integer in `bits` is between `low` and `high`, inclusive
+fn within<T: PrimInt + Hash>(bits: Range<u32>, low: u64, high: u64, schema: &Schema) -> Polynomial<T> {
+    if low > high {
+        return HashMap::new();
+    }
+    add(at_least(bits.clone(), low as u128, schema), &at_least(bits, high as u128 + 1, schema), -Scalar::ONE)
+}
+
+/// Query of the polynomial `p`, its constant kept apart, failing if it needs a monomial beyond `max_degree`
+fn query<T: PrimInt + Hash>(mut coefficients: Polynomial<T>, schema: &Schema, max_degree: u32) -> Result<Query<T>, String> {
+    let constant = coefficients.remove(&Monomial::new(T::zero())).unwrap_or(Scalar::ZERO);
+    if let Some(m) = coefficients.keys().find(|m| m.degree() > max_degree) {
+        return Err(format!("needs the monomial {} of degree {}, beyond the max degree {}", schema.describe_monomial(*m), m.degree(), max_degree));
+    }
+    Ok(Query { coefficients, constant })
+}
+
+/// Query counting the entries of a database of `schema` whose binary attribute `attribute` is between `low` and
+/// `high`, inclusive, failing if it needs a monomial beyond `max_degree`
+pub fn range_query<T: PrimInt + Hash>(schema: &Schema, attribute: &str, low: u64, high: u64, max_degree: u32) -> Result<Query<T>, String> {
+    Predicate::Range { attribute: attribute.to_string(), low, high }.compile(schema, max_degree)
+}
+
+/// Marginal of a set of attributes: a cell for each combination of their values, which together partition the entries
+#[derive(Clone, Debug)]
+pub struct Marginal<T> {
+    pub attributes: Vec<String>,
+    pub cells: Vec<Cell<T>>,
+}
+
+/// Cell of a marginal, by the values its entries have, e.g. `!smoker & region=north & 18<=age<65`, and the query
+/// counting them
+#[derive(Clone, Debug)]
+pub struct Cell<T> {
+    pub description: String,
+    pub query: Query<T>,
+}
+
+/// Each value of the attribute at `bits`, by its description and the polynomial that is 1 on the entries having it
+fn values<T: PrimInt + Hash>(schema: &Schema, attribute: &Attribute, bits: Range<u32>) -> Vec<(String, Polynomial<T>)> {
+    let one = || HashMap::from([(Monomial::new(T::zero()), Scalar::ONE)]);
+    let set = |bit: u32| HashMap::from([(Monomial::variable(bit as usize), Scalar::ONE)]);
+    match attribute.encoding {
+        Encoding::Boolean => vec![
+            (attribute.name.clone(), set(bits.start)),
+            (format!("!{}", attribute.name), add(one(), &set(bits.start), -Scalar::ONE)),
+        ],
+        Encoding::Categorical(_) | Encoding::Bucketed(_) => {
+            bits.map(|bit| (schema.describe(bit).expect("bit of the schema"), set(bit))).collect()
+        },
+        Encoding::Binary(n) => {
+            (0..1u64 << n).map(|v| (format!("{}={}", attribute.name, v), within(bits.clone(), v, v, schema))).collect()
+        },
+    }
+}
+
+/// Every k-way marginal over `attributes`, one per set of `k` of them in their order, with a query per cell, failing
+/// if a cell needs a monomial beyond `max_degree`. A binary attribute has a cell per value, so takes `max_degree` at
+/// least its bits.
+pub fn marginals<T: PrimInt + Hash>(schema: &Schema, attributes: &[&str], k: usize, max_degree: u32) -> Result<Vec<Marginal<T>>, String> {
+    if k == 0 || k > attributes.len() {
+        return Err(format!("cannot take {}-way marginals of {} attributes", k, attributes.len()));
+    }
+    let mut columns = Vec::new();
+    for (i, name) in attributes.iter().enumerate() {
+        if attributes[..i].contains(name) {
+            return Err(format!("attribute {} is named more than once", name));
+        }
+        let (attribute, bits) = schema.attribute(name)?;
+        if let Encoding::Binary(n) = attribute.encoding {
+            if n > max_degree {
+                return Err(format!("attribute {} takes {} bits, so its values need monomials beyond the max degree {}", name, n, max_degree));
+            }
+        }
+        columns.push((name.to_string(), values::<T>(schema, attribute, bits)));
+    }
+
+    // the subsets of k attributes in lexicographic order of their indices, each stepped to the next by advancing the
+    // last index that can still advance and resetting those after it
+    let mut marginals = Vec::new();
+    let mut subset: Vec<usize> = (0..k).collect();
+    loop {
+        let mut cells = vec![(Vec::new(), HashMap::from([(Monomial::new(T::zero()), Scalar::ONE)]))];
+        for &i in &subset {
+            cells = cells.iter()
+                .flat_map(|(names, p)| columns[i].1.iter().map(move |(name, q)| ([names.clone(), vec![name.clone()]].concat(), multiply(p, q, schema))))
+                .collect();
+        }
+        marginals.push(Marginal {
+            attributes: subset.iter().map(|&i| columns[i].0.clone()).collect(),
+            cells: cells.into_iter().map(|(names, p)| {
+                let description = names.join(" & ");
+                let query = query(p, schema, max_degree).map_err(|e| format!("cell {} {}", description, e))?;
+                Ok(Cell { description, query })
+            }).collect::<Result<_, String>>()?,
+        });
+        let Some(j) = (0..k).rev().find(|&j| subset[j] < attributes.len() - k + j) else {
+            return Ok(marginals);
+        };
+        subset[j] += 1;
+        for l in j + 1..k {
+            subset[l] = subset[l - 1] + 1;
+        }
+    }
+}
+
+/// Query counting the entries of a database of `schema` matching an odd number of `literals`, each an attribute and
+/// a value as `Schema::bit` takes them, failing if it needs a monomial beyond `max_degree`, which is at least the
+/// number of literals on distinct attributes. The sum of their ±1 signs, (-1) to the number matched, is then the
+/// number of entries less twice the count.
+pub fn parity_query<T: PrimInt + Hash>(schema: &Schema, literals: &[(&str, &str)], max_degree: u32) -> Result<Query<T>, String> {
+    let literals = literals.iter().map(|(attribute, value)| Predicate::Literal { attribute: attribute.to_string(), value: value.to_string() });
+    Predicate::Xor(literals.collect()).compile(schema, max_degree)
+}
+
+/// For a literal on `xN` outside the schema, the entry bit N, and whether the literal wants it set
+fn raw_bit<T: PrimInt>(schema: &Schema, attribute: &str, value: &str) -> Result<Option<(u32, bool)>, String> {
+    let bit = match attribute.strip_prefix('x').and_then(|n| n.parse::<u32>().ok()) {
+        Some(bit) if schema.attribute(attribute).is_err() => bit,
+        _ => return Ok(None),
+    };
+    if bit >= T::zero().count_zeros() {
+        return Err(format!("bit {} is beyond the {} bits of an entry", attribute, T::zero().count_zeros()));
+    }
+    match value {
+        "1" | "true" => Ok(Some((bit, true))),
+        "0" | "false" => Ok(Some((bit, false))),
+        _ => Err(format!("bit {} is 1 or 0, not {:?}", attribute, value)),
+    }
+}
+
+impl Predicate {
+    /// Polynomial that is 1 on the entries matching the predicate, and 0 on the others the schema encodes
+    fn polynomial<T: PrimInt + Hash>(&self, schema: &Schema) -> Result<Polynomial<T>, String> {
+        let one = || HashMap::from([(Monomial::new(T::zero()), Scalar::ONE)]);
+        Ok(match self {
+            Predicate::Literal { attribute, value } => match raw_bit::<T>(schema, attribute, value)? {
+                Some((bit, true)) => HashMap::from([(Monomial::variable(bit as usize), Scalar::ONE)]),
+                Some((bit, false)) => add(one(), &HashMap::from([(Monomial::variable(bit as usize), Scalar::ONE)]), -Scalar::ONE),
+                None => match schema.attribute(attribute)? {
+                    (Attribute { encoding: Encoding::Binary(_), .. }, bits) => {
+                        let value = value.parse().map_err(|_| format!("attribute {}: {:?} is not an unsigned integer", attribute, value))?;
+                        within(bits, value, value, schema)
+                    },
+                    _ => HashMap::from([(Monomial::variable(schema.bit(attribute, value)? as usize), Scalar::ONE)]),
+                },
+            },
+            Predicate::Range { attribute, low, high } => match schema.attribute(attribute)? {
+                (Attribute { encoding: Encoding::Binary(_), .. }, bits) => within(bits, *low, *high, schema),
+                _ => return Err(format!("attribute {} is not binary, so has no range", attribute)),
+            },
+            Predicate::Not(p) => add(one(), &p.polynomial(schema)?, -Scalar::ONE),
+            Predicate::And(ps) => ps.iter().try_fold(one(), |product, p| Ok::<_, String>(multiply(&product, &p.polynomial(schema)?, schema)))?,
+            Predicate::Or(ps) => ps.iter().try_fold(HashMap::new(), |union, p| {
+                let q = p.polynomial(schema)?;
+                let both = multiply(&union, &q, schema);
+                Ok::<_, String>(add(add(union, &q, Scalar::ONE), &both, -Scalar::ONE))
+            })?,
+            Predicate::Xor(ps) => ps.iter().try_fold(HashMap::new(), |parity, p| {
+                let q = p.polynomial(schema)?;
+                let both = multiply(&parity, &q, schema);
+                Ok::<_, String>(add(add(parity, &q, Scalar::ONE), &both, -Scalar::from(2u8)))
+            })?,
+        })
+    }
+
+    /// Compile the predicate into the query counting the entries of a database of `schema` that match it, failing if
+    /// it needs a monomial beyond `max_degree`, which the prover does not commit to
+    pub fn compile<T: PrimInt + Hash>(&self, schema: &Schema, max_degree: u32) -> Result<Query<T>, String> {
+        query(self.polynomial::<T>(schema)?, schema, max_degree).map_err(|e| format!("predicate {}", e))
+    }
+
+    /// Whether `entry`, encoded with `schema`, matches the predicate
+    pub fn matches<T: PrimInt>(&self, schema: &Schema, entry: T) -> Result<bool, String> {
+        Ok(match self {
+            Predicate::Literal { attribute, value } => match raw_bit::<T>(schema, attribute, value)? {
+                Some((bit, set)) => (entry >> bit as usize & T::one() == T::one()) == set,
+                None => match schema.attribute(attribute)? {
+                    (Attribute { encoding: Encoding::Binary(_), .. }, _) => {
+                        let value = value.parse().map_err(|_| format!("attribute {}: {:?} is not an unsigned integer", attribute, value))?;
+                        Predicate::Range { attribute: attribute.clone(), low: value, high: value }.matches(schema, entry)?
+                    },
+                    _ => entry >> schema.bit(attribute, value)? as usize & T::one() == T::one(),
+                },
+            },
+            Predicate::Range { attribute, low, high } => {
+                let (_, bits) = schema.attribute(attribute)?;
+                let value = (bits.clone()).rev().fold(0u64, |v, bit| v << 1 | (entry >> bit as usize & T::one()).to_u64().unwrap());
+                (*low..=*high).contains(&value)
+            },
+            Predicate::Not(p) => !p.matches(schema, entry)?,
+            Predicate::And(ps) => ps.iter().try_fold(true, |all, p| Ok::<_, String>(all && p.matches(schema, entry)?))?,
+            Predicate::Or(ps) => ps.iter().try_fold(false, |any, p| Ok::<_, String>(any || p.matches(schema, entry)?))?,
+            Predicate::Xor(ps) => ps.iter().try_fold(false, |odd, p| Ok::<_, String>(odd ^ p.matches(schema, entry)?))?,
+        })
+    }
+}
+
+/// With `&` binding tighter than `^`, `^` tighter than `|`, and `!` tighter than all three
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, ps: &[Predicate], op: &str| {
+            for (i, p) in ps.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                match p {
+                    Predicate::Or(_) if op != "|" => write!(f, "({})", p)?,
+                    Predicate::Xor(_) if op == "&" => write!(f, "({})", p)?,
+                    _ => write!(f, "{}", p)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Predicate::Literal { attribute, value } if value == "true" => write!(f, "{}", attribute),
+            Predicate::Literal { attribute, value } => write!(f, "{}={}", attribute, value),
+            Predicate::Range { attribute, low: 0, high } => write!(f, "{}<={}", attribute, high),
+            Predicate::Range { attribute, low, high: u64::MAX } => write!(f, "{}>={}", attribute, low),
+            Predicate::Range { attribute, low, high } => write!(f, "{}={}..{}", attribute, low, high),
+            Predicate::Not(p) => match **p {
+                Predicate::Literal { .. } | Predicate::Range { .. } | Predicate::Not(_) => write!(f, "!{}", p),
+                _ => write!(f, "!({})", p),
+            },
+            Predicate::And(ps) => join(f, ps, "&"),
+            Predicate::Or(ps) => join(f, ps, "|"),
+            Predicate::Xor(ps) => join(f, ps, "^"),
+        }
+    }
+}
+
+/// Parse a predicate from the command line: literals `NAME=VALUE`, or just `NAME` for a true boolean attribute, and
+/// for binary attributes `NAME=LOW..HIGH` or a comparison `NAME<VALUE`, `<=`, `>`, or `>=`, combined with `!`, `&`,
+/// `^` (exclusive or), `|`, and parentheses, e.g. `smoker & (region=north | !age=70)`, `children>=2 & income=3..7`, or
+/// `smoker ^ drinker ^ region=north`
+pub fn parse_predicate(s: &str) -> Result<Predicate, String> {
+    let mut parser = Parser { tokens: tokenize(s)?, next: 0 };
+    let predicate = parser.disjunction()?;
+    match parser.tokens.get(parser.next) {
+        None => Ok(predicate),
+        Some(token) => Err(format!("unexpected {:?} in predicate {:?}", token, s)),
+    }
+}
+
+/// Characters of the operators and parentheses, which words cannot contain
+const OPERATORS: &str = "!&|^()=<>";
+
+/// Operators, parentheses, and the words between them, which literals are made of
+fn tokenize(s: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if OPERATORS.contains(c) {
+            chars.next();
+            // comparisons may be followed by =
+            match chars.peek() {
+                Some('=') if c == '<' || c == '>' => {
+                    chars.next();
+                    tokens.push(format!("{}=", c));
+                },
+                _ => tokens.push(c.to_string()),
+            }
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace() && !OPERATORS.contains(**c)) {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+    if tokens.is_empty() {
+        return Err("empty predicate".to_string());
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(word) if !word.starts_with(|c| OPERATORS.contains(c)) => {
+                self.next += 1;
+                Ok(self.tokens[self.next - 1].clone())
+            },
+            Some(token) => Err(format!("expected an attribute or value, got {:?}", token)),
+            None => Err("predicate ends early".to_string()),
+        }
+    }
+
+    fn disjunction(&mut self) -> Result<Predicate, String> {
+        let mut ps = vec![self.parity()?];
+        while self.peek() == Some("|") {
+            self.next += 1;
+            ps.push(self.parity()?);
+        }
+        Ok(if ps.len() == 1 { ps.remove(0) } else { Predicate::Or(ps) })
+    }
+
+    fn parity(&mut self) -> Result<Predicate, String> {
+        let mut ps = vec![self.conjunction()?];
+        while self.peek() == Some("^") {
+            self.next += 1;
+            ps.push(self.conjunction()?);
+        }
+        Ok(if ps.len() == 1 { ps.remove(0) } else { Predicate::Xor(ps) })
+    }
+
+    fn conjunction(&mut self) -> Result<Predicate, String> {
+        let mut ps = vec![self.negation()?];
+        while self.peek() == Some("&") {
+            self.next += 1;
+            ps.push(self.negation()?);
+        }
+        Ok(if ps.len() == 1 { ps.remove(0) } else { Predicate::And(ps) })
+    }
+
+    fn negation(&mut self) -> Result<Predicate, String> {
+        match self.peek() {
+            Some("!") => {
+                self.next += 1;
+                Ok(Predicate::Not(Box::new(self.negation()?)))
+            },
+            Some("(") => {
+                self.next += 1;
+                let p = self.disjunction()?;
+                if self.peek() != Some(")") {
+                    return Err("unclosed parenthesis in predicate".to_string());
+                }
+                self.next += 1;
+                Ok(p)
+            },
+            _ => {
+                let attribute = self.word()?;
+                let comparison = self.peek().filter(|op| ["=", "<", "<=", ">", ">="].contains(op)).map(str::to_string);
+                let Some(op) = comparison else {
+                    return Ok(Predicate::Literal { attribute, value: "true".to_string() });
+                };
+                self.next += 1;
+                let value = self.word()?;
+                let number = |v: &str| v.parse::<u64>().map_err(|_| format!("{} {} needs an unsigned integer, got {:?}", attribute, op, v));
+                Ok(match op.as_str() {
+                    "=" => match value.split_once("..") {
+                        Some((low, high)) => Predicate::Range { low: number(low)?, high: number(high)?, attribute },
+                        None => Predicate::Literal { attribute, value },
+                    },
+                    "<" => match number(&value)?.checked_sub(1) {
+                        Some(high) => Predicate::Range { attribute, low: 0, high },
+                        None => return Err(format!("{} < 0 matches no unsigned integer", attribute)),
+                    },
+                    "<=" => Predicate::Range { high: number(&value)?, attribute, low: 0 },
+                    ">" => Predicate::Range { low: number(&value)?.saturating_add(1), attribute, high: u64::MAX },
+                    _ => Predicate::Range { low: number(&value)?, attribute, high: u64::MAX },
+                })
+            },
+        }
+    }
+}
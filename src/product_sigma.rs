@@ -1,123 +1,149 @@
-/**
+/*!
  * product_sigma.rs
  * 
  * Product Sigma Protocol implementation, used as a building block for the main protocol.
  */
 
-use curve25519_dalek::{RistrettoPoint, Scalar};
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::error::{Error, Result};
+use crate::group::{Group, Ristretto};
 use crate::pedersen;
+#[cfg(feature = "arbitrary")]
+use crate::fuzzing;
 
 /// Prover state for the product sigma protocol.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Prover {
-    m_1: Scalar,
-    c_1: RistrettoPoint,
-    r_1: Scalar,
-    m_2: Scalar,
-    c_2: RistrettoPoint,
-    r_2: Scalar,
-    m_3: Scalar,
-    c_3: RistrettoPoint,
-    r_3: Scalar,
-    b_1: Scalar,
-    b_2: Scalar,
-    b_3: Scalar,
-    b_4: Scalar,
-    b_5: Scalar,
+pub struct Prover<G: Group = Ristretto> {
+    m_1: G::Scalar,
+    c_1: G::Point,
+    r_1: G::Scalar,
+    m_2: G::Scalar,
+    c_2: G::Point,
+    r_2: G::Scalar,
+    m_3: G::Scalar,
+    c_3: G::Point,
+    r_3: G::Scalar,
+    b_1: G::Scalar,
+    b_2: G::Scalar,
+    b_3: G::Scalar,
+    b_4: G::Scalar,
+    b_5: G::Scalar,
 }
 
 /// Zero-out values by default
-impl Default for Prover {
+impl<G: Group> Default for Prover<G> {
     fn default() -> Self {
         Prover {
-            m_1: Scalar::default(),
-            c_1: RistrettoPoint::default(),
-            r_1: Scalar::default(),
-            m_2: Scalar::default(),
-            c_2: RistrettoPoint::default(),
-            r_2: Scalar::default(),
-            m_3: Scalar::default(),
-            c_3: RistrettoPoint::default(),
-            r_3: Scalar::default(),
-            b_1: Scalar::default(),
-            b_2: Scalar::default(),
-            b_3: Scalar::default(),
-            b_4: Scalar::default(),
-            b_5: Scalar::default(),
+            m_1: G::Scalar::default(),
+            c_1: G::Point::default(),
+            r_1: G::Scalar::default(),
+            m_2: G::Scalar::default(),
+            c_2: G::Point::default(),
+            r_2: G::Scalar::default(),
+            m_3: G::Scalar::default(),
+            c_3: G::Point::default(),
+            r_3: G::Scalar::default(),
+            b_1: G::Scalar::default(),
+            b_2: G::Scalar::default(),
+            b_3: G::Scalar::default(),
+            b_4: G::Scalar::default(),
+            b_5: G::Scalar::default(),
         }
     }
 }
 
+/// The committed values, their openings, and the nonces would let anyone reading the prover's memory open the commitments
+impl<G: Group> Zeroize for Prover<G> {
+    fn zeroize(&mut self) {
+        for scalar in [&mut self.m_1, &mut self.r_1, &mut self.m_2, &mut self.r_2, &mut self.m_3, &mut self.r_3,
+                       &mut self.b_1, &mut self.b_2, &mut self.b_3, &mut self.b_4, &mut self.b_5] {
+            scalar.zeroize();
+        }
+    }
+}
+
+impl<G: Group> Drop for Prover<G> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<G: Group> ZeroizeOnDrop for Prover<G> {}
+
 /// Verifier state for the product sigma protocol.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Verifier {
-    c_1: RistrettoPoint,
-    c_2: RistrettoPoint,
-    c_3: RistrettoPoint,
-    e: Scalar,
-    c1_prime: RistrettoPoint,
-    c2_prime: RistrettoPoint,
-    c3_prime: RistrettoPoint,
+pub struct Verifier<G: Group = Ristretto> {
+    c_1: G::Point,
+    c_2: G::Point,
+    c_3: G::Point,
+    e: G::Scalar,
+    c1_prime: G::Point,
+    c2_prime: G::Point,
+    c3_prime: G::Point,
 }
 
 /// Zero-out values by default
-impl Default for Verifier {
+impl<G: Group> Default for Verifier<G> {
     fn default() -> Self {
         Verifier {
-            c_1: RistrettoPoint::default(),
-            c_2: RistrettoPoint::default(),
-            c_3: RistrettoPoint::default(),
-            e: Scalar::default(),
-            c1_prime: RistrettoPoint::default(),
-            c2_prime: RistrettoPoint::default(),
-            c3_prime: RistrettoPoint::default(),
+            c_1: G::Point::default(),
+            c_2: G::Point::default(),
+            c_3: G::Point::default(),
+            e: G::Scalar::default(),
+            c1_prime: G::Point::default(),
+            c2_prime: G::Point::default(),
+            c3_prime: G::Point::default(),
         }
     }
 }
 
 /// Contents of commitment message from prover.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Commitment {
-    c_1: RistrettoPoint,
-    c_2: RistrettoPoint,
-    c_3: RistrettoPoint,
-    alpha: RistrettoPoint,
-    beta: RistrettoPoint,
-    gamma: RistrettoPoint,
+pub struct Commitment<G: Group = Ristretto> {
+    c_1: G::Point,
+    c_2: G::Point,
+    c_3: G::Point,
+    alpha: G::Point,
+    beta: G::Point,
+    gamma: G::Point,
+}
+
+impl<G: Group> Commitment<G> {
+    /// Commitments to the two factors and the product, so a chain of proofs can be checked to link up
+    pub fn statement(&self) -> (G::Point, G::Point, G::Point) {
+        (self.c_1, self.c_2, self.c_3)
+    }
 }
 
 /// Contents of challenge message from verifier.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Challenge {
-    e: Scalar,
+pub struct Challenge<G: Group = Ristretto> {
+    e: G::Scalar,
 }
 
 /// Contents of response message back from prover.
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Response {
-    z_1: Scalar,
-    z_2: Scalar,
-    z_3: Scalar,
-    z_4: Scalar,
-    z_5: Scalar,
+pub struct Response<G: Group = Ristretto> {
+    z_1: G::Scalar,
+    z_2: G::Scalar,
+    z_3: G::Scalar,
+    z_4: G::Scalar,
+    z_5: G::Scalar,
 }
 
 /// (1) The prover commits to the three values m_1 * m_2 = m_3.
-pub fn commit<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams,
-                                  (m_1, c_1, r_1): (Scalar, RistrettoPoint, Scalar),
-                                  (m_2, c_2, r_2): (Scalar, RistrettoPoint, Scalar),
-                                  (m_3, c_3, r_3): (Scalar, RistrettoPoint, Scalar)) -> (Prover, Commitment) {
+pub fn commit<G: Group, T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams<G>,
+                                            (m_1, c_1, r_1): (G::Scalar, G::Point, G::Scalar),
+                                            (m_2, c_2, r_2): (G::Scalar, G::Point, G::Scalar),
+                                            (m_3, c_3, r_3): (G::Scalar, G::Point, G::Scalar)) -> (Prover<G>, Commitment<G>) {
 
     let (b_1, b_2, b_3, b_4, b_5) =
-        (Scalar::random(rng), Scalar::random(rng), Scalar::random(rng), Scalar::random(rng), Scalar::random(rng));
+        (G::random_scalar(rng), G::random_scalar(rng), G::random_scalar(rng), G::random_scalar(rng), G::random_scalar(rng));
 
-    let special_pp = pedersen::PublicParams {
-        g: c_1,
-        h: pp.h,
-    };
+    let special_pp = pedersen::PublicParams::<G>::new(c_1, pp.h);
 
     (
         Prover {
@@ -136,28 +162,32 @@ pub fn commit<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams,
 }
 
 /// (2) The verifier picks a random challenge `e`.
-pub fn challenge<T: Rng + CryptoRng>(rng: &mut T, comm_msg: &Commitment) -> (Verifier, Challenge) {
+pub fn challenge<G: Group, T: Rng + CryptoRng>(rng: &mut T, comm_msg: &Commitment<G>) -> (Verifier<G>, Challenge<G>) {
 
-    let e = Scalar::random(rng);
+    let challenge = Challenge { e: G::random_scalar(rng) };
 
     (
-        Verifier {
-            c_1: comm_msg.c_1,
-            c_2: comm_msg.c_2,
-            c_3: comm_msg.c_3,
-            e,
-            c1_prime: comm_msg.alpha + (e * comm_msg.c_1),
-            c2_prime: comm_msg.beta + (e * comm_msg.c_2),
-            c3_prime: comm_msg.gamma + (e * comm_msg.c_3),
-        },
-        Challenge {
-            e,
-        }
+        verifier_for(comm_msg, &challenge),
+        challenge
     )
 }
 
+/// (2) Verifier state for a challenge chosen elsewhere, e.g. one read back from a recorded session.
+pub fn verifier_for<G: Group>(comm_msg: &Commitment<G>, challenge: &Challenge<G>) -> Verifier<G> {
+    let e = challenge.e;
+    Verifier {
+        c_1: comm_msg.c_1,
+        c_2: comm_msg.c_2,
+        c_3: comm_msg.c_3,
+        e,
+        c1_prime: comm_msg.alpha + (e * comm_msg.c_1),
+        c2_prime: comm_msg.beta + (e * comm_msg.c_2),
+        c3_prime: comm_msg.gamma + (e * comm_msg.c_3),
+    }
+}
+
 /// (3) The prover sends the response back to the verifier based on the challenge.
-pub fn response(sigma_p: &mut Prover, challenge: &Challenge) -> Response {
+pub fn response<G: Group>(sigma_p: &mut Prover<G>, challenge: &Challenge<G>) -> Response<G> {
 
     let z_1 = sigma_p.b_1 + (challenge.e * sigma_p.m_1);
     let z_2 = sigma_p.b_2 + (challenge.e * sigma_p.r_1);
@@ -171,24 +201,47 @@ pub fn response(sigma_p: &mut Prover, challenge: &Challenge) -> Response {
 }
 
 /// (4) The verifier checks each Pederesen commitment to finish the protocol.
-pub fn verify(pp: &pedersen::PublicParams, sigma_v: &mut Verifier, response: &Response) -> bool {
-
-    let special_pp = pedersen::PublicParams {
-        g: sigma_v.c_1,
-        h: pp.h,
-    };
-
-    if !pedersen::verify(&sigma_v.c1_prime, &response.z_1, &response.z_2, pp) {
-        return false;
+pub fn verify<G: Group>(pp: &pedersen::PublicParams<G>, sigma_v: &mut Verifier<G>, response: &Response<G>) -> Result<()> {
+    if certified_dp_core::product_sigma::verify(pp, sigma_v.c_1, [sigma_v.c1_prime, sigma_v.c2_prime, sigma_v.c3_prime],
+                                                [response.z_1, response.z_2, response.z_3, response.z_4, response.z_5]) {
+        Ok(())
+    } else {
+        Err(Error::Verification("c_3 is not the product of the committed values".to_string()))
     }
+}
 
-    if !pedersen::verify(&sigma_v.c2_prime, &response.z_3, &response.z_4, pp) {
-        return false;
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Commitment {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Commitment {
+            c_1: fuzzing::point(u)?,
+            c_2: fuzzing::point(u)?,
+            c_3: fuzzing::point(u)?,
+            alpha: fuzzing::point(u)?,
+            beta: fuzzing::point(u)?,
+            gamma: fuzzing::point(u)?,
+        })
     }
+}
 
-    if !pedersen::verify(&sigma_v.c3_prime, &response.z_3, &response.z_5, &special_pp) {
-        return false;
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Challenge {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Challenge {
+            e: fuzzing::scalar(u)?,
+        })
     }
+}
 
-    return true
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Response {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Response {
+            z_1: fuzzing::scalar(u)?,
+            z_2: fuzzing::scalar(u)?,
+            z_3: fuzzing::scalar(u)?,
+            z_4: fuzzing::scalar(u)?,
+            z_5: fuzzing::scalar(u)?,
+        })
+    }
 }
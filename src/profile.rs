@@ -0,0 +1,80 @@
+/*!
+ * profile.rs
+ *
+ * Configuration profiles for the personas of a deployment. The prover executable is run by the data owner, or by a
+ * dealer that only deals the shared parameters and certified noise; the verifier executable is run by an analyst, who
+ * learns the answers to its queries, or by an auditor, who verifies everything but may only see answers encrypted to
+ * an analyst. The protocol driver refuses to run any phase or operation the party's profile does not permit.
+ */
+
+use std::fmt;
+
+use crate::driver::{Phase, Side};
+
+/// Persona a party plays in the protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Holds the database: commits to it, updates it, and answers queries
+    DataOwner,
+    /// Deals the shared parameters and noise over a committed database, but neither updates it nor answers queries
+    Dealer,
+    /// Certifies the commitments and noise, then queries the database and learns the answers
+    Analyst,
+    /// Certifies the commitments, noise, and answers without learning any answer
+    Auditor,
+}
+
+impl Profile {
+    /// Profile of each executable when none is given, permitting everything that side can do
+    pub fn default_for(side: Side) -> Profile {
+        match side {
+            Side::Prover => Profile::DataOwner,
+            Side::Verifier => Profile::Analyst,
+        }
+    }
+
+    /// Side of the protocol the profile runs
+    pub fn side(&self) -> Side {
+        match self {
+            Profile::DataOwner | Profile::Dealer => Side::Prover,
+            Profile::Analyst | Profile::Auditor => Side::Verifier,
+        }
+    }
+
+    /// Whether the profile may take part in `phase`
+    pub fn permits(&self, phase: Phase) -> bool {
+        match phase {
+            Phase::Update | Phase::Query => *self != Profile::Dealer,
+            // only an auditor can vouch for the commitments to analysts who were not in the session
+            Phase::Certify => *self != Profile::Analyst,
+            _ => true,
+        }
+    }
+
+    /// Whether the profile may learn query answers in the clear
+    pub fn learns_answers(&self) -> bool {
+        matches!(self, Profile::DataOwner | Profile::Analyst)
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::DataOwner => "data-owner",
+            Profile::Dealer => "dealer",
+            Profile::Analyst => "analyst",
+            Profile::Auditor => "auditor",
+        })
+    }
+}
+
+/// Parse a profile from the command line: data-owner, dealer, analyst, or auditor
+pub fn parse_profile(s: &str) -> Result<Profile, String> {
+    match s {
+        "data-owner" => Ok(Profile::DataOwner),
+        "dealer" => Ok(Profile::Dealer),
+        "analyst" => Ok(Profile::Analyst),
+        "auditor" => Ok(Profile::Auditor),
+        _ => Err(format!("unknown profile {:?}, expected data-owner, dealer, analyst, or auditor", s)),
+    }
+}
@@ -0,0 +1,56 @@
+/*!
+ * protocol/mod.rs
+ *
+ * The protocol's phases for each side, as public functions on that side's state, so programs other than the prover
+ * and verifier executables can embed the protocol. Sequencing the phases and synchronizing around them is left to the
+ * caller, e.g. the executables' `driver::Role` implementations. The phases report their progress through the `log`
+ * facade rather than printing it, so the caller decides where it goes.
+ *
+ * For the plain protocol (the honest and dishonest commitment phases, then the noise, then queries),
+ * `prover::ProverSession` and `verifier::VerifierSession` sequence the phases themselves. Each is typed by the last
+ * phase it completed, so e.g. answering a query before the noise is flipped does not compile.
+ */
+
+use num_traits::PrimInt;
+
+use crate::config::max_dimension;
+use crate::error::{Error, Result};
+use crate::messages::SessionParams;
+
+pub mod prover;
+pub mod verifier;
+
+/// Entries of the dishonest commitment phase proven at a time: the prover sends a window's commitments, the verifier
+/// challenges them, and the prover responds before starting on the next window, so neither side holds the sigma
+/// protocol state of more entries than this. A spot check samples from every entry, so it proves the whole database
+/// as one window.
+pub const DISHONEST_WINDOW: usize = 128;
+
+/// Noise coins of the randomness phase with a single verifier flipped at a time: the prover commits to a window's
+/// coins, the verifier flips and challenges them, and the prover responds before starting on the next window. Both
+/// sides take a checkpoint after each window the verifier accepts, which a phase cut short resumes from.
+pub const RANDOMNESS_WINDOW: u32 = 256;
+
+/// Check that a session with `params` can run over entries of type `T`: they are as wide as the session says, which
+/// the peer encodes monomials at, and hold every attribute
+pub(crate) fn check_entries<T: PrimInt>(params: &SessionParams) -> Result<()> {
+    if params.entry_width != max_dimension::<T>() {
+        return Err(Error::Config(format!("Session is for {}-bit entries, but these are {}-bit", params.entry_width, max_dimension::<T>())));
+    }
+    if params.dimension > params.entry_width {
+        return Err(Error::Config(format!("Dimension {} exceeds the {} attributes an entry holds", params.dimension, params.entry_width)));
+    }
+    Ok(())
+}
+
+/// Session that has agreed on the commitment parameters, but has not committed to the database yet
+#[derive(Debug)]
+pub struct Setup;
+
+/// Session whose commitment phases completed, but whose noise has not been flipped yet
+#[derive(Debug)]
+pub struct Committed;
+
+/// Session whose noise has been flipped, ready for queries
+#[derive(Debug)]
+pub struct NoiseReady;
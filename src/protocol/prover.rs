@@ -0,0 +1,1442 @@
+/*!
+ * protocol/prover.rs
+ *
+ * Prover side of each protocol phase: setup, the honest, dishonest and cut-and-choose commitment phases, updates, the
+ * noise coin flipping, and answering queries. Each phase runs against an already connected stream to the verifier; the
+ * prover executable only sequences them through the driver, so another program can embed the prover the same way.
+ */
+
+use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use log::info;
+use num_traits::PrimInt;
+use rand::{CryptoRng, Rng};
+use rand::rngs::OsRng;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::Neg;
+use std::time::{Duration, Instant, SystemTime};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::analyst::{self, AnswerMode};
+use crate::audit;
+use crate::cut_and_choose;
+use crate::monomial::{extract_monomials, gen_monomial_map, subtract_monomial_map, Committed, Homomorphic, Monomial, MonomialTree};
+use crate::data::Data;
+use crate::dataset::DEFAULT_DATASET;
+use crate::driver::Side;
+use crate::elgamal;
+use crate::error::{Error, Result};
+use crate::messages::{read_message, write_abort, write_message, write_to_stream, AbortReason, BatchQueryAnswerMessage, BatchQueryMessage, BeaconRoundMessage, BitSigmaCombinedChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, CheckpointMessage, CommitmentMapMessage, CommitmentPayload, ContributionCommitmentMessage, ContributionDigestsMessage, ContributionMessage, ContributionsMessage, CutAndChooseChallengeMessage, CutAndChooseCommitmentMessage, CutAndChooseResponseMessage, ElGamalQueryAnswerMessage, EncryptedQueryAnswerMessage, Framed, MonomialChallengeTree, MonomialCommitmentTree, MonomialResponseTree, NoiseProductChallenges, NoiseProductCommitments, NoiseProductResponses, ProverRandomnessComm, ProverRandomnessComms, ProverRandomnessResponse, ProverRandomnessResponses, QueryAnswerMessage, QueryMessage, ResumeRandomnessMessage, SeedContributionMessage, SeedRevealMessage, SessionParams, SetupMessage, SketchChallengeMessage, SpotCheckMessage, UpdateMessage, VerifierCheckMessage, VerifierRandomnessChallenge, VerifierRandomnessChallenges, WIRE_VERSION};
+use crate::generators;
+use crate::joint;
+use crate::mac;
+use crate::pedersen;
+use crate::policy::Policy;
+use crate::beacon::{self, Beacon};
+use crate::bit_sigma;
+use crate::bulk::{self, BulkCommitter, CpuCommitter};
+use crate::checkpoint::Checkpoint;
+use crate::cache::{self, QueryDigest};
+use crate::product_sigma;
+use crate::protocol::{self, NoiseReady, Setup};
+use crate::sensitivity;
+use crate::sketch::{self, Attribute};
+use crate::snapshot::SnapshotId;
+use crate::transport::Transport;
+
+/// Entries of the dishonest commitment phase whose sigma protocols are started in parallel before any of them is sent,
+/// so the verifier can start on the first ones while the prover works on the next
+const PARALLEL_ENTRIES: usize = 64;
+
+/// Primary prover state for the protocol execution
+#[allow(non_snake_case)]
+pub struct ProverState {
+    pub rng: OsRng,
+    /// Seed the Pedersen generators were flipped from with the first session's verifier, once it has run
+    pub seed: Option<[u8; 32]>,
+    pub pedersen_pp: pedersen::PublicParams,
+    pub dealer_b: u32,
+    pub dealer_b_comm: RistrettoPoint,
+    pub dealer_b_proof: Scalar,
+    pub final_b: u32,
+    pub final_proof: Scalar,
+    /// Each coin flipped for the next noise and its opening, until the noise mechanism combines them
+    pub noise_flips: Vec<(u32, Scalar)>,
+    /// Checkpoint of the randomness phase with a single verifier, once it starts, which it resumes from if cut short
+    pub randomness_checkpoint: Option<Checkpoint>,
+    /// Window of coins committed to but not accepted by the verifier yet
+    pub randomness_window: Option<CoinWindow>,
+    /// The noise and its opening
+    pub randomness_bit_sum: Scalar,
+    pub randomness_bit_proof: Scalar,
+    pub sigma_prover: bit_sigma::Prover,
+    pub C1: RistrettoPoint,
+    pub CPROOF: Scalar,
+    pub entry_cache: Vec<EntryCommitments>,
+    pub snapshot: SnapshotId,
+    /// Answer and opening given to each query of the session so far, given again if the query is repeated
+    pub answers: HashMap<QueryDigest, (Scalar, Scalar)>,
+    /// Openings of the certified attribute sketch's buckets, for distinct-count queries
+    pub sketch: Vec<(Scalar, RistrettoPoint, Scalar)>,
+    /// Generates the commitment phases' bit and monomial commitments in bulk
+    pub committer: Box<dyn BulkCommitter + Send>,
+
+    pub randomness_sigma_duration: Duration,
+    pub coin_flipping_and_agg_duration: Duration,
+}
+
+/// The noise bits and every opening the prover keeps between phases, down to the cached commitments to the database,
+/// are wiped once the state is dropped
+impl Zeroize for ProverState {
+    fn zeroize(&mut self) {
+        self.dealer_b.zeroize();
+        self.dealer_b_proof.zeroize();
+        self.final_b.zeroize();
+        self.final_proof.zeroize();
+        self.noise_flips.zeroize();
+        if let Some(window) = self.randomness_window.as_mut() {
+            window.zeroize();
+        }
+        self.randomness_bit_sum.zeroize();
+        self.randomness_bit_proof.zeroize();
+        self.sigma_prover.zeroize();
+        self.CPROOF.zeroize();
+        self.entry_cache.iter_mut().for_each(Zeroize::zeroize);
+        self.answers.values_mut().for_each(|(answer, proof)| {
+            answer.zeroize();
+            proof.zeroize();
+        });
+        self.answers.clear();
+        self.sketch.zeroize();
+    }
+}
+
+impl Drop for ProverState {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ProverState {}
+
+//
+// -- SETUP PHASE --
+//
+
+/// Initialize prover state; the Pedersen commitment parameters are flipped with the verifier by `prover_send_setup` in
+/// the first session, and kept so that every later session reuses them, and therefore the same cached commitments.
+pub fn prover_setup() -> ProverState {
+    ProverState {
+        rng: OsRng,
+        seed: None,
+        pedersen_pp: pedersen::PublicParams::new(RistrettoPoint::default(), RistrettoPoint::default()),
+        C1: RistrettoPoint::default(),
+        CPROOF: Scalar::from(0u32),
+        dealer_b: 0,
+        dealer_b_comm: RistrettoPoint::default(),
+        dealer_b_proof: Scalar::default(),
+        final_b: 0,
+        final_proof: Scalar::default(),
+        noise_flips: Vec::new(),
+        randomness_checkpoint: None,
+        randomness_window: None,
+        randomness_bit_sum: Scalar::default(),
+        randomness_bit_proof: Scalar::default(),
+        sigma_prover: bit_sigma::Prover::default(),
+        entry_cache: Vec::new(),
+        snapshot: SnapshotId::default(),
+        answers: HashMap::new(),
+        sketch: Vec::new(),
+        committer: Box::new(CpuCommitter),
+
+        randomness_sigma_duration: Duration::from_secs(0),
+        coin_flipping_and_agg_duration: Duration::from_secs(0),
+    }
+}
+
+/// State for a session run concurrently with the sessions of `state`, on what they committed: the generators, the
+/// committed snapshot and sketch, and the current noise, which a resumed session answers on. The fork has no cached
+/// entry commitments, so it cannot commit to the database again, and no answers given yet.
+pub fn prover_fork(state: &ProverState) -> ProverState {
+    ProverState {
+        rng: OsRng,
+        seed: state.seed,
+        pedersen_pp: state.pedersen_pp.clone(),
+        C1: state.C1,
+        CPROOF: state.CPROOF,
+        dealer_b: state.dealer_b,
+        dealer_b_comm: state.dealer_b_comm,
+        dealer_b_proof: state.dealer_b_proof,
+        final_b: state.final_b,
+        final_proof: state.final_proof,
+        noise_flips: state.noise_flips.clone(),
+        randomness_checkpoint: None,
+        randomness_window: None,
+        randomness_bit_sum: state.randomness_bit_sum,
+        randomness_bit_proof: state.randomness_bit_proof,
+        sigma_prover: bit_sigma::Prover::default(),
+        entry_cache: Vec::new(),
+        snapshot: state.snapshot,
+        answers: HashMap::new(),
+        sketch: state.sketch.clone(),
+        committer: Box::new(CpuCommitter),
+
+        randomness_sigma_duration: Duration::from_secs(0),
+        coin_flipping_and_agg_duration: Duration::from_secs(0),
+    }
+}
+
+/// Setup of a session with `params` in budget epoch `epoch`, on the generators an earlier verifier flipped, e.g. for
+/// the session's co-verifiers
+pub fn prover_setup_message(state: &ProverState, params: &SessionParams, epoch: u64) -> SetupMessage {
+    SetupMessage {
+        seed: state.seed.unwrap_or_default(),
+        epoch,
+        version: WIRE_VERSION,
+        params: Some(*params),
+        seed_commitment: None,
+        nonce: OsRng.gen(),
+        session: None,
+    }
+}
+
+/// Send the setup to the verifier at the start of a session: the protocol version and the session's parameters, which
+/// the verifier checks against its own before anything is committed, and the seed of the Pedersen generators. The first
+/// session flips the seed with its verifier instead, which later sessions reuse.
+pub fn prover_send_setup<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, params: &SessionParams, epoch: u64) -> Result<()> {
+    if state.seed.is_some() {
+        write_message(stream, &prover_setup_message(state, params, epoch))?;
+    } else {
+        let half = generators::half(&mut state.rng);
+        write_message(
+            stream, &SetupMessage {
+                seed_commitment: Some(generators::commit(&half)),
+                ..prover_setup_message(state, params, epoch)
+            }
+        )?;
+        let contribution: SeedContributionMessage = read_message(stream)?;
+        write_message(stream, &SeedRevealMessage { seed: half })?;
+
+        let seed = generators::combine(&half, &contribution.seed);
+        state.pedersen_pp = generators::params(&seed);
+        state.C1 = pedersen::commit_with_r(&Scalar::from(1u32), &state.CPROOF, &state.pedersen_pp);
+        state.seed = Some(seed);
+    }
+
+    // each session answers its own queries, so only its repeats are answered from the cache
+    state.answers.clear();
+    state.randomness_checkpoint = None;
+    state.randomness_window = None;
+    state.randomness_sigma_duration = Duration::from_secs(0);
+    state.coin_flipping_and_agg_duration = Duration::from_secs(0);
+    Ok(())
+}
+
+//
+// -- COMMITMENT PHASE --
+//
+
+/// For a given monomial, calculate count of entries that satisfy the monomial condition
+fn calculate_monomial_sum<T: PrimInt>(indices: T, data: &[T]) -> Scalar {
+    let inv_indices = !indices;
+    let mut cnt: u32 = 0;
+    for entry in data {
+        if (*entry | inv_indices).count_zeros() == 0 {
+            cnt += 1;
+        }
+    }
+    Scalar::from(cnt)
+}
+
+/// Recursive helper function to generate monomial sums for all possible monomials, limited by max monomial degree and entry dimension
+fn generate_monomial_sums_helper<T: PrimInt + Hash>(indices: T, current_idx: T, data: &[T], monomial_map: &mut HashMap<Monomial<T>, Scalar>,
+                                                    dimension: u32, max_degree: u32) {
+
+    // we've recursively set bits for the bitwidth of the database entry type OR the max configured degree (number of set bits)
+    if current_idx.to_u32().unwrap() == dimension || indices.count_ones() == max_degree {
+        // skip the empty monomial
+        if indices.count_ones() > 0 {
+            let sum = calculate_monomial_sum(indices, data);
+            monomial_map.insert(Monomial::new(indices), sum);
+        }
+        return;
+    }
+
+    // set bit at current index to a 0 or 1 and recurse
+    generate_monomial_sums_helper(
+        indices, current_idx + T::one(), data, monomial_map, dimension, max_degree);
+    generate_monomial_sums_helper(
+        indices | (T::one() << current_idx.to_usize().unwrap()), current_idx + T::one(), data, monomial_map, dimension, max_degree);
+}
+
+/// Generate monomial sums for all possible monomials, limited by max monomial degree and entry dimension
+fn generate_monomial_sums<T: PrimInt + Hash>(data: &[T], dimension: u32, max_degree: u32) -> HashMap<Monomial<T>, Scalar> {
+    let mut map = HashMap::new();
+    generate_monomial_sums_helper(T::zero(), T::zero(), data, &mut map, dimension, max_degree);
+    map
+}
+
+/// Honest commitment phase: generate monomial sums for all possible monomials and commit to each. Send the commitments to the verifier.
+pub fn prover_honest_commitment_phase<T: PrimInt + Hash + Serialize, S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, database: &mut Data<T>, dimension: u32, max_degree: u32) -> Result<()> {
+
+    let mut m = CommitmentMapMessage::<T> {
+        commitment_map: HashMap::new(),
+        snapshot: SnapshotId::default(),
+        entries: database.entries.len() as u64,
+    };
+
+    let (monomials, monomial_sums): (Vec<Monomial<T>>, Vec<Scalar>) = generate_monomial_sums(&database.entries, dimension, max_degree).into_iter().unzip();
+    let openings = bulk::commit_all(state.committer.as_ref(), &mut state.rng, &state.pedersen_pp, monomial_sums);
+    for (monomial, opening) in monomials.into_iter().zip(openings) {
+        database.commitments.insert(monomial, opening);
+        m.commitment_map.insert(monomial, opening.1);
+    }
+    m.snapshot = database.snapshot();
+
+    write_message(
+        stream, &m
+    )
+}
+
+/// Prover's payload in a monomial tree: the opened commitment to the node's partial product, and the product sigma
+/// proof that it is the product of its parent's and the bit it multiplies in. Shared across monomials with a common prefix,
+/// to avoid recomputing partial product proofs for each different monomial.
+pub struct ProverNode {
+    pub commitment: Option<(Scalar, RistrettoPoint, Scalar)>,
+    pub product_sigma_prover: Option<product_sigma::Prover>,
+}
+
+impl ProverNode {
+    fn root() -> Self {
+        ProverNode { commitment: None, product_sigma_prover: None }
+    }
+}
+
+impl Zeroize for ProverNode {
+    fn zeroize(&mut self) {
+        self.commitment.zeroize();
+        self.product_sigma_prover.zeroize();
+    }
+}
+
+impl Drop for ProverNode {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ProverNode {}
+
+impl Committed for ProverNode {
+    type Value = (Scalar, RistrettoPoint, Scalar);
+
+    fn commitment(&self) -> Option<Self::Value> {
+        self.commitment
+    }
+}
+
+/// Commit to every bit of each of `entries`, in one batch through the state's committer
+fn commit_entry_bits<T: PrimInt>(state: &mut ProverState, entries: &[T], dimension: u32) -> Vec<Vec<(Scalar, RistrettoPoint, Scalar)>> {
+    let bits: Vec<Scalar> = entries.iter()
+        .flat_map(|&entry| (0..dimension).map(move |i| Scalar::from(((entry >> i as usize) & T::one() == T::one()) as u32)))
+        .collect();
+    let openings = bulk::commit_all(state.committer.as_ref(), &mut state.rng, &state.pedersen_pp, bits);
+    openings.chunks(dimension as usize).map(<[_]>::to_vec).collect()
+}
+
+/// Statement commitments for one database entry, kept after it is first proven so that later sessions (e.g. other
+/// auditors) see the same committed database. Only the Pedersen commitments are reused: sigma protocol commitments are
+/// regenerated every session, since answering two challenges for the same sigma commitment would reveal the witness.
+pub struct EntryCommitments {
+    bits: Vec<(Scalar, RistrettoPoint, Scalar)>,
+    tree: Option<MonomialTree<ProverNode>>,
+}
+
+/// The tree's nodes wipe themselves as they drop
+impl Zeroize for EntryCommitments {
+    fn zeroize(&mut self) {
+        self.bits.zeroize();
+        self.tree = None;
+    }
+}
+
+impl Drop for EntryCommitments {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for EntryCommitments {}
+
+/// Generate a tree of partial monomial sigma proofs for the dishonest commitment phase, along with the commitments sent to
+/// the verifier, reusing the product commitments of the cached tree (of the same shape, from an earlier session) if given
+fn gen_monomial_tree<R: Rng + CryptoRng>(rng: &mut R, pp: &pedersen::PublicParams, entry_bit_commitments: &[(Scalar, RistrettoPoint, Scalar)],
+                                         cached: Option<&MonomialTree<ProverNode>>, dimension: usize, max_degree: usize) -> (MonomialTree<ProverNode>, MonomialCommitmentTree) {
+
+    let root = (ProverNode::root(), CommitmentPayload { commitment: None, product_sigma_commitment: None });
+    // nodes are created in preorder, so this tracks the id of the node being created, which matches the cached tree
+    let mut id = 0;
+
+    MonomialTree::build(dimension, max_degree, root, |(parent, _), i| {
+        id += 1;
+        match parent.commitment {
+            None => (
+                ProverNode { commitment: Some(entry_bit_commitments[i]), product_sigma_prover: None },
+                CommitmentPayload { commitment: Some(entry_bit_commitments[i].1), product_sigma_commitment: None },
+            ),
+            Some((m_1, c_1, r_1)) => {
+                let (m_2, c_2, r_2) = entry_bit_commitments[i];
+                let (m_3, c_3, r_3) = match cached.and_then(|t| t[id].commitment) {
+                    Some(c) => c,
+                    None => {
+                        let m_3 = m_1 * m_2;
+                        let (c_3, r_3) = pedersen::commit(rng, &m_3, pp);
+                        (m_3, c_3, r_3)
+                    }
+                };
+
+                let (prover, commitment) = product_sigma::commit(rng, pp, (m_1, c_1, r_1), (m_2, c_2, r_2), (m_3, c_3, r_3));
+
+                (
+                    ProverNode { commitment: Some((m_3, c_3, r_3)), product_sigma_prover: Some(prover) },
+                    CommitmentPayload { commitment: Some(c_3), product_sigma_commitment: Some(commitment) },
+                )
+            }
+        }
+    }).unzip()
+}
+
+/// Sigma protocol state and messages of one database entry in the dishonest commitment phase
+struct EntrySigmas {
+    /// (bit, commitment, randomness) of each of the entry's bits
+    commitments: Vec<(Scalar, RistrettoPoint, Scalar)>,
+    bit_provers: Vec<bit_sigma::Prover>,
+    bit_commitments: Vec<bit_sigma::Commitment>,
+    /// Product sigma provers and the commitments sent for them, unless the dimension is 1
+    tree: Option<(MonomialTree<ProverNode>, MonomialCommitmentTree)>,
+}
+
+/// Start the bit sigma protocols for the given bits of an entry and their commitments, and its monomial tree if the
+/// dimension is over 1, reusing the product commitments of the cached tree if given
+fn commit_entry<R: Rng + CryptoRng>(rng: &mut R, pp: &pedersen::PublicParams, bits: &[u32], commitments: &[(Scalar, RistrettoPoint, Scalar)],
+                                    cached_tree: Option<&MonomialTree<ProverNode>>, max_degree: u32) -> EntrySigmas {
+    let commitments: Vec<_> = bits.iter().zip(commitments).map(|(&bit, &(_, comm, proof))| (Scalar::from(bit), comm, proof)).collect();
+    let (bit_provers, bit_commitments) = commitments.iter().zip(bits)
+        .map(|(&(_, comm, proof), &bit)| bit_sigma::commit(rng, pp, bit, comm, proof))
+        .unzip();
+
+    let tree = (bits.len() > 1).then(|| gen_monomial_tree(rng, pp, &commitments, cached_tree, bits.len(), max_degree as usize));
+    EntrySigmas { commitments, bit_provers, bit_commitments, tree }
+}
+
+/// Given a matching prover sigma protocol state and challenge tree, generate the response tree by advancing the sigma protocol at each node.
+/// Returns None if the challenge tree does not have the same shape as the prover tree.
+fn gen_response_tree(prover_tree: &mut MonomialTree<ProverNode>, challenge_tree: &MonomialChallengeTree) -> Option<MonomialResponseTree> {
+    prover_tree.zip_map(challenge_tree, |prover_node, challenge| {
+        match (challenge, prover_node.product_sigma_prover.as_mut()) {
+            (None, None) => Some(None),
+            (Some(c), Some(p)) => Some(Some(product_sigma::response(p, c))),
+            _ => None,
+        }
+    })
+}
+
+/// Dishonest commitment phase: compute the result of a set of all bit and product sigma protocols for database entries between us and the verifier, then aggregate into a <monomial -> commitment> map.
+/// Entries are proven a window at a time (`protocol::DISHONEST_WINDOW`), so only one window's sigma protocol state is held at once.
+/// In spot-check mode the verifier picks which entries to challenge once all commitments are sent, and only those entries are proven.
+pub fn prover_dishonest_commitment_phase<T: PrimInt + Hash + Serialize, S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, database: &mut Data<T>, dimension: u32, max_degree: u32, spot_check: Option<f64>) -> Result<()> {
+
+    // Monomial commitments summed over the entries proven so far; if d=1, we skip product proofs and sum the bits
+    let mut monomial_commitments: HashMap<Monomial<T>, (Scalar, RistrettoPoint, Scalar)> = HashMap::new();
+    if dimension == 1 {
+        monomial_commitments.insert(Monomial::variable(0), (Scalar::ZERO, RistrettoPoint::default(), Scalar::ZERO));
+    }
+    // Bit and product commitments per database entry, cached for later sessions
+    let mut entry_cache: Vec<EntryCommitments> = Vec::with_capacity(database.entries.len());
+
+    // commitments from an earlier session, if any; the bits of the entries after them are committed in one batch
+    let cache = std::mem::take(&mut state.entry_cache);
+    let mut fresh = commit_entry_bits(state, &database.entries[cache.len().min(database.entries.len())..], dimension).into_iter();
+    let (rng, pp) = (state.rng, &state.pedersen_pp);
+
+    // a spot check is sampled from every entry, so its window holds all of them
+    let window = match spot_check {
+        Some(_) => database.entries.len().max(1),
+        None => protocol::DISHONEST_WINDOW,
+    };
+
+    for (w, window_entries) in database.entries.chunks(window).enumerate() {
+        stream.check_deadline()?;
+        // Per-database entry bit sigma protocols of this window
+        let mut db_bit_sigma_provers: Vec<Vec<bit_sigma::Prover>> = Vec::new();
+        // Forest of monomial trees per-database element of this window
+        let mut monomial_prover_trees: Vec<MonomialTree<ProverNode>> = Vec::new();
+        // Bit commitments per database entry of this window
+        let mut db_bit_commitments: Vec<Vec<(Scalar, RistrettoPoint, Scalar)>> = Vec::new();
+
+        for (chunk, entries) in window_entries.chunks(PARALLEL_ENTRIES).enumerate() {
+            // each entry's bits and commitments, either cached or fresh, in order
+            let inputs: Vec<(Vec<u32>, Vec<_>, Option<&EntryCommitments>)> = entries.iter().enumerate().map(|(i, &entry)| {
+                let bits = (0..dimension).map(|j| ((entry >> j as usize) & T::one() == T::one()) as u32).collect();
+                match cache.get(w * window + chunk * PARALLEL_ENTRIES + i) {
+                    Some(c) => (bits, c.bits.clone(), Some(c)),
+                    None => (bits, fresh.next().expect("every entry without cached commitments has fresh ones"), None),
+                }
+            }).collect();
+
+            // the entries' sigma protocols are independent, so they start in parallel and are sent in order
+            let sigmas: Vec<EntrySigmas> = inputs.par_iter()
+                .map(|(bits, commitments, cached)| {
+                    commit_entry(&mut { rng }, pp, bits, commitments, cached.and_then(|c| c.tree.as_ref()), max_degree)
+                })
+                .collect();
+
+            for entry in sigmas {
+                db_bit_sigma_provers.push(entry.bit_provers);
+                db_bit_commitments.push(entry.commitments);
+
+                // send the entry bit sigma commitments to the verifier
+                write_message(
+                    stream, &BitSigmaCommitmentMessage {
+                        commitments: entry.bit_commitments
+                    }
+                )?;
+
+                if let Some((entry_prover_tree, entry_commitment_tree)) = entry.tree {
+                    monomial_prover_trees.push(entry_prover_tree);
+
+                    // send entry monomial tree to the verifier
+                    write_message(
+                        stream, &entry_commitment_tree
+                    )?;
+                }
+            }
+        }
+
+        // indices into the window; a spot check's window is the whole database
+        let audited: Vec<usize> = if let Some(rate) = spot_check {
+            let spot_check_m: SpotCheckMessage = read_message(stream)?;
+
+            let db_size = database.entries.len() as u32;
+            if !audit::is_valid_sample(&spot_check_m.entries, db_size) {
+                return Err(Error::Protocol("Spot check entries are not increasing indices into the database".to_string()));
+            }
+            if spot_check_m.entries.len() as u32 != audit::sample_size(db_size, rate) {
+                return Err(Error::Protocol(format!("Spot check audits {} entries, expected {} at rate {}", spot_check_m.entries.len(), audit::sample_size(db_size, rate), rate)));
+            }
+            info!("spot check: {}", audit::describe(db_size, spot_check_m.entries.len() as u32));
+            spot_check_m.entries.iter().map(|&i| i as usize).collect()
+        } else {
+            (0..window_entries.len()).collect()
+        };
+
+        let mut response_messages = Vec::new();
+
+        for i in audited {
+            // nothing of the window is sent until every challenge for it has been read and checked, so a malformed
+            // challenge leaks no responses of its window
+            let challenge_m: BitSigmaCombinedChallengeMessage = read_message(stream)?;
+
+            let mut entry_responses: Vec<bit_sigma::Response> = Vec::new();
+            for (bit_idx, m) in bit_sigma::expand_challenge(&challenge_m.challenge, dimension as usize).iter().enumerate() {
+                let response = bit_sigma::response(&mut db_bit_sigma_provers[i][bit_idx], m);
+                entry_responses.push(response);
+            }
+
+            let resp_mesg = stream.wire_format().encode(&BitSigmaResponseMessage {
+                responses: entry_responses
+            });
+            response_messages.push(resp_mesg);
+
+            if dimension == 1 {
+                continue;
+            }
+
+            let monomial_challenge_m: MonomialChallengeTree = read_message(stream)?;
+
+            let response_tree = match gen_response_tree(&mut monomial_prover_trees[i], &monomial_challenge_m) {
+                Some(t) => t,
+                None => {
+                    return Err(Error::Protocol("Challenge tree does not match the product sigma prover tree".to_string()));
+                }
+            };
+
+            let resp_root = stream.wire_format().encode(&response_tree);
+            response_messages.push(resp_root);
+        }
+
+        for m in response_messages {
+            write_to_stream(stream, &m)?;
+        }
+
+        if dimension == 1 {
+            let total = monomial_commitments.get_mut(&Monomial::variable(0)).unwrap();
+            for bits in &db_bit_commitments {
+                *total = total.combine(bits[0]);
+            }
+        } else {
+            gen_monomial_map(&monomial_prover_trees, &mut monomial_commitments);
+        }
+
+        // only the commitments are reused, so the spent product sigma provers are dropped, wiping them
+        let mut trees = monomial_prover_trees.iter()
+            .map(|t| t.map(|n| ProverNode { commitment: n.commitment, product_sigma_prover: None }));
+        entry_cache.extend(db_bit_commitments.into_iter().map(|bits| EntryCommitments { bits, tree: trees.next() }));
+    }
+
+    let check_m: VerifierCheckMessage = read_message(stream)?;
+
+    if !check_m.success {
+        return Err(Error::Verification("Verifier rejected the commitment phase".to_string()));
+    }
+    info!("check successful!");
+
+    if dimension == 1 {
+        database.commitments.extend(monomial_commitments);
+    } else {
+        for (k, v) in monomial_commitments {
+            database.commitments.entry(k)
+                .and_modify(|c| *c = c.combine(v))
+                .or_insert(v);
+        }
+    }
+    state.entry_cache = entry_cache;
+
+    Ok(())
+}
+
+/// Sketch phase, after the dishonest commitment phase: commit to the sketch of the attribute over the bits of the
+/// entries just certified, prove it, and keep its buckets for distinct-count queries.
+pub fn prover_sketch_phase<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, attribute: &Attribute, dimension: u32) -> Result<()> {
+    if let Some(j) = attribute.bits.iter().find(|&&j| j >= dimension as usize) {
+        return Err(Error::Config(format!("Distinct attribute bit {} is beyond the dimension {}", j, dimension)));
+    }
+    if state.entry_cache.is_empty() {
+        return Err(Error::Config("No entries to sketch".to_string()));
+    }
+
+    let rows: Vec<Vec<(Scalar, RistrettoPoint, Scalar)>> = state.entry_cache.iter()
+        .map(|entry| attribute.bits.iter().map(|&j| entry.bits[j]).collect())
+        .collect();
+    let (mut sketch_prover, commitment_m) = sketch::commit(&mut state.rng, &state.pedersen_pp, &rows);
+    write_message(stream, &commitment_m)?;
+
+    let challenge_m: SketchChallengeMessage = read_message(stream)?;
+    let Some(response_m) = sketch::response(&mut sketch_prover, &challenge_m) else {
+        return Err(Error::Protocol("Sketch challenge does not match the committed sketch".to_string()));
+    };
+    write_message(stream, &response_m)?;
+
+    let check_m: VerifierCheckMessage = read_message(stream)?;
+    if !check_m.success {
+        return Err(Error::Verification("Verifier rejected the sketch".to_string()));
+    }
+
+    state.sketch = sketch_prover.buckets();
+    info!("sketch of {} certified, {} buckets", attribute, state.sketch.len());
+    Ok(())
+}
+
+/// Generate the tree of partial monomial commitments for cut-and-choose mode; no product sigma proofs are needed
+fn gen_commitment_tree(state: &mut ProverState, entry_bit_commitments: &[(Scalar, RistrettoPoint, Scalar)], dimension: usize, max_degree: usize) -> MonomialTree<ProverNode> {
+    MonomialTree::build(dimension, max_degree, ProverNode::root(), |parent, i| {
+        let commitment = match parent.commitment {
+            None => entry_bit_commitments[i],
+            Some((m_1, _, _)) => {
+                let m_3 = m_1 * entry_bit_commitments[i].0;
+                let (c_3, r_3) = pedersen::commit(&mut state.rng, &m_3, &state.pedersen_pp);
+                (m_3, c_3, r_3)
+            }
+        };
+        ProverNode { commitment: Some(commitment), product_sigma_prover: None }
+    })
+}
+
+/// Cut-and-choose commitment phase: commit to each entry's bits and monomial tree along with several encodings of
+/// multiplication triples, open every encoding except the one the verifier keeps, and prove the entry's products with it.
+pub fn prover_cut_and_choose_phase<T: PrimInt + Hash + Serialize, S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, database: &mut Data<T>, dimension: u32, max_degree: u32, encodings: u32) -> Result<()> {
+
+    let mut db_bit_commitments: Vec<Vec<(Scalar, RistrettoPoint, Scalar)>> = Vec::new();
+    let mut monomial_prover_trees: Vec<MonomialTree<ProverNode>> = Vec::new();
+    // per entry: the triples of each encoding, one per multiplication
+    let mut db_triples: Vec<Vec<Vec<cut_and_choose::Triple>>> = Vec::new();
+
+    // commitments from an earlier session, if any; the bits of the entries after them are committed in one batch
+    let cache = std::mem::take(&mut state.entry_cache);
+    let mut fresh = commit_entry_bits(state, &database.entries[cache.len().min(database.entries.len())..], dimension).into_iter();
+    let mut cache = cache.into_iter();
+
+    for _ in &database.entries {
+        let cached = cache.next();
+
+        let bits: Vec<(Scalar, RistrettoPoint, Scalar)> = match &cached {
+            Some(c) => c.bits.clone(),
+            None => fresh.next().unwrap(),
+        };
+
+        let tree = match cached.and_then(|mut c| c.tree.take()) {
+            Some(t) => t,
+            None => gen_commitment_tree(state, &bits, dimension as usize, max_degree as usize),
+        };
+
+        let num_mults = cut_and_choose::multiplications(&bits, &tree).map_or(0, |m| m.len());
+        let mut entry_triples = Vec::new();
+        let mut entry_triple_commitments = Vec::new();
+        for _ in 0..encodings {
+            let (triples, commitments): (Vec<_>, Vec<_>) = (0..num_mults)
+                .map(|_| cut_and_choose::triple(&mut state.rng, &state.pedersen_pp))
+                .unzip();
+            entry_triples.push(triples);
+            entry_triple_commitments.push(commitments);
+        }
+
+        write_message(
+            stream, &CutAndChooseCommitmentMessage {
+                bit_commitments: bits.iter().map(|b| b.1).collect(),
+                // product sigma proofs are not used in this mode
+                tree: tree.map(|n| CommitmentPayload { commitment: n.commitment.map(|(_, c, _)| c), product_sigma_commitment: None }),
+                encodings: entry_triple_commitments,
+            }
+        )?;
+
+        db_bit_commitments.push(bits);
+        monomial_prover_trees.push(tree);
+        db_triples.push(entry_triples);
+    }
+
+    let challenge_m: CutAndChooseChallengeMessage = read_message(stream)?;
+    if challenge_m.kept.len() != database.entries.len() {
+        return Err(Error::Protocol(format!("Cut-and-choose challenge keeps {} encodings, expected one per entry ({})", challenge_m.kept.len(), database.entries.len())));
+    }
+    if challenge_m.kept.iter().any(|&k| k >= encodings) {
+        return Err(Error::Protocol(format!("Cut-and-choose challenge keeps an encoding outside of 0..{}", encodings)));
+    }
+
+    let mut response_messages = Vec::new();
+
+    for (e, triples) in db_triples.into_iter().enumerate() {
+        let kept = challenge_m.kept[e] as usize;
+        let mults = cut_and_choose::multiplications(&db_bit_commitments[e], &monomial_prover_trees[e]).unwrap_or_default();
+
+        let responses = mults.iter().zip(&triples[kept])
+            .map(|(&(x, y, z), triple)| cut_and_choose::respond(triple, x, y, z))
+            .collect();
+        let openings = triples.into_iter().enumerate()
+            .filter(|(k, _)| *k != kept)
+            .map(|(_, t)| t)
+            .collect();
+
+        response_messages.push(stream.wire_format().encode(&CutAndChooseResponseMessage {
+            openings,
+            responses,
+        }));
+    }
+
+    for m in response_messages {
+        write_to_stream(stream, &m)?;
+    }
+
+    let check_m: VerifierCheckMessage = read_message(stream)?;
+
+    if !check_m.success {
+        return Err(Error::Verification("Verifier rejected the commitment phase".to_string()));
+    }
+    info!("check successful!");
+
+    gen_monomial_map(&monomial_prover_trees, &mut database.commitments);
+
+    let mut trees = monomial_prover_trees.into_iter();
+    state.entry_cache = db_bit_commitments.into_iter()
+        .map(|bits| EntryCommitments { bits, tree: trees.next() })
+        .collect();
+
+    Ok(())
+}
+
+//
+// -- UPDATE PHASE --
+//
+
+/// Monomial commitments one database entry contributes to the commitment map, from its cached commitments
+fn entry_monomial_map<T: PrimInt + Hash>(entry: &EntryCommitments) -> HashMap<Monomial<T>, (Scalar, RistrettoPoint, Scalar)> {
+    let mut map = HashMap::new();
+    match &entry.tree {
+        Some(tree) => extract_monomials(tree, &mut map),
+        // with d=1 there is no tree, and the entry's only monomial is its bit
+        None => { map.insert(Monomial::variable(0), entry.bits[0]); },
+    }
+    map
+}
+
+/// Update phase: move the commitment map to a new version of the database. The added rows are proven as in the dishonest
+/// commitment phase and their contributions added to the map; the removed entries' contributions, already certified,
+/// are subtracted from it. Entries that did not change are not proven again.
+pub fn prover_update_phase<T: PrimInt + Hash + Serialize, S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, database: &mut Data<T>, added: Vec<T>, removed: Vec<u32>, dimension: u32, max_degree: u32) -> Result<()> {
+
+    if state.entry_cache.len() != database.entries.len() {
+        return Err(Error::Config("Updates need the per-entry commitments of the dishonest commitment phase or spot-check mode".to_string()));
+    }
+
+    write_message(
+        stream, &UpdateMessage {
+            removed: removed.clone(),
+            added: added.len() as u32,
+        }
+    )?;
+
+    // the added rows are committed on their own, so the cache of the current entries is set aside
+    let mut cache = std::mem::take(&mut state.entry_cache);
+    let mut additions = Data { entries: added, commitments: HashMap::new() };
+    prover_dishonest_commitment_phase(state, stream, &mut additions, dimension, max_degree, None)?;
+
+    for &i in removed.iter().rev() {
+        let entry = cache.remove(i as usize);
+        subtract_monomial_map(&mut database.commitments, &entry_monomial_map(&entry));
+        database.entries.remove(i as usize);
+    }
+    for (&monomial, &c) in &additions.commitments {
+        database.commitments.entry(monomial)
+            .and_modify(|total| *total = total.combine(c))
+            .or_insert(c);
+    }
+    database.entries.append(&mut additions.entries);
+
+    cache.append(&mut state.entry_cache);
+    state.entry_cache = cache;
+    Ok(())
+}
+
+//
+// -- RANDOMNESS PHASE --
+//
+
+/// Committed noise coin set aside until it is flipped: the bit, its commitment and opening, and its bit sigma prover
+type Coin = (u32, RistrettoPoint, Scalar, bit_sigma::Prover);
+
+/// Window of noise coins of the randomness phase with a single verifier, from when the prover commits to them until the
+/// verifier accepts them
+pub enum CoinWindow {
+    /// Committed to, but not challenged yet
+    Committed(Vec<Coin>),
+    /// Answered: the challenges answered, in their bincode encoding, each flipped coin and its opening, and the
+    /// responses sent
+    Answered { challenges: Vec<u8>, flips: Vec<(u32, Scalar)>, responses: ProverRandomnessResponses },
+}
+
+/// The coins and openings are wiped, the responses sent being public
+impl Zeroize for CoinWindow {
+    fn zeroize(&mut self) {
+        match self {
+            CoinWindow::Committed(coins) => coins.iter_mut().for_each(|(dealer_b, _, dealer_b_proof, sigma_prover)| {
+                dealer_b.zeroize();
+                dealer_b_proof.zeroize();
+                sigma_prover.zeroize();
+            }),
+            CoinWindow::Answered { flips, .. } => flips.zeroize(),
+        }
+    }
+}
+
+/// Prover randomness phase: generate a random bit and commit to it, returning the bit sigma commitment for the verifiers
+fn prover_commit_coin(state: &mut ProverState) -> bit_sigma::Commitment {
+
+    let dealer_b: u32 = state.rng.gen_range(0..2);
+    let (dealer_b_comm, dealer_b_proof) =
+        pedersen::commit(&mut state.rng, &Scalar::from(dealer_b), &state.pedersen_pp);
+
+    let _start = Instant::now();
+    let (sigma_prover, sigma_commitment) = 
+        bit_sigma::commit(&mut state.rng, &state.pedersen_pp, dealer_b, dealer_b_comm, dealer_b_proof);
+    state.randomness_sigma_duration += _start.elapsed();
+
+    state.sigma_prover = sigma_prover;
+    state.dealer_b = dealer_b;
+    state.dealer_b_comm = dealer_b_comm;
+    state.dealer_b_proof = dealer_b_proof;
+    sigma_commitment
+}
+
+/// Prover randomness phase: generate a random bit and commit to it. Send the commitment to every verifier.
+pub fn prover_randomness_phase_comm<S: Transport>(state: &mut ProverState, streams: &mut [&mut Framed<S>]) -> Result<()> {
+    let comm_m = ProverRandomnessComm {
+        commitment: prover_commit_coin(state)
+    };
+    streams.iter_mut().try_for_each(|stream| write_message(stream, &comm_m))
+}
+
+/// Prover randomness phase: commit to `n` coins at once, setting each aside until it is flipped
+fn prover_commit_coins(state: &mut ProverState, n: u32) -> (Vec<Coin>, ProverRandomnessComms) {
+    let mut coins = Vec::with_capacity(n as usize);
+    let mut commitments = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+        commitments.push(prover_commit_coin(state));
+        coins.push((state.dealer_b, state.dealer_b_comm, state.dealer_b_proof, std::mem::take(&mut state.sigma_prover)));
+    }
+    (coins, ProverRandomnessComms { commitments })
+}
+
+/// Prover randomness phase: flip each of the set-aside `coins` by the verifier's coin and answer its challenge, adding
+/// it to the noise, and send every response in one message. Then wait for the verifier to accept them all.
+fn prover_flip_coins<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, coins: Vec<Coin>, flips: Vec<(u32, bit_sigma::Challenge)>) -> Result<()> {
+    let mut responses = Vec::with_capacity(coins.len());
+    for ((dealer_b, dealer_b_comm, dealer_b_proof, sigma_prover), (player_b, sigma_challenge)) in coins.into_iter().zip(flips) {
+        state.dealer_b = dealer_b;
+        state.dealer_b_comm = dealer_b_comm;
+        state.dealer_b_proof = dealer_b_proof;
+        state.sigma_prover = sigma_prover;
+        responses.push(prover_randomness_phase_flip(state, player_b, &sigma_challenge));
+        prover_randomness_phase_aggregate(state);
+    }
+    write_message(stream, &ProverRandomnessResponses { responses })?;
+
+    if !read_message::<VerifierCheckMessage, _>(stream)?.success {
+        return Err(Error::Verification("Verifier rejected the coin flips".to_string()));
+    }
+    Ok(())
+}
+
+/// Prover randomness phase with several verifiers: collect every verifier's contribution digest, then its contribution,
+/// relaying each round to all of them, and combine the contributions into the joint coin flip and challenge.
+pub fn prover_joint_challenge<S: Transport>(streams: &mut [&mut Framed<S>]) -> Result<(u32, bit_sigma::Challenge)> {
+    let mut digests = Vec::with_capacity(streams.len());
+    for stream in streams.iter_mut() {
+        digests.push(read_message::<ContributionCommitmentMessage, _>(stream)?.digest);
+    }
+    if let Err(e) = joint::check_digests(streams.len(), &digests) {
+        return Err(Error::Protocol(format!("Joint coin flip failed: {}", e)));
+    }
+    let digests_m = ContributionDigestsMessage { digests: digests.clone() };
+    streams.iter_mut().try_for_each(|stream| write_message(stream, &digests_m))?;
+
+    let mut contributions = Vec::with_capacity(streams.len());
+    for stream in streams.iter_mut() {
+        contributions.push(read_message::<ContributionMessage, _>(stream)?);
+    }
+    let joint = joint::combine(streams.len(), &digests, &contributions)
+        .map_err(|e| Error::Protocol(format!("Joint coin flip failed: {}", e)))?;
+    let contributions_m = ContributionsMessage { contributions };
+    streams.iter_mut().try_for_each(|stream| write_message(stream, &contributions_m))?;
+    Ok(joint)
+}
+
+/// Prover randomness phase: flip the last committed coin by the verifiers' `player_b` and answer their challenge,
+/// returning the response for the verifiers.
+pub fn prover_randomness_phase_flip(state: &mut ProverState, player_b: u32, sigma_challenge: &bit_sigma::Challenge) -> ProverRandomnessResponse {
+
+    let final_commitment: RistrettoPoint;
+    let final_proof: Scalar;
+    let final_b: u32;
+
+    if player_b == 0 {
+        final_commitment = state.dealer_b_comm;
+        final_proof = state.dealer_b_proof;
+        final_b = state.dealer_b;
+    } else {
+        final_commitment = state.C1 + state.dealer_b_comm.neg();
+        final_proof = state.CPROOF + state.dealer_b_proof.neg();
+
+        final_b = 1 - state.dealer_b;
+    }
+
+    state.final_b = final_b;
+    state.final_proof = final_proof;
+
+    let _start = Instant::now();
+    let sigma_response = bit_sigma::response(&mut state.sigma_prover, sigma_challenge);
+    state.randomness_sigma_duration += _start.elapsed();
+
+    ProverRandomnessResponse {
+        final_commitment,
+        sigma_response
+    }
+}
+
+/// Prover randomness phase: generate a response to the verifiers' challenge. Send the response to every verifier.
+pub fn prover_randomness_phase_response<S: Transport>(state: &mut ProverState, streams: &mut [&mut Framed<S>]) -> Result<()> {
+
+    let (player_b, sigma_challenge) = if streams.len() == 1 {
+        let m: VerifierRandomnessChallenge = read_message(&mut *streams[0])?;
+        if m.player_b > 1 {
+            return Err(Error::Protocol(format!("Verifier coin flip {} is not a bit", m.player_b)));
+        }
+        (m.player_b, m.sigma_challenge)
+    } else {
+        prover_joint_challenge(streams)?
+    };
+
+    let response_m = prover_randomness_phase_flip(state, player_b, &sigma_challenge);
+    streams.iter_mut().try_for_each(|stream| write_message(stream, &response_m))?;
+
+    // every verifier has to accept the coin
+    streams.iter_mut().try_for_each(|stream| match read_message::<VerifierCheckMessage, _>(stream)?.success {
+        true => Ok(()),
+        false => Err(Error::Verification("Verifier rejected the coin flip".to_string())),
+    })
+}
+
+/// Prover randomness phase: set the last flipped coin aside for the noise
+pub fn prover_randomness_phase_aggregate(state: &mut ProverState) {
+    let _start = Instant::now();
+    state.noise_flips.push((state.final_b, state.final_proof));
+    state.coin_flipping_and_agg_duration += _start.elapsed();
+}
+
+/// Prover randomness phase with a single verifier: commit to all `n` coins, a window of `protocol::RANDOMNESS_WINDOW`
+/// at a time, in one message per window, take the verifier's flip and challenge of every coin of the window from its
+/// one reply, and answer them all in one message, taking a checkpoint once the verifier accepts them
+pub fn prover_batched_randomness_phase<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    state.randomness_checkpoint = Some(Checkpoint::default());
+    state.randomness_window = None;
+    prover_flip_windows(state, stream, n)
+}
+
+/// Prover randomness phase: flip the windows of coins left until `n` are flipped, from the last checkpoint on
+fn prover_flip_windows<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    while (state.noise_flips.len() as u32) < n {
+        stream.check_deadline()?;
+        let window = protocol::RANDOMNESS_WINDOW.min(n - state.noise_flips.len() as u32);
+        let (coins, comms_m) = prover_commit_coins(state, window);
+        // kept until the verifier accepts them, so a resumed phase answers with the same coins
+        state.randomness_window = Some(CoinWindow::Committed(coins));
+        write_message(stream, &comms_m)?;
+
+        let m: VerifierRandomnessChallenges = read_message(stream)?;
+        prover_answer_window(state, stream, m)?;
+    }
+    Ok(())
+}
+
+/// Prover randomness phase: flip each coin of the committed window by the verifier's coin in `m` and answer its
+/// challenge, sending every response in one message, or send the same responses again if the window was answered
+/// already, which it only is to the same challenges. Then wait for the verifier to accept the window, and take a
+/// checkpoint.
+fn prover_answer_window<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, m: VerifierRandomnessChallenges) -> Result<()> {
+    let challenges = bincode::serialize(&m).expect("protocol messages always encode");
+    let (flips, responses) = match state.randomness_window.take() {
+        Some(CoinWindow::Committed(coins)) => {
+            if m.challenges.len() != coins.len() {
+                return Err(Error::Protocol(format!("Verifier sent {} coin flips for {} coins", m.challenges.len(), coins.len())));
+            }
+            if let Some(c) = m.challenges.iter().find(|c| c.player_b > 1) {
+                return Err(Error::Protocol(format!("Verifier coin flip {} is not a bit", c.player_b)));
+            }
+            let mut flips = Vec::with_capacity(coins.len());
+            let mut responses = Vec::with_capacity(coins.len());
+            for ((dealer_b, dealer_b_comm, dealer_b_proof, sigma_prover), c) in coins.into_iter().zip(m.challenges) {
+                state.dealer_b = dealer_b;
+                state.dealer_b_comm = dealer_b_comm;
+                state.dealer_b_proof = dealer_b_proof;
+                state.sigma_prover = sigma_prover;
+                responses.push(prover_randomness_phase_flip(state, c.player_b, &c.sigma_challenge));
+                flips.push((state.final_b, state.final_proof));
+            }
+            (flips, ProverRandomnessResponses { responses })
+        },
+        Some(CoinWindow::Answered { challenges: answered, flips, responses }) if answered == challenges => (flips, responses),
+        Some(CoinWindow::Answered { .. }) => {
+            return Err(Error::Protocol("Verifier challenged coins it already challenged otherwise".to_string()));
+        },
+        None => return Err(Error::Protocol("Verifier challenged coins the prover did not commit to".to_string())),
+    };
+    let responses_m = stream.wire_format().encode(&responses);
+    state.randomness_window = Some(CoinWindow::Answered { challenges, flips, responses });
+    write_to_stream(stream, &responses_m)?;
+
+    if !read_message::<VerifierCheckMessage, _>(stream)?.success {
+        return Err(Error::Verification("Verifier rejected the coin flips".to_string()));
+    }
+    prover_accept_window(state);
+    Ok(())
+}
+
+/// Prover randomness phase: set the coins of the answered window aside for the noise once the verifier accepted them,
+/// and take a checkpoint
+fn prover_accept_window(state: &mut ProverState) {
+    if let Some(CoinWindow::Answered { flips, responses, .. }) = state.randomness_window.take() {
+        let _start = Instant::now();
+        let checkpoint = state.randomness_checkpoint.unwrap_or_default();
+        state.randomness_checkpoint = Some(checkpoint.advance(responses.responses.iter().map(|r| &r.final_commitment)));
+        state.noise_flips.extend(flips);
+        state.coin_flipping_and_agg_duration += _start.elapsed();
+    }
+}
+
+/// Prover randomness phase with a single verifier, resumed once `Framed::reconnect` has moved `stream` to a new
+/// connection after the last one dropped: catch up with the verifier's checkpoint if the verifier accepted the window
+/// answered last, which the dropped connection did not get to say, answer the verifier's challenges to the window it
+/// has not accepted yet, if any, and go on to all `n` coins. Any other checkpoint than the prover's own or the one it
+/// catches up with is aborted.
+pub fn prover_resume_randomness_phase<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    let Some(checkpoint) = state.randomness_checkpoint else {
+        return Err(Error::Config("No randomness phase to resume".to_string()));
+    };
+    let m: ResumeRandomnessMessage = read_message(stream)?;
+    if m.checkpoint != checkpoint {
+        let answered = match &state.randomness_window {
+            Some(CoinWindow::Answered { responses, .. }) => Some(checkpoint.advance(responses.responses.iter().map(|r| &r.final_commitment))),
+            _ => None,
+        };
+        if answered != Some(m.checkpoint) {
+            let reason = AbortReason::CheckpointMismatch { prover: checkpoint.coins, verifier: m.checkpoint.coins };
+            let _ = write_abort(stream, &reason);
+            return Err(Error::Aborted(reason));
+        }
+        prover_accept_window(state);
+    } else if m.challenges.is_none() {
+        // the verifier never challenged the window, so its coins are committed to afresh
+        state.randomness_window = None;
+    }
+    write_message(stream, &CheckpointMessage { checkpoint: m.checkpoint })?;
+    info!("resumed randomness phase after {} of {} coins", m.checkpoint.coins, n);
+
+    if let Some(challenges) = m.challenges {
+        prover_answer_window(state, stream, challenges)?;
+    }
+    prover_flip_windows(state, stream, n)
+}
+
+/// Prover randomness phase: flip `n` coins with the verifiers, all in one round with a single verifier, or one after
+/// another, each with its own round trips, when co-verifiers contribute to every coin
+pub fn prover_interactive_randomness_phase<S: Transport>(state: &mut ProverState, streams: &mut [&mut Framed<S>], n: u32) -> Result<()> {
+    if let [stream] = streams {
+        return prover_batched_randomness_phase(state, &mut **stream, n);
+    }
+    for _ in 0..n {
+        prover_randomness_phase_comm(state, streams)?;
+        prover_randomness_phase_response(state, streams)?;
+        prover_randomness_phase_aggregate(state);
+    }
+    Ok(())
+}
+
+/// Prover randomness phase driven by a randomness beacon: commit to all `n` coins at once, then flip and challenge them
+/// with the beacon round the verifier names, as read from the prover's own copy of the beacon
+pub fn prover_beacon_randomness_phase<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, beacon: &Beacon, n: u32) -> Result<()> {
+    let committed = SystemTime::now();
+    let (coins, comms_m) = prover_commit_coins(state, n);
+    write_message(stream, &comms_m)?;
+
+    let m: BeaconRoundMessage = read_message(stream)?;
+    let randomness = beacon.check_round(m.round, committed).and_then(|_| beacon.randomness(m.round))
+        .map_err(|e| Error::Protocol(format!("Beacon {}", e)))?;
+    info!("beacon round {}", m.round);
+
+    prover_flip_coins(state, stream, coins, beacon::coins(&randomness, n))
+}
+
+/// Prover randomness phase: combine the flipped coins into the noise by the session's noise mechanism, centered on zero.
+/// If the mechanism multiplies coins, commit to every product with its product sigma commitment, answer the verifier's
+/// challenges, and wait for the verifier to accept them.
+pub fn prover_randomness_phase_combine<S: Transport>(state: &mut ProverState, stream: &mut Framed<S>, params: &SessionParams) -> Result<()> {
+    let noise = params.noise();
+    let flips = std::mem::take(&mut state.noise_flips);
+    if flips.len() != noise.coins() as usize {
+        return Err(Error::Protocol(format!("Flipped {} coins, but the noise takes {}", flips.len(), noise.coins())));
+    }
+    let coins: Vec<(Scalar, Scalar)> = flips.iter().map(|&(b, r)| (Scalar::from(b), r)).collect();
+    let (one, cproof) = (Scalar::ONE, state.CPROOF);
+    let not = |(v, r): (Scalar, Scalar)| (one - v, cproof - r);
+
+    let gates = noise.gates();
+    let mut products = Vec::with_capacity(gates.len());
+    if !gates.is_empty() {
+        let _start = Instant::now();
+        let mut provers = Vec::with_capacity(gates.len());
+        let mut commitments = Vec::with_capacity(gates.len());
+        for (a, b) in &gates {
+            let ((m_1, r_1), (m_2, r_2)) = (a.resolve(&coins, &products, not), b.resolve(&coins, &products, not));
+            let (m_3, r_3) = (m_1 * m_2, Scalar::random(&mut state.rng));
+            let (sigma_prover, commitment) = product_sigma::commit(&mut state.rng, &state.pedersen_pp,
+                (m_1, pedersen::commit_with_r(&m_1, &r_1, &state.pedersen_pp), r_1),
+                (m_2, pedersen::commit_with_r(&m_2, &r_2, &state.pedersen_pp), r_2),
+                (m_3, pedersen::commit_with_r(&m_3, &r_3, &state.pedersen_pp), r_3));
+            products.push((m_3, r_3));
+            provers.push(sigma_prover);
+            commitments.push(commitment);
+        }
+        state.randomness_sigma_duration += _start.elapsed();
+        write_message(stream, &NoiseProductCommitments { commitments })?;
+
+        let m: NoiseProductChallenges = read_message(stream)?;
+        if m.challenges.len() != gates.len() {
+            return Err(Error::Protocol(format!("Verifier sent {} product challenges for {} products", m.challenges.len(), gates.len())));
+        }
+        let responses = provers.iter_mut().zip(&m.challenges).map(|(p, c)| product_sigma::response(p, c)).collect();
+        write_message(stream, &NoiseProductResponses { responses })?;
+        if !read_message::<VerifierCheckMessage, _>(stream)?.success {
+            return Err(Error::Verification("Verifier rejected the noise products".to_string()));
+        }
+    }
+
+    let _start = Instant::now();
+    let (mut sum, mut proof) = (-Scalar::from(noise.offset()), Scalar::ZERO);
+    for (factor, weight) in noise.terms() {
+        let (v, r) = factor.resolve(&coins, &products, not);
+        let weight = if weight < 0 { -Scalar::from(weight.unsigned_abs()) } else { Scalar::from(weight as u64) };
+        sum += weight * v;
+        proof += weight * r;
+    }
+    state.randomness_bit_sum = sum;
+    state.randomness_bit_proof = proof;
+    state.coin_flipping_and_agg_duration += _start.elapsed();
+    Ok(())
+}
+
+//
+// -- QUERYING PHASE --
+//
+
+/// Answer and opening of the query of `coefficients`, a distinct count if `distinct`, on the current noise, from the
+/// commitments of `database`. Fails if the query references an unknown monomial, or is a malformed distinct query.
+fn answer_on_noise<T: PrimInt + Hash>(state: &mut ProverState, database: &Data<T>, coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool) -> Result<(Scalar, Scalar)> {
+    let mut query_answer = state.randomness_bit_sum;
+    let mut query_proof = state.randomness_bit_proof;
+
+    // a distinct-count query sums the buckets of the attribute sketch
+    if distinct {
+        if state.sketch.is_empty() {
+            return Err(Error::Protocol("Distinct query, but no attribute sketch is committed".to_string()));
+        }
+        if !coefficients.is_empty() {
+            return Err(Error::Protocol("Distinct query also has monomial coefficients".to_string()));
+        }
+        for (bucket, _bucket_comm, bucket_proof) in &state.sketch {
+            query_answer += bucket;
+            query_proof += bucket_proof;
+        }
+    }
+
+    for monomial in coefficients.keys() {
+        if !database.commitments.contains_key(monomial) {
+            return Err(Error::Protocol(format!("Monomial {} not found in monomial map", monomial)));
+        }
+
+        let (monomial_sum, _monomial_comm, monomial_proof) = database.commitments.get(monomial).unwrap();
+        let monomial_coefficient = coefficients.get(monomial).unwrap();
+
+        let _start = Instant::now();
+        query_answer += monomial_coefficient * monomial_sum;
+        query_proof += monomial_coefficient * monomial_proof;
+        state.coin_flipping_and_agg_duration += _start.elapsed();
+    }
+    Ok((query_answer, query_proof))
+}
+
+/// Prover answers a query from the verifier, based on the coefficients of the monomials in the query. Send the answer to the verifier.
+/// Fails without answering if the query is malformed, is for another dataset or snapshot, or references an unknown monomial.
+/// A query moving further on one entry than the `sensitivity` the noise is scaled to, or breaking `policy`, is refused
+/// with an abort telling the verifier each way it does. A query the session has already answered gets the same answer
+/// and opening again, on the noise it was first answered on; returns whether the query was such a repeat.
+pub fn prover_answer_query<T, S: Transport>(state: &mut ProverState, database: &Data<T>, dataset: &str, stream: &mut Framed<S>, mode: analyst::AnswerMode, sensitivity: u32, policy: &Policy) -> Result<bool>
+where T: PrimInt + Hash + DeserializeOwned
+{
+    let query_m: QueryMessage<T> = read_message(stream)?;
+
+    check_query_target(state, dataset, &query_m.dataset, query_m.snapshot)?;
+    let violations = query_violations(&query_m.coefficients, sensitivity, policy);
+    if !violations.is_empty() {
+        return Err(refuse_query(stream, violations));
+    }
+
+    let ((query_answer, query_proof), repeated) = answer_or_repeat(state, database, &query_m.coefficients, query_m.distinct)?;
+
+    let query_comm = pedersen::commit_with_r(&query_answer, &query_proof, &state.pedersen_pp);
+    let answer_m = match mode {
+        AnswerMode::Plain => stream.wire_format().encode(&QueryAnswerMessage {
+            answer: query_answer,
+            proof: query_proof
+        }),
+        // the answer is only revealed to the analyst, but still proven against the query commitment
+        AnswerMode::Encrypted(pk) => stream.wire_format().encode(&EncryptedQueryAnswerMessage {
+            answer: analyst::encrypt(&mut state.rng, &state.pedersen_pp, &pk, &query_comm, &query_answer, &query_proof)
+        }),
+        // the query commitment is already half of a ciphertext for the analyst
+        AnswerMode::ElGamal(_) => {
+            let (handle, proof) = elgamal::reveal_handle(&mut state.rng, &state.pedersen_pp, &query_comm, &query_answer, &query_proof);
+            stream.wire_format().encode(&ElGamalQueryAnswerMessage { handle, proof })
+        },
+    };
+
+    write_to_stream(
+        stream,
+        &answer_m
+    )?;
+    Ok(repeated)
+}
+
+/// Prover answers a batch of queries from the verifier in the clear, each as `prover_answer_query` would, in a single
+/// message. Fails without answering if any query would fail, and refuses the whole batch if any query is beyond
+/// `sensitivity` or breaks `policy`, each violation prefixed by the position of its query. Returns how many of the
+/// queries were repeats.
+pub fn prover_answer_batch<T, S: Transport>(state: &mut ProverState, database: &Data<T>, dataset: &str, stream: &mut Framed<S>, sensitivity: u32, policy: &Policy) -> Result<usize>
+where T: PrimInt + Hash + DeserializeOwned
+{
+    let batch_m: BatchQueryMessage<T> = read_message(stream)?;
+
+    check_query_target(state, dataset, &batch_m.dataset, batch_m.snapshot)?;
+    let violations: Vec<String> = batch_m.queries.iter().enumerate()
+        .flat_map(|(i, coefficients)| query_violations(coefficients, sensitivity, policy).into_iter().map(move |v| format!("query {}: {}", i, v)))
+        .collect();
+    if !violations.is_empty() {
+        return Err(refuse_query(stream, violations));
+    }
+
+    let mut answers = Vec::with_capacity(batch_m.queries.len());
+    let mut repeats = 0;
+    for coefficients in &batch_m.queries {
+        let ((answer, proof), repeated) = answer_or_repeat(state, database, coefficients, false)?;
+        answers.push(QueryAnswerMessage { answer, proof });
+        repeats += repeated as usize;
+    }
+
+    write_message(stream, &BatchQueryAnswerMessage { answers })?;
+    Ok(repeats)
+}
+
+/// Check that a query for `query_dataset` at `query_snapshot` is for the session's `dataset` and committed snapshot
+fn check_query_target(state: &ProverState, dataset: &str, query_dataset: &str, query_snapshot: SnapshotId) -> Result<()> {
+    if query_dataset != dataset {
+        return Err(Error::Protocol(format!("Query is for dataset {:?}, but the session is for dataset {:?}", query_dataset, dataset)));
+    }
+    if query_snapshot != state.snapshot {
+        return Err(Error::Protocol(format!("Query is for snapshot {}, but the committed snapshot is {}", query_snapshot, state.snapshot)));
+    }
+    Ok(())
+}
+
+/// Every way the query of `coefficients` is one the prover does not answer: the noise only hides a change of up to
+/// `sensitivity` in its answer, so a query without integer coefficients, whose sensitivity has no bound, or one moving
+/// further on one entry, would give the entries away, and then whatever breaks `policy`
+fn query_violations<T: PrimInt + Hash>(coefficients: &HashMap<Monomial<T>, Scalar>, sensitivity: u32, policy: &Policy) -> Vec<String> {
+    let mut violations = Vec::new();
+    match sensitivity::l1(coefficients) {
+        Some(l1) if l1 <= sensitivity as u64 => {},
+        Some(l1) => violations.push(format!("query has sensitivity {}, beyond the session's {}", l1, sensitivity)),
+        None => violations.push("query has coefficients that are not integers, so no bound on its sensitivity".to_string()),
+    }
+    violations.extend(policy.violations(coefficients));
+    violations
+}
+
+/// Refuse a query the prover does not answer, for the reasons in `violations`, with an abort, returning the error to fail with
+fn refuse_query<S: Transport>(stream: &mut Framed<S>, violations: Vec<String>) -> Error {
+    let reason = AbortReason::QueryRefused { violations };
+    // the verifier may already be gone, which makes no difference to the refusal
+    let _ = write_abort(stream, &reason);
+    Error::Aborted(reason)
+}
+
+/// Answer and opening of the query of `coefficients`, as first given if the session already answered it and otherwise
+/// on the current noise, remembering them for a repeat; also returns whether the query was a repeat
+fn answer_or_repeat<T: PrimInt + Hash>(state: &mut ProverState, database: &Data<T>, coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool) -> Result<((Scalar, Scalar), bool)> {
+    let digest = cache::query_digest(coefficients, distinct);
+    let cached = state.answers.get(&digest).copied();
+    let answer = match cached {
+        Some(answer) => answer,
+        None => answer_on_noise(state, database, coefficients, distinct)?,
+    };
+    state.answers.insert(digest, answer);
+    Ok((answer, cached.is_some()))
+}
+
+//
+// -- SESSIONS --
+//
+
+/// Prover side of one session of the plain protocol, on a database it owns for the session. `S` is the last phase the
+/// session completed, which decides the phases that can run next.
+pub struct ProverSession<T, S> {
+    state: ProverState,
+    database: Data<T>,
+    params: SessionParams,
+    policy: Policy,
+    phase: PhantomData<S>,
+}
+
+impl<T, S> ProverSession<T, S> {
+    fn advance<N>(self) -> ProverSession<T, N> {
+        ProverSession { state: self.state, database: self.database, params: self.params, policy: self.policy, phase: PhantomData }
+    }
+
+    pub fn state(&self) -> &ProverState {
+        &self.state
+    }
+
+    pub fn database(&self) -> &Data<T> {
+        &self.database
+    }
+
+    /// Refuse the queries breaking `policy`, instead of answering every one
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<T: PrimInt + Hash + Serialize> ProverSession<T, Setup> {
+    /// Start a session on `database` with `params`, whose size must be the database's, sending fresh commitment
+    /// parameters to the verifier along with the session's parameters and the budget epoch it runs in
+    pub fn start<S: Transport>(stream: &mut Framed<S>, database: Data<T>, params: SessionParams, epoch: u64) -> Result<Self> {
+        if params.db_size as usize != database.entries.len() {
+            return Err(Error::Config(format!("Session is for {} entries, but the database has {}", params.db_size, database.entries.len())));
+        }
+        protocol::check_entries::<T>(&params)?;
+        stream.start_transcript();
+        let mut state = prover_setup();
+        mac::prover_agree(&mut state.rng, stream, None)?;
+        prover_send_setup(&mut state, stream, &params, epoch)?;
+        Ok(ProverSession { state, database, params, policy: Policy::default(), phase: PhantomData })
+    }
+
+    /// Run the honest and then the dishonest commitment phase on the database, for monomials up to the session's
+    /// maximum degree
+    pub fn commit<S: Transport>(mut self, stream: &mut Framed<S>) -> Result<ProverSession<T, protocol::Committed>> {
+        let SessionParams { dimension, max_degree, .. } = self.params;
+        prover_honest_commitment_phase(&mut self.state, stream, &mut self.database, dimension, max_degree)?;
+        self.state.snapshot = self.database.snapshot();
+
+        self.database.commitments.clear();
+        prover_dishonest_commitment_phase(&mut self.state, stream, &mut self.database, dimension, max_degree, None)?;
+        self.state.snapshot = self.database.snapshot();
+        stream.enter_session(Side::Prover);
+        Ok(self.advance())
+    }
+}
+
+impl<T> ProverSession<T, protocol::Committed> {
+    /// Flip the noise coins with the verifier, as many as the session's epsilon and delta call for over the database's
+    /// size. Called again once `Framed::reconnect` has moved `stream` to a new connection after the last one dropped,
+    /// resume from the last checkpoint instead.
+    pub fn flip_coins<S: Transport>(&mut self, stream: &mut Framed<S>) -> Result<()> {
+        match self.state.randomness_checkpoint {
+            None => prover_batched_randomness_phase(&mut self.state, stream, self.params.coins()),
+            Some(_) => prover_resume_randomness_phase(&mut self.state, stream, self.params.coins()),
+        }
+    }
+
+    /// Flip the noise coins, unless `flip_coins` already did, and combine them into the noise
+    pub fn randomness<S: Transport>(mut self, stream: &mut Framed<S>) -> Result<ProverSession<T, NoiseReady>> {
+        if self.state.randomness_checkpoint.is_none() {
+            self.flip_coins(stream)?;
+        }
+        prover_randomness_phase_combine(&mut self.state, stream, &self.params)?;
+        Ok(self.advance())
+    }
+}
+
+impl<T: PrimInt + Hash + DeserializeOwned> ProverSession<T, NoiseReady> {
+    /// Answer the verifier's next query in the clear, on the committed database and the noise, unless it breaks the
+    /// session's policy
+    pub fn answer_query<S: Transport>(&mut self, stream: &mut Framed<S>) -> Result<()> {
+        prover_answer_query(&mut self.state, &self.database, DEFAULT_DATASET, stream, AnswerMode::Plain, self.params.sensitivity.max(1), &self.policy).map(|_| ())
+    }
+
+    /// Answer the verifier's next batch of queries in the clear, unless one of them breaks the session's policy
+    pub fn answer_batch<S: Transport>(&mut self, stream: &mut Framed<S>) -> Result<()> {
+        prover_answer_batch(&mut self.state, &self.database, DEFAULT_DATASET, stream, self.params.sensitivity.max(1), &self.policy).map(|_| ())
+    }
+}
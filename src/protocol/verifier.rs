@@ -0,0 +1,1357 @@
+/*!
+ * protocol/verifier.rs
+ *
+ * Verifier side of each protocol phase, from checking the prover's commitments through flipping the noise coins to
+ * generating, sending and checking queries. The state accumulates everything verified so far, e.g. the monomial
+ * commitment map that query answers are checked against.
+ */
+
+use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
+use log::info;
+use num_traits::PrimInt;
+use rand::Rng;
+use rand::prelude::IteratorRandom;
+use rand::rngs::OsRng;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::Neg;
+use std::time::{Duration, SystemTime};
+use std::time::Instant;
+
+use crate::analyst::{self, AnswerMode, SavedAnswer};
+use crate::audit::{self, AuditMode};
+use crate::beacon::{self, Beacon};
+use crate::bit_sigma;
+use crate::challenges::{self, ChallengeTranscript};
+use crate::checkpoint::Checkpoint;
+use crate::calibration;
+use crate::cache::{self, QueryDigest};
+use crate::cut_and_choose;
+use crate::noise::{Binomial, NoiseMechanism};
+use crate::product_sigma;
+use crate::protocol::{self, NoiseReady, Setup};
+use crate::elgamal;
+use crate::error::{Error, Result};
+use crate::group::Ristretto;
+use crate::dataset::DEFAULT_DATASET;
+use crate::driver::Side;
+use crate::messages::{read_message, write_abort, write_message, write_to_stream, AbortReason, BatchQueryAnswerMessage, BatchQueryMessage, BeaconRoundMessage, BitSigmaCombinedChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, CheckpointMessage, CommitmentMapMessage, ContributionCommitmentMessage, ContributionDigestsMessage, ContributionsMessage, CutAndChooseChallengeMessage, CutAndChooseCommitmentMessage, CutAndChooseResponseMessage, ElGamalQueryAnswerMessage, EncryptedQueryAnswerMessage, Framed, MonomialChallengeTree, MonomialCommitmentTree, MonomialResponseTree, NoiseProductChallenges, NoiseProductCommitments, NoiseProductResponses, ProverRandomnessComm, ProverRandomnessComms, ProverRandomnessResponse, ProverRandomnessResponses, QueryAnswerMessage, QueryMessage, ResumeRandomnessMessage, SeedContributionMessage, SeedRevealMessage, SessionParams, SetupMessage, SketchCommitmentMessage, SketchResponseMessage, SpotCheckMessage, UpdateMessage, VerifierCheckMessage, VerifierRandomnessChallenge, VerifierRandomnessChallenges, WIRE_VERSION};
+use crate::joint;
+use crate::mac;
+use crate::monomial::{extract_monomials, gen_monomial_map, subtract_monomial_map, Committed, Monomial, MonomialTree};
+use crate::generators;
+use crate::pedersen;
+use crate::result::{self, QueryResult};
+use crate::query;
+use crate::query_certificate::{QueryCertificate, Term};
+use crate::sketch::{self, Attribute};
+use crate::snapshot::SnapshotId;
+use crate::transport::Transport;
+
+/// Verifier state for the main protocol.
+#[allow(non_snake_case)]
+pub struct VerifierState<T>
+where T: PrimInt + Hash
+{
+    pub rng: OsRng,
+    /// Transcript of the session so far, which every challenge to the prover is drawn from
+    pub transcript: ChallengeTranscript,
+    pub pedersen_pp: pedersen::PublicParams,
+    pub monomial_commitments: HashMap<Monomial<T>, RistrettoPoint>,
+    /// Each entry's contribution to the monomial commitments, kept for the update phase when it is expected
+    pub entry_commitments: Option<Vec<HashMap<Monomial<T>, RistrettoPoint>>>,
+    /// Each entry's bit commitments from the last dishonest commitment phase, which the attribute sketch is checked on
+    pub entry_bits: Vec<Vec<RistrettoPoint>>,
+    /// Commitments to the buckets of the certified attribute sketch, for distinct-count queries
+    pub sketch: Vec<RistrettoPoint>,
+    pub snapshot: SnapshotId,
+    /// Dataset the session audits, which every query is for
+    pub dataset: String,
+    pub epoch: u64,
+    pub player_b: u32,
+    /// Commitment to each coin flipped for the next noise, until the noise mechanism combines them
+    pub noise_flips: Vec<RistrettoPoint>,
+    /// Checkpoint of the randomness phase with a single verifier, once it starts, which it resumes from if cut short
+    pub randomness_checkpoint: Option<Checkpoint>,
+    /// Flip and bit sigma verifier of each coin of the window challenged but not accepted yet, and the challenges sent
+    pub randomness_window: Option<(Vec<(u32, bit_sigma::Verifier)>, VerifierRandomnessChallenges)>,
+    pub randomness_bit_comm: RistrettoPoint,
+    /// Noise commitment each query of the session was answered on, which the answer to a repeat must open against
+    pub answered: HashMap<QueryDigest, RistrettoPoint>,
+    /// Noise the queries are answered on and the privacy budget each query on it spends, for the query results
+    pub noise: Box<dyn NoiseMechanism>,
+    pub epsilon: f32,
+    /// Delta of the noise, derived from the database size unless the session set it
+    pub delta: f32,
+    /// Confidence level of the intervals reported with the answers
+    pub confidence: f64,
+    pub sigma_verifier: bit_sigma::Verifier,
+
+    pub C0: RistrettoPoint,
+    pub C1: RistrettoPoint,
+    pub CPROOF: Scalar,
+
+    pub comm_verify_duration: Duration,
+    pub audit_summary: Option<String>,
+    pub encrypted_answers: Vec<analyst::SavedAnswer>,
+    /// Certificates of the verified answers in the clear to queries of the monomial commitments, kept only once the
+    /// verifier asks for them
+    pub query_certificates: Option<Vec<QueryCertificate>>,
+    pub randomness_bit_sigma_verify_duration: Duration,
+    pub randomness_coin_flip_agg_duration: Duration,
+}
+
+//
+// -- SETUP PHASE --
+//
+
+/// Initialize verifier state; the commitment parameters are filled in by `verifier_setup` once the prover's seed arrives.
+pub fn verifier_init<T: PrimInt + Hash>() -> VerifierState<T> {
+    VerifierState {
+        rng: OsRng,
+        transcript: ChallengeTranscript::default(),
+        pedersen_pp: pedersen::PublicParams::new(RistrettoPoint::default(), RistrettoPoint::default()),
+        monomial_commitments: HashMap::new(),
+        entry_commitments: None,
+        entry_bits: Vec::new(),
+        sketch: Vec::new(),
+        snapshot: SnapshotId::default(),
+        dataset: DEFAULT_DATASET.to_string(),
+        epoch: 0,
+        C0: RistrettoPoint::default(),
+        C1: RistrettoPoint::default(),
+        CPROOF: Scalar::from(0u32),
+        player_b: 0,
+        noise_flips: Vec::new(),
+        randomness_checkpoint: None,
+        randomness_window: None,
+        randomness_bit_comm: RistrettoPoint::default(),
+        answered: HashMap::new(),
+        noise: Box::new(Binomial { coins: 0 }),
+        epsilon: 0.0,
+        delta: 0.0,
+        confidence: result::DEFAULT_CONFIDENCE,
+        sigma_verifier: bit_sigma::Verifier::default(),
+
+        randomness_bit_sigma_verify_duration: Duration::from_secs(0),
+        randomness_coin_flip_agg_duration: Duration::from_secs(0),
+        comm_verify_duration: Duration::from_secs(0),
+        audit_summary: None,
+        encrypted_answers: Vec::new(),
+        query_certificates: None,
+    }
+}
+
+/// Setup the verifier with the Pedersen generators, flipped with the prover unless an earlier session of the dataset
+/// already flipped them, recording the budget epoch the prover announces.
+/// Fails if the prover refuses the session instead, e.g. for an unknown dataset or an exhausted budget, which it does by
+/// closing the connection. Before anything is committed, the prover must run this protocol version and serve the
+/// session with the verifier's `params`, or the verifier aborts, telling the prover which parameters differ.
+pub fn verifier_setup<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, params: &SessionParams) -> Result<()> {
+    let setup_message: SetupMessage = read_message(stream)?;
+    if setup_message.version != WIRE_VERSION {
+        return Err(Error::Config(format!("Prover runs protocol version {}, but this verifier runs version {}", setup_message.version, WIRE_VERSION)));
+    }
+    let Some(announced) = setup_message.params else {
+        return Err(Error::Protocol("Prover announced no session parameters".to_string()));
+    };
+    if announced != *params {
+        let reason = AbortReason::ParameterMismatch { prover: announced, verifier: *params };
+        // the prover may already be gone, which makes no difference to the abort
+        let _ = write_abort(stream, &reason);
+        return Err(Error::Aborted(reason));
+    }
+    let seed = match setup_message.seed_commitment {
+        Some(commitment) => {
+            let half = generators::half(&mut state.rng);
+            write_message(stream, &SeedContributionMessage { seed: half })?;
+            let reveal: SeedRevealMessage = read_message(stream)?;
+            if generators::commit(&reveal.seed) != commitment {
+                return Err(Error::Verification("Prover's half of the generator seed does not open its commitment".to_string()));
+            }
+            info!("generators flipped");
+            generators::combine(&reveal.seed, &half)
+        },
+        None => {
+            info!("generators flipped in an earlier session");
+            setup_message.seed
+        },
+    };
+    let pp = generators::params(&seed);
+    state.transcript = ChallengeTranscript::default();
+    state.transcript.absorb(b"setup", &setup_message);
+    state.transcript.absorb(b"generators", &seed);
+
+    state.C0 = pedersen::commit_with_r(&Scalar::from(0u32), &state.CPROOF, &pp);
+    state.C1 = pedersen::commit_with_r(&Scalar::from(1u32), &state.CPROOF, &pp);
+    state.pedersen_pp = pp;
+    state.epoch = setup_message.epoch;
+    info!("epoch {}", state.epoch);
+    // a co-verifier joins after the commitment phases, in the session they made
+    if let Some(id) = setup_message.session {
+        stream.set_session_id(Some(id));
+        info!("session {}", id);
+    }
+    Ok(())
+}
+
+//
+// -- COMMITMENT PHASE --
+//
+
+/// Honest commitment phase: read commitment map from prover, checking it sums over the session's `db_size` entries and
+/// holds the monomials of its `dimension` and `max_degree`, and no others, and against the snapshot the prover claims for it.
+pub fn verifier_honest_commitment_phase<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, db_size: u32, dimension: u32, max_degree: u32) -> Result<()>
+where T: PrimInt + Eq + Hash + DeserializeOwned
+{
+    let m: CommitmentMapMessage<T> = read_message(stream)?;
+    check_count(stream, || "entries".to_string(), db_size as u64, m.entries)?;
+    check_commitment_map(stream, &m.commitment_map, dimension, max_degree)?;
+
+    state.monomial_commitments = m.commitment_map;
+    // the map is not made of per-entry contributions, so no entry can be removed from it
+    if let Some(entries) = &mut state.entry_commitments {
+        entries.clear();
+    }
+    if SnapshotId::of(state.monomial_commitments.iter().map(|(&k, &v)| (k, v))) != m.snapshot {
+        return Err(Error::Protocol(format!("Commitment map does not match its snapshot {}", m.snapshot)));
+    }
+    // the snapshot digests the whole map
+    state.transcript.absorb(b"snapshot", &m.snapshot);
+    Ok(())
+}
+
+/// Monomials of a mismatched commitment map named in the abort, of those missing and of those unexpected each
+const NAMED_MONOMIALS: usize = 8;
+
+/// Check that a commitment map holds exactly the monomials of degree one to `max_degree` over the first `dimension`
+/// variables, those of an entry's monomial tree, so every query the session allows can be answered. Aborts otherwise,
+/// naming the monomials missing and those unexpected.
+fn check_commitment_map<T: PrimInt + Hash, S: Transport>(stream: &mut Framed<S>, map: &HashMap<Monomial<T>, RistrettoPoint>, dimension: u32, max_degree: u32) -> Result<()> {
+    let expected: HashSet<Monomial<T>> = MonomialTree::build(dimension as usize, max_degree as usize, (), |_, _| ()).monomials().into_iter()
+        .map(|(monomial, _)| monomial)
+        .filter(|monomial| monomial.degree() > 0)
+        .collect();
+    let mut missing: Vec<_> = expected.iter().filter(|monomial| !map.contains_key(monomial)).collect();
+    let mut unexpected: Vec<_> = map.keys().filter(|monomial| !expected.contains(monomial)).collect();
+    if missing.is_empty() && unexpected.is_empty() {
+        return Ok(());
+    }
+    missing.sort();
+    unexpected.sort();
+    let named = |monomials: &[&Monomial<T>]| monomials.iter().take(NAMED_MONOMIALS).map(|m| m.to_string()).collect();
+    let reason = AbortReason::CommitmentMapMismatch {
+        missing: missing.len() as u64,
+        unexpected: unexpected.len() as u64,
+        first_missing: named(&missing),
+        first_unexpected: named(&unexpected),
+    };
+    Err(abort(stream, reason))
+}
+
+/// Check that the prover committed to as many of `what` as the session's parameters call for, `expected`, rather than
+/// `found`, which would leave the verifier waiting on messages that never come, or checking too few. Aborts otherwise.
+fn check_count<S: Transport>(stream: &mut Framed<S>, what: impl FnOnce() -> String, expected: u64, found: u64) -> Result<()> {
+    if found == expected {
+        return Ok(());
+    }
+    Err(abort(stream, AbortReason::CountMismatch { what: what(), expected, found }))
+}
+
+/// Send the prover `reason` in place of the next message, and fail the session with it
+fn abort<S: Transport>(stream: &mut Framed<S>, reason: AbortReason) -> Error {
+    // the prover may already be gone, which makes no difference to the abort
+    let _ = write_abort(stream, &reason);
+    Error::Aborted(reason)
+}
+
+/// Verifier's payload in a monomial tree: the commitment to the node's partial product, and the product sigma protocol
+/// that checks it.
+pub struct VerifierNode {
+    pub commitment: Option<RistrettoPoint>,
+    pub product_sigma_verifier: Option<product_sigma::Verifier>,
+}
+
+impl Committed for VerifierNode {
+    type Value = RistrettoPoint;
+
+    fn commitment(&self) -> Option<RistrettoPoint> {
+        self.commitment
+    }
+}
+
+/// Based on a tree of product sigma commitment nodes, generate the matching trees of verifiers and of challenges to send to the prover.
+/// Each node's challenge is labelled by the entry and the node's place in the tree.
+fn gen_challenge_tree<T: PrimInt + Hash>(state: &mut VerifierState<T>, entry: usize, comm_tree: &MonomialCommitmentTree) -> (MonomialTree<VerifierNode>, MonomialChallengeTree) {
+    let mut node = 0;
+    comm_tree.map(|comm_node| {
+        node += 1;
+        let (product_sigma_verifier, challenge) = match &comm_node.product_sigma_commitment {
+            Some(sigma_comm) => {
+                let mut rng = state.transcript.rng(challenges::PRODUCT_SIGMA, &[entry as u64, node - 1], &mut state.rng);
+                let (sigma_verifier, sigma_challenge) = product_sigma::challenge(&mut rng, sigma_comm);
+                (Some(sigma_verifier), Some(sigma_challenge))
+            },
+            None => (None, None),
+        };
+        (VerifierNode { commitment: comm_node.commitment, product_sigma_verifier }, challenge)
+    }).unzip()
+}
+
+/// Verify the response tree of sigma protocol nodes generated by the prover. Nodes are verified in parallel, and no
+/// further nodes are started once one of them fails.
+fn verify_response_tree(pp: &pedersen::PublicParams, verifier_tree: &mut MonomialTree<VerifierNode>, response_tree: &MonomialResponseTree) -> bool {
+    verifier_tree.par_zip_all(response_tree, |verifier_node, response| {
+        match (&mut verifier_node.product_sigma_verifier, response) {
+            (None, _) => true,
+            (Some(sigma_verifier), Some(response)) => product_sigma::verify(pp, sigma_verifier, response).is_ok(),
+            (Some(_), None) => false,
+        }
+    })
+}
+
+/// Verify the response trees of the given entries against their verifier trees, entries in parallel as well as the
+/// nodes within each, returning the first entry (in the order given) whose tree fails
+pub(crate) fn verify_response_trees(pp: &pedersen::PublicParams, verifier_trees: &mut [MonomialTree<VerifierNode>], responses: &[(usize, MonomialResponseTree)]) -> Option<usize> {
+    let mut trees: Vec<Option<&mut MonomialTree<VerifierNode>>> = verifier_trees.iter_mut().map(Some).collect();
+    let pairs: Vec<_> = responses.iter().map(|(i, response)| (*i, trees[*i].take().expect("each entry responds once"), response)).collect();
+
+    pairs.into_par_iter()
+        .map(|(i, verifier_tree, response)| (i, verify_response_tree(pp, verifier_tree, response)))
+        .find_first(|&(_, verified)| !verified)
+        .map(|(i, _)| i)
+}
+
+/// Dishonest commitment phase: read bit sigma and product sigma commitment messages from prover and generate matching challenges.
+/// Entries are proven a window at a time (`protocol::DISHONEST_WINDOW`), so only one window's sigma protocol state is held at once.
+/// With a partial audit mode only a random sample of the entries is verified; the rest are accepted as committed.
+pub fn verifier_dishonest_commitment_phase<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, db_size: u32, dimension: u32, mode: AuditMode) -> Result<()>
+where T: PrimInt + Eq + Hash + DeserializeOwned
+{
+    // Monomial commitments summed over the entries proven so far
+    let mut monomial_commitments: HashMap<Monomial<T>, RistrettoPoint> = HashMap::new();
+    // Per-entry monomial commitments, if kept for updates, and bit commitments
+    let mut entry_commitments: Vec<HashMap<Monomial<T>, RistrettoPoint>> = Vec::new();
+    let mut entry_bits: Vec<Vec<RistrettoPoint>> = Vec::with_capacity(db_size as usize);
+
+    // a spot check is sampled from every entry once all of them are committed, so its window holds all of them, while
+    // the entries a probabilistic audit checks are never revealed, so they can be drawn up front
+    let window = match mode {
+        AuditMode::SpotCheck(_) => (db_size as usize).max(1),
+        _ => protocol::DISHONEST_WINDOW,
+    };
+    let mut is_checked = vec![true; db_size as usize];
+    if let AuditMode::Probabilistic(rate) = mode {
+        let sample = audit::sample_entries(&mut state.transcript.rng(challenges::AUDIT, &[], &mut state.rng), db_size, rate);
+        state.audit_summary = Some(format!("Probabilistic audit: {}", audit::describe(db_size, sample.len() as u32)));
+        info!("{}", state.audit_summary.as_ref().unwrap());
+        is_checked = vec![false; db_size as usize];
+        for &i in &sample {
+            is_checked[i as usize] = true;
+        }
+    }
+
+    // once an entry fails, the rest are still challenged, so the prover reaches the final check, but not verified
+    let mut failure = None;
+
+    for start in (0..db_size as usize).step_by(window) {
+        let end = (start + window).min(db_size as usize);
+
+        // run challenge phase for each incoming commitment of the window
+        let mut db_bit_sigma_verifiers: Vec<Vec<bit_sigma::Verifier>> = Vec::new();
+        let mut monomial_product_sigma_verifiers: Vec<MonomialTree<VerifierNode>> = Vec::new();
+
+        let mut challenge_messages: Vec<Vec<Vec<u8>>> = Vec::new();
+
+        for entry in start..end {
+            let mut element_bit_sigma_verifiers: Vec<bit_sigma::Verifier> = Vec::new();
+
+            let bit_sigma_comm_m: BitSigmaCommitmentMessage = read_message(stream)?;
+            check_count(stream, || format!("bits of entry {}", entry), dimension as u64, bit_sigma_comm_m.commitments.len() as u64)?;
+            state.transcript.absorb(b"bit-sigma-commitment", &bit_sigma_comm_m);
+
+            // one challenge per entry; each bit's challenge is derived from it
+            let entry_challenge = bit_sigma::Challenge::random(&mut state.transcript.rng(challenges::BIT_SIGMA, &[entry as u64], &mut state.rng));
+            state.transcript.absorb(b"bit-sigma-challenge", &entry_challenge);
+            for (j, sigma_challenge) in bit_sigma::expand_challenge(&entry_challenge, dimension as usize).iter().enumerate() {
+                element_bit_sigma_verifiers.push(bit_sigma::verifier_for(&bit_sigma_comm_m.commitments[j], sigma_challenge));
+            }
+            db_bit_sigma_verifiers.push(element_bit_sigma_verifiers);
+
+            let mut entry_challenge_messages = vec![stream.wire_format().encode(&BitSigmaCombinedChallengeMessage {
+                challenge: entry_challenge
+            })];
+
+            if dimension == 1 {
+                challenge_messages.push(entry_challenge_messages);
+                continue;
+            }
+
+            let comm_m: MonomialCommitmentTree = read_message(stream)?;
+            state.transcript.absorb(b"monomial-commitment-tree", &comm_m);
+
+            let (verifier_tree, challenge_tree) = gen_challenge_tree(state, entry, &comm_m);
+            state.transcript.absorb(b"monomial-challenge-tree", &challenge_tree);
+            monomial_product_sigma_verifiers.push(verifier_tree);
+
+            entry_challenge_messages.push(stream.wire_format().encode(&challenge_tree));
+            challenge_messages.push(entry_challenge_messages);
+        }
+
+        // entries are sampled only once every commitment has been received, so the prover cannot adapt its commitments;
+        // indices are into the window, which is the whole database for a spot check
+        let proven: Vec<usize> = match mode {
+            AuditMode::SpotCheck(rate) => {
+                let sample = audit::sample_entries(&mut state.transcript.rng(challenges::AUDIT, &[start as u64], &mut state.rng), db_size, rate);
+                state.transcript.absorb(b"spot-check", &sample);
+                write_message(
+                    stream, &SpotCheckMessage { entries: sample.clone() }
+                )?;
+                state.audit_summary = Some(format!("Spot check: {}", audit::describe(db_size, sample.len() as u32)));
+                info!("{}", state.audit_summary.as_ref().unwrap());
+                is_checked = vec![false; db_size as usize];
+                for &i in &sample {
+                    is_checked[i as usize] = true;
+                }
+                sample.into_iter().map(|i| i as usize).collect()
+            },
+            _ => (0..end - start).collect(),
+        };
+
+        for &i in &proven {
+            for msg in &challenge_messages[i] {
+                write_to_stream(
+                    stream, msg
+                )?;
+            }
+        }
+
+        // bit responses of the window's checked entries, verified in one batch once every entry is in
+        let mut bit_responses = Vec::new();
+        // product sigma responses of the window's checked entries, whose trees are verified in parallel once every entry is in
+        let mut tree_responses = Vec::new();
+
+        for i in proven {
+            let resp_m: BitSigmaResponseMessage = read_message(stream)?;
+            let tree_m = match dimension {
+                1 => None,
+                _ => Some(read_message::<MonomialResponseTree, _>(stream)?),
+            };
+
+            if failure.is_some() || !is_checked[start + i] {
+                continue;
+            }
+
+            if resp_m.responses.len() != db_bit_sigma_verifiers[i].len() {
+                failure = Some(Error::Verification(format!("Bit sigma verification failed for entry {}: {} responses for {} bits",
+                                                           start + i, resp_m.responses.len(), db_bit_sigma_verifiers[i].len())));
+                continue;
+            }
+            bit_responses.push((i, resp_m.responses));
+            if let Some(tree_m) = tree_m {
+                tree_responses.push((i, tree_m));
+            }
+        }
+
+        if failure.is_none() {
+            let _start = Instant::now();
+            failure = verify_response_trees(&state.pedersen_pp, &mut monomial_product_sigma_verifiers, &tree_responses)
+                .map(|i| Error::Verification(format!("Monomial product sigma verification failed for entry {}", start + i)));
+
+            if failure.is_none() {
+                let (entries, pairs): (Vec<usize>, Vec<_>) = bit_responses.iter()
+                    .flat_map(|(i, responses)| db_bit_sigma_verifiers[*i].iter().zip(responses).map(move |pair| (*i, pair)))
+                    .unzip();
+                if let Err((j, e)) = bit_sigma::verify_batch(&mut state.rng, &state.pedersen_pp, &pairs) {
+                    failure = Some(Error::Verification(format!("Bit sigma verification failed for entry {}: {}", start + entries[j], e)));
+                }
+            }
+            state.comm_verify_duration += _start.elapsed();
+        }
+
+        if dimension == 1 {
+            let sum = monomial_commitments.entry(Monomial::variable(0)).or_default();
+            for v in &db_bit_sigma_verifiers {
+                *sum += v[0].b_comm;
+            }
+        } else {
+            gen_monomial_map(&monomial_product_sigma_verifiers, &mut monomial_commitments);
+        }
+
+        if state.entry_commitments.is_some() {
+            match dimension {
+                1 => entry_commitments.extend(db_bit_sigma_verifiers.iter().map(|v| HashMap::from([(Monomial::variable(0), v[0].b_comm)]))),
+                _ => entry_commitments.extend(monomial_product_sigma_verifiers.iter().map(entry_monomial_map)),
+            }
+        }
+        entry_bits.extend(db_bit_sigma_verifiers.iter().map(|v| v.iter().map(|s| s.b_comm).collect::<Vec<_>>()));
+    }
+
+    let sent = write_message(
+        stream, &VerifierCheckMessage { success: failure.is_none() }
+    );
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    sent?;
+
+    if dimension == 1 {
+        *state.monomial_commitments.entry(Monomial::variable(0)).or_default() += monomial_commitments.remove(&Monomial::variable(0)).unwrap_or_default();
+    } else {
+        for (k, v) in monomial_commitments {
+            *state.monomial_commitments.entry(k).or_default() += v;
+        }
+    }
+
+    if let Some(entries) = &mut state.entry_commitments {
+        *entries = entry_commitments;
+    }
+    state.entry_bits = entry_bits;
+    
+    Ok(())
+}
+
+/// Sketch phase, after the dishonest commitment phase: check the prover's sketch of the attribute over the bit
+/// commitments of the entries just certified, and keep the commitments to its buckets for distinct-count queries.
+pub fn verifier_sketch_phase<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, attribute: &Attribute) -> Result<()> {
+    let rows: Vec<Vec<RistrettoPoint>> = state.entry_bits.iter()
+        .map(|bits| attribute.bits.iter().map(|&j| bits[j]).collect())
+        .collect();
+
+    let commitment_m: SketchCommitmentMessage = read_message(stream)?;
+    state.transcript.absorb(b"sketch-commitment", &commitment_m);
+    let (sketch_verifier, challenge_m) = sketch::challenge(&mut state.transcript.rng(challenges::SKETCH, &[], &mut state.rng), &state.pedersen_pp, &rows, &commitment_m)
+        .map_err(|e| Error::Protocol(format!("Sketch rejected: {}", e)))?;
+    state.transcript.absorb(b"sketch-challenge", &challenge_m);
+    write_message(stream, &challenge_m)?;
+
+    let response_m: SketchResponseMessage = read_message(stream)?;
+    let _start = Instant::now();
+    let buckets = sketch::verify(&state.pedersen_pp, sketch_verifier, &response_m);
+    state.comm_verify_duration += _start.elapsed();
+
+    write_message(stream, &VerifierCheckMessage { success: buckets.is_some() })?;
+    let Some(buckets) = buckets else {
+        return Err(Error::Verification("Sketch sigma verification failed".to_string()));
+    };
+
+    state.sketch = buckets;
+    info!("sketch of {} certified, {} buckets", attribute, state.sketch.len());
+    Ok(())
+}
+
+/// Cut-and-choose commitment phase: read each entry's commitments and triple encodings, pick one encoding per entry to
+/// keep, then check the opened encodings and the product responses made with the kept ones.
+pub fn verifier_cut_and_choose_phase<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, db_size: u32, dimension: u32, encodings: u32) -> Result<()>
+where T: PrimInt + Eq + Hash + DeserializeOwned
+{
+    let mut monomial_commitment_trees: Vec<MonomialCommitmentTree> = Vec::new();
+    let mut db_multiplications: Vec<Vec<cut_and_choose::Multiplication<RistrettoPoint>>> = Vec::new();
+    let mut db_encodings: Vec<Vec<Vec<cut_and_choose::TripleCommitment>>> = Vec::new();
+
+    let mut failure = None;
+
+    for i in 0..db_size {
+        let comm_m: CutAndChooseCommitmentMessage = read_message(stream)?;
+        check_count(stream, || format!("bits of entry {}", i), dimension as u64, comm_m.bit_commitments.len() as u64)?;
+        state.transcript.absorb(b"cut-and-choose-commitment", &comm_m);
+
+        let Some(mults) = cut_and_choose::multiplications(&comm_m.bit_commitments, &comm_m.tree) else {
+            failure = Some(Error::Protocol("Monomial tree does not match the entry's bit commitments".to_string()));
+            break;
+        };
+        if comm_m.encodings.len() != encodings as usize || comm_m.encodings.iter().any(|enc| enc.len() != mults.len()) {
+            failure = Some(Error::Protocol(format!("Entry does not have {} encodings of {} multiplication triples", encodings, mults.len())));
+            break;
+        }
+
+        monomial_commitment_trees.push(comm_m.tree);
+        db_multiplications.push(mults);
+        db_encodings.push(comm_m.encodings);
+    }
+
+    if failure.is_none() {
+        // encodings are picked only once every commitment has been received
+        let mut rng = state.transcript.rng(challenges::CUT_AND_CHOOSE, &[], &mut state.rng);
+        let kept: Vec<u32> = (0..db_size).map(|_| rng.gen_range(0..encodings)).collect();
+        state.transcript.absorb(b"cut-and-choose-challenge", &kept);
+        write_message(
+            stream, &CutAndChooseChallengeMessage { kept: kept.clone() }
+        )?;
+        state.audit_summary = Some(format!("Cut-and-choose: {}", cut_and_choose::describe(encodings)));
+        info!("{}", state.audit_summary.as_ref().unwrap());
+
+        for (i, &k) in kept.iter().enumerate() {
+            let resp_m: CutAndChooseResponseMessage = read_message(stream)?;
+
+            let _start = Instant::now();
+            let opened = db_encodings[i].iter().enumerate().filter(|(j, _)| *j != k as usize).map(|(_, enc)| enc);
+            if resp_m.openings.len() != encodings as usize - 1
+                || !opened.zip(&resp_m.openings).all(|(enc, opening)| enc.len() == opening.len()
+                    && enc.iter().zip(opening).all(|(c, t)| cut_and_choose::verify_opening(&state.pedersen_pp, c, t))) {
+                failure = Some(Error::Verification(format!("Opened encoding of entry {} does not hold valid multiplication triples", i)));
+                break;
+            }
+
+            let kept_encoding = &db_encodings[i][k as usize];
+            if resp_m.responses.len() != kept_encoding.len()
+                || !db_multiplications[i].iter().zip(kept_encoding).zip(&resp_m.responses)
+                    .all(|((&mult, c), r)| cut_and_choose::verify(&state.pedersen_pp, c, mult, r)) {
+                failure = Some(Error::Verification(format!("Cut-and-choose product verification failed for entry {}", i)));
+                break;
+            }
+            state.comm_verify_duration += _start.elapsed();
+        }
+    }
+
+    let sent = write_message(
+        stream, &VerifierCheckMessage { success: failure.is_none() }
+    );
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    sent?;
+
+    gen_monomial_map(&monomial_commitment_trees, &mut state.monomial_commitments);
+    if let Some(entries) = &mut state.entry_commitments {
+        *entries = monomial_commitment_trees.iter().map(entry_monomial_map).collect();
+    }
+    Ok(())
+}
+
+//
+// -- UPDATE PHASE --
+//
+
+/// Monomial commitments one database entry contributes to the commitment map, from its monomial tree
+fn entry_monomial_map<T: PrimInt + Hash, P: Committed<Value = RistrettoPoint>>(tree: &MonomialTree<P>) -> HashMap<Monomial<T>, RistrettoPoint> {
+    let mut map = HashMap::new();
+    extract_monomials(tree, &mut map);
+    map
+}
+
+/// Update phase: check the prover's change to the database against the current commitment map. The added rows are
+/// verified as in the dishonest commitment phase and their contributions added to the map; the removed entries'
+/// contributions, certified by an earlier phase, are subtracted from it. Returns the new number of entries.
+pub fn verifier_update_phase<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, db_size: u32, dimension: u32) -> Result<u32>
+where T: PrimInt + Eq + Hash + DeserializeOwned
+{
+    let mut entries = match state.entry_commitments.take() {
+        Some(entries) if entries.len() == db_size as usize => entries,
+        _ => {
+            return Err(Error::Config("Updates need the per-entry commitments of the dishonest commitment phase or spot-check mode".to_string()));
+        }
+    };
+
+    let m: UpdateMessage = read_message(stream)?;
+
+    if !audit::is_valid_sample(&m.removed, db_size) {
+        return Err(Error::Protocol("Update removes entries that are not increasing indices into the database".to_string()));
+    }
+    if m.removed.len() as u64 >= db_size as u64 + m.added as u64 {
+        return Err(Error::Protocol("Update leaves an empty database".to_string()));
+    }
+    info!("{} rows added, {} removed", m.added, m.removed.len());
+
+    // every added row is verified, and the per-entry commitments it leaves are those of the added rows
+    state.entry_commitments = Some(Vec::new());
+    verifier_dishonest_commitment_phase(state, stream, m.added, dimension, AuditMode::Full)?;
+
+    for &i in m.removed.iter().rev() {
+        let entry = entries.remove(i as usize);
+        subtract_monomial_map(&mut state.monomial_commitments, &entry);
+    }
+    entries.append(state.entry_commitments.as_mut().unwrap());
+    let db_size = entries.len() as u32;
+    state.entry_commitments = Some(entries);
+    Ok(db_size)
+}
+
+//
+// -- RANDOMNESS PHASE --
+//
+
+/// Randomness phase: coin flip and bit sigma challenge generation; send results back to prover.
+pub fn verifer_randomness_phase_challenge<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>) -> Result<()> {
+
+    let m: ProverRandomnessComm = read_message(stream)?;
+    state.transcript.absorb(b"coin-commitment", &m);
+    let mut rng = state.transcript.rng(challenges::COIN, &[state.noise_flips.len() as u64], &mut state.rng);
+
+    let _cf_start = Instant::now();
+    state.player_b = rng.gen_range(0..2);
+    state.randomness_coin_flip_agg_duration += _cf_start.elapsed();
+
+    let _start = Instant::now();
+    let (sigma_verifier, sigma_challenge) = bit_sigma::challenge(&mut rng, &m.commitment);
+
+    state.sigma_verifier = sigma_verifier;
+    state.randomness_bit_sigma_verify_duration += _start.elapsed();
+
+    let challenge_m = VerifierRandomnessChallenge { player_b: state.player_b, sigma_challenge };
+    state.transcript.absorb(b"coin-challenge", &challenge_m);
+    write_message(
+        stream, &challenge_m
+    )?;
+    Ok(())
+}
+
+/// Randomness phase with co-verifiers: commit to a contribution to the coin flip, reveal it once every verifier has
+/// committed, and take the prover's coin as challenged by all of their contributions together.
+pub fn verifier_joint_randomness_phase_challenge<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, verifiers: u32) -> Result<()> {
+
+    let m: ProverRandomnessComm = read_message(stream)?;
+    state.transcript.absorb(b"coin-commitment", &m);
+
+    let _cf_start = Instant::now();
+    let contribution = joint::contribute(&mut state.transcript.rng(challenges::COIN, &[state.noise_flips.len() as u64], &mut state.rng));
+    let digest = joint::digest(&contribution);
+    state.randomness_coin_flip_agg_duration += _cf_start.elapsed();
+
+    write_message(stream, &ContributionCommitmentMessage { digest })?;
+    let digests: ContributionDigestsMessage = read_message(stream)?;
+    state.transcript.absorb(b"contribution-digests", &digests);
+    // the prover must relay this verifier's own digest, or its contribution could be left out
+    if !digests.digests.contains(&digest) {
+        return Err(Error::Protocol("Contribution digests do not include this verifier's".to_string()));
+    }
+
+    write_message(stream, &contribution)?;
+    let contributions: ContributionsMessage = read_message(stream)?;
+    state.transcript.absorb(b"contributions", &contributions);
+    let (player_b, sigma_challenge) = joint::combine(verifiers as usize, &digests.digests, &contributions.contributions)
+        .map_err(|e| Error::Protocol(format!("Joint coin flip failed: {}", e)))?;
+
+    let _start = Instant::now();
+    state.player_b = player_b;
+    state.sigma_verifier = bit_sigma::verifier_for(&m.commitment, &sigma_challenge);
+    state.randomness_bit_sigma_verify_duration += _start.elapsed();
+    Ok(())
+}
+
+/// Randomness phase: check prover responses
+pub fn verifier_randomness_phase_check<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>) -> Result<RistrettoPoint> {
+
+    let resp_msg: ProverRandomnessResponse = read_message(stream)?;
+    state.transcript.absorb(b"coin-response", &resp_msg);
+
+    let _cf_start = Instant::now();
+    if state.player_b == 0 {
+        if resp_msg.final_commitment != state.sigma_verifier.b_comm{
+            write_message(
+                stream, &VerifierCheckMessage {success: false}
+            )?;
+            return Err(Error::Verification("player_b = 0, final_commitment != b_comm".to_string()));
+        }
+    } else {
+        if resp_msg.final_commitment != state.C1 + state.sigma_verifier.b_comm.neg() {
+            write_message(
+                stream, &VerifierCheckMessage {success: false}
+            )?;
+            return Err(Error::Verification("player_b = 1, final_commitment != C1 + dealer_b_comm.neg()".to_string()));
+        }
+    }
+    state.randomness_coin_flip_agg_duration += _cf_start.elapsed();
+
+    let _start = Instant::now();
+    let sigma_verified = bit_sigma::verify(&state.pedersen_pp, &mut state.sigma_verifier, &resp_msg.sigma_response);
+    state.randomness_bit_sigma_verify_duration += _start.elapsed();
+
+    write_message(
+        stream, &VerifierCheckMessage {success: sigma_verified.is_ok()}
+    )?;
+
+    sigma_verified.map(|()| resp_msg.final_commitment)
+}
+
+/// Randomness phase: check the prover's responses for all of `coins`, each flipped by the verifiers' coin and
+/// challenged as its bit sigma verifier holds, with the bit sigma responses verified in one batch
+fn check_coins<T: PrimInt + Hash>(state: &mut VerifierState<T>, coins: &[(u32, bit_sigma::Verifier)], responses: &[ProverRandomnessResponse]) -> Result<()> {
+    if responses.len() != coins.len() {
+        return Err(Error::Protocol(format!("Prover sent {} coin responses for {} coins", responses.len(), coins.len())));
+    }
+    for (i, ((player_b, sigma_verifier), response)) in coins.iter().zip(responses).enumerate() {
+        let flipped = match player_b {
+            0 => sigma_verifier.b_comm,
+            _ => state.C1 + sigma_verifier.b_comm.neg(),
+        };
+        if response.final_commitment != flipped {
+            return Err(Error::Verification(format!("Coin {}: player_b = {}, final_commitment does not flip the committed coin", i, player_b)));
+        }
+    }
+
+    let pairs: Vec<_> = coins.iter().zip(responses).map(|((_, sigma_verifier), response)| (sigma_verifier, &response.sigma_response)).collect();
+    bit_sigma::verify_batch(&mut state.rng, &state.pedersen_pp, &pairs)
+        .map_err(|(i, e)| Error::Verification(format!("Coin {}: {}", i, e)))
+}
+
+/// Randomness phase: check the prover's responses for all of `coins` from its one message, tell the prover whether they
+/// verified, and set the flipped coins aside for the noise
+pub(crate) fn verifier_check_coins<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, coins: &[(u32, bit_sigma::Verifier)]) -> Result<()> {
+    let m: ProverRandomnessResponses = read_message(stream)?;
+    state.transcript.absorb(b"coin-responses", &m);
+
+    let _start = Instant::now();
+    let checked = check_coins(state, coins, &m.responses);
+    state.randomness_bit_sigma_verify_duration += _start.elapsed();
+
+    write_message(
+        stream, &VerifierCheckMessage { success: checked.is_ok() }
+    )?;
+    checked?;
+
+    let _agg_start = Instant::now();
+    state.noise_flips.extend(m.responses.iter().map(|response| response.final_commitment));
+    state.randomness_coin_flip_agg_duration += _agg_start.elapsed();
+    Ok(())
+}
+
+/// Randomness phase with a single verifier: take the prover's commitments to all `n` coins, a window of
+/// `protocol::RANDOMNESS_WINDOW` at a time, in one message per window, reply with the flip and challenge of every coin
+/// of the window in one message, and take a checkpoint once its responses verify
+pub fn verifier_batched_randomness_phase<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    state.randomness_checkpoint = Some(Checkpoint::default());
+    state.randomness_window = None;
+    flip_windows(state, stream, n)
+}
+
+/// Flip the windows of coins left until `n` are flipped, from the last checkpoint on
+fn flip_windows<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    while (state.noise_flips.len() as u32) < n {
+        let window = protocol::RANDOMNESS_WINDOW.min(n - state.noise_flips.len() as u32);
+        let m: ProverRandomnessComms = read_message(stream)?;
+        check_count(stream, || "noise coins in a window".to_string(), window as u64, m.commitments.len() as u64)?;
+
+        state.transcript.absorb(b"coin-commitments", &m);
+
+        let _start = Instant::now();
+        let mut coins = Vec::with_capacity(window as usize);
+        let mut challenges = Vec::with_capacity(window as usize);
+        for (i, commitment) in m.commitments.iter().enumerate() {
+            let mut rng = state.transcript.rng(challenges::COIN, &[(state.noise_flips.len() + i) as u64], &mut state.rng);
+            let player_b = rng.gen_range(0..2);
+            let (sigma_verifier, sigma_challenge) = bit_sigma::challenge(&mut rng, commitment);
+            coins.push((player_b, sigma_verifier));
+            challenges.push(VerifierRandomnessChallenge { player_b, sigma_challenge });
+        }
+        state.randomness_bit_sigma_verify_duration += _start.elapsed();
+
+        let challenges_m = VerifierRandomnessChallenges { challenges };
+        state.transcript.absorb(b"coin-challenges", &challenges_m);
+        let encoded = stream.wire_format().encode(&challenges_m);
+        // kept until the window is accepted, so a resumed phase challenges its coins the same way
+        state.randomness_window = Some((coins, challenges_m));
+        write_to_stream(stream, &encoded)?;
+        accept_window(state, stream)?;
+    }
+    Ok(())
+}
+
+/// Check the prover's responses to the window challenged last, and take a checkpoint once they verify; the window is
+/// kept for a resumed phase otherwise
+fn accept_window<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>) -> Result<()> {
+    let Some((coins, challenges)) = state.randomness_window.take() else {
+        return Ok(());
+    };
+    let flipped = state.noise_flips.len();
+    if let Err(e) = verifier_check_coins(state, stream, &coins) {
+        state.randomness_window = Some((coins, challenges));
+        return Err(e);
+    }
+    let checkpoint = state.randomness_checkpoint.unwrap_or_default().advance(&state.noise_flips[flipped..]);
+    state.randomness_checkpoint = Some(checkpoint);
+    Ok(())
+}
+
+/// Randomness phase with a single verifier, resumed once `Framed::reconnect` has moved `stream` to a new connection
+/// after the last one dropped: send the last checkpoint, with the challenges to the window not accepted yet if any,
+/// check the prover caught up with it, and go on to all `n` coins
+pub fn verifier_resume_randomness_phase<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, n: u32) -> Result<()> {
+    let Some(checkpoint) = state.randomness_checkpoint else {
+        return Err(Error::Config("No randomness phase to resume".to_string()));
+    };
+    let (coins, challenges) = state.randomness_window.take().unzip();
+    let resume_m = ResumeRandomnessMessage { checkpoint, challenges };
+    let encoded = stream.wire_format().encode(&resume_m);
+    state.randomness_window = coins.zip(resume_m.challenges);
+    write_to_stream(stream, &encoded)?;
+
+    let m: CheckpointMessage = read_message(stream)?;
+    if m.checkpoint != checkpoint {
+        return Err(abort(stream, AbortReason::CheckpointMismatch { prover: m.checkpoint.coins, verifier: checkpoint.coins }));
+    }
+    info!("resumed randomness phase after {} of {} coins", checkpoint.coins, n);
+    accept_window(state, stream)?;
+    flip_windows(state, stream, n)
+}
+
+/// Randomness phase: flip `n` noise coins with the prover, all in one round with a single verifier, or one after another
+/// jointly with the co-verifiers if there is more than one verifier, and set their commitments aside for the noise
+pub fn verifier_randomness_phase<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, n: u32, verifiers: u32) -> Result<()> {
+    state.noise_flips.clear();
+
+    if verifiers == 1 {
+        return verifier_batched_randomness_phase(state, stream, n);
+    }
+    for _ in 0..n {
+        verifier_joint_randomness_phase_challenge(state, stream, verifiers)?;
+        let c = verifier_randomness_phase_check(state, stream)?;
+        let _agg_start = Instant::now();
+        state.noise_flips.push(c);
+        state.randomness_coin_flip_agg_duration += _agg_start.elapsed();
+    }
+    Ok(())
+}
+
+/// Randomness phase driven by a randomness beacon: take the prover's commitments to all `n` coins, then name the first
+/// beacon round published after them, whose randomness flips and challenges every coin
+pub fn verifier_beacon_randomness_phase<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, beacon: &Beacon, n: u32) -> Result<()> {
+    state.noise_flips.clear();
+
+    let m: ProverRandomnessComms = read_message(stream)?;
+    check_count(stream, || "noise coins".to_string(), n as u64, m.commitments.len() as u64)?;
+
+    state.transcript.absorb(b"coin-commitments", &m);
+
+    let round = beacon.next_round(SystemTime::now());
+    state.transcript.absorb(b"beacon-round", &round);
+    info!("beacon round {}", round);
+    write_message(stream, &BeaconRoundMessage { round })?;
+    let randomness = beacon.randomness(round).map_err(|e| Error::Protocol(format!("Beacon {}", e)))?;
+
+    let coins: Vec<_> = m.commitments.iter().zip(beacon::coins(&randomness, n))
+        .map(|(commitment, (player_b, sigma_challenge))| (player_b, bit_sigma::verifier_for(commitment, &sigma_challenge)))
+        .collect();
+    verifier_check_coins(state, stream, &coins)
+}
+
+/// Randomness phase: combine the flipped coins' commitments into the noise commitment by the session's noise mechanism,
+/// centered on zero. If the mechanism multiplies coins, check that every product the prover commits to takes the
+/// factors it should, challenge its product sigma proof, and tell the prover whether they all verified.
+pub fn verifier_randomness_phase_combine<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, params: &SessionParams) -> Result<()> {
+    combine_noise(state, stream, params, |state, stream, commitments| {
+        state.transcript.absorb(b"noise-product-commitments", commitments);
+        let (verifiers, challenges) = commitments.iter().enumerate()
+            .map(|(i, commitment)| product_sigma::challenge(&mut state.transcript.rng(challenges::NOISE_PRODUCT, &[i as u64], &mut state.rng), commitment))
+            .unzip();
+        let challenges_m = NoiseProductChallenges { challenges };
+        state.transcript.absorb(b"noise-product-challenges", &challenges_m);
+        write_message(stream, &challenges_m)?;
+        Ok(verifiers)
+    })
+}
+
+/// Combine the flipped coins as `verifier_randomness_phase_combine` does, with `exchange` challenging the products the
+/// prover commits to and returning the verifiers that check its responses, e.g. for challenges read back from a
+/// recorded session
+pub(crate) fn combine_noise<T: PrimInt + Hash, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, params: &SessionParams,
+                                                            exchange: impl FnOnce(&mut VerifierState<T>, &mut Framed<S>, &[product_sigma::Commitment]) -> Result<Vec<product_sigma::Verifier>>) -> Result<()> {
+    let noise = params.noise();
+    let coins = std::mem::take(&mut state.noise_flips);
+    if coins.len() != noise.coins() as usize {
+        return Err(Error::Protocol(format!("Flipped {} coins, but the noise takes {}", coins.len(), noise.coins())));
+    }
+    let one = state.C1;
+    let not = |c: RistrettoPoint| one - c;
+
+    let gates = noise.gates();
+    let mut products = Vec::with_capacity(gates.len());
+    if !gates.is_empty() {
+        let m: NoiseProductCommitments = read_message(stream)?;
+        if m.commitments.len() != gates.len() {
+            return Err(Error::Protocol(format!("Prover committed to {} products of coins, expected {}", m.commitments.len(), gates.len())));
+        }
+
+        let _start = Instant::now();
+        for (i, ((a, b), commitment)) in gates.iter().zip(&m.commitments).enumerate() {
+            let (c_1, c_2, c_3) = commitment.statement();
+            if (c_1, c_2) != (a.resolve(&coins, &products, not), b.resolve(&coins, &products, not)) {
+                return Err(Error::Protocol(format!("Noise product {} does not link up with the coins", i)));
+            }
+            products.push(c_3);
+        }
+        state.randomness_bit_sigma_verify_duration += _start.elapsed();
+        let mut verifiers = exchange(state, stream, &m.commitments)?;
+
+        let m: NoiseProductResponses = read_message(stream)?;
+        if m.responses.len() != gates.len() {
+            return Err(Error::Protocol(format!("Prover sent {} product responses for {} products", m.responses.len(), gates.len())));
+        }
+        let _start = Instant::now();
+        let checked = verifiers.iter_mut().zip(&m.responses).enumerate()
+            .try_for_each(|(i, (v, r))| product_sigma::verify(&state.pedersen_pp, v, r)
+                .map_err(|e| Error::Verification(format!("Noise product {}: {}", i, e))));
+        state.randomness_bit_sigma_verify_duration += _start.elapsed();
+        write_message(stream, &VerifierCheckMessage { success: checked.is_ok() })?;
+        checked?;
+    }
+
+    let _start = Instant::now();
+    let mut comm = -pedersen::commit_with_r(&Scalar::from(noise.offset()), &Scalar::ZERO, &state.pedersen_pp);
+    for (factor, weight) in noise.terms() {
+        let weight = if weight < 0 { -Scalar::from(weight.unsigned_abs()) } else { Scalar::from(weight as u64) };
+        comm += weight * factor.resolve(&coins, &products, not);
+    }
+    state.randomness_bit_comm = comm;
+    state.randomness_coin_flip_agg_duration += _start.elapsed();
+    state.noise = noise;
+    state.epsilon = params.epsilon;
+    state.delta = calibration::delta(params.db_size, params.delta);
+    Ok(())
+}
+
+//
+// -- QUERYING PHASE --
+//
+
+/// Generate a random query polynomial with a given sparsity, choosing random monomials and random integer coefficients
+/// whose absolute values sum to `sensitivity`, the most the prover answers, failing if the prover committed to fewer
+/// monomials than that
+pub fn verifier_generate_query<T: PrimInt + Eq + Hash + Copy>(state: &mut VerifierState<T>, sparsity: u32, sensitivity: u32) -> Result<HashMap<Monomial<T>, Scalar>> {
+
+    if sparsity as usize > state.monomial_commitments.len() {
+        return Err(Error::Protocol(format!("Query sparsity {} exceeds the {} committed monomials", sparsity, state.monomial_commitments.len())));
+    }
+    let mut coefficients: HashMap<Monomial<T>, Scalar> = HashMap::new();
+    let mut left = sensitivity as u64;
+    for i in 0..sparsity {
+        let mut random_id = state.monomial_commitments.keys().choose(&mut state.rng).unwrap();
+        while coefficients.contains_key(random_id) {
+            random_id = state.monomial_commitments.keys().choose(&mut state.rng).unwrap();
+        }
+        // the last monomial takes whatever of the sensitivity the others left
+        let magnitude = if i + 1 == sparsity { left } else { state.rng.gen_range(0..=left) };
+        left -= magnitude;
+        let coeff = if state.rng.gen() { Scalar::from(magnitude) } else { -Scalar::from(magnitude) };
+        coefficients.insert(*random_id, coeff);
+    }
+
+    Ok(coefficients)
+}
+
+pub fn verifier_send_query<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, query_coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool) -> Result<()>
+where T: PrimInt + Eq + Hash + Clone + Serialize
+{
+    let m = QueryMessage::<T> {
+        coefficients: query_coefficients.clone(),
+        snapshot: state.snapshot,
+        dataset: state.dataset.clone(),
+        distinct,
+    };
+    write_message(
+        stream, &m
+    )
+}
+
+/// Having received a response from the prover, verify the query commitments. Unless answers are returned in the clear,
+/// the verifier does not learn the answer, and once verified keeps what the analyst needs in `encrypted_answers`.
+/// A distinct query is checked against the sum of the sketch's buckets. An answer that fails to verify is reported in
+/// the result rather than as an error. A query the session has already asked is checked against the noise it was first
+/// answered on, and charges no more of the budget.
+pub fn verifier_check_query<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, query_coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool, mode: AnswerMode) -> Result<(QueryResult, Duration, Duration)>
+where T: PrimInt + Eq + Hash
+{
+    let (mut query_result, noise_comm) = query_noise(state, query_coefficients, distinct);
+
+    let start_homomorphic = Instant::now();
+    let (mut coefficients, mut monomial_comms) = query_terms(state, query_coefficients)?;
+    if distinct {
+        coefficients.extend(state.sketch.iter().map(|_| Scalar::ONE));
+        monomial_comms.extend_from_slice(&state.sketch);
+    }
+    let query_comm = query::commitment::<Ristretto>(noise_comm, &coefficients, &monomial_comms);
+    let duration_homomorphic = start_homomorphic.elapsed();
+
+    let (result, duration_verify) = match mode {
+        AnswerMode::Plain => {
+            let query_answer_m: QueryAnswerMessage = read_message(stream)?;
+
+            let query_answer = query_answer_m.answer;
+            let query_proof = query_answer_m.proof;
+
+            // measure verification check
+            let start_verify = Instant::now();
+            let verified = query::verify_answer(&state.pedersen_pp, &query_comm, &query_answer, &query_proof);
+            let duration_verify = start_verify.elapsed();
+            if verified && !distinct {
+                keep_certificate(state, query_coefficients, noise_comm, query_answer, query_proof);
+            }
+            query_result.value = result::to_i64(&query_answer);
+            query_result.interval = query_result.value.and_then(|v| result::interval(v, state.noise.bound(state.confidence)));
+            (verified, duration_verify)
+        },
+        AnswerMode::Encrypted(pk) => {
+            let query_answer_m: EncryptedQueryAnswerMessage = read_message(stream)?;
+
+            let start_verify = Instant::now();
+            let result = analyst::verify(&state.pedersen_pp, &pk, &query_comm, &query_answer_m.answer);
+            let duration_verify = start_verify.elapsed();
+            if result {
+                state.encrypted_answers.push(SavedAnswer::Encrypted(query_answer_m.answer));
+            }
+            (result, duration_verify)
+        },
+        AnswerMode::ElGamal(_) => {
+            let query_answer_m: ElGamalQueryAnswerMessage = read_message(stream)?;
+
+            let start_verify = Instant::now();
+            let result = elgamal::verify_handle(&state.pedersen_pp, &query_comm, &query_answer_m.handle, &query_answer_m.proof);
+            let duration_verify = start_verify.elapsed();
+            if result {
+                state.encrypted_answers.push(SavedAnswer::ElGamal(elgamal::Ciphertext { handle: query_answer_m.handle, commitment: query_comm }));
+            }
+            (result, duration_verify)
+        },
+    };
+
+    query_result.verified = result;
+    Ok((query_result, duration_homomorphic, duration_verify))
+}
+
+/// Send a batch of queries with the given monomial coefficients to the prover, to be answered in the clear
+pub fn verifier_send_batch<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, queries: &[HashMap<Monomial<T>, Scalar>]) -> Result<()>
+where T: PrimInt + Eq + Hash + Clone + Serialize
+{
+    let m = BatchQueryMessage::<T> {
+        queries: queries.to_vec(),
+        snapshot: state.snapshot,
+        dataset: state.dataset.clone(),
+    };
+    write_message(
+        stream, &m
+    )
+}
+
+/// Having received the prover's answers to a batch of queries, verify them all at once: the openings are weighed by
+/// random scalars and checked against the same combination of the query commitments, which holds for every opening
+/// but with negligible probability unless each does, in a single multiscalar multiplication. Only if the combination
+/// fails is each answer checked on its own, to tell which did not verify. Repeats are checked and charged as in
+/// `verifier_check_query`.
+pub fn verifier_check_batch<T, S: Transport>(state: &mut VerifierState<T>, stream: &mut Framed<S>, queries: &[HashMap<Monomial<T>, Scalar>]) -> Result<(Vec<QueryResult>, Duration, Duration)>
+where T: PrimInt + Eq + Hash
+{
+    let mut query_results = Vec::with_capacity(queries.len());
+    let mut terms = Vec::with_capacity(queries.len());
+    for query_coefficients in queries {
+        let (query_result, noise_comm) = query_noise(state, query_coefficients, false);
+        query_results.push(query_result);
+        terms.push((noise_comm, query_terms(state, query_coefficients)?));
+    }
+
+    let batch_answer_m: BatchQueryAnswerMessage = read_message(stream)?;
+    if batch_answer_m.answers.len() != queries.len() {
+        return Err(Error::Protocol(format!("Prover answered {} queries of a batch of {}", batch_answer_m.answers.len(), queries.len())));
+    }
+
+    // weigh each query's noise and monomial commitments by its random weight, merging the terms of shared monomials
+    let start_homomorphic = Instant::now();
+    let weights: Vec<Scalar> = queries.iter().map(|_| Scalar::random(&mut state.rng)).collect();
+    let mut combined: HashMap<&Monomial<T>, Scalar> = HashMap::new();
+    for (weight, query_coefficients) in weights.iter().zip(queries) {
+        for (monomial, monomial_coefficient) in query_coefficients {
+            *combined.entry(monomial).or_insert(Scalar::ZERO) += weight * monomial_coefficient;
+        }
+    }
+    let mut coefficients: Vec<Scalar> = combined.values().copied().collect();
+    let mut comms: Vec<RistrettoPoint> = combined.keys().map(|monomial| state.monomial_commitments[*monomial]).collect();
+    coefficients.extend_from_slice(&weights);
+    comms.extend(terms.iter().map(|(noise_comm, _)| *noise_comm));
+    let batch_comm = query::commitment::<Ristretto>(RistrettoPoint::default(), &coefficients, &comms);
+    let duration_homomorphic = start_homomorphic.elapsed();
+
+    let start_verify = Instant::now();
+    let (answer, proof) = weights.iter().zip(&batch_answer_m.answers)
+        .fold((Scalar::ZERO, Scalar::ZERO), |(answer, proof), (weight, a)| (answer + weight * a.answer, proof + weight * a.proof));
+    let verified = query::verify_answer(&state.pedersen_pp, &batch_comm, &answer, &proof);
+    for (((query_result, (noise_comm, (coefficients, monomial_comms))), a), query_coefficients) in query_results.iter_mut().zip(&terms).zip(&batch_answer_m.answers).zip(queries) {
+        query_result.verified = verified || {
+            let query_comm = query::commitment::<Ristretto>(*noise_comm, coefficients, monomial_comms);
+            query::verify_answer(&state.pedersen_pp, &query_comm, &a.answer, &a.proof)
+        };
+        if query_result.verified {
+            keep_certificate(state, query_coefficients, *noise_comm, a.answer, a.proof);
+        }
+        query_result.value = result::to_i64(&a.answer);
+        query_result.interval = query_result.value.and_then(|v| result::interval(v, state.noise.bound(state.confidence)));
+    }
+    let duration_verify = start_verify.elapsed();
+
+    Ok((query_results, duration_homomorphic, duration_verify))
+}
+
+/// Result of the query of `query_coefficients` before its answer is read, and the noise commitment it is checked
+/// against: the current one for a new query, and the one it was first answered on for a repeat, which charges nothing
+fn query_noise<T: PrimInt + Hash>(state: &mut VerifierState<T>, query_coefficients: &HashMap<Monomial<T>, Scalar>, distinct: bool) -> (QueryResult, RistrettoPoint) {
+    let digest = cache::query_digest(query_coefficients, distinct);
+    let repeated = state.answered.contains_key(&digest);
+    let query_result = QueryResult {
+        value: None,
+        verified: false,
+        noise_std: state.noise.std(),
+        confidence: state.confidence,
+        interval: None,
+        epsilon_charged: if repeated { 0.0 } else { state.epsilon as f64 },
+        snapshot: state.snapshot,
+        repeated,
+    };
+    let noise_comm = *state.answered.entry(digest).or_insert(state.randomness_bit_comm);
+    (query_result, noise_comm)
+}
+
+/// Coefficients of the query and the commitments of the monomials they weigh, in the same order; fails if a monomial
+/// was never committed
+fn query_terms<T: PrimInt + Hash>(state: &VerifierState<T>, query_coefficients: &HashMap<Monomial<T>, Scalar>) -> Result<(Vec<Scalar>, Vec<RistrettoPoint>)> {
+    let mut coefficients = Vec::with_capacity(query_coefficients.len());
+    let mut monomial_comms = Vec::with_capacity(query_coefficients.len());
+    for (monomial, monomial_coefficient) in query_coefficients {
+        let Some(monomial_comm) = state.monomial_commitments.get(monomial) else {
+            return Err(Error::Config(format!("Monomial {} not found in monomial commitment map", monomial)));
+        };
+        coefficients.push(*monomial_coefficient);
+        monomial_comms.push(*monomial_comm);
+    }
+    Ok((coefficients, monomial_comms))
+}
+
+/// Keep the certificate of a verified answer to the query of `query_coefficients` on `noise_comm`, if the verifier asked
+/// for them
+fn keep_certificate<T: PrimInt + Hash>(state: &mut VerifierState<T>, query_coefficients: &HashMap<Monomial<T>, Scalar>, noise_comm: RistrettoPoint, answer: Scalar, proof: Scalar) {
+    let Some(certificates) = state.query_certificates.as_mut() else {
+        return;
+    };
+    // the answer verified, so every monomial of the query was committed
+    let mut terms: Vec<Term> = query_coefficients.iter()
+        .map(|(monomial, &coefficient)| Term {
+            monomial: monomial.mask().to_u128().unwrap(),
+            coefficient,
+            commitment: state.monomial_commitments[monomial],
+        })
+        .collect();
+    terms.sort_by_key(|term| term.monomial);
+    certificates.push(QueryCertificate {
+        g: state.pedersen_pp.g,
+        h: state.pedersen_pp.h,
+        dataset: state.dataset.clone(),
+        snapshot: state.snapshot,
+        epoch: state.epoch,
+        terms,
+        noise_commitment: noise_comm,
+        answer,
+        proof,
+        epsilon: state.epsilon,
+        delta: state.delta,
+        endorsements: Vec::new(),
+    });
+}
+
+//
+// -- SESSIONS --
+//
+
+/// Verifier side of one session of the plain protocol. `S` is the last phase the session completed, which decides the
+/// phases that can run next.
+pub struct VerifierSession<T: PrimInt + Hash, S> {
+    state: VerifierState<T>,
+    params: SessionParams,
+    phase: PhantomData<S>,
+}
+
+impl<T: PrimInt + Hash, S> VerifierSession<T, S> {
+    fn advance<N>(self) -> VerifierSession<T, N> {
+        VerifierSession { state: self.state, params: self.params, phase: PhantomData }
+    }
+
+    pub fn state(&self) -> &VerifierState<T> {
+        &self.state
+    }
+
+    /// Report the answers' intervals at `confidence` instead of the default 95%
+    pub fn set_confidence(&mut self, confidence: f64) {
+        self.state.confidence = confidence;
+    }
+
+    /// Keep a certificate of each answer that verifies from now on
+    pub fn keep_certificates(&mut self) {
+        self.state.query_certificates.get_or_insert_with(Vec::new);
+    }
+
+    /// Certificates of the answers verified since the last call
+    pub fn take_certificates(&mut self) -> Vec<QueryCertificate> {
+        self.state.query_certificates.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+impl<T: PrimInt + Hash + DeserializeOwned> VerifierSession<T, Setup> {
+    /// Join a session with `params`, reading the commitment parameters from the prover, which may refuse it or serve
+    /// other parameters
+    pub fn start<S: Transport>(stream: &mut Framed<S>, params: SessionParams) -> Result<Self> {
+        protocol::check_entries::<T>(&params)?;
+        stream.start_transcript();
+        let mut state = verifier_init();
+        mac::verifier_agree(&mut state.rng, stream, None)?;
+        verifier_setup(&mut state, stream, &params)?;
+        Ok(VerifierSession { state, params, phase: PhantomData })
+    }
+
+    /// Run the honest and then the dishonest commitment phase, checking every entry of the database
+    pub fn commit<S: Transport>(mut self, stream: &mut Framed<S>) -> Result<VerifierSession<T, protocol::Committed>> {
+        verifier_honest_commitment_phase(&mut self.state, stream, self.params.db_size, self.params.dimension, self.params.max_degree)?;
+        self.take_snapshot();
+
+        self.state.monomial_commitments.clear();
+        verifier_dishonest_commitment_phase(&mut self.state, stream, self.params.db_size, self.params.dimension, AuditMode::Full)?;
+        self.take_snapshot();
+        stream.enter_session(Side::Verifier);
+        Ok(self.advance())
+    }
+
+    fn take_snapshot(&mut self) {
+        self.state.snapshot = SnapshotId::of(self.state.monomial_commitments.iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+impl<T: PrimInt + Hash> VerifierSession<T, protocol::Committed> {
+    /// Flip the noise coins with the prover, as many as the session's epsilon and delta call for over its entries.
+    /// Called again once `Framed::reconnect` has moved `stream` to a new connection after the last one dropped, resume
+    /// from the last checkpoint instead.
+    pub fn flip_coins<S: Transport>(&mut self, stream: &mut Framed<S>) -> Result<()> {
+        match self.state.randomness_checkpoint {
+            None => verifier_randomness_phase(&mut self.state, stream, self.params.coins(), 1),
+            Some(_) => verifier_resume_randomness_phase(&mut self.state, stream, self.params.coins()),
+        }
+    }
+
+    /// Flip the noise coins, unless `flip_coins` already did, and combine them into the noise
+    pub fn randomness<S: Transport>(mut self, stream: &mut Framed<S>) -> Result<VerifierSession<T, NoiseReady>> {
+        if self.state.randomness_checkpoint.is_none() {
+            self.flip_coins(stream)?;
+        }
+        verifier_randomness_phase_combine(&mut self.state, stream, &self.params)?;
+        Ok(self.advance())
+    }
+}
+
+impl<T: PrimInt + Hash + Serialize> VerifierSession<T, NoiseReady> {
+    /// Random query of `sparsity` committed monomials, within the session's sensitivity
+    pub fn generate_query(&mut self, sparsity: u32) -> Result<HashMap<Monomial<T>, Scalar>> {
+        verifier_generate_query(&mut self.state, sparsity, self.params.sensitivity.max(1))
+    }
+
+    /// Send a query with the given monomial coefficients and check the prover's answer
+    pub fn query<S: Transport>(&mut self, stream: &mut Framed<S>, query_coefficients: &HashMap<Monomial<T>, Scalar>) -> Result<QueryResult> {
+        verifier_send_query(&mut self.state, stream, query_coefficients, false)?;
+        verifier_check_query(&mut self.state, stream, query_coefficients, false, AnswerMode::Plain)
+            .map(|(query_result, _, _)| query_result)
+    }
+
+    /// Send a batch of queries and check the prover's answers to them together
+    pub fn query_batch<S: Transport>(&mut self, stream: &mut Framed<S>, queries: &[HashMap<Monomial<T>, Scalar>]) -> Result<Vec<QueryResult>> {
+        verifier_send_batch(&mut self.state, stream, queries)?;
+        verifier_check_batch(&mut self.state, stream, queries)
+            .map(|(query_results, _, _)| query_results)
+    }
+}
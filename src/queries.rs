@@ -0,0 +1,144 @@
+/*!
+ * queries.rs
+ *
+ * Query files for the verifier: a JSON list of named queries, each either a predicate over the schema's attributes or
+ * the entry bits, as `--predicate` takes it, or a map of integer weights over such predicates, e.g.
+ * `[{"name": "smokers", "predicate": "smoker"}, {"name": "north less south", "coefficients": {"region=north": 1,
+ * "region=south": -1}}]`. A predicate counts the entries matching it, and a map of weights asks for the weighted sum of
+ * its predicates' counts, whose sensitivity is the sum of the weights' absolute values. The verifier asks the queries
+ * in order and writes each verified answer, decoded into the count or sum it stands for, to a JSON-lines file. At the
+ * verifier's interactive prompt, a query is typed as a predicate, or as a list of weighted monomials over the same
+ * literals, e.g. `2*smoker*region=north, -1*x3`, each monomial the conjunction of its literals.
+ */
+
+use curve25519_dalek::Scalar;
+use num_traits::PrimInt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, BufReader, Write};
+use std::path::Path;
+
+use crate::predicate::{self, Query};
+use crate::result::QueryResult;
+use crate::schema::Schema;
+
+/// Query of a query file as written, with exactly one of a predicate and a map of weights
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuerySpec {
+    name: String,
+    #[serde(default)]
+    predicate: Option<String>,
+    #[serde(default)]
+    coefficients: Option<BTreeMap<String, i64>>,
+}
+
+/// Query of a query file, compiled over the schema
+#[derive(Clone, Debug)]
+pub struct NamedQuery<T> {
+    pub name: String,
+    pub query: Query<T>,
+    /// L1 sensitivity of the count or sum the query asks for
+    pub sensitivity: u64,
+}
+
+/// Load the queries of the JSON file at `path`, compiling each over `schema` into monomials of up to `max_degree`;
+/// fails if a query is malformed, needs a monomial beyond it, or shares its name with an earlier one
+pub fn load<T: PrimInt + Hash>(path: &Path, schema: &Schema, max_degree: u32) -> Result<Vec<NamedQuery<T>>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let specs: Vec<QuerySpec> = serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if specs.is_empty() {
+        return Err(format!("{}: no queries", path.display()));
+    }
+    let mut names = HashSet::new();
+    specs.into_iter().map(|spec| {
+        if !names.insert(spec.name.clone()) {
+            return Err(format!("query {:?} is named twice", spec.name));
+        }
+        compile(&spec, schema, max_degree).map_err(|e| format!("query {:?}: {}", spec.name, e))
+    }).collect()
+}
+
+fn compile<T: PrimInt + Hash>(spec: &QuerySpec, schema: &Schema, max_degree: u32) -> Result<NamedQuery<T>, String> {
+    let weights = match (&spec.predicate, &spec.coefficients) {
+        (Some(p), None) => BTreeMap::from([(p.clone(), 1)]),
+        (None, Some(weights)) if !weights.is_empty() => weights.clone(),
+        (None, Some(_)) => return Err("has no coefficients".to_string()),
+        _ => return Err("needs either a predicate or coefficients".to_string()),
+    };
+
+    let mut coefficients = HashMap::new();
+    let mut constant = Scalar::ZERO;
+    let mut sensitivity = 0u64;
+    for (p, &weight) in &weights {
+        let query: Query<T> = predicate::parse_predicate(p)?.compile(schema, max_degree)?;
+        let weight_scalar = if weight < 0 { -Scalar::from(weight.unsigned_abs()) } else { Scalar::from(weight as u64) };
+        for (m, c) in query.coefficients {
+            *coefficients.entry(m).or_insert(Scalar::ZERO) += weight_scalar * c;
+        }
+        constant += weight_scalar * query.constant;
+        sensitivity = sensitivity.checked_add(weight.unsigned_abs()).ok_or("weights overflow")?;
+    }
+    coefficients.retain(|_, c| *c != Scalar::ZERO);
+    Ok(NamedQuery { name: spec.name.clone(), query: Query { coefficients, constant }, sensitivity })
+}
+
+/// Query typed at the prompt: a list of weighted monomials if `s` has a `*` or a `,`, and a predicate otherwise,
+/// compiled over `schema` into monomials of up to `max_degree` and named by what was typed
+pub fn parse_query<T: PrimInt + Hash>(s: &str, schema: &Schema, max_degree: u32) -> Result<NamedQuery<T>, String> {
+    let s = s.trim();
+    if !s.contains(['*', ',']) {
+        return compile(&QuerySpec { name: s.to_string(), predicate: Some(s.to_string()), coefficients: None }, schema, max_degree);
+    }
+    // each monomial is the conjunction of its literals, weighted as a predicate of a query file is
+    let mut weights = BTreeMap::new();
+    for term in s.split(',') {
+        let mut factors: Vec<&str> = term.split('*').map(str::trim).collect();
+        let weight = match factors[0].parse::<i64>() {
+            Ok(weight) => {
+                factors.remove(0);
+                weight
+            },
+            Err(_) => 1,
+        };
+        if factors.is_empty() || factors.contains(&"") {
+            return Err(format!("term {:?} has no monomial", term.trim()));
+        }
+        let total: &mut i64 = weights.entry(factors.join(" & ")).or_insert(0);
+        *total = total.checked_add(weight).ok_or("weights overflow")?;
+    }
+    compile(&QuerySpec { name: s.to_string(), predicate: None, coefficients: Some(weights) }, schema, max_degree)
+}
+
+/// Verified answer to a named query, as written to the answers file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NamedAnswer {
+    pub name: String,
+    /// Count or weighted sum the noisy answer stands for, if it is known and fits in 64 bits
+    pub answer: Option<i64>,
+    /// Interval around `answer` that holds the true count or sum at the result's confidence
+    pub interval: Option<(i64, i64)>,
+    pub result: QueryResult,
+}
+
+impl NamedAnswer {
+    /// Answer to `named` from the result of its query over `db_size` entries, adding its constant's share back
+    pub fn new<T>(named: &NamedQuery<T>, result: QueryResult, db_size: u32) -> Self {
+        let answer = result.value.and_then(|value| named.query.count(value, db_size));
+        let interval = answer.zip(result.value).zip(result.interval)
+            .and_then(|((answer, value), (low, high))| {
+                let shift = answer.checked_sub(value)?;
+                Some((low.checked_add(shift)?, high.checked_add(shift)?))
+            });
+        NamedAnswer { name: named.name.clone(), answer, interval, result }
+    }
+}
+
+/// Append `answer` to the JSON-lines file at `path`
+pub fn append_answer(path: &Path, answer: &NamedAnswer) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    serde_json::to_writer(&mut file, answer)?;
+    writeln!(file)
+}
@@ -0,0 +1,197 @@
+/*!
+ * query_certificate.rs
+ *
+ * Self-contained proofs of single query answers. Once an answer in the clear opens its query commitment, the verifier
+ * can export what the check took: the Pedersen generators, the query's coefficients with the commitment of each monomial
+ * they weigh, the noise commitment, and the answer with its opening. Anyone holding the session's Pedersen parameters
+ * re-verifies the answer from the certificate alone, recomputing the query commitment homomorphically as the verifier
+ * did. The certificate shows that the answer is the query evaluated on the committed monomials plus the committed
+ * noise; that those commitments came out of a verified session rests on the verifier that exported it.
+ *
+ * That verifier, or an auditor who re-verified the certificate, vouches for it by signing its guarantee: a statement
+ * binding digests of the commitments and of the query to the answer and to the (ε, δ) it was released under, so that
+ * whoever consumes a published statistic can check it was produced under certified differential privacy.
+ */
+
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::certificate::Endorsement;
+use crate::group::Ristretto;
+use crate::monomial::Monomial;
+use crate::pedersen;
+use crate::query;
+use crate::result;
+use crate::signature::{SigningKey, VerifyingKey};
+use crate::snapshot::SnapshotId;
+
+/// Domain separators for the digests of a certificate's commitments and query, and of its guarantee
+const COMMITMENTS_DOMAIN: &[u8] = b"certified-dp query commitments";
+const QUERY_DOMAIN: &[u8] = b"certified-dp query";
+const GUARANTEE_DOMAIN: &[u8] = b"certified-dp guarantee";
+
+/// Coefficient the query weighs a monomial with, and the monomial's commitment
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Term {
+    /// Mask of the monomial's variables, whatever the entries' width
+    pub monomial: u128,
+    pub coefficient: Scalar,
+    pub commitment: RistrettoPoint,
+}
+
+/// Certificate file of one verified answer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QueryCertificate {
+    pub g: RistrettoPoint,
+    pub h: RistrettoPoint,
+    pub dataset: String,
+    pub snapshot: SnapshotId,
+    pub epoch: u64,
+    /// Terms of the query, in order of monomial
+    pub terms: Vec<Term>,
+    /// Commitment to the noise the answer was released with, centered on zero
+    pub noise_commitment: RistrettoPoint,
+    pub answer: Scalar,
+    pub proof: Scalar,
+    /// Differential privacy epsilon and delta of the noise the answer was released with
+    pub epsilon: f32,
+    pub delta: f32,
+    /// Signatures on the certificate's guarantee
+    #[serde(default)]
+    pub endorsements: Vec<Endorsement>,
+}
+
+/// What a signature on a certificate vouches for: that the answer to the query of the `query` digest, on the
+/// commitments of the `commitments` digest rooted in the snapshot, was released under (`epsilon`, `delta`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Guarantee {
+    pub dataset: String,
+    pub snapshot: SnapshotId,
+    pub epoch: u64,
+    /// Digest of the generators, the monomial commitments, and the noise commitment
+    pub commitments: [u8; 32],
+    /// Digest of the query's monomials and coefficients
+    pub query: [u8; 32],
+    pub answer: Scalar,
+    pub epsilon: f32,
+    pub delta: f32,
+}
+
+impl Guarantee {
+    /// Digest the signatures are on
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(GUARANTEE_DOMAIN)
+            .chain_update(serde_json::to_vec(self).unwrap())
+            .finalize()
+            .into()
+    }
+
+    /// Hex digest, which the signer prints
+    pub fn fingerprint(&self) -> String {
+        self.digest().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl QueryCertificate {
+    /// Commitment to the noisy answer, as the verifier computed it
+    pub fn commitment(&self) -> RistrettoPoint {
+        let coefficients: Vec<Scalar> = self.terms.iter().map(|t| t.coefficient).collect();
+        let comms: Vec<RistrettoPoint> = self.terms.iter().map(|t| t.commitment).collect();
+        query::commitment::<Ristretto>(self.noise_commitment, &coefficients, &comms)
+    }
+
+    /// Whether the answer and proof open the query commitment under the certificate's generators
+    pub fn verify(&self) -> bool {
+        let pp = pedersen::PublicParams::<Ristretto>::new(self.g, self.h);
+        query::verify_answer(&pp, &self.commitment(), &self.answer, &self.proof)
+    }
+
+    /// Guarantee the certificate's signatures are on
+    pub fn guarantee(&self) -> Guarantee {
+        let mut commitments = Sha256::new()
+            .chain_update(COMMITMENTS_DOMAIN)
+            .chain_update(self.g.compress().as_bytes())
+            .chain_update(self.h.compress().as_bytes())
+            .chain_update(self.noise_commitment.compress().as_bytes());
+        let mut query = Sha256::new().chain_update(QUERY_DOMAIN);
+        for term in &self.terms {
+            commitments.update(term.monomial.to_le_bytes());
+            commitments.update(term.commitment.compress().as_bytes());
+            query.update(term.monomial.to_le_bytes());
+            query.update(term.coefficient.as_bytes());
+        }
+        Guarantee {
+            dataset: self.dataset.clone(),
+            snapshot: self.snapshot,
+            epoch: self.epoch,
+            commitments: commitments.finalize().into(),
+            query: query.finalize().into(),
+            answer: self.answer,
+            epsilon: self.epsilon,
+            delta: self.delta,
+        }
+    }
+
+    /// Sign the guarantee with `key`
+    pub fn sign(&mut self, key: &SigningKey) {
+        let signature = key.sign(&self.guarantee().digest());
+        self.endorsements.push(Endorsement { key: key.verifying_key(), signature });
+    }
+
+    /// Whether `key` signed the guarantee
+    pub fn signed_by(&self, key: &VerifyingKey) -> bool {
+        let digest = self.guarantee().digest();
+        self.endorsements.iter().any(|e| e.key == *key && e.key.verify(&digest, &e.signature))
+    }
+
+    /// Keys of the signatures on anything but the guarantee, e.g. one whose answer or epsilon was changed since
+    pub fn forged(&self) -> Vec<VerifyingKey> {
+        let digest = self.guarantee().digest();
+        self.endorsements.iter().filter(|e| !e.key.verify(&digest, &e.signature)).map(|e| e.key).collect()
+    }
+
+    /// Answer as a signed integer, if it fits in 64 bits
+    pub fn value(&self) -> Option<i64> {
+        result::to_i64(&self.answer)
+    }
+
+    /// Load the certificate at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write the certificate to `path`
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, serde_json::to_vec_pretty(self).unwrap()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// The query as a sum of weighted monomials, e.g. `3*x0*x2 - x1`, and `c` for any coefficient past 64 bits
+impl fmt::Display for QueryCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return f.write_str("0");
+        }
+        for (i, term) in self.terms.iter().enumerate() {
+            let monomial = Monomial::new(term.monomial);
+            match (result::to_i64(&term.coefficient), i) {
+                (Some(c), 0) if c < 0 => write!(f, "-")?,
+                (Some(c), _) if c < 0 => write!(f, " - ")?,
+                (_, 0) => {},
+                _ => write!(f, " + ")?,
+            }
+            match result::to_i64(&term.coefficient).map(i64::unsigned_abs) {
+                Some(1) => write!(f, "{}", monomial)?,
+                Some(c) => write!(f, "{}*{}", c, monomial)?,
+                None => write!(f, "c*{}", monomial)?,
+            }
+        }
+        Ok(())
+    }
+}
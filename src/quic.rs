@@ -0,0 +1,231 @@
+/*!
+ * quic.rs
+ *
+ * QUIC transport between the parties, with quinn, for WAN links where the single stream of a TCP connection stalls on
+ * each lost packet. Each frame crosses the connection on a unidirectional stream of its own, as a 4-byte length and the
+ * message, so frames sent back to back, like the per-entry trees of the dishonest commitment phase, travel in parallel
+ * and a lost packet only holds up the frame it carried. A party reads the streams as they arrive and hands the frames
+ * to the protocol in the order they were opened, so the phases, their transcripts, and the certificates signed over
+ * them are the same as over TCP. QUIC is always encrypted, so both parties take the TLS configurations of
+ * `certified_dp::tls`. The connection runs on a tokio runtime of its own, and is handed to the protocol as a blocking
+ * `Transport`, whose phase deadlines bound each read like any other's. Only built with the `quic` feature.
+ */
+
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{Connection, ConnectionError, Endpoint, RecvStream, TransportConfig};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{self, Runtime};
+use tokio::task::JoinHandle;
+
+use crate::tls::{ClientConfig, ServerConfig};
+use crate::transport::{FrameTooLarge, Transport};
+
+/// Longest a party waits as it closes a session, or the prover stops serving, for its last frames to reach the peer
+const LINGER: Duration = Duration::from_secs(10);
+
+/// Interval of the packets keeping a connection open while a party computes without sending
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+
+/// One end of a session over QUIC, either the prover's end of a connection it accepted or the verifier's
+pub struct QuicStream {
+    connection: Connection,
+    incoming: Receiver<io::Result<Vec<u8>>>,
+    /// Frames sent and not yet acknowledged by the peer
+    sending: Vec<JoinHandle<()>>,
+    timeout: Option<Duration>,
+    runtime: Arc<Runtime>,
+    /// The verifier's endpoint, closed with the session
+    endpoint: Option<Endpoint>,
+}
+
+impl QuicStream {
+    /// Verifier's end of a new session with the prover at `address`, whose certificate `config` must accept, refusing
+    /// frames from it over `limit` bytes
+    pub fn connect(address: SocketAddr, config: Arc<ClientConfig>, limit: usize) -> io::Result<QuicStream> {
+        let runtime = Arc::new(runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()?);
+        let mut client = quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(config).map_err(io::Error::other)?));
+        client.transport_config(transport_config());
+        let (endpoint, connection) = runtime.block_on(async {
+            let local: IpAddr = if address.is_ipv6() { Ipv6Addr::UNSPECIFIED.into() } else { Ipv4Addr::UNSPECIFIED.into() };
+            let endpoint = Endpoint::client(SocketAddr::new(local, 0))?;
+            let connecting = endpoint.connect_with(client, address, &address.ip().to_string()).map_err(io::Error::other)?;
+            let connection = tokio::time::timeout(Duration::from_secs(10), connecting).await
+                .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "no QUIC server answered"))?
+                .map_err(io::Error::other)?;
+            Ok::<_, io::Error>((endpoint, connection))
+        })?;
+        let incoming = {
+            let _runtime = runtime.enter();
+            forward(connection.clone(), limit)
+        };
+        Ok(QuicStream { connection, incoming, sending: Vec::new(), timeout: None, runtime, endpoint: Some(endpoint) })
+    }
+}
+
+impl Transport for QuicStream {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        // streams are opened in order, and the peer reads them in that order whichever finishes first
+        let mut stream = self.runtime.block_on(self.connection.open_uni()).map_err(io::Error::from)?;
+        let mut data = Vec::with_capacity(4 + frame.len());
+        data.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        data.extend_from_slice(frame);
+        self.sending.retain(|task| !task.is_finished());
+        self.sending.push(self.runtime.spawn(async move {
+            // a failed write loses the connection, which fails the reads after it
+            if stream.write_all(&data).await.is_ok() && stream.finish().is_ok() {
+                let _ = stream.stopped().await;
+            }
+        }));
+        Ok(())
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let disconnected = || io::Error::new(io::ErrorKind::UnexpectedEof, "peer disconnected");
+        let frame = match self.timeout {
+            Some(timeout) => self.incoming.recv_timeout(timeout).unwrap_or_else(|e| match e {
+                RecvTimeoutError::Timeout => Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out")),
+                RecvTimeoutError::Disconnected => Err(disconnected()),
+            })?,
+            None => self.incoming.recv().unwrap_or_else(|_| Err(disconnected()))?,
+        };
+
+        if frame.len() > limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size: frame.len(), limit }));
+        }
+        Ok(frame)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for QuicStream {
+    /// Close the connection once the peer has acknowledged every frame sent, which it can still read after the close;
+    /// the verifier then waits for the close to reach the prover before its runtime shuts down
+    fn drop(&mut self) {
+        let sending = std::mem::take(&mut self.sending);
+        let endpoint = self.endpoint.take();
+        let connection = self.connection.clone();
+        self.runtime.block_on(async move {
+            let _ = tokio::time::timeout(LINGER, async {
+                for task in sending {
+                    let _ = task.await;
+                }
+                connection.close(0u32.into(), b"session over");
+                if let Some(endpoint) = endpoint {
+                    endpoint.wait_idle().await;
+                }
+            }).await;
+        });
+    }
+}
+
+/// Prover's QUIC endpoint, accepting the connections verifiers open in order
+pub struct QuicListener {
+    endpoint: Endpoint,
+    runtime: Arc<Runtime>,
+    limit: usize,
+}
+
+impl QuicListener {
+    /// Serve QUIC on `address` with the certificate of `config`, refusing frames over `limit` bytes
+    pub fn bind(address: &str, config: Arc<ServerConfig>, limit: usize) -> io::Result<QuicListener> {
+        let address = address.parse::<SocketAddr>().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let runtime = Arc::new(runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()?);
+        let mut server = quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(config).map_err(io::Error::other)?));
+        server.transport_config(transport_config());
+        let endpoint = {
+            let _runtime = runtime.enter();
+            Endpoint::server(server, address)?
+        };
+        Ok(QuicListener { endpoint, runtime, limit })
+    }
+
+    /// Address the endpoint listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.endpoint.local_addr().expect("a bound endpoint has an address")
+    }
+
+    /// Wait for a verifier to open its next session, running the handshake on the connection it opens
+    pub fn accept(&self) -> io::Result<QuicStream> {
+        let connection = self.runtime.block_on(async {
+            let incoming = self.endpoint.accept().await
+                .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "QUIC endpoint closed"))?;
+            incoming.await.map_err(io::Error::other)
+        })?;
+        let incoming = {
+            let _runtime = self.runtime.enter();
+            forward(connection.clone(), self.limit)
+        };
+        Ok(QuicStream { connection, incoming, sending: Vec::new(), timeout: None, runtime: self.runtime.clone(), endpoint: None })
+    }
+}
+
+impl Drop for QuicListener {
+    /// Wait for the connections' closes to reach their verifiers, since the prover exits right after the last session
+    fn drop(&mut self) {
+        let endpoint = self.endpoint.clone();
+        self.runtime.block_on(async move {
+            let _ = tokio::time::timeout(LINGER, endpoint.wait_idle()).await;
+        });
+    }
+}
+
+/// Both parties' transport parameters: keep-alives, so a connection outlives the phases in which a party only computes
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config.keep_alive_interval(Some(KEEP_ALIVE));
+    Arc::new(config)
+}
+
+/// Read the peer's streams as they arrive, forwarding their frames, in the order the streams were opened, to a channel
+/// the protocol reads without a runtime, which ends once the peer closes the connection and its streams are drained
+fn forward(connection: Connection, limit: usize) -> Receiver<io::Result<Vec<u8>>> {
+    let (tx, rx) = mpsc::channel();
+    let (reads, mut ordered) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            match connection.accept_uni().await {
+                Ok(stream) => {
+                    if reads.send(tokio::spawn(read_frame(stream, limit))).is_err() {
+                        break;
+                    }
+                },
+                Err(ConnectionError::ApplicationClosed(_) | ConnectionError::LocallyClosed) => break,
+                Err(e) => {
+                    let _ = reads.send(tokio::spawn(async move { Err(io::Error::from(e)) }));
+                    break;
+                },
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Some(read) = ordered.recv().await {
+            let frame = read.await.unwrap_or_else(|e| Err(io::Error::other(e)));
+            let failed = frame.is_err();
+            if tx.send(frame).is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Read the frame on one of the peer's streams, refusing one over `limit` bytes before reading it
+async fn read_frame(mut stream: RecvStream, limit: usize) -> io::Result<Vec<u8>> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length).await.map_err(io::Error::other)?;
+    let size = u32::from_be_bytes(length) as usize;
+    if size > limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size, limit }));
+    }
+    let mut frame = vec![0u8; size];
+    stream.read_exact(&mut frame).await.map_err(io::Error::other)?;
+    Ok(frame)
+}
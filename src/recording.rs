@@ -0,0 +1,131 @@
+/*!
+ * recording.rs
+ *
+ * Recordings of every frame a party sends and receives, for debugging a session, reproducing it, or handing it to a
+ * third party to audit. Once a process opens a recording, each frame crossing a session that the thread running it has
+ * started recording is appended to the file as one JSON line: the party's side, the session, its phase, whether the
+ * frame was sent or received, when, and the frame's bytes in hex, as the protocol encoded them before any compression.
+ * Sessions served concurrently write to the same file, each line whole, and tell theirs apart by the session number.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::driver::{Phase, Side};
+
+/// File of the recording this process writes, if any
+static RECORDING: Mutex<Option<File>> = Mutex::new(None);
+
+thread_local! {
+    /// Side and session of the frames sent and received on this thread, once it starts recording them
+    static SESSION: Cell<Option<(Side, u32)>> = const { Cell::new(None) };
+    /// Phase the frames sent and received on this thread belong to
+    static PHASE: Cell<Phase> = const { Cell::new(Phase::Setup) };
+}
+
+/// Which way a frame crossed the connection, as seen by the party that recorded it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One line of a recording
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+    pub side: Side,
+    pub session: u32,
+    pub phase: Phase,
+    pub direction: Direction,
+    /// Microseconds since the Unix epoch
+    pub time: u64,
+    /// The frame, in hex
+    pub frame: String,
+}
+
+impl RecordedFrame {
+    /// The frame's bytes
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        from_hex(&self.frame)
+    }
+}
+
+/// Record the frames of the sessions started from now on to `path`, appending to the file if it exists
+pub fn open(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *RECORDING.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Record the frames sent and received on this thread from now on, if a recording is open, as those of the given
+/// session, played as `side`, starting with its setup phase
+pub fn start(side: Side, session: u32) {
+    SESSION.with(|s| s.set(Some((side, session))));
+    set_phase(Phase::Setup);
+}
+
+/// Record the frames sent and received on this thread from now on as the given phase's
+pub fn set_phase(phase: Phase) {
+    PHASE.with(|p| p.set(phase));
+}
+
+/// Append `frame` to the recording, if one is open and this thread has started recording
+pub fn record(frame: &[u8], direction: Direction) -> io::Result<()> {
+    let Some((side, session)) = SESSION.with(|s| s.get()) else {
+        return Ok(());
+    };
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(file) = recording.as_mut() else {
+        return Ok(());
+    };
+    let line = RecordedFrame {
+        side,
+        session,
+        phase: PHASE.with(|p| p.get()),
+        direction,
+        time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64,
+        frame: to_hex(frame),
+    };
+    let mut line = serde_json::to_vec(&line).unwrap();
+    line.push(b'\n');
+    // one write per line, so the lines of concurrent sessions never interleave
+    file.write_all(&line)
+}
+
+/// Frames recorded to `path`, in the order they were recorded
+pub fn load(path: &Path) -> Result<Vec<RecordedFrame>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    BufReader::new(file).lines().enumerate()
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_json::from_str(&line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+/// Encode bytes in hex
+fn to_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(2 * bytes.len());
+    for b in bytes {
+        hex.push(DIGITS[(b >> 4) as usize] as char);
+        hex.push(DIGITS[(b & 15) as usize] as char);
+    }
+    hex
+}
+
+/// Decode a hex string
+fn from_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(format!("invalid hex of {} digits", s.len()));
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| format!("invalid hex: {}", e)))
+        .collect()
+}
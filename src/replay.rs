@@ -0,0 +1,430 @@
+/*!
+ * replay.rs
+ *
+ * Offline verification of a session recorded with `recording`, so an auditor can check a past session without the
+ * prover. Replaying hands the prover's recorded frames to the verifier's checks in the order the prover sent them, and
+ * takes each of the verifier's challenges, coin flips and queries from the verifier's recorded frames instead of
+ * drawing them: the generator seed is checked against the prover's commitment to its half, every entry's bit and
+ * product sigma proofs against the recorded challenges, every noise coin against the recorded flips, and every answer
+ * against the commitments and noise the session agreed on. Either party's recording of a session will do, since each
+ * holds the frames both of them sent.
+ *
+ * The replay follows the plain protocol as the executables run it: setup, with or without authentication, the honest
+ * and dishonest commitment phases with every entry proven, the randomness phase with a single verifier, and queries
+ * answered in the clear, alone or in batches. A session that ran anything else, e.g. a spot check, a certify phase, a
+ * randomness phase resumed over a new connection or encrypted answers, fails to replay rather than replaying only in
+ * part.
+ */
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use num_traits::PrimInt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::io;
+
+use crate::analyst::AnswerMode;
+use crate::bit_sigma;
+use crate::driver::{self, Phase, Side};
+use crate::error::{Error, Result};
+use crate::generators;
+use crate::messages::{read_message, AuthChallengeMessage, BatchQueryMessage, BitSigmaCombinedChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, Framed, KeyShareMessage, MonomialChallengeTree, MonomialCommitmentTree, MonomialResponseTree, NoiseProductChallenges, ProverRandomnessComms, QueryMessage, ReadyMessage, SeedContributionMessage, SeedRevealMessage, SessionParams, SetupMessage, VerifierCheckMessage, VerifierRandomnessChallenges, WireFormatMessage, WIRE_VERSION};
+use crate::monomial::{gen_monomial_map, Monomial, MonomialTree};
+use crate::pedersen;
+use crate::product_sigma;
+use crate::protocol::{self, verifier::{self as live, VerifierNode, VerifierState}};
+use crate::recording::{Direction, RecordedFrame};
+use crate::result::QueryResult;
+use crate::snapshot::SnapshotId;
+use crate::transport::Transport;
+
+/// What replaying a session verified
+#[derive(Debug)]
+pub struct Replayed {
+    /// Side whose recording was replayed
+    pub side: Side,
+    pub params: SessionParams,
+    pub epoch: u64,
+    /// Phases the session ran, in order
+    pub phases: Vec<Phase>,
+    /// Snapshot of the commitments the queries were answered on
+    pub snapshot: SnapshotId,
+    /// Result of each query, in the order asked
+    pub queries: Vec<QueryResult>,
+}
+
+impl Replayed {
+    /// Whether every answer verified, on top of the checks whose failure fails the replay
+    pub fn verified(&self) -> bool {
+        self.queries.iter().all(|q| q.verified)
+    }
+}
+
+/// Frames of one phase of a recorded session, split by the party that sent them
+struct PhaseFrames {
+    phase: Phase,
+    prover: VecDeque<Vec<u8>>,
+    verifier: VecDeque<Vec<u8>>,
+}
+
+/// Recorded session as a `Transport` for the verifier's checks: receiving takes the prover's next frame of the current
+/// phase, and sending takes the verifier's, whose content the checks have already decided
+struct Replay {
+    phases: VecDeque<PhaseFrames>,
+    current: PhaseFrames,
+}
+
+impl Replay {
+    /// Replay the frames one side recorded of a session, in the order it recorded them
+    fn new<'a>(frames: impl IntoIterator<Item = &'a RecordedFrame>) -> Result<Self> {
+        let mut phases: VecDeque<PhaseFrames> = VecDeque::new();
+        for frame in frames {
+            let bytes = frame.bytes().map_err(|e| Error::Protocol(format!("Recorded frame is not valid: {}", e)))?;
+            if phases.back().is_none_or(|p| p.phase != frame.phase) {
+                // a phase the session already left only comes back if the file recorded the session number twice
+                if phases.iter().any(|p| p.phase == frame.phase) {
+                    return Err(Error::Protocol(format!("{} phase recorded twice, e.g. by two runs appending to the same file", frame.phase.name())));
+                }
+                phases.push_back(PhaseFrames { phase: frame.phase, prover: VecDeque::new(), verifier: VecDeque::new() });
+            }
+            let phase = phases.back_mut().unwrap();
+            match (frame.side, frame.direction) {
+                (Side::Prover, Direction::Sent) | (Side::Verifier, Direction::Received) => phase.prover.push_back(bytes),
+                _ => phase.verifier.push_back(bytes),
+            }
+        }
+        let current = phases.pop_front().ok_or_else(|| Error::Protocol("Recording holds no frames of the session".to_string()))?;
+        Ok(Replay { phases, current })
+    }
+
+    /// Phases recorded, in order
+    fn phases(&self) -> Vec<Phase> {
+        std::iter::once(self.current.phase).chain(self.phases.iter().map(|p| p.phase)).collect()
+    }
+
+    /// Move on to the next recorded phase, failing if the current one has frames the replay did not check
+    fn next_phase(&mut self) -> Result<Phase> {
+        self.check_finished()?;
+        self.current = self.phases.pop_front().ok_or_else(|| Error::Protocol("Recording ends after the last phase".to_string()))?;
+        Ok(self.current.phase)
+    }
+
+    /// Fail if any frame of the current phase is left unchecked
+    fn check_finished(&self) -> Result<()> {
+        let left = self.current.prover.len() + self.current.verifier.len();
+        match left {
+            0 => Ok(()),
+            _ => Err(Error::Protocol(format!("{} frames of the {} phase left over, which the replay does not check", left, self.current.phase.name()))),
+        }
+    }
+
+}
+
+/// Reading the recorded messages themselves, in the session's wire format
+impl Framed<Replay> {
+    /// Next message the verifier sent
+    fn verifier<M: DeserializeOwned>(&mut self) -> Result<M> {
+        let current = &mut self.get_mut().current;
+        let frame = current.verifier.pop_front().ok_or_else(|| ends("verifier", current.phase))?;
+        self.wire_format().decode(&frame)
+    }
+
+    /// Next message the prover sent, if it decodes as `M`, leaving it for the next read otherwise
+    fn prover_if<M: DeserializeOwned>(&mut self) -> Option<M> {
+        let m = self.wire_format().decode(self.get_ref().current.prover.front()?).ok()?;
+        self.get_mut().current.prover.pop_front();
+        Some(m)
+    }
+
+    /// Next message the verifier sent, if it decodes as `M`, leaving it for the next read otherwise
+    fn verifier_if<M: DeserializeOwned>(&mut self) -> Option<M> {
+        let m = self.wire_format().decode(self.get_ref().current.verifier.front()?).ok()?;
+        self.get_mut().current.verifier.pop_front();
+        Some(m)
+    }
+}
+
+/// Recording ending before the next frame `party` sent in `phase`
+fn ends(party: &str, phase: Phase) -> Error {
+    Error::Protocol(format!("Recording ends before the {}'s next frame of the {} phase", party, phase.name()))
+}
+
+impl Transport for Replay {
+    fn send(&mut self, _frame: &[u8]) -> io::Result<()> {
+        self.current.verifier.pop_front()
+            .map(|_| ())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, ends("verifier", self.current.phase).to_string()))
+    }
+
+    fn recv(&mut self, _limit: usize) -> io::Result<Vec<u8>> {
+        self.current.prover.pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, ends("prover", self.current.phase).to_string()))
+    }
+}
+
+/// Replay the given session of a recording, checking everything its verifier checked. The frames are those of the side
+/// that recorded the session first, in case the file holds both sides' recordings. Messages are decoded in the
+/// session's wire format, and its entries are as wide as the session says. Fails on the first check that fails, except
+/// for the answers, whose results say whether they verified.
+pub fn verify_session(frames: &[RecordedFrame], session: u32) -> Result<Replayed> {
+    let side = frames.iter().find(|f| f.session == session).map(|f| f.side)
+        .ok_or_else(|| Error::Config(format!("Recording holds no frames of session {}", session)))?;
+    let replay = Replay::new(frames.iter().filter(|f| f.session == session && f.side == side))?;
+    let phases = replay.phases();
+
+    // recorded frames were as large as the session allowed
+    let mut replay = Framed::new(replay, usize::MAX);
+    replay_phases(&mut replay).map(|(params, epoch, snapshot, queries)| Replayed { side, params, epoch, phases, snapshot, queries })
+}
+
+/// Replay every recorded phase in order, returning the session's parameters, epoch, final snapshot and query results
+fn replay_phases(replay: &mut Framed<Replay>) -> Result<(SessionParams, u64, SnapshotId, Vec<QueryResult>)> {
+    if replay.get_ref().current.phase != Phase::Setup {
+        return Err(Error::Protocol(format!("Recording starts in the {} phase instead of setup", replay.get_ref().current.phase.name())));
+    }
+    let (params, pp, epoch) = replay_setup(replay)?;
+    match params.entry_width {
+        8 => replay_session::<u8>(replay, params, pp, epoch),
+        16 => replay_session::<u16>(replay, params, pp, epoch),
+        32 => replay_session::<u32>(replay, params, pp, epoch),
+        64 => replay_session::<u64>(replay, params, pp, epoch),
+        128 => replay_session::<u128>(replay, params, pp, epoch),
+        width => Err(Error::Protocol(format!("Session is for {}-bit entries, which no executable runs", width))),
+    }
+}
+
+/// Replay the phases after setup over entries of type `T`, on the generators `pp` the setup agreed
+fn replay_session<T>(replay: &mut Framed<Replay>, params: SessionParams, pp: pedersen::PublicParams, epoch: u64) -> Result<(SessionParams, u64, SnapshotId, Vec<QueryResult>)>
+where T: PrimInt + Eq + Hash + Serialize + DeserializeOwned
+{
+    protocol::check_entries::<T>(&params)?;
+    let mut state: VerifierState<T> = live::verifier_init();
+    state.C0 = pedersen::commit_with_r(&0u32.into(), &state.CPROOF, &pp);
+    state.C1 = pedersen::commit_with_r(&1u32.into(), &state.CPROOF, &pp);
+    state.pedersen_pp = pp;
+    state.epoch = epoch;
+
+    let mut queries = Vec::new();
+    let mut noise_ready = false;
+    while !replay.get_ref().phases.is_empty() {
+        let phase = replay.get_mut().next_phase()?;
+        match phase {
+            Phase::HonestCommitment => barriers(replay, |replay| live::verifier_honest_commitment_phase(&mut state, replay, params.db_size, params.dimension, params.max_degree))?,
+            Phase::DishonestCommitment => {
+                state.monomial_commitments.clear();
+                barriers(replay, |replay| replay_dishonest(&mut state, replay, &params))?;
+            },
+            Phase::Randomness => {
+                barriers(replay, |replay| replay_randomness(&mut state, replay, &params))?;
+                noise_ready = true;
+            },
+            Phase::Query if noise_ready => queries = replay_queries(&mut state, replay)?,
+            Phase::Query => return Err(Error::Protocol("Queries were answered on the noise of an earlier session, which the recording does not hold".to_string())),
+            Phase::Setup | Phase::Update | Phase::Certify => return Err(Error::Protocol(format!("Replay does not check the {} phase", phase.name()))),
+        }
+        if matches!(phase, Phase::HonestCommitment | Phase::DishonestCommitment) {
+            state.snapshot = SnapshotId::of(state.monomial_commitments.iter().map(|(&k, &v)| (k, v)));
+        }
+    }
+    replay.get_ref().check_finished()?;
+    Ok((params, epoch, state.snapshot, queries))
+}
+
+/// Run a phase's checks between the barriers the driver puts around it
+fn barriers(replay: &mut Framed<Replay>, checks: impl FnOnce(&mut Framed<Replay>) -> Result<()>) -> Result<()> {
+    driver::synchronize(Side::Verifier, replay)?;
+    checks(replay)?;
+    driver::synchronize(Side::Verifier, replay)
+}
+
+/// Setup: agree on the wire format, pass over the key exchange, authentication and the names of the analyst and
+/// dataset, and check the generators against the seed both halves make up, as `verifier_setup` does, returning the
+/// parameters, generators and epoch of the session
+fn replay_setup(replay: &mut Framed<Replay>) -> Result<(SessionParams, pedersen::PublicParams, u64)> {
+    let request: WireFormatMessage = replay.verifier()?;
+    let answer: WireFormatMessage = read_message(replay)?;
+    if answer != request {
+        return Err(Error::Protocol(format!("Prover answered wire format {:?} to a request for {:?}", answer, request)));
+    }
+    // recorded frames are as the protocol encoded them, before any compression
+    replay.set_wire_format(request.format, false);
+
+    // the keys only authenticated the frames, and authentication only tells the prover who the verifier is, neither of
+    // which is a check of the prover's
+    read_message::<KeyShareMessage, _>(replay)?;
+    replay.verifier::<KeyShareMessage>()?;
+    if replay.prover_if::<AuthChallengeMessage>().is_some() {
+        replay.get_mut().current.verifier.pop_front().ok_or_else(|| ends("verifier", Phase::Setup))?;
+    }
+    let setup: SetupMessage = read_message(replay)?;
+    if setup.version != WIRE_VERSION {
+        return Err(Error::Config(format!("Session ran protocol version {}, but this replay runs version {}", setup.version, WIRE_VERSION)));
+    }
+    let params = setup.params.ok_or_else(|| Error::Protocol("Prover announced no session parameters".to_string()))?;
+
+    let seed = match setup.seed_commitment {
+        Some(commitment) => {
+            // the verifier's half is its last frame of setup, after the analyst and dataset names if it sent any
+            let half = replay.get_mut().current.verifier.pop_back().ok_or_else(|| ends("verifier", Phase::Setup))?;
+            let half = replay.wire_format().decode::<SeedContributionMessage>(&half)?.seed;
+            let reveal: SeedRevealMessage = read_message(replay)?;
+            if generators::commit(&reveal.seed) != commitment {
+                return Err(Error::Verification("Prover's half of the generator seed does not open its commitment".to_string()));
+            }
+            generators::combine(&reveal.seed, &half)
+        },
+        None => setup.seed,
+    };
+    // the names are the prover's to act on, and no check of it
+    replay.get_mut().current.verifier.clear();
+    Ok((params, generators::params(&seed), setup.epoch))
+}
+
+/// Dishonest commitment phase with every entry proven, a window at a time, each challenged as the verifier recorded
+fn replay_dishonest<T: PrimInt + Hash>(state: &mut VerifierState<T>, replay: &mut Framed<Replay>, params: &SessionParams) -> Result<()> {
+    let (db_size, dimension) = (params.db_size as usize, params.dimension as usize);
+    let mut monomial_commitments: HashMap<Monomial<T>, RistrettoPoint> = HashMap::new();
+
+    for start in (0..db_size).step_by(protocol::DISHONEST_WINDOW) {
+        let end = (start + protocol::DISHONEST_WINDOW).min(db_size);
+
+        let mut commitments = Vec::with_capacity(end - start);
+        for i in start..end {
+            let bits: BitSigmaCommitmentMessage = read_message(replay)?;
+            if bits.commitments.len() != dimension {
+                return Err(Error::Protocol(format!("Entry {} commits to {} bits, expected {}", i, bits.commitments.len(), dimension)));
+            }
+            let tree = match dimension {
+                1 => None,
+                _ => Some(read_message::<MonomialCommitmentTree, _>(replay)?),
+            };
+            commitments.push((bits, tree));
+        }
+
+        let mut bit_verifiers: Vec<Vec<bit_sigma::Verifier>> = Vec::with_capacity(end - start);
+        let mut trees: Vec<MonomialTree<VerifierNode>> = Vec::with_capacity(end - start);
+        for (i, (bits, tree)) in (start..end).zip(commitments.iter_mut()) {
+            let m: BitSigmaCombinedChallengeMessage = replay.verifier()?;
+            bit_verifiers.push(bits.commitments.iter().zip(bit_sigma::expand_challenge(&m.challenge, dimension))
+                .map(|(commitment, challenge)| bit_sigma::verifier_for(commitment, &challenge))
+                .collect());
+            if let Some(tree) = tree {
+                let challenges: MonomialChallengeTree = replay.verifier()?;
+                trees.push(challenged(tree, &challenges).ok_or_else(|| Error::Protocol(format!("Challenge tree of entry {} does not mirror its commitment tree", i)))?);
+            }
+        }
+
+        let mut bit_responses = Vec::with_capacity(end - start);
+        let mut tree_responses = Vec::with_capacity(end - start);
+        for i in 0..end - start {
+            let m: BitSigmaResponseMessage = read_message(replay)?;
+            if m.responses.len() != dimension {
+                return Err(Error::Verification(format!("Bit sigma verification failed for entry {}: {} responses for {} bits", start + i, m.responses.len(), dimension)));
+            }
+            bit_responses.push(m.responses);
+            if dimension > 1 {
+                tree_responses.push((i, read_message::<MonomialResponseTree, _>(replay)?));
+            }
+        }
+
+        if let Some(i) = live::verify_response_trees(&state.pedersen_pp, &mut trees, &tree_responses) {
+            return Err(Error::Verification(format!("Monomial product sigma verification failed for entry {}", start + i)));
+        }
+        let (entries, pairs): (Vec<usize>, Vec<_>) = bit_verifiers.iter().zip(&bit_responses).enumerate()
+            .flat_map(|(i, (verifiers, responses))| verifiers.iter().zip(responses).map(move |pair| (i, pair)))
+            .unzip();
+        if let Err((j, e)) = bit_sigma::verify_batch(&mut state.rng, &state.pedersen_pp, &pairs) {
+            return Err(Error::Verification(format!("Bit sigma verification failed for entry {}: {}", start + entries[j], e)));
+        }
+
+        match dimension {
+            1 => *monomial_commitments.entry(Monomial::variable(0)).or_default() += bit_verifiers.iter().map(|v| v[0].b_comm).sum::<RistrettoPoint>(),
+            _ => gen_monomial_map(&trees, &mut monomial_commitments),
+        }
+    }
+
+    replay.verifier::<VerifierCheckMessage>()?;
+    state.monomial_commitments = monomial_commitments;
+    Ok(())
+}
+
+/// Verifier tree of an entry whose commitment tree was challenged with `challenges`, or None if the trees differ
+fn challenged(tree: &mut MonomialCommitmentTree, challenges: &MonomialChallengeTree) -> Option<MonomialTree<VerifierNode>> {
+    tree.zip_map(challenges, |node, challenge| {
+        let product_sigma_verifier = match (&node.product_sigma_commitment, challenge) {
+            (Some(commitment), Some(challenge)) => Some(product_sigma::verifier_for(commitment, challenge)),
+            (None, None) => None,
+            _ => return None,
+        };
+        Some(VerifierNode { commitment: node.commitment, product_sigma_verifier })
+    })
+}
+
+/// Randomness phase with a single verifier: check every coin against the verifier's recorded flip and challenge, a
+/// window at a time, and the noise against the products of coins the mechanism takes
+fn replay_randomness<T: PrimInt + Hash>(state: &mut VerifierState<T>, replay: &mut Framed<Replay>, params: &SessionParams) -> Result<()> {
+    let n = params.coins() as usize;
+    state.noise_flips.clear();
+    while state.noise_flips.len() < n {
+        let window = (protocol::RANDOMNESS_WINDOW as usize).min(n - state.noise_flips.len());
+        let m: ProverRandomnessComms = read_message(replay)?;
+        let flips: VerifierRandomnessChallenges = replay.verifier()?;
+        if m.commitments.len() != window || flips.challenges.len() != window {
+            return Err(Error::Protocol(format!("{} coins committed and {} flipped, expected {}", m.commitments.len(), flips.challenges.len(), window)));
+        }
+        let coins: Vec<_> = m.commitments.iter().zip(&flips.challenges)
+            .map(|(commitment, flip)| (flip.player_b, bit_sigma::verifier_for(commitment, &flip.sigma_challenge)))
+            .collect();
+        live::verifier_check_coins(state, replay, &coins)?;
+    }
+
+    live::combine_noise(state, replay, params, |_, replay, commitments| {
+        let m: NoiseProductChallenges = replay.verifier()?;
+        if m.challenges.len() != commitments.len() {
+            return Err(Error::Protocol(format!("{} products challenged of {}", m.challenges.len(), commitments.len())));
+        }
+        Ok(commitments.iter().zip(&m.challenges).map(|(commitment, challenge)| product_sigma::verifier_for(commitment, challenge)).collect())
+    })
+}
+
+/// Query phase: check the answer to every query, or batch of queries, the verifier asked, each round between its
+/// barriers, until the recording ends or the verifier ended the phase
+fn replay_queries<T>(state: &mut VerifierState<T>, replay: &mut Framed<Replay>) -> Result<Vec<QueryResult>>
+where T: PrimInt + Eq + Hash + DeserializeOwned
+{
+    let mut results = Vec::new();
+    while !replay.get_ref().current.verifier.is_empty() {
+        let more: ReadyMessage = replay.verifier()?;
+        read_message::<ReadyMessage, _>(replay)?;
+        if !more.ready {
+            break;
+        }
+
+        if let Some(m) = replay.verifier_if::<QueryMessage<T>>() {
+            check_snapshot(state, m.snapshot)?;
+            if m.distinct {
+                return Err(Error::Protocol("Replay does not check distinct-count queries".to_string()));
+            }
+            state.dataset = m.dataset;
+            let (result, _, _) = live::verifier_check_query(state, replay, &m.coefficients, false, AnswerMode::Plain)?;
+            results.push(result);
+        } else {
+            let m: BatchQueryMessage<T> = replay.verifier()?;
+            check_snapshot(state, m.snapshot)?;
+            state.dataset = m.dataset;
+            let (batch, _, _) = live::verifier_check_batch(state, replay, &m.queries)?;
+            results.extend(batch);
+        }
+        driver::synchronize(Side::Verifier, replay)?;
+    }
+    Ok(results)
+}
+
+/// Fail unless a query was asked on the snapshot the replay verified
+fn check_snapshot<T: PrimInt + Hash>(state: &VerifierState<T>, snapshot: SnapshotId) -> Result<()> {
+    match snapshot == state.snapshot {
+        true => Ok(()),
+        false => Err(Error::Protocol(format!("Query asked on snapshot {}, but the commitments replayed make up {}", snapshot, state.snapshot))),
+    }
+}
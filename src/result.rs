@@ -0,0 +1,114 @@
+/*!
+ * result.rs
+ *
+ * Outcome of a query as the verifier reports it: whether the answer opened the query commitment, the answer itself
+ * when the verifier learns it, and what it cost. The binomial noise is the sum of N fair coins less N/2, so its
+ * standard deviation is sqrt(N)/2 whatever the answer. Its exact distribution also bounds it: with the chosen
+ * confidence, the noise is at most some k either way, so the true answer lies within k of the noisy one. Other noise
+ * mechanisms give their own deviation and bound.
+ */
+
+use curve25519_dalek::Scalar;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::LN_2;
+use std::fmt;
+
+use crate::snapshot::SnapshotId;
+
+/// Verified (or rejected) answer to one query
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct QueryResult {
+    /// Noisy answer, if the verifier learns it in the clear and it is an integer that fits in 64 bits
+    pub value: Option<i64>,
+    /// Whether the prover's answer opened the query commitment
+    pub verified: bool,
+    /// Standard deviation of the noise added to the answer
+    pub noise_std: f64,
+    /// Confidence level of `interval`, e.g. 0.95
+    pub confidence: f64,
+    /// Interval around the noisy answer that holds the true answer with probability `confidence`, if the answer is
+    /// known
+    pub interval: Option<(i64, i64)>,
+    /// Privacy budget the query spent
+    pub epsilon_charged: f64,
+    /// Snapshot of the database that answered the query
+    pub snapshot: SnapshotId,
+    /// Whether the query repeats one the session already asked, answered as before and charged nothing
+    #[serde(default)]
+    pub repeated: bool,
+}
+
+/// Standard deviation of the binomial noise of `coins` fair coins
+pub fn noise_std(coins: u32) -> f64 {
+    (coins as f64).sqrt() / 2.0
+}
+
+/// Confidence level of the intervals reported with answers, unless the verifier asks for another
+pub const DEFAULT_CONFIDENCE: f64 = 0.95;
+
+/// Parse a confidence level from the command line, strictly between 0 and 1, e.g. 0.99
+pub fn parse_confidence(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(c) if c > 0.0 && c < 1.0 => Ok(c),
+        _ => Err(format!("expected a confidence level between 0 and 1, got {:?}", s)),
+    }
+}
+
+/// Smallest k such that the noise of `coins` fair coins less half of them, rounded down, is within [-k, k] with
+/// probability at least `confidence`. The tails of the binomial are excluded from both ends inward while their mass
+/// stays within 1 - `confidence`, each probability stepped from the last in log space so that many coins do not
+/// underflow.
+pub fn noise_bound(coins: u32, confidence: f64) -> u32 {
+    let alpha = 1.0 - confidence;
+    let (half, odd) = (coins / 2, coins % 2);
+    // the noise of j heads is j - half, and of coins - j heads half + odd - j, which is as likely
+    let top = half + odd;
+    let mut ln_p = -(coins as f64) * LN_2;
+    let (mut previous, mut excluded) = (0.0, 0.0);
+    for j in 0..top {
+        let k = top - j;
+        let p = ln_p.exp();
+        // noise k, and -k when there are that many tails; for odd coins, -k is j - 1 heads
+        let mass = p + if k > half { 0.0 } else if odd == 1 { previous } else { p };
+        if excluded + mass > alpha {
+            return k;
+        }
+        excluded += mass;
+        previous = p;
+        ln_p += ((coins - j) as f64 / (j + 1) as f64).ln();
+    }
+    0
+}
+
+/// Interval of the true answers within `bound` of the noisy `value`, if its ends fit in 64 bits
+pub fn interval(value: i64, bound: u32) -> Option<(i64, i64)> {
+    Some((value.checked_sub(bound as i64)?, value.checked_add(bound as i64)?))
+}
+
+/// Answer scalar as a signed integer, if it or its negation (for answers that wrapped below zero) fits in 64 bits
+pub fn to_i64(answer: &Scalar) -> Option<i64> {
+    let low = |s: &Scalar| {
+        let bytes = s.as_bytes();
+        bytes[8..].iter().all(|&b| b == 0).then(|| u64::from_le_bytes(bytes[..8].try_into().unwrap()))
+    };
+    low(answer).and_then(|v| i64::try_from(v).ok())
+        .or_else(|| low(&-answer).and_then(|v| 0i64.checked_sub_unsigned(v)))
+}
+
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.verified { "verified" } else { "INVALID" })?;
+        match self.value {
+            Some(value) => write!(f, ", answer {}", value)?,
+            None => f.write_str(", no integer answer")?,
+        }
+        if let Some((low, high)) = self.interval {
+            write!(f, " in [{}, {}] at {}% confidence", low, high, self.confidence * 100.0)?;
+        }
+        write!(f, ", noise std {:.3}, epsilon {}, snapshot {}", self.noise_std, self.epsilon_charged, self.snapshot)?;
+        if self.repeated {
+            f.write_str(", repeated")?;
+        }
+        Ok(())
+    }
+}
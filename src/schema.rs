@@ -0,0 +1,283 @@
+/*!
+ * schema.rs
+ *
+ * Named attributes of a database and the bits of an entry each one takes, so that the loaders filling entries and the
+ * queries over them agree on every bit without tracking positions by hand. A schema is an ordered list of attributes
+ * whose bits follow each other from bit 0: a boolean takes one bit, a categorical attribute one per category, a
+ * bucketed numeric one per bucket between its boundaries, and a binary one a fixed number for an unsigned integer.
+ * The prover and the verifier load the same schema, from a JSON file or the command line.
+ */
+
+use num_traits::PrimInt;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::config::max_dimension;
+use crate::monomial::Monomial;
+
+/// How the values of an attribute are encoded into bits of an entry
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Encoding {
+    /// One bit, set for true, yes, or 1, and clear for false, no, or 0
+    #[serde(rename = "bool")]
+    Boolean,
+    /// One bit per category, in order, of which a value sets only its own
+    #[serde(rename = "one-hot")]
+    Categorical(Vec<String>),
+    /// Number, with one bit per bucket between these increasing boundaries, of which a value sets only its own; the
+    /// first bucket is below the first boundary, and each boundary starts the next
+    #[serde(rename = "buckets")]
+    Bucketed(Vec<f64>),
+    /// Unsigned integer in this many bits, least significant first
+    #[serde(rename = "binary")]
+    Binary(u32),
+}
+
+impl Encoding {
+    /// Bits of an entry the attribute takes
+    pub fn bits(&self) -> u32 {
+        match self {
+            Encoding::Boolean => 1,
+            Encoding::Categorical(categories) => categories.len() as u32,
+            Encoding::Bucketed(boundaries) => boundaries.len() as u32 + 1,
+            Encoding::Binary(bits) => *bits,
+        }
+    }
+
+    /// Check the encoding has distinct, non-empty categories, finite increasing boundaries, or 1 to 64 binary bits
+    fn check(&self) -> Result<(), String> {
+        match self {
+            Encoding::Boolean => Ok(()),
+            Encoding::Categorical(categories) => {
+                if categories.is_empty() || categories.iter().enumerate().any(|(i, c)| c.is_empty() || categories[..i].contains(c)) {
+                    return Err(format!("one-hot categories must be distinct and non-empty, got {:?}", categories.join(",")));
+                }
+                Ok(())
+            },
+            Encoding::Bucketed(boundaries) => {
+                if boundaries.is_empty() || boundaries.iter().any(|b| !b.is_finite()) || boundaries.windows(2).any(|w| w[0] >= w[1]) {
+                    return Err(format!("bucket boundaries must be finite and increasing, got {:?}", boundaries));
+                }
+                Ok(())
+            },
+            Encoding::Binary(1..=64) => Ok(()),
+            Encoding::Binary(bits) => Err(format!("binary attributes take 1 to 64 bits, got {}", bits)),
+        }
+    }
+
+    /// Bits encoding `value`, counted from the attribute's first bit
+    fn encode(&self, value: &str) -> Result<u128, String> {
+        match self {
+            Encoding::Boolean => match value.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(1),
+                "false" | "no" | "0" => Ok(0),
+                _ => Err(format!("{:?} is not a boolean", value)),
+            },
+            Encoding::Categorical(_) | Encoding::Bucketed(_) => Ok(1 << self.index(value)?),
+            Encoding::Binary(bits) => {
+                let n: u64 = value.parse().map_err(|e| format!("{:?} is not an unsigned integer: {}", value, e))?;
+                match n.checked_shr(*bits) {
+                    Some(high) if high != 0 => Err(format!("{} does not fit in {} bits", n, bits)),
+                    _ => Ok(n as u128),
+                }
+            },
+        }
+    }
+
+    /// Bit of the category or bucket of `value`, counted from the attribute's first bit, or the only bit of a boolean
+    /// attribute for a true `value`
+    fn index(&self, value: &str) -> Result<u32, String> {
+        match self {
+            Encoding::Boolean => match self.encode(value)? {
+                1 => Ok(0),
+                _ => Err(format!("{:?} sets no bit of a boolean attribute", value)),
+            },
+            Encoding::Categorical(categories) => categories.iter().position(|c| c == value)
+                .map(|i| i as u32)
+                .ok_or_else(|| format!("{:?} is not one of the categories {}", value, categories.join(", "))),
+            Encoding::Bucketed(boundaries) => match value.parse::<f64>() {
+                Ok(x) if !x.is_nan() => Ok(boundaries.iter().filter(|&&b| x >= b).count() as u32),
+                _ => Err(format!("{:?} is not a number", value)),
+            },
+            Encoding::Binary(_) => Err(format!("a binary attribute has no single bit for {:?}", value)),
+        }
+    }
+}
+
+/// Attribute of the entries, by the name of its column in a database file, and the encoding of its values
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub encoding: Encoding,
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.encoding {
+            Encoding::Boolean => write!(f, "{}=bool", self.name),
+            Encoding::Categorical(categories) => write!(f, "{}=one-hot:{}", self.name, categories.join(",")),
+            Encoding::Bucketed(boundaries) => {
+                write!(f, "{}=buckets:{}", self.name, boundaries.iter().map(f64::to_string).collect::<Vec<_>>().join(","))
+            },
+            Encoding::Binary(bits) => write!(f, "{}=binary:{}", self.name, bits),
+        }
+    }
+}
+
+/// Parse an attribute from the command line: its name, then `=` and its encoding, one of `bool`,
+/// `one-hot:CATEGORY,...`, `buckets:BOUNDARY,...`, or `binary:BITS` with at most 64 bits, e.g.
+/// `region=one-hot:north,south` or `age=buckets:18,65`
+pub fn parse_attribute(s: &str) -> Result<Attribute, String> {
+    let (name, encoding) = s.split_once('=').ok_or_else(|| format!("expected name=encoding, got {:?}", s))?;
+    let encoding = match encoding.split_once(':') {
+        None if encoding == "bool" => Encoding::Boolean,
+        Some(("one-hot", categories)) => Encoding::Categorical(categories.split(',').map(str::to_string).collect()),
+        Some(("buckets", boundaries)) => Encoding::Bucketed(boundaries.split(',')
+            .map(|b| b.parse().map_err(|_| format!("bucket boundaries must be numbers, got {:?}", b)))
+            .collect::<Result<_, _>>()?),
+        Some(("binary", bits)) => Encoding::Binary(bits.parse().map_err(|_| format!("binary attributes take 1 to 64 bits, got {:?}", bits))?),
+        _ => return Err(format!("unknown encoding {:?}, expected bool, one-hot:..., buckets:..., or binary:BITS", encoding)),
+    };
+    encoding.check()?;
+    Ok(Attribute { name: name.to_string(), encoding })
+}
+
+/// Attributes of a database's entries, in the order of their bits from bit 0
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Attribute>", into = "Vec<Attribute>")]
+pub struct Schema {
+    attributes: Vec<Attribute>,
+}
+
+impl TryFrom<Vec<Attribute>> for Schema {
+    type Error = String;
+
+    fn try_from(attributes: Vec<Attribute>) -> Result<Self, String> {
+        Schema::new(attributes)
+    }
+}
+
+impl From<Schema> for Vec<Attribute> {
+    fn from(schema: Schema) -> Self {
+        schema.attributes
+    }
+}
+
+impl Schema {
+    /// Schema of `attributes`, whose names must be distinct, encodings valid, and bits fit in the widest entries
+    pub fn new(attributes: Vec<Attribute>) -> Result<Self, String> {
+        for (i, attribute) in attributes.iter().enumerate() {
+            attribute.encoding.check().map_err(|e| format!("attribute {}: {}", attribute.name, e))?;
+            if attributes[..i].iter().any(|other| other.name == attribute.name) {
+                return Err(format!("attribute {} is named more than once", attribute.name));
+            }
+        }
+        let schema = Schema { attributes };
+        if schema.bits() > max_dimension::<u128>() {
+            return Err(format!("attributes take {} bits, but an entry holds at most {}", schema.bits(), max_dimension::<u128>()));
+        }
+        Ok(schema)
+    }
+
+    /// Load a schema from the JSON file at `path`, an array of attributes in the order of their bits, each its name
+    /// and encoding, e.g. `[{"name": "smoker", "encoding": "bool"}, {"name": "region", "encoding": {"one-hot":
+    /// ["north", "south"]}}, {"name": "age", "encoding": {"buckets": [18, 65]}}]`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Attributes, in the order of their bits
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// Bits of an entry the attributes take together, which the dimension must cover
+    pub fn bits(&self) -> u32 {
+        self.attributes.iter().map(|a| a.encoding.bits()).sum()
+    }
+
+    /// Attributes with the bits each one takes
+    pub fn positions(&self) -> impl Iterator<Item = (&Attribute, Range<u32>)> {
+        self.attributes.iter().scan(0, |offset, attribute| {
+            let start = *offset;
+            *offset += attribute.encoding.bits();
+            Some((attribute, start..*offset))
+        })
+    }
+
+    /// Attribute named `name` and the bits it takes
+    pub fn attribute(&self, name: &str) -> Result<(&Attribute, Range<u32>), String> {
+        self.positions().find(|(a, _)| a.name == name).ok_or_else(|| format!("no attribute {:?}", name))
+    }
+
+    /// Bit set in the entries whose attribute `name` is true, is the category `value`, or is in the bucket of `value`
+    pub fn bit(&self, name: &str, value: &str) -> Result<u32, String> {
+        let (attribute, bits) = self.attribute(name)?;
+        Ok(bits.start + attribute.encoding.index(value).map_err(|e| format!("attribute {}: {}", name, e))?)
+    }
+
+    /// Monomial of the conjunction of `literals`, each an attribute's name and a value as `bit` takes, which counts
+    /// the entries matching every literal
+    pub fn monomial<T: PrimInt>(&self, literals: &[(&str, &str)]) -> Result<Monomial<T>, String> {
+        literals.iter().try_fold(Monomial::new(T::zero()), |m, (name, value)| Ok(m.times(self.bit(name, value)? as usize)))
+    }
+
+    /// What the entry bit `bit` says about its attribute, e.g. `smoker`, `region=north`, `18<=age<65`, or `size[2]`
+    pub fn describe(&self, bit: u32) -> Option<String> {
+        let (attribute, bits) = self.positions().find(|(_, bits)| bits.contains(&bit))?;
+        let i = (bit - bits.start) as usize;
+        let name = &attribute.name;
+        Some(match &attribute.encoding {
+            Encoding::Boolean => name.clone(),
+            Encoding::Categorical(categories) => format!("{}={}", name, categories[i]),
+            Encoding::Bucketed(boundaries) => match (i.checked_sub(1).map(|j| boundaries[j]), boundaries.get(i)) {
+                (None, Some(high)) => format!("{}<{}", name, high),
+                (Some(low), Some(high)) => format!("{}<={}<{}", low, name, high),
+                (Some(low), None) => format!("{}>={}", name, low),
+                (None, None) => unreachable!("buckets have at least one boundary"),
+            },
+            Encoding::Binary(_) => format!("{}[{}]", name, i),
+        })
+    }
+
+    /// Whether no entry encoded with the schema has every bit of `m` set, as when two of them are categories or
+    /// buckets of one attribute, of which an entry sets only one
+    pub fn excludes<T: PrimInt>(&self, m: Monomial<T>) -> bool {
+        self.positions()
+            .filter(|(attribute, _)| matches!(attribute.encoding, Encoding::Categorical(_) | Encoding::Bucketed(_)))
+            .any(|(_, bits)| m.variables().filter(|&bit| bits.contains(&(bit as u32))).count() > 1)
+    }
+
+    /// Conjunction the monomial `m` counts the entries of, as what each of its bits says, e.g. `smoker*region=north`,
+    /// or `1` for every entry
+    pub fn describe_monomial<T: PrimInt>(&self, m: Monomial<T>) -> String {
+        let literals: Vec<String> = m.variables().map(|bit| self.describe(bit as u32).unwrap_or_else(|| format!("x{}", bit))).collect();
+        if literals.is_empty() { "1".to_string() } else { literals.join("*") }
+    }
+
+    /// Entry with `values`, one per attribute, encoded into its bits in order from bit 0
+    pub fn encode<T: PrimInt>(&self, values: &[&str]) -> Result<T, String> {
+        let mut entry = T::zero();
+        for ((attribute, bits), value) in self.positions().zip(values) {
+            let value = attribute.encoding.encode(value).map_err(|e| format!("column {}: {}", attribute.name, e))?;
+            for bit in bits.clone().filter(|&bit| value >> (bit - bits.start) & 1 == 1) {
+                entry = entry | T::one() << bit as usize;
+            }
+        }
+        Ok(entry)
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let positions: Vec<String> = self.positions()
+            .map(|(attribute, bits)| format!("{} (bits {}..{})", attribute, bits.start, bits.end))
+            .collect();
+        write!(f, "{}", positions.join(" "))
+    }
+}
@@ -0,0 +1,34 @@
+/*!
+ * sensitivity.rs
+ *
+ * L1 sensitivity of a query: how far its answer can move when one entry of the database changes. Every monomial is 0 or
+ * 1 on an entry, so an entry adds at most the sum of the absolute values of the query's coefficients, each read as the
+ * signed integer it stands for, e.g. the scalar -1 as -1 rather than as the group order less one. A query with a
+ * coefficient that is no 64-bit integer, such as the random benchmark queries, has no such bound. The noise of a
+ * session is scaled to the largest sensitivity of its queries: its standard deviation grows with the sensitivity, so the
+ * binomial noise takes its square times the coins.
+ */
+
+use curve25519_dalek::Scalar;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::monomial::Monomial;
+use crate::result;
+
+/// Sensitivity of a session's queries unless it is given another
+pub const DEFAULT_SENSITIVITY: u32 = 1;
+
+/// L1 sensitivity of the query with `coefficients`: the sum of their absolute values as signed integers, or None if one
+/// is not a 64-bit integer or the sum overflows
+pub fn l1<T: Eq + Hash>(coefficients: &HashMap<Monomial<T>, Scalar>) -> Option<u64> {
+    coefficients.values().try_fold(0u64, |sum, c| sum.checked_add(result::to_i64(c)?.unsigned_abs()))
+}
+
+/// Parse a sensitivity from the command line, a positive integer
+pub fn parse_sensitivity(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(sensitivity) if sensitivity > 0 => Ok(sensitivity),
+        _ => Err(format!("expected a positive integer sensitivity, got {:?}", s)),
+    }
+}
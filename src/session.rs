@@ -0,0 +1,60 @@
+/*!
+ * session.rs
+ *
+ * Verifier state saved after a successful session: the parameters it ran under, the verified monomial commitment map
+ * and its snapshot, and the noise commitment. A later session to the same prover resumes from it, sending further
+ * queries on the same commitments and noise without rerunning the commitment and randomness phases. The prover keeps
+ * its side of the state in memory, so it must still be serving the sessions that resume it.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::Path;
+
+use crate::monomial::Monomial;
+use crate::noise::Mechanism;
+use crate::snapshot::SnapshotId;
+
+/// Parameters a session ran under, which a resuming session must agree on with the prover
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Parameters {
+    pub dataset: String,
+    pub db_size: u32,
+    pub dimension: u32,
+    pub epsilon: f32,
+    pub delta: Option<f32>,
+    pub g: RistrettoPoint,
+    pub h: RistrettoPoint,
+    pub epoch: u64,
+}
+
+/// Verified state of a session, as saved to a file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedSession<T: Eq + Hash> {
+    pub parameters: Parameters,
+    pub snapshot: SnapshotId,
+    pub commitment_map: HashMap<Monomial<T>, RistrettoPoint>,
+    /// Commitment to the noise, already adjusted to be centered on zero
+    pub noise_commitment: RistrettoPoint,
+    pub noise_coins: u32,
+    /// Mechanism the noise coins were combined with; binomial in sessions saved before it
+    #[serde(default)]
+    pub mechanism: Mechanism,
+}
+
+impl<T: Eq + Hash + Serialize + DeserializeOwned> SavedSession<T> {
+    /// Load the session saved at `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write the session to `path`
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, serde_json::to_vec_pretty(self).unwrap()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
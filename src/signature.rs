@@ -0,0 +1,117 @@
+/*!
+ * signature.rs
+ *
+ * Ed25519 signatures (RFC 8032, by `ed25519-dalek`), with which an auditor (and optionally the prover) signs what it
+ * certified so that parties who were not in the session can rely on it, and a verifier authenticates to a prover that
+ * authorizes its key. Keys and signatures are sent as their standard encodings: a key as its 32-byte point, and a
+ * signature as its 32-byte R and 32-byte s. Signing is deterministic, so it takes no randomness.
+ */
+
+use ed25519_dalek::{Signer, SECRET_KEY_LENGTH};
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Secret key of a signer
+#[derive(Clone)]
+pub struct SigningKey {
+    key: ed25519_dalek::SigningKey,
+}
+
+/// Public key a signature is checked against, as its encoding, which need not be a valid key until checked against
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct VerifyingKey {
+    point: [u8; 32],
+}
+
+/// Ed25519 signature on a message
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Signature {
+    r: [u8; 32],
+    s: [u8; 32],
+}
+
+/// Generate a signing key pair
+pub fn keygen<T: Rng + CryptoRng>(rng: &mut T) -> (SigningKey, VerifyingKey) {
+    let sk = SigningKey { key: ed25519_dalek::SigningKey::from_bytes(&rng.gen::<[u8; SECRET_KEY_LENGTH]>()) };
+    let pk = sk.verifying_key();
+    (sk, pk)
+}
+
+impl SigningKey {
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey { point: self.key.verifying_key().to_bytes() }
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        let bytes = self.key.sign(message).to_bytes();
+        Signature { r: bytes[..32].try_into().unwrap(), s: bytes[32..].try_into().unwrap() }
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(self.key.as_bytes())
+    }
+}
+
+impl VerifyingKey {
+    /// Whether `signature` signs `message` under this key, refusing a key or signature that is not canonical, or a key
+    /// of small order, which would let one signature check under several keys or messages
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let signature = ed25519_dalek::Signature::from_components(signature.r, signature.s);
+        ed25519_dalek::VerifyingKey::from_bytes(&self.point).is_ok_and(|key| key.verify_strict(message, &signature).is_ok())
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.point)
+    }
+}
+
+/// Keeps the secret out of logs, e.g. of parsed command line arguments
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Result<[u8; 32], String> {
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(format!("expected 64 hex digits, got {:?}", s));
+    }
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|e| format!("{}", e))?;
+    }
+    Ok(bytes)
+}
+
+/// Parse a verifying key, an Ed25519 public key in hex, from the command line
+pub fn parse_verifying_key(s: &str) -> Result<VerifyingKey, String> {
+    let point = from_hex(s)?;
+    match ed25519_dalek::VerifyingKey::from_bytes(&point) {
+        Ok(key) if !key.is_weak() => Ok(VerifyingKey { point }),
+        _ => Err("verifying key is not a valid Ed25519 public key".to_string()),
+    }
+}
+
+/// Parse a signing key, an Ed25519 secret key in hex, from the command line
+pub fn parse_signing_key(s: &str) -> Result<SigningKey, String> {
+    Ok(SigningKey { key: ed25519_dalek::SigningKey::from_bytes(&from_hex(s)?) })
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for VerifyingKey {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(VerifyingKey { point: u.arbitrary()? })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Signature {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Signature { r: u.arbitrary()?, s: u.arbitrary()? })
+    }
+}
@@ -0,0 +1,264 @@
+/*!
+ * sketch.rs
+ *
+ * Committed sketch of an attribute, for distinct-count queries. The attribute is a few bits of each entry, and the
+ * sketch has a bucket per value of those bits, set when some row has that value. Each row commits to one indicator
+ * bit per bucket and proves, alongside the dishonest commitment phase, that the indicators are bits (bit-Σ), that
+ * exactly one is set (their sum opens to one), and that it is the row's own bucket (for each attribute bit, the
+ * indicators of the buckets with that bit set sum to the row's committed bit). A bucket is empty exactly when every
+ * row's (1 - indicator) is one, so each bucket is the product of those factors over the rows, chained with product-Σ
+ * proofs, subtracted from one. A distinct count is then the sum of the buckets, a count of sensitivity one.
+ */
+
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use rand::{CryptoRng, Rng};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::bit_sigma;
+use crate::messages::{SketchChallengeMessage, SketchCommitmentMessage, SketchResponseMessage, SketchRowCommitment};
+use crate::pedersen;
+use crate::product_sigma;
+
+/// Widest attribute a sketch covers, in bits, since every row commits to an indicator per value of the attribute
+pub const MAX_ATTRIBUTE_BITS: usize = 8;
+
+/// Attribute a sketch is over, as the positions of its bits in an entry, lowest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub bits: Vec<usize>,
+}
+
+/// Prover state between the sketch commitment and the verifier's challenges
+pub struct Prover {
+    indicators: Vec<bit_sigma::Prover>,
+    chains: Vec<Vec<product_sigma::Prover>>,
+    buckets: Vec<(Scalar, RistrettoPoint, Scalar)>,
+}
+
+impl Zeroize for Prover {
+    fn zeroize(&mut self) {
+        self.indicators.iter_mut().for_each(Zeroize::zeroize);
+        self.chains.iter_mut().flatten().for_each(Zeroize::zeroize);
+        self.buckets.zeroize();
+    }
+}
+
+impl Drop for Prover {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Prover {}
+
+/// Verifier state between its challenges and the prover's responses
+pub struct Verifier {
+    indicators: Vec<bit_sigma::Verifier>,
+    chains: Vec<Vec<product_sigma::Verifier>>,
+    buckets: Vec<RistrettoPoint>,
+}
+
+/// Commitment to 1 - x, given the opening of a commitment to x
+fn complement(pp: &pedersen::PublicParams, (x, _, r): (Scalar, RistrettoPoint, Scalar)) -> (Scalar, RistrettoPoint, Scalar) {
+    (Scalar::ONE - x, pedersen::commit_with_r(&(Scalar::ONE - x), &-r, pp), -r)
+}
+
+/// (1) The prover commits to the sketch of its rows, each given as the openings of its commitments to the attribute's
+/// bits, lowest first. There must be at least one row.
+pub fn commit<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams, rows: &[Vec<(Scalar, RistrettoPoint, Scalar)>]) -> (Prover, SketchCommitmentMessage) {
+    let width = rows.first().map_or(0, Vec::len);
+    let num_buckets = 1usize << width;
+
+    let mut indicators = Vec::with_capacity(rows.len() * num_buckets);
+    let mut row_commitments = Vec::with_capacity(rows.len());
+    let mut openings: Vec<Vec<(Scalar, RistrettoPoint, Scalar)>> = Vec::with_capacity(rows.len());
+
+    for bits in rows {
+        let bucket = bits.iter().enumerate()
+            .filter(|(_, &(bit, _, _))| bit == Scalar::ONE)
+            .fold(0, |bucket, (j, _)| bucket | (1 << j));
+
+        let mut row_indicators = Vec::with_capacity(num_buckets);
+        let mut row_openings = Vec::with_capacity(num_buckets);
+        for b in 0..num_buckets {
+            let y = (b == bucket) as u32;
+            let (comm, r) = pedersen::commit(rng, &Scalar::from(y), pp);
+            let (prover, commitment) = bit_sigma::commit(rng, pp, y, comm, r);
+            indicators.push(prover);
+            row_indicators.push(commitment);
+            row_openings.push((Scalar::from(y), comm, r));
+        }
+
+        let sum_proof = row_openings.iter().map(|&(_, _, r)| r).sum();
+        let bit_proofs = bits.iter().enumerate().map(|(j, &(_, _, r_bit))| {
+            row_openings.iter().enumerate()
+                .filter(|(b, _)| b & (1 << j) != 0)
+                .map(|(_, &(_, _, r))| r)
+                .sum::<Scalar>() - r_bit
+        }).collect();
+
+        row_commitments.push(SketchRowCommitment { indicators: row_indicators, sum_proof, bit_proofs });
+        openings.push(row_openings);
+    }
+
+    // each bucket's chain multiplies in one row's (1 - indicator) per proof, starting from the first row's
+    let mut chains = Vec::with_capacity(num_buckets);
+    let mut chain_commitments = Vec::with_capacity(num_buckets);
+    let mut buckets = Vec::with_capacity(num_buckets);
+    for b in 0..num_buckets {
+        let mut empty = complement(pp, openings[0][b]);
+        let mut chain = Vec::with_capacity(rows.len() - 1);
+        let mut chain_commitment = Vec::with_capacity(rows.len() - 1);
+        for row in &openings[1..] {
+            let factor = complement(pp, row[b]);
+            let m = empty.0 * factor.0;
+            let (c, r) = pedersen::commit(rng, &m, pp);
+            let (prover, commitment) = product_sigma::commit(rng, pp, empty, factor, (m, c, r));
+            chain.push(prover);
+            chain_commitment.push(commitment);
+            empty = (m, c, r);
+        }
+        chains.push(chain);
+        chain_commitments.push(chain_commitment);
+        buckets.push(complement(pp, empty));
+    }
+
+    (
+        Prover { indicators, chains, buckets },
+        SketchCommitmentMessage { rows: row_commitments, chains: chain_commitments },
+    )
+}
+
+/// (2) The verifier checks that the sketch commitment has the expected shape, that each row's indicators open to one
+/// and to the row's attribute bits, and that the chains link up, then challenges its sigma protocols. Each row is given
+/// as its commitments to the attribute's bits, lowest first. Returns why the sketch is rejected, if it is.
+pub fn challenge<T: Rng + CryptoRng>(rng: &mut T, pp: &pedersen::PublicParams, rows: &[Vec<RistrettoPoint>],
+                                     comm_m: &SketchCommitmentMessage) -> Result<(Verifier, SketchChallengeMessage), String> {
+    let width = rows.first().map_or(0, Vec::len);
+    let num_buckets = 1usize << width;
+
+    if comm_m.rows.len() != rows.len() || comm_m.chains.len() != num_buckets {
+        return Err("sketch does not have a row per entry and a chain per bucket".to_string());
+    }
+
+    let indicator_challenge = bit_sigma::Challenge::random(rng);
+    let indicator_challenges = bit_sigma::expand_challenge(&indicator_challenge, rows.len() * num_buckets);
+    let mut indicators = Vec::with_capacity(rows.len() * num_buckets);
+    let mut indicator_comms: Vec<Vec<RistrettoPoint>> = Vec::with_capacity(rows.len());
+
+    for (i, (bits, row)) in rows.iter().zip(&comm_m.rows).enumerate() {
+        if row.indicators.len() != num_buckets || row.bit_proofs.len() != width {
+            return Err(format!("sketch row {} does not have an indicator per bucket and a proof per attribute bit", i));
+        }
+
+        let row_verifiers: Vec<bit_sigma::Verifier> = row.indicators.iter().zip(&indicator_challenges[i * num_buckets..])
+            .map(|(commitment, challenge)| bit_sigma::verifier_for(commitment, challenge))
+            .collect();
+        let comms: Vec<RistrettoPoint> = row_verifiers.iter().map(|v| v.b_comm).collect();
+
+        if !pedersen::verify(&comms.iter().sum(), &Scalar::ONE, &row.sum_proof, pp) {
+            return Err(format!("sketch row {} does not have exactly one indicator set", i));
+        }
+        for (j, (bit, proof)) in bits.iter().zip(&row.bit_proofs).enumerate() {
+            let set: RistrettoPoint = comms.iter().enumerate().filter(|(b, _)| b & (1 << j) != 0).map(|(_, c)| c).sum();
+            if !pedersen::verify(&(set - bit), &Scalar::ZERO, proof, pp) {
+                return Err(format!("sketch row {} indicator does not match its attribute bit {}", i, j));
+            }
+        }
+
+        indicators.extend(row_verifiers);
+        indicator_comms.push(comms);
+    }
+
+    let mut chains = Vec::with_capacity(num_buckets);
+    let mut chain_challenges = Vec::with_capacity(num_buckets);
+    let mut buckets = Vec::with_capacity(num_buckets);
+    for (b, chain) in comm_m.chains.iter().enumerate() {
+        if chain.len() + 1 != rows.len() {
+            return Err(format!("sketch chain of bucket {} does not have a proof per row after the first", b));
+        }
+
+        let mut empty = pp.g - indicator_comms[0][b];
+        let mut verifiers = Vec::with_capacity(chain.len());
+        let mut challenges = Vec::with_capacity(chain.len());
+        for (comms, commitment) in indicator_comms[1..].iter().zip(chain) {
+            let (c_1, c_2, c_3) = commitment.statement();
+            if c_1 != empty || c_2 != pp.g - comms[b] {
+                return Err(format!("sketch chain of bucket {} does not link up", b));
+            }
+            let (verifier, challenge) = product_sigma::challenge(rng, commitment);
+            verifiers.push(verifier);
+            challenges.push(challenge);
+            empty = c_3;
+        }
+        chains.push(verifiers);
+        chain_challenges.push(challenges);
+        buckets.push(pp.g - empty);
+    }
+
+    Ok((
+        Verifier { indicators, chains, buckets },
+        SketchChallengeMessage { indicators: indicator_challenge, chains: chain_challenges },
+    ))
+}
+
+/// (3) The prover responds to the verifier's challenges. Returns None if they do not match the committed sketch.
+pub fn response(sigma_p: &mut Prover, challenge_m: &SketchChallengeMessage) -> Option<SketchResponseMessage> {
+    if challenge_m.chains.len() != sigma_p.chains.len()
+        || challenge_m.chains.iter().zip(&sigma_p.chains).any(|(challenges, chain)| challenges.len() != chain.len()) {
+        return None;
+    }
+
+    let challenges = bit_sigma::expand_challenge(&challenge_m.indicators, sigma_p.indicators.len());
+    let indicators = sigma_p.indicators.iter_mut().zip(&challenges)
+        .map(|(prover, challenge)| bit_sigma::response(prover, challenge))
+        .collect();
+    let chains = sigma_p.chains.iter_mut().zip(&challenge_m.chains)
+        .map(|(chain, challenges)| chain.iter_mut().zip(challenges).map(|(p, c)| product_sigma::response(p, c)).collect())
+        .collect();
+
+    Some(SketchResponseMessage { indicators, chains })
+}
+
+/// (4) The verifier checks the responses, returning the commitments to the buckets if they all verify.
+pub fn verify(pp: &pedersen::PublicParams, mut sigma_v: Verifier, response_m: &SketchResponseMessage) -> Option<Vec<RistrettoPoint>> {
+    if response_m.indicators.len() != sigma_v.indicators.len() || response_m.chains.len() != sigma_v.chains.len() {
+        return None;
+    }
+
+    let indicators_verified = sigma_v.indicators.iter_mut().zip(&response_m.indicators)
+        .all(|(v, resp)| bit_sigma::verify(pp, v, resp).is_ok());
+    let chains_verified = sigma_v.chains.iter_mut().zip(&response_m.chains)
+        .all(|(chain, responses)| chain.len() == responses.len()
+            && chain.iter_mut().zip(responses).all(|(v, resp)| product_sigma::verify(pp, v, resp).is_ok()));
+
+    (indicators_verified && chains_verified).then_some(sigma_v.buckets)
+}
+
+impl Prover {
+    /// Openings of the commitments to the buckets, each one if some row has its value and zero otherwise
+    pub fn buckets(mut self) -> Vec<(Scalar, RistrettoPoint, Scalar)> {
+        std::mem::take(&mut self.buckets)
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits: Vec<String> = self.bits.iter().map(|j| j.to_string()).collect();
+        write!(f, "bits {}", bits.join(","))
+    }
+}
+
+/// Parse an attribute from the command line, as the comma separated positions of its bits in an entry, e.g. 0,1,2
+pub fn parse_attribute(s: &str) -> Result<Attribute, String> {
+    let mut bits = s.split(',')
+        .map(|bit| bit.trim().parse::<usize>().map_err(|e| format!("bit {:?}: {}", bit, e)))
+        .collect::<Result<Vec<usize>, String>>()?;
+    bits.sort_unstable();
+    bits.dedup();
+    if bits.len() > MAX_ATTRIBUTE_BITS {
+        return Err(format!("attribute has {} bits, but a sketch covers at most {}", bits.len(), MAX_ATTRIBUTE_BITS));
+    }
+    Ok(Attribute { bits })
+}
@@ -0,0 +1,65 @@
+/*!
+ * snapshot.rs
+ *
+ * Identifiers for committed database states. A snapshot ID is a hash of the monomial commitments certified by the
+ * commitment phase, so the prover and verifier derive it independently; it is carried in the commitment map and in
+ * every query, so a query can be pinned to the snapshot the analyst expects and each answer records which one it came from.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use num_traits::PrimInt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+use crate::monomial::Monomial;
+
+/// Domain separator for snapshot hashes
+const SNAPSHOT_DOMAIN: &[u8] = b"certified-dp snapshot";
+
+/// Identifier of a committed database state; all zeros for peers that predate snapshots
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SnapshotId([u8; 32]);
+
+impl SnapshotId {
+    /// Snapshot of a set of (monomial, commitment) pairs, hashed in order of monomial so it does not depend on map order
+    pub fn of<T: PrimInt>(commitments: impl IntoIterator<Item = (Monomial<T>, RistrettoPoint)>) -> SnapshotId {
+        let mut commitments: Vec<(u128, RistrettoPoint)> = commitments.into_iter()
+            .map(|(monomial, comm)| (monomial.mask().to_u128().unwrap(), comm))
+            .collect();
+        commitments.sort_by_key(|&(monomial_id, _)| monomial_id);
+
+        let mut hasher = Sha256::new();
+        hasher.update(SNAPSHOT_DOMAIN);
+        for (monomial_id, comm) in commitments {
+            hasher.update(monomial_id.to_le_bytes());
+            hasher.update(comm.compress().as_bytes());
+        }
+        SnapshotId(hasher.finalize().into())
+    }
+}
+
+impl fmt::Display for SnapshotId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+/// Parse a snapshot ID, 64 hex digits, from the command line
+pub fn parse_snapshot(s: &str) -> Result<SnapshotId, String> {
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(format!("expected 64 hex digits, got {:?}", s));
+    }
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|e| format!("{}", e))?;
+    }
+    Ok(SnapshotId(bytes))
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for SnapshotId {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(SnapshotId(u.arbitrary()?))
+    }
+}
@@ -0,0 +1,138 @@
+/*!
+ * tls.rs
+ *
+ * TLS for the connections between prover and verifier, with rustls, so commitments, challenges, and answers do not
+ * cross the network in plaintext. The prover serves a certificate, which the verifier only accepts if a CA it trusts
+ * signed it for the address it dials. A prover given a CA of its own also requires each verifier to present a
+ * certificate that CA signed. The handshake completes as the connection opens, so a rejected certificate fails the
+ * connection rather than the first message sent over it.
+ */
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConnection, ConnectionCommon, RootCertStore, ServerConnection, SideData, StreamOwned};
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::net::TcpStream;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::transport::{read_frame, write_frame, Transport};
+
+pub use rustls::{ClientConfig, ServerConfig};
+
+/// Prover's end of a TLS connection
+pub type ServerStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Verifier's end of a TLS connection
+pub type ClientStream = StreamOwned<ClientConnection, TcpStream>;
+
+impl<C, D> Transport for StreamOwned<C, TcpStream>
+where C: DerefMut + Deref<Target = ConnectionCommon<D>>, D: SideData
+{
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        write_frame(self, frame)?;
+        self.flush()
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        read_frame(self, limit)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// Configuration of the prover's side: its certificate chain and key, both PEM files, and the CA that must have signed
+/// the verifiers' certificates, if it requires them
+pub fn server_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<Arc<ServerConfig>> {
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::Config(format!("TLS {}", e)))?;
+    let builder = match client_ca {
+        Some(ca) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca)?), provider())
+                .build()
+                .map_err(|e| Error::Config(format!("TLS CA {}: {}", ca.display(), e)))?;
+            builder.with_client_cert_verifier(verifier)
+        },
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder.with_single_cert(load_certs(cert)?, load_key(key)?)
+        .map_err(|e| Error::Config(format!("TLS certificate {} does not match its key: {}", cert.display(), e)))?;
+    Ok(Arc::new(config))
+}
+
+/// Configuration of the verifier's side: the CA that must have signed the prover's certificate, both PEM files, and the
+/// certificate chain and key to present to a prover that requires one
+pub fn client_config(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| Error::Config(format!("TLS {}", e)))?
+        .with_root_certificates(load_roots(ca)?);
+    let config = match identity {
+        Some((cert, key)) => builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+            .map_err(|e| Error::Config(format!("TLS certificate {} does not match its key: {}", cert.display(), e)))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Run the prover's side of the handshake on a connection it accepted
+pub fn accept(config: Arc<ServerConfig>, sock: TcpStream) -> io::Result<ServerStream> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    handshake(StreamOwned::new(conn, sock))
+}
+
+/// Run the verifier's side of the handshake with the prover at `host`, a name or IP address its certificate must be for
+pub fn connect(config: Arc<ClientConfig>, host: &str, sock: TcpStream) -> io::Result<ClientStream> {
+    let name = ServerName::try_from(host.to_string()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let conn = ClientConnection::new(config, name).map_err(io::Error::other)?;
+    handshake(StreamOwned::new(conn, sock))
+}
+
+fn handshake<C, D>(mut stream: StreamOwned<C, TcpStream>) -> io::Result<StreamOwned<C, TcpStream>>
+where C: DerefMut + Deref<Target = ConnectionCommon<D>>, D: SideData
+{
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+    Ok(stream)
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Certificate chain in the PEM file at `path`
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let unreadable = |e: io::Error| Error::Config(format!("Could not read TLS certificates {}: {}", path.display(), e));
+    let file = File::open(path).map_err(unreadable)?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).collect::<io::Result<Vec<_>>>().map_err(unreadable)?;
+    if certs.is_empty() {
+        return Err(Error::Config(format!("No TLS certificates in {}", path.display())));
+    }
+    Ok(certs)
+}
+
+/// First private key in the PEM file at `path`
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let unreadable = |e: io::Error| Error::Config(format!("Could not read TLS key {}: {}", path.display(), e));
+    let file = File::open(path).map_err(unreadable)?;
+    rustls_pemfile::private_key(&mut BufReader::new(file)).map_err(unreadable)?
+        .ok_or_else(|| Error::Config(format!("No TLS private key in {}", path.display())))
+}
+
+/// Trust anchors from the CA certificates in the PEM file at `path`
+fn load_roots(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| Error::Config(format!("Invalid TLS CA certificate in {}: {}", path.display(), e)))?;
+    }
+    Ok(roots)
+}
@@ -0,0 +1,149 @@
+/*!
+ * transport.rs
+ *
+ * Channels the protocol runs over. Every message travels as one frame of bytes, and a `Transport` only has to carry
+ * frames between the two parties in order, so the phase functions run the same over TCP, an in-memory channel, or an
+ * embedding program's own RPC layer. Byte streams such as `TcpStream` carry each frame behind a 4-byte size header,
+ * while a `Loopback` hands frames across threads of one process, so a whole run needs no sockets. Each party caps the
+ * size of the frames it accepts, so a peer cannot make it buffer gigabytes for one message.
+ */
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// Largest frame accepted from the peer unless the party sets its own limit, well above the commitment trees of the
+/// databases the protocol is practical for
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 256 << 20;
+
+/// Frame from the peer over the size limit, carried inside the `io::Error` that refuses it
+#[derive(Debug, thiserror::Error)]
+#[error("Frame of {size} bytes exceeds the limit of {limit} bytes")]
+pub struct FrameTooLarge {
+    pub size: usize,
+    pub limit: usize,
+}
+
+/// Channel to the peer carrying whole frames
+pub trait Transport {
+    /// Send one frame to the peer
+    fn send(&mut self, frame: &[u8]) -> io::Result<()>;
+
+    /// Receive the peer's next frame, failing if the peer disconnects or truncates it, or if the frame is over `limit`
+    /// bytes and the transport learns its size before receiving it, as a byte stream does from its size header
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>>;
+
+    /// Fail any `recv` that waits longer than `timeout` for the peer, or wait indefinitely with None, as phase
+    /// deadlines need. Transports that cannot time out a read keep the default, which ignores the timeout.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for TcpStream {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        write_frame(self, frame)
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        read_frame(self, limit)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        (**self).send(frame)
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        (**self).recv(limit)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        (**self).send(frame)
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        (**self).recv(limit)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+}
+
+/// One end of an in-memory connection to a peer in the same process, e.g. the verifier on another thread
+#[derive(Debug)]
+pub struct Loopback {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+impl Loopback {
+    /// Both ends of a new connection; dropping either one disconnects the other
+    pub fn pair() -> (Loopback, Loopback) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        (Loopback { tx: a_tx, rx: a_rx, timeout: None }, Loopback { tx: b_tx, rx: b_rx, timeout: None })
+    }
+}
+
+impl Transport for Loopback {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.tx.send(frame.to_vec()).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "peer disconnected"))
+    }
+
+    fn recv(&mut self, _limit: usize) -> io::Result<Vec<u8>> {
+        let disconnected = || io::Error::new(io::ErrorKind::UnexpectedEof, "peer disconnected");
+        match self.timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, "read timed out"),
+                RecvTimeoutError::Disconnected => disconnected(),
+            }),
+            None => self.rx.recv().map_err(|_| disconnected()),
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+/// Reads a frame from a byte stream, determined by a 4-byte size header, returning an error if the peer disconnects or
+/// truncates the frame, or announces one over `limit` bytes. The buffer grows with the bytes actually received, so a
+/// forged size header alone cannot force a huge allocation.
+pub fn read_frame<R: Read>(stream: &mut R, limit: usize) -> io::Result<Vec<u8>> {
+    let mut size_buf = [0; 4];
+    stream.read_exact(&mut size_buf)?;
+
+    let size = u32::from_le_bytes(size_buf) as usize;
+    if size > limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, FrameTooLarge { size, limit }));
+    }
+    let mut buffer = Vec::new();
+    stream.take(size as u64).read_to_end(&mut buffer)?;
+
+    if buffer.len() != size {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("frame truncated at {} of {} bytes", buffer.len(), size)));
+    }
+    Ok(buffer)
+}
+
+/// Writes a frame to a byte stream, with a 4-byte size header
+pub fn write_frame<W: Write>(stream: &mut W, frame: &[u8]) -> io::Result<()> {
+    stream.write_all(&(frame.len() as u32).to_le_bytes())?;
+    stream.write_all(frame)
+}
@@ -0,0 +1,115 @@
+/*!
+ * auth.rs
+ *
+ * Checks verifier authentication over an in-memory `Loopback`: a verifier answering the prover's challenge with the
+ * pre-shared key or a signature under an authorized key is served, one holding another signing key, or none the prover
+ * accepts, is refused with an abort both sides report, one holding another pre-shared key agrees keys under which the
+ * prover's challenge does not even open, and an answer to one challenge does not answer
+ * another, nor does it answer the same challenge on a connection whose keys were agreed from other shares, as an
+ * attacker relaying it between two exchanges of its own would need.
+ */
+
+use rand::rngs::OsRng;
+use std::thread;
+
+use certified_dp::auth::{self, parse_preshared_key, Authorized, Credential};
+use certified_dp::driver::Side;
+use certified_dp::error::Error;
+use certified_dp::mac::{self, FrameKeys, KeyExchange};
+use certified_dp::messages::{read_message, write_message, AbortReason, Framed, ReadyMessage};
+use certified_dp::signature;
+use certified_dp::transport::{Loopback, DEFAULT_MAX_FRAME_SIZE};
+
+const PSK: &str = "8e2c0a4f6d1b3e5a7c9f0b2d4e6a8c1f3b5d7e9a0c2e4f6b8d1a3c5e7f9b0d2c";
+
+/// Authenticate `credential` to a prover authorizing `authorized`, once the two agree keys for their frames, mixing in
+/// the pre-shared key each holds, returning the prover's outcome and the verifier's, which after answering reads the
+/// next message
+fn authenticate(authorized: Authorized, credential: Credential) -> (Result<(), Error>, Result<(), Error>) {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let (mut prover_stream, mut verifier_stream) = (Framed::new(prover_stream, DEFAULT_MAX_FRAME_SIZE), Framed::new(verifier_stream, DEFAULT_MAX_FRAME_SIZE));
+    let prover = thread::spawn(move || {
+        mac::prover_agree(&mut OsRng, &mut prover_stream, authorized.preshared.as_ref())?;
+        auth::prover_authenticate(&mut OsRng, &mut prover_stream, &authorized)?;
+        write_message(&mut prover_stream, &ReadyMessage { ready: true })
+    });
+    let psk = match &credential {
+        Credential::Preshared(key) => Some(key),
+        Credential::Key(_) => None,
+    };
+    let verifier = mac::verifier_agree(&mut OsRng, &mut verifier_stream, psk)
+        .and_then(|()| auth::verifier_authenticate(&mut verifier_stream, &credential))
+        .and_then(|()| read_message::<ReadyMessage, _>(&mut verifier_stream).map(|_| ()));
+    // a verifier that gave up hangs up, as the executable does
+    drop(verifier_stream);
+    (prover.join().unwrap(), verifier)
+}
+
+/// Verifier's keys from a fresh exchange with a prover
+fn keys() -> FrameKeys {
+    let prover = KeyExchange::new(&mut OsRng);
+    KeyExchange::new(&mut OsRng).finish(Side::Verifier, &prover.share(), None).unwrap()
+}
+
+/// Whether both sides failed on the prover refusing the verifier
+fn refused((prover, verifier): (Result<(), Error>, Result<(), Error>)) -> bool {
+    matches!(prover, Err(Error::Aborted(AbortReason::Unauthorized))) && matches!(verifier, Err(Error::PeerAborted(AbortReason::Unauthorized)))
+}
+
+#[test]
+fn preshared_keys_authenticate_verifiers() {
+    let psk = parse_preshared_key(PSK).unwrap();
+    let authorized = Authorized { preshared: Some(psk.clone()), keys: Vec::new() };
+    let (prover, verifier) = authenticate(authorized.clone(), Credential::Preshared(psk));
+    prover.unwrap();
+    verifier.unwrap();
+
+    let other = parse_preshared_key(&PSK.replace('8', "9")).unwrap();
+    let (prover, verifier) = authenticate(authorized.clone(), Credential::Preshared(other));
+    assert!(prover.is_err() && matches!(verifier, Err(Error::Protocol(e)) if e.contains("fails authentication")));
+    // a signature is no use to a prover without authorized keys
+    assert!(refused(authenticate(authorized, Credential::Key(signature::keygen(&mut OsRng).0))));
+    assert!(parse_preshared_key("8e2c").is_err());
+}
+
+#[test]
+fn signatures_under_authorized_keys_authenticate_verifiers() {
+    let (sk, vk) = signature::keygen(&mut OsRng);
+    let authorized = Authorized { preshared: None, keys: vec![signature::keygen(&mut OsRng).1, vk] };
+    let (prover, verifier) = authenticate(authorized.clone(), Credential::Key(sk.clone()));
+    prover.unwrap();
+    verifier.unwrap();
+
+    assert!(refused(authenticate(authorized.clone(), Credential::Key(signature::keygen(&mut OsRng).0))));
+    // a prover without a pre-shared key cannot mix one into the keys
+    let (prover, verifier) = authenticate(authorized.clone(), Credential::Preshared(parse_preshared_key(PSK).unwrap()));
+    assert!(matches!(prover, Err(Error::Protocol(e)) if e.contains("this prover has none")) && verifier.is_err());
+
+    // an answer only answers the challenge it was made for
+    let keys = keys();
+    let proof = Credential::Key(sk).prove(&[1; 32], &keys);
+    assert!(authorized.check(&[1; 32], &keys, &proof));
+    assert!(!authorized.check(&[2; 32], &keys, &proof));
+}
+
+#[test]
+fn answers_relayed_to_another_exchange_are_refused() {
+    // an attacker in the middle agrees keys of its own with each side, so the prover's shares are not the verifier's
+    let (relayed, prover) = (keys(), keys());
+    let psk = parse_preshared_key(PSK).unwrap();
+    let (sk, vk) = signature::keygen(&mut OsRng);
+    let authorized = Authorized { preshared: Some(psk.clone()), keys: vec![vk] };
+    for credential in [Credential::Preshared(psk), Credential::Key(sk)] {
+        let proof = credential.prove(&[1; 32], &relayed);
+        assert!(authorized.check(&[1; 32], &relayed, &proof));
+        assert!(!authorized.check(&[1; 32], &prover, &proof));
+    }
+}
+
+#[test]
+fn authentication_waits_for_the_frame_keys() {
+    let (prover_stream, _verifier_stream) = Loopback::pair();
+    let mut prover_stream = Framed::new(prover_stream, DEFAULT_MAX_FRAME_SIZE);
+    let authorized = Authorized { preshared: Some(parse_preshared_key(PSK).unwrap()), keys: Vec::new() };
+    assert!(matches!(auth::prover_authenticate(&mut OsRng, &mut prover_stream, &authorized), Err(Error::Protocol(_))));
+}
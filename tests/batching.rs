@@ -0,0 +1,70 @@
+/*!
+ * batching.rs
+ *
+ * Checks that `bit_sigma::verify_batch` accepts exactly the batches whose responses all pass `bit_sigma::verify`, and
+ * names the first response that does not, and that commitments over the precomputed tables of `g` and `h` are the
+ * same as without them, one at a time and in batches on either side of the size worth building tables for.
+ */
+
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+
+use certified_dp::bit_sigma;
+use certified_dp::error::Error;
+use certified_dp::pedersen;
+
+/// Verifier states and honest responses for bit sigma proofs of `bits`, with `claimed[i]` as the bit the prover
+/// claims for `bits[i]`
+fn proofs(pp: &pedersen::PublicParams, bits: &[u32], claimed: &[u32]) -> Vec<(bit_sigma::Verifier, bit_sigma::Response)> {
+    bits.iter().zip(claimed).map(|(&b, &claim)| {
+        let (b_comm, b_proof) = pedersen::commit(&mut OsRng, &Scalar::from(b), pp);
+        let (mut sigma_p, commitment) = bit_sigma::commit(&mut OsRng, pp, claim, b_comm, b_proof);
+        let (sigma_v, challenge) = bit_sigma::challenge(&mut OsRng, &commitment);
+        (sigma_v, bit_sigma::response(&mut sigma_p, &challenge))
+    }).collect()
+}
+
+fn verify_batch(pp: &pedersen::PublicParams, proofs: &[(bit_sigma::Verifier, bit_sigma::Response)]) -> Result<(), (usize, Error)> {
+    let pairs: Vec<_> = proofs.iter().map(|(v, r)| (v, r)).collect();
+    bit_sigma::verify_batch(&mut OsRng, pp, &pairs)
+}
+
+#[test]
+fn batch_of_valid_responses_verifies() {
+    let pp: pedersen::PublicParams = pedersen::setup(b"batching");
+    let bits = [0, 1, 1, 0, 1, 0, 0, 1];
+    assert!(verify_batch(&pp, &proofs(&pp, &bits, &bits)).is_ok());
+    assert!(verify_batch(&pp, &[]).is_ok());
+}
+
+#[test]
+fn batch_names_the_first_invalid_response() {
+    let pp: pedersen::PublicParams = pedersen::setup(b"batching");
+    // the prover claims the committed 2 in position 5 is a bit, and the committed 0 in position 6 is a 1
+    let batch = proofs(&pp, &[0, 1, 1, 0, 1, 2, 0, 1], &[0, 1, 1, 0, 1, 1, 1, 1]);
+
+    match verify_batch(&pp, &batch) {
+        Err((5, Error::Verification(_))) => (),
+        other => panic!("batch with invalid responses at 5 and 6 gave {:?}", other.map_err(|(i, _)| i)),
+    }
+}
+
+#[test]
+fn batched_commitments_open_to_their_values() {
+    let tabled: pedersen::PublicParams = pedersen::setup(b"batching");
+    let plain = pedersen::PublicParams::new(tabled.g, tabled.h);
+    assert!(tabled.is_precomputed() && !plain.is_precomputed());
+
+    for (pp, other) in [(&tabled, &plain), (&plain, &tabled)] {
+        for size in [0usize, 3, 40] {
+            let values: Vec<Scalar> = (0..size as u64).map(|v| Scalar::from(v * v)).collect();
+            let commitments = pedersen::commit_batch(&mut OsRng, &values, pp);
+            assert_eq!(commitments.len(), size);
+            for (value, (commitment, r)) in values.iter().zip(&commitments) {
+                assert!(pedersen::verify(commitment, value, r, pp));
+                assert!(pedersen::verify(commitment, value, r, other));
+                assert_eq!(*commitment, pedersen::commit_with_r(value, r, other));
+            }
+        }
+    }
+}
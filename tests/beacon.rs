@@ -0,0 +1,95 @@
+/*!
+ * beacon.rs
+ *
+ * Checks the randomness beacon's rounds: a round signed by the chain's key is accepted under either the chained scheme
+ * of drand's default chain, which signs on G2, or quicknet's, which signs on G1, while a round signed by another key,
+ * or whose randomness is not the hash of its signature, is refused.
+ */
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{G1Affine, G1Projective, G2Affine, G2Projective, Scalar};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use certified_dp::beacon::Beacon;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn secret() -> Scalar {
+    let mut bytes = [0; 64];
+    OsRng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_wide(&bytes)
+}
+
+/// Chain signing on G2 as drand's default chain does: its public key, and the signature of `round` after `previous`
+fn chained(sk: &Scalar, round: u64, previous: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let message = Sha256::new().chain_update(previous).chain_update(round.to_be_bytes()).finalize();
+    let hashed = <G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_");
+    let key = G1Affine::from(G1Affine::generator() * sk).to_compressed();
+    (key.to_vec(), G2Affine::from(hashed * sk).to_compressed().to_vec())
+}
+
+/// Chain signing on G1 as quicknet does: its public key, and the signature of `round`
+fn quicknet(sk: &Scalar, round: u64) -> (Vec<u8>, Vec<u8>) {
+    let message = Sha256::digest(round.to_be_bytes());
+    let hashed = <G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(message, b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_");
+    let key = G2Affine::from(G2Affine::generator() * sk).to_compressed();
+    (key.to_vec(), G1Affine::from(hashed * sk).to_compressed().to_vec())
+}
+
+/// Beacon file of the chain with `key` under `scheme`, holding `entries`, each a JSON line of drand's `/public/<round>`
+fn beacon(name: &str, scheme: &str, key: &[u8], entries: &[String]) -> (Beacon, PathBuf) {
+    let path = std::env::temp_dir().join(format!("certified-dp-beacon-{}-{}.jsonl", name, std::process::id()));
+    let genesis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 1000;
+    let info = format!("{{\"public_key\":\"{}\",\"period\":3,\"genesis_time\":{},\"schemeID\":\"{}\"}}", hex(key), genesis, scheme);
+    fs::write(&path, [info].iter().chain(entries).map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+    (Beacon::open(path.clone()).unwrap(), path)
+}
+
+fn entry(round: u64, signature: &[u8], previous: Option<&[u8]>) -> String {
+    let previous = previous.map(|p| format!(",\"previous_signature\":\"{}\"", hex(p))).unwrap_or_default();
+    format!("{{\"round\":{},\"randomness\":\"{}\",\"signature\":\"{}\"{}}}", round, hex(&Sha256::digest(signature)), hex(signature), previous)
+}
+
+#[test]
+fn rounds_signed_by_the_chain_are_accepted() {
+    let sk = secret();
+    let previous = [7u8; 96];
+    let (key, signature) = chained(&sk, 5, &previous);
+    let (chain, path) = beacon("chained", "pedersen-bls-chained", &key, &[entry(5, &signature, Some(&previous))]);
+    assert_eq!(chain.randomness(5).unwrap().as_slice(), Sha256::digest(&signature).as_slice());
+    fs::remove_file(path).unwrap();
+
+    let (key, signature) = quicknet(&sk, 9);
+    let (chain, path) = beacon("quicknet", "bls-unchained-g1-rfc9380", &key, &[entry(9, &signature, None)]);
+    assert_eq!(chain.randomness(9).unwrap().as_slice(), Sha256::digest(&signature).as_slice());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rounds_not_signed_by_the_chain_are_refused() {
+    let (key, _) = quicknet(&secret(), 9);
+    // hashing a signature of another key, or of another round, makes randomness nobody can tell from the chain's
+    let (_, forged) = quicknet(&secret(), 9);
+    let (_, replayed) = quicknet(&secret(), 8);
+    let (chain, path) = beacon("forged", "bls-unchained-g1-rfc9380", &key, &[entry(9, &forged, None), entry(10, &replayed, None)]);
+    assert!(chain.randomness(9).unwrap_err().contains("does not check against the chain's public key"));
+    assert!(chain.randomness(10).unwrap_err().contains("does not check against the chain's public key"));
+    fs::remove_file(path).unwrap();
+
+    // nor does the chained scheme accept a round without the signature it follows
+    let sk = secret();
+    let (key, signature) = chained(&sk, 5, &[7u8; 96]);
+    let mut altered = entry(5, &signature, Some(&[7u8; 96]));
+    altered = altered.replace(&hex(&Sha256::digest(&signature)), &hex(&[0u8; 32]));
+    let (chain, path) = beacon("altered", "pedersen-bls-chained", &key, &[altered, entry(6, &signature, None)]);
+    assert!(chain.randomness(5).unwrap_err().contains("not the hash of its signature"));
+    assert!(chain.randomness(6).unwrap_err().contains("no previous_signature"));
+    fs::remove_file(path).unwrap();
+}
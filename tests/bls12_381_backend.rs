@@ -0,0 +1,67 @@
+/*!
+ * bls12_381_backend.rs
+ *
+ * Runs the bit and product sigma protocols over the BLS12-381 backend, which shares all of its protocol logic with
+ * the default Ristretto one, with generators hashed into its prime-order subgroup. Only built with the `bls12-381`
+ * feature:
+ *
+ *   $ cargo test --features bls12-381 --test bls12_381_backend
+ */
+
+#![cfg(feature = "bls12-381")]
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use certified_dp::bit_sigma;
+use certified_dp::group::Bls12_381;
+use certified_dp::pedersen;
+use certified_dp::product_sigma;
+
+type Scalar = <Bls12_381 as certified_dp::group::Group>::Scalar;
+
+#[test]
+fn generators_are_hashed_into_the_prime_order_subgroup() {
+    for label in [&b""[..], b"bls12_381_backend", &[0xff; 32]] {
+        let pp = pedersen::setup::<Bls12_381>(label);
+        let g = bls12_381::G1Affine::from(pp.g);
+        assert!(bool::from(g.is_on_curve() & g.is_torsion_free()) && !bool::from(g.is_identity()));
+        assert_ne!(pp.g, pp.h);
+    }
+}
+
+#[test]
+fn bit_sigma() {
+    let mut rng = ChaCha20Rng::from_seed([7; 32]);
+    let pp = pedersen::setup::<Bls12_381>(b"bls12_381_backend");
+
+    for (b, claimed) in [(0, 0), (1, 1), (2, 1)] {
+        let (b_comm, b_proof) = pedersen::commit(&mut rng, &Scalar::from(b as u64), &pp);
+        let (mut sigma_p, commitment) = bit_sigma::commit(&mut rng, &pp, claimed, b_comm, b_proof);
+        let (mut sigma_v, challenge) = bit_sigma::challenge(&mut rng, &commitment);
+        let response = bit_sigma::response(&mut sigma_p, &challenge);
+
+        assert_eq!(bit_sigma::verify(&pp, &mut sigma_v, &response).is_ok(), b == claimed, "b = {}", b);
+    }
+}
+
+#[test]
+fn product_sigma() {
+    let mut rng = ChaCha20Rng::from_seed([8; 32]);
+    let pp = pedersen::setup::<Bls12_381>(b"bls12_381_backend");
+
+    for (m_1, m_2, m_3) in [(1, 1, 1), (0, 1, 0), (3, 5, 15), (1, 1, 0)] {
+        let mut open = |m: u64| {
+            let m = Scalar::from(m);
+            let (c, r) = pedersen::commit(&mut rng, &m, &pp);
+            (m, c, r)
+        };
+        let (first, second, third) = (open(m_1), open(m_2), open(m_3));
+
+        let (mut sigma_p, commitment) = product_sigma::commit(&mut rng, &pp, first, second, third);
+        let (mut sigma_v, challenge) = product_sigma::challenge(&mut rng, &commitment);
+        let response = product_sigma::response(&mut sigma_p, &challenge);
+
+        assert_eq!(product_sigma::verify(&pp, &mut sigma_v, &response).is_ok(), m_1 * m_2 == m_3, "{} * {} = {}", m_1, m_2, m_3);
+    }
+}
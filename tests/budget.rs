@@ -0,0 +1,109 @@
+/*!
+ * budget.rs
+ *
+ * Checks the privacy accountant: basic composition sums the queries' losses, advanced composition spends less epsilon
+ * over many queries for its slack delta, falling back to basic when that spends less or only basic fits the budget,
+ * and a charge the budget cannot cover is refused without spending anything. An epoch charges each analyst's own budget
+ * on top of the shared one, and what it spent survives a ledger saved and restored, until the next epoch starts.
+ */
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use certified_dp::budget::{parse_composition, parse_delta, Accountant, Composition};
+use certified_dp::epoch::{load_ledgers, parse_analyst, save_ledgers, Epochs};
+
+/// Whether `a` and `b` agree to within rounding
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+}
+
+#[test]
+fn basic_composition_sums_and_refuses_past_the_budget() {
+    let mut accountant = Accountant::new(Composition::Basic, Some(1.0), Some(1e-5));
+    for _ in 0..10 {
+        accountant.charge(1, 0.1, 1e-6).unwrap();
+    }
+    // ten charges of 0.1 reach the budget of 1.0 despite rounding
+    let (epsilon, delta) = accountant.spent();
+    assert!(close(epsilon, 1.0) && close(delta, 1e-5), "{} {}", epsilon, delta);
+    assert_eq!(accountant.queries(), 10);
+
+    let error = accountant.charge(1, 0.1, 0.0).unwrap_err();
+    assert!(error.contains("beyond the budget of ε = 1, δ = 1e-5"), "{}", error);
+    assert_eq!(accountant.queries(), 10);
+
+    accountant.reset();
+    assert_eq!((accountant.spent(), accountant.queries()), ((0.0, 0.0), 0));
+    // the delta budget binds too
+    assert!(accountant.charge(11, 0.01, 1e-6).unwrap_err().contains("δ = 1.100e-5"));
+    accountant.charge(4, 0.25, 0.0).unwrap();
+}
+
+#[test]
+fn advanced_composition_grows_with_the_square_root_of_the_queries() {
+    let slack = 1e-6;
+    let mut accountant = Accountant::new(Composition::Advanced(slack), None, None);
+    accountant.charge(1000, 0.01, 1e-9).unwrap();
+    let expected = (2.0 * (1.0f64 / slack).ln() * 1000.0 * 0.01 * 0.01).sqrt() + 1000.0 * 0.01 * 0.01f64.exp_m1();
+    let (epsilon, delta) = accountant.spent();
+    assert!(close(epsilon, expected) && close(delta, 1e-6 + 1e-6), "{} {}", epsilon, delta);
+    assert!(epsilon < 2.0);
+
+    // over a few queries basic composition spends less, and needs no slack
+    let mut accountant = Accountant::new(Composition::Advanced(slack), None, None);
+    accountant.charge(3, 0.5, 0.0).unwrap();
+    assert!(close(accountant.spent().0, 1.5) && accountant.spent().1 == 0.0);
+
+    // with no room for the slack, only basic composition fits, so it is the one charged
+    let mut accountant = Accountant::new(Composition::Advanced(slack), Some(10.0), Some(1e-7));
+    accountant.charge(1000, 0.01, 0.0).unwrap();
+    assert!(close(accountant.spent().0, 10.0) && accountant.spent().1 == 0.0);
+    // and a budget only advanced composition fits accepts many more queries than basic would
+    let mut accountant = Accountant::new(Composition::Advanced(slack), Some(2.0), None);
+    accountant.charge(1000, 0.01, 0.0).unwrap();
+    assert!(Accountant::new(Composition::Basic, Some(2.0), None).charge(1000, 0.01, 0.0).is_err());
+}
+
+#[test]
+fn compositions_and_deltas_parse() {
+    assert_eq!(parse_composition("basic"), Ok(Composition::Basic));
+    assert_eq!(parse_composition("advanced:1e-6"), Ok(Composition::Advanced(1e-6)));
+    assert_eq!(Composition::Advanced(1e-6).to_string(), "advanced:0.000001");
+    for s in ["", "advanced", "advanced:0", "advanced:1", "basic:1", "renyi"] {
+        assert!(parse_composition(s).is_err(), "{:?}", s);
+    }
+    assert_eq!(parse_delta("1e-5"), Ok(1e-5));
+    assert!(parse_delta("0").is_err() && parse_delta("1").is_err());
+}
+
+#[test]
+fn analysts_spend_their_own_budgets_which_ledgers_restore() {
+    let epochs = || Epochs::new(Some(Duration::from_secs(60)), Accountant::new(Composition::Basic, Some(3.0), None))
+        .with_analyst_budget(Accountant::new(Composition::Basic, Some(2.0), None));
+    let mut original = epochs();
+    original.advance(UNIX_EPOCH);
+    original.spend("alice", 2, 1.0, 0.0).unwrap();
+    // alice's own budget is spent, and refusing her spends nothing from the shared one
+    assert!(original.spend("alice", 1, 1.0, 0.0).unwrap_err().starts_with("analyst \"alice\""));
+    assert_eq!(original.accountant().queries(), 2);
+    original.spend("bob", 1, 1.0, 0.0).unwrap();
+    // the shared budget binds bob too
+    assert!(!original.spend("bob", 1, 1.0, 0.0).unwrap_err().starts_with("analyst"));
+
+    let path = std::env::temp_dir().join(format!("certified-dp-ledgers-{}.json", std::process::id()));
+    save_ledgers(&path, &[("census".to_string(), original.ledger())].into()).unwrap();
+    let ledgers = load_ledgers(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut restored = epochs();
+    restored.restore(&ledgers["census"]);
+    assert_eq!(restored.ledger(), original.ledger());
+    assert!(!restored.advance(UNIX_EPOCH + Duration::from_secs(59)));
+    assert_eq!(restored.analyst("alice").unwrap().queries(), 2);
+    assert_eq!(restored.analyst("carol").unwrap().queries(), 0);
+
+    // the next epoch starts every budget afresh
+    assert!(restored.advance(UNIX_EPOCH + Duration::from_secs(60)));
+    restored.spend("alice", 2, 1.0, 0.0).unwrap();
+    assert!(load_ledgers(&path).unwrap().is_empty());
+    assert!(parse_analyst("alice@example.org").is_ok() && parse_analyst("").is_err() && parse_analyst("a b").is_err());
+}
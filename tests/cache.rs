@@ -0,0 +1,31 @@
+/*!
+ * cache.rs
+ *
+ * Checks the digests repeated queries are recognized by: the same coefficients give the same digest whatever the order
+ * of their map, while another coefficient, another monomial, or a distinct count gives another.
+ */
+
+use curve25519_dalek::Scalar;
+use std::collections::HashMap;
+
+use certified_dp::cache::query_digest;
+use certified_dp::monomial::Monomial;
+
+#[test]
+fn digests_identify_queries_by_their_coefficients() {
+    let pairs = [(Monomial::<u16>::variable(0), Scalar::ONE), (Monomial::variable(3).times(5), -Scalar::ONE), (Monomial::variable(9), Scalar::from(4u64))];
+    let query: HashMap<_, _> = pairs.into_iter().collect();
+    let reversed: HashMap<_, _> = pairs.into_iter().rev().collect();
+    assert_eq!(query_digest(&query, false), query_digest(&reversed, false));
+
+    let mut scaled = query.clone();
+    scaled.insert(Monomial::variable(0), Scalar::from(2u64));
+    let mut moved = query.clone();
+    moved.remove(&Monomial::variable(9));
+    moved.insert(Monomial::variable(10), Scalar::from(4u64));
+    for other in [&scaled, &moved] {
+        assert_ne!(query_digest(&query, false), query_digest(other, false));
+    }
+    // a distinct count has no coefficients, but is not the empty query
+    assert_ne!(query_digest::<u16>(&HashMap::new(), true), query_digest::<u16>(&HashMap::new(), false));
+}
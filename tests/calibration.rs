@@ -0,0 +1,60 @@
+/*!
+ * calibration.rs
+ *
+ * Checks the noise calibrations: the approximate calibration keeps the coin count of N = 8 log(2/δ) / ε² per query,
+ * while zCDP and Rényi calibrations size the noise for the whole session, needing fewer coins for the same total
+ * epsilon once it has more than a few queries, though never so few that a session goes without noise, and the
+ * calibration is parsed and scaled to the session's queries.
+ */
+
+use certified_dp::calibration::{coins, delta, parse_calibration, Calibration};
+
+#[test]
+fn approximate_keeps_the_per_query_coins() {
+    assert_eq!(coins(1000, 0.5, Some(0.0078125), Calibration::Approximate), 256);
+    // the default delta of 1/size^(log size) gives N = 8 (log² size + 1) / ε²
+    assert_eq!(coins(1024, 1.0, None, Calibration::Approximate), 808);
+    assert_eq!(delta(1024, None), 1.0 / 1024f32.powi(10));
+    assert_eq!(delta(1024, Some(1e-6)), 1e-6);
+}
+
+#[test]
+fn session_calibrations_need_fewer_coins_over_many_queries() {
+    let (db_size, epsilon, d) = (1000, 1.0, Some(1e-6));
+    for queries in [16, 64, 256] {
+        let approximate = coins(db_size, epsilon, d, Calibration::Approximate);
+        let zcdp = coins(db_size, epsilon, d, Calibration::Zcdp { queries });
+        let renyi = coins(db_size, epsilon, d, Calibration::Renyi { queries });
+        assert!(zcdp < approximate && renyi < approximate, "{} queries: {} {} {}", queries, approximate, zcdp, renyi);
+        // Rényi composition is never looser than the zCDP bound it refines, up to the coarseness of its grid
+        assert!(renyi as f64 <= zcdp as f64 * 1.01, "{} queries: {} {}", queries, zcdp, renyi);
+    }
+    // the session's epsilon grows with its queries faster than their loss does, so more queries take fewer coins
+    let few = coins(db_size, 0.1, d, Calibration::Zcdp { queries: 16 });
+    let many = coins(db_size, 0.1, d, Calibration::Zcdp { queries: 256 });
+    assert!(many < few, "{} {}", few, many);
+}
+
+#[test]
+fn session_calibrations_keep_the_noise_the_bounds_call_for() {
+    let (db_size, epsilon, d) = (1000, 1.0, Some(1e-6));
+    assert_eq!(coins(db_size, epsilon, d, Calibration::Approximate), 168);
+    assert_eq!((coins(db_size, epsilon, d, Calibration::Zcdp { queries: 1 }), coins(db_size, epsilon, d, Calibration::Renyi { queries: 1 })), (115, 83));
+    assert_eq!((coins(db_size, epsilon, d, Calibration::Zcdp { queries: 10 }), coins(db_size, epsilon, d, Calibration::Renyi { queries: 10 })), (15, 13));
+    // Rényi composition only tightens the zCDP bound, by well under half
+    for queries in [1, 4, 16] {
+        let zcdp = coins(db_size, epsilon, d, Calibration::Zcdp { queries });
+        let renyi = coins(db_size, epsilon, d, Calibration::Renyi { queries });
+        assert!(renyi * 2 >= zcdp, "{} queries: {} {}", queries, zcdp, renyi);
+    }
+}
+
+#[test]
+fn calibrations_parse_and_scale_to_the_session() {
+    assert_eq!(parse_calibration("approximate"), Ok(Calibration::Approximate));
+    assert_eq!(parse_calibration("zcdp").unwrap().with_queries(8), Calibration::Zcdp { queries: 8 });
+    assert_eq!(parse_calibration("renyi").unwrap().with_queries(8), Calibration::Renyi { queries: 8 });
+    assert_eq!(Calibration::Approximate.with_queries(8), Calibration::Approximate);
+    assert!(parse_calibration("gaussian").unwrap_err().contains("approximate, zcdp, or renyi"));
+    assert_eq!(Calibration::Zcdp { queries: 8 }.to_string(), "zcdp over 8 queries");
+}
@@ -0,0 +1,39 @@
+/*!
+ * challenges.rs
+ *
+ * Checks the transcript the verifier's challenges are drawn from: keyed with the same randomness, the same transcript
+ * gives the same challenge, while a different message absorbed before it, or another label or index for it, gives
+ * another.
+ */
+
+use curve25519_dalek::Scalar;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use certified_dp::challenges::{self, ChallengeTranscript, Label};
+
+/// Challenge drawn after absorbing `message`, as labelled, keyed with the same randomness every time
+fn challenge(message: &[u8], label: Label, indices: &[u64]) -> Scalar {
+    let mut transcript = ChallengeTranscript::default();
+    transcript.absorb(b"commitment", message);
+    Scalar::random(&mut transcript.rng(label, indices, &mut ChaCha20Rng::from_seed([1; 32])))
+}
+
+#[test]
+fn same_transcript_gives_the_same_challenge() {
+    assert_eq!(challenge(b"commitment", challenges::BIT_SIGMA, &[3]), challenge(b"commitment", challenges::BIT_SIGMA, &[3]));
+}
+
+#[test]
+fn challenges_are_bound_to_the_messages_before_them() {
+    assert_ne!(challenge(b"commitment", challenges::BIT_SIGMA, &[3]), challenge(b"another commitment", challenges::BIT_SIGMA, &[3]));
+}
+
+#[test]
+fn challenges_are_separated_by_label_and_index() {
+    let node = challenge(b"commitment", challenges::PRODUCT_SIGMA, &[3, 1]);
+    assert_ne!(node, challenge(b"commitment", challenges::NOISE_PRODUCT, &[3, 1]));
+    assert_ne!(node, challenge(b"commitment", challenges::PRODUCT_SIGMA, &[3, 2]));
+    // node 1 of entry 3 is not node 3 of entry 1
+    assert_ne!(node, challenge(b"commitment", challenges::PRODUCT_SIGMA, &[1, 3]));
+}
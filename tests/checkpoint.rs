@@ -0,0 +1,27 @@
+/*!
+ * checkpoint.rs
+ *
+ * Checks the checkpoints of the randomness phase: each counts the coins flipped so far, and two sides taking them at
+ * the same windows agree on one only if they flipped the same coins in the same order.
+ */
+
+use curve25519_dalek::RistrettoPoint;
+use rand::rngs::OsRng;
+
+use certified_dp::checkpoint::Checkpoint;
+
+#[test]
+fn checkpoints_count_the_coins_flipped() {
+    let coins: Vec<_> = (0..5).map(|_| RistrettoPoint::random(&mut OsRng)).collect();
+    assert_eq!(Checkpoint::default().advance(&coins[..2]).advance(&coins[2..]).coins, 5);
+    assert_eq!(Checkpoint::default().advance([]).coins, 0);
+}
+
+#[test]
+fn checkpoints_agree_only_on_the_same_coins_in_order() {
+    let coins: Vec<_> = (0..4).map(|_| RistrettoPoint::random(&mut OsRng)).collect();
+    let checkpoint = Checkpoint::default().advance(&coins[..2]).advance(&coins[2..]);
+    assert_eq!(checkpoint, Checkpoint::default().advance(&coins[..2]).advance(&coins[2..]));
+    assert_ne!(checkpoint, Checkpoint::default().advance(&coins[..2]).advance(coins[2..].iter().rev()));
+    assert_ne!(checkpoint, Checkpoint::default().advance(&coins[..2]).advance([&coins[2], &RistrettoPoint::random(&mut OsRng)]));
+}
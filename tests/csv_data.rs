@@ -0,0 +1,47 @@
+/*!
+ * csv_data.rs
+ *
+ * Checks loading a database from a CSV file: each configured column is encoded into the next bits of the entries, in
+ * order from bit 0, whatever the order of the file's columns, and values, columns, or encodings that do not fit are
+ * refused with the row and column at fault.
+ */
+
+use std::fs;
+use std::path::PathBuf;
+
+use certified_dp::data::Data;
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Write `contents` to a CSV file of this test process named `name`
+fn csv_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certified-dp-{}-{}.csv", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn columns(specs: &[&str]) -> Schema {
+    Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+#[test]
+fn columns_encode_into_consecutive_bits() {
+    let path = csv_file("encode", "age,region,id,smoker\n5,south,1,yes\n0, east ,2,FALSE\n15,north,3,1\n");
+    let columns = columns(&["smoker=bool", "region=one-hot:north,south,east", "age=binary:4"]);
+    // smoker in bit 0, region in bits 1 to 3, and age in bits 4 to 7
+    let expected = [1 | 0b010 << 1 | 5 << 4, 0b100 << 1, 1 | 0b001 << 1 | 15 << 4];
+    assert_eq!(Data::<u16>::new_from_csv(&path, &columns).unwrap().entries, expected);
+    // wider entries hold the same bits
+    assert_eq!(Data::<u128>::new_from_csv(&path, &columns).unwrap().entries, expected.map(u128::from));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn values_columns_and_widths_that_do_not_fit_are_refused() {
+    let path = csv_file("refuse", "region,age\nnorth,3\nwest,20\n");
+    let error = |specs: &[&str]| Data::<u8>::new_from_csv(&path, &columns(specs)).err().unwrap();
+    assert!(error(&["region=one-hot:north,south"]).contains("row 2, column region"));
+    assert!(error(&["age=binary:4"]).contains("20 does not fit in 4 bits"));
+    assert!(error(&["smoker=bool"]).contains("no column \"smoker\""));
+    assert!(error(&["region=one-hot:north,west", "age=binary:7"]).contains("columns take 9 bits, but an entry holds 8"));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,132 @@
+/*!
+ * frames.rs
+ *
+ * Frames as the stream layer hands them to the protocol. In a session that compresses its frames, large ones must
+ * cross the connection gzipped and come back intact, small ones plain, and a frame without a compression marker must be
+ * refused rather than passed on as a message. Frames over the receiver's size limit, as sent or once decompressed,
+ * must be refused as a protocol violation. Once a session has an ID, a frame sent again, or ahead of its turn, must be
+ * refused as well.
+ */
+
+use certified_dp::error::Error;
+use certified_dp::messages::{compress, read_from_stream, write_message, write_to_stream, Framed, ReadyMessage, SessionId, WireFormat, COMPRESSION_THRESHOLD};
+use certified_dp::transport::{self, read_frame, Loopback, Transport, DEFAULT_MAX_FRAME_SIZE};
+
+/// A frame of `len` bytes that compresses well, as the commitment trees do
+fn frame(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 7) as u8).collect()
+}
+
+/// Both ends of an in-memory connection framing JSON messages, gzipped if `compress`, and refusing frames over `limit`
+/// bytes
+fn pair(compress: bool, limit: usize) -> (Framed<Loopback>, Framed<Loopback>) {
+    let (a, b) = Loopback::pair();
+    let (mut a, mut b) = (Framed::new(a, limit), Framed::new(b, limit));
+    a.set_wire_format(WireFormat::Json, compress);
+    b.set_wire_format(WireFormat::Json, compress);
+    (a, b)
+}
+
+#[test]
+fn large_frames_are_gzipped_and_read_back() {
+    let (mut sender, mut receiver) = pair(true, DEFAULT_MAX_FRAME_SIZE);
+    let large = frame(COMPRESSION_THRESHOLD * 4);
+
+    write_to_stream(&mut sender, &large).unwrap();
+    let on_the_wire = receiver.get_mut().recv(DEFAULT_MAX_FRAME_SIZE).unwrap();
+    assert_eq!(on_the_wire[0], 1, "large frame was not marked as gzipped");
+    assert!(on_the_wire.len() < large.len() / 4, "large frame did not shrink: {} bytes", on_the_wire.len());
+
+    write_to_stream(&mut sender, &large).unwrap();
+    assert_eq!(read_from_stream(&mut receiver).unwrap(), large);
+}
+
+#[test]
+fn small_frames_are_sent_plain() {
+    let (mut sender, mut receiver) = pair(true, DEFAULT_MAX_FRAME_SIZE);
+    let small = frame(64);
+
+    write_to_stream(&mut sender, &small).unwrap();
+    assert_eq!(receiver.get_mut().recv(DEFAULT_MAX_FRAME_SIZE).unwrap(), [&[0][..], &small].concat());
+
+    write_to_stream(&mut sender, &small).unwrap();
+    assert_eq!(read_from_stream(&mut receiver).unwrap(), small);
+}
+
+#[test]
+fn frames_without_a_marker_are_refused() {
+    let (mut sender, mut receiver) = pair(true, DEFAULT_MAX_FRAME_SIZE);
+
+    sender.get_mut().send(&[7, 1, 2, 3]).unwrap();
+    assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(_))));
+    // a marker claiming gzip over bytes that are not
+    sender.get_mut().send(&[1, 1, 2, 3]).unwrap();
+    assert!(read_from_stream(&mut receiver).is_err());
+}
+
+#[test]
+fn frames_over_the_limit_are_refused() {
+    let (mut sender, mut receiver) = pair(false, 1024);
+
+    sender.get_mut().send(&frame(1025)).unwrap();
+    assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(_))));
+    sender.get_mut().send(&frame(1024)).unwrap();
+    assert_eq!(read_from_stream(&mut receiver).unwrap(), frame(1024));
+}
+
+#[test]
+fn size_headers_over_the_limit_are_refused_before_reading() {
+    // only the header arrives, so reading the frame it announces would fail differently
+    let header = 1_000_000u32.to_le_bytes();
+    let error = read_frame(&mut &header[..], 1024).unwrap_err();
+    assert!(error.get_ref().is_some_and(|inner| inner.is::<transport::FrameTooLarge>()), "{}", error);
+}
+
+#[test]
+fn frames_that_decompress_past_the_limit_are_refused() {
+    let (mut sender, mut receiver) = pair(true, 64 * 1024);
+
+    // a few kilobytes that unpack into a megabyte
+    let bomb = [&[1][..], &compress(&vec![0; 1 << 20])].concat();
+    assert!(bomb.len() < 64 * 1024);
+    sender.get_mut().send(&bomb).unwrap();
+    assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(_))));
+}
+
+/// Frames numbered 0, 1 and 2 of a session, as sent on the wire, with both ends of a connection in that session, the
+/// receiving one yet to read any of them
+fn session_frames() -> (Vec<Vec<u8>>, Framed<Loopback>, Framed<Loopback>) {
+    let (mut sender, mut receiver) = pair(false, DEFAULT_MAX_FRAME_SIZE);
+    sender.set_session_id(Some(SessionId::new([5; 32])));
+    receiver.set_session_id(Some(SessionId::new([5; 32])));
+    let frames = (0..3).map(|_| {
+        write_message(&mut sender, &ReadyMessage { ready: true }).unwrap();
+        receiver.get_mut().recv(DEFAULT_MAX_FRAME_SIZE).unwrap()
+    }).collect();
+    (frames, sender, receiver)
+}
+
+#[test]
+fn session_frames_are_read_in_order() {
+    let (frames, mut sender, mut receiver) = session_frames();
+    for frame in &frames {
+        sender.get_mut().send(frame).unwrap();
+        assert_eq!(read_from_stream(&mut receiver).unwrap(), WireFormat::Json.encode(&ReadyMessage { ready: true }));
+    }
+}
+
+#[test]
+fn replayed_frames_are_refused() {
+    let (frames, mut sender, mut receiver) = session_frames();
+    sender.get_mut().send(&frames[0]).unwrap();
+    read_from_stream(&mut receiver).unwrap();
+    sender.get_mut().send(&frames[0]).unwrap();
+    assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(e)) if e.contains("replayed")));
+}
+
+#[test]
+fn frames_out_of_order_are_refused() {
+    let (frames, mut sender, mut receiver) = session_frames();
+    sender.get_mut().send(&frames[1]).unwrap();
+    assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(e)) if e.contains("out of order")));
+}
@@ -0,0 +1,87 @@
+/*!
+ * grpc.rs
+ *
+ * Runs the protocol over the gRPC transport, with the prover serving the `Prover` service and the verifier streaming
+ * its session to it: a session through the typed sessions verifies every query, in JSON and in compressed bincode, and
+ * a verifier connecting to no server fails to connect. Only built with the `grpc` feature:
+ *
+ *   $ cargo test --features grpc --test grpc
+ */
+
+#![cfg(feature = "grpc")]
+
+use rand::rngs::OsRng;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::grpc::{GrpcListener, GrpcStream};
+use certified_dp::messages::{Framed, SessionParams, WireFormat};
+use certified_dp::noise::Mechanism;
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::transport;
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+const NUM_QUERIES: u32 = 2;
+
+/// `stream` sending messages in `format`, gzipping large frames if `compress`
+fn framed<S>(stream: S, format: WireFormat, compress: bool) -> Framed<S> {
+    let mut stream = Framed::new(stream, transport::DEFAULT_MAX_FRAME_SIZE);
+    stream.set_wire_format(format, compress);
+    stream
+}
+
+/// Run a session over gRPC with messages in `format`, gzipping large frames if `compress`
+fn run_session(format: WireFormat, compress: bool) {
+    let listener = GrpcListener::bind("127.0.0.1:0", transport::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut framed(listener.accept()?, format, compress);
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+        let session = session.commit(stream)?;
+        let mut session = session.randomness(stream)?;
+        for _ in 0..NUM_QUERIES {
+            session.answer_query(stream)?;
+        }
+        Ok(())
+    });
+
+    let mut connection = framed(GrpcStream::connect(address, transport::DEFAULT_MAX_FRAME_SIZE).unwrap(), format, compress);
+    let stream = &mut connection;
+    let session = VerifierSession::<DataT, _>::start(stream, PARAMS).expect("setup failed");
+    let session = session.commit(stream).expect("commitment phases failed");
+    let mut session = session.randomness(stream).expect("randomness phase failed");
+    for _ in 0..NUM_QUERIES {
+        let coefficients = session.generate_query(2).expect("too few monomials committed");
+        let result = session.query(stream, &coefficients).expect("query failed");
+        assert!(result.verified, "query failed to verify: {}", result);
+    }
+    // the prover's server only stops once the verifier has closed its session
+    drop(connection);
+    prover.join().unwrap().expect("prover session failed");
+}
+
+#[test]
+fn sessions_run_over_grpc() {
+    run_session(WireFormat::Json, false);
+}
+
+#[test]
+fn sessions_run_over_grpc_with_compressed_bincode_messages() {
+    run_session(WireFormat::Bincode, true);
+}
+
+#[test]
+fn connecting_to_no_server_fails() {
+    // a port just released, on which nothing listens
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let e = GrpcStream::connect(address, transport::DEFAULT_MAX_FRAME_SIZE).err().unwrap();
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+}
@@ -0,0 +1,130 @@
+/*!
+ * http.rs
+ *
+ * Runs the protocol against a prover served over HTTP, with the verifier posting its messages as a client written in
+ * another language would: a session through the typed sessions verifies every query, a verifier hanging up fails the
+ * prover's next read, and requests to unknown or ended sessions are refused. Only built with the `http` feature:
+ *
+ *   $ cargo test --features http --test http
+ */
+
+#![cfg(feature = "http")]
+
+use rand::rngs::OsRng;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::http::HttpListener;
+use certified_dp::messages::{Framed, SessionParams};
+use certified_dp::noise::Mechanism;
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::transport::{self, Transport};
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+const NUM_QUERIES: u32 = 2;
+
+/// Send one request to the server at `address`, returning the response's status and body
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method, path, address, body.len(), body).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.split(' ').nth(1).unwrap().parse().unwrap(), body.to_string())
+}
+
+/// Open a session on the server at `address`, returning its ID
+fn open_session(address: SocketAddr) -> u64 {
+    let (status, body) = request(address, "POST", "/sessions", "");
+    assert_eq!(status, 201, "{}", body);
+    serde_json::from_str::<Value>(&body).unwrap()["session"].as_u64().unwrap()
+}
+
+/// Verifier's end of a session over HTTP: the frames it sends are held until it next reads, then posted together, and
+/// the prover's replies read in order
+struct HttpClient {
+    address: SocketAddr,
+    session: u64,
+    sent: Vec<Vec<u8>>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl Transport for HttpClient {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.sent.push(frame.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self, _limit: usize) -> io::Result<Vec<u8>> {
+        if self.received.is_empty() {
+            let messages: Vec<&str> = self.sent.iter().map(|frame| std::str::from_utf8(frame).unwrap()).collect();
+            let (status, body) = request(self.address, "POST", &format!("/sessions/{}/messages", self.session), &format!("[{}]", messages.join(",")));
+            self.sent.clear();
+            if status != 200 {
+                return Err(io::Error::other(format!("HTTP {}: {}", status, body)));
+            }
+            let replies: Vec<Box<RawValue>> = serde_json::from_str(&body).unwrap();
+            self.received.extend(replies.iter().map(|m| m.get().as_bytes().to_vec()));
+        }
+        self.received.pop_front().ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "prover sent nothing"))
+    }
+}
+
+#[test]
+fn sessions_run_over_http() {
+    let listener = HttpListener::bind("127.0.0.1:0", transport::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut Framed::new(listener.accept()?, transport::DEFAULT_MAX_FRAME_SIZE);
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+        let session = session.commit(stream)?;
+        let mut session = session.randomness(stream)?;
+        for _ in 0..NUM_QUERIES {
+            session.answer_query(stream)?;
+        }
+        Ok(())
+    });
+
+    let client = HttpClient { address, session: open_session(address), sent: Vec::new(), received: VecDeque::new() };
+    let stream = &mut Framed::new(client, transport::DEFAULT_MAX_FRAME_SIZE);
+    let session = VerifierSession::<DataT, _>::start(stream, PARAMS).expect("setup failed");
+    let session = session.commit(stream).expect("commitment phases failed");
+    let mut session = session.randomness(stream).expect("randomness phase failed");
+    for _ in 0..NUM_QUERIES {
+        let coefficients = session.generate_query(2).expect("too few monomials committed");
+        let result = session.query(stream, &coefficients).expect("query failed");
+        assert!(result.verified, "query failed to verify: {}", result);
+    }
+    prover.join().unwrap().expect("prover session failed");
+}
+
+#[test]
+fn unknown_and_ended_sessions_are_refused() {
+    let listener = HttpListener::bind("127.0.0.1:0", transport::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+    assert_eq!(request(address, "POST", "/sessions/99/messages", "[]").0, 404);
+
+    // hanging up fails the prover's next read, and the session is gone
+    let hung_up = open_session(address);
+    let mut stream = listener.accept().unwrap();
+    assert_eq!(request(address, "DELETE", &format!("/sessions/{}", hung_up), "").0, 204);
+    assert_eq!(stream.recv(transport::DEFAULT_MAX_FRAME_SIZE).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(request(address, "POST", &format!("/sessions/{}/messages", hung_up), "[]").0, 404);
+
+    // a session the prover has ended refuses the verifier's next messages
+    let ended = open_session(address);
+    drop(listener.accept().unwrap());
+    assert_eq!(request(address, "POST", &format!("/sessions/{}/messages", ended), r#"[{"ready": true}]"#).0, 410);
+}
@@ -0,0 +1,69 @@
+/*!
+ * jsonl_data.rs
+ *
+ * Checks loading a database from a JSON-lines file, with its columns from a schema file: the fields are encoded into
+ * the entries as the same columns of a CSV file are, and schemas, records, and values that do not fit are refused with
+ * the line and column at fault.
+ */
+
+use std::fs;
+use std::path::PathBuf;
+
+use certified_dp::data::Data;
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Write `contents` to a file of this test process named `name`
+fn file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certified-dp-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn fields_encode_as_csv_columns() {
+    let schema = file("encode-schema.json", r#"[
+        {"name": "smoker", "encoding": "bool"},
+        {"name": "region", "encoding": {"one-hot": ["north", "south", "east"]}},
+        {"name": "age", "encoding": {"binary": 4}}
+    ]"#);
+    let columns = Schema::load(&schema).unwrap();
+    let specs = ["smoker=bool", "region=one-hot:north,south,east", "age=binary:4"];
+    assert_eq!(columns, Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap());
+
+    let jsonl = file("encode.jsonl", concat!(
+        r#"{"age": 5, "region": "south", "id": 1, "smoker": true}"#, "\n",
+        "\n",
+        r#"{"age": 0, "region": "east", "id": 2, "smoker": "no", "note": [1, 2]}"#, "\n",
+        r#"{"age": 15, "region": "north", "id": 3, "smoker": 1}"#, "\n",
+    ));
+    let csv = file("encode.csv", "age,region,id,smoker\n5,south,1,yes\n0,east,2,false\n15,north,3,1\n");
+    let database = Data::<u16>::new_from_jsonl(&jsonl, &columns).unwrap();
+    assert_eq!(database.entries, Data::<u16>::new_from_csv(&csv, &columns).unwrap().entries);
+    assert_eq!(database.entries, [1 | 0b010 << 1 | 5 << 4, 0b100 << 1, 1 | 0b001 << 1 | 15 << 4]);
+    for path in [schema, jsonl, csv] {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn schemas_records_and_values_that_do_not_fit_are_refused() {
+    for (schema, error) in [
+        (r#"[{"name": "region", "encoding": {"one-hot": []}}]"#, "attribute region: one-hot categories"),
+        (r#"[{"name": "age", "encoding": {"binary": 65}}]"#, "attribute age: binary attributes take 1 to 64 bits, got 65"),
+        (r#"[{"name": "age", "encoding": "decimal"}]"#, "unknown variant"),
+    ] {
+        let path = file("refuse-schema.json", schema);
+        assert!(Schema::load(&path).unwrap_err().contains(error), "{}", schema);
+        fs::remove_file(path).unwrap();
+    }
+
+    let path = file("refuse.jsonl", "{\"smoker\": true, \"age\": 3}\n{\"smoker\": false, \"age\": -1}\n[true]\n");
+    let error = |specs: &[&str]| {
+        let columns = Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap();
+        Data::<u8>::new_from_jsonl(&path, &columns).err().unwrap()
+    };
+    assert!(error(&["age=binary:4"]).contains("line 2, column age: -1 is not a boolean, an unsigned integer, or a string"));
+    assert!(error(&["height=binary:4"]).contains("line 1, column height: no field"));
+    assert!(error(&["smoker=bool"]).contains("line 3, "));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,110 @@
+/*!
+ * mac.rs
+ *
+ * Checks the authentication of a session's frames: keys agreed from the two key shares authenticate each side's frames
+ * to the other, while a frame altered on the wire, replayed, reflected back at its sender, or sent without a tag is
+ * refused, as is a frame outside its envelope or a key share that would make the secret known to anyone. Keys mixing a
+ * pre-shared key only open frames sealed with keys mixing the same one.
+ */
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use rand::rngs::OsRng;
+
+use certified_dp::auth::parse_preshared_key;
+use certified_dp::driver::Side;
+use certified_dp::error::Error;
+use certified_dp::mac::{FrameKeys, KeyExchange};
+use certified_dp::messages::{read_from_stream, write_message, write_to_stream, Framed, ReadyMessage, SessionId, WireFormat};
+use certified_dp::transport::{Loopback, Transport, DEFAULT_MAX_FRAME_SIZE};
+
+/// Prover's and verifier's keys from one exchange, mixing in the pre-shared keys each holds, if any
+fn agree_with(prover_psk: Option<&str>, verifier_psk: Option<&str>) -> (FrameKeys, FrameKeys) {
+    let (prover, verifier) = (KeyExchange::new(&mut OsRng), KeyExchange::new(&mut OsRng));
+    let (prover_share, verifier_share) = (prover.share(), verifier.share());
+    let (prover_psk, verifier_psk) = (prover_psk.map(|k| parse_preshared_key(k).unwrap()), verifier_psk.map(|k| parse_preshared_key(k).unwrap()));
+    (prover.finish(Side::Prover, &verifier_share, prover_psk.as_ref()).unwrap(), verifier.finish(Side::Verifier, &prover_share, verifier_psk.as_ref()).unwrap())
+}
+
+fn agree() -> (FrameKeys, FrameKeys) {
+    agree_with(None, None)
+}
+
+#[test]
+fn each_side_opens_the_others_frames() {
+    let (mut prover, mut verifier) = agree();
+    for frame in [&b"setup"[..], b"commitments"] {
+        let tag = prover.seal(frame);
+        assert!(verifier.open(frame, &tag));
+    }
+    let tag = verifier.seal(b"challenge");
+    assert!(prover.open(b"challenge", &tag));
+}
+
+#[test]
+fn altered_replayed_and_reflected_frames_fail_to_open() {
+    let (mut prover, mut verifier) = agree();
+    let tag = prover.seal(b"commitments");
+    assert!(!verifier.open(b"commitment5", &tag));
+    assert!(verifier.open(b"commitments", &tag));
+    // the same frame again is not the next one
+    assert!(!verifier.open(b"commitments", &tag));
+    // nor does the prover take its own frame back as the verifier's
+    let tag = prover.seal(b"challenge");
+    assert!(!prover.open(b"challenge", &tag));
+}
+
+#[test]
+fn shares_that_fix_the_secret_are_refused() {
+    let identity = CompressedRistretto::default();
+    assert!(matches!(KeyExchange::new(&mut OsRng).finish(Side::Verifier, &identity, None), Err(Error::Protocol(_))));
+    assert!(matches!(KeyExchange::new(&mut OsRng).finish(Side::Verifier, &CompressedRistretto([0xff; 32]), None), Err(Error::Protocol(_))));
+}
+
+#[test]
+fn keys_mixing_a_preshared_key_need_the_same_key_on_both_sides() {
+    let psk = "8e2c0a4f6d1b3e5a7c9f0b2d4e6a8c1f3b5d7e9a0c2e4f6b8d1a3c5e7f9b0d2c";
+    let (mut prover, mut verifier) = agree_with(Some(psk), Some(psk));
+    let tag = prover.seal(b"challenge");
+    assert!(verifier.open(b"challenge", &tag));
+
+    // an attacker in the middle has the shares of its own exchange, but not the key
+    for (prover_psk, verifier_psk) in [(Some(psk), None), (None, Some(psk)), (Some(psk), Some(&psk.replace('8', "9")[..]))] {
+        let (mut prover, mut verifier) = agree_with(prover_psk, verifier_psk);
+        let tag = prover.seal(b"challenge");
+        assert!(!verifier.open(b"challenge", &tag));
+    }
+}
+
+#[test]
+fn frames_altered_on_the_wire_are_refused() {
+    for format in [WireFormat::Json, WireFormat::Bincode] {
+        let (prover, verifier) = agree();
+        let (sender, receiver) = Loopback::pair();
+        let (mut sender, mut receiver) = (Framed::new(sender, DEFAULT_MAX_FRAME_SIZE), Framed::new(receiver, DEFAULT_MAX_FRAME_SIZE));
+        sender.set_wire_format(format, false);
+        receiver.set_wire_format(format, false);
+        sender.set_frame_keys(Some(prover));
+        receiver.set_frame_keys(Some(verifier));
+        let ready = format.encode(&ReadyMessage { ready: true });
+
+        write_to_stream(&mut sender, &ready).unwrap();
+        assert_eq!(read_from_stream(&mut receiver).unwrap(), ready);
+
+        write_message(&mut sender, &ReadyMessage { ready: false }).unwrap();
+        // the message inside the envelope turned from a refusal into an acceptance
+        let mut frame = receiver.get_mut().recv(DEFAULT_MAX_FRAME_SIZE).unwrap();
+        match format {
+            WireFormat::Json => frame = String::from_utf8(frame).unwrap().replace("false", "true").into_bytes(),
+            WireFormat::Bincode => *frame.last_mut().unwrap() ^= 1,
+        }
+        sender.get_mut().send(&frame).unwrap();
+        assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(e)) if e.contains("fails authentication")));
+        // nor does a frame outside an envelope, or an envelope without a tag, get past a party that agreed keys
+        sender.get_mut().send(&ready).unwrap();
+        assert!(matches!(read_from_stream(&mut receiver), Err(Error::Malformed { message: "frame envelope", .. })));
+        sender.set_frame_keys(None);
+        sender.set_session_id(Some(SessionId::new([5; 32])));
+        write_to_stream(&mut sender, &ready).unwrap();
+        assert!(matches!(read_from_stream(&mut receiver), Err(Error::Protocol(e)) if e.contains("no authentication tag")));
+    }
+}
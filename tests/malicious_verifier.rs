@@ -0,0 +1,900 @@
+/*!
+ * malicious_verifier.rs
+ *
+ * Robustness harness that plays a misbehaving verifier against the prover executable. Each scenario follows the
+ * honest protocol up to some point, then sends a malformed, replayed, or out-of-order frame and checks that the
+ * prover aborts cleanly: no panic, a failing exit code, and no further frames (responses or answers) sent back, other
+ * than an abort naming a frame that did not decode. A phase is aborted as soon as it runs past its deadline, even while
+ * each of the verifier's messages arrives well within it. A prover serving as a daemon instead goes on to the next
+ * verifier. Sessions a prover serves concurrently are set up side by side, and answer from the database the first one
+ * committed.
+ * An analyst past their own budget is refused, even by a prover restarted since it charged them, as is a verifier
+ * naming an analyst other than the one its key is bound to.
+ */
+
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Barrier;
+use std::thread::{self, sleep};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use certified_dp::auth::{self, Credential};
+use certified_dp::bit_sigma;
+use certified_dp::config::DataT;
+use certified_dp::driver::Side;
+use certified_dp::error::Error;
+use certified_dp::generators;
+use certified_dp::joint;
+use certified_dp::mac;
+use certified_dp::messages::{read_from_stream, request_wire_format, write_to_stream, Framed, WireFormat, WireFormatMessage, AbortMessage, AbortReason, AnalystMessage, BeaconRoundMessage, BitSigmaChallengeMessage, BitSigmaCombinedChallengeMessage, BitSigmaCommitmentMessage, BitSigmaResponseMessage, CertificateSignatureMessage, CommitmentMapMessage, ContributionCommitmentMessage, CutAndChooseChallengeMessage, CutAndChooseCommitmentMessage, DatasetMessage, MonomialChallengeTree, MonomialCommitmentTree, MonomialResponseTree, ProverRandomnessComm, ProverRandomnessComms, ProverRandomnessResponses, QueryAnswerMessage, QueryMessage, ReadyMessage, SeedContributionMessage, SeedRevealMessage, SessionId, SetupMessage, SketchChallengeMessage, SketchCommitmentMessage, SpotCheckMessage, UpdateMessage, VerifierCheckMessage, VerifierRandomnessChallenge, VerifierRandomnessChallenges};
+use certified_dp::monomial::{gen_monomial_map, Monomial, MonomialTree};
+use certified_dp::product_sigma;
+use certified_dp::signature;
+use certified_dp::snapshot::SnapshotId;
+use certified_dp::transport::{Transport, DEFAULT_MAX_FRAME_SIZE};
+
+const DB_SIZE: u32 = 4;
+const DIMENSION: u32 = 3;
+const MAX_DEGREE: u32 = 2;
+
+/// A prover process under test along with the verifier end of its connection
+struct Session {
+    prover: Child,
+    stream: Framed<TcpStream>,
+    port: u16,
+}
+
+/// Pick a currently unused local port for the prover to listen on
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Spawn the prover executable with a small configuration and connect to it as the verifier
+fn start_prover(skip_dishonest: bool) -> Session {
+    start_prover_with(skip_dishonest, &[])
+}
+
+/// Spawn the prover executable with additional command line arguments
+fn start_prover_with(skip_dishonest: bool, extra_args: &[&str]) -> Session {
+    let port = free_port();
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_prover"));
+    cmd.args(["--db-size", &DB_SIZE.to_string()])
+        .args(["--dimension", &DIMENSION.to_string()])
+        .args(["--max-degree", &MAX_DEGREE.to_string()])
+        .args(["--epsilon", "10"])
+        .args(["--sparsity", "1"])
+        .args(["--port", &port.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    // a single query, unless the scenario asks for more
+    if !extra_args.contains(&"--num-queries") {
+        cmd.args(["--num-queries", "1"]);
+    }
+    cmd.args(extra_args);
+    if skip_dishonest {
+        cmd.arg("--skip-dishonest");
+    }
+    let mut prover = cmd.spawn().unwrap();
+
+    for _ in 0..100 {
+        if let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) {
+            let mut stream = Framed::new(stream, DEFAULT_MAX_FRAME_SIZE);
+            stream.start_transcript();
+            request_wire_format(&mut stream, WireFormatMessage { format: WireFormat::Json, compress: false }).unwrap();
+            mac::verifier_agree(&mut OsRng, &mut stream, None).unwrap();
+            return Session { prover, stream, port };
+        }
+        sleep(Duration::from_millis(50));
+    }
+    prover.kill().unwrap();
+    prover.wait().unwrap();
+    panic!("prover did not start listening on port {}", port);
+}
+
+/// Open another session with the running prover, agreeing on JSON messages and on the key its frames are
+/// authenticated with as the verifier executable does
+fn reconnect(port: u16) -> Framed<TcpStream> {
+    let mut stream = Framed::new(TcpStream::connect(("127.0.0.1", port)).unwrap(), DEFAULT_MAX_FRAME_SIZE);
+    stream.start_transcript();
+    request_wire_format(&mut stream, WireFormatMessage { format: WireFormat::Json, compress: false }).unwrap();
+    mac::verifier_agree(&mut OsRng, &mut stream, None).unwrap();
+    stream
+}
+
+/// Send a raw JSON message. A failed write is left for the scenario's checks, since the prover may already have
+/// aborted on an earlier frame.
+fn send<M: serde::Serialize>(stream: &mut Framed<TcpStream>, m: &M) {
+    let _ = write_to_stream(stream, &serde_json::to_vec(m).unwrap());
+}
+
+/// Receive and decode a message the honest prover is expected to send
+fn recv<M: serde::de::DeserializeOwned>(stream: &mut Framed<TcpStream>) -> M {
+    serde_json::from_slice(&read_from_stream(stream).unwrap()).unwrap()
+}
+
+/// Verifier side of the synchronization barrier between phases
+fn sync(stream: &mut Framed<TcpStream>) {
+    send(stream, &ReadyMessage { ready: true });
+    let _: ReadyMessage = recv(stream);
+}
+
+/// Run the setup phase, flipping the generators with the prover if it has not flipped them yet, and joining the session
+/// it names if any, as a co-verifier does
+fn setup(stream: &mut Framed<TcpStream>) {
+    let m: SetupMessage = recv(stream);
+    if let Some(id) = m.session {
+        stream.set_session_id(Some(id));
+    }
+    if m.seed_commitment.is_some() {
+        send(stream, &SeedContributionMessage { seed: generators::half(&mut OsRng) });
+        let _: SeedRevealMessage = recv(stream);
+    }
+}
+
+/// Run the honest setup and honest commitment phases
+fn honest_prefix(stream: &mut Framed<TcpStream>) -> CommitmentMapMessage<DataT> {
+    setup(stream);
+    sync(stream);
+    let m: CommitmentMapMessage<DataT> = recv(stream);
+    sync(stream);
+    m
+}
+
+/// Build an honest challenge tree mirroring a product sigma commitment tree
+fn challenge_tree(comm_tree: &MonomialCommitmentTree) -> MonomialChallengeTree {
+    comm_tree.map(|n| n.product_sigma_commitment.as_ref().map(|c| product_sigma::challenge(&mut OsRng, c).1))
+}
+
+/// Read every per-entry commitment the prover sends at the start of the dishonest phase
+fn read_dishonest_commitments(stream: &mut Framed<TcpStream>) -> Vec<(BitSigmaCommitmentMessage, MonomialCommitmentTree)> {
+    (0..DB_SIZE).map(|_| (recv(stream), recv(stream))).collect()
+}
+
+/// Honest challenges for a single entry
+fn entry_challenges(tree: &MonomialCommitmentTree) -> (BitSigmaCombinedChallengeMessage, MonomialChallengeTree) {
+    (
+        BitSigmaCombinedChallengeMessage {
+            challenge: bit_sigma::Challenge::random(&mut OsRng),
+        },
+        challenge_tree(tree),
+    )
+}
+
+/// Derive the session's ID as its commitment phases end, as the prover does, then pass the barrier into the next phase
+fn enter_session(stream: &mut Framed<TcpStream>) {
+    stream.enter_session(Side::Verifier);
+    sync(stream);
+}
+
+/// Run the honest randomness phase against a prover, leaving every coin as the prover committed it
+fn honest_randomness(stream: &mut Framed<TcpStream>) {
+    enter_session(stream);
+    let m: ProverRandomnessComms = recv(stream);
+    send(stream, &VerifierRandomnessChallenges {
+        challenges: m.commitments.iter()
+            .map(|commitment| VerifierRandomnessChallenge { player_b: 0, sigma_challenge: bit_sigma::challenge(&mut OsRng, commitment).1 })
+            .collect(),
+    });
+    let _: ProverRandomnessResponses = recv(stream);
+    send(stream, &VerifierCheckMessage { success: true });
+    sync(stream);
+}
+
+/// Pedersen commitments (not sigma commitments) in a monomial commitment tree, in preorder
+fn tree_commitments(tree: &MonomialCommitmentTree) -> Vec<serde_json::Value> {
+    tree.preorder().map(|v| serde_json::to_value(tree[v.id].commitment).unwrap()).collect()
+}
+
+/// Challenge every entry's commitments honestly, then accept the responses without checking them
+fn prove_every_entry(stream: &mut Framed<TcpStream>, comms: &[(BitSigmaCommitmentMessage, MonomialCommitmentTree)]) {
+    for (_, tree) in comms {
+        let (bits_c, tree_c) = entry_challenges(tree);
+        send(stream, &bits_c);
+        send(stream, &tree_c);
+    }
+    for _ in comms {
+        let _: BitSigmaResponseMessage = recv(stream);
+        let _: MonomialResponseTree = recv(stream);
+    }
+    send(stream, &VerifierCheckMessage { success: true });
+}
+
+/// Run the setup and honest commitment phases against a prover in spot-check mode auditing every entry, returning
+/// its entry commitments
+fn honest_spot_check_prefix(stream: &mut Framed<TcpStream>) -> Vec<(BitSigmaCommitmentMessage, MonomialCommitmentTree)> {
+    setup(stream);
+    sync(stream);
+
+    let comms = read_dishonest_commitments(stream);
+    send(stream, &SpotCheckMessage { entries: (0..DB_SIZE).collect() });
+    prove_every_entry(stream, &comms);
+    sync(stream);
+    comms
+}
+
+/// Run an honest session against a prover in spot-check mode auditing every entry, returning its entry commitments
+fn honest_spot_check_session(stream: &mut Framed<TcpStream>) -> Vec<(BitSigmaCommitmentMessage, MonomialCommitmentTree)> {
+    let comms = honest_spot_check_prefix(stream);
+
+    honest_randomness(stream);
+
+    let (bits, trees): (Vec<_>, Vec<_>) = comms.into_iter().unzip();
+    let mut commitment_map = HashMap::new();
+    gen_monomial_map::<DataT, _>(&trees, &mut commitment_map);
+
+    sync(stream);
+    send(stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(Monomial::variable(0), Scalar::ONE)]), snapshot: SnapshotId::of(commitment_map), dataset: String::new(), distinct: false });
+    let _: QueryAnswerMessage = recv(stream);
+    sync(stream);
+    bits.into_iter().zip(trees).collect()
+}
+
+/// Close our side of the connection, then check that the prover sent nothing further but the abort for a malformed
+/// frame, returned if it sent one, and exited without panicking, with one of the exit codes for a failed session
+/// rather than the one for bad arguments
+fn assert_fails_safely(mut session: Session) -> (Output, Option<AbortReason>) {
+    session.stream.get_mut().shutdown(Shutdown::Write).unwrap();
+
+    // an abort comes in an envelope like any other frame once keys are agreed
+    let abort = match read_from_stream(&mut session.stream) {
+        Ok(frame) => match serde_json::from_slice::<AbortMessage>(&frame) {
+            Ok(AbortMessage { reason: reason @ AbortReason::Malformed { .. } }) => Some(reason),
+            _ => panic!("prover sent {} bytes after misbehavior", frame.len()),
+        },
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+        Err(e) => panic!("prover sent an unreadable frame after misbehavior: {}", e),
+    };
+    let mut leaked = Vec::new();
+    session.stream.get_mut().read_to_end(&mut leaked).unwrap();
+    assert!(leaked.is_empty(), "prover sent {} bytes after misbehavior", leaked.len());
+
+    let output = session.prover.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "prover panicked:\n{}", stderr);
+    assert!(matches!(output.status.code(), Some(1 | 3..=6)), "prover did not abort ({:?}):\n{}", output.status.code(), stderr);
+    assert!(stderr.contains("ERROR"), "prover aborted without a diagnostic:\n{}", stderr);
+    (output, abort)
+}
+
+#[test]
+fn seed_contribution_cut_short() {
+    let mut s = start_prover(true);
+    let m: SetupMessage = recv(&mut s.stream);
+    assert!(m.seed_commitment.is_some(), "first session did not flip the generators");
+    send(&mut s.stream, &ReadyMessage { ready: true });
+    let (_, abort) = assert_fails_safely(s);
+    assert!(matches!(abort, Some(AbortReason::Malformed { ref message, .. }) if message.ends_with("SeedContributionMessage")), "{:?}", abort);
+}
+
+#[test]
+fn garbage_instead_of_ready() {
+    let mut s = start_prover(true);
+    setup(&mut s.stream);
+    // JSON, or it would not fit in an authenticated envelope, and be refused before it is decoded
+    write_to_stream(&mut s.stream, b"{\"ready\":\"not a flag\"}").unwrap();
+    let (output, abort) = assert_fails_safely(s);
+    assert_eq!(output.status.code(), Some(4), "garbage was not a protocol error");
+    assert!(matches!(abort, Some(AbortReason::Malformed { ref message, .. }) if message.ends_with("ReadyMessage")), "{:?}", abort);
+}
+
+#[test]
+fn truncated_frame() {
+    let mut s = start_prover(true);
+    setup(&mut s.stream);
+    s.stream.get_mut().write_all(&64u32.to_le_bytes()).unwrap();
+    s.stream.get_mut().write_all(b"{\"ready\":").unwrap();
+    assert_fails_safely(s);
+}
+
+#[test]
+fn frame_over_the_size_limit() {
+    let mut s = start_prover_with(true, &["--max-message-size", "1"]);
+    setup(&mut s.stream);
+    // the header alone must be refused, before any of the frame arrives
+    s.stream.get_mut().write_all(&(2u32 << 20).to_le_bytes()).unwrap();
+    let (output, abort) = assert_fails_safely(s);
+    assert_eq!(output.status.code(), Some(4), "oversized frame was not a protocol error");
+    assert!(abort.is_none(), "oversized frame was decoded: {:?}", abort);
+}
+
+#[test]
+fn query_before_commitment() {
+    let mut s = start_prover(true);
+    setup(&mut s.stream);
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(Monomial::variable(0), Scalar::ONE)]), snapshot: SnapshotId::default(), dataset: String::new(), distinct: false });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn legacy_per_bit_challenges() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    let bits = BitSigmaChallengeMessage {
+        challenges: comms[0].0.commitments.iter().map(|c| bit_sigma::challenge(&mut OsRng, c).1).collect(),
+    };
+    send(&mut s.stream, &bits);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn malformed_challenge_scalar() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    read_dishonest_commitments(&mut s.stream);
+    let bad = format!("{{\"challenge\":{{\"e\":[{}]}}}}", ["255"; 32].join(","));
+    write_to_stream(&mut s.stream, bad.as_bytes()).unwrap();
+    let (_, abort) = assert_fails_safely(s);
+    assert!(matches!(abort, Some(AbortReason::Malformed { .. })), "{:?}", abort);
+}
+
+#[test]
+fn mismatched_challenge_tree() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    let (bits, _) = entry_challenges(&comms[0].1);
+    // well formed, but without the degree two nodes of the commitment tree
+    let tree: MonomialChallengeTree = MonomialTree::build(DIMENSION as usize, 1, None, |_, _| None);
+    send(&mut s.stream, &bits);
+    send(&mut s.stream, &tree);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn challenge_tree_node_with_two_parents() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    let (bits, tree) = entry_challenges(&comms[0].1);
+    let mut raw = serde_json::to_value(&tree).unwrap();
+    let last = tree.len() - 1;
+    raw["nodes"][1]["children"].as_array_mut().unwrap().push(last.into());
+    send(&mut s.stream, &bits);
+    send(&mut s.stream, &raw);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn replayed_bit_challenges_in_place_of_tree() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    let (bits, _) = entry_challenges(&comms[0].1);
+    send(&mut s.stream, &bits);
+    send(&mut s.stream, &bits);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn challenges_cut_short_leak_no_responses() {
+    let mut s = start_prover(false);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    for (_, tree) in comms.iter().take(DB_SIZE as usize - 1) {
+        let (bits_c, tree_c) = entry_challenges(tree);
+        send(&mut s.stream, &bits_c);
+        send(&mut s.stream, &tree_c);
+    }
+    assert_fails_safely(s);
+}
+
+#[test]
+fn spot_check_entry_out_of_range() {
+    let mut s = start_prover_with(true, &["--spot-check", "0.5"]);
+    setup(&mut s.stream);
+    sync(&mut s.stream);
+
+    read_dishonest_commitments(&mut s.stream);
+    send(&mut s.stream, &SpotCheckMessage { entries: vec![1, DB_SIZE] });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn spot_check_smaller_than_agreed_rate() {
+    let mut s = start_prover_with(true, &["--spot-check", "0.5"]);
+    setup(&mut s.stream);
+    sync(&mut s.stream);
+
+    read_dishonest_commitments(&mut s.stream);
+    send(&mut s.stream, &SpotCheckMessage { entries: vec![2] });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn cut_and_choose_kept_encoding_out_of_range() {
+    let mut s = start_prover_with(false, &["--cut-and-choose", "3"]);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    for _ in 0..DB_SIZE {
+        let _: CutAndChooseCommitmentMessage = recv(&mut s.stream);
+    }
+    send(&mut s.stream, &CutAndChooseChallengeMessage { kept: vec![0, 1, 3, 2] });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn cut_and_choose_missing_kept_encodings() {
+    let mut s = start_prover_with(false, &["--cut-and-choose", "3"]);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    for _ in 0..DB_SIZE {
+        let _: CutAndChooseCommitmentMessage = recv(&mut s.stream);
+    }
+    send(&mut s.stream, &CutAndChooseChallengeMessage { kept: vec![0; DB_SIZE as usize - 1] });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn coin_flip_out_of_range() {
+    let mut s = start_prover(true);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+
+    let m: ProverRandomnessComms = recv(&mut s.stream);
+    send(&mut s.stream, &VerifierRandomnessChallenges {
+        challenges: m.commitments.iter()
+            .map(|commitment| VerifierRandomnessChallenge { player_b: 2, sigma_challenge: bit_sigma::challenge(&mut OsRng, commitment).1 })
+            .collect(),
+    });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn fewer_coin_flips_than_coins() {
+    let mut s = start_prover(true);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+
+    // the coins left unflipped would be the prover's own choice
+    let _: ProverRandomnessComms = recv(&mut s.stream);
+    send(&mut s.stream, &VerifierRandomnessChallenges { challenges: Vec::new() });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn replayed_ready_in_place_of_randomness_challenge() {
+    let mut s = start_prover(true);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+
+    let _: ProverRandomnessComms = recv(&mut s.stream);
+    send(&mut s.stream, &ReadyMessage { ready: true });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn co_verifier_replays_contribution_digest() {
+    let mut s = start_prover_with(true, &["--verifiers", "2"]);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+
+    // the co-verifier's setup carries no session ID, but names the session it joins
+    let mut co_verifier = Framed::new(TcpStream::connect(("127.0.0.1", s.port)).unwrap(), DEFAULT_MAX_FRAME_SIZE);
+    mac::verifier_agree(&mut OsRng, &mut co_verifier, None).unwrap();
+    setup(&mut co_verifier);
+    assert_eq!(co_verifier.session_id(), s.stream.session_id());
+    // the co-verifier's frames are numbered apart from the verifier's
+    let _: ProverRandomnessComm = recv(&mut s.stream);
+    let _: ProverRandomnessComm = recv(&mut co_verifier);
+
+    // counting the verifier's contribution twice would cancel out its coin
+    let digest = joint::digest(&joint::contribute(&mut OsRng));
+    send(&mut s.stream, &ContributionCommitmentMessage { digest });
+    send(&mut co_verifier, &ContributionCommitmentMessage { digest });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn beacon_round_published_before_commitments() {
+    let path = std::env::temp_dir().join(format!("certified-dp-beacon-{}.jsonl", std::process::id()));
+    let genesis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 1000;
+    // the chain's key is the generator of G1, which no round of the file needs to check against
+    let key = "97f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb";
+    fs::write(&path, format!("{{\"public_key\":\"{}\",\"period\":1,\"genesis_time\":{}}}\n", key, genesis)).unwrap();
+
+    let mut s = start_prover_with(true, &["--beacon", path.to_str().unwrap()]);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+    let _: ProverRandomnessComms = recv(&mut s.stream);
+
+    // anyone could have known an old round's randomness when the prover committed, so it certifies nothing
+    send(&mut s.stream, &BeaconRoundMessage { round: 1 });
+    assert_fails_safely(s);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn stall_past_phase_deadline() {
+    let mut s = start_prover_with(true, &["--deadline", "honest-commitment=1s"]);
+    setup(&mut s.stream);
+    sync(&mut s.stream);
+    let _: CommitmentMapMessage<DataT> = recv(&mut s.stream);
+
+    // withholding the closing barrier keeps the phase open until its deadline, when the prover says why it aborts
+    let abort: AbortMessage = recv(&mut s.stream);
+    assert!(matches!(abort.reason, AbortReason::DeadlineExceeded { ref phase, .. } if phase == "Honest commitment"), "{:?}", abort.reason);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn trickle_past_phase_deadline() {
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--num-queries", "8", "--deadline", "query=1s"]);
+    let trees: Vec<MonomialCommitmentTree> = honest_spot_check_prefix(&mut s.stream).into_iter().map(|(_, tree)| tree).collect();
+    honest_randomness(&mut s.stream);
+    let mut commitment_map = HashMap::new();
+    gen_monomial_map::<DataT, _>(&trees, &mut commitment_map);
+    let query = QueryMessage::<DataT> { coefficients: HashMap::from([(Monomial::variable(0), Scalar::ONE)]), snapshot: SnapshotId::of(commitment_map), dataset: String::new(), distinct: false };
+
+    // each message arrives well within the deadline, but the rounds of queries together run past it, which the prover
+    // aborts on partway through them rather than after the last
+    let mut answered = 0;
+    let abort = 'rounds: loop {
+        for step in 0..3 {
+            sleep(Duration::from_millis(200));
+            match step {
+                1 => send(&mut s.stream, &query),
+                _ => send(&mut s.stream, &ReadyMessage { ready: true }),
+            }
+            let frame = read_from_stream(&mut s.stream).unwrap();
+            if let Ok(abort) = serde_json::from_slice::<AbortMessage>(&frame) {
+                break 'rounds abort;
+            }
+        }
+        answered += 1;
+    };
+    assert!(matches!(abort.reason, AbortReason::DeadlineExceeded { ref phase, .. } if phase == "Query"), "{:?}", abort.reason);
+    assert!(answered < 3, "prover answered {} queries past the deadline", answered);
+    // the prover hangs up on the frame already on its way, so there is no connection left to check for leaks
+    let output = s.prover.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "prover panicked:\n{}", stderr);
+    assert_eq!(output.status.code(), Some(6), "{}", stderr);
+}
+
+#[test]
+fn certificate_signed_over_another_statement() {
+    let path = std::env::temp_dir().join(format!("certified-dp-certificate-{}.json", std::process::id()));
+
+    // the subcommand goes last, so --skip-dishonest is passed ahead of it rather than appended
+    let mut s = start_prover_with(false, &["--skip-dishonest", "certify", "--out", path.to_str().unwrap()]);
+    honest_prefix(&mut s.stream);
+    enter_session(&mut s.stream);
+
+    // a signature on anything but the session's statement must not end up in a certificate
+    let (sk, key) = signature::keygen(&mut OsRng);
+    send(&mut s.stream, &CertificateSignatureMessage { key, signature: sk.sign(&[0u8; 32]) });
+    assert_fails_safely(s);
+    assert!(!path.exists(), "prover wrote a certificate it could not vouch for");
+}
+
+#[test]
+fn sketch_challenge_missing_a_bucket() {
+    let mut s = start_prover_with(false, &["--distinct", "0,1"]);
+    honest_prefix(&mut s.stream);
+    sync(&mut s.stream);
+
+    let comms = read_dishonest_commitments(&mut s.stream);
+    prove_every_entry(&mut s.stream, &comms);
+    let sketch_m: SketchCommitmentMessage = recv(&mut s.stream);
+    assert_eq!(sketch_m.chains.len(), 4);
+
+    // challenges for every bucket's chain but the first, which would leave its product proofs unanswered
+    send(&mut s.stream, &SketchChallengeMessage {
+        indicators: bit_sigma::Challenge::random(&mut OsRng),
+        chains: sketch_m.chains[1..].iter().map(|chain| chain.iter().map(|c| product_sigma::challenge(&mut OsRng, c).1).collect()).collect(),
+    });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn distinct_query_without_a_sketch() {
+    let mut s = start_prover(true);
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::new(), snapshot: map.snapshot, dataset: String::new(), distinct: true });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn query_for_unknown_monomial() {
+    let mut s = start_prover(true);
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    let unknown = Monomial::<DataT>::new((1 << DIMENSION) - 1);
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(unknown, Scalar::ONE)]), snapshot: map.snapshot, dataset: String::new(), distinct: false });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn query_for_other_snapshot() {
+    let mut s = start_prover(true);
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    let monomial = *map.commitment_map.keys().next().unwrap();
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(monomial, Scalar::ONE)]), snapshot: SnapshotId::default(), dataset: String::new(), distinct: false });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn session_for_unknown_dataset() {
+    let mut s = start_prover_with(true, &["--dataset", "census", "--dataset", "payroll"]);
+    send(&mut s.stream, &DatasetMessage { name: "survey".to_string() });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn query_for_other_dataset() {
+    let mut s = start_prover_with(true, &["--dataset", "census", "--dataset", "payroll,max-degree=1"]);
+    send(&mut s.stream, &DatasetMessage { name: "payroll".to_string() });
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    let monomial = *map.commitment_map.keys().next().unwrap();
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(monomial, Scalar::ONE)]), snapshot: map.snapshot, dataset: "census".to_string(), distinct: false });
+    assert_fails_safely(s);
+}
+
+#[test]
+fn query_for_named_dataset_is_answered() {
+    let mut s = start_prover_with(true, &["--dataset", "census", "--dataset", "payroll,max-degree=1"]);
+    send(&mut s.stream, &DatasetMessage { name: "payroll".to_string() });
+    let map = honest_prefix(&mut s.stream);
+    // only the monomials up to the dataset's own max degree are committed
+    assert!(map.commitment_map.keys().all(|monomial| monomial.degree() <= 1));
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    let monomial = *map.commitment_map.keys().next().unwrap();
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(monomial, Scalar::ONE)]), snapshot: map.snapshot, dataset: "payroll".to_string(), distinct: false });
+    let _: QueryAnswerMessage = recv(&mut s.stream);
+    sync(&mut s.stream);
+
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn honest_query_is_answered() {
+    let mut s = start_prover(true);
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    sync(&mut s.stream);
+    let monomial = *map.commitment_map.keys().next().unwrap();
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(monomial, Scalar::ONE)]), snapshot: map.snapshot, dataset: String::new(), distinct: false });
+    let _: QueryAnswerMessage = recv(&mut s.stream);
+    sync(&mut s.stream);
+
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn progressive_query_without_its_noise() {
+    let mut s = start_prover_with(true, &["--progressive", "--num-queries", "2"]);
+    let map = honest_prefix(&mut s.stream);
+    honest_randomness(&mut s.stream);
+
+    let monomial = *map.commitment_map.keys().next().unwrap();
+    let query = QueryMessage::<DataT> { coefficients: HashMap::from([(monomial, Scalar::ONE)]), snapshot: map.snapshot, dataset: String::new(), distinct: false };
+    sync(&mut s.stream);
+    send(&mut s.stream, &query);
+    let _: QueryAnswerMessage = recv(&mut s.stream);
+    sync(&mut s.stream);
+
+    // the second query must wait for its own coins, not be answered on the first query's noise
+    sync(&mut s.stream);
+    let _: ProverRandomnessComms = recv(&mut s.stream);
+    send(&mut s.stream, &query);
+    assert_fails_safely(s);
+}
+
+#[test]
+fn auditors_share_commitments_but_not_sigma_commitments() {
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--auditors", "2"]);
+    let first = honest_spot_check_session(&mut s.stream);
+
+    let mut second_stream = reconnect(s.port);
+    let second = honest_spot_check_session(&mut second_stream);
+
+    for ((bits_1, tree_1), (bits_2, tree_2)) in first.iter().zip(second.iter()) {
+        assert_eq!(tree_commitments(tree_1), tree_commitments(tree_2));
+        for (c_1, c_2) in bits_1.commitments.iter().zip(bits_2.commitments.iter()) {
+            let (c_1, c_2) = (serde_json::to_value(c_1).unwrap(), serde_json::to_value(c_2).unwrap());
+            assert_eq!(c_1["b_comm"], c_2["b_comm"]);
+            assert_ne!(c_1["c_0"], c_2["c_0"]);
+        }
+    }
+
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn session_over_epoch_budget_is_refused() {
+    // each session spends epsilon = 10 on its one query, so the budget only covers the first
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--auditors", "2", "--epoch-budget", "15"]);
+    honest_spot_check_session(&mut s.stream);
+
+    let stream = reconnect(s.port);
+    assert_fails_safely(Session { stream, ..s });
+}
+
+#[test]
+fn analyst_over_their_own_budget_is_refused_after_a_restart() {
+    let path = std::env::temp_dir().join(format!("certified-dp-budget-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let state = path.to_str().unwrap();
+    let ((alice, alice_key), (bob, bob_key)) = (signature::keygen(&mut OsRng), signature::keygen(&mut OsRng));
+    let (alice_key, bob_key) = (format!("alice={}", alice_key.to_hex()), format!("bob={}", bob_key.to_hex()));
+    let keys = ["--authorized-key", &alice_key, "--authorized-key", &bob_key];
+
+    // each session spends epsilon = 10 on its one query, so each analyst's own budget only covers their first
+    let mut s = start_prover_with(true, &[&["--spot-check", "1", "--analyst-budget", "15", "--budget-state", state][..], &keys].concat());
+    auth::verifier_authenticate(&mut s.stream, &Credential::Key(alice.clone())).unwrap();
+    send(&mut s.stream, &AnalystMessage { name: "alice".to_string() });
+    honest_spot_check_session(&mut s.stream);
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // the restarted prover still counts alice's session, but not against bob
+    let mut s = start_prover_with(true, &[&["--spot-check", "1", "--analyst-budget", "15", "--budget-state", state, "--daemon"][..], &keys].concat());
+    auth::verifier_authenticate(&mut s.stream, &Credential::Key(alice)).unwrap();
+    send(&mut s.stream, &AnalystMessage { name: "alice".to_string() });
+    let mut leaked = Vec::new();
+    s.stream.get_mut().read_to_end(&mut leaked).unwrap();
+    assert!(leaked.is_empty(), "prover sent {} bytes to a refused analyst", leaked.len());
+    let mut stream = reconnect(s.port);
+    auth::verifier_authenticate(&mut stream, &Credential::Key(bob)).unwrap();
+    send(&mut stream, &AnalystMessage { name: "bob".to_string() });
+    honest_spot_check_session(&mut stream);
+
+    s.prover.kill().unwrap();
+    let output = s.prover.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Session refused in epoch 0: analyst \"alice\""), "{}", stderr);
+    let ledgers: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    let analysts = &ledgers[""]["analysts"];
+    assert_eq!((&analysts["alice"]["queries"], &analysts["bob"]["queries"]), (&1.into(), &1.into()));
+    assert_eq!(ledgers[""]["shared"]["queries"], 2);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn analyst_named_by_another_key_is_refused() {
+    let ((alice, alice_key), (_, bob_key)) = (signature::keygen(&mut OsRng), signature::keygen(&mut OsRng));
+    let (alice_key, bob_key) = (format!("alice={}", alice_key.to_hex()), format!("bob={}", bob_key.to_hex()));
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--analyst-budget", "15", "--daemon", "--authorized-key", &alice_key, "--authorized-key", &bob_key]);
+    auth::verifier_authenticate(&mut s.stream, &Credential::Key(alice.clone())).unwrap();
+    send(&mut s.stream, &AnalystMessage { name: "alice".to_string() });
+    honest_spot_check_session(&mut s.stream);
+
+    // alice's key cannot spend bob's budget, nor open a fresh one under a name nobody was given
+    for name in ["bob", "carol"] {
+        let mut stream = reconnect(s.port);
+        auth::verifier_authenticate(&mut stream, &Credential::Key(alice.clone())).unwrap();
+        send(&mut stream, &AnalystMessage { name: name.to_string() });
+        let abort: AbortMessage = serde_json::from_slice(&read_from_stream(&mut stream).unwrap()).unwrap();
+        assert!(matches!(abort.reason, AbortReason::Unauthorized));
+    }
+
+    s.prover.kill().unwrap();
+    let output = s.prover.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("analyst \"carol\" is not bound to the verifier's key"), "{}", stderr);
+}
+
+#[test]
+fn daemon_serves_on_past_a_failed_session() {
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--daemon"]);
+    setup(&mut s.stream);
+    s.stream.get_mut().send(b"\xff\x00not json").unwrap();
+    // the prover aborts the session and closes its connection, but keeps listening
+    let mut leaked = Vec::new();
+    s.stream.get_mut().read_to_end(&mut leaked).unwrap();
+
+    for _ in 0..2 {
+        honest_spot_check_session(&mut reconnect(s.port));
+    }
+    assert!(s.prover.try_wait().unwrap().is_none(), "daemon exited after its sessions");
+
+    s.prover.kill().unwrap();
+    let output = s.prover.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "prover panicked:\n{}", stderr);
+    assert!(stderr.contains("Session 1 failed") && stderr.contains("Session 3"), "{}", stderr);
+}
+
+#[test]
+fn concurrent_sessions_share_the_committed_database() {
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--resumable", "--auditors", "3", "--concurrent", "2"]);
+    let trees: Vec<MonomialCommitmentTree> = honest_spot_check_session(&mut s.stream).into_iter().map(|(_, tree)| tree).collect();
+    let mut commitment_map = HashMap::new();
+    gen_monomial_map::<DataT, _>(&trees, &mut commitment_map);
+    let query = QueryMessage::<DataT> { coefficients: HashMap::from([(Monomial::variable(0), Scalar::ONE)]), snapshot: SnapshotId::of(commitment_map), dataset: String::new(), distinct: false };
+
+    // both resumed sessions are set up before either queries, which a prover serving one at a time never gets to
+    // each runs on a thread of its own, with the transcript of its setup, which makes its ID as it resumes
+    let set_up = Barrier::new(2);
+    let sessions: Vec<(SessionId, QueryAnswerMessage)> = thread::scope(|scope| {
+        let sessions: Vec<_> = (0..2).map(|_| scope.spawn(|| {
+            let mut stream = reconnect(s.port);
+            stream.get_mut().set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+            setup(&mut stream);
+            let id = stream.enter_session(Side::Verifier);
+            set_up.wait();
+            sync(&mut stream);
+            send(&mut stream, &query);
+            let answer: QueryAnswerMessage = recv(&mut stream);
+            sync(&mut stream);
+            (id, answer)
+        })).collect();
+        sessions.into_iter().map(|session| session.join().unwrap()).collect()
+    });
+    assert_ne!(sessions[0].0, sessions[1].0);
+    // each session's state is its own, but both answer on the first session's commitments and noise
+    assert_eq!((sessions[0].1.answer, sessions[0].1.proof), (sessions[1].1.answer, sessions[1].1.proof));
+
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn update_is_applied_to_the_committed_snapshot() {
+    let mut s = start_prover_with(true, &["--spot-check", "1", "--add-rows", "2", "--remove-rows", "1"]);
+    let comms = honest_spot_check_prefix(&mut s.stream);
+
+    sync(&mut s.stream);
+    let update: UpdateMessage = recv(&mut s.stream);
+    let added: Vec<(BitSigmaCommitmentMessage, MonomialCommitmentTree)> = (0..update.added)
+        .map(|_| (recv(&mut s.stream), recv(&mut s.stream)))
+        .collect();
+    prove_every_entry(&mut s.stream, &added);
+    sync(&mut s.stream);
+
+    // the prover only answers queries for the snapshot of the updated database, aggregated here from scratch
+    let mut trees: Vec<MonomialCommitmentTree> = comms.into_iter().map(|(_, tree)| tree).collect();
+    for &i in update.removed.iter().rev() {
+        trees.remove(i as usize);
+    }
+    trees.extend(added.into_iter().map(|(_, tree)| tree));
+    let mut commitment_map = HashMap::new();
+    gen_monomial_map::<DataT, _>(&trees, &mut commitment_map);
+
+    honest_randomness(&mut s.stream);
+    sync(&mut s.stream);
+    send(&mut s.stream, &QueryMessage::<DataT> { coefficients: HashMap::from([(Monomial::variable(0), Scalar::ONE)]), snapshot: SnapshotId::of(commitment_map), dataset: String::new(), distinct: false });
+    let _: QueryAnswerMessage = recv(&mut s.stream);
+    sync(&mut s.stream);
+
+    let output = s.prover.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,46 @@
+/*!
+ * monomials.rs
+ *
+ * Checks the `Monomial` keys of the commitment maps: the variables, degree, and display of a monomial follow its mask,
+ * those of a monomial tree's nodes follow their paths from the root, and on the wire a monomial is just its mask, so
+ * maps keyed by monomials encode as they did when keyed by masks.
+ */
+
+use std::collections::HashMap;
+
+use certified_dp::config::DataT;
+use certified_dp::monomial::{Monomial, MonomialTree};
+
+#[test]
+fn monomial_follows_its_mask() {
+    let m = Monomial::<u16>::variable(3).times(7).times(12);
+    assert_eq!(m.mask(), (1 << 3) | (1 << 7) | (1 << 12));
+    assert_eq!(m.degree(), 3);
+    assert_eq!(m.variables().collect::<Vec<_>>(), vec![3, 7, 12]);
+    assert!(m.contains(7) && !m.contains(4) && !m.contains(16));
+    assert_eq!(m.to_string(), "x3*x7*x12");
+    assert_eq!(Monomial::<u16>::new(0).to_string(), "1");
+
+    // 128-bit masks hold the variables past the 64th
+    let wide = Monomial::<u128>::variable(3).times(70);
+    assert_eq!((wide.degree(), wide.variables().collect::<Vec<_>>()), (2, vec![3, 70]));
+    assert_eq!(wide.to_string(), "x3*x70");
+}
+
+#[test]
+fn tree_nodes_name_the_monomials_of_their_paths() {
+    let tree = MonomialTree::build(3, 2, (), |_, _| ());
+    let monomials: Vec<_> = tree.monomials::<DataT>().into_iter().map(|(m, _)| m.to_string()).collect();
+    assert_eq!(monomials, ["1", "x0", "x0*x1", "x0*x2", "x1", "x1*x2", "x2"]);
+}
+
+#[test]
+fn monomial_keys_encode_as_their_masks() {
+    let keyed = HashMap::from([(Monomial::<DataT>::new(5), 1u32)]);
+    let json = serde_json::to_string(&keyed).unwrap();
+    assert_eq!(json, serde_json::to_string(&HashMap::from([(5 as DataT, 1u32)])).unwrap());
+    assert_eq!(serde_json::from_str::<HashMap<Monomial<DataT>, u32>>(&json).unwrap(), keyed);
+    assert_eq!(bincode::serialize(&Monomial::<DataT>::new(5)).unwrap(), bincode::serialize(&(5 as DataT)).unwrap());
+    let wide = HashMap::from([(Monomial::<u128>::variable(100), 1u32)]);
+    assert_eq!(serde_json::from_str::<HashMap<Monomial<u128>, u32>>(&serde_json::to_string(&wide).unwrap()).unwrap(), wide);
+}
@@ -0,0 +1,166 @@
+/*!
+ * net.rs
+ *
+ * Runs the protocol over `certified_dp::net`, the connections driven on the shared tokio runtime: sessions over plain TCP
+ * and mutually authenticated TLS, several at once, verify every query, either end talks to a blocking `TcpStream` or
+ * TLS stream on the other, a frame announced over the limit is refused before it is read, a read past its timeout fails,
+ * and a prover certificate the verifier does not trust fails the handshake on both ends. The certificates are the TLS
+ * fixtures under `tests/fixtures/tls/`.
+ */
+
+use rand::rngs::OsRng;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::net::{NetListener, NetStream};
+use certified_dp::noise::Mechanism;
+use certified_dp::messages::{Framed, SessionParams};
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::tls::{self, ClientConfig, ServerConfig};
+use certified_dp::transport::{FrameTooLarge, Transport, DEFAULT_MAX_FRAME_SIZE};
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+const NUM_QUERIES: u32 = 2;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("tls").join(name)
+}
+
+fn prover_config(client_ca: Option<&str>) -> Arc<ServerConfig> {
+    tls::server_config(&fixture("prover.pem"), &fixture("prover.key"), client_ca.map(fixture).as_deref()).unwrap()
+}
+
+fn verifier_config(ca: &str, with_certificate: bool) -> Arc<ClientConfig> {
+    let (cert, key) = (fixture("verifier.pem"), fixture("verifier.key"));
+    tls::client_config(&fixture(ca), with_certificate.then_some((cert.as_path(), key.as_path()))).unwrap()
+}
+
+/// Run `sessions` sessions at once against one listener, each prover on its own thread, with TLS if `tls`
+fn run_sessions(sessions: usize, tls: bool) {
+    let listener = NetListener::bind("127.0.0.1:0", tls.then(|| prover_config(Some("ca.pem"))), DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+
+    let verifiers: Vec<_> = (0..sessions).map(|_| thread::spawn(move || {
+        let connection = NetStream::connect(address, tls.then(|| verifier_config("ca.pem", true)), DEFAULT_MAX_FRAME_SIZE).unwrap();
+        let stream = &mut Framed::new(connection, DEFAULT_MAX_FRAME_SIZE);
+        let session = VerifierSession::<DataT, _>::start(stream, PARAMS).expect("setup failed");
+        let session = session.commit(stream).expect("commitment phases failed");
+        let mut session = session.randomness(stream).expect("randomness phase failed");
+        for _ in 0..NUM_QUERIES {
+            let coefficients = session.generate_query(2).expect("too few monomials committed");
+            let result = session.query(stream, &coefficients).expect("query failed");
+            assert!(result.verified, "query failed to verify: {}", result);
+        }
+    })).collect();
+
+    let provers: Vec<_> = (0..sessions).map(|_| {
+        let mut stream = Framed::new(listener.accept().unwrap(), DEFAULT_MAX_FRAME_SIZE);
+        thread::spawn(move || -> certified_dp::Result<()> {
+            let stream = &mut stream;
+            let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+            let session = session.commit(stream)?;
+            let mut session = session.randomness(stream)?;
+            for _ in 0..NUM_QUERIES {
+                session.answer_query(stream)?;
+            }
+            Ok(())
+        })
+    }).collect();
+
+    for verifier in verifiers {
+        verifier.join().unwrap();
+    }
+    for prover in provers {
+        prover.join().unwrap().expect("prover session failed");
+    }
+}
+
+#[test]
+fn sessions_run_over_tcp() {
+    run_sessions(1, false);
+}
+
+#[test]
+fn sessions_run_over_mutually_authenticated_tls() {
+    run_sessions(1, true);
+}
+
+#[test]
+fn sessions_run_at_once_on_the_shared_runtime() {
+    run_sessions(4, true);
+}
+
+#[test]
+fn blocking_streams_talk_to_async_ones() {
+    // a blocking verifier against the async listener, over TLS
+    let listener = NetListener::bind("127.0.0.1:0", Some(prover_config(None)), DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+    let verifier = thread::spawn(move || {
+        let mut stream = tls::connect(verifier_config("ca.pem", false), "127.0.0.1", TcpStream::connect(address).unwrap()).unwrap();
+        stream.send(b"ping").unwrap();
+        stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap()
+    });
+    let mut stream = listener.accept().unwrap();
+    assert_eq!(stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap(), b"ping");
+    stream.send(&[7; 100_000]).unwrap();
+    assert_eq!(verifier.join().unwrap(), [7; 100_000]);
+
+    // an async verifier against a blocking prover, over plain TCP
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let prover = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let frame = stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap();
+        stream.send(&frame).unwrap();
+    });
+    let mut stream = NetStream::connect(address, None, DEFAULT_MAX_FRAME_SIZE).unwrap();
+    stream.send(b"echo").unwrap();
+    assert_eq!(stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap(), b"echo");
+    prover.join().unwrap();
+}
+
+#[test]
+fn oversized_frames_and_late_reads_fail() {
+    let listener = NetListener::bind("127.0.0.1:0", None, 1024).unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr()).unwrap();
+    let mut stream = listener.accept().unwrap();
+
+    // nothing sent yet, so a read with a timeout gives up
+    stream.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    assert_eq!(stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    stream.set_read_timeout(None).unwrap();
+
+    // the header alone announcing a frame over the limit is refused, with no payload sent
+    peer.write_all(&4096u32.to_le_bytes()).unwrap();
+    let e = stream.recv(DEFAULT_MAX_FRAME_SIZE).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    let refused = e.get_ref().and_then(|e| e.downcast_ref::<FrameTooLarge>()).expect("not a FrameTooLarge error");
+    assert_eq!((refused.size, refused.limit), (4096, 1024));
+
+    // as does the limit the read is given, under the listener's
+    let listener = NetListener::bind("127.0.0.1:0", None, DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let mut peer = TcpStream::connect(listener.local_addr()).unwrap();
+    let mut stream = listener.accept().unwrap();
+    peer.send(&[0; 2048]).unwrap();
+    assert_eq!(stream.recv(1024).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn untrusted_prover_certificates_fail_the_handshake() {
+    let listener = NetListener::bind("127.0.0.1:0", Some(prover_config(None)), DEFAULT_MAX_FRAME_SIZE).unwrap();
+    let address = listener.local_addr();
+    let verifier = thread::spawn(move || NetStream::connect(address, Some(verifier_config("other-ca.pem", false)), DEFAULT_MAX_FRAME_SIZE).err());
+    assert!(verifier.join().unwrap().is_some(), "verifier accepted a certificate its CA did not sign");
+    assert!(listener.accept().is_err(), "prover accepted a verifier that refused its certificate");
+}
@@ -0,0 +1,80 @@
+/*!
+ * noise.rs
+ *
+ * Checks the noise mechanisms on plain coins: the products and terms of each mechanism add up to the noise it stands
+ * for, the discrete Laplace noise spends no more than the session's epsilon, and the mechanisms that cannot run with a
+ * calibration or with co-verifiers are refused.
+ */
+
+use rand::rngs::OsRng;
+use rand::Rng;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::noise::{self, Binomial, DiscreteLaplace, Mechanism, NoiseMechanism};
+
+/// The noise `mechanism` combines `coins` into, evaluating its gates and terms on the plain bits
+fn evaluate(mechanism: &dyn NoiseMechanism, coins: &[i64]) -> i64 {
+    let not = |v: i64| 1 - v;
+    let mut products = Vec::new();
+    for (a, b) in mechanism.gates() {
+        products.push(a.resolve(coins, &products, not) * b.resolve(coins, &products, not));
+    }
+    mechanism.terms().iter().map(|(factor, weight)| weight * factor.resolve(coins, &products, not)).sum::<i64>() - mechanism.offset() as i64
+}
+
+#[test]
+fn binomial_noise_sums_the_coins() {
+    let binomial = Binomial { coins: 9 };
+    assert!(binomial.gates().is_empty());
+    let coins = [1, 0, 1, 1, 0, 0, 1, 1, 1];
+    assert_eq!(evaluate(&binomial, &coins), 6 - 4);
+}
+
+#[test]
+fn discrete_laplace_noise_counts_continued_trials() {
+    for stop in 1..4 {
+        let laplace = DiscreteLaplace { stop, trials: 5 };
+        assert_eq!(laplace.coins(), 2 * 5 * stop);
+        for _ in 0..200 {
+            let coins: Vec<i64> = (0..laplace.coins()).map(|_| OsRng.gen_range(0..2)).collect();
+            // each geometric counts its trials before the first whose coins all come up one
+            let geometric = |g: usize| {
+                coins.chunks(stop as usize).skip(g * 5).take(5).take_while(|trial| trial.contains(&0)).count() as i64
+            };
+            assert_eq!(evaluate(&laplace, &coins), geometric(0) - geometric(1), "{} coins per trial: {:?}", stop, coins);
+        }
+    }
+}
+
+#[test]
+fn discrete_laplace_noise_stays_within_the_session_epsilon() {
+    for epsilon in [2.0, 1.0, 0.5, 0.1] {
+        let laplace = DiscreteLaplace::new(epsilon, 1e-6);
+        assert!(laplace.epsilon() <= epsilon as f64, "{} {:?}", epsilon, laplace);
+        // one coin fewer per trial would spend more than epsilon
+        let looser = DiscreteLaplace { stop: laplace.stop - 1, ..laplace };
+        assert!(laplace.stop == 1 || looser.epsilon() > epsilon as f64, "{} {:?}", epsilon, laplace);
+        // running past the trials is within delta
+        assert!(laplace.ratio().powi(laplace.trials as i32) <= 0.5e-6);
+    }
+    let laplace = DiscreteLaplace::new(1.0, 1e-6);
+    assert_eq!((laplace.stop, laplace.std()), (1, 2.0));
+    // beyond 4 either way with probability 2 (1/2)^5 / (3/2) = 1/24, within 5%
+    assert_eq!(laplace.bound(0.95), 4);
+}
+
+#[test]
+fn mechanisms_parse_and_refuse_what_they_cannot_run_with() {
+    assert_eq!(noise::parse_mechanism("discrete-laplace"), Ok(Mechanism::DiscreteLaplace));
+    assert_eq!(Mechanism::DiscreteLaplace.to_string(), "discrete-laplace");
+    assert!(noise::parse_mechanism("gaussian").is_err());
+    assert_eq!(Mechanism::Binomial.noise(1000, 0.5, Some(0.0078125), Calibration::Approximate, 1).coins(), 256);
+
+    assert!(noise::check(Mechanism::Binomial, Calibration::Zcdp { queries: 4 }, 1, true).is_ok());
+    assert!(noise::check(Mechanism::Binomial, Calibration::Approximate, 3, false).is_ok());
+    // queries sharing their noise do not compose
+    assert!(noise::check(Mechanism::Binomial, Calibration::Zcdp { queries: 4 }, 1, false).unwrap_err().contains("progressive noise"));
+    assert!(noise::check(Mechanism::DiscreteLaplace, Calibration::Approximate, 1, false).is_ok());
+    assert!(noise::check(Mechanism::DiscreteLaplace, Calibration::Renyi { queries: 4 }, 1, true).unwrap_err().contains("approximate calibration"));
+    assert!(noise::check(Mechanism::DiscreteLaplace, Calibration::Approximate, 2, false).unwrap_err().contains("single verifier"));
+}
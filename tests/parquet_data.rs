@@ -0,0 +1,87 @@
+/*!
+ * parquet_data.rs
+ *
+ * Checks loading a database from a Parquet file: the configured columns are encoded into the entries as from a CSV
+ * file, whatever their Parquet types among booleans, integers, and strings, only the first rows are loaded under a
+ * row limit, and columns of other types are refused. Only built with the `parquet` feature:
+ *
+ *   $ cargo test --features parquet --test parquet_data
+ */
+
+#![cfg(feature = "parquet")]
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use certified_dp::data::Data;
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Write a Parquet file of this test process named `name`, with four rows of a boolean, a string, an integer, and a
+/// floating-point column
+fn parquet_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certified-dp-{}-{}.parquet", name, std::process::id()));
+    let schema = parse_message_type("message db { REQUIRED BOOLEAN smoker; REQUIRED BYTE_ARRAY region (UTF8); REQUIRED INT32 age; REQUIRED DOUBLE score; }").unwrap();
+    let mut writer = SerializedFileWriter::new(File::create(&path).unwrap(), Arc::new(schema), Default::default()).unwrap();
+    let mut row_group = writer.next_row_group().unwrap();
+
+    let mut column = row_group.next_column().unwrap().unwrap();
+    column.typed::<BoolType>().write_batch(&[true, false, true, false], None, None).unwrap();
+    column.close().unwrap();
+    let mut column = row_group.next_column().unwrap().unwrap();
+    let regions: Vec<ByteArray> = ["south", "east", "north", "west"].into_iter().map(ByteArray::from).collect();
+    column.typed::<ByteArrayType>().write_batch(&regions, None, None).unwrap();
+    column.close().unwrap();
+    let mut column = row_group.next_column().unwrap().unwrap();
+    column.typed::<Int32Type>().write_batch(&[5, 0, 15, 9], None, None).unwrap();
+    column.close().unwrap();
+    let mut column = row_group.next_column().unwrap().unwrap();
+    column.typed::<DoubleType>().write_batch(&[0.5, 1.5, 2.5, 3.5], None, None).unwrap();
+    column.close().unwrap();
+
+    row_group.close().unwrap();
+    writer.close().unwrap();
+    path
+}
+
+fn columns(specs: &[&str]) -> Schema {
+    Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+#[test]
+fn columns_encode_as_from_csv() {
+    let path = parquet_file("encode");
+    let columns = columns(&["smoker=bool", "region=one-hot:north,south,east,west", "age=binary:4"]);
+    // smoker in bit 0, region in bits 1 to 4, and age in bits 5 to 8
+    let expected = [1 | 0b0010 << 1 | 5 << 5, 0b0100 << 1, 1 | 0b0001 << 1 | 15 << 5, 0b1000 << 1 | 9 << 5];
+    assert_eq!(Data::<u16>::new_from_parquet(&path, &columns, None).unwrap().entries, expected);
+
+    let csv = std::env::temp_dir().join(format!("certified-dp-encode-{}.csv", std::process::id()));
+    fs::write(&csv, "smoker,region,age\ntrue,south,5\nfalse,east,0\ntrue,north,15\nfalse,west,9\n").unwrap();
+    assert_eq!(Data::<u16>::new_from_csv(&csv, &columns).unwrap().entries, expected);
+    fs::remove_file(csv).unwrap();
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn row_limit_loads_the_first_rows() {
+    let path = parquet_file("limit");
+    let columns = columns(&["age=binary:4"]);
+    assert_eq!(Data::<u8>::new_from_parquet(&path, &columns, Some(2)).unwrap().entries, vec![5, 0]);
+    assert_eq!(Data::<u8>::new_from_parquet(&path, &columns, Some(10)).unwrap().entries.len(), 4);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unsupported_and_missing_columns_are_refused() {
+    let path = parquet_file("refuse");
+    let error = |specs: &[&str]| Data::<u8>::new_from_parquet(&path, &columns(specs), None).err().unwrap();
+    assert!(error(&["score=binary:2"]).contains("row 1, column score: 0.5 is not a boolean, an integer, or a string"));
+    assert!(error(&["region=one-hot:north,south"]).contains("row 2, column region"));
+    assert!(error(&["height=binary:2"]).contains("no column \"height\""));
+    fs::remove_file(path).unwrap();
+}
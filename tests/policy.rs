@@ -0,0 +1,74 @@
+/*!
+ * policy.rs
+ *
+ * Checks the prover's query policy: a query conforms unless it breaks one of the limits given, each violation is
+ * listed in the order of the query's monomials, and a policy file's attributes resolve to the bits they take in the
+ * schema, refusing any monomial reading another attribute's bits.
+ */
+
+use curve25519_dalek::Scalar;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use certified_dp::monomial::Monomial;
+use certified_dp::policy::Policy;
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Schema of a boolean, then a categorical attribute taking bits 1 to 3
+fn schema() -> Schema {
+    Schema::new(["smoker=bool", "region=one-hot:north,south,east"].iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+/// Write `contents` to a policy file named for the test
+fn policy_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certified-dp-policy-{}-{}.json", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn default_policy_allows_every_query() {
+    let random = HashMap::from([(Monomial::<u8>::new(0b111), Scalar::from_bytes_mod_order([0x55; 32]))]);
+    assert!(Policy::default().violations(&random).is_empty());
+}
+
+#[test]
+fn violations_list_every_limit_broken() {
+    let path = policy_file("limits", r#"{"max_degree": 1, "max_sparsity": 2, "max_coefficient": 3}"#);
+    let policy = Policy::load(&path, &Schema::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let conforming = HashMap::from([(Monomial::<u8>::variable(0), Scalar::from(3u64)), (Monomial::variable(2), -Scalar::from(3u64))]);
+    assert!(policy.violations(&conforming).is_empty());
+    let query = HashMap::from([
+        (Monomial::<u8>::variable(0), -Scalar::from(4u64)),
+        (Monomial::variable(1).times(2), Scalar::ONE),
+        (Monomial::variable(3), Scalar::from_bytes_mod_order([0x55; 32])),
+    ]);
+    assert_eq!(policy.violations(&query), [
+        "query has 3 monomials, beyond 2",
+        "coefficient -4 of monomial x0 is beyond 3",
+        "monomial x1*x2 has degree 2, beyond 1",
+        "coefficient of monomial x3 is not an integer",
+    ]);
+}
+
+#[test]
+fn allowed_attributes_resolve_against_the_schema() {
+    let path = policy_file("attributes", r#"{"attributes": ["region"]}"#);
+    let policy = Policy::load(&path, &schema()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let region = HashMap::from([(Monomial::<u8>::variable(1), Scalar::ONE), (Monomial::variable(3), Scalar::ONE)]);
+    assert!(policy.violations(&region).is_empty());
+    let smoker = HashMap::from([(Monomial::<u8>::variable(0).times(2), Scalar::ONE)]);
+    assert_eq!(policy.violations(&smoker), ["monomial x0*x2 reads bit 0, outside the allowed attributes"]);
+
+    // an attribute the schema lacks, or a limit the policy does not know, is refused when the policy is loaded
+    let path = policy_file("unknown", r#"{"attributes": ["age"]}"#);
+    assert!(Policy::load(&path, &schema()).unwrap_err().contains("no attribute \"age\""));
+    fs::write(&path, r#"{"max_queries": 3}"#).unwrap();
+    assert!(Policy::load(&path, &schema()).unwrap_err().contains("max_queries"));
+    fs::remove_file(&path).unwrap();
+}
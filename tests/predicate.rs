@@ -0,0 +1,186 @@
+/*!
+ * predicate.rs
+ *
+ * Checks compiling predicates over a schema's attributes into counting queries: on every entry the schema encodes,
+ * the compiled polynomial, its constant included, is 1 exactly when the entry matches the predicate, so its sum over a
+ * database is the count of matching entries. Predicates parse and display alike, and ones the prover's commitments cannot answer
+ * are refused. Parity expands into signed powers of two, ranges over a binary attribute compile by prefix
+ * decomposition into the same kind of query, and the cells of each k-way marginal partition the entries.
+ */
+
+use curve25519_dalek::Scalar;
+
+use certified_dp::predicate::{marginals, parity_query, parse_predicate, range_query, Query};
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Schema of a boolean, a categorical, and a bucketed numeric attribute, taking bits 0, 1 to 3, and 4 to 6
+fn schema() -> Schema {
+    let specs = ["smoker=bool", "region=one-hot:north,south,east", "age=buckets:18,65"];
+    Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+/// Every entry the schema encodes, one per combination of its attributes' values
+fn entries(schema: &Schema) -> Vec<u8> {
+    let mut entries = Vec::new();
+    for smoker in ["no", "yes"] {
+        for region in ["north", "south", "east"] {
+            for age in ["10", "30", "70"] {
+                entries.push(schema.encode(&[smoker, region, age]).unwrap());
+            }
+        }
+    }
+    entries
+}
+
+/// Value of the compiled query's polynomial on `entry`
+fn evaluate(query: &Query<u8>, entry: u8) -> Scalar {
+    query.coefficients.iter().filter(|(m, _)| m.mask() & entry == m.mask()).map(|(_, c)| c).sum::<Scalar>() + query.constant
+}
+
+#[test]
+fn compiled_queries_indicate_matching_entries() {
+    let schema = schema();
+    for s in ["smoker", "!smoker", "smoker & region=north", "region=north | region=south | age=70", "!(smoker | age=10) & !region=east",
+              "smoker & (region=north | !age=70)", "!!smoker | smoker & !smoker", "smoker ^ region=north ^ age=70",
+              "smoker ^ smoker", "(smoker | age=10) ^ !region=east & age=30"] {
+        let predicate = parse_predicate(s).unwrap();
+        let query = predicate.compile::<u8>(&schema, 3).unwrap();
+        for entry in entries(&schema) {
+            let expected = if predicate.matches(&schema, entry).unwrap() { Scalar::ONE } else { Scalar::ZERO };
+            assert_eq!(evaluate(&query, entry), expected, "{} on {:#09b}", s, entry);
+        }
+    }
+
+    // a disjunction of the categories of one attribute needs no products, since only one of them is ever set
+    let query = parse_predicate("region=north | region=south").unwrap().compile::<u8>(&schema, 1).unwrap();
+    assert_eq!(query.coefficients.len(), 2);
+    let query = parse_predicate("!smoker").unwrap().compile::<u8>(&schema, 1).unwrap();
+    assert_eq!((query.coefficients.len(), query.constant), (1, Scalar::ONE));
+    // the constant counts every entry, which the answer over the other monomials then corrects
+    assert_eq!(query.count(-40, 100), Some(60));
+
+    // parity expands to coefficients ±2^(|S|-1) over each nonempty subset S of its literals
+    let query = parity_query::<u8>(&schema, &[("smoker", "true"), ("region", "north"), ("age", "70")], 3).unwrap();
+    let mut coefficients: Vec<(u32, Scalar)> = query.coefficients.iter().map(|(m, &c)| (m.degree(), c)).collect();
+    coefficients.sort_by_key(|&(degree, c)| (degree, c.to_bytes()));
+    let two = Scalar::from(2u8);
+    assert_eq!(coefficients, [(1, Scalar::ONE), (1, Scalar::ONE), (1, Scalar::ONE), (2, -two), (2, -two), (2, -two), (3, two * two)]);
+    assert!(parity_query::<u8>(&schema, &[("smoker", "true"), ("region", "north")], 1).unwrap_err().contains("of degree 2"));
+}
+
+#[test]
+fn predicates_parse_and_display_alike() {
+    for s in ["smoker", "!smoker", "smoker & region=north", "smoker | region=north & !age=70", "(smoker | region=north) & !(age=70 & smoker)",
+              "smoker ^ region=north & age=70 | age=10", "(smoker ^ region=north) & (smoker | age=10 ^ age=70)"] {
+        assert_eq!(parse_predicate(s).unwrap().to_string(), s);
+    }
+    assert_eq!(parse_predicate("  smoker&(region = north)").unwrap().to_string(), "smoker & region=north");
+    for s in ["", "smoker &", "smoker ^", "^smoker", "(smoker", "smoker)", "region=", "=north", "smoker region"] {
+        assert!(parse_predicate(s).is_err(), "{:?}", s);
+    }
+}
+
+#[test]
+fn predicates_beyond_the_commitments_are_refused() {
+    let schema = schema();
+    let error = |s: &str, max_degree| parse_predicate(s).unwrap().compile::<u8>(&schema, max_degree).unwrap_err();
+    assert!(error("smoker & region=north & age=70", 2).contains("smoker*region=north*age>=65 of degree 3, beyond the max degree 2"));
+    assert!(error("smoker | region=north", 1).contains("of degree 2"));
+    assert!(error("region=west", 2).contains("not one of the categories"));
+    assert!(error("height=3", 2).contains("no attribute \"height\""));
+}
+
+#[test]
+fn ranges_over_binary_attributes_count_by_prefix() {
+    // a flag below a 4-bit count, so ranges need not start at bit 0
+    let specs = ["smoker=bool", "children=binary:4"];
+    let schema = Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap();
+    for s in ["children>=0", "children>=5", "children>15", "children<1", "children<=9", "children=3..12", "children=6",
+              "children=12..3", "smoker & children>7", "!children=2..13"] {
+        let predicate = parse_predicate(s).unwrap();
+        let query = predicate.compile::<u8>(&schema, 4).unwrap();
+        for entry in 0..32 {
+            let expected = if predicate.matches(&schema, entry).unwrap() { Scalar::ONE } else { Scalar::ZERO };
+            assert_eq!(evaluate(&query, entry), expected, "{} on {:#07b}", s, entry);
+        }
+    }
+
+    // the terms for the bits clear in a threshold cancel down to its set bits above them, so 8 needs only the top one
+    let query = range_query::<u8>(&schema, "children", 8, u64::MAX, 1).unwrap();
+    assert_eq!((query.coefficients.len(), query.constant), (1, Scalar::ZERO));
+    assert!(range_query::<u8>(&schema, "children", 5, u64::MAX, 2).unwrap_err().contains("beyond the max degree 2"));
+    assert!(range_query::<u8>(&schema, "smoker", 0, 1, 4).unwrap_err().contains("not binary"));
+
+    for s in ["children>=5", "children<=9", "children=3..12"] {
+        assert_eq!(parse_predicate(s).unwrap().to_string(), s);
+    }
+    for s in ["children<0", "children>=-1", "children=3..", "children=>3", "children<smoker"] {
+        assert!(parse_predicate(s).is_err(), "{:?}", s);
+    }
+}
+
+#[test]
+fn marginal_cells_partition_the_entries() {
+    let schema = schema();
+    let all = marginals::<u8>(&schema, &["smoker", "region", "age"], 2, 2).unwrap();
+    let attributes: Vec<Vec<String>> = all.iter().map(|m| m.attributes.clone()).collect();
+    assert_eq!(attributes, [["smoker", "region"], ["smoker", "age"], ["region", "age"]]);
+    assert_eq!(all.iter().map(|m| m.cells.len()).collect::<Vec<_>>(), [6, 6, 9]);
+    for marginal in &all {
+        for entry in entries(&schema) {
+            // the one cell an entry falls in describes its values
+            let cells: Vec<&str> = marginal.cells.iter().filter(|c| evaluate(&c.query, entry) == Scalar::ONE).map(|c| c.description.as_str()).collect();
+            assert_eq!(cells.len(), 1, "{:?} on {:#09b}", marginal.attributes, entry);
+            assert!(marginal.cells.iter().all(|c| [Scalar::ZERO, Scalar::ONE].contains(&evaluate(&c.query, entry))));
+            let values: Vec<String> = marginal.attributes.iter().map(|name| {
+                let (_, bits) = schema.attribute(name).unwrap();
+                match bits.clone().find(|&bit| entry >> bit & 1 == 1) {
+                    Some(bit) => schema.describe(bit).unwrap(),
+                    None => format!("!{}", name),
+                }
+            }).collect();
+            assert_eq!(cells[0], values.join(" & "));
+        }
+    }
+    assert_eq!(marginals::<u8>(&schema, &["smoker", "region", "age"], 3, 3).unwrap()[0].cells[1].description, "smoker & region=north & 18<=age<65");
+
+    // a binary attribute has a cell per value, each needing every one of its bits
+    let specs = ["smoker=bool", "children=binary:2"];
+    let binary = Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap();
+    assert_eq!(marginals::<u8>(&binary, &["smoker", "children"], 2, 3).unwrap()[0].cells.len(), 8);
+    assert!(marginals::<u8>(&binary, &["smoker", "children"], 1, 1).unwrap_err().contains("beyond the max degree 1"));
+    assert!(marginals::<u8>(&schema, &["smoker", "region"], 2, 1).unwrap_err().contains("cell smoker & region=north needs"));
+    assert!(marginals::<u8>(&schema, &["smoker", "smoker"], 1, 2).unwrap_err().contains("more than once"));
+    assert!(marginals::<u8>(&schema, &["smoker"], 2, 2).is_err());
+}
+
+#[test]
+fn negated_bits_expand_into_signed_monomials() {
+    // without a schema, literals name the entry bits themselves
+    let schema = Schema::default();
+    for s in ["x3 & !x5", "x3 & x5=0", "!x0 & !x1 | x7=1", "!(x2 | x4) ^ x6=false"] {
+        let predicate = parse_predicate(s).unwrap();
+        let query = predicate.compile::<u8>(&schema, 3).unwrap();
+        for entry in 0..=u8::MAX {
+            let expected = if predicate.matches(&schema, entry).unwrap() { Scalar::ONE } else { Scalar::ZERO };
+            assert_eq!(evaluate(&query, entry), expected, "{} on {:#010b}", s, entry);
+        }
+    }
+
+    // x3 - x3*x5, so the answer over the committed monomials is the count as it is
+    let query = parse_predicate("x3 & !x5").unwrap().compile::<u8>(&schema, 2).unwrap();
+    assert_eq!(query.coefficients.len(), 2);
+    assert_eq!(query.constant, Scalar::ZERO);
+    // 1 - x0 - x1 + x0*x1, whose constant counts every entry
+    let query = parse_predicate("!x0 & !x1").unwrap().compile::<u8>(&schema, 2).unwrap();
+    assert_eq!((query.coefficients.len(), query.constant), (3, Scalar::ONE));
+    assert_eq!(query.count(-70, 100), Some(30));
+
+    let error = |s: &str| parse_predicate(s).unwrap().compile::<u8>(&schema, 2).unwrap_err();
+    assert!(error("x8").contains("beyond the 8 bits"));
+    assert!(error("x3=2").contains("1 or 0"));
+    // the schema's attributes come first, even named like bits
+    let named = Schema::new(vec![parse_attribute("x1=one-hot:a,b").unwrap()]).unwrap();
+    assert!(parse_predicate("x1=1").unwrap().compile::<u8>(&named, 2).unwrap_err().contains("not one of the categories"));
+    assert!(parse_predicate("x3 & x1=a").unwrap().compile::<u8>(&named, 2).is_ok());
+}
@@ -0,0 +1,564 @@
+/*!
+ * protocol_session.rs
+ *
+ * Runs the plain protocol in-process through the typed sessions of `certified_dp::protocol`, with the prover on its own
+ * thread, as a program embedding the protocol would: across a TCP loopback connection, the same connection under
+ * mutually authenticated TLS, and an in-memory `Loopback` that needs no sockets, with messages in JSON and in
+ * compressed bincode, also over more entries than the dishonest phase proves at a time and over entries of more than 64
+ * attributes. A verifier given other parameters or entry width than the prover's aborts the session at setup, as does
+ * one whose prover reveals a half of the generator seed other than the one it committed to, and one left with too few
+ * committed monomials for a query refuses it rather than hanging, and a prover refuses a query moving further on one
+ * entry than the sensitivity its noise is scaled to. A prover swapping the product-Σ responses of two entries is
+ * caught, and the first of them is named, though the verifier checks the entries in parallel, and one committing to
+ * other monomials, or to another number of entries, than the session's is aborted, naming them. Both parties derive the
+ * same session ID from the commitment phases, and a frame tagged with another session's is refused.
+ * A randomness phase whose connection drops partway resumes over a new one from the last checkpoint.
+ */
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::Scalar;
+use num_traits::PrimInt;
+use rand::rngs::OsRng;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::error::Error;
+use certified_dp::generators;
+use certified_dp::mac;
+use certified_dp::monomial::Monomial;
+use certified_dp::noise::Mechanism;
+use certified_dp::policy::Policy;
+use certified_dp::messages::{self, AbortReason, BatchQueryAnswerMessage, BatchQueryMessage, CommitmentMapMessage, Framed, MonomialResponseTree, SeedContributionMessage, QueryAnswerMessage, ReadyMessage, SeedRevealMessage, SessionId, SessionParams, SetupMessage, WireFormat, WIRE_VERSION};
+use certified_dp::protocol::{DISHONEST_WINDOW, RANDOMNESS_WINDOW};
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::{verifier_generate_query, verifier_init, VerifierSession};
+use certified_dp::snapshot::SnapshotId;
+use certified_dp::tls;
+use certified_dp::transport::{Loopback, Transport, DEFAULT_MAX_FRAME_SIZE};
+
+const DB_SIZE: u32 = 4;
+const DIMENSION: u32 = 3;
+const MAX_DEGREE: u32 = 2;
+const EPSILON: f32 = 5.0;
+const SPARSITY: u32 = 2;
+const NUM_QUERIES: u32 = 2;
+const PARAMS: SessionParams = SessionParams {
+    db_size: DB_SIZE, dimension: DIMENSION, max_degree: MAX_DEGREE, epsilon: EPSILON, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+
+/// `stream` framing messages in `format`, gzipping large frames if `compress`, as a session that agreed on them
+fn framed<S: Transport>(stream: S, format: WireFormat, compress: bool) -> Framed<S> {
+    let mut stream = Framed::new(stream, DEFAULT_MAX_FRAME_SIZE);
+    stream.set_wire_format(format, compress);
+    stream
+}
+
+/// Both ends of an in-memory connection, framing JSON messages
+fn pair() -> (Framed<Loopback>, Framed<Loopback>) {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    (framed(prover_stream, WireFormat::Json, false), framed(verifier_stream, WireFormat::Json, false))
+}
+
+/// Run a session with the prover on its own thread over `prover_stream`, and the verifier on this one over
+/// `verifier_stream`, both sending messages in `format`, and gzipping large frames if `compress`, checking that both
+/// derive the same session ID, and that every query verifies on the snapshot the prover committed to
+fn run_sessions<P, V>(prover_stream: P, verifier_stream: V, format: WireFormat, compress: bool)
+where P: Transport + Send + 'static, V: Transport
+{
+    run_sessions_with(prover_stream, verifier_stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, format, compress)
+}
+
+/// `run_sessions` over `database`, with the given parameters
+fn run_sessions_with<T, P, V>(prover_stream: P, verifier_stream: V, database: Data<T>, params: SessionParams, format: WireFormat, compress: bool)
+where T: PrimInt + Hash + Serialize + DeserializeOwned + Send + 'static, P: Transport + Send + 'static, V: Transport
+{
+    let mut prover_stream = framed(prover_stream, format, compress);
+    let mut verifier_stream = framed(verifier_stream, format, compress);
+    let prover = thread::spawn(move || -> certified_dp::Result<(SnapshotId, Option<SessionId>)> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, database, params, 0)?;
+        let session = session.commit(stream)?;
+        let mut session = session.randomness(stream)?;
+        for _ in 0..NUM_QUERIES {
+            session.answer_query(stream)?;
+        }
+        Ok((session.state().snapshot, stream.session_id()))
+    });
+
+    let stream = &mut verifier_stream;
+    let session = VerifierSession::<T, _>::start(stream, params).expect("setup failed");
+    let session = session.commit(stream).expect("commitment phases failed");
+    let mut session = session.randomness(stream).expect("randomness phase failed");
+    let mut results = Vec::new();
+    for _ in 0..NUM_QUERIES {
+        let coefficients = session.generate_query(SPARSITY).expect("too few monomials committed");
+        results.push(session.query(stream, &coefficients).expect("query failed"));
+    }
+
+    let (snapshot, id) = prover.join().unwrap().expect("prover session failed");
+    assert!(id.is_some() && id == verifier_stream.session_id(), "parties derived different session IDs");
+    for result in results {
+        assert!(result.verified, "query failed to verify: {}", result);
+        assert_eq!(result.snapshot, snapshot);
+    }
+}
+
+#[test]
+fn sessions_run_the_protocol_in_order() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let verifier_stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (prover_stream, _) = listener.accept().unwrap();
+    run_sessions(prover_stream, verifier_stream, WireFormat::Json, false);
+}
+
+/// Certificate or key checked in under `tests/fixtures/tls/`
+fn tls_fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("tls").join(name)
+}
+
+#[test]
+fn sessions_run_over_mutually_authenticated_tls() {
+    let server = tls::server_config(&tls_fixture("prover.pem"), &tls_fixture("prover.key"), Some(&tls_fixture("ca.pem"))).unwrap();
+    let client = tls::client_config(&tls_fixture("ca.pem"), Some((&tls_fixture("verifier.pem"), &tls_fixture("verifier.key")))).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // each side's handshake waits on the other's
+    let accepted = thread::spawn(move || tls::accept(server, listener.accept().unwrap().0));
+    let verifier_stream = tls::connect(client, "127.0.0.1", TcpStream::connect(address).unwrap()).unwrap();
+    let prover_stream = accepted.join().unwrap().unwrap();
+    run_sessions(prover_stream, verifier_stream, WireFormat::Json, false);
+}
+
+#[test]
+fn sessions_run_over_an_in_memory_loopback() {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    run_sessions(prover_stream, verifier_stream, WireFormat::Json, false);
+}
+
+#[test]
+fn sessions_run_with_compressed_bincode_messages() {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    run_sessions(prover_stream, verifier_stream, WireFormat::Bincode, true);
+}
+
+#[test]
+fn sessions_run_over_more_entries_than_one_window() {
+    // the dishonest phase proves a window of entries at a time, whose commitments the prover builds in parallel blocks;
+    // they must still arrive in order, and the monomial map must sum over every window
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let params = SessionParams { db_size: DISHONEST_WINDOW as u32 + 2, dimension: 2, ..PARAMS };
+    run_sessions_with(prover_stream, verifier_stream, Data::<DataT>::new(&mut OsRng, params.db_size), params, WireFormat::Bincode, false);
+}
+
+#[test]
+fn sessions_run_over_more_attributes_than_64_bits_hold() {
+    // with 128-bit entries, the attributes past the 64th are committed and queried like the others, in either format
+    let params = SessionParams { dimension: 70, max_degree: 1, entry_width: 128, ..PARAMS };
+    for format in [WireFormat::Json, WireFormat::Bincode] {
+        let (prover_stream, verifier_stream) = Loopback::pair();
+        run_sessions_with(prover_stream, verifier_stream, Data::<u128>::new(&mut OsRng, DB_SIZE), params, format, false);
+    }
+}
+
+#[test]
+fn sessions_run_on_discrete_laplace_noise() {
+    // the prover proves the products of coins the noise is built from before the first query
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let params = SessionParams { mechanism: Mechanism::DiscreteLaplace, ..PARAMS };
+    run_sessions_with(prover_stream, verifier_stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), params, WireFormat::Bincode, false);
+}
+
+#[test]
+fn repeated_queries_are_answered_as_before() {
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let params = SessionParams { sensitivity: 2, ..PARAMS };
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), params, 0)?;
+        let mut session = session.commit(stream)?.randomness(stream)?;
+        (0..3).try_for_each(|_| session.answer_query(stream))
+    });
+
+    let stream = &mut verifier_stream;
+    let session = VerifierSession::<DataT, _>::start(stream, params).unwrap();
+    let mut session = session.commit(stream).unwrap().randomness(stream).unwrap();
+    let count = HashMap::from([(Monomial::variable(0), Scalar::ONE), (Monomial::variable(1), Scalar::ONE)]);
+    let first = session.query(stream, &count).unwrap();
+    let other = session.query(stream, &HashMap::from([(Monomial::variable(2), Scalar::ONE)])).unwrap();
+    let repeat = session.query(stream, &count).unwrap();
+    prover.join().unwrap().expect("prover session failed");
+
+    assert!(first.verified && other.verified && repeat.verified);
+    assert!(!first.repeated && !other.repeated && repeat.repeated);
+    // the repeat spends no more of the budget on the same answer
+    assert_eq!(repeat.value, first.value);
+    assert_eq!((first.epsilon_charged, repeat.epsilon_charged), (EPSILON as f64, 0.0));
+}
+
+#[test]
+fn batched_queries_are_checked_together() {
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let params = SessionParams { sensitivity: 4, ..PARAMS };
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), params, 0)?;
+        let mut session = session.commit(stream)?.randomness(stream)?;
+        session.answer_batch(stream)?;
+        // answer the second batch by hand, off by one on its last query
+        let batch_m: BatchQueryMessage<DataT> = messages::read_message(stream)?;
+        let (state, database) = (session.state(), session.database());
+        let mut answers: Vec<QueryAnswerMessage> = batch_m.queries.iter().map(|coefficients| {
+            let (answer, proof) = coefficients.iter().fold((state.randomness_bit_sum, state.randomness_bit_proof), |(answer, proof), (m, c)| {
+                let (sum, _, r) = database.commitments[m];
+                (answer + c * sum, proof + c * r)
+            });
+            QueryAnswerMessage { answer, proof }
+        }).collect();
+        answers.last_mut().unwrap().answer += Scalar::ONE;
+        messages::write_message(stream, &BatchQueryAnswerMessage { answers })
+    });
+
+    let stream = &mut verifier_stream;
+    let session = VerifierSession::<DataT, _>::start(stream, params).unwrap();
+    let mut session = session.commit(stream).unwrap().randomness(stream).unwrap();
+    let count = HashMap::from([(Monomial::variable(0), Scalar::ONE), (Monomial::variable(1), Scalar::ONE)]);
+    let other = HashMap::from([(Monomial::variable(1), Scalar::ONE), (Monomial::variable(2), Scalar::from(3u64))]);
+    let results = session.query_batch(stream, &[count.clone(), other, count]).unwrap();
+    assert!(results.iter().all(|result| result.verified));
+    // a query repeated within the batch is answered and charged as its first occurrence
+    assert_eq!(results.iter().map(|result| result.repeated).collect::<Vec<_>>(), [false, false, true]);
+    assert_eq!((results[2].value, results[2].epsilon_charged), (results[0].value, 0.0));
+
+    // the combined check fails, and each answer is checked on its own to find the forged one
+    let queries = [HashMap::from([(Monomial::variable(2), Scalar::ONE)]), HashMap::from([(Monomial::variable(0), Scalar::from(2u64))])];
+    let results = session.query_batch(stream, &queries).unwrap();
+    prover.join().unwrap().expect("prover session failed");
+    assert_eq!(results.iter().map(|result| result.verified).collect::<Vec<_>>(), [true, false]);
+}
+
+#[test]
+fn prover_refuses_a_query_breaking_its_policy() {
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let params = SessionParams { sensitivity: 2, ..PARAMS };
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let mut policy = Policy::default();
+        (policy.max_degree, policy.max_coefficient) = (Some(1), Some(1));
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), params, 0)?.with_policy(policy);
+        let mut session = session.commit(stream)?.randomness(stream)?;
+        session.answer_query(stream)?;
+        session.answer_query(stream)
+    });
+
+    let stream = &mut verifier_stream;
+    let session = VerifierSession::<DataT, _>::start(stream, params).unwrap();
+    let mut session = session.commit(stream).unwrap().randomness(stream).unwrap();
+    // a count over single bits conforms
+    let count = HashMap::from([(Monomial::variable(0), Scalar::ONE), (Monomial::variable(2), -Scalar::ONE)]);
+    assert!(session.query(stream, &count).unwrap().verified);
+    // a product of two bits is beyond the policy's degree, and a coefficient of 7 beyond its magnitude
+    let refused = session.query(stream, &HashMap::from([(Monomial::variable(0).times(1), Scalar::from(7u64))]));
+    let Err(Error::PeerAborted(AbortReason::QueryRefused { violations })) = refused else {
+        panic!("query was not refused: {:?}", refused.map(|r| r.to_string()));
+    };
+    assert_eq!(violations, ["query has sensitivity 7, beyond the session's 2", "monomial x0*x1 has degree 2, beyond 1", "coefficient 7 of monomial x0*x1 is beyond 1"]);
+    assert!(matches!(prover.join().unwrap(), Err(Error::Aborted(AbortReason::QueryRefused { .. }))));
+}
+
+#[test]
+fn prover_refuses_a_query_beyond_its_sensitivity() {
+    // the noise only hides a change of one in the answer, which a large coefficient, or one that is no integer, scales
+    // far past
+    let large = HashMap::from([(Monomial::variable(0), Scalar::from(1_000_000u64))]);
+    let unbounded = HashMap::from([(Monomial::variable(0), Scalar::random(&mut OsRng))]);
+    let cases = [(large, "query has sensitivity 1000000, beyond the session's 1"), (unbounded, "query has coefficients that are not integers, so no bound on its sensitivity")];
+    for (query, violation) in cases {
+        let (mut prover_stream, mut verifier_stream) = pair();
+        let prover = thread::spawn(move || -> certified_dp::Result<()> {
+            let stream = &mut prover_stream;
+            let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, 0)?;
+            let mut session = session.commit(stream)?.randomness(stream)?;
+            session.answer_query(stream)
+        });
+
+        let stream = &mut verifier_stream;
+        let session = VerifierSession::<DataT, _>::start(stream, PARAMS).unwrap();
+        let mut session = session.commit(stream).unwrap().randomness(stream).unwrap();
+        let refused = session.query(stream, &query);
+        let Err(Error::PeerAborted(AbortReason::QueryRefused { violations })) = refused else {
+            panic!("query was not refused: {:?}", refused.map(|r| r.to_string()));
+        };
+        assert_eq!(violations, [violation]);
+        assert!(matches!(prover.join().unwrap(), Err(Error::Aborted(AbortReason::QueryRefused { .. }))));
+    }
+}
+
+#[test]
+fn verifier_aborts_a_session_with_other_parameters() {
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, 0)?;
+        session.commit(stream).map(|_| ())
+    });
+
+    let expected = SessionParams { dimension: DIMENSION + 1, ..PARAMS };
+    let refused = VerifierSession::<DataT, _>::start(&mut verifier_stream, expected);
+    assert!(matches!(refused, Err(Error::Aborted(AbortReason::ParameterMismatch { .. }))), "verifier did not abort");
+    // the prover learns why instead of committing to the whole database for nothing
+    let outcome = prover.join().unwrap();
+    assert!(matches!(outcome, Err(Error::PeerAborted(AbortReason::ParameterMismatch { verifier, .. })) if verifier == expected), "prover did not see the abort");
+}
+
+#[test]
+fn verifier_aborts_a_session_over_entries_of_another_width() {
+    // the masks of the monomials are encoded at the entries' width, so the two sides must agree on it
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<u32>::new(&mut OsRng, DB_SIZE), SessionParams { entry_width: 32, ..PARAMS }, 0)?;
+        session.commit(stream).map(|_| ())
+    });
+
+    let refused = VerifierSession::<DataT, _>::start(&mut verifier_stream, PARAMS);
+    assert!(matches!(refused, Err(Error::Aborted(AbortReason::ParameterMismatch { .. }))), "verifier did not abort");
+    assert!(matches!(prover.join().unwrap(), Err(Error::PeerAborted(_))), "prover did not see the abort");
+    // nor can a session claim a width its own entries do not have
+    let (mut prover_stream, _verifier_stream) = pair();
+    let misdeclared = ProverSession::start(&mut prover_stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), SessionParams { entry_width: 32, ..PARAMS }, 0);
+    assert!(matches!(misdeclared, Err(Error::Config(_))));
+}
+
+#[test]
+fn prover_changing_its_half_of_the_generator_seed_is_caught() {
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let prover = thread::spawn(move || {
+        let committed = generators::half(&mut OsRng);
+        mac::prover_agree(&mut OsRng, &mut prover_stream, None).unwrap();
+        let setup = SetupMessage {
+            seed: [0; 32], epoch: 0, version: WIRE_VERSION, params: Some(PARAMS), seed_commitment: Some(generators::commit(&committed)), nonce: [0; 32], session: None,
+        };
+        messages::write_message(&mut prover_stream, &setup).unwrap();
+        // a prover that waits for the verifier's half could otherwise pick its own to steer the generators
+        let contribution: SeedContributionMessage = messages::read_message(&mut prover_stream).unwrap();
+        messages::write_message(&mut prover_stream, &SeedRevealMessage { seed: contribution.seed }).unwrap();
+    });
+
+    let refused = VerifierSession::<DataT, _>::start(&mut verifier_stream, PARAMS);
+    assert!(matches!(refused, Err(Error::Verification(_))), "verifier accepted a seed that does not open its commitment");
+    prover.join().unwrap();
+}
+
+/// Prover's end of a connection that sends the product sigma response tree of entry `first` in place of the next
+/// entry's, and the next entry's in its place, holding back the frames in between
+struct SwapResponseTrees {
+    inner: Loopback,
+    first: usize,
+    trees: usize,
+    held: Vec<Vec<u8>>,
+}
+
+impl Transport for SwapResponseTrees {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        let is_tree = WireFormat::Json.decode::<MonomialResponseTree>(frame).is_ok();
+        if is_tree {
+            self.trees += 1;
+        }
+        match (is_tree, self.trees) {
+            (true, n) if n == self.first + 1 => self.held.push(frame.to_vec()),
+            (true, n) if n == self.first + 2 => {
+                self.inner.send(frame)?;
+                let held: Vec<_> = self.held.drain(..).collect();
+                for held in held[1..].iter().chain(&held[..1]) {
+                    self.inner.send(held)?;
+                }
+            },
+            _ if !self.held.is_empty() => self.held.push(frame.to_vec()),
+            _ => self.inner.send(frame)?,
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        self.inner.recv(limit)
+    }
+}
+
+#[test]
+fn prover_swapping_two_entries_product_responses_is_caught() {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let mut prover_stream = Framed::new(SwapResponseTrees { inner: prover_stream, first: 1, trees: 0, held: Vec::new() }, DEFAULT_MAX_FRAME_SIZE);
+    let mut verifier_stream = Framed::new(verifier_stream, DEFAULT_MAX_FRAME_SIZE);
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, 0)?;
+        // the swap stands in for the prover's own, which holds the keys, so its frames go unauthenticated
+        stream.set_frame_keys(None);
+        session.commit(stream).map(|_| ())
+    });
+
+    let session = VerifierSession::<DataT, _>::start(&mut verifier_stream, PARAMS).expect("setup failed");
+    verifier_stream.set_frame_keys(None);
+    match session.commit(&mut verifier_stream) {
+        Err(Error::Verification(e)) => assert!(e.contains("product sigma verification failed for entry 1"), "{}", e),
+        Err(e) => panic!("verifier failed otherwise than on the swapped responses: {}", e),
+        Ok(_) => panic!("verifier accepted swapped product sigma responses"),
+    }
+    assert!(matches!(prover.join().unwrap(), Err(Error::Verification(_))), "prover did not see the rejection");
+}
+
+/// Verifier's abort of a session whose prover commits to the monomials of `masks` over `entries` entries in the honest
+/// commitment phase, once the prover has seen the abort as well
+fn commit_map(masks: &[DataT], entries: u64) -> AbortReason {
+    let commitment_map = masks.iter().map(|&mask| (Monomial::<DataT>::new(mask), RistrettoPoint::default())).collect();
+    let (mut prover_stream, mut verifier_stream) = pair();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, 0)?;
+        messages::write_message(stream, &CommitmentMapMessage { commitment_map, snapshot: SnapshotId::default(), entries })?;
+        messages::read_message::<ReadyMessage, _>(stream).map(|_| ())
+    });
+
+    let session = VerifierSession::<DataT, _>::start(&mut verifier_stream, PARAMS).expect("setup failed");
+    let reason = match session.commit(&mut verifier_stream) {
+        Err(Error::Aborted(reason)) => reason,
+        Err(e) => panic!("verifier failed otherwise than by aborting: {}", e),
+        Ok(_) => panic!("verifier accepted the commitment map"),
+    };
+    assert!(matches!(prover.join().unwrap(), Err(Error::PeerAborted(seen)) if seen == reason), "prover did not see the abort");
+    reason
+}
+
+/// Masks of the session's monomials, those of degree one and two over its three attributes
+const MONOMIALS: [DataT; 6] = [0b001, 0b010, 0b100, 0b011, 0b101, 0b110];
+
+#[test]
+fn commitment_map_without_the_sessions_monomials_is_aborted() {
+    // x0 left out, and x3 put in though the session's entries have only three attributes
+    match commit_map(&[&MONOMIALS[1..], &[0b1000]].concat(), DB_SIZE as u64) {
+        AbortReason::CommitmentMapMismatch { missing, unexpected, first_missing, first_unexpected } => {
+            assert_eq!((missing, unexpected), (1, 1));
+            assert_eq!((first_missing, first_unexpected), (vec!["x0".to_string()], vec!["x3".to_string()]));
+        },
+        reason => panic!("verifier aborted otherwise than on the monomials: {}", reason),
+    }
+}
+
+#[test]
+fn commitment_map_over_too_few_entries_is_aborted() {
+    // a prover committing to fewer entries than the session's would leave the verifier waiting on the rest
+    match commit_map(&MONOMIALS, DB_SIZE as u64 - 1) {
+        AbortReason::CountMismatch { what, expected, found } => assert_eq!((what.as_str(), expected, found), ("entries", DB_SIZE as u64, DB_SIZE as u64 - 1)),
+        reason => panic!("verifier aborted otherwise than on the number of entries: {}", reason),
+    }
+}
+
+#[test]
+fn frames_from_another_session_are_refused() {
+    for format in [WireFormat::Json, WireFormat::Bincode] {
+        let (prover_stream, verifier_stream) = Loopback::pair();
+        let (mut prover_stream, mut verifier_stream) = (framed(prover_stream, format, false), framed(verifier_stream, format, false));
+        let prover = thread::spawn(move || -> certified_dp::Result<()> {
+            let stream = &mut prover_stream;
+            let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), PARAMS, 0)?;
+            let session = session.commit(stream)?;
+            // frames misrouted from another session carry that session's ID
+            stream.set_session_id(Some(SessionId::new([1; 32])));
+            session.randomness(stream).map(|_| ())
+        });
+
+        let session = VerifierSession::<DataT, _>::start(&mut verifier_stream, PARAMS).expect("setup failed");
+        let session = session.commit(&mut verifier_stream).expect("commitment phases failed");
+        match session.randomness(&mut verifier_stream) {
+            Err(Error::Protocol(e)) => assert!(e.contains("not this session"), "{}", e),
+            Err(e) => panic!("verifier failed otherwise than on the other session's frame: {}", e),
+            Ok(_) => panic!("verifier accepted frames from another session"),
+        }
+        drop(verifier_stream);
+        assert!(prover.join().unwrap().is_err(), "prover went on after the verifier refused its frame");
+    }
+}
+
+/// Prover's end of a connection that drops once `ops_left` more frames have been sent or received, e.g. partway
+/// through the randomness phase
+struct DropAfter {
+    inner: Option<Loopback>,
+    ops_left: Option<usize>,
+}
+
+impl DropAfter {
+    /// Inner connection, unless it dropped now or before
+    fn inner(&mut self) -> io::Result<&mut Loopback> {
+        match &mut self.ops_left {
+            Some(0) => self.inner = None,
+            Some(left) => *left -= 1,
+            None => {},
+        }
+        self.inner.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "connection dropped"))
+    }
+}
+
+impl Transport for DropAfter {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.inner()?.send(frame)
+    }
+
+    fn recv(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        self.inner()?.recv(limit)
+    }
+}
+
+#[test]
+fn randomness_phase_resumes_over_a_new_connection() {
+    // two windows of coins, each committed, challenged, answered and accepted in four frames on the prover's side; the
+    // connection drops before each of them in turn, and the session resumes over a new one from the last checkpoint
+    let params = SessionParams { epsilon: 0.39, ..PARAMS };
+    assert!(params.coins() > RANDOMNESS_WINDOW);
+    for drop_after in 0..7 {
+        let (prover_stream, verifier_stream) = Loopback::pair();
+        let (prover_resumed, verifier_resumed) = Loopback::pair();
+        let mut prover_stream = Framed::new(DropAfter { inner: Some(prover_stream), ops_left: None }, DEFAULT_MAX_FRAME_SIZE);
+        let mut verifier_stream = Framed::new(verifier_stream, DEFAULT_MAX_FRAME_SIZE);
+        let prover = thread::spawn(move || -> certified_dp::Result<()> {
+            let stream = &mut prover_stream;
+            let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, DB_SIZE), params, 0)?;
+            let mut session = session.commit(stream)?;
+            stream.get_mut().ops_left = Some(drop_after);
+            assert!(matches!(session.flip_coins(stream), Err(Error::Io(_))), "prover's connection did not drop");
+            stream.reconnect(DropAfter { inner: Some(prover_resumed), ops_left: None });
+            session.flip_coins(stream)?;
+            let mut session = session.randomness(stream)?;
+            session.answer_query(stream).map(|_| ())
+        });
+
+        let session = VerifierSession::<DataT, _>::start(&mut verifier_stream, params).expect("setup failed");
+        let mut session = session.commit(&mut verifier_stream).expect("commitment phases failed");
+        assert!(matches!(session.flip_coins(&mut verifier_stream), Err(Error::Io(_))), "verifier's connection did not drop");
+        verifier_stream.reconnect(verifier_resumed);
+        session.flip_coins(&mut verifier_stream).expect("resumed randomness phase failed");
+        let mut session = session.randomness(&mut verifier_stream).expect("noise failed to combine");
+        let coefficients = session.generate_query(SPARSITY).expect("too few monomials committed");
+        let result = session.query(&mut verifier_stream, &coefficients).expect("query failed");
+        prover.join().unwrap().expect("prover session failed");
+        assert!(result.verified, "query failed to verify after dropping the connection after {} frames: {}", drop_after, result);
+    }
+}
+
+#[test]
+fn query_sparser_than_the_committed_monomials_is_refused() {
+    // a prover that commits to fewer monomials than a query needs must not hang or crash the verifier
+    let mut state = verifier_init::<DataT>();
+    state.monomial_commitments.insert(Monomial::variable(0), RistrettoPoint::default());
+    assert!(matches!(verifier_generate_query(&mut state, SPARSITY, 1), Err(Error::Protocol(_))));
+}
@@ -0,0 +1,103 @@
+/*!
+ * queries.rs
+ *
+ * Checks query files: each query compiles from its predicate, or from weights over several, into the same
+ * coefficients a predicate alone would take, with the sum of its weights' magnitudes as its sensitivity, malformed or
+ * ambiguous files are refused, an answer is decoded into the count it stands for, constant included, and a query typed
+ * at the prompt compiles as a predicate or as a list of weighted monomials.
+ */
+
+use curve25519_dalek::Scalar;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use certified_dp::monomial::Monomial;
+use certified_dp::queries::{self, NamedAnswer};
+use certified_dp::result::QueryResult;
+use certified_dp::schema::{parse_attribute, Schema};
+use certified_dp::snapshot::SnapshotId;
+
+/// Schema of a boolean, then a categorical attribute taking bits 1 and 2
+fn schema() -> Schema {
+    Schema::new(["smoker=bool", "region=one-hot:north,south"].iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+/// Write `contents` to a query file named for the test
+fn query_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("certified-dp-queries-{}-{}.json", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn queries_compile_from_predicates_and_weights() {
+    let path = query_file("compile", r#"[
+        {"name": "north smokers", "predicate": "smoker & region=north"},
+        {"name": "north less south", "coefficients": {"region=north": 2, "region=south": -1}}
+    ]"#);
+    let named = queries::load::<u8>(&path, &schema(), 2).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(named.iter().map(|q| q.name.as_str()).collect::<Vec<_>>(), ["north smokers", "north less south"]);
+    assert_eq!(named[0].query.coefficients, HashMap::from([(Monomial::new(0b011), Scalar::ONE)]));
+    assert_eq!(named[0].sensitivity, 1);
+    assert_eq!(named[1].query.coefficients, HashMap::from([(Monomial::variable(1), Scalar::from(2u8)), (Monomial::variable(2), -Scalar::ONE)]));
+    assert_eq!(named[1].sensitivity, 3);
+}
+
+#[test]
+fn malformed_query_files_are_refused() {
+    for (name, contents, error) in [
+        ("empty", "[]", "no queries"),
+        ("both", r#"[{"name": "q", "predicate": "smoker", "coefficients": {"smoker": 1}}]"#, "either a predicate or coefficients"),
+        ("neither", r#"[{"name": "q"}]"#, "either a predicate or coefficients"),
+        ("twice", r#"[{"name": "q", "predicate": "smoker"}, {"name": "q", "predicate": "!smoker"}]"#, "named twice"),
+        ("unknown", r#"[{"name": "q", "predicate": "age=30"}]"#, "no attribute"),
+        ("degree", r#"[{"name": "q", "predicate": "smoker & region=north"}]"#, "beyond the max degree"),
+    ] {
+        let path = query_file(name, contents);
+        let e = queries::load::<u8>(&path, &schema(), 1).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(e.contains(error), "{}: {}", name, e);
+    }
+}
+
+#[test]
+fn answers_decode_into_counts() {
+    let path = query_file("decode", r#"[{"name": "non-smokers", "predicate": "!smoker"}]"#);
+    let named = queries::load::<u8>(&path, &schema(), 2).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // !smoker is 1 - smoker, so the answer over the smoker bit is taken from the number of entries
+    let result = QueryResult {
+        value: Some(-7),
+        verified: true,
+        noise_std: 2.0,
+        confidence: 0.95,
+        interval: Some((-11, -3)),
+        epsilon_charged: 1.0,
+        snapshot: SnapshotId::default(),
+        repeated: false,
+    };
+    let answer = NamedAnswer::new(&named[0], result, 20);
+    assert_eq!((answer.name.as_str(), answer.answer, answer.interval), ("non-smokers", Some(13), Some((9, 17))));
+}
+
+#[test]
+fn typed_queries_compile_as_predicates_or_monomials() {
+    let predicate = queries::parse_query::<u8>("smoker & region=north", &schema(), 2).unwrap();
+    assert_eq!(predicate.name, "smoker & region=north");
+    assert_eq!(predicate.query.coefficients, HashMap::from([(Monomial::new(0b011), Scalar::ONE)]));
+    assert_eq!(predicate.sensitivity, 1);
+
+    // repeated monomials merge their weights, and a bare monomial weighs 1
+    let monomials = queries::parse_query::<u8>("2*smoker*region=north, -1*x2, region=north*smoker", &schema(), 2).unwrap();
+    assert_eq!(monomials.query.coefficients, HashMap::from([(Monomial::new(0b011), Scalar::from(3u8)), (Monomial::variable(2), -Scalar::ONE)]));
+    assert_eq!(monomials.sensitivity, 4);
+
+    for (typed, error) in [("3*, smoker", "has no monomial"), ("smoker,", "has no monomial"), ("smoker*region=north", "beyond the max degree")] {
+        let e = queries::parse_query::<u8>(typed, &schema(), 1).unwrap_err();
+        assert!(e.contains(error), "{}: {}", typed, e);
+    }
+}
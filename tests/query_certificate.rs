@@ -0,0 +1,98 @@
+/*!
+ * query_certificate.rs
+ *
+ * Runs a session over an in-memory `Loopback` with the verifier keeping a certificate of each answer, alone and in a
+ * batch: every certificate re-verifies on its own after a round trip through its file, with the query's answer, while
+ * one whose answer, coefficients, or noise commitment were changed does not. A signature on a certificate's guarantee
+ * holds for that certificate alone, and no longer once its answer or epsilon change.
+ */
+
+use curve25519_dalek::Scalar;
+use rand::rngs::OsRng;
+use std::collections::HashMap;
+use std::fs;
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::messages::{Framed, SessionParams};
+use certified_dp::monomial::Monomial;
+use certified_dp::noise::Mechanism;
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::query_certificate::QueryCertificate;
+use certified_dp::signature;
+use certified_dp::transport::{Loopback, DEFAULT_MAX_FRAME_SIZE};
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+
+#[test]
+fn certificates_of_answers_verify_on_their_own() {
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let (mut prover_stream, mut verifier_stream) = (Framed::new(prover_stream, DEFAULT_MAX_FRAME_SIZE), Framed::new(verifier_stream, DEFAULT_MAX_FRAME_SIZE));
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+        let mut session = session.commit(stream)?.randomness(stream)?;
+        session.answer_query(stream)?;
+        session.answer_batch(stream)
+    });
+
+    let stream = &mut verifier_stream;
+    let session = VerifierSession::<DataT, _>::start(stream, PARAMS).unwrap();
+    let mut session = session.commit(stream).unwrap().randomness(stream).unwrap();
+    session.keep_certificates();
+    let query: HashMap<Monomial<DataT>, Scalar> = HashMap::from([(Monomial::new(0b101), Scalar::ONE)]);
+    let mut results = vec![session.query(stream, &query).unwrap()];
+    let batch = [session.generate_query(2).unwrap(), session.generate_query(2).unwrap()];
+    results.extend(session.query_batch(stream, &batch).unwrap());
+    prover.join().unwrap().unwrap();
+
+    let certificates = session.take_certificates();
+    assert_eq!(certificates.len(), 3);
+    assert!(session.take_certificates().is_empty());
+    assert_eq!(certificates[0].to_string(), "x0*x2");
+    assert_eq!(certificates[0].snapshot, session.state().snapshot);
+
+    let path = std::env::temp_dir().join(format!("certified-dp-query-certificate-{}.json", std::process::id()));
+    for (certificate, result) in certificates.iter().zip(&results) {
+        assert!(result.verified);
+        certificate.save(&path).unwrap();
+        let loaded = QueryCertificate::load(&path).unwrap();
+        assert_eq!(&loaded, certificate);
+        assert!(loaded.verify());
+        assert_eq!(loaded.value(), result.value);
+    }
+    fs::remove_file(path).unwrap();
+
+    let certificate = &certificates[1];
+    let mut changed = certificate.clone();
+    changed.answer += Scalar::ONE;
+    assert!(!changed.verify());
+    let mut changed = certificate.clone();
+    changed.terms[0].coefficient += Scalar::ONE;
+    assert!(!changed.verify());
+    let mut changed = certificate.clone();
+    changed.noise_commitment += certificate.g;
+    assert!(!changed.verify());
+
+    let (sk, vk) = signature::keygen(&mut OsRng);
+    let mut signed = certificate.clone();
+    signed.sign(&sk);
+    assert!(signed.signed_by(&vk) && signed.forged().is_empty());
+    assert_eq!(signed.epsilon, PARAMS.epsilon);
+    assert!(!signed.signed_by(&signature::keygen(&mut OsRng).1));
+    let mut changed = signed.clone();
+    changed.epsilon /= 10.0;
+    assert!(!changed.signed_by(&vk) && changed.forged() == [vk]);
+    let mut changed = signed.clone();
+    changed.answer += Scalar::ONE;
+    assert!(!changed.signed_by(&vk));
+    let mut other = certificates[2].clone();
+    other.endorsements = signed.endorsements;
+    assert!(!other.signed_by(&vk));
+}
@@ -0,0 +1,68 @@
+/*!
+ * query_result.rs
+ *
+ * Checks decoding verified answers: a scalar below zero wraps to near the group order, and comes back as the negative
+ * integer it stands for, as do the answers of signed coefficients and noise down to -N/2; scalars that are no 64-bit
+ * integer either way, like the answers of random coefficients, decode to nothing. The noise bound behind each answer's
+ * confidence interval is the tightest the exact binomial distribution allows.
+ */
+
+use curve25519_dalek::Scalar;
+
+use certified_dp::result::{interval, noise_bound, parse_confidence, to_i64};
+
+/// Scalar of a signed integer, as the prover's sums reach it
+fn scalar(v: i64) -> Scalar {
+    if v < 0 { -Scalar::from(v.unsigned_abs()) } else { Scalar::from(v as u64) }
+}
+
+#[test]
+fn signed_answers_decode_to_integers() {
+    for v in [0, 1, -1, 42, -42, i64::MAX, i64::MIN] {
+        assert_eq!(to_i64(&scalar(v)), Some(v));
+    }
+
+    // an answer of 3 with a coefficient -2 entry, plus noise of -N/2 for N = 16 coins
+    let answer = Scalar::from(3u8) - Scalar::from(2u8) * Scalar::from(5u8) - Scalar::from(8u8);
+    assert_eq!(to_i64(&answer), Some(-15));
+
+    assert_eq!(to_i64(&Scalar::from(u64::MAX)), None);
+    assert_eq!(to_i64(&(Scalar::from(u64::MAX) + Scalar::ONE)), None);
+    assert_eq!(to_i64(&-(Scalar::from(u64::MAX) + Scalar::ONE)), None);
+    assert_eq!(to_i64(&Scalar::from_bytes_mod_order([0x5a; 32])), None);
+}
+
+/// Probability that the noise of `coins` coins, heads less half of them rounded down, is beyond `k` either way
+fn beyond(coins: u32, k: u32) -> f64 {
+    let mut choose = 1.0;
+    let mut mass = 0.0;
+    for heads in 0..=coins {
+        if (heads as i64 - (coins / 2) as i64).unsigned_abs() > k as u64 {
+            mass += choose;
+        }
+        choose = choose * (coins - heads) as f64 / (heads + 1) as f64;
+    }
+    mass / 2f64.powi(coins as i32)
+}
+
+#[test]
+fn noise_bounds_are_the_tightest_the_binomial_allows() {
+    for coins in 0..=60 {
+        for confidence in [0.5, 0.9, 0.95, 0.99] {
+            let k = noise_bound(coins, confidence);
+            assert!(beyond(coins, k) <= 1.0 - confidence + 1e-12, "{} coins at {}", coins, confidence);
+            assert!(k == 0 || beyond(coins, k - 1) > 1.0 - confidence, "{} coins at {}", coins, confidence);
+        }
+    }
+
+    // past the reach of 2^-N in floating point, the bound nears the normal one, 1.96 standard deviations
+    let k = noise_bound(4_000_000, 0.95);
+    assert!((1950..=1970).contains(&k), "{}", k);
+
+    assert_eq!(interval(10, 3), Some((7, 13)));
+    assert_eq!(interval(i64::MAX, 1), None);
+    assert_eq!(parse_confidence("0.99"), Ok(0.99));
+    for s in ["0", "1", "95", "high"] {
+        assert!(parse_confidence(s).is_err(), "{:?}", s);
+    }
+}
@@ -0,0 +1,123 @@
+/*!
+ * quic.rs
+ *
+ * Runs the protocol over the QUIC transport, each frame on a stream of its own: a session through the typed sessions
+ * verifies every query, in JSON and in compressed bincode, frames sent back to back are read in the order they were
+ * sent whichever stream finishes first, and a verifier that does not trust the prover's certificate fails to connect.
+ * The certificates are the TLS fixtures under `tests/fixtures/tls/`. Only built with the `quic` feature:
+ *
+ *   $ cargo test --features quic --test quic
+ */
+
+#![cfg(feature = "quic")]
+
+use rand::rngs::OsRng;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::messages::{Framed, SessionParams, WireFormat};
+use certified_dp::noise::Mechanism;
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::quic::{QuicListener, QuicStream};
+use certified_dp::tls;
+use certified_dp::transport::{self, Transport};
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+const NUM_QUERIES: u32 = 2;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("tls").join(name)
+}
+
+fn listener() -> QuicListener {
+    let config = tls::server_config(&fixture("prover.pem"), &fixture("prover.key"), None).unwrap();
+    QuicListener::bind("127.0.0.1:0", config, transport::DEFAULT_MAX_FRAME_SIZE).unwrap()
+}
+
+/// `stream` sending messages in `format`, gzipping large frames if `compress`
+fn framed<S>(stream: S, format: WireFormat, compress: bool) -> Framed<S> {
+    let mut stream = Framed::new(stream, transport::DEFAULT_MAX_FRAME_SIZE);
+    stream.set_wire_format(format, compress);
+    stream
+}
+
+/// Run a session over QUIC with messages in `format`, gzipping large frames if `compress`
+fn run_session(format: WireFormat, compress: bool) {
+    let listener = listener();
+    let address = listener.local_addr();
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut framed(listener.accept()?, format, compress);
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+        let session = session.commit(stream)?;
+        let mut session = session.randomness(stream)?;
+        for _ in 0..NUM_QUERIES {
+            session.answer_query(stream)?;
+        }
+        Ok(())
+    });
+
+    let config = tls::client_config(&fixture("ca.pem"), None).unwrap();
+    let stream = &mut framed(QuicStream::connect(address, config, transport::DEFAULT_MAX_FRAME_SIZE).unwrap(), format, compress);
+    let session = VerifierSession::<DataT, _>::start(stream, PARAMS).expect("setup failed");
+    let session = session.commit(stream).expect("commitment phases failed");
+    let mut session = session.randomness(stream).expect("randomness phase failed");
+    for _ in 0..NUM_QUERIES {
+        let coefficients = session.generate_query(2).expect("too few monomials committed");
+        let result = session.query(stream, &coefficients).expect("query failed");
+        assert!(result.verified, "query failed to verify: {}", result);
+    }
+    prover.join().unwrap().expect("prover session failed");
+}
+
+#[test]
+fn sessions_run_over_quic() {
+    run_session(WireFormat::Json, false);
+}
+
+#[test]
+fn sessions_run_over_quic_with_compressed_bincode_messages() {
+    run_session(WireFormat::Bincode, true);
+}
+
+#[test]
+fn frames_sent_back_to_back_arrive_in_order() {
+    let listener = listener();
+    let address = listener.local_addr();
+    let prover = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap();
+        // larger frames first, which take longer on their streams than the small ones after them
+        for i in 0..64u32 {
+            stream.send(&vec![i as u8; (64 - i as usize) << 12]).unwrap();
+        }
+        stream.recv(transport::DEFAULT_MAX_FRAME_SIZE).unwrap()
+    });
+
+    let config = tls::client_config(&fixture("ca.pem"), None).unwrap();
+    let mut stream = QuicStream::connect(address, config, transport::DEFAULT_MAX_FRAME_SIZE).unwrap();
+    for i in 0..64u32 {
+        let frame = stream.recv(transport::DEFAULT_MAX_FRAME_SIZE).unwrap();
+        assert_eq!((frame.len(), frame[0]), ((64 - i as usize) << 12, i as u8));
+    }
+    stream.send(b"done").unwrap();
+    assert_eq!(prover.join().unwrap(), b"done");
+}
+
+#[test]
+fn untrusted_prover_certificates_are_refused() {
+    let listener = listener();
+    let address = listener.local_addr();
+    let prover = thread::spawn(move || listener.accept().err());
+
+    let config = tls::client_config(&fixture("other-ca.pem"), None).unwrap();
+    let e = QuicStream::connect(address, config, transport::DEFAULT_MAX_FRAME_SIZE).err().unwrap();
+    assert!(e.to_string().contains("certificate"), "{}", e);
+    // the prover's accept fails with the handshake, as over TLS
+    assert!(prover.join().unwrap().is_some());
+}
@@ -0,0 +1,94 @@
+/*!
+ * recording.rs
+ *
+ * Records a session run over an in-memory `Loopback`, its prover and verifier on their own threads writing to one file:
+ * every frame one side sent is recorded, in order, as received by the other, under the phase each side was in, with
+ * times that never go backwards, and a thread that never started recording records nothing.
+ */
+
+use rand::rngs::OsRng;
+use std::fs;
+use std::thread;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::driver::{Phase, Side};
+use certified_dp::messages::{write_message, Framed, ReadyMessage, SessionParams};
+use certified_dp::noise::Mechanism;
+use certified_dp::protocol::prover::ProverSession;
+use certified_dp::protocol::verifier::VerifierSession;
+use certified_dp::recording::{self, Direction, RecordedFrame};
+use certified_dp::transport::{Loopback, DEFAULT_MAX_FRAME_SIZE};
+
+const PARAMS: SessionParams = SessionParams {
+    db_size: 4, dimension: 3, max_degree: 2, epsilon: 5.0, delta: None, entry_width: DataT::BITS,
+    calibration: Calibration::Approximate, mechanism: Mechanism::Binomial, sensitivity: 1,
+};
+const NUM_QUERIES: u32 = 2;
+
+/// Frames `side` recorded in `direction`, with the phase of each
+fn frames(recorded: &[RecordedFrame], side: Side, direction: Direction) -> Vec<(Phase, Vec<u8>)> {
+    recorded.iter()
+        .filter(|f| f.side == side && f.direction == direction)
+        .map(|f| (f.phase, f.bytes().unwrap()))
+        .collect()
+}
+
+#[test]
+fn both_sides_record_every_frame_of_a_session() {
+    let path = std::env::temp_dir().join(format!("certified-dp-recording-{}.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    recording::open(&path).unwrap();
+
+    let (prover_stream, verifier_stream) = Loopback::pair();
+    let (mut prover_stream, mut verifier_stream) = (Framed::new(prover_stream, DEFAULT_MAX_FRAME_SIZE), Framed::new(verifier_stream, DEFAULT_MAX_FRAME_SIZE));
+    let prover = thread::spawn(move || -> certified_dp::Result<()> {
+        let stream = &mut prover_stream;
+        recording::start(Side::Prover, 7);
+        let session = ProverSession::start(stream, Data::<DataT>::new(&mut OsRng, PARAMS.db_size), PARAMS, 0)?;
+        recording::set_phase(Phase::HonestCommitment);
+        let session = session.commit(stream)?;
+        recording::set_phase(Phase::Randomness);
+        let mut session = session.randomness(stream)?;
+        recording::set_phase(Phase::Query);
+        for _ in 0..NUM_QUERIES {
+            session.answer_query(stream)?;
+        }
+        Ok(())
+    });
+
+    let stream = &mut verifier_stream;
+    recording::start(Side::Verifier, 7);
+    let session = VerifierSession::<DataT, _>::start(stream, PARAMS).expect("setup failed");
+    recording::set_phase(Phase::HonestCommitment);
+    let session = session.commit(stream).expect("commitment phases failed");
+    recording::set_phase(Phase::Randomness);
+    let mut session = session.randomness(stream).expect("randomness phase failed");
+    recording::set_phase(Phase::Query);
+    for _ in 0..NUM_QUERIES {
+        let coefficients = session.generate_query(2).expect("too few monomials committed");
+        assert!(session.query(stream, &coefficients).expect("query failed").verified);
+    }
+    prover.join().unwrap().expect("prover session failed");
+
+    // a thread that never started recording leaves no trace
+    let (sender, _receiver) = Loopback::pair();
+    let mut sender = Framed::new(sender, DEFAULT_MAX_FRAME_SIZE);
+    thread::spawn(move || write_message(&mut sender, &ReadyMessage { ready: true }).unwrap()).join().unwrap();
+
+    let recorded = recording::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(recorded.iter().all(|f| f.session == 7));
+    for side in [Side::Prover, Side::Verifier] {
+        let times: Vec<u64> = recorded.iter().filter(|f| f.side == side).map(|f| f.time).collect();
+        assert!(times.windows(2).all(|t| t[0] <= t[1]), "{:?} recorded times out of order", side);
+    }
+    let (prover_sent, verifier_sent) = (frames(&recorded, Side::Prover, Direction::Sent), frames(&recorded, Side::Verifier, Direction::Sent));
+    assert!(!prover_sent.is_empty() && !verifier_sent.is_empty());
+    assert_eq!(prover_sent, frames(&recorded, Side::Verifier, Direction::Received));
+    assert_eq!(verifier_sent, frames(&recorded, Side::Prover, Direction::Received));
+    for phase in [Phase::Setup, Phase::HonestCommitment, Phase::Randomness, Phase::Query] {
+        assert!(prover_sent.iter().any(|(p, _)| *p == phase), "nothing sent in {:?}", phase);
+    }
+}
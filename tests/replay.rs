@@ -0,0 +1,107 @@
+/*!
+ * replay.rs
+ *
+ * Runs the prover and verifier executables with `--record`, then verifies the session offline from either party's
+ * recording: both replay to the same snapshot with every answer verified, while a recording whose prover responses or
+ * answers were tampered with fails the replay, or reports the answer as invalid.
+ */
+
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+use certified_dp::driver::{Phase, Side};
+use certified_dp::error::Error;
+use certified_dp::recording::{self, Direction, RecordedFrame};
+use certified_dp::replay;
+
+const SESSION: [&str; 12] = ["--db-size", "4", "--dimension", "3", "--max-degree", "2", "--epsilon", "1", "--sparsity", "2", "--num-queries", "2"];
+
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("certified-dp-replay-{}-{}.jsonl", std::process::id(), name))
+}
+
+/// Run a session between the executables, each recording it, with `extra` arguments on the verifier; returns the
+/// prover's and the verifier's recordings
+fn record_session(name: &str, extra: &[&str]) -> (Vec<RecordedFrame>, Vec<RecordedFrame>) {
+    let (prover_path, verifier_path) = (temp(&format!("{}-prover", name)), temp(&format!("{}-verifier", name)));
+    let _ = fs::remove_file(&prover_path);
+    let _ = fs::remove_file(&verifier_path);
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port().to_string();
+
+    let mut prover = Command::new(env!("CARGO_BIN_EXE_prover"))
+        .args(SESSION).args(["--port", &port]).arg("--record").arg(&prover_path)
+        .stdout(Stdio::null()).stderr(Stdio::null())
+        .spawn().unwrap();
+    // a verifier that could not connect yet, before the prover listens, recorded nothing and runs again
+    let verifier = (0..100).map(|_| {
+        let status = Command::new(env!("CARGO_BIN_EXE_verifier"))
+            .args(SESSION).args(extra).args(["--prover-address", &format!("127.0.0.1:{}", port)]).arg("--record").arg(&verifier_path)
+            .stdout(Stdio::null()).stderr(Stdio::null())
+            .status().unwrap();
+        sleep(Duration::from_millis(50));
+        status
+    }).find(|status| status.success() || fs::metadata(&verifier_path).is_ok_and(|m| m.len() > 0)).unwrap();
+    assert!(verifier.success(), "verifier failed");
+    assert!(prover.wait().unwrap().success(), "prover failed");
+
+    let recordings = (load(&prover_path), load(&verifier_path));
+    fs::remove_file(prover_path).unwrap();
+    fs::remove_file(verifier_path).unwrap();
+    recordings
+}
+
+fn load(path: &Path) -> Vec<RecordedFrame> {
+    recording::load(path).unwrap()
+}
+
+/// Flip a bit near the end of the last frame the prover sent in `phase` for which `pick` holds
+fn tamper(frames: &mut [RecordedFrame], phase: Phase, pick: impl Fn(&[u8]) -> bool) {
+    let frame = frames.iter_mut().rev()
+        .find(|f| f.phase == phase && f.direction == Direction::Received && pick(&f.bytes().unwrap()))
+        .expect("no such frame");
+    let mut bytes = frame.bytes().unwrap();
+    let i = bytes.len() - 5;
+    bytes[i] ^= 1;
+    frame.frame = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+#[test]
+fn either_recording_of_a_session_verifies() {
+    let (prover, verifier) = record_session("honest", &[]);
+    let from_prover = replay::verify_session(&prover, 1).unwrap();
+    let from_verifier = replay::verify_session(&verifier, 1).unwrap();
+
+    assert_eq!((from_prover.side, from_verifier.side), (Side::Prover, Side::Verifier));
+    assert_eq!(from_verifier.phases, [Phase::Setup, Phase::HonestCommitment, Phase::DishonestCommitment, Phase::Randomness, Phase::Query]);
+    assert_eq!(from_verifier.params.db_size, 4);
+    assert_eq!(from_prover.snapshot, from_verifier.snapshot);
+    assert_eq!(from_verifier.queries.len(), 2);
+    assert!(from_prover.verified() && from_verifier.verified());
+    assert!(matches!(replay::verify_session(&verifier, 2), Err(Error::Config(_))));
+}
+
+#[test]
+fn tampered_recordings_fail_to_verify() {
+    let (_, verifier) = record_session("tampered", &["--wire-format", "bincode"]);
+
+    // the last entry's product sigma responses, the prover's last frame of the phase but for its ready message
+    let mut frames = verifier.clone();
+    tamper(&mut frames, Phase::DishonestCommitment, |frame| frame.len() > 100);
+    assert!(matches!(replay::verify_session(&frames, 1), Err(Error::Verification(_))));
+
+    // the coin responses
+    let mut frames = verifier.clone();
+    tamper(&mut frames, Phase::Randomness, |frame| frame.len() > 100);
+    assert!(matches!(replay::verify_session(&frames, 1), Err(Error::Verification(_))));
+
+    // the last answer, which the replay reports rather than fails on
+    let mut frames = verifier;
+    tamper(&mut frames, Phase::Query, |frame| frame.len() > 16);
+    let replayed = replay::verify_session(&frames, 1).unwrap();
+    assert!(replayed.queries[0].verified && !replayed.queries[1].verified);
+    assert!(!replayed.verified());
+}
@@ -0,0 +1,64 @@
+/*!
+ * schema.rs
+ *
+ * Checks the schema of a database's attributes: each attribute takes the bits after the previous one's, literals and
+ * conjunctions of them name those bits and the monomials over them, and the same bits come back as descriptions of
+ * the attributes, so loaders and queries agree on every bit. Attributes parse and display alike, and schemas that
+ * could not be encoded are refused.
+ */
+
+use certified_dp::monomial::Monomial;
+use certified_dp::schema::{parse_attribute, Schema};
+
+/// Schema of a boolean, a categorical, a bucketed numeric, and a binary attribute, taking bits 0, 1 to 3, 4 to 6, and
+/// 7 to 9
+fn schema() -> Schema {
+    let specs = ["smoker=bool", "region=one-hot:north,south,east", "age=buckets:18,65", "children=binary:3"];
+    Schema::new(specs.iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap()
+}
+
+#[test]
+fn attributes_take_consecutive_bits() {
+    let schema = schema();
+    assert_eq!(schema.bits(), 10);
+    let positions: Vec<_> = schema.positions().map(|(attribute, bits)| (attribute.name.as_str(), bits)).collect();
+    assert_eq!(positions, [("smoker", 0..1), ("region", 1..4), ("age", 4..7), ("children", 7..10)]);
+
+    assert_eq!(schema.bit("smoker", "yes").unwrap(), 0);
+    assert_eq!(schema.bit("region", "east").unwrap(), 3);
+    assert_eq!([schema.bit("age", "17.5").unwrap(), schema.bit("age", "18").unwrap(), schema.bit("age", "90").unwrap()], [4, 5, 6]);
+    assert_eq!(schema.encode::<u16>(&["no", "south", "40", "5"]).unwrap(), 0b010 << 1 | 0b010 << 4 | 5 << 7);
+}
+
+#[test]
+fn literals_name_bits_and_monomials() {
+    let schema = schema();
+    let m = schema.monomial::<u16>(&[("smoker", "true"), ("region", "north"), ("age", "70")]).unwrap();
+    assert_eq!(m, Monomial::variable(0).times(1).times(6));
+    assert_eq!(schema.describe_monomial(m), "smoker*region=north*age>=65");
+    assert_eq!(schema.describe_monomial(Monomial::<u16>::new(0b1_0010_0000 | 1 << 12)), "18<=age<65*children[1]*x12");
+    assert_eq!(schema.describe_monomial(Monomial::<u16>::new(0)), "1");
+
+    assert!(schema.bit("smoker", "no").unwrap_err().contains("sets no bit"));
+    assert!(schema.bit("region", "west").unwrap_err().contains("not one of the categories"));
+    assert!(schema.bit("children", "2").unwrap_err().contains("no single bit"));
+    assert!(schema.bit("height", "2").unwrap_err().contains("no attribute \"height\""));
+}
+
+#[test]
+fn attributes_parse_and_display_alike() {
+    for spec in ["smoker=bool", "region=one-hot:north,south", "age=buckets:18,65.5", "children=binary:7"] {
+        assert_eq!(parse_attribute(spec).unwrap().to_string(), spec);
+    }
+    for spec in ["smoker", "smoker=boolean", "region=one-hot:north,north", "region=one-hot:", "age=buckets:65,18", "age=buckets:inf",
+                 "children=binary:0", "children=binary:65"] {
+        assert!(parse_attribute(spec).is_err(), "{}", spec);
+    }
+
+    let twice = vec![parse_attribute("smoker=bool").unwrap(), parse_attribute("smoker=binary:2").unwrap()];
+    assert!(Schema::new(twice).unwrap_err().contains("named more than once"));
+    let wide = (0..3).map(|i| parse_attribute(&format!("a{}=binary:64", i)).unwrap()).collect();
+    assert!(Schema::new(wide).unwrap_err().contains("take 192 bits"));
+    let json = serde_json::to_string(&schema()).unwrap();
+    assert_eq!(serde_json::from_str::<Schema>(&json).unwrap(), schema());
+}
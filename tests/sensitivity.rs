@@ -0,0 +1,58 @@
+/*!
+ * sensitivity.rs
+ *
+ * Checks the query sensitivity calculator: the L1 sensitivity of a query reads its coefficients as signed integers, a
+ * query with a random coefficient has none, a predicate's count moves by one whatever its coefficients, and the noise
+ * of both mechanisms is scaled to the session's sensitivity.
+ */
+
+use curve25519_dalek::Scalar;
+use std::collections::HashMap;
+
+use certified_dp::calibration::Calibration;
+use certified_dp::monomial::Monomial;
+use certified_dp::noise::{DiscreteLaplace, Mechanism, NoiseMechanism};
+use certified_dp::predicate::parse_predicate;
+use certified_dp::schema::{parse_attribute, Schema};
+use certified_dp::sensitivity::{l1, parse_sensitivity};
+
+#[test]
+fn l1_sums_the_signed_coefficients() {
+    let coefficients: HashMap<Monomial<u32>, Scalar> = HashMap::from([
+        (Monomial::new(0b001), Scalar::ONE),
+        (Monomial::new(0b010), -Scalar::ONE),
+        (Monomial::new(0b110), Scalar::from(3u64)),
+    ]);
+    assert_eq!(l1(&coefficients), Some(5));
+    assert_eq!(l1::<u32>(&HashMap::new()), Some(0));
+    let random = HashMap::from([(Monomial::<u32>::new(0b001), Scalar::from_bytes_mod_order([0x55; 32]))]);
+    assert_eq!(l1(&random), None);
+}
+
+#[test]
+fn predicates_count_with_sensitivity_one() {
+    let schema = Schema::new(["a=bool", "b=bool"].iter().map(|s| parse_attribute(s).unwrap()).collect()).unwrap();
+    // a or b is a + b - ab, of coefficients summing to 3 in absolute value, but still 0 or 1 on every entry
+    let query = parse_predicate("a | b").unwrap().compile::<u32>(&schema, 2).unwrap();
+    assert_eq!(l1(&query.coefficients), Some(3));
+    assert_eq!(query.sensitivity(), 1);
+}
+
+#[test]
+fn noise_scales_with_the_sensitivity() {
+    let coins = |s| Mechanism::Binomial.noise(1000, 0.5, Some(0.0078125), Calibration::Approximate, s).coins();
+    assert_eq!(coins(1), 256);
+    assert_eq!(coins(2), 4 * 256);
+    let laplace = Mechanism::DiscreteLaplace.noise(1000, 1.0, Some(1e-6), Calibration::Approximate, 4);
+    let per_unit = DiscreteLaplace::new(0.25, 1e-6);
+    assert!(per_unit.epsilon() <= 0.25);
+    assert_eq!(laplace.std(), per_unit.std());
+    assert!(laplace.std() > Mechanism::DiscreteLaplace.noise(1000, 1.0, Some(1e-6), Calibration::Approximate, 1).std());
+}
+
+#[test]
+fn sensitivities_parse_as_positive_integers() {
+    assert_eq!(parse_sensitivity("3"), Ok(3));
+    assert!(parse_sensitivity("0").unwrap_err().contains("positive integer"));
+    assert!(parse_sensitivity("-1").is_err());
+}
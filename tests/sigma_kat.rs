@@ -0,0 +1,155 @@
+/*!
+ * sigma_kat.rs
+ *
+ * Known-answer tests for the bit and product sigma protocols. Each vector fixes the prover and verifier RNG seeds,
+ * replays commitment/challenge/response/verify, and compares the serialized transcript against the golden copy in
+ * `tests/vectors/`. Refactors of the crypto must reproduce these transcripts byte for byte.
+ *
+ * To regenerate the vectors after an intentional change to the transcript format, run:
+ *
+ *   $ CERTIFIED_DP_UPDATE_KAT=1 cargo test --test sigma_kat
+ */
+
+use curve25519_dalek::scalar::Scalar;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+use certified_dp::bit_sigma;
+use certified_dp::pedersen;
+use certified_dp::product_sigma;
+
+/// A single golden transcript
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Vector {
+    name: String,
+    prover_seed: u8,
+    verifier_seed: u8,
+    inputs: Value,
+    commitment: Value,
+    challenge: Value,
+    response: Value,
+    verified: bool,
+}
+
+fn vector_path(file: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("vectors").join(file)
+}
+
+/// Compare freshly computed vectors against the golden file, or rewrite it in update mode
+fn check_vectors(file: &str, computed: Vec<Vector>) {
+    let path = vector_path(file);
+
+    if std::env::var_os("CERTIFIED_DP_UPDATE_KAT").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, serde_json::to_string_pretty(&computed).unwrap() + "\n").unwrap();
+        return;
+    }
+
+    let golden: Vec<Vector> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(golden.len(), computed.len(), "number of vectors in {} changed", file);
+    for (g, c) in golden.iter().zip(computed.iter()) {
+        assert_eq!(g, c, "vector {} does not match {}", g.name, file);
+    }
+}
+
+/// Serialize a protocol message, rendering each 32-byte scalar or compressed point as a hex string
+fn to_value<S: Serialize>(s: &S) -> Value {
+    fn hexify(v: Value) -> Value {
+        match v {
+            Value::Array(a) if a.len() == 32 && a.iter().all(|x| x.is_u64()) => {
+                Value::String(a.iter().map(|x| format!("{:02x}", x.as_u64().unwrap())).collect())
+            },
+            Value::Array(a) => Value::Array(a.into_iter().map(hexify).collect()),
+            Value::Object(o) => Value::Object(o.into_iter().map(|(k, v)| (k, hexify(v))).collect()),
+            v => v,
+        }
+    }
+    hexify(serde_json::to_value(s).unwrap())
+}
+
+/// Run the bit sigma protocol on bit `b` with fixed seeds
+fn bit_sigma_vector(name: &str, b: u32, prover_seed: u8, verifier_seed: u8) -> Vector {
+    let mut prover_rng = ChaCha20Rng::from_seed([prover_seed; 32]);
+    let mut verifier_rng = ChaCha20Rng::from_seed([verifier_seed; 32]);
+
+    let pp: pedersen::PublicParams = pedersen::setup(&[prover_seed]);
+    let (b_comm, b_proof) = pedersen::commit(&mut prover_rng, &Scalar::from(b), &pp);
+
+    let (mut sigma_p, commitment) = bit_sigma::commit(&mut prover_rng, &pp, b, b_comm, b_proof);
+    let (mut sigma_v, challenge) = bit_sigma::challenge(&mut verifier_rng, &commitment);
+    let response = bit_sigma::response(&mut sigma_p, &challenge);
+    let verified = bit_sigma::verify(&pp, &mut sigma_v, &response).is_ok();
+
+    Vector {
+        name: name.to_string(),
+        prover_seed,
+        verifier_seed,
+        inputs: json!({ "b": b }),
+        commitment: to_value(&commitment),
+        challenge: to_value(&challenge),
+        response: to_value(&response),
+        verified,
+    }
+}
+
+/// Run the product sigma protocol on committed values `m_1 * m_2 = m_3` (or a false claim) with fixed seeds
+fn product_sigma_vector(name: &str, (m_1, m_2, m_3): (u32, u32, u32), prover_seed: u8, verifier_seed: u8) -> Vector {
+    let mut prover_rng = ChaCha20Rng::from_seed([prover_seed; 32]);
+    let mut verifier_rng = ChaCha20Rng::from_seed([verifier_seed; 32]);
+
+    let pp: pedersen::PublicParams = pedersen::setup(&[prover_seed]);
+    let open = |rng: &mut ChaCha20Rng, m: u32| {
+        let m = Scalar::from(m);
+        let (c, r) = pedersen::commit(rng, &m, &pp);
+        (m, c, r)
+    };
+    let first = open(&mut prover_rng, m_1);
+    let second = open(&mut prover_rng, m_2);
+    let third = open(&mut prover_rng, m_3);
+
+    let (mut sigma_p, commitment) = product_sigma::commit(&mut prover_rng, &pp, first, second, third);
+    let (mut sigma_v, challenge) = product_sigma::challenge(&mut verifier_rng, &commitment);
+    let response = product_sigma::response(&mut sigma_p, &challenge);
+    let verified = product_sigma::verify(&pp, &mut sigma_v, &response).is_ok();
+
+    Vector {
+        name: name.to_string(),
+        prover_seed,
+        verifier_seed,
+        inputs: json!({ "m_1": m_1, "m_2": m_2, "m_3": m_3 }),
+        commitment: to_value(&commitment),
+        challenge: to_value(&challenge),
+        response: to_value(&response),
+        verified,
+    }
+}
+
+#[test]
+fn bit_sigma_known_answers() {
+    let vectors = vec![
+        bit_sigma_vector("bit-0", 0, 1, 2),
+        bit_sigma_vector("bit-1", 1, 3, 4),
+        bit_sigma_vector("bit-0-alt-seed", 0, 5, 6),
+        bit_sigma_vector("bit-1-alt-seed", 1, 7, 8),
+    ];
+    assert!(vectors.iter().all(|v| v.verified));
+    check_vectors("bit_sigma.json", vectors);
+}
+
+#[test]
+fn product_sigma_known_answers() {
+    let vectors = vec![
+        product_sigma_vector("0*0=0", (0, 0, 0), 1, 2),
+        product_sigma_vector("0*1=0", (0, 1, 0), 3, 4),
+        product_sigma_vector("1*1=1", (1, 1, 1), 5, 6),
+        product_sigma_vector("3*5=15", (3, 5, 15), 7, 8),
+        product_sigma_vector("false-1*1=0", (1, 1, 0), 9, 10),
+    ];
+    assert!(vectors.iter().filter(|v| v.name.starts_with("false")).all(|v| !v.verified));
+    assert!(vectors.iter().filter(|v| !v.name.starts_with("false")).all(|v| v.verified));
+    check_vectors("product_sigma.json", vectors);
+}
@@ -0,0 +1,83 @@
+/*!
+ * tls.rs
+ *
+ * Handshakes that `certified_dp::tls` must refuse: a prover certificate from a CA the verifier does not trust, one for
+ * an address other than the one dialed, and a verifier without a certificate when the prover requires one. The
+ * certificates are checked in under `tests/fixtures/tls/`, signed by `ca.pem`, with `other-ca.pem` a second,
+ * unrelated CA.
+ */
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use certified_dp::error::Error;
+use certified_dp::tls::{self, ClientConfig, ServerConfig};
+use certified_dp::transport::{Transport, DEFAULT_MAX_FRAME_SIZE};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("tls").join(name)
+}
+
+fn prover_config(client_ca: Option<&str>) -> Arc<ServerConfig> {
+    tls::server_config(&fixture("prover.pem"), &fixture("prover.key"), client_ca.map(fixture).as_deref()).unwrap()
+}
+
+fn verifier_config(ca: &str, with_certificate: bool) -> Arc<ClientConfig> {
+    let (cert, key) = (fixture("verifier.pem"), fixture("verifier.key"));
+    tls::client_config(&fixture(ca), with_certificate.then_some((cert.as_path(), key.as_path()))).unwrap()
+}
+
+/// Run both sides of a handshake, the verifier dialing `host`, and return how each side ended
+fn handshake(server: Arc<ServerConfig>, client: Arc<ClientConfig>, host: &str) -> (io::Result<()>, io::Result<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let prover = thread::spawn(move || {
+        let mut stream = tls::accept(server, listener.accept().unwrap().0)?;
+        // a TLS 1.3 server only learns that the client refused its certificate once it next reads
+        stream.recv(DEFAULT_MAX_FRAME_SIZE).map(|_| ())
+    });
+    let verifier = tls::connect(client, host, TcpStream::connect(address).unwrap()).map(|mut stream| {
+        let _ = stream.send(b"hello");
+        stream
+    });
+    // closing the verifier's end with the prover's session tickets unread would reset the connection under the prover
+    let prover = prover.join().unwrap();
+    (prover, verifier.map(drop))
+}
+
+#[test]
+fn trusted_certificates_complete_the_handshake() {
+    let (prover, verifier) = handshake(prover_config(Some("ca.pem")), verifier_config("ca.pem", true), "127.0.0.1");
+    prover.expect("prover refused a trusted verifier");
+    verifier.expect("verifier refused a trusted prover");
+}
+
+#[test]
+fn verifier_refuses_a_prover_certificate_from_an_untrusted_ca() {
+    let (prover, verifier) = handshake(prover_config(None), verifier_config("other-ca.pem", false), "127.0.0.1");
+    assert!(verifier.is_err(), "verifier accepted a certificate its CA did not sign");
+    assert!(prover.is_err());
+}
+
+#[test]
+fn verifier_refuses_a_prover_certificate_for_another_address() {
+    let (_, verifier) = handshake(prover_config(None), verifier_config("ca.pem", false), "127.0.0.2");
+    assert!(verifier.is_err(), "verifier accepted a certificate for an address it did not dial");
+}
+
+#[test]
+fn prover_refuses_a_verifier_without_a_certificate() {
+    let (prover, _) = handshake(prover_config(Some("ca.pem")), verifier_config("ca.pem", false), "127.0.0.1");
+    assert!(prover.is_err(), "prover accepted a verifier with no certificate");
+}
+
+#[test]
+fn unreadable_files_are_configuration_errors() {
+    let missing = fixture("missing.pem");
+    assert!(matches!(tls::client_config(&missing, None), Err(Error::Config(_))));
+    // a CA certificate holds no private key
+    assert!(matches!(tls::server_config(&fixture("prover.pem"), &fixture("ca.pem"), None), Err(Error::Config(_))));
+}
@@ -0,0 +1,423 @@
+/*!
+ * wire_fixtures.rs
+ *
+ * Serialized samples of every protocol message, checked in per wire-format version under `tests/fixtures/wire/v<N>/`.
+ * Fixtures from every version must still decode with the current code; fixtures for the current `WIRE_VERSION` must
+ * also match what the current code encodes, and come back unchanged through the bincode wire format. A message holding
+ * a map encodes to the same bytes however the map happens to iterate.
+ *
+ * To write the fixtures for the current version after an intentional format change (and bump of `WIRE_VERSION`), run:
+ *
+ *   $ CERTIFIED_DP_UPDATE_FIXTURES=1 cargo test --test wire_fixtures
+ */
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::scalar::Scalar;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use certified_dp::analyst;
+use certified_dp::bit_sigma;
+use certified_dp::calibration::Calibration;
+use certified_dp::checkpoint::Checkpoint;
+use certified_dp::config::DataT;
+use certified_dp::cut_and_choose;
+use certified_dp::elgamal;
+use certified_dp::messages::*;
+use certified_dp::monomial::Monomial;
+use certified_dp::noise::Mechanism;
+use certified_dp::pedersen;
+use certified_dp::product_sigma;
+use certified_dp::signature;
+use certified_dp::snapshot::SnapshotId;
+
+fn fixture_root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("wire")
+}
+
+fn updating() -> bool {
+    std::env::var_os("CERTIFIED_DP_UPDATE_FIXTURES").is_some()
+}
+
+/// Every wire-format version that has checked-in fixtures, plus the current one when writing its fixtures
+fn fixture_versions() -> Vec<u32> {
+    let mut versions: Vec<u32> = fs::read_dir(fixture_root()).unwrap()
+        .filter_map(|e| e.unwrap().file_name().to_str()?.strip_prefix('v')?.parse().ok())
+        .collect();
+    if updating() && !versions.contains(&WIRE_VERSION) {
+        versions.push(WIRE_VERSION);
+    }
+    versions.sort();
+    versions
+}
+
+/// Deterministic samples of each message, built by running the sigma protocols with fixed seeds
+struct Samples {
+    bit_commitment: bit_sigma::Commitment,
+    bit_challenge: bit_sigma::Challenge,
+    bit_response: bit_sigma::Response,
+    product_commitment: product_sigma::Commitment,
+    product_challenge: product_sigma::Challenge,
+    product_response: product_sigma::Response,
+    triples: Vec<(cut_and_choose::Triple, cut_and_choose::TripleCommitment)>,
+    triple_response: cut_and_choose::ProductResponse,
+    encrypted_answer: analyst::EncryptedAnswer,
+    elgamal_handle: (curve25519_dalek::RistrettoPoint, elgamal::HandleProof),
+    points: Vec<curve25519_dalek::RistrettoPoint>,
+    scalars: Vec<Scalar>,
+}
+
+fn samples() -> Samples {
+    let mut rng = ChaCha20Rng::from_seed([42; 32]);
+    // generators drawn from the RNG, as setup once did, so the samples match the fixtures of every version
+    let h = curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+    let pp = pedersen::PublicParams::new(Scalar::random(&mut rng) * h, h);
+
+    let (b_comm, b_proof) = pedersen::commit(&mut rng, &Scalar::ONE, &pp);
+    let (mut bit_p, bit_commitment) = bit_sigma::commit(&mut rng, &pp, 1, b_comm, b_proof);
+    let (_, bit_challenge) = bit_sigma::challenge(&mut rng, &bit_commitment);
+    let bit_response = bit_sigma::response(&mut bit_p, &bit_challenge);
+
+    let (c_1, r_1) = pedersen::commit(&mut rng, &Scalar::ONE, &pp);
+    let (c_2, r_2) = pedersen::commit(&mut rng, &Scalar::ONE, &pp);
+    let (c_3, r_3) = pedersen::commit(&mut rng, &Scalar::ONE, &pp);
+    let (mut product_p, product_commitment) =
+        product_sigma::commit(&mut rng, &pp, (Scalar::ONE, c_1, r_1), (Scalar::ONE, c_2, r_2), (Scalar::ONE, c_3, r_3));
+    let (_, product_challenge) = product_sigma::challenge(&mut rng, &product_commitment);
+    let product_response = product_sigma::response(&mut product_p, &product_challenge);
+
+    let triples = vec![cut_and_choose::triple(&mut rng, &pp), cut_and_choose::triple(&mut rng, &pp)];
+    let triple_response = cut_and_choose::respond(&triples[1].0, (Scalar::ONE, c_1, r_1), (Scalar::ONE, c_2, r_2), (Scalar::ONE, c_3, r_3));
+
+    let (_, analyst_key) = analyst::keygen(&mut rng);
+    let query_comm = pedersen::commit_with_r(&Scalar::ONE, &r_1, &pp);
+    let encrypted_answer = analyst::encrypt(&mut rng, &pp, &analyst_key, &query_comm, &Scalar::ONE, &r_1);
+    let elgamal_pp = elgamal::params(&pp, &analyst_key);
+    let elgamal_comm = pedersen::commit_with_r(&Scalar::ONE, &r_1, &elgamal_pp);
+    let elgamal_handle = elgamal::reveal_handle(&mut rng, &elgamal_pp, &elgamal_comm, &Scalar::ONE, &r_1);
+
+    Samples {
+        bit_commitment, bit_challenge, bit_response,
+        product_commitment, product_challenge, product_response,
+        triples, triple_response,
+        encrypted_answer, elgamal_handle,
+        points: vec![c_1, c_2, c_3],
+        scalars: vec![r_1, r_2, r_3],
+    }
+}
+
+/// Decode a fixture with the current code; for the current version, also check it matches the current encoding
+fn check_fixture<M: Serialize + DeserializeOwned>(version: u32, name: &str, sample: &M) {
+    let path = fixture_root().join(format!("v{}", version)).join(format!("{}.json", name));
+    let encoded = serde_json::to_vec(sample).unwrap();
+
+    if version == WIRE_VERSION && updating() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &encoded).unwrap();
+        return;
+    }
+
+    let fixture = fs::read(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path.display(), e));
+    let decoded: M = WireFormat::Json.decode(&fixture)
+        .unwrap_or_else(|e| panic!("v{} {} no longer decodes: {}", version, name, e));
+
+    if version == WIRE_VERSION {
+        let expected: Value = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), expected, "v{} {} encoding changed without a version bump", version, name);
+
+        // sessions may also send it as bincode, which must carry the same message
+        let binary = WireFormat::Bincode.decode::<M>(&WireFormat::Bincode.encode(&decoded))
+            .unwrap_or_else(|e| panic!("{} does not survive bincode: {}", name, e));
+        assert_eq!(serde_json::to_value(&binary).unwrap(), expected, "{} changed through bincode", name);
+    }
+}
+
+#[test]
+fn current_version_has_fixtures() {
+    assert!(fixture_versions().contains(&WIRE_VERSION));
+}
+
+#[test]
+fn fixtures_decode_for_every_version() {
+    for version in fixture_versions() {
+        // messages are consumed by value, and bit sigma messages appear in several messages, so take identical sample sets
+        let s = samples();
+        let t = samples();
+        let u = samples();
+
+        let commitment_tree = MonomialCommitmentTreeNode {
+            commitment: None,
+            product_sigma_commitment: None,
+            children: vec![Box::new(MonomialCommitmentTreeNode {
+                commitment: Some(s.points[0]),
+                product_sigma_commitment: Some(s.product_commitment),
+                children: vec![Box::new(MonomialCommitmentTreeNode { commitment: Some(s.points[1]), product_sigma_commitment: None, children: Vec::new() })],
+            })],
+        };
+        let challenge_tree = MonomialChallengeTreeNode {
+            product_sigma_challenge: None,
+            children: vec![Box::new(MonomialChallengeTreeNode { product_sigma_challenge: Some(s.product_challenge), children: Vec::new() })],
+        };
+        let response_tree = MonomialResponseTreeNode {
+            product_sigma_response: None,
+            children: vec![Box::new(MonomialResponseTreeNode { product_sigma_response: Some(s.product_response), children: Vec::new() })],
+        };
+
+        // the same trees, as sent since version 3
+        let mut indexed_commitment_tree = MonomialCommitmentTree::new(CommitmentPayload { commitment: None, product_sigma_commitment: None });
+        let child = indexed_commitment_tree.add_child(0, CommitmentPayload { commitment: Some(t.points[0]), product_sigma_commitment: Some(t.product_commitment) });
+        indexed_commitment_tree.add_child(child, CommitmentPayload { commitment: Some(t.points[1]), product_sigma_commitment: None });
+        let mut indexed_challenge_tree = MonomialChallengeTree::new(None);
+        indexed_challenge_tree.add_child(0, Some(t.product_challenge));
+        let mut indexed_response_tree = MonomialResponseTree::new(None);
+        indexed_response_tree.add_child(0, Some(t.product_response));
+
+        check_fixture(version, "ready", &ReadyMessage { ready: true });
+        // since version 26, a verifier not ready for another query ends the query phase
+        if version >= 26 {
+            check_fixture(version, "ready_end", &ReadyMessage { ready: false });
+        }
+        // epochs are sent since version 5, and default to zero before
+        let epoch = if version >= 5 { 3 } else { 0 };
+        // the protocol version and session parameters are sent since version 15, the entry width since version 19, the
+        // calibration since version 20, the noise mechanism since version 21, and the sensitivity since version 22
+        let entry_width = if version >= 19 { 16 } else { 0 };
+        let calibration = if version >= 20 { Calibration::Zcdp { queries: 4 } } else { Calibration::Approximate };
+        let mechanism = if version >= 21 { Mechanism::DiscreteLaplace } else { Mechanism::Binomial };
+        let sensitivity = if version >= 22 { 2 } else { 0 };
+        let (setup_version, params) = match version >= 15 {
+            true => (15, Some(SessionParams { db_size: 1000, dimension: 8, max_degree: 3, epsilon: 0.5, delta: Some(0.0078125), entry_width, calibration, mechanism, sensitivity })),
+            false => (0, None),
+        };
+        // a nonce is sent since version 29
+        let nonce = if version >= 29 { [12; 32] } else { [0; 32] };
+        // and a key share from version 31 to 35, which the current code no longer reads
+        check_fixture(version, "setup", &SetupMessage { seed: [7; 32], epoch, version: setup_version, params, seed_commitment: None, nonce, session: None });
+        // the generators are flipped since version 16
+        if version >= 16 {
+            check_fixture(version, "setup_flip", &SetupMessage { seed: [0; 32], epoch, version: 16, params, seed_commitment: Some([8; 32]), nonce, session: None });
+            check_fixture(version, "seed_contribution", &SeedContributionMessage { seed: [9; 32] });
+            check_fixture(version, "seed_reveal", &SeedRevealMessage { seed: [10; 32] });
+        }
+        // a co-verifier is told the session it joins since version 29
+        if version >= 29 {
+            let session = Some(SessionId::new([11; 32]));
+            check_fixture(version, "setup_co_verifier", &SetupMessage { seed: [7; 32], epoch, version: 29, params, seed_commitment: None, nonce, session });
+        }
+        // the verifier answers with a key share of its own since version 31, as the prover sends its own since version 36
+        if version >= 31 {
+            // saying whether it mixes in a pre-shared key since version 38
+            let preshared = version >= 38;
+            check_fixture(version, "key_share", &KeyShareMessage { share: CompressedRistretto([14; 32]), preshared });
+        }
+        check_fixture(version, "bit_sigma_commitment", &BitSigmaCommitmentMessage { commitments: vec![s.bit_commitment] });
+        // trees are flattened since version 3
+        if version >= 3 {
+            check_fixture(version, "monomial_commitment_tree", &indexed_commitment_tree);
+        } else {
+            check_fixture(version, "monomial_commitment_tree", &commitment_tree);
+        }
+        check_fixture(version, "bit_sigma_challenge", &BitSigmaChallengeMessage { challenges: vec![s.bit_challenge] });
+        if version >= 3 {
+            check_fixture(version, "monomial_challenge_tree", &indexed_challenge_tree);
+        } else {
+            check_fixture(version, "monomial_challenge_tree", &challenge_tree);
+        }
+        check_fixture(version, "bit_sigma_response", &BitSigmaResponseMessage { responses: vec![s.bit_response] });
+        if version >= 3 {
+            check_fixture(version, "monomial_response_tree", &indexed_response_tree);
+        } else {
+            check_fixture(version, "monomial_response_tree", &response_tree);
+        }
+        // snapshots are sent since version 4, and default to zero before, as the number of entries does before version 33
+        let commitment_map = HashMap::from([(Monomial::new(1), s.points[0]), (Monomial::new(3), s.points[1]), (Monomial::new(6), s.points[2])]);
+        let snapshot = if version >= 4 { SnapshotId::of(commitment_map.clone()) } else { SnapshotId::default() };
+        let entries = if version >= 33 { 1000 } else { 0 };
+        check_fixture(version, "commitment_map", &CommitmentMapMessage::<DataT> { commitment_map, snapshot, entries });
+        check_fixture(version, "prover_randomness_comm", &ProverRandomnessComm { commitment: t.bit_commitment });
+        check_fixture(version, "verifier_randomness_challenge", &VerifierRandomnessChallenge { player_b: 1, sigma_challenge: t.bit_challenge });
+        check_fixture(version, "prover_randomness_response", &ProverRandomnessResponse { final_commitment: s.points[2], sigma_response: t.bit_response });
+        check_fixture(version, "verifier_check", &VerifierCheckMessage { success: true });
+        // the randomness phase is batched since version 17
+        if version >= 17 {
+            let u = samples();
+            check_fixture(version, "prover_randomness_comms", &ProverRandomnessComms { commitments: vec![u.bit_commitment] });
+            check_fixture(version, "verifier_randomness_challenges", &VerifierRandomnessChallenges {
+                challenges: vec![VerifierRandomnessChallenge { player_b: 0, sigma_challenge: u.bit_challenge }],
+            });
+            check_fixture(version, "prover_randomness_responses", &ProverRandomnessResponses {
+                responses: vec![ProverRandomnessResponse { final_commitment: u.points[2], sigma_response: u.bit_response }],
+            });
+        }
+        // noise mechanisms other than the binomial prove products of the coins since version 21
+        if version >= 21 {
+            let u = samples();
+            check_fixture(version, "noise_product_commitments", &NoiseProductCommitments { commitments: vec![u.product_commitment] });
+            check_fixture(version, "noise_product_challenges", &NoiseProductChallenges { challenges: vec![u.product_challenge] });
+            check_fixture(version, "noise_product_responses", &NoiseProductResponses { responses: vec![u.product_response] });
+        }
+        check_fixture(version, "query", &QueryMessage::<DataT> {
+            coefficients: HashMap::from([(Monomial::new(1), s.scalars[0]), (Monomial::new(6), s.scalars[1])]),
+            snapshot,
+            // named datasets since version 6
+            dataset: if version >= 6 { "census".to_string() } else { String::new() },
+            distinct: false,
+        });
+        // introduced in version 2
+        if version >= 2 {
+            check_fixture(version, "bit_sigma_combined_challenge", &BitSigmaCombinedChallengeMessage { challenge: u.bit_challenge });
+
+            let [(opened, opened_comm), (_, kept_comm)]: [_; 2] = t.triples.try_into().unwrap();
+            check_fixture(version, "cut_and_choose_challenge", &CutAndChooseChallengeMessage { kept: vec![1, 0] });
+            check_fixture(version, "cut_and_choose_response", &CutAndChooseResponseMessage { openings: vec![vec![opened]], responses: vec![t.triple_response] });
+
+            // sent with a nested monomial tree in version 2, which is no longer decoded
+            if version >= 3 {
+                check_fixture(version, "cut_and_choose_commitment", &CutAndChooseCommitmentMessage {
+                    bit_commitments: vec![s.points[0]],
+                    tree: {
+                        let mut tree = MonomialCommitmentTree::new(CommitmentPayload { commitment: None, product_sigma_commitment: None });
+                        tree.add_child(0, CommitmentPayload { commitment: Some(s.points[0]), product_sigma_commitment: None });
+                        tree
+                    },
+                    encodings: vec![vec![opened_comm], vec![kept_comm]],
+                });
+            }
+        }
+        check_fixture(version, "spot_check", &SpotCheckMessage { entries: vec![0, 2, 5] });
+        check_fixture(version, "query_answer", &QueryAnswerMessage { answer: s.scalars[2], proof: s.scalars[0] });
+        // introduced in version 3
+        if version >= 3 {
+            check_fixture(version, "encrypted_query_answer", &EncryptedQueryAnswerMessage { answer: s.encrypted_answer });
+            let (handle, proof) = s.elgamal_handle;
+            check_fixture(version, "elgamal_query_answer", &ElGamalQueryAnswerMessage { handle, proof });
+        }
+        // introduced in version 5
+        if version >= 5 {
+            check_fixture(version, "update", &UpdateMessage { removed: vec![1, 4], added: 3 });
+        }
+        // introduced in version 6
+        if version >= 6 {
+            check_fixture(version, "dataset", &DatasetMessage { name: "census".to_string() });
+        }
+        // introduced in version 7
+        if version >= 7 {
+            let contribution = ContributionMessage { player_b: 1, sigma_challenge: samples().bit_challenge, nonce: [9; 32] };
+            check_fixture(version, "contribution_commitment", &ContributionCommitmentMessage { digest: [5; 32] });
+            check_fixture(version, "contribution_digests", &ContributionDigestsMessage { digests: vec![[5; 32], [6; 32]] });
+            check_fixture(version, "contributions", &ContributionsMessage { contributions: vec![contribution] });
+            check_fixture(version, "contribution", &ContributionMessage { player_b: 0, sigma_challenge: samples().bit_challenge, nonce: [8; 32] });
+        }
+        // introduced in version 8
+        if version >= 8 {
+            check_fixture(version, "beacon_round", &BeaconRoundMessage { round: 4_200_000 });
+        }
+        // introduced in version 9
+        if version >= 9 {
+            let mut rng = ChaCha20Rng::from_seed([9; 32]);
+            let (sk, key) = signature::keygen(&mut rng);
+            let signature = sk.sign(b"certificate");
+            check_fixture(version, "certificate_signature", &CertificateSignatureMessage { key, signature });
+        }
+        if version >= 10 {
+            let reason = AbortReason::DeadlineExceeded { phase: "Randomness".to_string(), deadline: Duration::from_secs(60), elapsed: Duration::from_millis(60_250) };
+            check_fixture(version, "abort", &AbortMessage { reason });
+        }
+        if version >= 11 {
+            let (s, t) = (samples(), samples());
+            check_fixture(version, "sketch_commitment", &SketchCommitmentMessage {
+                rows: vec![SketchRowCommitment { indicators: vec![s.bit_commitment, t.bit_commitment], sum_proof: s.scalars[0], bit_proofs: vec![s.scalars[1]] }],
+                chains: vec![vec![s.product_commitment], vec![t.product_commitment]],
+            });
+            check_fixture(version, "sketch_challenge", &SketchChallengeMessage { indicators: s.bit_challenge, chains: vec![vec![s.product_challenge], vec![t.product_challenge]] });
+            check_fixture(version, "sketch_response", &SketchResponseMessage { indicators: vec![s.bit_response, t.bit_response], chains: vec![vec![s.product_response], vec![t.product_response]] });
+        }
+        if version >= 12 {
+            // compression is asked for since version 13
+            check_fixture(version, "wire_format", &WireFormatMessage { format: WireFormat::Bincode, compress: version >= 13 });
+        }
+        if version >= 14 {
+            let reason = AbortReason::Malformed { message: "certified_dp::messages::ReadyMessage".to_string(), error: "EOF while parsing a value at line 1 column 0".to_string() };
+            check_fixture(version, "abort_malformed", &AbortMessage { reason });
+        }
+        if version >= 15 {
+            let prover = SessionParams { db_size: 1000, dimension: 8, max_degree: 3, epsilon: 0.5, delta: None, entry_width, calibration, mechanism, sensitivity };
+            let reason = AbortReason::ParameterMismatch { prover, verifier: SessionParams { max_degree: 2, ..prover } };
+            check_fixture(version, "abort_parameters", &AbortMessage { reason });
+        }
+        // the prover refuses queries breaking its policy since version 23
+        if version >= 23 {
+            let violations = vec!["query has 3 monomials, beyond 2".to_string(), "coefficient of monomial x0*x1 is not an integer".to_string()];
+            check_fixture(version, "abort_policy", &AbortMessage { reason: AbortReason::QueryRefused { violations } });
+        }
+        // queries are batched since version 25
+        if version >= 25 {
+            check_fixture(version, "batch_query", &BatchQueryMessage::<DataT> {
+                queries: vec![HashMap::from([(Monomial::new(1), s.scalars[0])]), HashMap::from([(Monomial::new(6), s.scalars[1])])],
+                snapshot,
+                dataset: "census".to_string(),
+            });
+            let answers = vec![QueryAnswerMessage { answer: s.scalars[2], proof: s.scalars[0] }, QueryAnswerMessage { answer: s.scalars[1], proof: s.scalars[2] }];
+            check_fixture(version, "batch_query_answer", &BatchQueryAnswerMessage { answers });
+        }
+        // analysts name themselves for their own budgets since version 27
+        if version >= 27 {
+            check_fixture(version, "analyst", &AnalystMessage { name: "alice".to_string() });
+        }
+        // the prover authenticates its verifiers since version 28
+        if version >= 28 {
+            let mut rng = ChaCha20Rng::from_seed([28; 32]);
+            let (sk, key) = signature::keygen(&mut rng);
+            let signature = sk.sign(b"challenge");
+            check_fixture(version, "auth_challenge", &AuthChallengeMessage { challenge: [7; 32] });
+            check_fixture(version, "auth_response_tag", &AuthResponseMessage { proof: AuthProof::Tag { tag: [3; 32] } });
+            check_fixture(version, "auth_response_signature", &AuthResponseMessage { proof: AuthProof::Signature { key, signature } });
+            check_fixture(version, "abort_unauthorized", &AbortMessage { reason: AbortReason::Unauthorized });
+        }
+        // the verifier checks which monomials the commitment map holds since version 32
+        if version >= 32 {
+            let reason = AbortReason::CommitmentMapMismatch { missing: 1, unexpected: 2, first_missing: vec!["x0".to_string()], first_unexpected: vec!["x8".to_string(), "x0*x1*x2*x3".to_string()] };
+            check_fixture(version, "abort_commitment_map", &AbortMessage { reason });
+        }
+        // and how many entries, bits, and coins the prover commits to since version 33
+        if version >= 33 {
+            let reason = AbortReason::CountMismatch { what: "bits of entry 3".to_string(), expected: 8, found: 7 };
+            check_fixture(version, "abort_count", &AbortMessage { reason });
+        }
+        // the randomness phase resumes from a checkpoint over a new connection since version 34
+        if version >= 34 {
+            let u = samples();
+            let checkpoint = Checkpoint::default().advance([&u.points[0], &u.points[1]]);
+            check_fixture(version, "resume_randomness", &ResumeRandomnessMessage {
+                checkpoint,
+                challenges: Some(VerifierRandomnessChallenges {
+                    challenges: vec![VerifierRandomnessChallenge { player_b: 1, sigma_challenge: u.bit_challenge }],
+                }),
+            });
+            check_fixture(version, "checkpoint", &CheckpointMessage { checkpoint });
+            check_fixture(version, "abort_checkpoint", &AbortMessage { reason: AbortReason::CheckpointMismatch { prover: 1024, verifier: 2048 } });
+        }
+    }
+}
+
+#[test]
+fn maps_encode_in_order_of_their_keys() {
+    // each map hashes its keys its own way, so the same entries iterate in a different order in each
+    let s = samples();
+    let coefficients = |order: &mut dyn Iterator<Item = u16>| -> HashMap<Monomial<DataT>, _> {
+        order.map(|mask| (Monomial::new(mask), s.scalars[mask as usize % 3])).collect()
+    };
+    let query = |coefficients| QueryMessage::<DataT> { coefficients, snapshot: SnapshotId::default(), dataset: String::new(), distinct: false };
+    for format in [WireFormat::Json, WireFormat::Bincode] {
+        let ascending = format.encode(&query(coefficients(&mut (1..64))));
+        let descending = format.encode(&query(coefficients(&mut (1..64).rev())));
+        assert_eq!(ascending, descending, "the same query encoded to different {:?} frames", format);
+    }
+}
@@ -0,0 +1,62 @@
+/*!
+ * zeroize.rs
+ *
+ * Checks that the prover's secret state is wiped: the bit and product sigma provers, which hold the witnesses and
+ * nonces of their proofs, and the openings of a database's monomial commitments.
+ */
+
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use zeroize::Zeroize;
+
+use certified_dp::bit_sigma;
+use certified_dp::config::DataT;
+use certified_dp::data::Data;
+use certified_dp::group::Ristretto;
+use certified_dp::monomial::Monomial;
+use certified_dp::pedersen;
+use certified_dp::product_sigma;
+
+#[test]
+fn bit_sigma_prover_is_wiped() {
+    let pp: pedersen::PublicParams = pedersen::setup(b"zeroize");
+    let (b_comm, b_proof) = pedersen::commit(&mut OsRng, &Scalar::ONE, &pp);
+    let (mut sigma_p, _) = bit_sigma::commit(&mut OsRng, &pp, 1, b_comm, b_proof);
+    let wiped = serde_json::to_value(bit_sigma::Prover::<Ristretto>::default()).unwrap();
+    assert_ne!(serde_json::to_value(&sigma_p).unwrap(), wiped);
+
+    sigma_p.zeroize();
+    assert_eq!(serde_json::to_value(&sigma_p).unwrap(), wiped);
+}
+
+#[test]
+fn product_sigma_prover_is_wiped() {
+    let pp: pedersen::PublicParams = pedersen::setup(b"zeroize");
+    let open = |m: u32| {
+        let m = Scalar::from(m);
+        let (c, r) = pedersen::commit(&mut OsRng, &m, &pp);
+        (m, c, r)
+    };
+    let (first, second, third) = (open(2), open(3), open(6));
+    let (mut sigma_p, _) = product_sigma::commit(&mut OsRng, &pp, first, second, third);
+
+    sigma_p.zeroize();
+    // the commitments themselves are public, so only the secrets are wiped
+    let wiped = serde_json::to_value(&sigma_p).unwrap();
+    for secret in ["m_1", "r_1", "m_2", "r_2", "m_3", "r_3", "b_1", "b_2", "b_3", "b_4", "b_5"] {
+        assert_eq!(wiped[secret], serde_json::to_value(Scalar::ZERO).unwrap(), "{} not wiped", secret);
+    }
+}
+
+#[test]
+fn database_openings_are_wiped() {
+    let pp: pedersen::PublicParams = pedersen::setup(b"zeroize");
+    let mut database = Data::<DataT>::new(&mut OsRng, 4);
+    for mask in 1..4 {
+        let (comm, proof) = pedersen::commit(&mut OsRng, &Scalar::from(2u32), &pp);
+        database.commitments.insert(Monomial::new(mask), (Scalar::from(2u32), comm, proof));
+    }
+
+    database.zeroize();
+    assert!(database.commitments.values().all(|&(value, _, opening)| value == Scalar::ZERO && opening == Scalar::ZERO));
+}